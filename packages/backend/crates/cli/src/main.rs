@@ -0,0 +1,332 @@
+//! `agents-dashboard`: a dashboard's sessions from the terminal.
+
+mod table;
+
+use agents_dashboard_client::types::{
+    AgentSessionSummary, AgentStateType, SearchResponse, ServerEvent,
+};
+use agents_dashboard_client::{Client, Error};
+use chrono::{Local, Utc};
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::io::IsTerminal;
+use std::process::ExitCode;
+use table::{Align, Cell, Table};
+
+const DEFAULT_URL: &str = "http://localhost:3001";
+
+/// `status` found a session in the error state.
+const EXIT_SESSION_ERROR: u8 = 1;
+const EXIT_USAGE: u8 = 2;
+/// The dashboard couldn't be reached or refused the request.
+const EXIT_REQUEST_FAILED: u8 = 3;
+
+const USAGE: &str = "\
+Usage: agents-dashboard [options] <command>
+
+Commands:
+  status          Table of sessions; exits 1 if any is in the error state
+  watch           Print state changes as they happen, reconnecting as needed
+  search <query>  Sessions matching the query
+
+Options:
+  --url <url>     Dashboard to talk to (default: $DASHBOARD_URL, else http://localhost:3001)
+  --all           status: include archived sessions
+  --json          Print JSON instead (one event per line for watch)
+  --no-color      No colors (also off when NO_COLOR is set or output isn't a terminal)
+  -h, --help      Show this help
+
+DASHBOARD_TOKEN, when set, is sent as a bearer token.
+Exit codes: 0 ok, 1 a session is in error (status), 2 bad usage, 3 request failed.
+";
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Status,
+    Watch,
+    Search(String),
+}
+
+#[derive(Debug, PartialEq)]
+struct Args {
+    command: Command,
+    url: Option<String>,
+    all: bool,
+    json: bool,
+    no_color: bool,
+}
+
+/// None for `--help`.
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Option<Args>, String> {
+    let mut url = None;
+    let (mut all, mut json, mut no_color) = (false, false, false);
+    let mut positional = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--url" => url = Some(args.next().ok_or("--url needs a value")?),
+            "--all" => all = true,
+            "--json" => json = true,
+            "--no-color" => no_color = true,
+            _ if arg.starts_with("--url=") => url = Some(arg["--url=".len()..].to_string()),
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return Err(format!("unknown option {}", arg))
+            }
+            _ => positional.push(arg),
+        }
+    }
+    let mut positional = positional.into_iter();
+    let command = match positional.next().as_deref() {
+        Some("status") => Command::Status,
+        Some("watch") => Command::Watch,
+        Some("search") => {
+            let query: Vec<String> = positional.by_ref().collect();
+            if query.is_empty() {
+                return Err("search needs a query".to_string());
+            }
+            Command::Search(query.join(" "))
+        }
+        Some(other) => return Err(format!("unknown command {}", other)),
+        None => return Err("no command given".to_string()),
+    };
+    if let Some(extra) = positional.next() {
+        return Err(format!("unexpected argument {}", extra));
+    }
+    if all && command != Command::Status {
+        return Err("--all only applies to status".to_string());
+    }
+    Ok(Some(Args {
+        command,
+        url,
+        all,
+        json,
+        no_color,
+    }))
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            print!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprint!("agents-dashboard: {}\n\n{}", e, USAGE);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+    let env = |name: &str| std::env::var(name).ok().filter(|v| !v.trim().is_empty());
+    let url = args
+        .url
+        .clone()
+        .or_else(|| env("DASHBOARD_URL"))
+        .unwrap_or_else(|| DEFAULT_URL.to_string());
+    let mut client = match Client::new(&url) {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("agents-dashboard: {}", e);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+    if let Some(token) = env("DASHBOARD_TOKEN") {
+        client = client.with_token(token.trim());
+    }
+    let color =
+        !args.no_color && std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal();
+
+    let result = match &args.command {
+        Command::Status => status(&client, args.all, args.json, color).await,
+        Command::Watch => watch(&client, &url, args.json, color).await,
+        Command::Search(query) => search(&client, query, args.json, color).await,
+    };
+    match result {
+        Ok(code) => ExitCode::from(code),
+        Err(e) => {
+            eprintln!("agents-dashboard: {}: {}", url, e);
+            ExitCode::from(EXIT_REQUEST_FAILED)
+        }
+    }
+}
+
+async fn status(client: &Client, all: bool, json: bool, color: bool) -> Result<u8, Error> {
+    let sessions = client.list_sessions(all).await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&sessions).unwrap());
+    } else if sessions.is_empty() {
+        println!("No sessions");
+    } else {
+        print!("{}", table::sessions(&sessions, Utc::now(), color));
+    }
+    let any_error = sessions.iter().any(|s| s.state == AgentStateType::Error);
+    Ok(if any_error { EXIT_SESSION_ERROR } else { 0 })
+}
+
+/// Runs until interrupted. Fails only if the dashboard can't be reached at the start;
+/// later disconnects are retried.
+async fn watch(client: &Client, url: &str, json: bool, color: bool) -> Result<u8, Error> {
+    client.health().await?;
+    let mut events = client.events();
+    let mut known: HashMap<String, AgentSessionSummary> = HashMap::new();
+    let mut connections = 0;
+    while let Some(event) = events.next().await {
+        if json {
+            if is_watched(&event) {
+                println!("{}", serde_json::to_string(&event).unwrap());
+            }
+            continue;
+        }
+        match event {
+            ServerEvent::Hello { .. } => {
+                connections += 1;
+                if connections > 1 {
+                    eprintln!("Reconnected to {}", url);
+                }
+            }
+            ServerEvent::SessionsInit { sessions, .. } => {
+                if connections <= 1 {
+                    eprintln!("Watching {} sessions on {}", sessions.len(), url);
+                }
+                // After a reconnect, report what changed while disconnected.
+                for session in sessions {
+                    match known.get(&session.session_id) {
+                        Some(old) if old.state != session.state => {
+                            print_change(&session, Some(old.state), color)
+                        }
+                        None if connections > 1 => print_change(&session, None, color),
+                        _ => {}
+                    }
+                    known.insert(session.session_id.clone(), session);
+                }
+            }
+            ServerEvent::SessionDiscovered { session } => {
+                print_change(&session, None, color);
+                known.insert(session.session_id.clone(), session);
+            }
+            ServerEvent::StateChanged {
+                previous, session, ..
+            } => {
+                print_change(&session, Some(previous), color);
+                known.insert(session.session_id.clone(), session);
+            }
+            ServerEvent::SessionRemoved { session_id } => {
+                if let Some(session) = known.remove(&session_id) {
+                    println!(
+                        "{}  {}  removed",
+                        Local::now().format("%H:%M:%S"),
+                        session.project_name
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(0)
+}
+
+fn is_watched(event: &ServerEvent) -> bool {
+    matches!(
+        event,
+        ServerEvent::SessionsInit { .. }
+            | ServerEvent::SessionDiscovered { .. }
+            | ServerEvent::StateChanged { .. }
+            | ServerEvent::SessionRemoved { .. }
+    )
+}
+
+/// `12:03:04  api  running → idle`, or `new → idle` for a session seen the first time.
+fn print_change(session: &AgentSessionSummary, previous: Option<AgentStateType>, color: bool) {
+    let previous = match previous {
+        Some(state) => table::paint(&table::state(state), color),
+        None => "new".to_string(),
+    };
+    println!(
+        "{}  {}  {} → {}",
+        Local::now().format("%H:%M:%S"),
+        session.project_name,
+        previous,
+        table::paint(&table::state(session.state), color)
+    );
+}
+
+async fn search(client: &Client, query: &str, json: bool, color: bool) -> Result<u8, Error> {
+    let response = client.search(query).await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&response).unwrap());
+    } else {
+        print!("{}", search_table(&response, color));
+    }
+    Ok(0)
+}
+
+fn search_table(response: &SearchResponse, color: bool) -> String {
+    if response.results.is_empty() {
+        return format!(
+            "No matches for \"{}\" in {} sessions\n",
+            response.query, response.total_sessions
+        );
+    }
+    let mut table = Table::new(&[
+        ("PROJECT", Align::Left),
+        ("STATE", Align::Left),
+        ("MATCHES", Align::Right),
+        ("FIRST MATCH", Align::Left),
+    ]);
+    for result in &response.results {
+        let first = result
+            .matches
+            .first()
+            .map(|m| m.content.as_str())
+            .unwrap_or("");
+        table.push(vec![
+            result.session.project_name.as_str().into(),
+            table::state(result.session.state),
+            result.match_count.to_string().into(),
+            Cell::from(first),
+        ]);
+    }
+    table.render(color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Args>, String> {
+        parse_args(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args = parse(&["--json", "status", "--all", "--url=http://desk:3001"])
+            .unwrap()
+            .unwrap();
+        assert_eq!(args.command, Command::Status);
+        assert_eq!(args.url.as_deref(), Some("http://desk:3001"));
+        assert!(args.all && args.json && !args.no_color);
+
+        let args = parse(&["search", "failing", "test", "--no-color"])
+            .unwrap()
+            .unwrap();
+        assert_eq!(args.command, Command::Search("failing test".to_string()));
+        assert!(args.no_color);
+
+        let args = parse(&["--url", "http://x", "watch"]).unwrap().unwrap();
+        assert_eq!(args.command, Command::Watch);
+        assert!(parse(&["status", "--help"]).unwrap().is_none());
+
+        for bad in [
+            &[][..],
+            &["search"],
+            &["stauts"],
+            &["status", "extra"],
+            &["watch", "--all"],
+            &["status", "--url"],
+            &["status", "-x"],
+        ] {
+            assert!(parse(bad).is_err(), "{:?} should be rejected", bad);
+        }
+    }
+}
@@ -0,0 +1,284 @@
+//! Plain-text tables for the terminal, with optional ANSI colors.
+
+use agents_dashboard_client::types::{AgentSessionSummary, AgentStateType, GitStatus};
+use chrono::{DateTime, Utc};
+
+/// Cells longer than this are cut short with an ellipsis.
+const MAX_CELL_WIDTH: usize = 40;
+const GAP: &str = "  ";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Align {
+    Left,
+    Right,
+}
+
+pub struct Cell {
+    text: String,
+    /// SGR parameters, e.g. `31` for red.
+    color: Option<&'static str>,
+}
+
+impl Cell {
+    pub fn colored(text: impl Into<String>, color: &'static str) -> Self {
+        Self {
+            text: text.into(),
+            color: Some(color),
+        }
+    }
+}
+
+impl<T: Into<String>> From<T> for Cell {
+    fn from(text: T) -> Self {
+        Self {
+            text: text.into(),
+            color: None,
+        }
+    }
+}
+
+pub struct Table {
+    columns: Vec<(&'static str, Align)>,
+    rows: Vec<Vec<Cell>>,
+}
+
+impl Table {
+    pub fn new(columns: &[(&'static str, Align)]) -> Self {
+        Self {
+            columns: columns.to_vec(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, row: Vec<Cell>) {
+        debug_assert_eq!(row.len(), self.columns.len());
+        self.rows.push(row);
+    }
+
+    /// Columns are as wide as their widest cell; colors don't count towards width.
+    pub fn render(&self, color: bool) -> String {
+        let rows: Vec<Vec<(String, Option<&'static str>)>> = self
+            .rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| (truncate(&cell.text), cell.color))
+                    .collect()
+            })
+            .collect();
+        let widths: Vec<usize> = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, (header, _))| {
+                rows.iter()
+                    .map(|row| row[i].0.chars().count())
+                    .chain([header.len()])
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        let mut out = String::new();
+        let header: Vec<(String, Option<&'static str>)> = self
+            .columns
+            .iter()
+            .map(|(header, _)| (header.to_string(), color.then_some("1")))
+            .collect();
+        for row in std::iter::once(&header).chain(&rows) {
+            let mut line = String::new();
+            for (i, (text, cell_color)) in row.iter().enumerate() {
+                if i > 0 {
+                    line.push_str(GAP);
+                }
+                let padding = " ".repeat(widths[i] - text.chars().count());
+                let text = colorize(text, cell_color.filter(|_| color));
+                match self.columns[i].1 {
+                    Align::Left => {
+                        line.push_str(&text);
+                        line.push_str(&padding);
+                    }
+                    Align::Right => {
+                        line.push_str(&padding);
+                        line.push_str(&text);
+                    }
+                }
+            }
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+        out
+    }
+}
+
+fn truncate(text: &str) -> String {
+    // One line per row.
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() <= MAX_CELL_WIDTH {
+        return text;
+    }
+    let mut cut: String = text.chars().take(MAX_CELL_WIDTH - 1).collect();
+    cut.push('…');
+    cut
+}
+
+/// The `status` table.
+pub fn sessions(sessions: &[AgentSessionSummary], now: DateTime<Utc>, color: bool) -> String {
+    let mut table = Table::new(&[
+        ("PROJECT", Align::Left),
+        ("STATE", Align::Left),
+        ("MODEL", Align::Left),
+        ("COST", Align::Right),
+        ("LAST ACTIVITY", Align::Left),
+        ("BRANCH", Align::Left),
+    ]);
+    for session in sessions {
+        table.push(vec![
+            session.project_name.as_str().into(),
+            state(session.state),
+            dash_if_empty(&session.model).into(),
+            cost(session.cumulative_usage.estimated_cost).into(),
+            ago(&session.last_activity_at, now).into(),
+            branch(&session.git_status).into(),
+        ]);
+    }
+    table.render(color)
+}
+
+pub fn state(state: AgentStateType) -> Cell {
+    let color = match state {
+        AgentStateType::Running => "32",
+        AgentStateType::Idle => "36",
+        AgentStateType::PermissionWaiting => "33",
+        AgentStateType::Error => "31",
+        AgentStateType::Stopped => "2",
+    };
+    Cell::colored(state.to_string(), color)
+}
+
+/// `cell` as it would appear in a table, for one-off lines.
+pub fn paint(cell: &Cell, color: bool) -> String {
+    colorize(&cell.text, cell.color.filter(|_| color))
+}
+
+fn colorize(text: &str, sgr: Option<&str>) -> String {
+    match sgr {
+        Some(sgr) => format!("\x1b[{}m{}\x1b[0m", sgr, text),
+        None => text.to_string(),
+    }
+}
+
+fn dash_if_empty(text: &str) -> &str {
+    if text.is_empty() {
+        "-"
+    } else {
+        text
+    }
+}
+
+fn cost(dollars: f64) -> String {
+    format!("${:.2}", dollars)
+}
+
+/// `12s ago`, `5m ago`, `3h ago`, `2d ago`; `-` for a missing or bad timestamp.
+pub fn ago(timestamp: &str, now: DateTime<Utc>) -> String {
+    let Ok(at) = DateTime::parse_from_rfc3339(timestamp) else {
+        return "-".to_string();
+    };
+    let secs = (now - at.with_timezone(&Utc)).num_seconds().max(0);
+    match secs {
+        0..60 => format!("{}s ago", secs),
+        60..3600 => format!("{}m ago", secs / 60),
+        3600..86400 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+/// Branch plus staged and unstaged line changes, e.g. `main +12 -3`.
+fn branch(git: &GitStatus) -> String {
+    if git.branch.is_empty() {
+        return "-".to_string();
+    }
+    let additions = git.additions + git.staged_additions;
+    let deletions = git.deletions + git.staged_deletions;
+    if additions == 0 && deletions == 0 {
+        return git.branch.clone();
+    }
+    format!("{} +{} -{}", git.branch, additions, deletions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(project: &str, state: AgentStateType, cost: f64) -> AgentSessionSummary {
+        let mut session = AgentSessionSummary {
+            session_id: project.to_string(),
+            project_name: project.to_string(),
+            state,
+            model: "claude-sonnet-4-5".to_string(),
+            last_activity_at: "2026-01-01T11:55:00Z".to_string(),
+            ..Default::default()
+        };
+        session.cumulative_usage.estimated_cost = cost;
+        session.git_status.branch = "main".to_string();
+        session.git_status.additions = 10;
+        session.git_status.staged_additions = 2;
+        session.git_status.deletions = 3;
+        session
+    }
+
+    #[test]
+    fn test_sessions_table() {
+        let now = DateTime::parse_from_rfc3339("2026-01-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let sessions = [
+            session("api", AgentStateType::Running, 12.5),
+            session("web-frontend", AgentStateType::Error, 0.4),
+        ];
+        assert_eq!(
+            super::sessions(&sessions, now, false),
+            "\
+PROJECT       STATE    MODEL                COST  LAST ACTIVITY  BRANCH
+api           running  claude-sonnet-4-5  $12.50  5m ago         main +12 -3
+web-frontend  error    claude-sonnet-4-5   $0.40  5m ago         main +12 -3
+"
+        );
+
+        // Colors wrap the text without changing the layout.
+        let colored = super::sessions(&sessions, now, true);
+        assert!(colored.contains("\x1b[31merror\x1b[0m    claude"));
+        let strip = |s: &str| {
+            let mut out = String::new();
+            let mut in_escape = false;
+            for c in s.chars() {
+                match c {
+                    '\x1b' => in_escape = true,
+                    'm' if in_escape => in_escape = false,
+                    _ if !in_escape => out.push(c),
+                    _ => {}
+                }
+            }
+            out
+        };
+        assert_eq!(strip(&colored), super::sessions(&sessions, now, false));
+    }
+
+    #[test]
+    fn test_cells_and_times() {
+        let long = "x".repeat(50);
+        assert_eq!(truncate(&long).chars().count(), MAX_CELL_WIDTH);
+        assert!(truncate(&long).ends_with('…'));
+        assert_eq!(truncate("two\nlines"), "two lines");
+
+        let now = DateTime::parse_from_rfc3339("2026-01-03T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(ago("2026-01-02T23:59:30Z", now), "30s ago");
+        assert_eq!(ago("2026-01-02T21:00:00Z", now), "3h ago");
+        assert_eq!(ago("2026-01-01T00:00:00Z", now), "2d ago");
+        assert_eq!(ago("", now), "-");
+        assert_eq!(branch(&GitStatus::default()), "-");
+    }
+}
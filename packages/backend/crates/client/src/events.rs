@@ -0,0 +1,176 @@
+//! The `/ws` event stream, reconnecting with backoff and re-sending subscriptions.
+
+use crate::types::{ClientEvent, ServerEvent};
+use futures::{SinkExt, Stream, StreamExt};
+use std::collections::HashSet;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::{header, HeaderValue};
+use tokio_tungstenite::tungstenite::{Error, Message};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+type Socket = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+const RECONNECT_MIN: Duration = Duration::from_millis(500);
+const RECONNECT_MAX: Duration = Duration::from_secs(30);
+
+enum Command {
+    Subscribe(String),
+    Unsubscribe(String),
+}
+
+/// Events sent by the server, across reconnects. Dropping it closes the connection.
+pub struct EventStream {
+    events: mpsc::UnboundedReceiver<ServerEvent>,
+    commands: mpsc::UnboundedSender<Command>,
+}
+
+impl EventStream {
+    pub(crate) fn connect(url: String, token: Option<String>) -> Self {
+        let (events_tx, events) = mpsc::unbounded_channel();
+        let (commands, commands_rx) = mpsc::unbounded_channel();
+        tokio::spawn(run(url, token, events_tx, commands_rx));
+        Self { events, commands }
+    }
+
+    /// Also receive `session:new_message` events for `session_id`, now and after every
+    /// reconnect.
+    pub fn subscribe(&self, session_id: &str) {
+        let _ = self
+            .commands
+            .send(Command::Subscribe(session_id.to_string()));
+    }
+
+    pub fn unsubscribe(&self, session_id: &str) {
+        let _ = self
+            .commands
+            .send(Command::Unsubscribe(session_id.to_string()));
+    }
+}
+
+impl Stream for EventStream {
+    type Item = ServerEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<ServerEvent>> {
+        self.events.poll_recv(cx)
+    }
+}
+
+/// How a connection ended.
+enum Ended {
+    /// The server closed it or it failed; connect again.
+    Lost,
+    /// The `EventStream` was dropped; stop.
+    Dropped,
+}
+
+async fn run(
+    url: String,
+    token: Option<String>,
+    events: mpsc::UnboundedSender<ServerEvent>,
+    mut commands: mpsc::UnboundedReceiver<Command>,
+) {
+    let mut subscriptions = HashSet::new();
+    let mut backoff = RECONNECT_MIN;
+    loop {
+        if let Ok(socket) = connect(&url, token.as_deref()).await {
+            backoff = RECONNECT_MIN;
+            if let Ended::Dropped = serve(socket, &events, &mut commands, &mut subscriptions).await
+            {
+                return;
+            }
+        }
+        // Keep taking (un)subscriptions while waiting to reconnect.
+        let sleep = tokio::time::sleep(backoff);
+        tokio::pin!(sleep);
+        loop {
+            tokio::select! {
+                _ = &mut sleep => break,
+                command = commands.recv() => match command {
+                    Some(command) => apply(&mut subscriptions, command),
+                    None => return,
+                },
+            }
+        }
+        backoff = (backoff * 2).min(RECONNECT_MAX);
+    }
+}
+
+async fn connect(url: &str, token: Option<&str>) -> Result<Socket, Error> {
+    let mut request = url.into_client_request()?;
+    if let Some(token) = token {
+        let value = HeaderValue::from_str(&format!("Bearer {}", token))
+            .map_err(|e| Error::HttpFormat(e.into()))?;
+        request.headers_mut().insert(header::AUTHORIZATION, value);
+    }
+    let (socket, _) = tokio_tungstenite::connect_async(request).await?;
+    Ok(socket)
+}
+
+async fn serve(
+    socket: Socket,
+    events: &mpsc::UnboundedSender<ServerEvent>,
+    commands: &mut mpsc::UnboundedReceiver<Command>,
+    subscriptions: &mut HashSet<String>,
+) -> Ended {
+    let (mut tx, mut rx) = socket.split();
+    for session_id in subscriptions.iter() {
+        let subscribe = ClientEvent::Subscribe {
+            session_id: session_id.clone(),
+        };
+        if send(&mut tx, &subscribe).await.is_err() {
+            return Ended::Lost;
+        }
+    }
+    loop {
+        tokio::select! {
+            message = rx.next() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    // Event types from a newer server are skipped.
+                    if let Ok(event) = serde_json::from_str::<ServerEvent>(&text) {
+                        if events.send(event).is_err() {
+                            return Ended::Dropped;
+                        }
+                    }
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return Ended::Lost,
+                Some(Ok(_)) => {}
+            },
+            command = commands.recv() => {
+                let Some(command) = command else {
+                    return Ended::Dropped;
+                };
+                let event = match &command {
+                    Command::Subscribe(session_id) => ClientEvent::Subscribe {
+                        session_id: session_id.clone(),
+                    },
+                    Command::Unsubscribe(session_id) => ClientEvent::Unsubscribe {
+                        session_id: session_id.clone(),
+                    },
+                };
+                apply(subscriptions, command);
+                if send(&mut tx, &event).await.is_err() {
+                    return Ended::Lost;
+                }
+            }
+        }
+    }
+}
+
+fn apply(subscriptions: &mut HashSet<String>, command: Command) {
+    match command {
+        Command::Subscribe(session_id) => subscriptions.insert(session_id),
+        Command::Unsubscribe(session_id) => subscriptions.remove(&session_id),
+    };
+}
+
+async fn send<T: futures::Sink<Message> + Unpin>(
+    tx: &mut T,
+    event: &ClientEvent,
+) -> Result<(), ()> {
+    let json = serde_json::to_string(event).map_err(|_| ())?;
+    tx.send(Message::Text(json.into())).await.map_err(|_| ())
+}
@@ -0,0 +1,180 @@
+//! A client for the agents dashboard API, for scripts, status bar widgets and other
+//! tools that want sessions without a browser.
+//!
+//! ```no_run
+//! use agents_dashboard_client::Client;
+//! use futures::StreamExt;
+//!
+//! # async fn run() -> Result<(), agents_dashboard_client::Error> {
+//! let client = Client::new("http://localhost:3001")?;
+//! for session in client.list_sessions(false).await? {
+//!     println!("{} {}", session.project_name, session.state);
+//! }
+//! let mut events = client.events();
+//! while let Some(event) = events.next().await {
+//!     println!("{}", event.event_type());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+mod events;
+
+pub use agents_dashboard_types as types;
+pub use events::EventStream;
+
+use reqwest::{RequestBuilder, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use types::{AgentSessionDetail, AgentSessionSummary, HealthResponse, SearchResponse};
+
+#[derive(Debug)]
+pub enum Error {
+    /// The base URL given to [`Client::new`] isn't an http(s) URL.
+    InvalidUrl(String),
+    /// The request could not be sent or its response read.
+    Http(reqwest::Error),
+    /// The server answered with an error status; `message` is the reason it gave.
+    Status { status: u16, message: String },
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::InvalidUrl(url) => write!(f, "not a dashboard URL: {}", url),
+            Error::Http(e) => write!(f, "request failed: {}", e),
+            Error::Status { status, message } => write!(f, "{}: {}", status, message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Http(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Http(e)
+    }
+}
+
+/// One dashboard server, e.g. `http://localhost:3001`.
+#[derive(Debug, Clone)]
+pub struct Client {
+    base_url: Url,
+    http: reqwest::Client,
+    token: Option<String>,
+}
+
+impl Client {
+    pub fn new(base_url: &str) -> Result<Self, Error> {
+        Self::with_http_client(base_url, reqwest::Client::new())
+    }
+
+    /// Use `http` for requests, e.g. one with a timeout or proxy set.
+    pub fn with_http_client(base_url: &str, http: reqwest::Client) -> Result<Self, Error> {
+        let base_url = Url::parse(base_url)
+            .ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https") && !url.cannot_be_a_base())
+            .ok_or_else(|| Error::InvalidUrl(base_url.to_string()))?;
+        Ok(Self {
+            base_url,
+            http,
+            token: None,
+        })
+    }
+
+    /// Send `token` as a bearer token on every request and WebSocket connection, for
+    /// a dashboard behind an authenticating proxy.
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    pub async fn health(&self) -> Result<HealthResponse, Error> {
+        self.send(self.http.get(self.url(&["api", "health"]))).await
+    }
+
+    /// Archived sessions are left out unless `include_archived` is set.
+    pub async fn list_sessions(
+        &self,
+        include_archived: bool,
+    ) -> Result<Vec<AgentSessionSummary>, Error> {
+        let mut request = self.http.get(self.url(&["api", "sessions"]));
+        if include_archived {
+            request = request.query(&[("include_archived", "true")]);
+        }
+        self.send(request).await
+    }
+
+    /// A session with its recent messages, or None if the server doesn't know it.
+    pub async fn session_detail(
+        &self,
+        session_id: &str,
+    ) -> Result<Option<AgentSessionDetail>, Error> {
+        let request = self.http.get(self.url(&["api", "sessions", session_id]));
+        match self.send(request).await {
+            Ok(detail) => Ok(Some(detail)),
+            Err(Error::Status { status, .. }) if status == StatusCode::NOT_FOUND.as_u16() => {
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Sessions matching `query` in any field, as the dashboard's search box does.
+    pub async fn search(&self, query: &str) -> Result<SearchResponse, Error> {
+        self.send(
+            self.http
+                .get(self.url(&["api", "search"]))
+                .query(&[("q", query)]),
+        )
+        .await
+    }
+
+    /// Events from `/ws`, reconnecting whenever the connection drops. Each connection
+    /// starts with `server:hello` and `sessions:init`, so a consumer that rebuilds its
+    /// state from those stays in sync across reconnects.
+    pub fn events(&self) -> EventStream {
+        let mut url = self.url(&["ws"]);
+        let scheme = if url.scheme() == "https" { "wss" } else { "ws" };
+        // Both are special schemes, so the switch can't fail.
+        let _ = url.set_scheme(scheme);
+        EventStream::connect(url.to_string(), self.token.clone())
+    }
+
+    fn url(&self, segments: &[&str]) -> Url {
+        let mut url = self.base_url.clone();
+        url.path_segments_mut()
+            .expect("checked in Client::new")
+            .pop_if_empty()
+            .extend(segments);
+        url
+    }
+
+    async fn send<T: DeserializeOwned>(&self, mut request: RequestBuilder) -> Result<T, Error> {
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            // Errors come as `{"error": "..."}`.
+            let message = response
+                .json::<serde_json::Value>()
+                .await
+                .ok()
+                .and_then(|body| body["error"].as_str().map(str::to_string))
+                .unwrap_or_else(|| status.to_string());
+            return Err(Error::Status {
+                status: status.as_u16(),
+                message,
+            });
+        }
+        Ok(response.json().await?)
+    }
+}
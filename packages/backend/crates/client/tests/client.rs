@@ -0,0 +1,272 @@
+//! The client against an in-process router that answers like the dashboard.
+
+use agents_dashboard_client::types::{
+    AgentSessionDetail, AgentSessionSummary, ClientEvent, HealthResponse, SearchResponse,
+    ServerEvent,
+};
+use agents_dashboard_client::{Client, Error, EventStream};
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+struct Mock {
+    connections: AtomicUsize,
+    /// (connection number, frame) for every frame a client sends on `/ws`.
+    received: mpsc::UnboundedSender<(usize, ClientEvent)>,
+}
+
+fn summary(session_id: &str) -> AgentSessionSummary {
+    AgentSessionSummary {
+        session_id: session_id.to_string(),
+        project_name: "demo".to_string(),
+        ..Default::default()
+    }
+}
+
+async fn serve() -> (String, mpsc::UnboundedReceiver<(usize, ClientEvent)>) {
+    let (received, received_rx) = mpsc::unbounded_channel();
+    let mock = Arc::new(Mock {
+        connections: AtomicUsize::new(0),
+        received,
+    });
+    let router = Router::new()
+        .route(
+            "/api/health",
+            get(|| async {
+                Json(HealthResponse {
+                    status: "ok".to_string(),
+                    instance: "mock".to_string(),
+                    cli_versions: Default::default(),
+                    watchers: Default::default(),
+                    connection_lags: 0,
+                    events_missed: 0,
+                    unparseable_timestamps: 0,
+                    suppressed_git_checks: 0,
+                    watchdog: Default::default(),
+                    transcript_memory: Default::default(),
+                })
+            }),
+        )
+        .route(
+            "/api/sessions",
+            get(|Query(params): Query<HashMap<String, String>>| async move {
+                let mut sessions = vec![summary("s1")];
+                if params.get("include_archived").map(String::as_str) == Some("true") {
+                    sessions.push(summary("archived"));
+                }
+                Json(sessions)
+            }),
+        )
+        .route("/api/sessions/{session_id}", get(detail))
+        .route(
+            "/api/search",
+            get(|Query(params): Query<HashMap<String, String>>| async move {
+                Json(SearchResponse {
+                    query: params["q"].clone(),
+                    total_sessions: 1,
+                    results: Vec::new(),
+                })
+            }),
+        )
+        .route("/ws", get(ws))
+        .with_state(mock);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+    (format!("http://{}", addr), received_rx)
+}
+
+async fn detail(Path(session_id): Path<String>) -> Response {
+    if session_id != "s1" {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Session not found" })),
+        )
+            .into_response();
+    }
+    Json(AgentSessionDetail {
+        summary: summary("s1"),
+        messages: Vec::new(),
+        usage_points: Vec::new(),
+        model_history: Vec::new(),
+        watcher: None,
+        settings: None,
+    })
+    .into_response()
+}
+
+async fn ws(State(mock): State<Arc<Mock>>, upgrade: WebSocketUpgrade) -> Response {
+    upgrade.on_upgrade(move |socket| connection(mock, socket))
+}
+
+/// Sends `sessions:init`, then reports what the client sends. The first connection
+/// is dropped after the client's first frame, to make it reconnect.
+async fn connection(mock: Arc<Mock>, mut socket: WebSocket) {
+    let number = mock.connections.fetch_add(1, Ordering::SeqCst);
+    let init = ServerEvent::SessionsInit {
+        sessions: vec![summary(&format!("s{}", number))],
+        unread_counts: None,
+    };
+    let json = serde_json::to_string(&init).unwrap();
+    if socket.send(Message::Text(json.into())).await.is_err() {
+        return;
+    }
+    // Not a `ServerEvent`; the client should skip it.
+    let unknown = r#"{"type":"from:the_future"}"#;
+    let _ = socket.send(Message::Text(unknown.into())).await;
+    while let Some(Ok(message)) = socket.recv().await {
+        if let Message::Text(text) = message {
+            let event: ClientEvent = serde_json::from_str(&text).unwrap();
+            let _ = mock.received.send((number, event));
+            if number == 0 {
+                return;
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_http_methods() {
+    let (base_url, _) = serve().await;
+    // A trailing slash is fine.
+    let client = Client::new(&format!("{}/", base_url)).unwrap();
+
+    assert_eq!(client.health().await.unwrap().instance, "mock");
+
+    let sessions = client.list_sessions(false).await.unwrap();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].project_name, "demo");
+    assert_eq!(client.list_sessions(true).await.unwrap().len(), 2);
+
+    let detail = client.session_detail("s1").await.unwrap().unwrap();
+    assert_eq!(detail.summary.session_id, "s1");
+    assert!(client.session_detail("missing").await.unwrap().is_none());
+
+    let search = client.search("a b&c").await.unwrap();
+    assert_eq!(search.query, "a b&c");
+
+    assert!(matches!(
+        Client::new("ftp://localhost"),
+        Err(Error::InvalidUrl(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_error_status() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let router = Router::new().route(
+        "/api/search",
+        get(|| async {
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                Json(serde_json::json!({ "error": "Too many requests" })),
+            )
+        }),
+    );
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+    let client = Client::new(&format!("http://{}", addr)).unwrap();
+    match client.search("x").await {
+        Err(Error::Status { status, message }) => {
+            assert_eq!(status, 429);
+            assert_eq!(message, "Too many requests");
+        }
+        other => panic!("expected a status error, got {:?}", other),
+    }
+}
+
+async fn next(events: &mut EventStream) -> ServerEvent {
+    tokio::time::timeout(Duration::from_secs(5), events.next())
+        .await
+        .expect("no event")
+        .expect("stream ended")
+}
+
+#[tokio::test]
+async fn test_events_reconnect_and_resubscribe() {
+    let (base_url, mut received) = serve().await;
+    let client = Client::new(&base_url).unwrap();
+    let mut events = client.events();
+    let init_session = |event: ServerEvent| match event {
+        ServerEvent::SessionsInit { sessions, .. } => sessions[0].session_id.clone(),
+        other => panic!("expected sessions:init, got {}", other.event_type()),
+    };
+
+    assert_eq!(init_session(next(&mut events).await), "s0");
+    events.subscribe("s1");
+    let (connection, event) = received.recv().await.unwrap();
+    assert_eq!(connection, 0);
+    assert!(matches!(event, ClientEvent::Subscribe { session_id } if session_id == "s1"));
+
+    // The server dropped the first connection; the stream carries on over a new one,
+    // which is subscribed again.
+    assert_eq!(init_session(next(&mut events).await), "s1");
+    let (connection, event) = received.recv().await.unwrap();
+    assert_eq!(connection, 1);
+    assert!(matches!(event, ClientEvent::Subscribe { session_id } if session_id == "s1"));
+
+    events.unsubscribe("s1");
+    let (_, event) = received.recv().await.unwrap();
+    assert!(matches!(event, ClientEvent::Unsubscribe { session_id } if session_id == "s1"));
+}
+
+#[tokio::test]
+async fn test_token_sent_on_requests_and_websocket() {
+    async fn authorized(headers: axum::http::HeaderMap) -> bool {
+        headers.get("authorization").and_then(|v| v.to_str().ok()) == Some("Bearer s3cret")
+    }
+    let router = Router::new()
+        .route(
+            "/api/sessions",
+            get(|headers: axum::http::HeaderMap| async move {
+                if !authorized(headers).await {
+                    return StatusCode::UNAUTHORIZED.into_response();
+                }
+                Json(Vec::<AgentSessionSummary>::new()).into_response()
+            }),
+        )
+        .route(
+            "/ws",
+            get(
+                |headers: axum::http::HeaderMap, upgrade: WebSocketUpgrade| async move {
+                    if !authorized(headers).await {
+                        return StatusCode::UNAUTHORIZED.into_response();
+                    }
+                    upgrade.on_upgrade(|mut socket| async move {
+                        let init = ServerEvent::SessionsInit {
+                            sessions: Vec::new(),
+                            unread_counts: None,
+                        };
+                        let json = serde_json::to_string(&init).unwrap();
+                        let _ = socket.send(Message::Text(json.into())).await;
+                        while socket.recv().await.is_some() {}
+                    })
+                },
+            ),
+        );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+    let client = Client::new(&base_url).unwrap();
+    assert!(matches!(
+        client.list_sessions(false).await,
+        Err(Error::Status { status: 401, .. })
+    ));
+    let client = client.with_token("s3cret");
+    assert!(client.list_sessions(false).await.unwrap().is_empty());
+    assert!(matches!(
+        next(&mut client.events()).await,
+        ServerEvent::SessionsInit { .. }
+    ));
+}
@@ -0,0 +1,2150 @@
+//! Types of the agents dashboard API: what its HTTP endpoints return and the events
+//! sent over `/ws`. The backend serializes these; `agents-dashboard-client` reads them.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+// ── Agent State ──
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentStateType {
+    Running,
+    Idle,
+    PermissionWaiting,
+    Error,
+    Stopped,
+}
+
+impl std::fmt::Display for AgentStateType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AgentStateType::Running => write!(f, "running"),
+            AgentStateType::Idle => write!(f, "idle"),
+            AgentStateType::PermissionWaiting => write!(f, "permission_waiting"),
+            AgentStateType::Error => write!(f, "error"),
+            AgentStateType::Stopped => write!(f, "stopped"),
+        }
+    }
+}
+
+/// For summaries built from `Default`: a session nothing has been read for isn't doing
+/// anything. Sessions being tracked always set their state themselves.
+impl Default for AgentStateType {
+    fn default() -> Self {
+        AgentStateType::Stopped
+    }
+}
+
+// ── Usage ──
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CumulativeUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cache_creation_tokens: u64,
+    /// Cache writes with the 5-minute TTL, counted from entries that split them by TTL;
+    /// None if none did (older CLI versions).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_creation_5m_tokens: Option<u64>,
+    /// Cache writes with the 1-hour TTL, which cost more, counted the same way.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_creation_1h_tokens: Option<u64>,
+    pub estimated_cost: f64,
+}
+
+// ── Git Status ──
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GitStatus {
+    pub branch: String,
+    /// Unstaged line changes (`git diff --shortstat`).
+    pub additions: u64,
+    pub deletions: u64,
+    /// Staged line changes (`git diff --cached --shortstat`).
+    pub staged_additions: u64,
+    pub staged_deletions: u64,
+    pub untracked: u64,
+    /// Submodules with new commits, modified content or untracked files.
+    pub dirty_submodules: u32,
+    /// Commits ahead of / behind the upstream branch. 0 when there is no upstream.
+    pub ahead: u64,
+    pub behind: u64,
+    /// Unstaged changes per file, largest first, capped.
+    pub files: Vec<GitFileChange>,
+    /// None for repositories without any commits.
+    pub last_commit: Option<GitCommitInfo>,
+    /// The working directory is a linked `git worktree` checkout.
+    pub is_worktree: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GitCommitInfo {
+    /// Abbreviated commit hash.
+    pub hash: String,
+    pub subject: String,
+    pub author: String,
+    /// Committer date, RFC 3339.
+    pub timestamp: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GitFileChange {
+    pub path: String,
+    pub additions: u64,
+    pub deletions: u64,
+}
+
+/// A session's uncommitted changes as a unified diff, for `GET /api/sessions/{id}/diff`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GitDiff {
+    pub patch: String,
+    /// The patch was cut at the size limit, at the end of a line.
+    pub truncated: bool,
+    /// Every changed file, in the order of the patch, whether or not it was truncated.
+    pub files: Vec<GitFileChange>,
+    /// Changes staged in the index rather than in the working tree.
+    pub staged: bool,
+}
+
+/// A diff saved when a session changed state, as listed by
+/// `GET /api/sessions/{id}/diff/snapshots`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffSnapshotInfo {
+    /// Milliseconds since the epoch; names the snapshot in its URL.
+    pub timestamp: i64,
+    pub taken_at: String,
+    pub compressed_bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffSnapshot {
+    pub timestamp: i64,
+    pub taken_at: String,
+    /// The state the session had just entered.
+    pub state: AgentStateType,
+    pub diff: GitDiff,
+}
+
+/// Outcome of `POST /api/sessions/{id}/open`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenSessionResponse {
+    /// The command exited successfully or was still running after a few seconds.
+    pub success: bool,
+    /// The command as run, with the working directory filled in.
+    pub command: Vec<String>,
+    pub message: String,
+}
+
+/// Body of `POST /api/sessions/{id}/interrupt`, optional.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct InterruptRequest {
+    /// Which process to signal when several run in the session's directory.
+    #[serde(default)]
+    pub pid: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct InterruptResponse {
+    pub pid: u32,
+    /// E.g. `SIGINT`.
+    pub signal: String,
+}
+
+/// What `POST /api/sessions/{id}/interrupt?dry_run=true` would do.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct InterruptPlan {
+    pub session_id: String,
+    /// The Claude process that would be signalled.
+    pub pid: u32,
+    /// E.g. `SIGINT`.
+    pub signal: String,
+    /// Where the process runs.
+    pub working_directory: String,
+}
+
+/// What `DELETE /api/sessions/{id}?dry_run=true` would do.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DismissPlan {
+    pub session_id: String,
+    pub state: AgentStateType,
+    /// The log that would no longer be watched. It stays on disk, and the session
+    /// comes back if it is written to again.
+    pub log_file: String,
+}
+
+/// What `DELETE /api/replay/{id}?dry_run=true` would do.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StopReplayPlan {
+    pub session_id: String,
+    /// Messages replayed so far, which would be dropped with the session.
+    pub message_count: u64,
+}
+
+/// What `POST /api/replay?dry_run=true` would do.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayPlan {
+    /// Log entries that would be replayed.
+    pub entry_count: u64,
+    pub speed: f64,
+    /// How long the replay would take at that speed.
+    pub duration_ms: u64,
+}
+
+/// What `POST /api/sessions/{id}/open?dry_run=true` would do.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenPlan {
+    pub session_id: String,
+    /// The command that would run, with the working directory filled in.
+    pub command: Vec<String>,
+    pub working_directory: String,
+}
+
+/// What `POST /api/sessions/{id}/summarize?dry_run=true` would do.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SummarizePlan {
+    pub session_id: String,
+    /// The model that would be asked.
+    pub model: String,
+    /// Turns of the outline it would be sent.
+    pub turn_count: u64,
+}
+
+/// What `POST /api/projects/history?dry_run=true` would do.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryPlan {
+    pub project_path: String,
+    /// Sessions whose logs would be read, most recent first.
+    pub session_ids: Vec<String>,
+}
+
+/// What `POST /api/sessions/batch?dry_run=true` would return.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionBatchPlan {
+    /// Requested ids that would be returned, in request order.
+    pub found: Vec<String>,
+    /// Requested ids that aren't tracked, in request order.
+    pub not_found: Vec<String>,
+}
+
+// ── Session Preferences ──
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionPrefs {
+    #[serde(default)]
+    pub muted: bool,
+    #[serde(default)]
+    pub pinned: bool,
+    #[serde(default)]
+    pub archived: bool,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub note: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<GeneratedSummary>,
+}
+
+/// A short summary of a session written by a model from its outline.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GeneratedSummary {
+    pub text: String,
+    pub model: String,
+    pub generated_at: String,
+    /// What writing it cost, counted under the `dashboard` project.
+    pub estimated_cost: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionPrefsPatch {
+    #[serde(default)]
+    pub muted: Option<bool>,
+    #[serde(default)]
+    pub pinned: Option<bool>,
+    #[serde(default)]
+    pub archived: Option<bool>,
+}
+
+impl SessionPrefsPatch {
+    /// Set the flags the patch names, leaving the others as they are.
+    pub fn apply(&self, prefs: &mut SessionPrefs) {
+        if let Some(muted) = self.muted {
+            prefs.muted = muted;
+        }
+        if let Some(pinned) = self.pinned {
+            prefs.pinned = pinned;
+        }
+        if let Some(archived) = self.archived {
+            prefs.archived = archived;
+        }
+    }
+}
+
+// ── Session Summary ──
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentSessionSummary {
+    pub session_id: String,
+    pub provider: String,
+    pub state: AgentStateType,
+    pub project_path: String,
+    pub project_name: String,
+    pub working_directory: String,
+    /// Where the agent is working now, e.g. a package it cd'd into. The project fields
+    /// stay on the directory the session was started in.
+    #[serde(default)]
+    pub current_cwd: String,
+    /// The first prompt of the session.
+    #[serde(alias = "currentTask")]
+    pub initial_task: String,
+    /// The most recent prompt the user typed, leaving out slash commands and tool
+    /// results. The same as `initial_task` until they send another.
+    #[serde(default)]
+    pub latest_task: String,
+    /// The start of the agent's most recent reply text, as plain text. Cleared when
+    /// the user sends another prompt.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latest_assistant_text: Option<String>,
+    pub model: String,
+    pub last_activity_at: String,
+    pub started_at: String,
+    pub cumulative_usage: CumulativeUsage,
+    pub git_status: GitStatus,
+    pub pinned: bool,
+    /// Free-form triage note set via the API.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,
+    /// Written on request or when the session stops, if summaries are configured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<GeneratedSummary>,
+    /// Session this one continues (`claude --resume`), when that could be detected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resumed_from: Option<String>,
+    /// Estimated cost per hour over the last 10 minutes; decays to 0 when quiet.
+    #[serde(default)]
+    pub spend_rate_per_hour: f64,
+    /// Input + output tokens per minute over the same window.
+    #[serde(default)]
+    pub tokens_per_minute: f64,
+    /// Tokens in the latest prompt (input plus cache reads and writes), which is
+    /// how much of the context window is in use.
+    #[serde(default)]
+    pub context_tokens: u64,
+    /// The model's context window; null for models not in the context limit table.
+    #[serde(default)]
+    pub context_limit: Option<u64>,
+    /// Claude Code version that wrote the most recent log entry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cli_version: Option<String>,
+    /// Human-readable title from Claude Code (a conversation summary, else the session
+    /// slug). Preferred over `initial_task` for display.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// The session's most used tools, most calls first (at most 5).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub top_tools: Vec<ToolCount>,
+    /// Fed from a recorded log by `POST /api/replay` rather than a live CLI session.
+    #[serde(default)]
+    pub replay: bool,
+    /// Loaded from an old log by `POST /api/projects/history`; not watched, and
+    /// dropped again after a while.
+    #[serde(default)]
+    pub historical: bool,
+    /// The machine the session runs on: this instance's name, or for a relayed
+    /// session the name of the instance that relayed it.
+    #[serde(default)]
+    pub host: String,
+    /// The kind of work the session is doing, e.g. `coding` or `research`, from the
+    /// activity rules; re-evaluated as the session goes on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub activity_type: Option<String>,
+    /// The Claude Code permission mode, e.g. `default`, `acceptEdits`, `plan` or
+    /// `bypassPermissions`, as of the latest entry that showed it. Sessions bypassing
+    /// permissions run tool calls without asking and never wait for approval.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub permission_mode: Option<String>,
+    /// The tool call awaiting approval while the session is permission_waiting.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pending_tool: Option<PendingTool>,
+    /// Why the session stopped, when that was something other than going quiet:
+    /// `log_deleted` if its log file disappeared.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<String>,
+    /// Hook runs that failed, or blocked a tool call or the agent.
+    #[serde(default)]
+    pub hook_errors: u64,
+    /// Turns that ended in an API error, or in a response the API cut short. Three in
+    /// a row put the session in Error until a response goes through.
+    #[serde(default)]
+    pub failed_turns: u64,
+    /// Requests sent again after failing.
+    #[serde(default)]
+    pub retry_count: u64,
+    /// Time spent Running, up to the latest log entry. Unlike `startedAt` to
+    /// `lastActivityAt`, this leaves out idle time and waiting for permission.
+    #[serde(default)]
+    pub active_duration_ms: u64,
+    /// Messages read from the log so far, including those no longer kept in memory.
+    #[serde(default)]
+    pub message_count: u64,
+    /// Tool calls among them.
+    #[serde(default)]
+    pub tool_call_count: u64,
+    /// Size of the session's log file as of the latest read.
+    #[serde(default)]
+    pub transcript_bytes: u64,
+    /// Distinct pages and searches pulled in with WebFetch and WebSearch.
+    #[serde(default)]
+    pub source_count: u64,
+    /// `remote-or-container` when the working directory isn't on the dashboard's
+    /// machine (a devcontainer, Docker or SSH session), so there is no git
+    /// information. Git checks resume once the directory appears.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<String>,
+}
+
+/// A conversation spread over several sessions by resuming, oldest session first.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionChain {
+    pub session_id: String,
+    pub sessions: Vec<AgentSessionSummary>,
+    /// Usage summed over every session in the chain.
+    pub total_usage: CumulativeUsage,
+}
+
+/// Body of `POST /api/sessions/batch`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionBatchRequest {
+    pub session_ids: Vec<String>,
+}
+
+/// Body of `POST /api/projects/history`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryRequest {
+    /// The project's directory, as in a session's `projectPath`.
+    pub project_path: String,
+    /// Sessions to load. Empty loads the project's most recent ones.
+    #[serde(default)]
+    pub session_ids: Vec<String>,
+}
+
+/// Summaries of the requested sessions, by id. Archived sessions are included.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionBatchResponse {
+    pub sessions: HashMap<String, AgentSessionSummary>,
+    /// Requested ids that aren't tracked, in request order.
+    pub not_found: Vec<String>,
+}
+
+// ── Messages ──
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageRole {
+    User,
+    Assistant,
+    System,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageType {
+    Text,
+    ToolUse,
+    ToolResult,
+    Thinking,
+    StateChange,
+    Error,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentMessage {
+    pub id: String,
+    pub session_id: String,
+    pub timestamp: String,
+    pub role: MessageRole,
+    #[serde(rename = "type")]
+    pub msg_type: MessageType,
+    // Shared between a session's messages with the same text, e.g. a file read twice.
+    pub content: Arc<str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<HashMap<String, serde_json::Value>>,
+    /// Uuid of the log entry this message's entry follows. The ids of that entry's
+    /// messages start with it (`{uuid}:{block}`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+    /// For a tool result, the id of the message with the tool call it answers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_use_message_id: Option<String>,
+}
+
+// ── Session Detail ──
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentSessionDetail {
+    #[serde(flatten)]
+    pub summary: AgentSessionSummary,
+    // Arc so the server can hand out its message buffer without copying it.
+    pub messages: Vec<Arc<AgentMessage>>,
+    /// The most recent usage points, for a sparkline.
+    #[serde(default)]
+    pub usage_points: Vec<UsagePoint>,
+    /// The first model the session ran on, then each switch, oldest first.
+    #[serde(default)]
+    pub model_history: Vec<ModelSwitch>,
+    /// How the session's log is being read. None for replayed, relayed and historical
+    /// sessions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watcher: Option<WatcherHealth>,
+    /// The settings the session is judged by. None for relayed sessions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settings: Option<SessionConfig>,
+}
+
+/// A session's settings: the global ones, or those of the `[projects."<path>"]`
+/// section whose path is the longest prefix of its discovery project path.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionConfig {
+    /// The `[projects]` section that applies; None when only global settings do.
+    pub project_override: Option<String>,
+    pub permission_wait_secs: u64,
+    pub idle_secs: u64,
+    pub running_stopped_secs: u64,
+    pub idle_stopped_secs: u64,
+    pub process_gone_secs: u64,
+    /// States that send a push notification.
+    pub notify_states: Vec<AgentStateType>,
+    pub notify_context_warnings: bool,
+    /// Between git checks of an idle or waiting session.
+    pub git_check_interval_secs: u64,
+    pub git_check_interval_running_secs: u64,
+    /// Messages kept in memory.
+    pub max_messages: u64,
+    /// How long the session stays stopped before its messages are dropped.
+    pub dehydrate_after_secs: u64,
+}
+
+/// How a session's log watcher is doing, to tell a quiet session from a watcher
+/// that stopped reading.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WatcherHealth {
+    pub mode: WatcherMode,
+    /// When the log was last read without an error.
+    pub last_read_at: Option<String>,
+    /// Bytes read from the log since the watcher started.
+    pub bytes_read: u64,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WatcherMode {
+    /// Read on file change notifications, with polling as a backstop.
+    Notify,
+    /// Notifications couldn't be set up; the log is only polled.
+    PollOnly,
+    /// Not watched, e.g. a long-stopped session.
+    Stopped,
+}
+
+// ── Usage Timeline ──
+
+/// The model a session ran on from `timestamp`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelSwitch {
+    pub timestamp: String,
+    pub model: String,
+}
+
+/// Cumulative usage of a session as of `timestamp`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UsagePoint {
+    pub timestamp: String,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cache_creation_tokens: u64,
+    pub estimated_cost: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageTimeline {
+    pub session_id: String,
+    pub points: Vec<UsagePoint>,
+}
+
+// ── Timeline ──
+
+/// A contiguous period during which the session was Running.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivityInterval {
+    pub start: String,
+    /// None while the session is still running.
+    pub end: Option<String>,
+    pub duration_ms: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cache_creation_tokens: u64,
+    pub estimated_cost: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionTimeline {
+    pub session_id: String,
+    pub started_at: String,
+    pub total_active_ms: u64,
+    pub intervals: Vec<ActivityInterval>,
+}
+
+// ── Tool Stats ──
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolCount {
+    pub name: String,
+    pub count: u64,
+}
+
+/// A tool call with no result yet.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingTool {
+    pub tool_use_id: String,
+    pub name: String,
+    /// One line about what the call does: the command for Bash, the file for Edit
+    /// and Write, the pattern for Grep, and so on.
+    pub summary: String,
+}
+
+/// Calls a session made to one tool.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolStats {
+    pub name: String,
+    pub count: u64,
+    /// Results flagged `is_error`.
+    pub error_count: u64,
+    /// Size of the tool results as logged, before truncation for display.
+    pub output_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionToolStats {
+    pub session_id: String,
+    /// Most calls first.
+    pub tools: Vec<ToolStats>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceKind {
+    /// A page read with WebFetch.
+    Fetch,
+    /// A query run with WebSearch.
+    Search,
+}
+
+/// A page or search an agent pulled in while researching.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceRef {
+    pub kind: SourceKind,
+    /// The fetched URL, for `fetch`.
+    pub url: Option<String>,
+    /// The search query, for `search`.
+    pub query: Option<String>,
+    /// When it was last requested.
+    pub timestamp: String,
+    /// The message with the latest call.
+    pub message_id: String,
+    /// Size of the latest result as logged, or None while it hasn't come back.
+    pub result_bytes: Option<u64>,
+    /// Whether the latest result was flagged `is_error`.
+    pub is_error: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionSources {
+    pub session_id: String,
+    /// Oldest first; a source requested again moves to the end.
+    pub sources: Vec<SourceRef>,
+}
+
+/// A session's transcript split into turns: a prompt and everything the agent did
+/// for it, up to the `turn_duration` marker.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionOutline {
+    pub session_id: String,
+    /// Oldest first. Only turns within the messages the server keeps are listed.
+    pub turns: Vec<TurnOutline>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TurnOutline {
+    /// Start of the prompt. None for a turn already under way where the kept
+    /// messages begin.
+    pub prompt: Option<String>,
+    pub started_at: String,
+    /// None while the turn is still running.
+    pub ended_at: Option<String>,
+    /// As reported by the agent when the turn completed.
+    pub duration_ms: Option<u64>,
+    /// Most calls first.
+    pub tools: Vec<ToolCount>,
+    /// Files edited, written or read, in the order first touched.
+    pub files: Vec<String>,
+    pub message_count: u64,
+    /// Tokens and cost of the turn. None when the usage at its start isn't known.
+    pub usage: Option<CumulativeUsage>,
+}
+
+// ── Health ──
+
+/// `GET /api/health`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthResponse {
+    pub status: String,
+    /// Names the machine the server runs on.
+    pub instance: String,
+    /// Tracked sessions per Claude Code version.
+    pub cli_versions: BTreeMap<String, usize>,
+    #[serde(default)]
+    pub watchers: WatcherCounts,
+    /// Times WebSocket connections fell behind the server's event channels since it
+    /// started, and the events they missed.
+    #[serde(default)]
+    pub connection_lags: u64,
+    #[serde(default)]
+    pub events_missed: u64,
+    /// Log timestamps that could not be read and were replaced by the time they
+    /// were seen, since the server started.
+    #[serde(default)]
+    pub unparseable_timestamps: u64,
+    /// Git checks skipped since the server started because the session's working
+    /// directory isn't on this machine.
+    #[serde(default)]
+    pub suppressed_git_checks: u64,
+    #[serde(default)]
+    pub watchdog: WatchdogStatus,
+    #[serde(default)]
+    pub transcript_memory: TranscriptMemory,
+}
+
+/// Memory held by the messages of every tracked session, and what was dropped to
+/// stay within `MAX_TRANSCRIPT_MEMORY_MB`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TranscriptMemory {
+    /// Roughly: message text and metadata plus a fixed overhead per message.
+    pub retained_bytes: u64,
+    /// 0 when there is no limit.
+    pub budget_bytes: u64,
+    /// Times since the server started that a session's messages were dropped to
+    /// stay within the budget, and the messages dropped.
+    pub sheds: u64,
+    pub shed_messages: u64,
+}
+
+/// What the server found when it first read the logs after starting: the sessions
+/// discovery's first scan found, and what the first read of their logs held.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StartupSummary {
+    pub sessions_discovered: u32,
+    pub entries_processed: u64,
+    /// Log lines that held no entry this server could read.
+    pub parse_errors: u64,
+    /// From the server starting until the last of those logs was read.
+    pub duration_ms: u64,
+    /// Some logs were still being read when startup stopped waiting for them; their
+    /// entries aren't counted.
+    #[serde(default)]
+    pub timed_out: bool,
+}
+
+/// What the watchdog found when it last checked that discovery, the log watchers
+/// and event routing are still running, and what it restarted since the server
+/// started.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchdogStatus {
+    /// None before the first check.
+    pub last_check_at: Option<String>,
+    pub last_discovery_scan_at: Option<String>,
+    /// The checks that failed last time: `discovery`, `routing`, or
+    /// `watcher:<session id>`.
+    pub failing: Vec<String>,
+    pub discovery_restarts: u64,
+    pub watcher_restarts: u64,
+    /// Checks that found the routing loop not moving. It can't be restarted in place.
+    pub routing_stalls: u64,
+}
+
+/// The log watchers of the sessions being followed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WatcherCounts {
+    pub notify: usize,
+    pub poll_only: usize,
+    /// Sessions whose watcher's last read failed, or that stopped reading while
+    /// their log kept growing.
+    pub failing: Vec<String>,
+}
+
+// ── Dashboard Stats ──
+
+/// Totals across every tracked session, for the dashboard header.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DashboardStats {
+    pub total_sessions: usize,
+    /// Sessions that are Running or waiting for permission.
+    pub active_sessions: usize,
+    /// Cost since UTC midnight.
+    pub cost_today: f64,
+    /// Cost since Monday 00:00 UTC.
+    pub cost_this_week: f64,
+    /// Input, output, cache read and cache creation tokens.
+    pub total_tokens: u64,
+    /// Project name whose sessions used the most tokens.
+    pub busiest_project: Option<String>,
+    /// Completed turns: times a session went back to Idle after working.
+    pub total_turns: u64,
+    /// Sessions holding their messages in memory and watching their logs.
+    pub hydrated_sessions: usize,
+    /// Sessions stopped long enough that their messages were dropped until needed.
+    pub dehydrated_sessions: usize,
+    /// Mean time from a session's first to its latest activity.
+    pub average_session_duration_ms: u64,
+    /// Time sessions spent Running since UTC midnight, summed.
+    #[serde(default)]
+    pub active_duration_ms_today: u64,
+    pub tool_calls: u64,
+    /// Tool stats summed over sessions, most calls first.
+    pub tools: Vec<ToolStats>,
+    pub generated_at: String,
+}
+
+// ── Projects ──
+
+/// The span a project budget covers. Periods start at UTC midnight; weeks on Monday.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BudgetPeriod {
+    Day,
+    #[default]
+    Week,
+    Month,
+    /// Everything ever recorded for the project.
+    Total,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BudgetStatus {
+    pub period: BudgetPeriod,
+    pub limit_usd: f64,
+    /// Spent in the current period.
+    pub spent_usd: f64,
+    pub exceeded: bool,
+}
+
+/// Usage summed over every session seen for a project, including sessions no
+/// longer tracked. Keyed by the project directory the session was discovered in.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectUsage {
+    pub project_path: String,
+    pub project_name: String,
+    pub sessions: usize,
+    pub usage: CumulativeUsage,
+    /// Cost since UTC midnight.
+    pub cost_today: f64,
+    /// Cost since Monday 00:00 UTC.
+    pub cost_this_week: f64,
+    /// Cost since the first of the month, 00:00 UTC.
+    pub cost_this_month: f64,
+    /// Only set when the config file has a budget for this project.
+    pub budget: Option<BudgetStatus>,
+}
+
+/// A project and the sessions discovered in it, from `GET /api/sessions?group_by=project`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectSessions {
+    /// As `GET /api/projects` lists it; zero usage if none was recorded yet.
+    pub project: ProjectUsage,
+    /// Most recently active first. With `group_chains`, only the latest session of
+    /// each resume chain.
+    pub sessions: Vec<AgentSessionSummary>,
+}
+
+// ── Usage Reports ──
+
+/// The days a usage report covers: the last 7 or 30 UTC days, today included.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportPeriod {
+    #[default]
+    Week,
+    Month,
+}
+
+impl ReportPeriod {
+    pub fn days(self) -> i64 {
+        match self {
+            ReportPeriod::Week => 7,
+            ReportPeriod::Month => 30,
+        }
+    }
+}
+
+/// `GET /api/reports/usage`: usage over a period, from the per-project tallies, so
+/// sessions no longer tracked are included. Tokens count input, output, cache read
+/// and cache creation tokens.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageReport {
+    pub period: ReportPeriod,
+    /// First and last day of the period, `YYYY-MM-DD` (UTC).
+    pub from: String,
+    pub to: String,
+    pub generated_at: String,
+    pub coverage: ReportCoverage,
+    pub total_cost: f64,
+    pub total_tokens: u64,
+    /// Sessions with usage in the period.
+    pub sessions: usize,
+    /// Most expensive first.
+    pub projects: Vec<ReportProject>,
+    /// Most expensive first.
+    pub models: Vec<ReportModel>,
+    /// The most expensive days, at most 5.
+    pub busiest_days: Vec<ReportDay>,
+    /// At most 5, longest first.
+    pub longest_sessions: Vec<ReportSession>,
+    /// Calls over the lifetime of the period's sessions, at most 10, most first.
+    pub top_tools: Vec<ToolCount>,
+}
+
+/// How much of the period the recorded history spans.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportCoverage {
+    /// First day with recorded usage, `YYYY-MM-DD`; null if nothing was recorded yet.
+    pub history_from: Option<String>,
+    /// Whether the history reaches back to the start of the period.
+    pub complete: bool,
+    /// Sessions recorded before tokens, models, durations and tools were kept;
+    /// only their cost is counted.
+    pub sessions_without_details: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportProject {
+    pub project_path: String,
+    pub project_name: String,
+    pub cost: f64,
+    pub tokens: u64,
+    pub sessions: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportModel {
+    pub model: String,
+    pub cost: f64,
+    pub tokens: u64,
+    pub sessions: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportDay {
+    /// `YYYY-MM-DD` (UTC).
+    pub date: String,
+    pub cost: f64,
+    pub tokens: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportSession {
+    pub session_id: String,
+    pub project_name: String,
+    pub title: String,
+    pub model: String,
+    pub started_at: String,
+    pub last_activity_at: String,
+    /// From the first to the latest activity.
+    pub duration_secs: u64,
+    /// Cost within the period.
+    pub cost: f64,
+}
+
+// ── Day Digests ──
+
+/// `GET /api/projects/{path}/digest`: what agents did in one project on one UTC day.
+/// Sessions no longer tracked only left their usage behind, so their active time,
+/// files and commits are missing.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DayDigest {
+    pub project_path: String,
+    pub project_name: String,
+    /// `YYYY-MM-DD` (UTC).
+    pub date: String,
+    pub generated_at: String,
+    pub sessions_started: usize,
+    pub sessions_ended: usize,
+    pub total_cost: f64,
+    pub total_tokens: u64,
+    /// Time spent running, across the sessions still tracked.
+    pub active_ms: u64,
+    /// Files edited or written by the sessions whose messages are in memory, in the
+    /// order first touched.
+    pub files: Vec<String>,
+    /// Commits made that day, newest first. Only the latest commit in each session's
+    /// working tree is seen. None when no session has a git status.
+    pub commits: Option<Vec<GitCommitInfo>>,
+    /// Sessions that used anything, started or were last active that day, oldest first.
+    pub sessions: Vec<DigestSession>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DigestSession {
+    pub session_id: String,
+    pub title: Option<String>,
+    pub model: String,
+    /// None for sessions no longer tracked.
+    pub state: Option<AgentStateType>,
+    pub started_at: String,
+    pub started_on_day: bool,
+    /// When the session stopped, if that was on this day. Sessions no longer tracked
+    /// count as stopped at their last activity.
+    pub ended_at: Option<String>,
+    /// Cost and tokens on this day only.
+    pub cost: f64,
+    pub tokens: u64,
+    /// Time spent running on this day. None for sessions no longer tracked.
+    pub active_ms: Option<u64>,
+    /// Files edited or written on this day, in the order first touched. None when
+    /// the session's messages aren't in memory.
+    pub files: Option<Vec<String>>,
+    /// Calls over the session's lifetime, most first.
+    pub top_tools: Vec<ToolCount>,
+}
+
+// ── Event Journal ──
+
+/// A provider event as recorded by the event journal.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct JournalEntry {
+    /// When the event was routed.
+    pub ingested_at: String,
+    pub session_id: String,
+    /// The event, tagged by `type` (e.g. `state_changed`).
+    pub event: serde_json::Value,
+}
+
+// ── Connections ──
+
+/// Outcome of `POST /api/config/reload`: dotted keys that changed in the file,
+/// split by whether they took effect.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigReload {
+    pub applied: Vec<String>,
+    /// Changed, but only read at startup.
+    pub requires_restart: Vec<String>,
+}
+
+/// A connected WebSocket client, for `GET /api/connections`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionInfo {
+    pub id: u64,
+    /// Unknown when the server was not started with connection info.
+    pub remote_addr: Option<String>,
+    pub connected_at: String,
+    /// Sessions whose messages the client is subscribed to, sorted.
+    pub subscriptions: Vec<String>,
+    /// Projects whose sessions' messages the client is subscribed to, sorted.
+    #[serde(default)]
+    pub project_subscriptions: Vec<String>,
+    pub events_sent: u64,
+    /// Events dropped or merged because the client was not reading fast enough.
+    pub events_dropped: u64,
+    /// Times the connection fell behind the server's event channels.
+    #[serde(default)]
+    pub lags: u64,
+    /// Events it missed when it did.
+    #[serde(default)]
+    pub events_missed: u64,
+    /// The last message sent to or received from the client.
+    pub last_activity_at: String,
+}
+
+// ── Public Status ──
+
+/// A session as the public status page shows it: no prompts, paths, messages or
+/// costs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicSessionSummary {
+    pub session_id: String,
+    pub project_name: String,
+    pub state: AgentStateType,
+    pub model: String,
+    pub started_at: String,
+    pub last_activity_at: String,
+    /// From `startedAt` to `lastActivityAt`.
+    pub duration_ms: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+/// Events on `GET /status/api/events`, sent as server-sent events named after `type`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type")]
+pub enum PublicStatusEvent {
+    /// First event on every stream.
+    #[serde(rename = "sessions:init")]
+    SessionsInit { sessions: Vec<PublicSessionSummary> },
+
+    /// A session appeared or changed state.
+    #[serde(rename = "session:updated")]
+    SessionUpdated { session: PublicSessionSummary },
+
+    #[serde(rename = "session:removed")]
+    #[serde(rename_all = "camelCase")]
+    SessionRemoved { session_id: String },
+}
+
+impl PublicStatusEvent {
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            PublicStatusEvent::SessionsInit { .. } => "sessions:init",
+            PublicStatusEvent::SessionUpdated { .. } => "session:updated",
+            PublicStatusEvent::SessionRemoved { .. } => "session:removed",
+        }
+    }
+}
+
+// ── Archive ──
+
+/// An archived session as listed by `GET /api/archive`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchivedSessionInfo {
+    pub session_id: String,
+    pub project_path: String,
+    pub project_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub initial_task: String,
+    pub model: String,
+    pub started_at: String,
+    pub last_activity_at: String,
+    pub archived_at: String,
+    pub compressed_bytes: u64,
+}
+
+/// A stopped session as it was when it left memory, from `GET /api/archive/{id}`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchivedSession {
+    pub archived_at: String,
+    pub detail: AgentSessionDetail,
+    /// When the session was running.
+    #[serde(default)]
+    pub timeline: Option<SessionTimeline>,
+    #[serde(default)]
+    pub usage: Option<UsageTimeline>,
+}
+
+// ── Search ──
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchScope {
+    ProjectName,
+    Title,
+    CurrentTask,
+    WorkingDirectory,
+    Content,
+    Note,
+    Labels,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchMatch {
+    pub content: String,
+    pub scope: SearchScope,
+    pub message_role: MessageRole,
+    pub message_type: MessageType,
+    pub timestamp: String,
+    /// The hit is in part of the message that was truncated away; `content` shows
+    /// what is still stored.
+    #[serde(default)]
+    pub truncated_source: bool,
+    /// Content scope: id of the matched message, for jumping to it. Null for other scopes.
+    pub message_id: Option<String>,
+    /// Content scope: position of the message in the session's current message list.
+    /// Null for other scopes and for trimmed messages.
+    pub message_index: Option<u32>,
+    /// The message has been trimmed from the session's message list; only the
+    /// snippet is available.
+    #[serde(default)]
+    pub message_trimmed: bool,
+    /// Current task scope: which of the session's prompts matched.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task: Option<TaskPrompt>,
+}
+
+/// The prompts a session's task is shown from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskPrompt {
+    Initial,
+    Latest,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionSearchResult {
+    pub session: AgentSessionSummary,
+    /// With `group_chains`, counts the matches of the whole resume chain.
+    pub match_count: u32,
+    pub matches: Vec<SearchMatch>,
+    /// With `group_chains`, the results for earlier sessions of the same resume
+    /// chain, oldest first; `session` is then the latest session of the chain that
+    /// matched.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub earlier_in_chain: Vec<SessionSearchResult>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResponse {
+    pub query: String,
+    pub total_sessions: u32,
+    pub results: Vec<SessionSearchResult>,
+}
+
+// ── WebSocket Protocol ──
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type")]
+pub enum ServerEvent {
+    /// First frame on every connection, before `sessions:init`. `capabilities` lists
+    /// the event types this server sends plus optional features (e.g. `thinking`).
+    #[serde(rename = "server:hello")]
+    #[serde(rename_all = "camelCase")]
+    Hello {
+        protocol_version: u32,
+        server_version: String,
+        capabilities: Vec<String>,
+    },
+
+    #[serde(rename = "sessions:init")]
+    #[serde(rename_all = "camelCase")]
+    SessionsInit {
+        sessions: Vec<AgentSessionSummary>,
+        /// Messages since this client's last `ack:read`, for sessions it has acked.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        unread_counts: Option<HashMap<String, u64>>,
+    },
+
+    #[serde(rename = "session:discovered")]
+    SessionDiscovered {
+        session: AgentSessionSummary,
+    },
+
+    #[serde(rename = "session:removed")]
+    #[serde(rename_all = "camelCase")]
+    SessionRemoved {
+        session_id: String,
+    },
+
+    /// Summary fields without a dedicated event (prefs, note, labels, resume link) changed.
+    #[serde(rename = "session:updated")]
+    SessionUpdated {
+        session: AgentSessionSummary,
+    },
+
+    #[serde(rename = "session:state_changed")]
+    #[serde(rename_all = "camelCase")]
+    StateChanged {
+        session_id: String,
+        previous: AgentStateType,
+        current: AgentStateType,
+        session: AgentSessionSummary,
+        /// Messages since this client's last `ack:read` of the session, if it has one.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        unread_count: Option<u64>,
+    },
+
+    #[serde(rename = "session:new_message")]
+    #[serde(rename_all = "camelCase")]
+    NewMessage {
+        session_id: String,
+        message: AgentMessage,
+    },
+
+    /// Many messages arrived at once (e.g. a long log was read in). Subscribed clients
+    /// should re-subscribe to get them in one `session:messages_init`.
+    #[serde(rename = "session:messages_appended")]
+    #[serde(rename_all = "camelCase")]
+    MessagesAppended {
+        session_id: String,
+        count: u32,
+        latest_timestamp: String,
+    },
+
+    #[serde(rename = "session:messages_init")]
+    #[serde(rename_all = "camelCase")]
+    MessagesInit {
+        session_id: String,
+        messages: Vec<Arc<AgentMessage>>,
+    },
+
+    #[serde(rename = "session:usage_updated")]
+    #[serde(rename_all = "camelCase")]
+    UsageUpdated {
+        session_id: String,
+        usage: CumulativeUsage,
+        spend_rate_per_hour: f64,
+        tokens_per_minute: f64,
+        context_tokens: u64,
+        context_limit: Option<u64>,
+    },
+
+    #[serde(rename = "session:git_status_updated")]
+    #[serde(rename_all = "camelCase")]
+    GitStatusUpdated {
+        session_id: String,
+        git_status: GitStatus,
+    },
+
+    /// The context passed a warning threshold (`threshold`%) for the first time since
+    /// it was last compacted.
+    #[serde(rename = "session:context_warning")]
+    #[serde(rename_all = "camelCase")]
+    ContextWarning {
+        session_id: String,
+        percent_used: u32,
+        threshold: u32,
+        session: AgentSessionSummary,
+    },
+
+    /// The session's main thread switched models, e.g. with `/model` or a fallback.
+    #[serde(rename = "session:model_changed")]
+    #[serde(rename_all = "camelCase")]
+    ModelChanged {
+        session_id: String,
+        previous: String,
+        current: String,
+    },
+
+    /// The session's log file was deleted while it was being watched. The session is
+    /// stopped, and removed a few minutes later unless the log is written to again.
+    #[serde(rename = "session:log_deleted")]
+    #[serde(rename_all = "camelCase")]
+    LogDeleted {
+        session_id: String,
+        log_file: String,
+    },
+
+    /// A project's spend went over its configured budget. Sent once per budget period.
+    #[serde(rename = "project:budget_exceeded")]
+    #[serde(rename_all = "camelCase")]
+    BudgetExceeded {
+        project_path: String,
+        project_name: String,
+        period: BudgetPeriod,
+        limit_usd: f64,
+        spent_usd: f64,
+    },
+
+    /// Every log found when the server started has been read once (or startup
+    /// stopped waiting). Sent once; `GET /api/startup` has the same summary.
+    #[serde(rename = "server:startup_complete")]
+    #[serde(rename_all = "camelCase")]
+    StartupComplete {
+        sessions_discovered: u32,
+        entries_processed: u64,
+        parse_errors: u64,
+        duration_ms: u64,
+        timed_out: bool,
+    },
+
+    /// A frame from the client couldn't be handled: it wasn't a known event, or its
+    /// fields didn't match. `event_type` is the `type` it was sent with, if it had one.
+    #[serde(rename = "server:error")]
+    #[serde(rename_all = "camelCase")]
+    Error {
+        message: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        event_type: Option<String>,
+    },
+}
+
+/// Every `type` a [`ServerEvent`] can have.
+pub const SERVER_EVENT_TYPES: &[&str] = &[
+    "server:hello",
+    "sessions:init",
+    "session:discovered",
+    "session:removed",
+    "session:updated",
+    "session:state_changed",
+    "session:new_message",
+    "session:messages_appended",
+    "session:messages_init",
+    "session:usage_updated",
+    "session:git_status_updated",
+    "session:context_warning",
+    "session:model_changed",
+    "session:log_deleted",
+    "project:budget_exceeded",
+    "server:startup_complete",
+    "server:error",
+];
+
+impl ServerEvent {
+    /// The `type` tag this event is sent with.
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            ServerEvent::Hello { .. } => "server:hello",
+            ServerEvent::SessionsInit { .. } => "sessions:init",
+            ServerEvent::SessionDiscovered { .. } => "session:discovered",
+            ServerEvent::SessionRemoved { .. } => "session:removed",
+            ServerEvent::SessionUpdated { .. } => "session:updated",
+            ServerEvent::StateChanged { .. } => "session:state_changed",
+            ServerEvent::NewMessage { .. } => "session:new_message",
+            ServerEvent::MessagesAppended { .. } => "session:messages_appended",
+            ServerEvent::MessagesInit { .. } => "session:messages_init",
+            ServerEvent::UsageUpdated { .. } => "session:usage_updated",
+            ServerEvent::GitStatusUpdated { .. } => "session:git_status_updated",
+            ServerEvent::ContextWarning { .. } => "session:context_warning",
+            ServerEvent::ModelChanged { .. } => "session:model_changed",
+            ServerEvent::LogDeleted { .. } => "session:log_deleted",
+            ServerEvent::BudgetExceeded { .. } => "project:budget_exceeded",
+            ServerEvent::StartupComplete { .. } => "server:startup_complete",
+            ServerEvent::Error { .. } => "server:error",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type")]
+pub enum ClientEvent {
+    /// Optional reply to `server:hello`. A client on an older protocol version only
+    /// gets the event types and features it lists in `capabilities`.
+    #[serde(rename = "client:hello")]
+    #[serde(rename_all = "camelCase")]
+    Hello {
+        protocol_version: u32,
+        #[serde(default)]
+        capabilities: Vec<String>,
+        /// Stable id the client picked for itself, so what it has read is kept
+        /// across reconnects.
+        #[serde(default)]
+        client_id: Option<String>,
+    },
+
+    #[serde(rename = "subscribe:session")]
+    #[serde(rename_all = "camelCase")]
+    Subscribe { session_id: String },
+
+    #[serde(rename = "unsubscribe:session")]
+    #[serde(rename_all = "camelCase")]
+    Unsubscribe { session_id: String },
+
+    /// Messages of every session whose `projectPath` is `project_path`, including
+    /// sessions that show up later.
+    #[serde(rename = "subscribe:project")]
+    #[serde(rename_all = "camelCase")]
+    SubscribeProject { project_path: String },
+
+    #[serde(rename = "unsubscribe:project")]
+    #[serde(rename_all = "camelCase")]
+    UnsubscribeProject { project_path: String },
+
+    /// The client has seen a session's messages up to and including `message_id`.
+    #[serde(rename = "ack:read")]
+    #[serde(rename_all = "camelCase")]
+    AckRead {
+        session_id: String,
+        message_id: String,
+    },
+
+    /// Any `type` this server doesn't know, e.g. from a newer client. Answered with
+    /// `server:error`.
+    #[serde(rename = "unknown")]
+    #[serde(other)]
+    Unknown,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_agent_state_serialization() {
+        assert_eq!(
+            serde_json::to_string(&AgentStateType::Running).unwrap(),
+            r#""running""#
+        );
+        assert_eq!(
+            serde_json::to_string(&AgentStateType::PermissionWaiting).unwrap(),
+            r#""permission_waiting""#
+        );
+    }
+
+    #[test]
+    fn test_cumulative_usage_camel_case() {
+        let usage = CumulativeUsage {
+            input_tokens: 100,
+            output_tokens: 200,
+            cache_read_tokens: 50,
+            cache_creation_tokens: 25,
+            cache_creation_5m_tokens: None,
+            cache_creation_1h_tokens: Some(25),
+            estimated_cost: 0.01,
+        };
+        let json = serde_json::to_value(&usage).unwrap();
+        assert!(json.get("inputTokens").is_some());
+        assert!(json.get("outputTokens").is_some());
+        assert!(json.get("cacheReadTokens").is_some());
+        assert!(json.get("cacheCreationTokens").is_some());
+        assert!(json.get("estimatedCost").is_some());
+        assert_eq!(json["cacheCreation1hTokens"], 25);
+        assert!(json.get("cacheCreation5mTokens").is_none());
+    }
+
+    #[test]
+    fn test_session_summary_camel_case() {
+        let summary = AgentSessionSummary {
+            session_id: "abc".into(),
+            provider: "claude-code".into(),
+            state: AgentStateType::Running,
+            project_path: "/tmp".into(),
+            project_name: "test".into(),
+            working_directory: "/tmp".into(),
+            current_cwd: "/tmp".into(),
+            initial_task: "hello".into(),
+            latest_task: String::new(),
+            latest_assistant_text: None,
+            model: "claude-sonnet-4-20250514".into(),
+            last_activity_at: "2025-01-01T00:00:00Z".into(),
+            started_at: "2025-01-01T00:00:00Z".into(),
+            cumulative_usage: CumulativeUsage::default(),
+            git_status: GitStatus::default(),
+            pinned: false,
+            note: None,
+            labels: vec![],
+            summary: None,
+            permission_mode: None,
+            resumed_from: None,
+            spend_rate_per_hour: 0.0,
+            tokens_per_minute: 0.0,
+            context_tokens: 144_000,
+            context_limit: None,
+            cli_version: None,
+            title: None,
+            top_tools: Vec::new(),
+            replay: false,
+            historical: false,
+            host: "laptop".into(),
+            activity_type: None,
+            pending_tool: None,
+            stop_reason: None,
+            hook_errors: 0,
+            failed_turns: 0,
+            retry_count: 0,
+            active_duration_ms: 0,
+            message_count: 0,
+            tool_call_count: 0,
+            transcript_bytes: 0,
+            source_count: 0,
+            environment: None,
+        };
+        let json = serde_json::to_value(&summary).unwrap();
+        assert!(json.get("sessionId").is_some());
+        assert!(json.get("projectPath").is_some());
+        assert!(json.get("projectName").is_some());
+        assert!(json.get("workingDirectory").is_some());
+        assert!(json.get("initialTask").is_some());
+        assert!(json.get("latestTask").is_some());
+        assert!(json.get("lastActivityAt").is_some());
+        assert!(json.get("startedAt").is_some());
+        assert!(json.get("cumulativeUsage").is_some());
+        assert_eq!(json["host"], "laptop");
+        assert_eq!(json["contextTokens"], 144_000);
+        assert!(json["contextLimit"].is_null());
+        assert_eq!(json["messageCount"], 0);
+        assert_eq!(json["transcriptBytes"], 0);
+        assert_eq!(json["pinned"], false);
+        assert!(json.get("note").is_none());
+        assert!(json.get("labels").is_none());
+        assert!(json.get("pendingTool").is_none());
+
+        let summary = AgentSessionSummary {
+            pending_tool: Some(PendingTool {
+                tool_use_id: "toolu_1".into(),
+                name: "Bash".into(),
+                summary: "rm -rf node_modules".into(),
+            }),
+            ..summary
+        };
+        let json = serde_json::to_value(&summary).unwrap();
+        assert_eq!(json["pendingTool"]["toolUseId"], "toolu_1");
+        assert_eq!(json["pendingTool"]["summary"], "rm -rf node_modules");
+
+        // Summaries from before the latest task was tracked.
+        let mut old = json;
+        let task = old.as_object_mut().unwrap().remove("initialTask").unwrap();
+        old.as_object_mut().unwrap().remove("latestTask");
+        old["currentTask"] = task;
+        let parsed: AgentSessionSummary = serde_json::from_value(old).unwrap();
+        assert_eq!(parsed.initial_task, "hello");
+        assert_eq!(parsed.latest_task, "");
+    }
+
+    #[test]
+    fn test_server_event_sessions_init() {
+        let event = ServerEvent::SessionsInit {
+            sessions: vec![],
+            unread_counts: None,
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "sessions:init");
+        assert!(json["sessions"].is_array());
+        assert!(json.get("unreadCounts").is_none());
+
+        let event = ServerEvent::SessionsInit {
+            sessions: vec![],
+            unread_counts: Some(HashMap::from([("s1".to_string(), 3)])),
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["unreadCounts"]["s1"], 3);
+    }
+
+    #[test]
+    fn test_summary_without_host_still_parses() {
+        let mut json = serde_json::to_value(AgentSessionSummary::default()).unwrap();
+        json.as_object_mut().unwrap().remove("host");
+        let summary: AgentSessionSummary = serde_json::from_value(json).unwrap();
+        assert_eq!(summary.host, "");
+    }
+
+    #[test]
+    fn test_event_type_matches_tag() {
+        let events = [
+            ServerEvent::Hello {
+                protocol_version: 2,
+                server_version: "0.1.0".into(),
+                capabilities: vec![],
+            },
+            ServerEvent::SessionsInit {
+                sessions: vec![],
+                unread_counts: None,
+            },
+            ServerEvent::SessionRemoved {
+                session_id: "s1".into(),
+            },
+            ServerEvent::MessagesAppended {
+                session_id: "s1".into(),
+                count: 1,
+                latest_timestamp: String::new(),
+            },
+            ServerEvent::MessagesInit {
+                session_id: "s1".into(),
+                messages: vec![],
+            },
+            ServerEvent::GitStatusUpdated {
+                session_id: "s1".into(),
+                git_status: GitStatus::default(),
+            },
+            ServerEvent::BudgetExceeded {
+                project_path: "/work/app".into(),
+                project_name: "app".into(),
+                period: BudgetPeriod::Week,
+                limit_usd: 10.0,
+                spent_usd: 10.5,
+            },
+        ];
+        for event in events {
+            let json = serde_json::to_value(&event).unwrap();
+            assert_eq!(json["type"], event.event_type());
+            assert!(SERVER_EVENT_TYPES.contains(&event.event_type()));
+        }
+    }
+
+    #[test]
+    fn test_client_hello_capabilities_optional() {
+        let event: ClientEvent =
+            serde_json::from_str(r#"{"type":"client:hello","protocolVersion":1}"#).unwrap();
+        assert!(matches!(
+            event,
+            ClientEvent::Hello { protocol_version: 1, capabilities, client_id: None }
+                if capabilities.is_empty()
+        ));
+    }
+
+    #[test]
+    fn test_client_ack_read() {
+        let event: ClientEvent = serde_json::from_str(
+            r#"{"type":"ack:read","sessionId":"s1","messageId":"m1"}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            event,
+            ClientEvent::AckRead { session_id, message_id } if session_id == "s1" && message_id == "m1"
+        ));
+    }
+
+    #[test]
+    fn test_server_event_state_changed() {
+        let event = ServerEvent::StateChanged {
+            session_id: "s1".into(),
+            previous: AgentStateType::Running,
+            current: AgentStateType::Idle,
+            session: AgentSessionSummary {
+                session_id: "s1".into(),
+                provider: "claude-code".into(),
+                state: AgentStateType::Idle,
+                project_path: "/tmp".into(),
+                project_name: "test".into(),
+                working_directory: "/tmp".into(),
+                current_cwd: "/tmp".into(),
+                initial_task: "".into(),
+                latest_task: String::new(),
+                latest_assistant_text: Some("Fixed the redirect check.".into()),
+                model: "claude-sonnet-4-20250514".into(),
+                last_activity_at: "2025-01-01T00:00:00Z".into(),
+                started_at: "2025-01-01T00:00:00Z".into(),
+                cumulative_usage: CumulativeUsage::default(),
+                git_status: GitStatus::default(),
+                pinned: false,
+                note: None,
+                labels: vec![],
+                summary: None,
+                permission_mode: None,
+                resumed_from: None,
+                spend_rate_per_hour: 0.0,
+                tokens_per_minute: 0.0,
+                context_tokens: 0,
+                context_limit: None,
+                cli_version: None,
+                title: None,
+                top_tools: Vec::new(),
+                replay: false,
+                historical: false,
+                host: "laptop".into(),
+                activity_type: None,
+                pending_tool: None,
+                stop_reason: None,
+                hook_errors: 0,
+                failed_turns: 0,
+                retry_count: 0,
+                active_duration_ms: 0,
+                message_count: 0,
+                tool_call_count: 0,
+                transcript_bytes: 0,
+                source_count: 0,
+                environment: None,
+            },
+            unread_count: Some(2),
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "session:state_changed");
+        assert_eq!(json["unreadCount"], 2);
+        assert_eq!(json["sessionId"], "s1");
+        assert_eq!(json["previous"], "running");
+        assert_eq!(json["current"], "idle");
+        assert!(json["session"].is_object());
+        assert_eq!(
+            json["session"]["latestAssistantText"],
+            "Fixed the redirect check."
+        );
+    }
+
+    #[test]
+    fn test_server_event_new_message() {
+        let event = ServerEvent::NewMessage {
+            session_id: "s1".into(),
+            message: AgentMessage {
+                id: "msg_1".into(),
+                session_id: "s1".into(),
+                timestamp: "2025-01-01T00:00:00Z".into(),
+                role: MessageRole::User,
+                msg_type: MessageType::Text,
+                content: "hello".into(),
+                metadata: None,
+                parent_id: None,
+                tool_use_message_id: None,
+            },
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "session:new_message");
+        assert_eq!(json["sessionId"], "s1");
+        assert_eq!(json["message"]["type"], "text");
+        assert_eq!(json["message"]["role"], "user");
+    }
+
+    #[test]
+    fn test_client_event_subscribe() {
+        let json = r#"{"type":"subscribe:session","sessionId":"abc123"}"#;
+        let event: ClientEvent = serde_json::from_str(json).unwrap();
+        match event {
+            ClientEvent::Subscribe { session_id } => {
+                assert_eq!(session_id, "abc123");
+            }
+            _ => panic!("Expected Subscribe"),
+        }
+    }
+
+    #[test]
+    fn test_client_event_unsubscribe() {
+        let json = r#"{"type":"unsubscribe:session","sessionId":"abc123"}"#;
+        let event: ClientEvent = serde_json::from_str(json).unwrap();
+        match event {
+            ClientEvent::Unsubscribe { session_id } => {
+                assert_eq!(session_id, "abc123");
+            }
+            _ => panic!("Expected Unsubscribe"),
+        }
+    }
+
+    /// Serializes `event`, parses it back and checks nothing changed on the way.
+    fn round_trip<T: Serialize + serde::de::DeserializeOwned>(event: &T) -> serde_json::Value {
+        let json = serde_json::to_value(event).unwrap();
+        let parsed: T = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), json);
+        json
+    }
+
+    #[test]
+    fn test_server_events_round_trip() {
+        let session = AgentSessionSummary {
+            session_id: "s1".into(),
+            project_path: "/work/app".into(),
+            ..Default::default()
+        };
+        let message = AgentMessage {
+            id: "msg_1".into(),
+            session_id: "s1".into(),
+            timestamp: "2025-01-01T00:00:00Z".into(),
+            role: MessageRole::Assistant,
+            msg_type: MessageType::Text,
+            content: "done".into(),
+            metadata: None,
+            parent_id: None,
+            tool_use_message_id: None,
+        };
+        let events = [
+            ServerEvent::Hello {
+                protocol_version: 2,
+                server_version: "0.1.0".into(),
+                capabilities: vec!["thinking".into()],
+            },
+            ServerEvent::SessionsInit {
+                sessions: vec![session.clone()],
+                unread_counts: Some(HashMap::from([("s1".to_string(), 2)])),
+            },
+            ServerEvent::SessionDiscovered {
+                session: session.clone(),
+            },
+            ServerEvent::SessionRemoved {
+                session_id: "s1".into(),
+            },
+            ServerEvent::SessionUpdated {
+                session: session.clone(),
+            },
+            ServerEvent::StateChanged {
+                session_id: "s1".into(),
+                previous: AgentStateType::Running,
+                current: AgentStateType::PermissionWaiting,
+                session: session.clone(),
+                unread_count: Some(1),
+            },
+            ServerEvent::NewMessage {
+                session_id: "s1".into(),
+                message: message.clone(),
+            },
+            ServerEvent::MessagesAppended {
+                session_id: "s1".into(),
+                count: 3,
+                latest_timestamp: "2025-01-01T00:00:00Z".into(),
+            },
+            ServerEvent::MessagesInit {
+                session_id: "s1".into(),
+                messages: vec![Arc::new(message)],
+            },
+            ServerEvent::UsageUpdated {
+                session_id: "s1".into(),
+                usage: CumulativeUsage {
+                    input_tokens: 100,
+                    output_tokens: 20,
+                    estimated_cost: 0.5,
+                    ..Default::default()
+                },
+                spend_rate_per_hour: 1.5,
+                tokens_per_minute: 40.0,
+                context_tokens: 1_000,
+                context_limit: Some(200_000),
+            },
+            ServerEvent::GitStatusUpdated {
+                session_id: "s1".into(),
+                git_status: GitStatus::default(),
+            },
+            ServerEvent::ContextWarning {
+                session_id: "s1".into(),
+                percent_used: 82,
+                threshold: 80,
+                session: session.clone(),
+            },
+            ServerEvent::ModelChanged {
+                session_id: "s1".into(),
+                previous: "claude-opus-4".into(),
+                current: "claude-sonnet-4".into(),
+            },
+            ServerEvent::LogDeleted {
+                session_id: "s1".into(),
+                log_file: "/logs/s1.jsonl".into(),
+            },
+            ServerEvent::BudgetExceeded {
+                project_path: "/work/app".into(),
+                project_name: "app".into(),
+                period: BudgetPeriod::Day,
+                limit_usd: 5.0,
+                spent_usd: 5.25,
+            },
+            ServerEvent::StartupComplete {
+                sessions_discovered: 3,
+                entries_processed: 1_200,
+                parse_errors: 2,
+                duration_ms: 450,
+                timed_out: false,
+            },
+            ServerEvent::Error {
+                message: "unknown event type".into(),
+                event_type: Some("client:shout".into()),
+            },
+        ];
+        let types: std::collections::HashSet<_> =
+            events.iter().map(ServerEvent::event_type).collect();
+        assert_eq!(
+            types.len(),
+            SERVER_EVENT_TYPES.len(),
+            "every event type is covered"
+        );
+        for event in &events {
+            let json = round_trip(event);
+            assert_eq!(json["type"], event.event_type());
+        }
+    }
+
+    #[test]
+    fn test_client_events_round_trip() {
+        let events = [
+            (
+                ClientEvent::Hello {
+                    protocol_version: 1,
+                    capabilities: vec!["session:new_message".into()],
+                    client_id: Some("tab-1".into()),
+                },
+                "client:hello",
+            ),
+            (
+                ClientEvent::Subscribe {
+                    session_id: "s1".into(),
+                },
+                "subscribe:session",
+            ),
+            (
+                ClientEvent::Unsubscribe {
+                    session_id: "s1".into(),
+                },
+                "unsubscribe:session",
+            ),
+            (
+                ClientEvent::SubscribeProject {
+                    project_path: "/work/app".into(),
+                },
+                "subscribe:project",
+            ),
+            (
+                ClientEvent::UnsubscribeProject {
+                    project_path: "/work/app".into(),
+                },
+                "unsubscribe:project",
+            ),
+            (
+                ClientEvent::AckRead {
+                    session_id: "s1".into(),
+                    message_id: "m1".into(),
+                },
+                "ack:read",
+            ),
+            (ClientEvent::Unknown, "unknown"),
+        ];
+        for (event, event_type) in &events {
+            assert_eq!(round_trip(event)["type"], *event_type);
+        }
+    }
+
+    #[test]
+    fn test_unknown_client_event_type() {
+        let event: ClientEvent =
+            serde_json::from_str(r#"{"type":"client:shout","volume":11}"#).unwrap();
+        assert!(matches!(event, ClientEvent::Unknown));
+        // A known type with the wrong fields is still an error.
+        assert!(serde_json::from_str::<ClientEvent>(r#"{"type":"subscribe:session"}"#).is_err());
+    }
+
+    #[test]
+    fn test_agent_message_type_field_name() {
+        let msg = AgentMessage {
+            id: "1".into(),
+            session_id: "s1".into(),
+            timestamp: "2025-01-01T00:00:00Z".into(),
+            role: MessageRole::Assistant,
+            msg_type: MessageType::ToolUse,
+            content: "Read".into(),
+            metadata: None,
+            parent_id: None,
+            tool_use_message_id: None,
+        };
+        let json = serde_json::to_value(&msg).unwrap();
+        // Must use "type" not "msgType"
+        assert_eq!(json["type"], "tool_use");
+        assert!(json.get("msgType").is_none());
+    }
+
+    #[test]
+    fn test_session_prefs_patch_partial() {
+        let patch: SessionPrefsPatch = serde_json::from_str(r#"{"pinned":true}"#).unwrap();
+        assert_eq!(patch.pinned, Some(true));
+        assert!(patch.muted.is_none());
+        assert!(patch.archived.is_none());
+    }
+
+    #[test]
+    fn test_session_removed_event() {
+        let event = ServerEvent::SessionRemoved {
+            session_id: "s1".into(),
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "session:removed");
+        assert_eq!(json["sessionId"], "s1");
+    }
+
+    #[test]
+    fn test_session_updated_event() {
+        let event = ServerEvent::SessionUpdated {
+            session: AgentSessionSummary {
+                session_id: "s1".into(),
+                note: Some("flaky tests".into()),
+                labels: vec!["ci".into()],
+                ..Default::default()
+            },
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "session:updated");
+        assert_eq!(json["session"]["note"], "flaky tests");
+        assert_eq!(json["session"]["labels"][0], "ci");
+    }
+
+    #[test]
+    fn test_usage_updated_event() {
+        let event = ServerEvent::UsageUpdated {
+            session_id: "s1".into(),
+            usage: CumulativeUsage {
+                input_tokens: 100,
+                output_tokens: 200,
+                cache_read_tokens: 50,
+                cache_creation_tokens: 25,
+                estimated_cost: 0.01,
+                ..Default::default()
+            },
+            spend_rate_per_hour: 0.45,
+            tokens_per_minute: 1200.0,
+            context_tokens: 144_000,
+            context_limit: Some(200_000),
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "session:usage_updated");
+        assert_eq!(json["sessionId"], "s1");
+        assert_eq!(json["usage"]["inputTokens"], 100);
+        assert_eq!(json["spendRatePerHour"], 0.45);
+        assert_eq!(json["contextTokens"], 144_000);
+        assert_eq!(json["contextLimit"], 200_000);
+    }
+
+    #[test]
+    fn test_messages_appended_event() {
+        let event = ServerEvent::MessagesAppended {
+            session_id: "s1".into(),
+            count: 120,
+            latest_timestamp: "2025-01-01T00:00:00Z".into(),
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "session:messages_appended");
+        assert_eq!(json["count"], 120);
+        assert_eq!(json["latestTimestamp"], "2025-01-01T00:00:00Z");
+    }
+}
@@ -0,0 +1,298 @@
+//! Tags sessions with the kind of work they are doing ("coding", "research",
+//! "debugging", ...) from their tool mix and recent prompts. The rules are plain
+//! thresholds, checked in order; the first one that matches names the activity.
+
+use crate::types::ToolStats;
+use serde::{Deserialize, Serialize};
+
+/// Tools that change files.
+pub const EDIT_TOOLS: &[&str] = &["Edit", "MultiEdit", "Write", "NotebookEdit"];
+/// Tools that only look things up.
+const READ_TOOLS: &[&str] = &["Read", "Grep", "Glob", "LS", "WebFetch", "WebSearch"];
+/// How many of the latest prompts are searched for keywords.
+pub const RECENT_PROMPTS: usize = 20;
+
+/// One row of the rules table. Every condition must hold; unset ones always do.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ActivityRule {
+    /// The tag for sessions this rule matches, e.g. `debugging`.
+    pub activity: String,
+    /// Tool calls made so far.
+    pub min_calls: u64,
+    /// Calls to Edit, MultiEdit, Write and NotebookEdit.
+    pub min_edits: u64,
+    pub max_edits: Option<u64>,
+    /// Share of calls that edit files, from 0 to 1.
+    pub min_edit_share: f64,
+    /// Share of calls that only read: Read, Grep, Glob, LS, WebFetch and WebSearch.
+    pub min_read_share: f64,
+    /// Bash calls whose result was an error, such as a failing test run.
+    pub min_failed_commands: u64,
+    /// At least one of these starts a word in a recent prompt, ignoring case.
+    pub keywords: Vec<String>,
+}
+
+impl ActivityRule {
+    fn matches(&self, mix: &ToolMix, prompts: &[String]) -> bool {
+        mix.calls >= self.min_calls
+            && mix.edits >= self.min_edits
+            && self.max_edits.is_none_or(|max| mix.edits <= max)
+            && mix.share(mix.edits) >= self.min_edit_share
+            && mix.share(mix.reads) >= self.min_read_share
+            && mix.failed_commands >= self.min_failed_commands
+            && (self.keywords.is_empty()
+                || self.keywords.iter().any(|keyword| {
+                    let keyword = keyword.to_lowercase();
+                    prompts.iter().any(|p| starts_word(p, &keyword))
+                }))
+    }
+}
+
+/// Used when the config has no `[[analysis.rules]]`.
+pub fn default_rules() -> Vec<ActivityRule> {
+    let words = |words: &[&str]| words.iter().map(|w| w.to_string()).collect();
+    vec![
+        // Commands kept failing and files were changed in between.
+        ActivityRule {
+            activity: "debugging".to_string(),
+            min_edits: 1,
+            min_failed_commands: 2,
+            ..Default::default()
+        },
+        ActivityRule {
+            activity: "refactor".to_string(),
+            min_edits: 1,
+            keywords: words(&[
+                "refactor",
+                "rename",
+                "restructure",
+                "reorganize",
+                "clean up",
+                "cleanup",
+                "simplify",
+                "extract",
+            ]),
+            ..Default::default()
+        },
+        ActivityRule {
+            activity: "debugging".to_string(),
+            min_calls: 3,
+            keywords: words(&[
+                "bug", "fix", "debug", "error", "failing", "fails", "crash", "broken",
+            ]),
+            ..Default::default()
+        },
+        // Looking around without touching anything.
+        ActivityRule {
+            activity: "research".to_string(),
+            min_calls: 5,
+            max_edits: Some(0),
+            min_read_share: 0.5,
+            ..Default::default()
+        },
+        ActivityRule {
+            activity: "coding".to_string(),
+            min_calls: 5,
+            min_edit_share: 0.2,
+            ..Default::default()
+        },
+    ]
+}
+
+/// Counts of calls by kind, the part of a session's tool stats the rules look at.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ToolMix {
+    pub calls: u64,
+    pub edits: u64,
+    pub reads: u64,
+    pub failed_commands: u64,
+}
+
+impl ToolMix {
+    pub fn from_stats(tools: &[ToolStats]) -> Self {
+        let mut mix = Self::default();
+        for tool in tools {
+            mix.calls += tool.count;
+            if EDIT_TOOLS.contains(&tool.name.as_str()) {
+                mix.edits += tool.count;
+            }
+            if READ_TOOLS.contains(&tool.name.as_str()) {
+                mix.reads += tool.count;
+            }
+            if tool.name == "Bash" {
+                mix.failed_commands += tool.error_count;
+            }
+        }
+        mix
+    }
+
+    fn share(&self, count: u64) -> f64 {
+        if self.calls == 0 {
+            0.0
+        } else {
+            count as f64 / self.calls as f64
+        }
+    }
+}
+
+/// The activity of the first rule that matches, if any.
+pub fn classify(rules: &[ActivityRule], tools: &[ToolStats], prompts: &[&str]) -> Option<String> {
+    let mix = ToolMix::from_stats(tools);
+    let prompts: Vec<String> = prompts.iter().map(|p| p.to_lowercase()).collect();
+    rules
+        .iter()
+        .find(|rule| rule.matches(&mix, &prompts))
+        .map(|rule| rule.activity.clone())
+}
+
+/// Whether `needle` occurs in `haystack` at the start of a word, so `fix` matches
+/// "fixing" but not "prefix".
+fn starts_word(haystack: &str, needle: &str) -> bool {
+    !needle.is_empty()
+        && haystack.match_indices(needle).any(|(i, _)| {
+            !haystack[..i]
+                .chars()
+                .next_back()
+                .is_some_and(char::is_alphanumeric)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tools(counts: &[(&str, u64, u64)]) -> Vec<ToolStats> {
+        counts
+            .iter()
+            .map(|&(name, count, error_count)| ToolStats {
+                name: name.to_string(),
+                count,
+                error_count,
+                output_bytes: 0,
+            })
+            .collect()
+    }
+
+    fn classify_default(counts: &[(&str, u64, u64)], prompts: &[&str]) -> Option<String> {
+        classify(&default_rules(), &tools(counts), prompts)
+    }
+
+    #[test]
+    fn test_tool_mix() {
+        let mix = ToolMix::from_stats(&tools(&[
+            ("Read", 6, 0),
+            ("Edit", 3, 1),
+            ("Write", 1, 0),
+            ("Bash", 4, 2),
+            ("WebFetch", 2, 0),
+        ]));
+        assert_eq!(
+            mix,
+            ToolMix {
+                calls: 16,
+                edits: 4,
+                reads: 8,
+                failed_commands: 2,
+            }
+        );
+        assert_eq!(mix.share(mix.reads), 0.5);
+        assert_eq!(ToolMix::default().share(0), 0.0);
+    }
+
+    #[test]
+    fn test_default_rules() {
+        let prompts = ["Add a CSV export to the reports page"];
+        assert_eq!(
+            classify_default(
+                &[("Read", 12, 0), ("Grep", 4, 0), ("WebFetch", 3, 0)],
+                &prompts
+            )
+            .as_deref(),
+            Some("research")
+        );
+        assert_eq!(
+            classify_default(&[("Read", 5, 0), ("Edit", 6, 0), ("Write", 2, 0)], &prompts)
+                .as_deref(),
+            Some("coding")
+        );
+        // Failing test runs with edits in between.
+        assert_eq!(
+            classify_default(&[("Bash", 6, 3), ("Edit", 2, 0), ("Read", 4, 0)], &prompts)
+                .as_deref(),
+            Some("debugging")
+        );
+        assert_eq!(
+            classify_default(
+                &[("Read", 3, 0), ("Edit", 5, 0)],
+                &["Rename UserId to AccountId everywhere"]
+            )
+            .as_deref(),
+            Some("refactor")
+        );
+        assert_eq!(
+            classify_default(&[("Read", 3, 0)], &["The login page CRASHES on submit"]).as_deref(),
+            Some("debugging")
+        );
+        // Too little to go on yet.
+        assert_eq!(classify_default(&[("Read", 2, 0)], &prompts), None);
+        assert_eq!(classify_default(&[], &[]), None);
+    }
+
+    #[test]
+    fn test_reclassified_as_session_evolves() {
+        let prompts = ["Add pagination to the API"];
+        let mut counts = vec![("Read", 6, 0), ("Grep", 2, 0)];
+        assert_eq!(
+            classify_default(&counts, &prompts).as_deref(),
+            Some("research")
+        );
+        counts.push(("Edit", 4, 0));
+        assert_eq!(
+            classify_default(&counts, &prompts).as_deref(),
+            Some("coding")
+        );
+        counts.push(("Bash", 3, 2));
+        assert_eq!(
+            classify_default(&counts, &prompts).as_deref(),
+            Some("debugging")
+        );
+    }
+
+    #[test]
+    fn test_custom_rules_in_order() {
+        let rules = vec![
+            ActivityRule {
+                activity: "docs".to_string(),
+                min_edits: 1,
+                keywords: vec!["README".to_string(), "docs".to_string()],
+                ..Default::default()
+            },
+            ActivityRule {
+                activity: "other".to_string(),
+                ..Default::default()
+            },
+        ];
+        let edits = tools(&[("Edit", 1, 0)]);
+        assert_eq!(
+            classify(&rules, &edits, &["update the readme"]).as_deref(),
+            Some("docs")
+        );
+        // Keywords start words: "docs" isn't in "undocsumented".
+        assert_eq!(
+            classify(&rules, &edits, &["undocsumented"]).as_deref(),
+            Some("other")
+        );
+        assert_eq!(classify(&[], &edits, &["docs"]), None);
+    }
+
+    #[test]
+    fn test_starts_word() {
+        assert!(starts_word("fixing the build", "fix"));
+        assert!(starts_word("please (fix) it", "fix"));
+        assert!(starts_word("a prefix, then fix", "fix"));
+        assert!(!starts_word("prefix", "fix"));
+        assert!(starts_word("time to clean up", "clean up"));
+        assert!(!starts_word("anything", ""));
+    }
+}
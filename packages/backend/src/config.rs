@@ -0,0 +1,2140 @@
+//! Server settings: `config.toml`, overridden by the environment variables that
+//! came before it. Every key is optional; the defaults are what the server does
+//! with no configuration at all.
+
+use crate::analysis::{self, ActivityRule};
+use crate::cost::{ContextLimits, ModelContextLimit, ModelPricing, Pricing, DEFAULT_FALLBACK_MODEL};
+use crate::days;
+use crate::notifications::NotifyRules;
+use crate::providers::claude_code::git_diff::DEFAULT_DIFF_MAX_BYTES;
+use crate::providers::claude_code::git_status::GitBackend;
+use crate::providers::claude_code::process_probe::{claude_process_cwds, InterruptSignal};
+use crate::providers::claude_code::project_settings::ProjectSettings;
+use crate::providers::claude_code::redact::Redactor;
+use crate::providers::claude_code::session_discovery::DiscoveryConfig;
+use crate::providers::claude_code::state_machine::StateTimeouts;
+use crate::providers::claude_code::text_util::TextLimits;
+use crate::providers::claude_code::{ProviderConfig, SessionSettings, DEFAULT_HOST};
+use crate::server::coalesce;
+use crate::server::limits::{self, ApiLimits};
+use crate::server::open;
+use crate::server::raw_log;
+use crate::server::ws;
+use crate::server::relay::RelayConfig;
+use crate::session::archive::{self, ArchiveConfig};
+use crate::session::diff_snapshots::{self, DiffSnapshotConfig};
+use crate::session::journal::{self, JournalConfig};
+use crate::session::remote::{is_valid_host, sanitize_host};
+use crate::session::summarizer::{self, SummaryConfig};
+use crate::types::{AgentStateType, BudgetPeriod};
+use arc_swap::ArcSwap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Names the config file when `--config` isn't given.
+pub const CONFIG_PATH_VAR: &str = "AGENTS_DASHBOARD_CONFIG";
+
+/// Shown in place of secrets by `GET /api/config`.
+const REDACTED: &str = "[redacted]";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub providers: ProvidersConfig,
+    pub state_machine: StateMachineConfig,
+    pub cost: CostConfig,
+    pub notifications: NotificationsConfig,
+    pub analysis: AnalysisConfig,
+    pub summaries: SummariesConfig,
+    /// Settings for the sessions of a project, keyed by its directory; see
+    /// [`ProjectSection`].
+    pub projects: BTreeMap<String, ProjectSection>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// `PORT`
+    pub port: u16,
+    /// Base URL for links in notifications. `DASHBOARD_URL`; defaults to localhost.
+    pub dashboard_url: Option<String>,
+    /// Dashboard-owned state. `DATA_DIR`; defaults to the platform data directory.
+    pub data_dir: Option<PathBuf>,
+    /// IANA zone (e.g. `Asia/Tokyo`) that days are counted in for daily usage, the
+    /// stats' "today", reports and CSV exports. `DASHBOARD_TIMEZONE`; defaults to the
+    /// system's zone, else UTC. A change only affects days counted from then on.
+    pub timezone: Option<String>,
+    /// Requests per minute per client to search and exports, 0 for no limit.
+    /// `API_RATE_LIMIT`
+    pub api_rate_limit: u32,
+    /// `SEARCH_MAX_QUERY_LEN`
+    pub search_max_query_len: usize,
+    /// `SEARCH_CONCURRENCY`
+    pub search_concurrency: usize,
+    /// Most bytes of a log returned by one `GET /api/sessions/{id}/raw`.
+    /// `RAW_LOG_MAX_BYTES`
+    pub raw_log_max_bytes: u64,
+    /// Largest patch `GET /api/sessions/{id}/diff` returns. `DIFF_MAX_BYTES`
+    pub diff_max_bytes: u64,
+    pub diff_snapshots: DiffSnapshotsSection,
+    pub archive: ArchiveSection,
+    pub journal: JournalSection,
+    /// Names this machine on its sessions and in `/api/health`. `INSTANCE_NAME`;
+    /// defaults to the host name.
+    pub instance_name: Option<String>,
+    /// Bearer token other instances must send to relay sessions to `/ingest`;
+    /// unset turns the route off. `INGEST_TOKEN`
+    pub ingest_token: Option<String>,
+    pub relay: RelaySection,
+    /// Run by `POST /api/sessions/{id}/open`, with `{path}` replaced by the session's
+    /// working directory, e.g. `code {path}`. `OPEN_COMMAND`
+    pub open_command: Option<String>,
+    /// Lets the dashboard run `open_command`, for requests from this machine only.
+    /// `ALLOW_OPEN_COMMANDS`
+    pub allow_open_commands: bool,
+    /// Lets the dashboard signal sessions' Claude processes. `ALLOW_SESSION_CONTROL`
+    pub allow_session_control: bool,
+    /// Sent by `POST /api/sessions/{id}/interrupt`. `INTERRUPT_SIGNAL`
+    pub interrupt_signal: InterruptSignal,
+    /// `POST`, `PUT`, `PATCH` and `DELETE` endpoints only act when sent
+    /// `X-Confirm: yes`. `REQUIRE_CONFIRM`
+    pub require_confirm: bool,
+    /// Usage and git status updates are sent at most once per session per this many
+    /// milliseconds, with the latest values; 0 sends every one. `EVENT_FLUSH_MS`
+    pub event_flush_ms: u64,
+    /// Session events held for WebSocket clients that are behind. `BROADCAST_CAPACITY`
+    pub broadcast_capacity: usize,
+    /// Messages held for WebSocket clients that are behind; a client that falls
+    /// further back is sent its subscribed sessions again. `MESSAGE_CAPACITY`
+    pub message_capacity: usize,
+    /// Serves `/status/api/...`: states, models and token counts of sessions without
+    /// their content, for embedding elsewhere. `PUBLIC_STATUS`
+    pub public_status: bool,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            port: 3001,
+            dashboard_url: None,
+            data_dir: None,
+            timezone: None,
+            api_rate_limit: limits::DEFAULT_RATE_PER_MINUTE,
+            search_max_query_len: limits::DEFAULT_MAX_QUERY_LEN,
+            search_concurrency: limits::DEFAULT_SEARCH_CONCURRENCY,
+            raw_log_max_bytes: raw_log::DEFAULT_MAX_CHUNK_BYTES,
+            diff_max_bytes: DEFAULT_DIFF_MAX_BYTES,
+            diff_snapshots: DiffSnapshotsSection::default(),
+            archive: ArchiveSection::default(),
+            journal: JournalSection::default(),
+            instance_name: None,
+            ingest_token: None,
+            relay: RelaySection::default(),
+            open_command: None,
+            allow_open_commands: false,
+            allow_session_control: false,
+            interrupt_signal: InterruptSignal::default(),
+            require_confirm: true,
+            event_flush_ms: coalesce::DEFAULT_FLUSH_INTERVAL_MS,
+            broadcast_capacity: ws::DEFAULT_BROADCAST_CAPACITY,
+            message_capacity: ws::DEFAULT_MESSAGE_CAPACITY,
+            public_status: false,
+        }
+    }
+}
+
+/// Diffs saved to the data directory when a session changes state.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiffSnapshotsSection {
+    /// `DIFF_SNAPSHOTS`
+    pub enabled: bool,
+    /// States whose start triggers a snapshot. `DIFF_SNAPSHOT_STATES`, comma-separated.
+    pub states: Vec<AgentStateType>,
+    /// Snapshots kept across all sessions. `DIFF_SNAPSHOT_MAX_COUNT`
+    pub max_count: usize,
+    /// Bytes of (compressed) snapshots kept. `DIFF_SNAPSHOT_MAX_BYTES`
+    pub max_total_bytes: u64,
+}
+
+impl Default for DiffSnapshotsSection {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            states: vec![AgentStateType::Idle],
+            max_count: diff_snapshots::DEFAULT_MAX_COUNT,
+            max_total_bytes: diff_snapshots::DEFAULT_MAX_TOTAL_BYTES,
+        }
+    }
+}
+
+/// Stopped sessions moved out of memory into compressed files in the data directory.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ArchiveSection {
+    /// `ARCHIVE`
+    pub enabled: bool,
+    /// Hours a session stays stopped before it's archived. `ARCHIVE_AFTER_HOURS`
+    pub after_hours: u64,
+}
+
+impl Default for ArchiveSection {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            after_hours: archive::DEFAULT_AFTER_HOURS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JournalSection {
+    /// Setting a path turns the journal on. `EVENT_JOURNAL_PATH`
+    pub path: Option<PathBuf>,
+    /// `EVENT_JOURNAL_MAX_BYTES`
+    pub max_bytes: u64,
+    /// `EVENT_JOURNAL_KEEP`
+    pub keep_files: usize,
+}
+
+impl Default for JournalSection {
+    fn default() -> Self {
+        Self {
+            path: None,
+            max_bytes: journal::DEFAULT_MAX_BYTES,
+            keep_files: journal::DEFAULT_KEEP_FILES,
+        }
+    }
+}
+
+/// Forwarding this instance's sessions to a central dashboard.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RelaySection {
+    /// The central instance's ingest URL, e.g. `ws://central:3001/ingest`. Setting
+    /// it turns relaying on. `RELAY_URL`
+    pub url: Option<String>,
+    /// Sent as a bearer token. `RELAY_TOKEN`
+    pub token: Option<String>,
+    /// Shown on the central dashboard and prefixed to session ids. `RELAY_HOST`;
+    /// defaults to the instance name.
+    pub host: Option<String>,
+    /// Leave out prompts, replies, messages, paths and branches; off sends the
+    /// sessions whole. `RELAY_ANONYMIZE`
+    pub anonymize: bool,
+}
+
+impl Default for RelaySection {
+    fn default() -> Self {
+        Self {
+            url: None,
+            token: None,
+            host: None,
+            anonymize: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProvidersConfig {
+    pub claude_code: ClaudeCodeConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClaudeCodeConfig {
+    /// Defaults to `~/.claude/projects`.
+    pub projects_dir: Option<PathBuf>,
+    pub scan_interval_secs: u64,
+    /// Logs not written to for this long are not picked up.
+    pub max_log_age_hours: u64,
+    pub poll_interval_secs: u64,
+    pub timer_interval_secs: u64,
+    /// `PROCESS_DETECTION`
+    pub process_detection: bool,
+    /// `GIT_STATUS_BACKEND`
+    pub git_status_backend: GitBackend,
+    /// `SUPERSEDE_GRACE_SECS`
+    pub supersede_grace_secs: u64,
+    /// Stop a project's older sessions as soon as a new one appears.
+    /// `SINGLE_SESSION_PER_PROJECT`
+    pub single_session_per_project: bool,
+    /// Mask API keys, tokens and private keys in message content. `REDACT_SECRETS`
+    pub redact_secrets: bool,
+    /// Regular expressions masked as well as the built-in ones. A `secret` group,
+    /// if present, limits the mask to that part of the match.
+    pub redact_patterns: Vec<String>,
+    /// Percentages of the context window at which a warning is sent, once each until
+    /// the context is compacted. `CONTEXT_WARNING_PERCENTS`
+    pub context_warning_percents: Vec<u32>,
+    /// Stop watching a stopped session's log and drop its messages from memory after
+    /// this long. `DEHYDRATE_AFTER_SECS`
+    pub dehydrate_after_secs: u64,
+    /// Messages held in memory across all sessions are kept under about this much,
+    /// shedding from the largest stopped and idle sessions first. 0 turns the cap
+    /// off. `MAX_TRANSCRIPT_MEMORY_MB`
+    pub max_transcript_memory_mb: u64,
+    /// Log lines longer than this are skipped rather than read into memory.
+    /// `MAX_LINE_BYTES`
+    pub max_line_bytes: u64,
+    /// Between git checks of an idle or permission-waiting session.
+    pub git_check_interval_secs: u64,
+    /// Between git checks of a running session.
+    pub git_check_interval_running_secs: u64,
+    /// Messages each session keeps in memory; older ones are only searchable.
+    pub max_messages: usize,
+    pub text_limits: TextLimitsSection,
+}
+
+impl Default for ClaudeCodeConfig {
+    fn default() -> Self {
+        let defaults = ProviderConfig::default();
+        let session = SessionSettings::default();
+        Self {
+            projects_dir: None,
+            scan_interval_secs: defaults.discovery.scan_interval.as_secs(),
+            max_log_age_hours: defaults.discovery.max_log_age.as_secs() / 3600,
+            poll_interval_secs: defaults.poll_interval.as_secs(),
+            timer_interval_secs: defaults.timer_interval.as_secs(),
+            process_detection: true,
+            git_status_backend: session.git_backend,
+            supersede_grace_secs: session.supersede_grace.as_secs(),
+            single_session_per_project: false,
+            redact_secrets: true,
+            redact_patterns: Vec::new(),
+            context_warning_percents: session.context_warning_percents,
+            dehydrate_after_secs: session.dehydrate_after.as_secs(),
+            max_transcript_memory_mb: session.transcript_memory_budget / (1024 * 1024),
+            max_line_bytes: defaults.max_line_bytes as u64,
+            git_check_interval_secs: session.git_check_interval.as_secs(),
+            git_check_interval_running_secs: session.git_check_interval_running.as_secs(),
+            max_messages: session.max_messages,
+            text_limits: TextLimitsSection::default(),
+        }
+    }
+}
+
+/// How much text is kept, counted in what a reader sees as characters rather
+/// than bytes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TextLimitsSection {
+    /// Prompts and hook messages as stored; search still finds the rest.
+    pub message: usize,
+    pub tool_result: usize,
+    /// A session's initial and latest task, and its latest reply preview.
+    pub task: usize,
+    /// Search matches whose hit is only in the truncated-away text.
+    pub search_preview: usize,
+    /// Shown on each side of a search hit.
+    pub snippet_context: usize,
+}
+
+impl Default for TextLimitsSection {
+    fn default() -> Self {
+        let defaults = TextLimits::default();
+        Self {
+            message: defaults.message,
+            tool_result: defaults.tool_result,
+            task: defaults.task,
+            search_preview: defaults.preview,
+            snippet_context: defaults.snippet_context,
+        }
+    }
+}
+
+impl TextLimitsSection {
+    fn limits(&self) -> TextLimits {
+        TextLimits {
+            message: self.message,
+            tool_result: self.tool_result,
+            task: self.task,
+            preview: self.search_preview,
+            snippet_context: self.snippet_context,
+        }
+    }
+}
+
+/// Silence, in seconds, before each time-based state change.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StateMachineConfig {
+    pub permission_wait_secs: u64,
+    pub idle_secs: u64,
+    pub running_stopped_secs: u64,
+    pub idle_stopped_secs: u64,
+    pub process_gone_secs: u64,
+}
+
+impl Default for StateMachineConfig {
+    fn default() -> Self {
+        let defaults = StateTimeouts::default();
+        let secs = |ms: i64| ms as u64 / 1000;
+        Self {
+            permission_wait_secs: secs(defaults.permission_wait_ms),
+            idle_secs: secs(defaults.idle_ms),
+            running_stopped_secs: secs(defaults.running_stopped_ms),
+            idle_stopped_secs: secs(defaults.idle_stopped_ms),
+            process_gone_secs: secs(defaults.process_gone_ms),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CostConfig {
+    /// Unknown models are priced like the model with this prefix.
+    pub fallback_model: String,
+    /// Prices matched before the built-in ones, e.g. for a newly released model.
+    pub models: Vec<ModelPricing>,
+    /// Context windows matched before the built-in ones.
+    pub context_limits: Vec<ModelContextLimit>,
+    /// Spending limits per project; going over one sends `project:budget_exceeded`.
+    pub budgets: Vec<ProjectBudget>,
+}
+
+impl Default for CostConfig {
+    fn default() -> Self {
+        Self {
+            fallback_model: DEFAULT_FALLBACK_MODEL.to_string(),
+            models: Vec::new(),
+            context_limits: Vec::new(),
+            budgets: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectBudget {
+    /// The project directory, as in `projectPath` of `GET /api/projects`.
+    pub project: String,
+    pub limit_usd: f64,
+    #[serde(default)]
+    pub period: BudgetPeriod,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotificationsConfig {
+    pub ntfy: NtfySection,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NtfySection {
+    /// Setting a topic turns ntfy on. `NTFY_TOPIC`
+    pub topic: Option<String>,
+    /// `NTFY_SERVER`
+    pub server: String,
+    /// `NTFY_TOKEN`
+    pub token: Option<String>,
+    /// States that trigger a notification. `NTFY_STATES`, comma-separated.
+    pub states: Vec<AgentStateType>,
+    /// Notify when a session's context nears its limit. `NTFY_CONTEXT_WARNINGS`
+    pub context_warnings: bool,
+}
+
+impl Default for NtfySection {
+    fn default() -> Self {
+        let rules = NotifyRules::default();
+        Self {
+            topic: None,
+            server: "https://ntfy.sh".to_string(),
+            token: None,
+            states: rules.states,
+            context_warnings: rules.context_warnings,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SummariesConfig {
+    /// Anthropic API key; setting one turns summaries on. `SUMMARY_API_KEY`
+    pub api_key: Option<String>,
+    /// `SUMMARY_MODEL`
+    pub model: String,
+    /// `SUMMARY_API_URL`
+    pub api_url: String,
+    /// Summarize sessions when they stop, not only on request. `SUMMARY_ON_STOP`
+    pub on_stop: bool,
+}
+
+impl Default for SummariesConfig {
+    fn default() -> Self {
+        Self {
+            api_key: None,
+            model: summarizer::DEFAULT_MODEL.to_string(),
+            api_url: summarizer::DEFAULT_API_URL.to_string(),
+            on_stop: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AnalysisConfig {
+    /// Rules tagging a session's activity, first match wins. Setting any replaces
+    /// the built-in ones; an empty list turns tagging off.
+    pub rules: Vec<ActivityRule>,
+}
+
+impl Default for AnalysisConfig {
+    fn default() -> Self {
+        Self {
+            rules: analysis::default_rules(),
+        }
+    }
+}
+
+/// Settings for sessions discovered in one project directory or below it, e.g.
+/// `[projects."/Users/me/infra"]`. The section with the longest matching directory
+/// applies; keys it leaves out keep their global value.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectSection {
+    pub state_machine: ProjectStateMachine,
+    pub notifications: ProjectNotifications,
+    pub git_check_interval_secs: Option<u64>,
+    pub git_check_interval_running_secs: Option<u64>,
+    pub max_messages: Option<usize>,
+    pub dehydrate_after_secs: Option<u64>,
+}
+
+/// `[state_machine]` keys a project can set.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectStateMachine {
+    pub permission_wait_secs: Option<u64>,
+    pub idle_secs: Option<u64>,
+    pub running_stopped_secs: Option<u64>,
+    pub idle_stopped_secs: Option<u64>,
+    pub process_gone_secs: Option<u64>,
+}
+
+/// `[notifications.ntfy]` rules a project can set. Where notifications go stays global.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectNotifications {
+    pub states: Option<Vec<AgentStateType>>,
+    pub context_warnings: Option<bool>,
+}
+
+impl ProjectSection {
+    /// `global` with this section's keys in place, for the project at `path`.
+    fn apply(&self, path: &str, global: &SessionSettings) -> SessionSettings {
+        let ms = |secs: Option<u64>, global: i64| secs.map_or(global, |secs| secs as i64 * 1000);
+        let (timeouts, global_timeouts) = (&self.state_machine, &global.timeouts);
+        let mut settings = global.clone();
+        settings.timeouts = StateTimeouts {
+            permission_wait_ms: ms(
+                timeouts.permission_wait_secs,
+                global_timeouts.permission_wait_ms,
+            ),
+            idle_ms: ms(timeouts.idle_secs, global_timeouts.idle_ms),
+            running_stopped_ms: ms(
+                timeouts.running_stopped_secs,
+                global_timeouts.running_stopped_ms,
+            ),
+            idle_stopped_ms: ms(timeouts.idle_stopped_secs, global_timeouts.idle_stopped_ms),
+            process_gone_ms: ms(timeouts.process_gone_secs, global_timeouts.process_gone_ms),
+        };
+        if let Some(states) = &self.notifications.states {
+            settings.notify.states = states.clone();
+        }
+        if let Some(context_warnings) = self.notifications.context_warnings {
+            settings.notify.context_warnings = context_warnings;
+        }
+        if let Some(secs) = self.git_check_interval_secs {
+            settings.git_check_interval = Duration::from_secs(secs);
+        }
+        if let Some(secs) = self.git_check_interval_running_secs {
+            settings.git_check_interval_running = Duration::from_secs(secs);
+        }
+        if let Some(max) = self.max_messages {
+            settings.max_messages = max;
+        }
+        if let Some(secs) = self.dehydrate_after_secs {
+            settings.dehydrate_after = Duration::from_secs(secs);
+        }
+        settings.project = Some(path.to_string());
+        settings
+    }
+}
+
+/// Keys (or whole sections) that take effect on reload. Everything else is read
+/// once at startup.
+const LIVE_KEYS: &[&str] = &[
+    "server.dashboard_url",
+    "server.timezone",
+    "server.api_rate_limit",
+    "server.search_max_query_len",
+    "server.search_concurrency",
+    "server.raw_log_max_bytes",
+    "server.diff_max_bytes",
+    "server.ingest_token",
+    "server.open_command",
+    "server.allow_open_commands",
+    "server.allow_session_control",
+    "server.public_status",
+    "server.interrupt_signal",
+    "server.require_confirm",
+    "providers.claude_code.git_status_backend",
+    "providers.claude_code.supersede_grace_secs",
+    "providers.claude_code.single_session_per_project",
+    "providers.claude_code.redact_secrets",
+    "providers.claude_code.redact_patterns",
+    "providers.claude_code.context_warning_percents",
+    "providers.claude_code.dehydrate_after_secs",
+    "providers.claude_code.max_transcript_memory_mb",
+    "providers.claude_code.git_check_interval_secs",
+    "providers.claude_code.git_check_interval_running_secs",
+    "providers.claude_code.max_messages",
+    "providers.claude_code.text_limits",
+    "state_machine",
+    "cost",
+    "notifications",
+    "analysis",
+    "projects",
+];
+
+/// Whether a change to `key` (as returned by [`Config::changed_keys`]) can be
+/// applied without a restart.
+pub fn applies_live(key: &str) -> bool {
+    LIVE_KEYS.iter().any(|live| {
+        key.strip_prefix(live)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+    })
+}
+
+/// Where the config file is looked for, kept so it can be read again on reload.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigSource {
+    pub path: Option<PathBuf>,
+    /// The path was given explicitly, so it must exist.
+    required: bool,
+}
+
+impl ConfigSource {
+    /// `--config <path>` in `args`, or `AGENTS_DASHBOARD_CONFIG`, or
+    /// `$XDG_CONFIG_HOME/agents-dashboard/config.toml` if it exists.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self, ConfigError> {
+        let source = match config_arg(args)? {
+            Some(path) => Self::file(path),
+            None => match env_var(CONFIG_PATH_VAR) {
+                Some(path) => Self::file(PathBuf::from(path)),
+                None => Self {
+                    path: default_path(),
+                    required: false,
+                },
+            },
+        };
+        Ok(source)
+    }
+
+    /// A file that must exist.
+    pub fn file(path: PathBuf) -> Self {
+        Self {
+            path: Some(path),
+            required: true,
+        }
+    }
+
+    /// Read the file, apply environment overrides and validate.
+    pub fn load(&self) -> Result<Config, ConfigError> {
+        let mut config = Config::default();
+        if let Some(path) = &self.path {
+            match std::fs::read_to_string(path) {
+                Ok(text) => {
+                    config = Config::parse(&text).map_err(|mut e| {
+                        if e.key.is_empty() {
+                            e.key = path.display().to_string();
+                        }
+                        e
+                    })?;
+                }
+                Err(e) if self.required || e.kind() != std::io::ErrorKind::NotFound => {
+                    return Err(ConfigError::new(
+                        &path.display().to_string(),
+                        format!("could not read config file: {}", e),
+                    ));
+                }
+                Err(_) => {}
+            }
+        }
+        config.apply_env(env_var)?;
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|v| !v.trim().is_empty())
+}
+
+/// A configuration problem, naming the key (or environment variable) at fault.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError {
+    pub key: String,
+    pub message: String,
+}
+
+impl ConfigError {
+    fn new(key: &str, message: impl Into<String>) -> Self {
+        Self {
+            key: key.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.key, self.message)
+    }
+}
+
+impl Config {
+    /// Parse a config file. Errors name the offending key where there is one.
+    pub fn parse(text: &str) -> Result<Self, ConfigError> {
+        let deserializer = toml::Deserializer::new(text);
+        serde_path_to_error::deserialize(deserializer).map_err(|e| {
+            let key = e.path().to_string();
+            let key = if key == "." { String::new() } else { key };
+            ConfigError::new(&key, e.into_inner().message().to_string())
+        })
+    }
+
+    /// Let environment variables override file values. Unset or blank ones are ignored.
+    pub fn apply_env(&mut self, get: impl Fn(&str) -> Option<String>) -> Result<(), ConfigError> {
+        fn parse<T: std::str::FromStr>(
+            name: &str,
+            value: Option<String>,
+            target: &mut T,
+        ) -> Result<(), ConfigError> {
+            if let Some(value) = value {
+                *target = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| ConfigError::new(name, format!("invalid value `{}`", value)))?;
+            }
+            Ok(())
+        }
+        let flag = |name: &str| -> Result<Option<bool>, ConfigError> {
+            get(name)
+                .map(|v| match v.trim().to_ascii_lowercase().as_str() {
+                    "1" | "true" => Ok(true),
+                    "0" | "false" => Ok(false),
+                    _ => Err(ConfigError::new(name, format!("invalid value `{}`", v))),
+                })
+                .transpose()
+        };
+
+        let server = &mut self.server;
+        parse("PORT", get("PORT"), &mut server.port)?;
+        if let Some(url) = get("DASHBOARD_URL") {
+            server.dashboard_url = Some(url);
+        }
+        if let Some(dir) = get("DATA_DIR") {
+            server.data_dir = Some(PathBuf::from(dir));
+        }
+        if let Some(zone) = get("DASHBOARD_TIMEZONE") {
+            server.timezone = Some(zone.trim().to_string());
+        }
+        parse(
+            "API_RATE_LIMIT",
+            get("API_RATE_LIMIT"),
+            &mut server.api_rate_limit,
+        )?;
+        parse(
+            "SEARCH_MAX_QUERY_LEN",
+            get("SEARCH_MAX_QUERY_LEN"),
+            &mut server.search_max_query_len,
+        )?;
+        parse(
+            "SEARCH_CONCURRENCY",
+            get("SEARCH_CONCURRENCY"),
+            &mut server.search_concurrency,
+        )?;
+        parse(
+            "RAW_LOG_MAX_BYTES",
+            get("RAW_LOG_MAX_BYTES"),
+            &mut server.raw_log_max_bytes,
+        )?;
+        parse(
+            "DIFF_MAX_BYTES",
+            get("DIFF_MAX_BYTES"),
+            &mut server.diff_max_bytes,
+        )?;
+        if let Some(enabled) = flag("DIFF_SNAPSHOTS")? {
+            server.diff_snapshots.enabled = enabled;
+        }
+        if let Some(states) = get("DIFF_SNAPSHOT_STATES") {
+            server.diff_snapshots.states = states
+                .split(',')
+                .map(|name| {
+                    serde_json::from_value(serde_json::Value::String(name.trim().to_string()))
+                })
+                .collect::<Result<_, _>>()
+                .map_err(|_| {
+                    ConfigError::new(
+                        "DIFF_SNAPSHOT_STATES",
+                        format!("invalid value `{}`", states),
+                    )
+                })?;
+        }
+        parse(
+            "DIFF_SNAPSHOT_MAX_COUNT",
+            get("DIFF_SNAPSHOT_MAX_COUNT"),
+            &mut server.diff_snapshots.max_count,
+        )?;
+        parse(
+            "DIFF_SNAPSHOT_MAX_BYTES",
+            get("DIFF_SNAPSHOT_MAX_BYTES"),
+            &mut server.diff_snapshots.max_total_bytes,
+        )?;
+        if let Some(enabled) = flag("ARCHIVE")? {
+            server.archive.enabled = enabled;
+        }
+        parse(
+            "ARCHIVE_AFTER_HOURS",
+            get("ARCHIVE_AFTER_HOURS"),
+            &mut server.archive.after_hours,
+        )?;
+        if let Some(path) = get("EVENT_JOURNAL_PATH") {
+            server.journal.path = Some(PathBuf::from(path.trim()));
+        }
+        parse(
+            "EVENT_JOURNAL_MAX_BYTES",
+            get("EVENT_JOURNAL_MAX_BYTES"),
+            &mut server.journal.max_bytes,
+        )?;
+        parse(
+            "EVENT_JOURNAL_KEEP",
+            get("EVENT_JOURNAL_KEEP"),
+            &mut server.journal.keep_files,
+        )?;
+        if let Some(name) = get("INSTANCE_NAME") {
+            server.instance_name = Some(name.trim().to_string());
+        }
+        if let Some(token) = get("INGEST_TOKEN") {
+            server.ingest_token = Some(token.trim().to_string());
+        }
+        if let Some(url) = get("RELAY_URL") {
+            server.relay.url = Some(url.trim().to_string());
+        }
+        if let Some(token) = get("RELAY_TOKEN") {
+            server.relay.token = Some(token.trim().to_string());
+        }
+        if let Some(host) = get("RELAY_HOST") {
+            server.relay.host = Some(host.trim().to_string());
+        }
+        if let Some(anonymize) = flag("RELAY_ANONYMIZE")? {
+            server.relay.anonymize = anonymize;
+        }
+        if let Some(command) = get("OPEN_COMMAND") {
+            server.open_command = Some(command.trim().to_string());
+        }
+        if let Some(allow) = flag("ALLOW_OPEN_COMMANDS")? {
+            server.allow_open_commands = allow;
+        }
+        if let Some(allow) = flag("ALLOW_SESSION_CONTROL")? {
+            server.allow_session_control = allow;
+        }
+        if let Some(require) = flag("REQUIRE_CONFIRM")? {
+            server.require_confirm = require;
+        }
+        if let Some(public) = flag("PUBLIC_STATUS")? {
+            server.public_status = public;
+        }
+        if let Some(signal) = get("INTERRUPT_SIGNAL") {
+            server.interrupt_signal = serde_json::from_value(serde_json::Value::String(
+                signal.trim().to_ascii_uppercase(),
+            ))
+            .map_err(|_| {
+                ConfigError::new(
+                    "INTERRUPT_SIGNAL",
+                    format!("expected SIGINT, SIGTERM or SIGHUP, got `{}`", signal),
+                )
+            })?;
+        }
+        parse(
+            "EVENT_FLUSH_MS",
+            get("EVENT_FLUSH_MS"),
+            &mut server.event_flush_ms,
+        )?;
+        parse(
+            "BROADCAST_CAPACITY",
+            get("BROADCAST_CAPACITY"),
+            &mut server.broadcast_capacity,
+        )?;
+        parse(
+            "MESSAGE_CAPACITY",
+            get("MESSAGE_CAPACITY"),
+            &mut server.message_capacity,
+        )?;
+
+        let claude = &mut self.providers.claude_code;
+        if let Some(enabled) = flag("PROCESS_DETECTION")? {
+            claude.process_detection = enabled;
+        }
+        if let Some(backend) = get("GIT_STATUS_BACKEND") {
+            claude.git_status_backend = match backend.trim() {
+                "libgit2" => GitBackend::Libgit2,
+                "cli" => GitBackend::Cli,
+                _ => {
+                    return Err(ConfigError::new(
+                        "GIT_STATUS_BACKEND",
+                        format!("expected `libgit2` or `cli`, got `{}`", backend),
+                    ))
+                }
+            };
+        }
+        parse(
+            "SUPERSEDE_GRACE_SECS",
+            get("SUPERSEDE_GRACE_SECS"),
+            &mut claude.supersede_grace_secs,
+        )?;
+        parse(
+            "DEHYDRATE_AFTER_SECS",
+            get("DEHYDRATE_AFTER_SECS"),
+            &mut claude.dehydrate_after_secs,
+        )?;
+        parse(
+            "MAX_TRANSCRIPT_MEMORY_MB",
+            get("MAX_TRANSCRIPT_MEMORY_MB"),
+            &mut claude.max_transcript_memory_mb,
+        )?;
+        parse(
+            "MAX_LINE_BYTES",
+            get("MAX_LINE_BYTES"),
+            &mut claude.max_line_bytes,
+        )?;
+        if let Some(single) = flag("SINGLE_SESSION_PER_PROJECT")? {
+            claude.single_session_per_project = single;
+        }
+        if let Some(redact) = flag("REDACT_SECRETS")? {
+            claude.redact_secrets = redact;
+        }
+        if let Some(percents) = get("CONTEXT_WARNING_PERCENTS") {
+            claude.context_warning_percents = percents
+                .split(',')
+                .map(|percent| percent.trim().parse())
+                .collect::<Result<_, _>>()
+                .map_err(|_| {
+                    ConfigError::new(
+                        "CONTEXT_WARNING_PERCENTS",
+                        format!("invalid value `{}`", percents),
+                    )
+                })?;
+        }
+
+        let ntfy = &mut self.notifications.ntfy;
+        if let Some(topic) = get("NTFY_TOPIC") {
+            ntfy.topic = Some(topic);
+        }
+        if let Some(server) = get("NTFY_SERVER") {
+            ntfy.server = server;
+        }
+        if let Some(token) = get("NTFY_TOKEN") {
+            ntfy.token = Some(token);
+        }
+        if let Some(states) = get("NTFY_STATES") {
+            // Unknown names are skipped, as they always have been for this variable.
+            let states: Vec<AgentStateType> = states
+                .split(',')
+                .filter_map(|name| {
+                    serde_json::from_value(serde_json::Value::String(name.trim().to_string())).ok()
+                })
+                .collect();
+            if !states.is_empty() {
+                ntfy.states = states;
+            }
+        }
+        if let Some(warnings) = flag("NTFY_CONTEXT_WARNINGS")? {
+            ntfy.context_warnings = warnings;
+        }
+
+        let summaries = &mut self.summaries;
+        if let Some(key) = get("SUMMARY_API_KEY") {
+            summaries.api_key = Some(key.trim().to_string());
+        }
+        if let Some(model) = get("SUMMARY_MODEL") {
+            summaries.model = model.trim().to_string();
+        }
+        if let Some(url) = get("SUMMARY_API_URL") {
+            summaries.api_url = url.trim().to_string();
+        }
+        if let Some(on_stop) = flag("SUMMARY_ON_STOP")? {
+            summaries.on_stop = on_stop;
+        }
+        Ok(())
+    }
+
+    /// Check values that parse but make no sense.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let positive = |key: &str, value: u64| {
+            if value == 0 {
+                Err(ConfigError::new(key, "must be greater than 0"))
+            } else {
+                Ok(())
+            }
+        };
+        positive("server.port", self.server.port as u64)?;
+        positive(
+            "server.search_max_query_len",
+            self.server.search_max_query_len as u64,
+        )?;
+        positive(
+            "server.search_concurrency",
+            self.server.search_concurrency as u64,
+        )?;
+        positive("server.raw_log_max_bytes", self.server.raw_log_max_bytes)?;
+        positive(
+            "server.broadcast_capacity",
+            self.server.broadcast_capacity as u64,
+        )?;
+        positive(
+            "server.message_capacity",
+            self.server.message_capacity as u64,
+        )?;
+        positive("server.diff_max_bytes", self.server.diff_max_bytes)?;
+        positive(
+            "server.diff_snapshots.max_count",
+            self.server.diff_snapshots.max_count as u64,
+        )?;
+        positive(
+            "server.diff_snapshots.max_total_bytes",
+            self.server.diff_snapshots.max_total_bytes,
+        )?;
+        positive("server.archive.after_hours", self.server.archive.after_hours)?;
+        if self.server.archive.after_hours > archive::MAX_AFTER_HOURS {
+            return Err(ConfigError::new(
+                "server.archive.after_hours",
+                format!("must be at most {}", archive::MAX_AFTER_HOURS),
+            ));
+        }
+        positive("server.journal.max_bytes", self.server.journal.max_bytes)?;
+        positive(
+            "server.journal.keep_files",
+            self.server.journal.keep_files as u64,
+        )?;
+        if let Some(zone) = &self.server.timezone {
+            days::parse_zone(zone).map_err(|e| ConfigError::new("server.timezone", e))?;
+        }
+
+        let claude = &self.providers.claude_code;
+        positive(
+            "providers.claude_code.scan_interval_secs",
+            claude.scan_interval_secs,
+        )?;
+        positive(
+            "providers.claude_code.max_log_age_hours",
+            claude.max_log_age_hours,
+        )?;
+        positive(
+            "providers.claude_code.poll_interval_secs",
+            claude.poll_interval_secs,
+        )?;
+        positive(
+            "providers.claude_code.timer_interval_secs",
+            claude.timer_interval_secs,
+        )?;
+        positive(
+            "providers.claude_code.max_line_bytes",
+            claude.max_line_bytes,
+        )?;
+        let limits = &claude.text_limits;
+        for (key, value) in [
+            ("message", limits.message),
+            ("tool_result", limits.tool_result),
+            ("task", limits.task),
+            ("search_preview", limits.search_preview),
+            ("snippet_context", limits.snippet_context),
+        ] {
+            positive(
+                &format!("providers.claude_code.text_limits.{}", key),
+                value as u64,
+            )?;
+        }
+        positive(
+            "providers.claude_code.git_check_interval_secs",
+            claude.git_check_interval_secs,
+        )?;
+        positive(
+            "providers.claude_code.git_check_interval_running_secs",
+            claude.git_check_interval_running_secs,
+        )?;
+        positive(
+            "providers.claude_code.max_messages",
+            claude.max_messages as u64,
+        )?;
+        for (i, pattern) in claude.redact_patterns.iter().enumerate() {
+            if let Err(e) = Redactor::new(true, std::slice::from_ref(pattern)) {
+                return Err(ConfigError::new(
+                    &format!("providers.claude_code.redact_patterns[{}]", i),
+                    e.to_string(),
+                ));
+            }
+        }
+        for (i, &percent) in claude.context_warning_percents.iter().enumerate() {
+            if !(1..=100).contains(&percent) {
+                return Err(ConfigError::new(
+                    &format!("providers.claude_code.context_warning_percents[{}]", i),
+                    "must be between 1 and 100",
+                ));
+            }
+        }
+
+        let timeouts = &self.state_machine;
+        positive(
+            "state_machine.permission_wait_secs",
+            timeouts.permission_wait_secs,
+        )?;
+        positive("state_machine.idle_secs", timeouts.idle_secs)?;
+        positive(
+            "state_machine.running_stopped_secs",
+            timeouts.running_stopped_secs,
+        )?;
+        positive(
+            "state_machine.idle_stopped_secs",
+            timeouts.idle_stopped_secs,
+        )?;
+        positive(
+            "state_machine.process_gone_secs",
+            timeouts.process_gone_secs,
+        )?;
+
+        for (path, project) in &self.projects {
+            let key = |name: &str| format!("projects.\"{}\".{}", path, name);
+            if !Path::new(path).is_absolute() {
+                return Err(ConfigError::new(
+                    &format!("projects.\"{}\"", path),
+                    "must be an absolute directory",
+                ));
+            }
+            let timeouts = &project.state_machine;
+            for (name, value) in [
+                (
+                    "state_machine.permission_wait_secs",
+                    timeouts.permission_wait_secs,
+                ),
+                ("state_machine.idle_secs", timeouts.idle_secs),
+                (
+                    "state_machine.running_stopped_secs",
+                    timeouts.running_stopped_secs,
+                ),
+                (
+                    "state_machine.idle_stopped_secs",
+                    timeouts.idle_stopped_secs,
+                ),
+                (
+                    "state_machine.process_gone_secs",
+                    timeouts.process_gone_secs,
+                ),
+                ("git_check_interval_secs", project.git_check_interval_secs),
+                (
+                    "git_check_interval_running_secs",
+                    project.git_check_interval_running_secs,
+                ),
+                ("max_messages", project.max_messages.map(|max| max as u64)),
+            ] {
+                if let Some(value) = value {
+                    positive(&key(name), value)?;
+                }
+            }
+        }
+
+        for (i, model) in self.cost.models.iter().enumerate() {
+            let key = format!("cost.models[{}]", i);
+            if model.prefix.trim().is_empty() {
+                return Err(ConfigError::new(
+                    &format!("{}.prefix", key),
+                    "must not be empty",
+                ));
+            }
+            let prices = [
+                ("input", model.input),
+                ("output", model.output),
+                ("cache_read", model.cache_read),
+                ("cache_creation", model.cache_creation),
+                ("cache_creation_1h", model.cache_creation_1h.unwrap_or(0.0)),
+            ];
+            for (name, price) in prices {
+                if !(price >= 0.0 && price.is_finite()) {
+                    return Err(ConfigError::new(
+                        &format!("{}.{}", key, name),
+                        "must be a non-negative number",
+                    ));
+                }
+            }
+        }
+        for (i, limit) in self.cost.context_limits.iter().enumerate() {
+            let key = format!("cost.context_limits[{}]", i);
+            if limit.prefix.trim().is_empty() {
+                return Err(ConfigError::new(
+                    &format!("{}.prefix", key),
+                    "must not be empty",
+                ));
+            }
+            positive(&format!("{}.tokens", key), limit.tokens)?;
+        }
+        for (i, budget) in self.cost.budgets.iter().enumerate() {
+            let key = format!("cost.budgets[{}]", i);
+            if budget.project.trim().is_empty() {
+                return Err(ConfigError::new(
+                    &format!("{}.project", key),
+                    "must not be empty",
+                ));
+            }
+            if !(budget.limit_usd > 0.0 && budget.limit_usd.is_finite()) {
+                return Err(ConfigError::new(
+                    &format!("{}.limit_usd", key),
+                    "must be a positive number",
+                ));
+            }
+        }
+        if self.pricing().is_none() {
+            return Err(ConfigError::new(
+                "cost.fallback_model",
+                format!("no model has the prefix `{}`", self.cost.fallback_model),
+            ));
+        }
+
+        if self.server.ingest_token.as_ref().is_some_and(|t| t.is_empty()) {
+            return Err(ConfigError::new("server.ingest_token", "must not be empty"));
+        }
+        if let Some(command) = &self.server.open_command {
+            open::split_template(command)
+                .map_err(|e| ConfigError::new("server.open_command", e))?;
+        }
+        let relay = &self.server.relay;
+        if let Some(url) = &relay.url {
+            if !url.starts_with("ws://") && !url.starts_with("wss://") {
+                return Err(ConfigError::new(
+                    "server.relay.url",
+                    "must be a ws(s) URL",
+                ));
+            }
+        }
+        let instance_name = self.server.instance_name.as_ref();
+        if instance_name.is_some_and(|name| !is_valid_host(name)) {
+            return Err(ConfigError::new(
+                "server.instance_name",
+                "may only contain letters, digits, `.`, `_` and `-`",
+            ));
+        }
+        if relay.host.as_ref().is_some_and(|host| !is_valid_host(host)) {
+            return Err(ConfigError::new(
+                "server.relay.host",
+                "may only contain letters, digits, `.`, `_` and `-`",
+            ));
+        }
+
+        let ntfy = &self.notifications.ntfy;
+        if ntfy.topic.as_ref().is_some_and(|t| t.trim().is_empty()) {
+            return Err(ConfigError::new(
+                "notifications.ntfy.topic",
+                "must not be empty",
+            ));
+        }
+        if !ntfy.server.starts_with("http://") && !ntfy.server.starts_with("https://") {
+            return Err(ConfigError::new(
+                "notifications.ntfy.server",
+                "must be an http(s) URL",
+            ));
+        }
+
+        let summaries = &self.summaries;
+        if summaries.api_key.as_ref().is_some_and(|k| k.trim().is_empty()) {
+            return Err(ConfigError::new("summaries.api_key", "must not be empty"));
+        }
+        if summaries.model.trim().is_empty() {
+            return Err(ConfigError::new("summaries.model", "must not be empty"));
+        }
+        if !summaries.api_url.starts_with("http://") && !summaries.api_url.starts_with("https://")
+        {
+            return Err(ConfigError::new(
+                "summaries.api_url",
+                "must be an http(s) URL",
+            ));
+        }
+
+        for (i, rule) in self.analysis.rules.iter().enumerate() {
+            let key = format!("analysis.rules[{}]", i);
+            if rule.activity.trim().is_empty() {
+                return Err(ConfigError::new(
+                    &format!("{}.activity", key),
+                    "must not be empty",
+                ));
+            }
+            let shares = [
+                ("min_edit_share", rule.min_edit_share),
+                ("min_read_share", rule.min_read_share),
+            ];
+            for (name, share) in shares {
+                if !(0.0..=1.0).contains(&share) {
+                    return Err(ConfigError::new(
+                        &format!("{}.{}", key, name),
+                        "must be between 0 and 1",
+                    ));
+                }
+            }
+            if rule.keywords.iter().any(|k| k.trim().is_empty()) {
+                return Err(ConfigError::new(
+                    &format!("{}.keywords", key),
+                    "must not contain empty keywords",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// The configuration with secrets replaced, for display.
+    pub fn redacted(&self) -> Self {
+        let mut config = self.clone();
+        for token in [
+            &mut config.notifications.ntfy.token,
+            &mut config.server.ingest_token,
+            &mut config.server.relay.token,
+            &mut config.summaries.api_key,
+        ] {
+            if token.is_some() {
+                *token = Some(REDACTED.to_string());
+            }
+        }
+        config
+    }
+
+    pub fn dashboard_url(&self) -> String {
+        self.server
+            .dashboard_url
+            .clone()
+            .unwrap_or_else(|| format!("http://localhost:{}", self.server.port))
+    }
+
+    /// The zone days are counted in.
+    pub fn timezone(&self) -> chrono_tz::Tz {
+        self.server
+            .timezone
+            .as_deref()
+            .and_then(|zone| days::parse_zone(zone).ok())
+            .unwrap_or_else(days::system_zone)
+    }
+
+    pub fn data_dir(&self) -> PathBuf {
+        self.server
+            .data_dir
+            .clone()
+            .unwrap_or_else(crate::session::prefs::default_data_dir)
+    }
+
+    pub fn api_limits(&self) -> ApiLimits {
+        ApiLimits::new(
+            self.server.api_rate_limit,
+            self.server.search_max_query_len,
+            self.server.search_concurrency,
+        )
+    }
+
+    pub fn journal_config(&self) -> Option<JournalConfig> {
+        let journal = &self.server.journal;
+        Some(JournalConfig {
+            path: journal.path.clone()?,
+            max_bytes: journal.max_bytes,
+            keep_files: journal.keep_files,
+        })
+    }
+
+    /// Where and when to save diff snapshots, unless they're turned off.
+    pub fn diff_snapshot_config(&self) -> Option<DiffSnapshotConfig> {
+        let snapshots = &self.server.diff_snapshots;
+        if !snapshots.enabled || snapshots.states.is_empty() {
+            return None;
+        }
+        Some(DiffSnapshotConfig {
+            dir: self.data_dir().join(diff_snapshots::SNAPSHOTS_DIR),
+            states: snapshots.states.clone(),
+            max_count: snapshots.max_count,
+            max_total_bytes: snapshots.max_total_bytes,
+            max_diff_bytes: self.server.diff_max_bytes,
+        })
+    }
+
+    /// Where and when to archive stopped sessions, if they're archived.
+    pub fn archive_config(&self) -> Option<ArchiveConfig> {
+        let archive = &self.server.archive;
+        if !archive.enabled {
+            return None;
+        }
+        Some(ArchiveConfig {
+            dir: self.data_dir().join(archive::ARCHIVE_DIR),
+            after: archive
+                .after_hours
+                .checked_mul(60 * 60)
+                .map_or(Duration::MAX, Duration::from_secs),
+        })
+    }
+
+    /// How to write session summaries, unless no API key is set.
+    pub fn summary_config(&self) -> Option<SummaryConfig> {
+        let summaries = &self.summaries;
+        Some(SummaryConfig {
+            api_key: summaries.api_key.clone()?,
+            model: summaries.model.clone(),
+            api_url: summaries.api_url.trim_end_matches('/').to_string(),
+            on_stop: summaries.on_stop,
+        })
+    }
+
+    /// Where to relay sessions to, if anywhere.
+    pub fn relay_config(&self) -> Option<RelayConfig> {
+        let relay = &self.server.relay;
+        Some(RelayConfig {
+            url: relay.url.clone()?,
+            token: relay.token.clone(),
+            host: relay.host.clone().unwrap_or_else(|| self.instance_name()),
+            anonymize: relay.anonymize,
+        })
+    }
+
+    /// `server.instance_name`, else the machine's host name.
+    pub fn instance_name(&self) -> String {
+        self.server
+            .instance_name
+            .clone()
+            .or_else(|| sysinfo::System::host_name().and_then(|host| sanitize_host(&host)))
+            .unwrap_or_else(|| DEFAULT_HOST.to_string())
+    }
+
+    fn pricing(&self) -> Option<Pricing> {
+        Pricing::new(self.cost.models.clone(), &self.cost.fallback_model)
+    }
+
+    /// Dotted keys whose values differ in `other`, e.g. `state_machine.idle_secs`.
+    /// Lists such as `cost.models` count as one key.
+    pub fn changed_keys(&self, other: &Config) -> Vec<String> {
+        fn diff(prefix: &str, a: &serde_json::Value, b: &serde_json::Value, out: &mut Vec<String>) {
+            match (a, b) {
+                (serde_json::Value::Object(a), serde_json::Value::Object(b)) => {
+                    let keys: std::collections::BTreeSet<&String> =
+                        a.keys().chain(b.keys()).collect();
+                    for key in keys {
+                        let path = if prefix.is_empty() {
+                            key.clone()
+                        } else {
+                            format!("{}.{}", prefix, key)
+                        };
+                        let null = serde_json::Value::Null;
+                        diff(
+                            &path,
+                            a.get(key).unwrap_or(&null),
+                            b.get(key).unwrap_or(&null),
+                            out,
+                        );
+                    }
+                }
+                _ if a != b => out.push(prefix.to_string()),
+                _ => {}
+            }
+        }
+        let mut changed = Vec::new();
+        diff(
+            "",
+            &serde_json::to_value(self).unwrap_or_default(),
+            &serde_json::to_value(other).unwrap_or_default(),
+            &mut changed,
+        );
+        changed
+    }
+
+    /// This configuration with the live keys taken from `other`: what is in effect
+    /// after reloading `other`.
+    pub fn with_live_from(&self, other: &Config) -> Config {
+        let mut config = self.clone();
+        config.server.dashboard_url = other.server.dashboard_url.clone();
+        config.server.timezone = other.server.timezone.clone();
+        config.server.api_rate_limit = other.server.api_rate_limit;
+        config.server.search_max_query_len = other.server.search_max_query_len;
+        config.server.search_concurrency = other.server.search_concurrency;
+        config.server.raw_log_max_bytes = other.server.raw_log_max_bytes;
+        config.server.diff_max_bytes = other.server.diff_max_bytes;
+        config.server.ingest_token = other.server.ingest_token.clone();
+        config.server.open_command = other.server.open_command.clone();
+        config.server.allow_open_commands = other.server.allow_open_commands;
+        config.server.allow_session_control = other.server.allow_session_control;
+        config.server.public_status = other.server.public_status;
+        config.server.interrupt_signal = other.server.interrupt_signal;
+        config.server.require_confirm = other.server.require_confirm;
+        let (claude, new_claude) = (
+            &mut config.providers.claude_code,
+            &other.providers.claude_code,
+        );
+        claude.git_status_backend = new_claude.git_status_backend;
+        claude.supersede_grace_secs = new_claude.supersede_grace_secs;
+        claude.single_session_per_project = new_claude.single_session_per_project;
+        claude.redact_secrets = new_claude.redact_secrets;
+        claude.redact_patterns = new_claude.redact_patterns.clone();
+        claude.context_warning_percents = new_claude.context_warning_percents.clone();
+        claude.dehydrate_after_secs = new_claude.dehydrate_after_secs;
+        claude.max_transcript_memory_mb = new_claude.max_transcript_memory_mb;
+        claude.git_check_interval_secs = new_claude.git_check_interval_secs;
+        claude.git_check_interval_running_secs = new_claude.git_check_interval_running_secs;
+        claude.max_messages = new_claude.max_messages;
+        claude.text_limits = new_claude.text_limits.clone();
+        config.state_machine = other.state_machine.clone();
+        config.cost = other.cost.clone();
+        config.notifications = other.notifications.clone();
+        config.analysis = other.analysis.clone();
+        config.projects = other.projects.clone();
+        config
+    }
+
+    /// The settings every tracked session reads, with those of each project section;
+    /// swapped in place on reload.
+    pub fn session_settings(&self) -> SessionSettings {
+        let claude = &self.providers.claude_code;
+        let timeouts = &self.state_machine;
+        let ms = |secs: u64| secs as i64 * 1000;
+        let global = SessionSettings {
+            timeouts: StateTimeouts {
+                permission_wait_ms: ms(timeouts.permission_wait_secs),
+                idle_ms: ms(timeouts.idle_secs),
+                running_stopped_ms: ms(timeouts.running_stopped_secs),
+                idle_stopped_ms: ms(timeouts.idle_stopped_secs),
+                process_gone_ms: ms(timeouts.process_gone_secs),
+            },
+            pricing: Arc::new(self.pricing().unwrap_or_default()),
+            context_limits: Arc::new(ContextLimits::new(self.cost.context_limits.clone())),
+            context_warning_percents: claude.context_warning_percents.clone(),
+            git_backend: claude.git_status_backend,
+            supersede_grace: if claude.single_session_per_project {
+                Duration::ZERO
+            } else {
+                Duration::from_secs(claude.supersede_grace_secs)
+            },
+            redactor: Arc::new(
+                Redactor::new(claude.redact_secrets, &claude.redact_patterns).unwrap_or_default(),
+            ),
+            dehydrate_after: Duration::from_secs(claude.dehydrate_after_secs),
+            transcript_memory_budget: claude.max_transcript_memory_mb * 1024 * 1024,
+            activity_rules: Arc::new(self.analysis.rules.clone()),
+            text_limits: claude.text_limits.limits(),
+            timezone: self.timezone(),
+            git_check_interval: Duration::from_secs(claude.git_check_interval_secs),
+            git_check_interval_running: Duration::from_secs(claude.git_check_interval_running_secs),
+            max_messages: claude.max_messages,
+            notify: NotifyRules {
+                states: self.notifications.ntfy.states.clone(),
+                context_warnings: self.notifications.ntfy.context_warnings,
+            },
+            projects: Arc::new(Vec::new()),
+            project: None,
+        };
+        let projects = self
+            .projects
+            .iter()
+            .map(|(path, section)| {
+                let path = project_key(path);
+                ProjectSettings {
+                    path: path.to_string(),
+                    settings: Arc::new(section.apply(path, &global)),
+                }
+            })
+            .collect();
+        SessionSettings {
+            projects: Arc::new(projects),
+            ..global
+        }
+    }
+
+    pub fn provider_config(&self) -> ProviderConfig {
+        let claude = &self.providers.claude_code;
+        let mut discovery = DiscoveryConfig::default();
+        if let Some(dir) = &claude.projects_dir {
+            discovery.projects_dir = dir.clone();
+        }
+        discovery.scan_interval = Duration::from_secs(claude.scan_interval_secs);
+        discovery.max_log_age = Duration::from_secs(claude.max_log_age_hours * 3600);
+        ProviderConfig {
+            discovery,
+            timer_interval: Duration::from_secs(claude.timer_interval_secs),
+            poll_interval: Duration::from_secs(claude.poll_interval_secs),
+            max_line_bytes: claude.max_line_bytes as usize,
+            process_lookup: claude
+                .process_detection
+                .then_some(claude_process_cwds as fn() -> _),
+            session: Arc::new(ArcSwap::from_pointee(self.session_settings())),
+            host: self.instance_name(),
+        }
+    }
+}
+
+/// A `[projects]` key as a directory to match against: without a trailing separator.
+fn project_key(path: &str) -> &str {
+    match path.trim_end_matches(['/', '\\']) {
+        "" => path,
+        trimmed => trimmed,
+    }
+}
+
+/// The value of `--config <path>` or `--config=<path>`.
+fn config_arg(args: impl IntoIterator<Item = String>) -> Result<Option<PathBuf>, ConfigError> {
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            return match args.next() {
+                Some(path) => Ok(Some(PathBuf::from(path))),
+                None => Err(ConfigError::new("--config", "expected a file path")),
+            };
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Ok(Some(PathBuf::from(path)));
+        }
+    }
+    Ok(None)
+}
+
+/// `$XDG_CONFIG_HOME/agents-dashboard/config.toml`, or under `~/.config`.
+fn default_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| dirs::home_dir().map(|home| home.join(".config")))?;
+    Some(config_file_in(&base))
+}
+
+fn config_file_in(base: &Path) -> PathBuf {
+    base.join("agents-dashboard").join("config.toml")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cost::EntryUsage;
+    use std::collections::HashMap;
+
+    fn vars<'a>(pairs: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        let map: HashMap<&str, &str> = pairs.iter().copied().collect();
+        move |key| map.get(key).map(|v| v.to_string())
+    }
+
+    #[test]
+    fn test_empty_file_is_todays_defaults() {
+        let config = Config::parse("").unwrap();
+        assert_eq!(config, Config::default());
+        config.validate().unwrap();
+
+        let provider = config.provider_config();
+        let defaults = ProviderConfig::default();
+        assert_eq!(provider.timer_interval, defaults.timer_interval);
+        assert_eq!(provider.poll_interval, defaults.poll_interval);
+        assert_eq!(
+            provider.discovery.scan_interval,
+            defaults.discovery.scan_interval
+        );
+        assert_eq!(
+            provider.discovery.max_log_age,
+            defaults.discovery.max_log_age
+        );
+        assert!(provider.process_lookup.is_some());
+        let session = config.session_settings();
+        let default_session = SessionSettings::default();
+        assert_eq!(session.timeouts, default_session.timeouts);
+        assert_eq!(session.supersede_grace, default_session.supersede_grace);
+        assert!(config.journal_config().is_none());
+        assert_eq!(config.dashboard_url(), "http://localhost:3001");
+    }
+
+    #[test]
+    fn test_parse_sections() {
+        let config = Config::parse(
+            r#"
+            [server]
+            port = 4000
+            journal.path = "/var/log/agents.jsonl"
+
+            [providers.claude_code]
+            projects_dir = "/data/claude/projects"
+            git_status_backend = "cli"
+            single_session_per_project = true
+            text_limits.message = 1000
+
+            [state_machine]
+            idle_secs = 20
+
+            [cost]
+            fallback_model = "claude-opus"
+
+            [[cost.models]]
+            prefix = "claude-sonnet-5"
+            input = 2.0
+            output = 10.0
+            cache_read = 0.2
+            cache_creation = 2.5
+
+            [[cost.budgets]]
+            project = "/work/billing"
+            limit_usd = 25.0
+            period = "month"
+
+            [[cost.budgets]]
+            project = "/work/site"
+            limit_usd = 5.0
+
+            [notifications.ntfy]
+            topic = "agents"
+            states = ["idle"]
+
+            [[analysis.rules]]
+            activity = "docs"
+            min_edits = 1
+            keywords = ["readme", "docs"]
+            "#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+
+        assert_eq!(config.dashboard_url(), "http://localhost:4000");
+        assert_eq!(
+            config.journal_config().unwrap().path,
+            PathBuf::from("/var/log/agents.jsonl")
+        );
+        let provider = config.provider_config();
+        assert_eq!(
+            provider.discovery.projects_dir,
+            PathBuf::from("/data/claude/projects")
+        );
+        let session = config.session_settings();
+        assert_eq!(session.git_backend, GitBackend::Cli);
+        assert_eq!(session.supersede_grace, Duration::ZERO);
+        assert_eq!(session.timeouts.idle_ms, 20_000);
+        let pricing = &session.pricing;
+        let tokens = EntryUsage {
+            input_tokens: 1_000_000,
+            ..Default::default()
+        };
+        let cost = pricing.calculate_cost("claude-sonnet-5-20260101", &tokens);
+        assert!((cost - 2.0).abs() < 1e-10);
+        let cost = pricing.calculate_cost("gpt-4", &tokens);
+        assert!((cost - 15.0).abs() < 1e-10);
+        assert_eq!(config.notifications.ntfy.states, [AgentStateType::Idle]);
+        assert_eq!(config.cost.budgets[0].period, BudgetPeriod::Month);
+        assert_eq!(config.cost.budgets[1].period, BudgetPeriod::Week);
+        assert_eq!(session.activity_rules.len(), 1);
+        assert_eq!(session.activity_rules[0].activity, "docs");
+        assert_eq!(session.activity_rules[0].max_edits, None);
+        assert_eq!(session.text_limits.message, 1000);
+        assert_eq!(session.text_limits.task, 200);
+    }
+
+    #[test]
+    fn test_parse_errors_name_the_key() {
+        let err = Config::parse("[server]\nport = \"http\"\n").unwrap_err();
+        assert_eq!(err.key, "server.port");
+
+        let err = Config::parse("[providers.claude_code]\npoll_intervall_secs = 1\n").unwrap_err();
+        assert_eq!(err.key, "providers.claude_code.poll_intervall_secs");
+
+        let err = Config::parse("[notifications.ntfy]\nstates = [\"asleep\"]\n").unwrap_err();
+        assert_eq!(err.key, "notifications.ntfy.states[0]");
+
+        let err = Config::parse("[server\n").unwrap_err();
+        assert_eq!(err.key, "");
+    }
+
+    #[test]
+    fn test_validation_names_the_key() {
+        let mut config = Config::default();
+        config.state_machine.idle_secs = 0;
+        assert_eq!(
+            config.validate().unwrap_err().key,
+            "state_machine.idle_secs"
+        );
+
+        let config = Config::parse("[server]\ntimezone = \"Tokyo\"\n").unwrap();
+        assert_eq!(config.validate().unwrap_err().key, "server.timezone");
+
+        let mut config = Config::default();
+        config.server.archive.after_hours = u64::MAX;
+        assert_eq!(
+            config.validate().unwrap_err().key,
+            "server.archive.after_hours"
+        );
+
+        let config = Config::parse("[cost]\nfallback_model = \"gpt\"\n").unwrap();
+        assert_eq!(config.validate().unwrap_err().key, "cost.fallback_model");
+
+        let config = Config::parse(
+            "[[cost.models]]\nprefix = \"x\"\ninput = -1.0\noutput = 0.0\ncache_read = 0.0\ncache_creation = 0.0\n",
+        )
+        .unwrap();
+        assert_eq!(config.validate().unwrap_err().key, "cost.models[0].input");
+
+        let config =
+            Config::parse("[[cost.budgets]]\nproject = \"/work\"\nlimit_usd = 0.0\n").unwrap();
+        assert_eq!(
+            config.validate().unwrap_err().key,
+            "cost.budgets[0].limit_usd"
+        );
+
+        let config = Config::parse(
+            "[providers.claude_code]\nredact_patterns = [\"token-[a-z]+\", \"(unclosed\"]\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.validate().unwrap_err().key,
+            "providers.claude_code.redact_patterns[1]"
+        );
+
+        let config =
+            Config::parse("[providers.claude_code]\ncontext_warning_percents = [80, 120]\n")
+                .unwrap();
+        assert_eq!(
+            config.validate().unwrap_err().key,
+            "providers.claude_code.context_warning_percents[1]"
+        );
+
+        let config = Config::parse("[server]\nopen_command = \"code '{path}\"\n").unwrap();
+        assert_eq!(config.validate().unwrap_err().key, "server.open_command");
+
+        let config =
+            Config::parse("[[analysis.rules]]\nactivity = \"coding\"\nmin_edit_share = 20.0\n")
+                .unwrap();
+        assert_eq!(
+            config.validate().unwrap_err().key,
+            "analysis.rules[0].min_edit_share"
+        );
+
+        let config = Config::parse("[projects.\"/work/infra\"]\nmax_messages = 0\n").unwrap();
+        assert_eq!(
+            config.validate().unwrap_err().key,
+            "projects.\"/work/infra\".max_messages"
+        );
+
+        let config = Config::parse("[projects.infra]\nmax_messages = 10\n").unwrap();
+        assert_eq!(config.validate().unwrap_err().key, "projects.\"infra\"");
+    }
+
+    #[test]
+    fn test_project_sections_override_globals() {
+        let config = Config::parse(
+            r#"
+            [providers.claude_code]
+            max_messages = 300
+            git_check_interval_secs = 45
+
+            [state_machine]
+            permission_wait_secs = 10
+            idle_secs = 20
+
+            [notifications.ntfy]
+            topic = "agents"
+            states = ["permission_waiting", "error"]
+
+            [projects."/Users/me/infra"]
+            state_machine.permission_wait_secs = 1
+            notifications.states = ["permission_waiting"]
+            git_check_interval_secs = 15
+
+            [projects."/Users/me/infra/terraform/"]
+            max_messages = 2000
+
+            [projects."/Users/me/scratch"]
+            notifications = { states = [], context_warnings = false }
+            dehydrate_after_secs = 60
+            "#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+        let global = Arc::new(config.session_settings());
+
+        let infra = global.for_project("/Users/me/infra/ansible");
+        assert_eq!(infra.project.as_deref(), Some("/Users/me/infra"));
+        assert_eq!(infra.timeouts.permission_wait_ms, 1_000);
+        // Keys the section leaves out keep their global value.
+        assert_eq!(infra.timeouts.idle_ms, 20_000);
+        assert_eq!(infra.notify.states, [AgentStateType::PermissionWaiting]);
+        assert!(infra.notify.context_warnings);
+        assert_eq!(infra.git_check_interval, Duration::from_secs(15));
+        assert_eq!(infra.max_messages, 300);
+
+        // The most specific section applies, on its own: sections don't stack.
+        let terraform = global.for_project("/Users/me/infra/terraform");
+        assert_eq!(
+            terraform.project.as_deref(),
+            Some("/Users/me/infra/terraform")
+        );
+        assert_eq!(terraform.max_messages, 2000);
+        assert_eq!(terraform.timeouts.permission_wait_ms, 10_000);
+        assert_eq!(terraform.git_check_interval, Duration::from_secs(45));
+
+        let scratch = global.for_project("/Users/me/scratch");
+        assert!(scratch.notify.states.is_empty());
+        assert!(!scratch.notify.context_warnings);
+        assert_eq!(scratch.dehydrate_after, Duration::from_secs(60));
+
+        let other = global.for_project("/Users/me/infra-old");
+        assert!(Arc::ptr_eq(&other, &global));
+        assert_eq!(other.project, None);
+        assert_eq!(
+            other.notify.states,
+            [AgentStateType::PermissionWaiting, AgentStateType::Error]
+        );
+    }
+
+    #[test]
+    fn test_env_overrides_file() {
+        let mut config = Config::parse(
+            "[server]\nport = 4000\napi_rate_limit = 10\n[notifications.ntfy]\ntopic = \"file\"\n",
+        )
+        .unwrap();
+        config
+            .apply_env(vars(&[
+                ("PORT", "5000"),
+                ("PROCESS_DETECTION", "0"),
+                ("SUPERSEDE_GRACE_SECS", "5"),
+                ("DEHYDRATE_AFTER_SECS", "60"),
+                ("MAX_TRANSCRIPT_MEMORY_MB", "64"),
+                ("MAX_LINE_BYTES", "1048576"),
+                ("CONTEXT_WARNING_PERCENTS", "70, 90"),
+                ("NTFY_TOPIC", "env"),
+                ("NTFY_STATES", "idle, error, bogus"),
+                ("EVENT_JOURNAL_PATH", "/tmp/journal.jsonl"),
+                ("DIFF_SNAPSHOT_STATES", "idle, error"),
+                ("OPEN_COMMAND", "code {path}"),
+                ("ALLOW_OPEN_COMMANDS", "1"),
+                ("INTERRUPT_SIGNAL", "sigterm"),
+                ("REQUIRE_CONFIRM", "0"),
+                ("DASHBOARD_TIMEZONE", "Asia/Tokyo"),
+                ("MESSAGE_CAPACITY", "16384"),
+                ("PUBLIC_STATUS", "true"),
+                ("ARCHIVE", "1"),
+                ("ARCHIVE_AFTER_HOURS", "2"),
+            ]))
+            .unwrap();
+        assert_eq!(config.server.port, 5000);
+        // Values the environment doesn't set stay as in the file.
+        assert_eq!(config.server.api_rate_limit, 10);
+        assert!(!config.providers.claude_code.process_detection);
+        assert_eq!(config.providers.claude_code.supersede_grace_secs, 5);
+        assert_eq!(
+            config.session_settings().dehydrate_after,
+            Duration::from_secs(60)
+        );
+        assert_eq!(config.provider_config().max_line_bytes, 1024 * 1024);
+        assert_eq!(
+            config.session_settings().transcript_memory_budget,
+            64 * 1024 * 1024
+        );
+        assert_eq!(
+            config.providers.claude_code.context_warning_percents,
+            [70, 90]
+        );
+        assert_eq!(config.notifications.ntfy.topic.as_deref(), Some("env"));
+        assert_eq!(
+            config.notifications.ntfy.states,
+            [AgentStateType::Idle, AgentStateType::Error]
+        );
+        assert!(config.journal_config().is_some());
+        assert_eq!(config.server.open_command.as_deref(), Some("code {path}"));
+        assert!(config.server.allow_open_commands);
+        assert_eq!(config.server.interrupt_signal, InterruptSignal::Sigterm);
+        assert!(!config.server.require_confirm);
+        assert_eq!(
+            config.session_settings().timezone,
+            chrono_tz::Tz::Asia__Tokyo
+        );
+        assert_eq!(config.server.message_capacity, 16384);
+        assert!(config.server.public_status);
+        assert_eq!(
+            config.server.broadcast_capacity,
+            ws::DEFAULT_BROADCAST_CAPACITY
+        );
+        assert_eq!(
+            config.diff_snapshot_config().unwrap().states,
+            [AgentStateType::Idle, AgentStateType::Error]
+        );
+        config.apply_env(vars(&[("DIFF_SNAPSHOTS", "0")])).unwrap();
+        assert!(config.diff_snapshot_config().is_none());
+        assert_eq!(
+            config.archive_config().unwrap().after,
+            Duration::from_secs(2 * 60 * 60)
+        );
+    }
+
+    #[test]
+    fn test_bad_env_values_name_the_variable() {
+        for (name, value) in [
+            ("PORT", "http"),
+            ("PROCESS_DETECTION", "maybe"),
+            ("GIT_STATUS_BACKEND", "svn"),
+            ("EVENT_JOURNAL_KEEP", "-1"),
+            ("CONTEXT_WARNING_PERCENTS", "80,high"),
+            ("DIFF_SNAPSHOT_STATES", "idle, bogus"),
+            ("ALLOW_OPEN_COMMANDS", "maybe"),
+            ("INTERRUPT_SIGNAL", "SIGKILL"),
+        ] {
+            let err = Config::default()
+                .apply_env(vars(&[(name, value)]))
+                .unwrap_err();
+            assert_eq!(err.key, name);
+        }
+    }
+
+    #[test]
+    fn test_redacted_hides_token() {
+        let mut config = Config::default();
+        config.notifications.ntfy.token = Some("tk_secret".to_string());
+        config.server.ingest_token = Some("ingest_secret".to_string());
+        config.server.relay.token = Some("relay_secret".to_string());
+        config.summaries.api_key = Some("sk-ant-secret".to_string());
+        let json = serde_json::to_string(&config.redacted()).unwrap();
+        for token in ["tk_secret", "ingest_secret", "relay_secret", "sk-ant-secret"] {
+            assert!(!json.contains(token));
+        }
+        assert!(json.contains(REDACTED));
+    }
+
+    #[test]
+    fn test_relay_config() {
+        let mut config = Config::default();
+        assert!(config.relay_config().is_none());
+
+        config
+            .apply_env(vars(&[
+                ("RELAY_URL", "ws://central:3001/ingest"),
+                ("RELAY_HOST", "laptop"),
+            ]))
+            .unwrap();
+        config.validate().unwrap();
+        let relay = config.relay_config().unwrap();
+        assert_eq!(relay.url, "ws://central:3001/ingest");
+        assert_eq!(relay.host, "laptop");
+        assert_eq!(relay.token, None);
+        assert!(relay.anonymize);
+        config
+            .apply_env(vars(&[("RELAY_ANONYMIZE", "0")]))
+            .unwrap();
+        assert!(!config.relay_config().unwrap().anonymize);
+
+        config.server.relay.url = Some("http://central:3001/ingest".to_string());
+        assert_eq!(config.validate().unwrap_err().key, "server.relay.url");
+        config.server.relay.url = None;
+        config.server.relay.host = Some("my laptop".to_string());
+        assert_eq!(config.validate().unwrap_err().key, "server.relay.host");
+    }
+
+    #[test]
+    fn test_summary_config() {
+        let mut config = Config::default();
+        assert!(config.summary_config().is_none());
+
+        config
+            .apply_env(vars(&[
+                ("SUMMARY_API_KEY", "sk-ant-test"),
+                ("SUMMARY_API_URL", "http://localhost:9000/"),
+                ("SUMMARY_ON_STOP", "false"),
+            ]))
+            .unwrap();
+        let summaries = config.summary_config().unwrap();
+        assert_eq!(summaries.api_key, "sk-ant-test");
+        assert_eq!(summaries.api_url, "http://localhost:9000");
+        assert_eq!(summaries.model, summarizer::DEFAULT_MODEL);
+        assert!(!summaries.on_stop);
+
+        config.summaries.api_url = "localhost:9000".to_string();
+        assert_eq!(config.validate().unwrap_err().key, "summaries.api_url");
+    }
+
+    #[test]
+    fn test_config_arg() {
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            config_arg(args(&["--config", "/etc/dash.toml"])).unwrap(),
+            Some(PathBuf::from("/etc/dash.toml"))
+        );
+        assert_eq!(
+            config_arg(args(&["--config=dash.toml"])).unwrap(),
+            Some(PathBuf::from("dash.toml"))
+        );
+        assert_eq!(config_arg(args(&[])).unwrap(), None);
+        assert_eq!(config_arg(args(&["--config"])).unwrap_err().key, "--config");
+    }
+
+    #[test]
+    fn test_changed_keys_and_live() {
+        let old = Config::default();
+        let new = Config::parse(
+            r#"
+            [server]
+            port = 4000
+            search_concurrency = 8
+            require_confirm = false
+            open_command = "code {path}"
+
+            [state_machine]
+            idle_secs = 20
+
+            [notifications.ntfy]
+            topic = "agents"
+            "#,
+        )
+        .unwrap();
+        let changed = old.changed_keys(&new);
+        assert_eq!(
+            changed,
+            [
+                "notifications.ntfy.topic",
+                "server.open_command",
+                "server.port",
+                "server.require_confirm",
+                "server.search_concurrency",
+                "state_machine.idle_secs",
+            ]
+        );
+        let live: Vec<_> = changed.iter().filter(|key| applies_live(key)).collect();
+        assert_eq!(live.len(), 5);
+        assert!(!applies_live("server.port"));
+        assert!(!applies_live("server.journal.path"));
+        assert!(!applies_live("costs"));
+        assert!(applies_live("projects./work/infra.state_machine.idle_secs"));
+        assert!(new.changed_keys(&new).is_empty());
+
+        let effective = old.with_live_from(&new);
+        assert!(old
+            .changed_keys(&effective)
+            .iter()
+            .all(|key| applies_live(key)));
+        assert_eq!(effective.changed_keys(&new), ["server.port"]);
+    }
+
+    #[test]
+    fn test_source_reads_file_again() {
+        let dir =
+            std::env::temp_dir().join(format!("agents-dashboard-config-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        let source = ConfigSource::file(path.clone());
+        assert_eq!(source.load().unwrap_err().key, path.display().to_string());
+
+        std::fs::write(&path, "[state_machine]\nidle_secs = 20\n").unwrap();
+        assert_eq!(source.load().unwrap().state_machine.idle_secs, 20);
+        std::fs::write(&path, "[state_machine]\nidle_secs = 0\n").unwrap();
+        assert_eq!(source.load().unwrap_err().key, "state_machine.idle_secs");
+
+        let optional = ConfigSource {
+            path: Some(dir.join("missing.toml")),
+            required: false,
+        };
+        assert!(optional.load().is_ok());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
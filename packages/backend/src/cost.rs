@@ -1,74 +1,217 @@
 use crate::types::CumulativeUsage;
+use serde::{Deserialize, Serialize};
 
-struct ModelPricing {
-    prefix: &'static str,
-    input: f64,
-    output: f64,
-    cache_read: f64,
-    cache_creation: f64,
+/// Unknown models are priced like this one.
+pub const DEFAULT_FALLBACK_MODEL: &str = "claude-sonnet";
+
+/// Prices in dollars per million tokens for models whose name starts with `prefix`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModelPricing {
+    pub prefix: String,
+    pub input: f64,
+    pub output: f64,
+    pub cache_read: f64,
+    /// Cache writes with the default 5-minute TTL.
+    pub cache_creation: f64,
+    /// Cache writes with the 1-hour TTL; twice the input price if not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_creation_1h: Option<f64>,
+}
+
+impl ModelPricing {
+    fn cache_creation_1h(&self) -> f64 {
+        self.cache_creation_1h.unwrap_or(self.input * 2.0)
+    }
+}
+
+fn builtin_pricing() -> Vec<ModelPricing> {
+    let model = |prefix: &str, input, output, cache_read, cache_creation, cache_creation_1h| {
+        ModelPricing {
+            prefix: prefix.to_string(),
+            input,
+            output,
+            cache_read,
+            cache_creation,
+            cache_creation_1h: Some(cache_creation_1h),
+        }
+    };
+    vec![
+        model("claude-opus", 15.0, 75.0, 1.5, 18.75, 30.0),
+        model("claude-sonnet", 3.0, 15.0, 0.3, 3.75, 6.0),
+        model("claude-haiku", 0.8, 4.0, 0.08, 1.0, 1.6),
+    ]
+}
+
+/// Tokens one API call used, as its log entry reports them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EntryUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_read_tokens: u64,
+    /// Cache writes of either TTL.
+    pub cache_creation_tokens: u64,
+    /// How `cache_creation_tokens` split into 5-minute and 1-hour TTL writes, if the
+    /// entry says; without it they are all priced as 5-minute writes.
+    pub cache_creation_ttl: Option<CacheCreationTtl>,
+    /// `standard`, `priority` or `batch`, if the entry says.
+    pub service_tier: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CacheCreationTtl {
+    pub five_minute_tokens: u64,
+    pub one_hour_tokens: u64,
+}
+
+/// Batch requests are billed at half price; other tiers at the listed prices.
+const BATCH_DISCOUNT: f64 = 0.5;
+
+/// The price list cost estimates are made with.
+#[derive(Debug, Clone)]
+pub struct Pricing {
+    models: Vec<ModelPricing>,
+    /// Index in `models` of the prices for unknown models.
+    fallback: usize,
+}
+
+impl Pricing {
+    /// The built-in prices, with `overrides` matched before them, and unknown models
+    /// priced like the first entry whose prefix is `fallback_model`. None if there is
+    /// no such entry.
+    pub fn new(overrides: Vec<ModelPricing>, fallback_model: &str) -> Option<Self> {
+        let mut models = overrides;
+        models.extend(builtin_pricing());
+        let fallback = models.iter().position(|m| m.prefix == fallback_model)?;
+        Some(Self { models, fallback })
+    }
+
+    fn get(&self, model: &str) -> &ModelPricing {
+        self.models
+            .iter()
+            .find(|m| model.starts_with(&m.prefix))
+            .unwrap_or(&self.models[self.fallback])
+    }
+
+    pub fn calculate_cost(&self, model: &str, usage: &EntryUsage) -> f64 {
+        let pricing = self.get(model);
+        let one_hour_tokens = usage
+            .cache_creation_ttl
+            .map_or(0, |ttl| ttl.one_hour_tokens.min(usage.cache_creation_tokens));
+        let cost = (usage.input_tokens as f64 * pricing.input
+            + usage.output_tokens as f64 * pricing.output
+            + usage.cache_read_tokens as f64 * pricing.cache_read
+            + (usage.cache_creation_tokens - one_hour_tokens) as f64 * pricing.cache_creation
+            + one_hour_tokens as f64 * pricing.cache_creation_1h())
+            / 1_000_000.0;
+        match usage.service_tier.as_deref() {
+            Some("batch") => cost * BATCH_DISCOUNT,
+            _ => cost,
+        }
+    }
+
+    pub fn add_usage(
+        &self,
+        current: &CumulativeUsage,
+        model: &str,
+        usage: &EntryUsage,
+    ) -> CumulativeUsage {
+        let entry_cost = self.calculate_cost(model, usage);
+        let ttl = usage.cache_creation_ttl;
+        let add = |total: Option<u64>, tokens: Option<u64>| match tokens {
+            Some(tokens) => Some(total.unwrap_or(0) + tokens),
+            None => total,
+        };
+        CumulativeUsage {
+            input_tokens: current.input_tokens + usage.input_tokens,
+            output_tokens: current.output_tokens + usage.output_tokens,
+            cache_read_tokens: current.cache_read_tokens + usage.cache_read_tokens,
+            cache_creation_tokens: current.cache_creation_tokens + usage.cache_creation_tokens,
+            cache_creation_5m_tokens: add(
+                current.cache_creation_5m_tokens,
+                ttl.map(|ttl| ttl.five_minute_tokens),
+            ),
+            cache_creation_1h_tokens: add(
+                current.cache_creation_1h_tokens,
+                ttl.map(|ttl| ttl.one_hour_tokens),
+            ),
+            estimated_cost: current.estimated_cost + entry_cost,
+        }
+    }
+}
+
+impl Default for Pricing {
+    fn default() -> Self {
+        Self::new(Vec::new(), DEFAULT_FALLBACK_MODEL).unwrap()
+    }
+}
+
+/// Context window, in tokens, of models whose name starts with `prefix`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModelContextLimit {
+    pub prefix: String,
+    pub tokens: u64,
+}
+
+/// Window of models run with the 1M-token context beta.
+pub const EXTENDED_CONTEXT_TOKENS: u64 = 1_000_000;
+
+fn builtin_context_limits() -> Vec<ModelContextLimit> {
+    let model = |prefix: &str, tokens| ModelContextLimit {
+        prefix: prefix.to_string(),
+        tokens,
+    };
+    vec![
+        model("claude-opus", 200_000),
+        model("claude-sonnet", 200_000),
+        model("claude-haiku", 200_000),
+        model("claude-3", 200_000),
+    ]
 }
 
-const MODEL_PRICING: &[ModelPricing] = &[
-    ModelPricing {
-        prefix: "claude-opus",
-        input: 15.0,
-        output: 75.0,
-        cache_read: 1.5,
-        cache_creation: 18.75,
-    },
-    ModelPricing {
-        prefix: "claude-sonnet",
-        input: 3.0,
-        output: 15.0,
-        cache_read: 0.3,
-        cache_creation: 3.75,
-    },
-    ModelPricing {
-        prefix: "claude-haiku",
-        input: 0.8,
-        output: 4.0,
-        cache_read: 0.08,
-        cache_creation: 1.0,
-    },
-];
-
-fn get_pricing(model: &str) -> &'static ModelPricing {
-    MODEL_PRICING
-        .iter()
-        .find(|m| model.starts_with(m.prefix))
-        .unwrap_or(&MODEL_PRICING[1]) // default: sonnet
+/// How much context each model has, for estimating how full a session's is.
+#[derive(Debug, Clone)]
+pub struct ContextLimits {
+    models: Vec<ModelContextLimit>,
 }
 
-pub fn calculate_cost(
-    model: &str,
-    input_tokens: u64,
-    output_tokens: u64,
-    cache_read_tokens: u64,
-    cache_creation_tokens: u64,
-) -> f64 {
-    let pricing = get_pricing(model);
-    (input_tokens as f64 * pricing.input
-        + output_tokens as f64 * pricing.output
-        + cache_read_tokens as f64 * pricing.cache_read
-        + cache_creation_tokens as f64 * pricing.cache_creation)
-        / 1_000_000.0
+impl ContextLimits {
+    /// The built-in limits, with `overrides` matched before them.
+    pub fn new(overrides: Vec<ModelContextLimit>) -> Self {
+        let mut models = overrides;
+        models.extend(builtin_context_limits());
+        Self { models }
+    }
+
+    /// The context window of `model`, given a prompt of `context_tokens` was just
+    /// sent to it. A `[1m]` suffix, or a prompt larger than the model's usual window,
+    /// means the 1M-token context is in use. None for models not in the table.
+    pub fn get(&self, model: &str, context_tokens: u64) -> Option<u64> {
+        if let Some(model) = model.strip_suffix("[1m]") {
+            return self
+                .find(model)
+                .map(|limit| limit.max(EXTENDED_CONTEXT_TOKENS));
+        }
+        let limit = self.find(model)?;
+        Some(if context_tokens > limit {
+            limit.max(EXTENDED_CONTEXT_TOKENS)
+        } else {
+            limit
+        })
+    }
+
+    fn find(&self, model: &str) -> Option<u64> {
+        self.models
+            .iter()
+            .find(|m| model.starts_with(&m.prefix))
+            .map(|m| m.tokens)
+    }
 }
 
-pub fn add_usage(
-    current: &CumulativeUsage,
-    model: &str,
-    input_tokens: u64,
-    output_tokens: u64,
-    cache_read_tokens: u64,
-    cache_creation_tokens: u64,
-) -> CumulativeUsage {
-    let entry_cost = calculate_cost(model, input_tokens, output_tokens, cache_read_tokens, cache_creation_tokens);
-    CumulativeUsage {
-        input_tokens: current.input_tokens + input_tokens,
-        output_tokens: current.output_tokens + output_tokens,
-        cache_read_tokens: current.cache_read_tokens + cache_read_tokens,
-        cache_creation_tokens: current.cache_creation_tokens + cache_creation_tokens,
-        estimated_cost: current.estimated_cost + entry_cost,
+impl Default for ContextLimits {
+    fn default() -> Self {
+        Self::new(Vec::new())
     }
 }
 
@@ -76,30 +219,43 @@ pub fn add_usage(
 mod tests {
     use super::*;
 
+    fn tokens(input: u64, output: u64, cache_read: u64, cache_creation: u64) -> EntryUsage {
+        EntryUsage {
+            input_tokens: input,
+            output_tokens: output,
+            cache_read_tokens: cache_read,
+            cache_creation_tokens: cache_creation,
+            ..Default::default()
+        }
+    }
+
     #[test]
     fn test_sonnet_cost() {
-        let cost = calculate_cost("claude-sonnet-4-20250514", 1000, 500, 200, 100);
+        let cost = Pricing::default()
+            .calculate_cost("claude-sonnet-4-20250514", &tokens(1000, 500, 200, 100));
         let expected = (1000.0 * 3.0 + 500.0 * 15.0 + 200.0 * 0.3 + 100.0 * 3.75) / 1_000_000.0;
         assert!((cost - expected).abs() < 1e-10);
     }
 
     #[test]
     fn test_opus_cost() {
-        let cost = calculate_cost("claude-opus-4-20250514", 1000, 500, 0, 0);
+        let cost =
+            Pricing::default().calculate_cost("claude-opus-4-20250514", &tokens(1000, 500, 0, 0));
         let expected = (1000.0 * 15.0 + 500.0 * 75.0) / 1_000_000.0;
         assert!((cost - expected).abs() < 1e-10);
     }
 
     #[test]
     fn test_haiku_cost() {
-        let cost = calculate_cost("claude-haiku-3-20250307", 1000, 500, 0, 0);
+        let cost =
+            Pricing::default().calculate_cost("claude-haiku-3-20250307", &tokens(1000, 500, 0, 0));
         let expected = (1000.0 * 0.8 + 500.0 * 4.0) / 1_000_000.0;
         assert!((cost - expected).abs() < 1e-10);
     }
 
     #[test]
     fn test_unknown_model_defaults_to_sonnet() {
-        let cost = calculate_cost("gpt-4", 1000, 500, 0, 0);
+        let cost = Pricing::default().calculate_cost("gpt-4", &tokens(1000, 500, 0, 0));
         let expected = (1000.0 * 3.0 + 500.0 * 15.0) / 1_000_000.0;
         assert!((cost - expected).abs() < 1e-10);
     }
@@ -107,11 +263,136 @@ mod tests {
     #[test]
     fn test_add_usage() {
         let current = CumulativeUsage::default();
-        let updated = add_usage(&current, "claude-sonnet-4-20250514", 100, 200, 50, 25);
+        let updated = Pricing::default().add_usage(
+            &current,
+            "claude-sonnet-4-20250514",
+            &tokens(100, 200, 50, 25),
+        );
         assert_eq!(updated.input_tokens, 100);
         assert_eq!(updated.output_tokens, 200);
         assert_eq!(updated.cache_read_tokens, 50);
         assert_eq!(updated.cache_creation_tokens, 25);
+        assert_eq!(updated.cache_creation_1h_tokens, None);
         assert!(updated.estimated_cost > 0.0);
     }
+
+    #[test]
+    fn test_one_hour_cache_writes_cost_more() {
+        let pricing = Pricing::default();
+        let split = |five_minute_tokens, one_hour_tokens| EntryUsage {
+            cache_creation_ttl: Some(CacheCreationTtl {
+                five_minute_tokens,
+                one_hour_tokens,
+            }),
+            ..tokens(0, 0, 0, five_minute_tokens + one_hour_tokens)
+        };
+        let model = "claude-sonnet-4-5-20250929";
+        let five_minute = pricing.calculate_cost(model, &split(1_000_000, 0));
+        let one_hour = pricing.calculate_cost(model, &split(0, 1_000_000));
+        assert!((five_minute - 3.75).abs() < 1e-10);
+        assert!((one_hour - 6.0).abs() < 1e-10);
+        // Without the split, writes are priced as 5-minute ones, as before.
+        let unsplit = pricing.calculate_cost(model, &tokens(0, 0, 0, 1_000_000));
+        assert!((unsplit - five_minute).abs() < 1e-10);
+        let mixed = pricing.calculate_cost(model, &split(600_000, 400_000));
+        assert!((mixed - (0.6 * 3.75 + 0.4 * 6.0)).abs() < 1e-10);
+        let opus = pricing.calculate_cost("claude-opus-4-1-20250805", &split(0, 1_000_000));
+        assert!((opus - 30.0).abs() < 1e-10);
+
+        // Overrides without a 1-hour price charge twice the input price.
+        let custom = ModelPricing {
+            prefix: "claude-sonnet-5".to_string(),
+            input: 2.0,
+            output: 10.0,
+            cache_read: 0.2,
+            cache_creation: 2.5,
+            cache_creation_1h: None,
+        };
+        let pricing = Pricing::new(vec![custom], DEFAULT_FALLBACK_MODEL).unwrap();
+        let cost = pricing.calculate_cost("claude-sonnet-5-20260101", &split(0, 1_000_000));
+        assert!((cost - 4.0).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_add_usage_counts_ttl_split() {
+        let pricing = Pricing::default();
+        let model = "claude-sonnet-4-5-20250929";
+        let unsplit = pricing.add_usage(&CumulativeUsage::default(), model, &tokens(0, 0, 0, 50));
+        assert_eq!(unsplit.cache_creation_5m_tokens, None);
+        let split = EntryUsage {
+            cache_creation_ttl: Some(CacheCreationTtl {
+                five_minute_tokens: 20,
+                one_hour_tokens: 80,
+            }),
+            ..tokens(0, 0, 0, 100)
+        };
+        let updated = pricing.add_usage(&unsplit, model, &split);
+        let updated = pricing.add_usage(&updated, model, &split);
+        assert_eq!(updated.cache_creation_tokens, 250);
+        assert_eq!(updated.cache_creation_5m_tokens, Some(40));
+        assert_eq!(updated.cache_creation_1h_tokens, Some(160));
+    }
+
+    #[test]
+    fn test_batch_tier_is_half_price() {
+        let pricing = Pricing::default();
+        let tier = |service_tier: &str| EntryUsage {
+            service_tier: Some(service_tier.to_string()),
+            ..tokens(1000, 500, 0, 0)
+        };
+        let model = "claude-sonnet-4-20250514";
+        let standard = pricing.calculate_cost(model, &tier("standard"));
+        assert!((standard - pricing.calculate_cost(model, &tokens(1000, 500, 0, 0))).abs() < 1e-10);
+        assert!((pricing.calculate_cost(model, &tier("batch")) - standard / 2.0).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_overrides_match_first() {
+        let custom = ModelPricing {
+            prefix: "claude-sonnet-4-5".to_string(),
+            input: 1.0,
+            output: 2.0,
+            cache_read: 0.0,
+            cache_creation: 0.0,
+            cache_creation_1h: None,
+        };
+        let pricing = Pricing::new(vec![custom], "claude-haiku").unwrap();
+        let cost = pricing.calculate_cost("claude-sonnet-4-5-20250929", &tokens(1000, 500, 0, 0));
+        assert!((cost - 2000.0 / 1_000_000.0).abs() < 1e-10);
+        // Other sonnet models keep the built-in price; unknown ones use the fallback.
+        let cost = pricing.calculate_cost("claude-sonnet-4-20250514", &tokens(1000, 0, 0, 0));
+        assert!((cost - 3000.0 / 1_000_000.0).abs() < 1e-10);
+        let cost = pricing.calculate_cost("gpt-4", &tokens(1000, 0, 0, 0));
+        assert!((cost - 800.0 / 1_000_000.0).abs() < 1e-10);
+
+        assert!(Pricing::new(Vec::new(), "gpt").is_none());
+    }
+
+    #[test]
+    fn test_context_limits() {
+        let limits = ContextLimits::default();
+        assert_eq!(limits.get("claude-sonnet-4-20250514", 50_000), Some(200_000));
+        assert_eq!(limits.get("claude-opus-4-1-20250805", 150_000), Some(200_000));
+        assert_eq!(limits.get("claude-3-5-haiku-20241022", 0), Some(200_000));
+        assert_eq!(limits.get("gpt-4", 50_000), None);
+    }
+
+    #[test]
+    fn test_context_limits_1m_variants() {
+        let limits = ContextLimits::default();
+        assert_eq!(limits.get("claude-sonnet-4-5-20250929[1m]", 50_000), Some(1_000_000));
+        // The log only names the base model; a prompt past 200k gives the beta away.
+        assert_eq!(limits.get("claude-sonnet-4-5-20250929", 350_000), Some(1_000_000));
+        assert_eq!(limits.get("gpt-4[1m]", 50_000), None);
+    }
+
+    #[test]
+    fn test_context_limit_overrides_match_first() {
+        let limits = ContextLimits::new(vec![ModelContextLimit {
+            prefix: "claude-sonnet-5".to_string(),
+            tokens: 500_000,
+        }]);
+        assert_eq!(limits.get("claude-sonnet-5-20260101", 0), Some(500_000));
+        assert_eq!(limits.get("claude-sonnet-4-20250514", 0), Some(200_000));
+    }
 }
@@ -0,0 +1,174 @@
+//! Calendar days in the dashboard's time zone (`DASHBOARD_TIMEZONE`). A day is the
+//! zoned date as days since 1970-01-01, so an index names the same date whatever
+//! the zone; only which instants fall on it depends on the zone.
+
+use chrono::{DateTime, NaiveDate, TimeDelta, TimeZone, Utc};
+use chrono_tz::Tz;
+
+pub const DAY_MS: i64 = 24 * 60 * 60_000;
+
+/// An IANA zone name such as `Asia/Tokyo`.
+pub fn parse_zone(name: &str) -> Result<Tz, String> {
+    name.trim()
+        .parse()
+        .map_err(|_| format!("unknown time zone `{}`", name.trim()))
+}
+
+/// The system's zone, or UTC if it can't be told.
+pub fn system_zone() -> Tz {
+    iana_time_zone::get_timezone()
+        .ok()
+        .and_then(|name| name.parse().ok())
+        .unwrap_or(Tz::UTC)
+}
+
+/// Days since the epoch for `date`.
+pub fn day_index(date: NaiveDate) -> i64 {
+    (date - DateTime::UNIX_EPOCH.date_naive()).num_days()
+}
+
+/// The date of a day since the epoch.
+pub fn date(day: i64) -> NaiveDate {
+    TimeDelta::try_days(day)
+        .and_then(|days| DateTime::UNIX_EPOCH.date_naive().checked_add_signed(days))
+        .unwrap_or(NaiveDate::MIN)
+}
+
+/// The day `ms` (since the epoch) falls on in `tz`.
+pub fn day_of(ms: i64, tz: Tz) -> i64 {
+    DateTime::from_timestamp_millis(ms).map_or(ms.div_euclid(DAY_MS), |at| {
+        day_index(at.with_timezone(&tz).date_naive())
+    })
+}
+
+/// The date in `tz` as of `now`.
+pub fn today(now: DateTime<Utc>, tz: Tz) -> NaiveDate {
+    now.with_timezone(&tz).date_naive()
+}
+
+/// The first instant of `day` in `tz`: midnight, unless a clock change skips it.
+pub fn day_start_ms(day: i64, tz: Tz) -> i64 {
+    let midnight = date(day).and_hms_opt(0, 0, 0).unwrap_or_default();
+    (0..=16)
+        .find_map(|quarter| {
+            tz.from_local_datetime(&(midnight + TimeDelta::minutes(15 * quarter)))
+                .earliest()
+        })
+        .map_or(day * DAY_MS, |start| start.timestamp_millis())
+}
+
+/// `start_ms..end_ms` cut at midnights in `tz`, as (day, millis) pairs.
+pub fn split_by_day(start_ms: i64, end_ms: i64, tz: Tz) -> Vec<(i64, i64)> {
+    let mut parts = Vec::new();
+    let mut from = start_ms;
+    while from < end_ms {
+        let day = day_of(from, tz);
+        let to = end_ms.min(day_start_ms(day + 1, tz).max(from + 1));
+        parts.push((day, to - from));
+        from = to;
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(timestamp: &str) -> i64 {
+        timestamp
+            .parse::<DateTime<Utc>>()
+            .unwrap()
+            .timestamp_millis()
+    }
+
+    fn day(date: &str) -> i64 {
+        day_index(date.parse().unwrap())
+    }
+
+    #[test]
+    fn test_utc_plus_nine_midnight() {
+        let tokyo = parse_zone("Asia/Tokyo").unwrap();
+        // 23:59:59 and 00:00 in Tokyo.
+        assert_eq!(day_of(ms("2025-03-01T14:59:59Z"), tokyo), day("2025-03-01"));
+        assert_eq!(day_of(ms("2025-03-01T15:00:00Z"), tokyo), day("2025-03-02"));
+        assert_eq!(
+            day_of(ms("2025-03-01T15:00:00Z"), Tz::UTC),
+            day("2025-03-01")
+        );
+        assert_eq!(
+            day_start_ms(day("2025-03-02"), tokyo),
+            ms("2025-03-01T15:00:00Z")
+        );
+        assert_eq!(
+            today("2025-03-01T20:00:00Z".parse().unwrap(), tokyo).to_string(),
+            "2025-03-02"
+        );
+
+        // An evening session is one day in Tokyo and two in UTC.
+        let (start, end) = (ms("2025-03-01T11:00:00Z"), ms("2025-03-01T14:30:00Z"));
+        assert_eq!(
+            split_by_day(start, end, tokyo),
+            [(day("2025-03-01"), end - start)]
+        );
+        let (start, end) = (ms("2025-03-01T14:00:00Z"), ms("2025-03-01T16:00:00Z"));
+        assert_eq!(
+            split_by_day(start, end, tokyo),
+            [
+                (day("2025-03-01"), 3_600_000),
+                (day("2025-03-02"), 3_600_000)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_days_across_dst_transitions() {
+        let new_york = parse_zone("America/New_York").unwrap();
+        // Clocks go forward on 2025-03-09 and back on 2025-11-02.
+        let spring = day("2025-03-09");
+        assert_eq!(day_start_ms(spring, new_york), ms("2025-03-09T05:00:00Z"));
+        assert_eq!(
+            day_start_ms(spring + 1, new_york) - day_start_ms(spring, new_york),
+            23 * 3_600_000
+        );
+        let autumn = day("2025-11-02");
+        assert_eq!(
+            day_start_ms(autumn + 1, new_york) - day_start_ms(autumn, new_york),
+            25 * 3_600_000
+        );
+        // 23:30 local on either side of the change.
+        assert_eq!(day_of(ms("2025-11-03T04:30:00Z"), new_york), autumn);
+        assert_eq!(day_of(ms("2025-11-03T05:00:00Z"), new_york), autumn + 1);
+
+        let parts = split_by_day(
+            ms("2025-03-08T23:00:00Z"),
+            ms("2025-03-10T05:00:00Z"),
+            new_york,
+        );
+        assert_eq!(
+            parts,
+            [
+                (spring - 1, 6 * 3_600_000),
+                (spring, 23 * 3_600_000),
+                (spring + 1, 3_600_000),
+            ]
+        );
+
+        // Where midnight itself is skipped, the day starts when clocks resume.
+        let santiago = parse_zone("America/Santiago").unwrap();
+        assert_eq!(
+            day_start_ms(day("2025-09-07"), santiago),
+            ms("2025-09-07T04:00:00Z")
+        );
+    }
+
+    #[test]
+    fn test_zone_names() {
+        assert_eq!(parse_zone(" Europe/Berlin ").unwrap(), Tz::Europe__Berlin);
+        assert_eq!(
+            parse_zone("Mars/Olympus").unwrap_err(),
+            "unknown time zone `Mars/Olympus`"
+        );
+        assert_eq!(date(day("2024-02-29")).to_string(), "2024-02-29");
+        assert_eq!(day("1970-01-01"), 0);
+    }
+}
@@ -1,13 +1,33 @@
+mod analysis;
+mod config;
 mod cost;
+mod days;
+mod notifications;
 mod providers;
+mod schema;
 mod server;
 mod session;
+#[cfg(test)]
+mod testing;
 mod types;
 
-use providers::ProviderEvent;
+use arc_swap::ArcSwap;
+use config::ConfigSource;
+use notifications::ntfy::NtfyConfig;
+use notifications::Notifier;
+use server::connections::ConnectionRegistry;
 use server::http::{create_router, AppState};
+use server::reload::spawn_watchers;
+use session::archive::ArchiveStore;
+use session::diff_snapshots::DiffSnapshotStore;
+use session::journal::EventJournal;
 use session::manager::SessionManager;
+use session::prefs::PrefsStore;
+use session::read_marks::ReadMarkStore;
+use session::project_usage::ProjectUsageStore;
+use session::summarizer::Summarizer;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::broadcast;
 use tracing::info;
 use types::ServerEvent;
@@ -21,27 +41,102 @@ async fn main() {
         )
         .init();
 
-    let port: u16 = std::env::var("PORT")
-        .ok()
-        .and_then(|p| p.parse().ok())
-        .unwrap_or(3001);
+    let (config, config_source) =
+        match ConfigSource::from_args(std::env::args().skip(1)).and_then(|source| {
+            let config = source.load()?;
+            Ok((config, source))
+        }) {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                eprintln!("Invalid configuration: {}", e);
+                std::process::exit(2);
+            }
+        };
+    if let Some(path) = config_source.path.as_ref().filter(|path| path.is_file()) {
+        info!("[Config] Loaded {}", path.display());
+    }
+    let port = config.server.port;
+    let event_flush = Duration::from_millis(config.server.event_flush_ms);
+
+    let notifier = Arc::new(Notifier::new(NtfyConfig::from_section(
+        &config.notifications.ntfy,
+        &config.dashboard_url(),
+    )));
 
     // Broadcast channels
-    let (broadcast_tx, _) = broadcast::channel::<ServerEvent>(256);
-    let (message_tx, _) = broadcast::channel::<ServerEvent>(1024);
+    let (broadcast_tx, _) = broadcast::channel::<ServerEvent>(config.server.broadcast_capacity);
+    let (message_tx, _) = broadcast::channel::<ServerEvent>(config.server.message_capacity);
 
     // Session manager
-    let session_manager = Arc::new(SessionManager::new());
+    let session_manager = Arc::new(SessionManager::with_config(
+        PrefsStore::load_in(&config.data_dir()),
+        ProjectUsageStore::load_in(&config.data_dir()),
+        ReadMarkStore::load_in(&config.data_dir()),
+        config.provider_config(),
+    ));
+    session_manager.set_budgets(config.cost.budgets.clone());
+    session_manager.set_diff_snapshots(config.diff_snapshot_config().map(DiffSnapshotStore::new));
+    session_manager.set_archive(config.archive_config().map(ArchiveStore::load));
+    session_manager.set_summarizer(config.summary_config().map(Summarizer::new));
+    let journal = config
+        .journal_config()
+        .map(|config| Arc::new(EventJournal::start(config)));
+    // Route provider events to broadcast channels. This starts first so nothing the
+    // providers find at startup sits in the queue while they're still starting.
+    server::routing::spawn(
+        session_manager.clone(),
+        broadcast_tx.clone(),
+        message_tx.clone(),
+        journal.clone(),
+        notifier.clone(),
+        event_flush,
+    );
     session_manager.start().await;
 
+    // Project totals change with every usage update; write them out periodically.
+    let sm_save = session_manager.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(30));
+        loop {
+            interval.tick().await;
+            sm_save.save_project_usage().await;
+        }
+    });
+
+    // Sessions that have been stopped long enough move to the archive.
+    let sm_archive = session_manager.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            sm_archive.archive_stopped().await;
+        }
+    });
+
+    // Restart discovery or watchers that stopped without the process noticing.
+    let sm_watchdog = session_manager.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(session::watchdog::CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            sm_watchdog.run_watchdog().await;
+        }
+    });
+
     // Frontend dist path
-    let frontend_dist = ["packages/frontend/build", "../frontend/build"]
-        .iter()
-        .map(std::path::PathBuf::from)
-        .find(|p| p.join("index.html").exists())
-        .map(|p| p.to_string_lossy().to_string());
+    let frontend_dist = if cfg!(feature = "embed-frontend") {
+        None
+    } else {
+        ["packages/frontend/build", "../frontend/build"]
+            .iter()
+            .map(std::path::PathBuf::from)
+            .find(|p| p.join("index.html").exists())
+            .map(|p| p.to_string_lossy().to_string())
+    };
 
-    if let Some(ref dist) = frontend_dist {
+    if cfg!(feature = "embed-frontend") {
+        info!("Serving embedded frontend");
+    } else if let Some(ref dist) = frontend_dist {
         info!("Serving frontend from {}", dist);
     } else {
         info!("No frontend build found, serving API only");
@@ -51,82 +146,21 @@ async fn main() {
         session_manager: session_manager.clone(),
         broadcast_tx: broadcast_tx.clone(),
         message_tx: message_tx.clone(),
+        limits: Arc::new(config.api_limits()),
+        journal,
+        connections: Arc::new(ConnectionRegistry::new()),
+        config: ArcSwap::from_pointee(config),
+        config_source,
+        notifier: notifier.clone(),
+        schema: Arc::new(schema::combined()),
     });
+    spawn_watchers(state.clone());
+    if let Some(relay) = state.config.load().relay_config() {
+        server::relay::spawn(relay, session_manager.clone(), &broadcast_tx, &message_tx);
+    }
 
     let app = create_router(state, frontend_dist);
 
-    // Route provider events to broadcast channels
-    let broadcast_tx_route = broadcast_tx.clone();
-    let message_tx_route = message_tx.clone();
-    let sm_route = session_manager.clone();
-    tokio::spawn(async move {
-        let mut event_rx = sm_route.event_rx.lock().await;
-        while let Some(event) = event_rx.recv().await {
-            let server_event = match &event {
-                ProviderEvent::SessionDiscovered { session } => {
-                    info!(
-                        "[Session] Discovered: {} ({})",
-                        session.session_id, session.project_name
-                    );
-                    Some(ServerEvent::SessionDiscovered {
-                        session: session.clone(),
-                    })
-                }
-                ProviderEvent::SessionRemoved { session_id } => {
-                    info!("[Session] Removed: {}", session_id);
-                    Some(ServerEvent::SessionRemoved {
-                        session_id: session_id.clone(),
-                    })
-                }
-                ProviderEvent::StateChanged {
-                    session_id,
-                    previous,
-                    current,
-                } => {
-                    info!("[Session] {}: {} → {}", session_id, previous, current);
-                    if let Some(session) = sm_route.get_session_summary(session_id).await {
-                        Some(ServerEvent::StateChanged {
-                            session_id: session_id.clone(),
-                            previous: *previous,
-                            current: *current,
-                            session,
-                        })
-                    } else {
-                        None
-                    }
-                }
-                ProviderEvent::UsageUpdated { session_id, usage } => {
-                    Some(ServerEvent::UsageUpdated {
-                        session_id: session_id.clone(),
-                        usage: usage.clone(),
-                    })
-                }
-                ProviderEvent::NewMessage {
-                    session_id,
-                    message,
-                } => {
-                    let msg_event = ServerEvent::NewMessage {
-                        session_id: session_id.clone(),
-                        message: message.clone(),
-                    };
-                    let _ = message_tx_route.send(msg_event);
-                    None
-                }
-                ProviderEvent::GitStatusUpdated {
-                    session_id,
-                    git_status,
-                } => Some(ServerEvent::GitStatusUpdated {
-                    session_id: session_id.clone(),
-                    git_status: git_status.clone(),
-                }),
-            };
-
-            if let Some(evt) = server_event {
-                let _ = broadcast_tx_route.send(evt);
-            }
-        }
-    });
-
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", port))
         .await
         .expect("Failed to bind");
@@ -145,5 +179,10 @@ async fn main() {
         std::process::exit(0);
     });
 
-    axum::serve(listener, app).await.expect("Server error");
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .await
+    .expect("Server error");
 }
@@ -0,0 +1,80 @@
+pub mod ntfy;
+
+use crate::types::{AgentStateType, ServerEvent};
+use arc_swap::ArcSwapOption;
+use ntfy::{NtfyConfig, NtfySink};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+/// Which of a session's events are pushed: the `[notifications.ntfy]` rules, or a
+/// project's own.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotifyRules {
+    pub states: Vec<AgentStateType>,
+    pub context_warnings: bool,
+}
+
+impl Default for NotifyRules {
+    fn default() -> Self {
+        Self {
+            states: vec![AgentStateType::PermissionWaiting, AgentStateType::Error],
+            context_warnings: true,
+        }
+    }
+}
+
+/// Notifier forwards state changes and context warnings to the configured push sinks.
+pub struct Notifier {
+    ntfy: ArcSwapOption<NtfySink>,
+}
+
+impl Notifier {
+    pub fn new(ntfy: Option<NtfyConfig>) -> Self {
+        let notifier = Self {
+            ntfy: ArcSwapOption::empty(),
+        };
+        notifier.reconfigure(ntfy);
+        notifier
+    }
+
+    /// Replace the sinks, e.g. after a config reload. Notifications already being
+    /// sent finish with the old settings.
+    pub fn reconfigure(&self, ntfy: Option<NtfyConfig>) {
+        let ntfy = ntfy.map(|config| {
+            info!("[Notify] ntfy enabled: {}/{}", config.server, config.topic);
+            Arc::new(NtfySink::new(config))
+        });
+        self.ntfy.store(ntfy);
+    }
+
+    /// Inspect a server event and publish push notifications for it, by the session's
+    /// `rules` if it has its own. Sending happens on spawned tasks so the event routing
+    /// loop never waits on the network.
+    pub fn notify(&self, event: &ServerEvent, rules: Option<&NotifyRules>) {
+        let Some(sink) = self.ntfy.load_full() else {
+            return;
+        };
+        let wants = |state| rules.map_or_else(|| sink.wants(state), |r| r.states.contains(&state));
+        let wants_context_warnings =
+            rules.map_or_else(|| sink.wants_context_warnings(), |r| r.context_warnings);
+        let message = match event {
+            ServerEvent::StateChanged {
+                previous,
+                current,
+                session,
+                ..
+            } if previous != current && wants(*current) => sink.build_message(session),
+            ServerEvent::ContextWarning {
+                percent_used,
+                session,
+                ..
+            } if wants_context_warnings => sink.build_context_warning(session, *percent_used),
+            _ => return,
+        };
+        tokio::spawn(async move {
+            if let Err(e) = sink.send(&message).await {
+                warn!("[Notify] ntfy publish failed: {}", e);
+            }
+        });
+    }
+}
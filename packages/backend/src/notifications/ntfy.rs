@@ -0,0 +1,395 @@
+use crate::config::NtfySection;
+use crate::types::{AgentSessionSummary, AgentStateType, PendingTool};
+use serde::Serialize;
+
+#[derive(Debug, Clone)]
+pub struct NtfyConfig {
+    pub server: String,
+    pub topic: String,
+    pub token: Option<String>,
+    pub states: Vec<AgentStateType>,
+    pub context_warnings: bool,
+    pub dashboard_url: String,
+}
+
+impl NtfyConfig {
+    /// Returns None when no topic is configured.
+    pub fn from_section(section: &NtfySection, dashboard_url: &str) -> Option<Self> {
+        let topic = section.topic.as_deref().filter(|t| !t.trim().is_empty())?;
+        Some(Self {
+            server: section.server.trim_end_matches('/').to_string(),
+            topic: topic.trim().to_string(),
+            token: section.token.clone().filter(|t| !t.trim().is_empty()),
+            states: section.states.clone(),
+            context_warnings: section.context_warnings,
+            dashboard_url: dashboard_url.trim_end_matches('/').to_string(),
+        })
+    }
+}
+
+/// JSON body for ntfy's publish-as-JSON API.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NtfyMessage {
+    pub topic: String,
+    pub title: String,
+    pub message: String,
+    pub priority: u8,
+    pub tags: Vec<String>,
+    pub click: String,
+}
+
+/// ntfy priorities: 1=min, 2=low, 3=default, 4=high, 5=max.
+fn priority_for(state: AgentStateType) -> u8 {
+    match state {
+        AgentStateType::Error => 4,
+        AgentStateType::PermissionWaiting => 3,
+        AgentStateType::Idle | AgentStateType::Stopped => 2,
+        AgentStateType::Running => 1,
+    }
+}
+
+fn tag_for(state: AgentStateType) -> &'static str {
+    match state {
+        AgentStateType::Error => "rotating_light",
+        AgentStateType::PermissionWaiting => "raised_hand",
+        AgentStateType::Idle => "white_check_mark",
+        AgentStateType::Stopped => "stop_sign",
+        AgentStateType::Running => "arrow_forward",
+    }
+}
+
+/// `Wants to run `npm test``, `Wants to use Edit on `src/main.rs``.
+fn pending_tool_message(tool: &PendingTool) -> String {
+    match (tool.name.as_str(), tool.summary.as_str()) {
+        (_, "") => format!("Wants to use {}", tool.name),
+        ("Bash", command) => format!("Wants to run `{}`", command),
+        (name, summary) => format!("Wants to use {} on `{}`", name, summary),
+    }
+}
+
+pub fn build_message(config: &NtfyConfig, session: &AgentSessionSummary) -> NtfyMessage {
+    let message = match &session.pending_tool {
+        Some(tool) if session.state == AgentStateType::PermissionWaiting => {
+            pending_tool_message(tool)
+        }
+        _ if session.initial_task.is_empty() => format!("Session is now {}", session.state),
+        _ => session.initial_task.clone(),
+    };
+
+    NtfyMessage {
+        topic: config.topic.clone(),
+        title: session.project_name.clone(),
+        message,
+        priority: priority_for(session.state),
+        tags: vec![
+            tag_for(session.state).to_string(),
+            session.state.to_string(),
+        ],
+        click: format!("{}/agent/{}", config.dashboard_url, session.session_id),
+    }
+}
+
+/// The session's context passed `percent_used`% and will soon be compacted, losing
+/// detail the agent was working with.
+pub fn build_context_warning(
+    config: &NtfyConfig,
+    session: &AgentSessionSummary,
+    percent_used: u32,
+) -> NtfyMessage {
+    NtfyMessage {
+        topic: config.topic.clone(),
+        title: session.project_name.clone(),
+        message: format!("Context {}% full; it will be compacted soon", percent_used),
+        priority: 3,
+        tags: vec!["warning".to_string(), "context".to_string()],
+        click: format!("{}/agent/{}", config.dashboard_url, session.session_id),
+    }
+}
+
+pub struct NtfySink {
+    config: NtfyConfig,
+    client: reqwest::Client,
+}
+
+impl NtfySink {
+    pub fn new(config: NtfyConfig) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn wants(&self, state: AgentStateType) -> bool {
+        self.config.states.contains(&state)
+    }
+
+    pub fn build_message(&self, session: &AgentSessionSummary) -> NtfyMessage {
+        build_message(&self.config, session)
+    }
+
+    pub fn wants_context_warnings(&self) -> bool {
+        self.config.context_warnings
+    }
+
+    pub fn build_context_warning(
+        &self,
+        session: &AgentSessionSummary,
+        percent_used: u32,
+    ) -> NtfyMessage {
+        build_context_warning(&self.config, session, percent_used)
+    }
+
+    pub async fn send(&self, message: &NtfyMessage) -> Result<(), reqwest::Error> {
+        let mut request = self.client.post(&self.config.server).json(message);
+        if let Some(token) = &self.config.token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{CumulativeUsage, GitStatus};
+
+    fn make_summary(state: AgentStateType, initial_task: &str) -> AgentSessionSummary {
+        AgentSessionSummary {
+            session_id: "s1".into(),
+            provider: "claude-code".into(),
+            state,
+            project_path: "/Users/me/agents-dashboard".into(),
+            project_name: "agents-dashboard".into(),
+            working_directory: "/Users/me/agents-dashboard".into(),
+            current_cwd: "/Users/me/agents-dashboard".into(),
+            initial_task: initial_task.into(),
+            latest_task: initial_task.into(),
+            latest_assistant_text: None,
+            model: "claude-sonnet-4-20250514".into(),
+            last_activity_at: "2025-01-01T00:00:00Z".into(),
+            started_at: "2025-01-01T00:00:00Z".into(),
+            cumulative_usage: CumulativeUsage::default(),
+            git_status: GitStatus::default(),
+            pinned: false,
+            note: None,
+            labels: vec![],
+            summary: None,
+            permission_mode: None,
+            resumed_from: None,
+            spend_rate_per_hour: 0.0,
+            tokens_per_minute: 0.0,
+            context_tokens: 0,
+            context_limit: None,
+            cli_version: None,
+            title: None,
+            top_tools: Vec::new(),
+            replay: false,
+            historical: false,
+            host: "laptop".into(),
+            activity_type: None,
+            pending_tool: None,
+            stop_reason: None,
+            hook_errors: 0,
+            failed_turns: 0,
+            retry_count: 0,
+            active_duration_ms: 0,
+            message_count: 0,
+            tool_call_count: 0,
+            transcript_bytes: 0,
+            source_count: 0,
+            environment: None,
+        }
+    }
+
+    fn make_config(server: &str, token: Option<&str>) -> NtfyConfig {
+        NtfyConfig {
+            server: server.into(),
+            topic: "agents".into(),
+            token: token.map(String::from),
+            states: vec![AgentStateType::PermissionWaiting, AgentStateType::Error],
+            context_warnings: true,
+            dashboard_url: "http://localhost:3001".into(),
+        }
+    }
+
+    #[test]
+    fn test_config_requires_topic() {
+        let section = NtfySection {
+            server: "https://ntfy.example".into(),
+            ..Default::default()
+        };
+        assert!(NtfyConfig::from_section(&section, "").is_none());
+    }
+
+    #[test]
+    fn test_config_defaults() {
+        let section = NtfySection {
+            topic: Some("agents".into()),
+            ..Default::default()
+        };
+        let config = NtfyConfig::from_section(&section, "http://localhost:3001/").unwrap();
+        assert_eq!(config.server, "https://ntfy.sh");
+        assert_eq!(config.topic, "agents");
+        assert!(config.token.is_none());
+        assert_eq!(
+            config.states,
+            vec![AgentStateType::PermissionWaiting, AgentStateType::Error]
+        );
+        assert_eq!(config.dashboard_url, "http://localhost:3001");
+    }
+
+    #[test]
+    fn test_config_overrides() {
+        let section = NtfySection {
+            topic: Some(" agents ".into()),
+            server: "https://ntfy.example/".into(),
+            token: Some("tk_secret".into()),
+            states: vec![AgentStateType::Idle, AgentStateType::Error],
+            context_warnings: false,
+        };
+        let config = NtfyConfig::from_section(&section, "").unwrap();
+        assert_eq!(config.server, "https://ntfy.example");
+        assert_eq!(config.topic, "agents");
+        assert_eq!(config.token.as_deref(), Some("tk_secret"));
+        assert_eq!(
+            config.states,
+            vec![AgentStateType::Idle, AgentStateType::Error]
+        );
+        assert!(!config.context_warnings);
+    }
+
+    #[test]
+    fn test_build_message_error() {
+        let config = make_config("https://ntfy.sh", None);
+        let msg = build_message(&config, &make_summary(AgentStateType::Error, "Fix the bug"));
+        assert_eq!(msg.topic, "agents");
+        assert_eq!(msg.title, "agents-dashboard");
+        assert_eq!(msg.message, "Fix the bug");
+        assert_eq!(msg.priority, 4);
+        assert_eq!(msg.click, "http://localhost:3001/agent/s1");
+        assert!(msg.tags.contains(&"error".to_string()));
+    }
+
+    #[test]
+    fn test_build_message_permission_waiting_default_priority() {
+        let config = make_config("https://ntfy.sh", None);
+        let msg = build_message(
+            &config,
+            &make_summary(AgentStateType::PermissionWaiting, "Run tests"),
+        );
+        assert_eq!(msg.priority, 3);
+        assert_eq!(msg.message, "Run tests");
+    }
+
+    #[test]
+    fn test_build_message_names_pending_tool() {
+        let config = make_config("https://ntfy.sh", None);
+        let mut session = make_summary(AgentStateType::PermissionWaiting, "Clean up");
+        let tool = |name: &str, summary: &str| PendingTool {
+            tool_use_id: "toolu_1".into(),
+            name: name.into(),
+            summary: summary.into(),
+        };
+        session.pending_tool = Some(tool("Bash", "rm -rf node_modules"));
+        assert_eq!(
+            build_message(&config, &session).message,
+            "Wants to run `rm -rf node_modules`"
+        );
+        session.pending_tool = Some(tool("Edit", "src/main.rs"));
+        assert_eq!(
+            build_message(&config, &session).message,
+            "Wants to use Edit on `src/main.rs`"
+        );
+        session.pending_tool = Some(tool("mcp__github__create_issue", ""));
+        assert_eq!(
+            build_message(&config, &session).message,
+            "Wants to use mcp__github__create_issue"
+        );
+    }
+
+    #[test]
+    fn test_build_message_empty_task_falls_back() {
+        let config = make_config("https://ntfy.sh", None);
+        let msg = build_message(&config, &make_summary(AgentStateType::Idle, ""));
+        assert_eq!(msg.message, "Session is now idle");
+        assert_eq!(msg.priority, 2);
+    }
+
+    #[test]
+    fn test_build_context_warning() {
+        let config = make_config("https://ntfy.sh", None);
+        let msg = build_context_warning(&config, &make_summary(AgentStateType::Running, ""), 81);
+        assert_eq!(msg.title, "agents-dashboard");
+        assert_eq!(msg.message, "Context 81% full; it will be compacted soon");
+        assert_eq!(msg.priority, 3);
+        assert_eq!(msg.click, "http://localhost:3001/agent/s1");
+    }
+
+    #[test]
+    fn test_message_json_shape() {
+        let config = make_config("https://ntfy.sh", None);
+        let msg = build_message(&config, &make_summary(AgentStateType::Error, "task"));
+        let json = serde_json::to_value(&msg).unwrap();
+        for key in ["topic", "title", "message", "priority", "tags", "click"] {
+            assert!(json.get(key).is_some(), "missing {}", key);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_to_mock_server() {
+        use axum::{http::HeaderMap, routing::post, Json, Router};
+        use tokio::sync::mpsc;
+
+        let (tx, mut rx) = mpsc::unbounded_channel::<(Option<String>, serde_json::Value)>();
+        let app = Router::new().route(
+            "/",
+            post(
+                move |headers: HeaderMap, Json(body): Json<serde_json::Value>| {
+                    let tx = tx.clone();
+                    async move {
+                        let auth = headers
+                            .get("authorization")
+                            .and_then(|v| v.to_str().ok())
+                            .map(String::from);
+                        let _ = tx.send((auth, body));
+                        "ok"
+                    }
+                },
+            ),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let sink = NtfySink::new(make_config(&format!("http://{}", addr), Some("tk_secret")));
+        let msg = sink.build_message(&make_summary(AgentStateType::Error, "Fix the bug"));
+        sink.send(&msg).await.unwrap();
+
+        let (auth, body) = rx.recv().await.unwrap();
+        assert_eq!(auth.as_deref(), Some("Bearer tk_secret"));
+        assert_eq!(body["topic"], "agents");
+        assert_eq!(body["title"], "agents-dashboard");
+        assert_eq!(body["message"], "Fix the bug");
+        assert_eq!(body["priority"], 4);
+        assert_eq!(body["click"], "http://localhost:3001/agent/s1");
+    }
+
+    #[tokio::test]
+    async fn test_send_reports_http_errors() {
+        use axum::{http::StatusCode, routing::post, Router};
+
+        let app = Router::new().route("/", post(|| async { StatusCode::FORBIDDEN }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let sink = NtfySink::new(make_config(&format!("http://{}", addr), None));
+        let msg = sink.build_message(&make_summary(AgentStateType::Error, "task"));
+        assert!(sink.send(&msg).await.is_err());
+    }
+}
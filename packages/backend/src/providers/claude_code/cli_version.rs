@@ -0,0 +1,69 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::sync::{Mutex, OnceLock};
+use tracing::warn;
+
+/// Claude Code versions whose JSONL format the parser has been checked against.
+pub const TESTED_VERSIONS: &[&str] = &["1.0.0", "1.0.128", "2.0.0", "2.0.14"];
+
+/// Compare dotted version strings numerically (`2.0.10` > `2.0.9`). Non-numeric
+/// parts such as `-beta` are ignored.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let parts = |v: &str| -> Vec<u64> {
+        v.split(['.', '-', '+'])
+            .map_while(|p| p.parse().ok())
+            .collect()
+    };
+    let (a, b) = (parts(a), parts(b));
+    for i in 0..a.len().max(b.len()) {
+        let ord = a.get(i).unwrap_or(&0).cmp(b.get(i).unwrap_or(&0));
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+    Ordering::Equal
+}
+
+/// The version is newer than every tested version.
+pub fn is_untested(version: &str) -> bool {
+    TESTED_VERSIONS
+        .iter()
+        .all(|known| compare_versions(version, known) == Ordering::Greater)
+}
+
+/// Log a warning the first time an untested version is seen; parse errors tend to
+/// start with a new CLI release.
+pub fn warn_if_untested(version: &str) {
+    static WARNED: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+    if !is_untested(version) {
+        return;
+    }
+    let mut warned = WARNED.get_or_init(Default::default).lock().unwrap();
+    if warned.insert(version.to_string()) {
+        warn!(
+            "[ClaudeCode] CLI version {} is newer than the parser was tested against (latest {})",
+            version,
+            TESTED_VERSIONS.last().unwrap()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_versions_numerically() {
+        assert_eq!(compare_versions("2.0.10", "2.0.9"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0", "1.0.0"), Ordering::Equal);
+        assert_eq!(compare_versions("2.0.14-beta", "2.0.14"), Ordering::Equal);
+        assert_eq!(compare_versions("0.9.1", "1.0.0"), Ordering::Less);
+    }
+
+    #[test]
+    fn test_is_untested() {
+        assert!(is_untested("9.0.0"));
+        assert!(!is_untested("2.0.14"));
+        assert!(!is_untested("1.0.50"));
+    }
+}
@@ -0,0 +1,70 @@
+use std::time::Instant;
+
+/// Source of time for the state machine, so timeouts can be tested without waiting.
+pub trait Clock: Send + Sync + std::fmt::Debug {
+    /// Wall-clock time in millis since the epoch, comparable with log timestamps.
+    fn now_millis(&self) -> i64;
+    /// Monotonic time for measuring how long something took; unaffected by clock
+    /// changes (NTP adjustments, the machine sleeping and waking up).
+    fn now_instant(&self) -> Instant;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_millis(&self) -> i64 {
+        chrono::Utc::now().timestamp_millis()
+    }
+
+    fn now_instant(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves when told to.
+#[cfg(test)]
+#[derive(Debug)]
+pub struct ManualClock {
+    start_millis: i64,
+    start: Instant,
+    elapsed: std::sync::Mutex<std::time::Duration>,
+    /// Total wall-clock corrections, in millis.
+    wall_jump: std::sync::atomic::AtomicI64,
+}
+
+#[cfg(test)]
+impl ManualClock {
+    /// Starts at the current wall-clock time.
+    pub fn new() -> Self {
+        Self {
+            start_millis: chrono::Utc::now().timestamp_millis(),
+            start: Instant::now(),
+            elapsed: std::sync::Mutex::new(std::time::Duration::ZERO),
+            wall_jump: std::sync::atomic::AtomicI64::new(0),
+        }
+    }
+
+    pub fn advance(&self, by: std::time::Duration) {
+        *self.elapsed.lock().unwrap() += by;
+    }
+
+    /// Move the wall clock by `by_ms` without any time passing, as an NTP correction would.
+    pub fn jump_wall_clock(&self, by_ms: i64) {
+        self.wall_jump
+            .fetch_add(by_ms, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+impl Clock for ManualClock {
+    fn now_millis(&self) -> i64 {
+        self.start_millis
+            + self.wall_jump.load(std::sync::atomic::Ordering::Relaxed)
+            + self.elapsed.lock().unwrap().as_millis() as i64
+    }
+
+    fn now_instant(&self) -> Instant {
+        self.start + *self.elapsed.lock().unwrap()
+    }
+}
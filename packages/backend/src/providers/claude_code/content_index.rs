@@ -0,0 +1,218 @@
+use crate::types::AgentMessage;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+
+/// Only this much of a message is indexed; longer text is cut off.
+const MAX_INDEXED_CHARS: usize = 200_000;
+/// Posting entries kept per session before the oldest messages are evicted (~4 bytes each).
+const MAX_POSTINGS: usize = 1_000_000;
+
+/// Trigram index over the full text of messages whose stored content was truncated.
+///
+/// Messages are identified by their per-session sequence number. A hit means every
+/// trigram of the query occurs in the message, which can occasionally match text
+/// where they aren't contiguous; the full text isn't kept, so it can't be verified.
+///
+/// Indexed messages that are trimmed out of the session's message buffer are kept
+/// here (with their truncated content) until evicted, so they stay searchable.
+#[derive(Debug, Clone)]
+pub struct ContentIndex {
+    postings: HashMap<u32, Vec<u64>>,
+    /// Indexed messages, oldest first, with their number of postings.
+    indexed: VecDeque<(u64, usize)>,
+    /// Indexed messages that have left the message buffer.
+    trimmed: BTreeMap<u64, Arc<AgentMessage>>,
+    total: usize,
+    max_postings: usize,
+}
+
+impl ContentIndex {
+    pub fn new() -> Self {
+        Self::with_max_postings(MAX_POSTINGS)
+    }
+
+    fn with_max_postings(max_postings: usize) -> Self {
+        Self {
+            postings: HashMap::new(),
+            indexed: VecDeque::new(),
+            trimmed: BTreeMap::new(),
+            total: 0,
+            max_postings,
+        }
+    }
+
+    /// Index the full text of message `seq`. Sequence numbers must increase.
+    pub fn add(&mut self, seq: u64, full_text: &str) {
+        let trigrams = trigrams(full_text.chars().take(MAX_INDEXED_CHARS));
+        let count = trigrams.len();
+        for t in trigrams {
+            self.postings.entry(t).or_default().push(seq);
+        }
+        self.indexed.push_back((seq, count));
+        self.total += count;
+
+        if self.total > self.max_postings {
+            let mut floor = seq;
+            while self.total > self.max_postings / 2 {
+                let Some((old_seq, old_count)) = self.indexed.pop_front() else {
+                    break;
+                };
+                self.total -= old_count;
+                floor = old_seq + 1;
+            }
+            self.prune(floor);
+        }
+    }
+
+    /// Message `seq` was trimmed out of the message buffer. Keep it if it is indexed.
+    pub fn keep_trimmed(&mut self, seq: u64, message: Arc<AgentMessage>) {
+        if self.is_indexed(seq) {
+            self.trimmed.insert(seq, message);
+        }
+    }
+
+    /// Trimmed messages still searchable through the index, oldest first.
+    pub fn trimmed(&self) -> impl Iterator<Item = (u64, &Arc<AgentMessage>)> {
+        self.trimmed.iter().map(|(seq, msg)| (*seq, msg))
+    }
+
+    fn is_indexed(&self, seq: u64) -> bool {
+        self.indexed.binary_search_by_key(&seq, |(s, _)| *s).is_ok()
+    }
+
+    fn prune(&mut self, floor: u64) {
+        self.trimmed = self.trimmed.split_off(&floor);
+        self.postings.retain(|_, seqs| {
+            let keep_from = seqs.partition_point(|s| *s < floor);
+            seqs.drain(..keep_from);
+            !seqs.is_empty()
+        });
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::with_max_postings(self.max_postings);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indexed.is_empty()
+    }
+
+    /// Messages whose full text may contain `needle` (case-insensitive).
+    /// None when the needle is too short to look up.
+    pub fn candidates(&self, needle: &str) -> Option<HashSet<u64>> {
+        let lower = needle.to_lowercase();
+        let query = trigrams(lower.chars());
+        if query.is_empty() {
+            return None;
+        }
+        let mut lists: Vec<&Vec<u64>> = Vec::with_capacity(query.len());
+        for t in &query {
+            match self.postings.get(t) {
+                Some(list) => lists.push(list),
+                None => return Some(HashSet::new()),
+            }
+        }
+        lists.sort_by_key(|l| l.len());
+        let mut result: HashSet<u64> = lists[0].iter().copied().collect();
+        for list in &lists[1..] {
+            result.retain(|s| list.binary_search(s).is_ok());
+        }
+        Some(result)
+    }
+}
+
+/// Distinct hashed trigrams of lowercased text.
+fn trigrams(chars: impl Iterator<Item = char>) -> HashSet<u32> {
+    let mut out = HashSet::new();
+    let mut window = ['\0'; 3];
+    for (i, c) in chars.flat_map(char::to_lowercase).enumerate() {
+        window = [window[1], window[2], c];
+        if i >= 2 {
+            out.insert(hash_trigram(window));
+        }
+    }
+    out
+}
+
+fn hash_trigram(t: [char; 3]) -> u32 {
+    // FNV-1a over the three code points.
+    let mut h: u32 = 0x811c_9dc5;
+    for c in t {
+        for b in (c as u32).to_le_bytes() {
+            h ^= b as u32;
+            h = h.wrapping_mul(0x0100_0193);
+        }
+    }
+    h
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_finds_text_case_insensitively() {
+        let mut index = ContentIndex::new();
+        index.add(
+            1,
+            "cargo build failed: error[E0425]: cannot find value `Foo`",
+        );
+        index.add(2, "all tests passed");
+        let hits = index.candidates("CANNOT FIND").unwrap();
+        assert_eq!(hits, HashSet::from([1]));
+        assert!(index.candidates("nothing like this").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_short_needle_is_not_looked_up() {
+        let mut index = ContentIndex::new();
+        index.add(1, "ok");
+        assert_eq!(index.candidates("ok"), None);
+    }
+
+    #[test]
+    fn test_keeps_only_indexed_trimmed_messages() {
+        let message = |id: &str| AgentMessage {
+            id: id.into(),
+            session_id: "s1".into(),
+            timestamp: String::new(),
+            role: crate::types::MessageRole::User,
+            msg_type: crate::types::MessageType::ToolResult,
+            content: "truncated...".into(),
+            metadata: None,
+            parent_id: None,
+            tool_use_message_id: None,
+        };
+        let mut index = ContentIndex::new();
+        index.add(3, "long tool output");
+        index.keep_trimmed(2, Arc::new(message("not indexed")));
+        index.keep_trimmed(3, Arc::new(message("indexed")));
+        let kept: Vec<(u64, &str)> = index.trimmed().map(|(s, m)| (s, m.id.as_str())).collect();
+        assert_eq!(kept, vec![(3, "indexed")]);
+    }
+
+    #[test]
+    fn test_evicts_oldest_over_cap() {
+        let mut index = ContentIndex::with_max_postings(10_000);
+        // Pseudo-random CJK text: ~2k distinct trigrams per message.
+        let text = |i: u64| {
+            let mut x = i as u32 + 1;
+            (0..2_000)
+                .map(|_| {
+                    x = x.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                    char::from_u32(0x4e00 + (x >> 16) % 20_000).unwrap()
+                })
+                .collect::<String>()
+        };
+        for i in 0..20 {
+            index.add(i, &text(i));
+        }
+        assert!(index.total <= 10_000);
+        assert_eq!(
+            index.total,
+            index.postings.values().map(Vec::len).sum::<usize>()
+        );
+        assert!(index.indexed.front().unwrap().0 > 0);
+        assert!(index.trimmed.is_empty());
+    }
+}
@@ -0,0 +1,68 @@
+//! Sessions running where the dashboard can't look: in a devcontainer, a Docker
+//! container or on another machine over SSH. Their working directory doesn't exist
+//! here, so there is no git information to read.
+
+use super::session_discovery::local_path;
+use std::io::ErrorKind;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// `environment` of a session whose working directory isn't on this machine.
+pub const REMOTE_OR_CONTAINER: &str = "remote-or-container";
+
+/// Where devcontainers, Codespaces and Docker Desktop put a project inside a container.
+const CONTAINER_PREFIXES: &[&str] = &[
+    "/workspaces/",
+    "/com.docker.devenvs/",
+    "/run/desktop/mnt/host/",
+    "/var/lib/docker/",
+];
+
+static SUPPRESSED_GIT_CHECKS: AtomicU64 = AtomicU64::new(0);
+
+/// Whether `path` looks like a directory inside a container.
+pub fn container_hint(path: &str) -> bool {
+    CONTAINER_PREFIXES
+        .iter()
+        .any(|prefix| path.starts_with(prefix) || path == prefix.trim_end_matches('/'))
+}
+
+/// Whether `working_directory` is missing on this machine. Other errors (e.g. no
+/// permission) don't count: the directory is there, git may still manage.
+pub async fn working_directory_missing(working_directory: &str) -> bool {
+    matches!(
+        tokio::fs::metadata(local_path(working_directory)).await,
+        Err(e) if e.kind() == ErrorKind::NotFound
+    )
+}
+
+/// Count a git check skipped because the working directory is missing.
+pub fn suppress_git_check() {
+    SUPPRESSED_GIT_CHECKS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Git checks skipped since the server started.
+pub fn suppressed_git_checks() -> u64 {
+    SUPPRESSED_GIT_CHECKS.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_container_hints() {
+        assert!(container_hint("/workspaces/api"));
+        assert!(container_hint("/workspaces"));
+        assert!(container_hint("/com.docker.devenvs/abc/src"));
+        assert!(!container_hint("/home/me/workspaces/api"));
+        assert!(!container_hint("/workspacesfoo"));
+    }
+
+    #[tokio::test]
+    async fn test_missing_working_directory() {
+        let dir = std::env::temp_dir();
+        assert!(!working_directory_missing(dir.to_str().unwrap()).await);
+        let gone = dir.join(format!("agents-dashboard-gone-{}", uuid::Uuid::new_v4()));
+        assert!(working_directory_missing(gone.to_str().unwrap()).await);
+    }
+}
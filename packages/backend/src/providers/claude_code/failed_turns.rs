@@ -0,0 +1,208 @@
+//! Turns that failed. Claude Code retries a request the API rejects (overloaded,
+//! rate limited, a 5xx), writing a system entry before each attempt, and once it
+//! gives up writes an assistant entry holding the error in place of a response.
+
+use super::jsonl_parser::{RawAssistantMessage, RawContentBlock, RawEntry, RawSystemEntry};
+use serde_json::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
+
+/// Failed turns in a row after which the session is in Error, whatever its other
+/// entries say.
+pub const FAILED_TURNS_FOR_ERROR: u32 = 3;
+/// Stop reasons of responses the API cut short.
+const ERROR_STOP_REASONS: &[&str] = &["refusal", "model_context_window_exceeded"];
+/// Responses remembered per session. A retry logs a response again right away, so
+/// only the recent ones matter.
+const MAX_RESPONSES: usize = 1_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    /// The turn ended in an error instead of a response.
+    FailedTurn,
+    /// A request failed and was sent again.
+    Retry,
+}
+
+/// Whether `msg` ends its turn in failure: the error written in place of a response,
+/// or a response stopped for a reason other than being done.
+pub fn is_failed_turn(msg: &RawAssistantMessage) -> bool {
+    msg.is_api_error_message == Some(true)
+        || msg
+            .message
+            .stop_reason
+            .as_deref()
+            .is_some_and(|reason| ERROR_STOP_REASONS.contains(&reason))
+}
+
+/// The error a request failed with before it was retried: the `error` of an
+/// `api_error` entry, or the text of the `API Error ...` message older versions write.
+pub fn retry_error(sys: &RawSystemEntry) -> Option<String> {
+    if sys.subtype.as_deref() == Some("api_error") {
+        let error = sys.error.as_ref();
+        return Some(error.map_or_else(|| "API Error".to_string(), error_text));
+    }
+    let content = sys.content.as_deref()?;
+    (sys.level.as_deref() == Some("error") && content.starts_with("API Error"))
+        .then(|| content.to_string())
+}
+
+/// `API Error: 529 Overloaded`, from the status and the innermost message of an
+/// error as the SDK reports it.
+fn error_text(error: &Value) -> String {
+    let status = error.get("status").and_then(|v| v.as_u64());
+    let mut message = None;
+    let mut inner = Some(error);
+    while let Some(error) = inner {
+        if let Some(m) = error.get("message").and_then(|v| v.as_str()) {
+            message = Some(m);
+        }
+        inner = error.get("error").filter(|e| e.is_object());
+    }
+    match (status, message) {
+        (Some(status), Some(message)) => format!("API Error: {} {}", status, message),
+        (Some(status), None) => format!("API Error: {}", status),
+        (None, Some(message)) => format!("API Error: {}", message),
+        (None, None) => "API Error".to_string(),
+    }
+}
+
+/// Counts a session's failures. Besides the entries saying so, a response logged
+/// twice (same message id and content under a new uuid) is a request that was sent
+/// again.
+#[derive(Debug, Clone, Default)]
+pub struct FailureTracker {
+    responses: HashSet<u64>,
+    order: VecDeque<u64>,
+}
+
+impl FailureTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The failure `entry` records, if any. Entries already seen (by uuid) must not
+    /// be passed again.
+    pub fn observe(&mut self, entry: &RawEntry) -> Option<Failure> {
+        match entry {
+            RawEntry::Assistant(msg) if is_failed_turn(msg) => Some(Failure::FailedTurn),
+            RawEntry::Assistant(msg) => {
+                let key = response_key(msg)?;
+                (!self.remember(key)).then_some(Failure::Retry)
+            }
+            RawEntry::System(sys) => retry_error(sys).map(|_| Failure::Retry),
+            _ => None,
+        }
+    }
+
+    /// Record `key`; false if it was already recorded.
+    fn remember(&mut self, key: u64) -> bool {
+        if self.responses.contains(&key) {
+            return false;
+        }
+        if self.order.len() >= MAX_RESPONSES {
+            if let Some(oldest) = self.order.pop_front() {
+                self.responses.remove(&oldest);
+            }
+        }
+        self.responses.insert(key);
+        self.order.push_back(key);
+        true
+    }
+}
+
+/// A response's message id and content. A response spread over several entries
+/// shares the id between them, so the id alone isn't enough.
+fn response_key(msg: &RawAssistantMessage) -> Option<u64> {
+    let id = msg.message.id.as_deref().filter(|id| !id.is_empty())?;
+    let mut hasher = DefaultHasher::new();
+    id.hash(&mut hasher);
+    for block in &msg.message.content {
+        match block {
+            RawContentBlock::Text { text } => ("text", text).hash(&mut hasher),
+            RawContentBlock::Thinking { thinking, .. } => ("thinking", thinking).hash(&mut hasher),
+            RawContentBlock::ToolUse { id, .. } => ("tool_use", id).hash(&mut hasher),
+            RawContentBlock::ToolResult { tool_use_id, .. } => {
+                ("tool_result", tool_use_id).hash(&mut hasher)
+            }
+        }
+    }
+    Some(hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::claude_code::jsonl_parser::{parse_jsonl_chunk, parse_jsonl_line};
+
+    #[test]
+    fn test_retried_turn() {
+        let entries = parse_jsonl_chunk(include_str!("testdata/retried_turn.jsonl")).entries;
+        let mut tracker = FailureTracker::new();
+        let failures: Vec<Option<Failure>> = entries.iter().map(|e| tracker.observe(e)).collect();
+        use Failure::*;
+        assert_eq!(
+            failures,
+            [
+                None,
+                // Two retries, then the turn fails.
+                Some(Retry),
+                Some(Retry),
+                Some(FailedTurn),
+                // Asked again; the response is logged twice.
+                None,
+                None,
+                Some(Retry),
+                None,
+            ]
+        );
+        let RawEntry::System(sys) = &entries[1] else {
+            panic!("expected a system entry");
+        };
+        assert_eq!(
+            retry_error(sys).as_deref(),
+            Some("API Error: 529 Overloaded")
+        );
+    }
+
+    #[test]
+    fn test_error_stop_reason_and_older_retry_message() {
+        let refusal = parse_jsonl_line(
+            r#"{"type":"assistant","message":{"id":"msg_1","content":[],"stop_reason":"refusal"}}"#,
+        )
+        .unwrap();
+        let RawEntry::Assistant(msg) = &refusal else {
+            panic!("expected an assistant entry");
+        };
+        assert!(is_failed_turn(msg));
+
+        let retry = parse_jsonl_line(
+            r#"{"type":"system","level":"error","content":"API Error (Request timed out.) · Retrying in 2 seconds… (attempt 1/10)"}"#,
+        )
+        .unwrap();
+        assert_eq!(FailureTracker::new().observe(&retry), Some(Failure::Retry));
+
+        // Other system messages aren't failures.
+        let info = parse_jsonl_line(
+            r#"{"type":"system","level":"info","content":"Conversation compacted"}"#,
+        )
+        .unwrap();
+        assert_eq!(FailureTracker::new().observe(&info), None);
+    }
+
+    #[test]
+    fn test_blocks_of_one_response_are_not_retries() {
+        let mut tracker = FailureTracker::new();
+        for block in [
+            r#"{"type":"text","text":"Let me look"}"#,
+            r#"{"type":"tool_use","id":"t1","name":"Read","input":{}}"#,
+        ] {
+            let line = format!(
+                r#"{{"type":"assistant","message":{{"id":"msg_1","content":[{}]}}}}"#,
+                block
+            );
+            assert_eq!(tracker.observe(&parse_jsonl_line(&line).unwrap()), None);
+        }
+    }
+}
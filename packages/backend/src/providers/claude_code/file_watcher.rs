@@ -1,23 +1,168 @@
-use super::jsonl_parser::{parse_jsonl_chunk, RawEntry};
+use super::jsonl_parser::{LineReader, RawEntry, DEFAULT_MAX_LINE_BYTES};
+use crate::types::{WatcherHealth, WatcherMode};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::fs::File;
 use tokio::io::{AsyncReadExt, AsyncSeekExt, SeekFrom};
 use tokio::sync::mpsc;
 use tracing::{debug, error};
 
+/// Fallback for changes file notifications miss.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Poll intervals without a successful read before a running watcher counts as stalled.
+const STALL_POLLS: u32 = 5;
+/// Logs are read this much at a time, so a huge line is never held in memory whole.
+const READ_CHUNK_BYTES: usize = 1024 * 1024;
+/// Reads in a row that find the log missing before it counts as deleted, so a log
+/// replaced by rename isn't mistaken for one that is gone.
+const GONE_CHECKS: u32 = 3;
+
+/// What a watcher sends to the session it reads for.
+#[derive(Debug)]
+pub enum WatchEvent {
+    /// Complete lines appended to the log.
+    Entries(Vec<RawEntry>),
+    /// The log has been missing for `GONE_CHECKS` reads in a row. Sent once; reading
+    /// resumes if the file comes back.
+    FileGone,
+    /// The first read after the watcher started is done, and its entries were sent.
+    /// `entries` and `parse_errors` count the lines that read held.
+    InitialRead { entries: u64, parse_errors: u64 },
+}
+
+/// What the watcher task reports back, so a watcher that stopped reading can be
+/// told apart from a log nothing is written to.
+#[derive(Debug, Default)]
+struct Status {
+    mode: Option<WatcherMode>,
+    started_at_ms: i64,
+    last_read_at_ms: Option<i64>,
+    bytes_read: u64,
+    last_error: Option<(i64, String)>,
+}
+
+impl Status {
+    fn record(&mut self, result: &Result<u64, std::io::Error>) {
+        let now_ms = chrono::Utc::now().timestamp_millis();
+        match result {
+            Ok(bytes) => {
+                self.last_read_at_ms = Some(now_ms);
+                self.bytes_read += bytes;
+            }
+            Err(e) => self.last_error = Some((now_ms, e.to_string())),
+        }
+    }
+}
+
 pub struct FileWatcher {
     file_path: PathBuf,
-    tx: mpsc::UnboundedSender<Vec<RawEntry>>,
+    tx: mpsc::UnboundedSender<WatchEvent>,
     shutdown: tokio::sync::watch::Sender<bool>,
+    poll_interval: Duration,
+    max_line_bytes: usize,
+    /// End of the last complete line read, so a stopped watcher resumes where it was.
+    offset: Arc<AtomicU64>,
+    /// Size of the log when it was last read, set before the entries read are sent.
+    file_size: Arc<AtomicU64>,
+    status: Arc<Mutex<Status>>,
 }
 
 impl FileWatcher {
-    pub fn new(file_path: PathBuf, tx: mpsc::UnboundedSender<Vec<RawEntry>>) -> Self {
+    pub fn new(file_path: PathBuf, tx: mpsc::UnboundedSender<WatchEvent>) -> Self {
         let (shutdown, _) = tokio::sync::watch::channel(false);
         Self {
             file_path,
             tx,
             shutdown,
+            poll_interval: POLL_INTERVAL,
+            max_line_bytes: DEFAULT_MAX_LINE_BYTES,
+            offset: Arc::new(AtomicU64::new(0)),
+            file_size: Arc::new(AtomicU64::new(0)),
+            status: Arc::default(),
+        }
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Skip lines longer than this rather than buffer them.
+    pub fn with_max_line_bytes(mut self, max_line_bytes: usize) -> Self {
+        self.max_line_bytes = max_line_bytes;
+        self
+    }
+
+    pub fn max_line_bytes(&self) -> usize {
+        self.max_line_bytes
+    }
+
+    /// Start reading at `offset`, the end of a line, rather than from the beginning.
+    pub fn with_offset(self, offset: u64) -> Self {
+        self.offset.store(offset, Ordering::Relaxed);
+        self
+    }
+
+    pub fn file_path(&self) -> &Path {
+        &self.file_path
+    }
+
+    /// Bytes of the log read as complete lines.
+    pub fn offset(&self) -> u64 {
+        self.offset.load(Ordering::Relaxed)
+    }
+
+    /// Size of the log as of the latest read. A log read up to `offset` is at least
+    /// that long, even before the watcher has read it itself.
+    pub fn file_size(&self) -> u64 {
+        self.file_size.load(Ordering::Relaxed).max(self.offset())
+    }
+
+    /// None until the watcher has been started.
+    pub fn health(&self) -> Option<WatcherHealth> {
+        let status = self.status.lock().unwrap();
+        let (last_error_at, last_error) = match &status.last_error {
+            Some((at_ms, error)) => (Some(rfc3339(*at_ms)), Some(error.clone())),
+            None => (None, None),
+        };
+        Some(WatcherHealth {
+            mode: status.mode?,
+            last_read_at: status.last_read_at_ms.map(rfc3339),
+            bytes_read: status.bytes_read,
+            last_error,
+            last_error_at,
+        })
+    }
+
+    /// Since when a running watcher hasn't managed to read the log, if that is longer
+    /// than it should take to poll it a few times.
+    pub fn stalled_since(&self, now_ms: i64) -> Option<i64> {
+        let status = self.status.lock().unwrap();
+        if !matches!(
+            status.mode,
+            Some(WatcherMode::Notify | WatcherMode::PollOnly)
+        ) {
+            return None;
+        }
+        // Reads from before a restart don't count.
+        let since = status
+            .last_read_at_ms
+            .unwrap_or(0)
+            .max(status.started_at_ms);
+        let stall_ms = (self.poll_interval * STALL_POLLS).as_millis() as i64;
+        (now_ms - since > stall_ms).then_some(since)
+    }
+
+    /// The last read failed, with nothing read successfully since.
+    pub fn failing(&self) -> bool {
+        let status = self.status.lock().unwrap();
+        match (&status.last_error, status.last_read_at_ms) {
+            (Some((error_ms, _)), Some(read_ms)) => *error_ms > read_ms,
+            (Some(_), None) => true,
+            (None, _) => false,
         }
     }
 
@@ -25,15 +170,46 @@ impl FileWatcher {
         let file_path = self.file_path.clone();
         let tx = self.tx.clone();
         let mut shutdown_rx = self.shutdown.subscribe();
+        let poll_every = self.poll_interval;
+        let shared_offset = self.offset.clone();
+        let file_size = self.file_size.clone();
+        let status = self.status.clone();
+        let mut lines = LineReader::new(self.max_line_bytes);
+        {
+            let mut status = status.lock().unwrap();
+            status.mode = Some(WatcherMode::PollOnly);
+            status.started_at_ms = chrono::Utc::now().timestamp_millis();
+        }
 
         tokio::spawn(async move {
-            let mut offset: u64 = 0;
-            let mut remainder = String::new();
+            let mut offset = shared_offset.load(Ordering::Relaxed);
+            let mut missing_reads = 0;
+            let read_to = |offset: u64, lines: &LineReader| {
+                shared_offset.store(
+                    offset.saturating_sub(lines.pending_bytes()),
+                    Ordering::Relaxed,
+                )
+            };
 
             // Initial read
-            if let Err(e) = read_new_content(&file_path, &mut offset, &mut remainder, &tx).await {
+            let result = read_new_content(
+                &file_path,
+                &mut offset,
+                &mut lines,
+                &file_size,
+                &mut missing_reads,
+                &tx,
+            )
+            .await;
+            if let Err(e) = &result {
                 error!("Initial read error for {}: {}", file_path.display(), e);
             }
+            status.lock().unwrap().record(&result);
+            read_to(offset, &lines);
+            let _ = tx.send(WatchEvent::InitialRead {
+                entries: lines.entries_read(),
+                parse_errors: lines.parse_errors(),
+            });
 
             // Set up notify watcher
             let (notify_tx, mut notify_rx) = mpsc::unbounded_channel();
@@ -51,16 +227,25 @@ impl FileWatcher {
                 )
                 .ok();
 
-                if let Some(ref mut w) = watcher {
-                    if let Err(e) = w.watch(&file_path, RecursiveMode::NonRecursive) {
-                        error!("Failed to watch {}: {}", file_path.display(), e);
-                    }
+                let watching = match watcher {
+                    Some(ref mut w) => match w.watch(&file_path, RecursiveMode::NonRecursive) {
+                        Ok(()) => true,
+                        Err(e) => {
+                            error!("Failed to watch {}: {}", file_path.display(), e);
+                            false
+                        }
+                    },
+                    None => false,
+                };
+                let mut status = status.lock().unwrap();
+                if watching && status.mode == Some(WatcherMode::PollOnly) {
+                    status.mode = Some(WatcherMode::Notify);
                 }
                 watcher
             };
 
-            // Polling interval (2s fallback)
-            let mut poll_interval = tokio::time::interval(std::time::Duration::from_secs(2));
+            // Polling fallback
+            let mut poll_interval = tokio::time::interval(poll_every);
             poll_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
 
             loop {
@@ -70,14 +255,20 @@ impl FileWatcher {
                         break;
                     }
                     _ = notify_rx.recv() => {
-                        if let Err(e) = read_new_content(&file_path, &mut offset, &mut remainder, &tx).await {
+                        let result = read_new_content(&file_path, &mut offset, &mut lines, &file_size, &mut missing_reads, &tx).await;
+                        if let Err(e) = &result {
                             error!("Read error for {}: {}", file_path.display(), e);
                         }
+                        status.lock().unwrap().record(&result);
+                        read_to(offset, &lines);
                     }
                     _ = poll_interval.tick() => {
-                        if let Err(e) = read_new_content(&file_path, &mut offset, &mut remainder, &tx).await {
+                        let result = read_new_content(&file_path, &mut offset, &mut lines, &file_size, &mut missing_reads, &tx).await;
+                        if let Err(e) = &result {
                             error!("Poll read error for {}: {}", file_path.display(), e);
                         }
+                        status.lock().unwrap().record(&result);
+                        read_to(offset, &lines);
                     }
                 }
             }
@@ -86,42 +277,98 @@ impl FileWatcher {
 
     pub fn stop(&self) {
         let _ = self.shutdown.send(true);
+        let mut status = self.status.lock().unwrap();
+        if status.mode.is_some() {
+            status.mode = Some(WatcherMode::Stopped);
+        }
     }
 }
 
+/// The entries in the last `max_bytes` of a log before `end`, starting at the first
+/// complete line, and where the last complete line ends. For sessions that no longer
+/// hold their messages, and logs read once.
+pub async fn read_tail(
+    file_path: &Path,
+    end: u64,
+    max_bytes: u64,
+    max_line_bytes: usize,
+) -> Result<(Vec<RawEntry>, u64), std::io::Error> {
+    // One byte early, to tell whether the first line read is complete.
+    let start = end.saturating_sub(max_bytes).saturating_sub(1);
+    let mut file = File::open(file_path).await?;
+    file.seek(SeekFrom::Start(start)).await?;
+    let mut buf = Vec::new();
+    file.take(end - start).read_to_end(&mut buf).await?;
+    if start > 0 {
+        let first_line = buf
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(buf.len(), |i| i + 1);
+        buf.drain(..first_line);
+    }
+    let mut lines = LineReader::new(max_line_bytes);
+    let entries = lines.push(&buf);
+    Ok((entries, end - lines.pending_bytes()))
+}
+
+fn rfc3339(ms: i64) -> String {
+    chrono::DateTime::from_timestamp_millis(ms)
+        .map(|dt| dt.to_rfc3339())
+        .unwrap_or_default()
+}
+
+/// Read what was appended since `offset`, returning the number of bytes read.
 async fn read_new_content(
     file_path: &Path,
     offset: &mut u64,
-    remainder: &mut String,
-    tx: &mpsc::UnboundedSender<Vec<RawEntry>>,
-) -> Result<(), std::io::Error> {
+    lines: &mut LineReader,
+    file_size: &AtomicU64,
+    missing_reads: &mut u32,
+    tx: &mpsc::UnboundedSender<WatchEvent>,
+) -> Result<u64, std::io::Error> {
     let metadata = match tokio::fs::metadata(file_path).await {
         Ok(m) => m,
-        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            *missing_reads += 1;
+            if *missing_reads == GONE_CHECKS {
+                let _ = tx.send(WatchEvent::FileGone);
+            }
+            return Ok(0);
+        }
         Err(e) => return Err(e),
     };
+    *missing_reads = 0;
 
     let size = metadata.len();
+    file_size.store(size, Ordering::Relaxed);
     if size <= *offset {
-        return Ok(());
+        return Ok(0);
     }
 
     let mut file = File::open(file_path).await?;
     file.seek(SeekFrom::Start(*offset)).await?;
 
-    let to_read = (size - *offset) as usize;
-    let mut buf = vec![0u8; to_read];
-    file.read_exact(&mut buf).await?;
-    *offset = size;
-
-    let text = String::from_utf8_lossy(&buf);
-    let full_chunk = format!("{}{}", remainder, text);
-    let result = parse_jsonl_chunk(&full_chunk);
-    *remainder = result.remainder;
+    let to_read = size - *offset;
+    let mut file = file.take(to_read);
+    let mut buf = vec![0u8; READ_CHUNK_BYTES.min(to_read as usize)];
+    let mut entries = Vec::new();
+    let mut read = 0;
+    // What was read before an error is passed on; the offset is past it.
+    let result = loop {
+        match file.read(&mut buf).await {
+            Ok(0) => break Ok(read),
+            Ok(n) => {
+                read += n as u64;
+                *offset += n as u64;
+                entries.extend(lines.push(&buf[..n]));
+            }
+            Err(e) => break Err(e),
+        }
+    };
 
-    if !result.entries.is_empty() {
-        let _ = tx.send(result.entries);
+    if !entries.is_empty() {
+        let _ = tx.send(WatchEvent::Entries(entries));
     }
 
-    Ok(())
+    result
 }
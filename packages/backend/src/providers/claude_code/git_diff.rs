@@ -0,0 +1,236 @@
+//! The patch of a session's uncommitted changes, read with the `git` CLI for
+//! `GET /api/sessions/{id}/diff`.
+
+use super::git_status::numstat_files;
+use super::session_discovery::local_path;
+use crate::types::GitDiff;
+use std::process::Stdio;
+use tokio::io::AsyncReadExt;
+
+/// Lines of context around each change unless the request asks for more or fewer.
+pub const DEFAULT_DIFF_CONTEXT: u32 = 3;
+/// Default for the largest patch returned.
+pub const DEFAULT_DIFF_MAX_BYTES: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DiffOptions {
+    /// The index against HEAD instead of the working tree against the index.
+    pub staged: bool,
+    /// Only this file or directory, relative to the repository root.
+    pub path: Option<String>,
+    /// Lines of context around each change.
+    pub context: u32,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            staged: false,
+            path: None,
+            context: DEFAULT_DIFF_CONTEXT,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DiffError {
+    NotARepository,
+    /// git could not be run or failed; the message says why.
+    Git(String),
+}
+
+/// The repository's diff as `git diff` prints it, cut at `max_bytes` (at the end of a
+/// line), with per-file stats for the whole diff. Covers the whole repository even
+/// when `working_directory` is a subdirectory of it.
+pub async fn fetch_git_diff(
+    working_directory: &str,
+    options: &DiffOptions,
+    max_bytes: u64,
+) -> Result<GitDiff, DiffError> {
+    let working_directory = local_path(working_directory);
+    if working_directory.as_os_str().is_empty() || !working_directory.is_dir() {
+        return Err(DiffError::NotARepository);
+    }
+    let (root, _) = run_git(
+        &working_directory.to_string_lossy(),
+        &["rev-parse".into(), "--show-toplevel".into()],
+        u64::MAX,
+    )
+    .await
+    .map_err(|e| match e {
+        DiffError::Git(_) => DiffError::NotARepository,
+        e => e,
+    })?;
+    let root = String::from_utf8_lossy(&root).trim().to_string();
+
+    let mut args = vec![
+        "diff".to_string(),
+        "--no-color".to_string(),
+        "--no-ext-diff".to_string(),
+    ];
+    if options.staged {
+        args.push("--cached".to_string());
+    }
+    let pathspec: Vec<String> = match &options.path {
+        Some(path) => vec!["--".to_string(), path.clone()],
+        None => Vec::new(),
+    };
+    let patch_args = [
+        args.as_slice(),
+        &[format!("-U{}", options.context)],
+        &pathspec,
+    ]
+    .concat();
+    let numstat_args = [args.as_slice(), &["--numstat".to_string()], &pathspec].concat();
+
+    let (patch, numstat) = tokio::join!(
+        run_git(&root, &patch_args, max_bytes),
+        run_git(&root, &numstat_args, u64::MAX),
+    );
+    let (mut patch, truncated) = patch?;
+    if truncated {
+        // Don't end on half a line.
+        if let Some(end) = patch.iter().rposition(|&b| b == b'\n') {
+            patch.truncate(end + 1);
+        }
+    }
+    Ok(GitDiff {
+        patch: String::from_utf8_lossy(&patch).into_owned(),
+        truncated,
+        files: numstat_files(&String::from_utf8_lossy(&numstat?.0)),
+        staged: options.staged,
+    })
+}
+
+/// Up to `max_bytes` of git's output, and whether there was more. git is stopped once
+/// the limit is reached.
+async fn run_git(dir: &str, args: &[String], max_bytes: u64) -> Result<(Vec<u8>, bool), DiffError> {
+    let mut child = tokio::process::Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| DiffError::Git(format!("could not run git: {}", e)))?;
+    let mut output = Vec::new();
+    child
+        .stdout
+        .take()
+        .expect("stdout is piped")
+        .take(max_bytes.saturating_add(1))
+        .read_to_end(&mut output)
+        .await
+        .map_err(|e| DiffError::Git(e.to_string()))?;
+    if output.len() as u64 > max_bytes {
+        output.truncate(max_bytes as usize);
+        let _ = child.kill().await;
+        return Ok((output, true));
+    }
+    let finished = child
+        .wait_with_output()
+        .await
+        .map_err(|e| DiffError::Git(e.to_string()))?;
+    if !finished.status.success() {
+        return Err(DiffError::Git(
+            String::from_utf8_lossy(&finished.stderr).trim().to_string(),
+        ));
+    }
+    Ok((output, false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = std::process::Command::new("git")
+            .args([
+                "-c",
+                "user.name=Test User",
+                "-c",
+                "user.email=test@example.com",
+                "-c",
+                "commit.gpgsign=false",
+            ])
+            .args(args)
+            .current_dir(dir)
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    #[tokio::test]
+    async fn test_diff_filters_and_truncates() {
+        if std::process::Command::new("git")
+            .arg("--version")
+            .output()
+            .is_err()
+        {
+            return;
+        }
+        let dir =
+            std::env::temp_dir().join(format!("agents-dashboard-diff-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        git(&dir, &["init", "-q"]);
+        std::fs::write(dir.join("a.txt"), "one\ntwo\nthree\n").unwrap();
+        std::fs::write(dir.join("src/b.txt"), "keep\n").unwrap();
+        git(&dir, &["add", "."]);
+        git(&dir, &["commit", "-q", "-m", "Initial commit"]);
+        std::fs::write(dir.join("a.txt"), "one\nTWO\nthree\n").unwrap();
+        std::fs::write(dir.join("src/b.txt"), "keep\nstaged\n").unwrap();
+        git(&dir, &["add", "src/b.txt"]);
+
+        // Run from a subdirectory, as an agent that cd'd into one would be.
+        let wd = dir.join("src");
+        let wd = wd.to_str().unwrap();
+        let diff = fetch_git_diff(wd, &DiffOptions::default(), 1 << 20)
+            .await
+            .unwrap();
+        assert!(diff.patch.contains("-two\n+TWO\n"));
+        assert!(!diff.patch.contains("staged"));
+        assert!(!diff.truncated);
+        assert_eq!(diff.files.len(), 1);
+        assert_eq!(diff.files[0].path, "a.txt");
+        assert_eq!((diff.files[0].additions, diff.files[0].deletions), (1, 1));
+
+        let staged = DiffOptions {
+            staged: true,
+            ..Default::default()
+        };
+        let diff = fetch_git_diff(wd, &staged, 1 << 20).await.unwrap();
+        assert!(diff.patch.contains("+staged\n"));
+        assert_eq!(diff.files[0].path, "src/b.txt");
+
+        let other_file = DiffOptions {
+            path: Some("src/b.txt".into()),
+            ..Default::default()
+        };
+        let diff = fetch_git_diff(wd, &other_file, 1 << 20).await.unwrap();
+        assert!(diff.patch.is_empty() && diff.files.is_empty());
+
+        let diff = fetch_git_diff(wd, &DiffOptions::default(), 40)
+            .await
+            .unwrap();
+        assert!(diff.truncated);
+        assert!(diff.patch.len() <= 40 && diff.patch.ends_with('\n'));
+        assert_eq!(diff.files.len(), 1);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_missing_directory_is_not_a_repository() {
+        let missing =
+            std::env::temp_dir().join(format!("agents-dashboard-{}", uuid::Uuid::new_v4()));
+        assert_eq!(
+            fetch_git_diff(missing.to_str().unwrap(), &DiffOptions::default(), 100).await,
+            Err(DiffError::NotARepository)
+        );
+        assert_eq!(
+            fetch_git_diff("", &DiffOptions::default(), 100).await,
+            Err(DiffError::NotARepository)
+        );
+    }
+}
@@ -0,0 +1,677 @@
+use super::session_discovery::local_path;
+use super::timestamps::{format_timestamp, parse_timestamp};
+use crate::types::{GitCommitInfo, GitFileChange, GitStatus};
+use serde::{Deserialize, Serialize};
+
+/// At most this many changed files are reported, largest changes first.
+const MAX_CHANGED_FILES: usize = 50;
+
+/// `git log` format: short hash, subject, author name, committer date, separated by US (0x1f).
+const LAST_COMMIT_FORMAT: &str = "--format=%h%x1f%s%x1f%an%x1f%cI";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GitBackend {
+    /// In-process via libgit2. No `git` binary needed.
+    #[default]
+    Libgit2,
+    /// Shell out to the `git` CLI. Useful for repositories libgit2 handles poorly
+    /// (sparse checkouts, fsmonitor).
+    Cli,
+}
+
+/// Collect working-tree status for `working_directory`.
+/// Returns None if the directory is not inside a git repository.
+/// The branch is the checked-out branch, `detached@<hash>` for a detached HEAD,
+/// or empty if git could not tell (callers then keep the branch from the session log).
+pub async fn fetch_git_status(backend: GitBackend, working_directory: &str) -> Option<GitStatus> {
+    // A Windows path logged by a session seen from WSL, or the other way around.
+    let wd = local_path(working_directory).to_string_lossy().into_owned();
+    match backend {
+        GitBackend::Libgit2 => {
+            // git2 is synchronous; keep it off the async worker threads.
+            tokio::task::spawn_blocking(move || fetch_git_status_libgit2(&wd))
+                .await
+                .ok()
+                .flatten()
+        }
+        GitBackend::Cli => fetch_git_status_cli(&wd).await,
+    }
+}
+
+fn fetch_git_status_libgit2(working_directory: &str) -> Option<GitStatus> {
+    use git2::{Branch, Patch, Repository, StatusOptions};
+
+    let repo = Repository::discover(working_directory).ok()?;
+    if repo.is_bare() {
+        return None;
+    }
+    let index = repo.index().ok()?;
+    let head = repo.head().ok();
+    let head_commit = head.as_ref().and_then(|h| h.peel_to_commit().ok());
+    let head_tree = head_commit.as_ref().and_then(|c| c.tree().ok());
+
+    let unstaged = repo.diff_index_to_workdir(Some(&index), None).ok()?;
+    let staged = repo
+        .diff_tree_to_index(head_tree.as_ref(), Some(&index), None)
+        .ok()?;
+    let totals = |diff: &git2::Diff| {
+        diff.stats()
+            .map(|s| (s.insertions() as u64, s.deletions() as u64))
+            .unwrap_or((0, 0))
+    };
+    let (additions, deletions) = totals(&unstaged);
+    let (staged_additions, staged_deletions) = totals(&staged);
+
+    let mut files = Vec::new();
+    for (idx, delta) in unstaged.deltas().enumerate() {
+        let Some(path) = delta.new_file().path().or_else(|| delta.old_file().path()) else {
+            continue;
+        };
+        let (additions, deletions) = match Patch::from_diff(&unstaged, idx) {
+            Ok(Some(patch)) => patch
+                .line_stats()
+                .map(|(_, a, d)| (a as u64, d as u64))
+                .unwrap_or((0, 0)),
+            _ => (0, 0),
+        };
+        files.push(GitFileChange {
+            path: path.to_string_lossy().into_owned(),
+            additions,
+            deletions,
+        });
+    }
+
+    // Match `git status`: untracked directories count once, not per file.
+    let mut status_opts = StatusOptions::new();
+    status_opts
+        .include_untracked(true)
+        .recurse_untracked_dirs(false)
+        .include_ignored(false);
+    let submodule_paths: Vec<String> = repo
+        .submodules()
+        .map(|subs| {
+            subs.iter()
+                .map(|s| s.path().to_string_lossy().into_owned())
+                .collect()
+        })
+        .unwrap_or_default();
+    let (untracked, dirty_submodules) = repo
+        .statuses(Some(&mut status_opts))
+        .map(|statuses| {
+            let untracked = statuses.iter().filter(|e| e.status().is_wt_new()).count() as u64;
+            let dirty_submodules = statuses
+                .iter()
+                .filter(|e| {
+                    e.path()
+                        .is_some_and(|p| submodule_paths.iter().any(|s| s == p))
+                        && e.status() != git2::Status::CURRENT
+                })
+                .count() as u32;
+            (untracked, dirty_submodules)
+        })
+        .unwrap_or((0, 0));
+
+    // No upstream (or no branch) leaves ahead/behind at 0, as with the CLI.
+    let (ahead, behind) = repo
+        .head()
+        .ok()
+        .filter(|h| h.is_branch())
+        .map(Branch::wrap)
+        .and_then(|local| {
+            let local_oid = local.get().target()?;
+            let upstream_oid = local.upstream().ok()?.get().target()?;
+            repo.graph_ahead_behind(local_oid, upstream_oid).ok()
+        })
+        .map(|(a, b)| (a as u64, b as u64))
+        .unwrap_or((0, 0));
+
+    let last_commit = head_commit.as_ref().map(|commit| {
+        let when = commit.committer().when();
+        let timestamp = chrono::DateTime::from_timestamp(when.seconds(), 0)
+            .map(format_timestamp)
+            .unwrap_or_default();
+        GitCommitInfo {
+            hash: commit
+                .as_object()
+                .short_id()
+                .ok()
+                .and_then(|b| b.as_str().map(String::from))
+                .unwrap_or_default(),
+            subject: commit.summary().unwrap_or_default().to_string(),
+            author: commit.author().name().unwrap_or_default().to_string(),
+            timestamp,
+        }
+    });
+
+    let head_name = if repo.head_detached().unwrap_or(false) {
+        Some("(detached)".to_string())
+    } else if let Some(head) = head.as_ref() {
+        head.shorthand().map(String::from)
+    } else {
+        // Unborn branch (no commits yet): HEAD still names the branch symbolically.
+        repo.find_reference("HEAD")
+            .ok()
+            .and_then(|r| r.symbolic_target().map(String::from))
+            .map(|t| t.trim_start_matches("refs/heads/").to_string())
+    };
+
+    Some(GitStatus {
+        branch: branch_name(head_name.as_deref(), last_commit.as_ref()),
+        additions,
+        deletions,
+        staged_additions,
+        staged_deletions,
+        untracked,
+        dirty_submodules,
+        ahead,
+        behind,
+        files: sort_and_cap_files(files),
+        last_commit,
+        is_worktree: repo.is_worktree(),
+    })
+}
+
+async fn fetch_git_status_cli(working_directory: &str) -> Option<GitStatus> {
+    // Resolve the repository root first so stats cover the whole repository even when
+    // the agent has cd'd into a subdirectory. Failing here means this isn't a repository.
+    let rev_parse = run_git(
+        working_directory,
+        &[
+            "rev-parse",
+            "--path-format=absolute",
+            "--show-toplevel",
+            "--git-dir",
+            "--git-common-dir",
+        ],
+    )
+    .await?;
+    let (root, is_worktree) = parse_rev_parse(&rev_parse)?;
+    let root = root.as_str();
+
+    let (status, unstaged, staged, numstat, last_commit) = tokio::join!(
+        run_git(root, &["status", "--porcelain=v2", "--branch"]),
+        run_git(root, &["diff", "--shortstat"]),
+        run_git(root, &["diff", "--cached", "--shortstat"]),
+        run_git(root, &["diff", "--numstat"]),
+        run_git(root, &["log", "-1", LAST_COMMIT_FORMAT]),
+    );
+
+    let porcelain = parse_porcelain_v2(&status?);
+    let (additions, deletions) = unstaged
+        .as_deref()
+        .and_then(parse_shortstat)
+        .unwrap_or((0, 0));
+    let (staged_additions, staged_deletions) = staged
+        .as_deref()
+        .and_then(parse_shortstat)
+        .unwrap_or((0, 0));
+    let files = numstat.as_deref().map(parse_numstat).unwrap_or_default();
+    // `git log` fails on a repository with no commits yet.
+    let last_commit = last_commit.as_deref().and_then(parse_last_commit);
+    let branch = branch_name(porcelain.head.as_deref(), last_commit.as_ref());
+
+    Some(GitStatus {
+        branch,
+        additions,
+        deletions,
+        staged_additions,
+        staged_deletions,
+        untracked: porcelain.untracked,
+        dirty_submodules: porcelain.dirty_submodules,
+        ahead: porcelain.ahead,
+        behind: porcelain.behind,
+        files,
+        last_commit,
+        is_worktree,
+    })
+}
+
+/// Parse `git rev-parse --show-toplevel --git-dir --git-common-dir` into the repository
+/// root and whether it is a linked worktree (whose git dir differs from the common dir).
+fn parse_rev_parse(output: &str) -> Option<(String, bool)> {
+    let mut lines = output.lines().map(str::trim);
+    let root = lines.next().filter(|l| !l.is_empty())?;
+    let git_dir = lines.next()?;
+    let common_dir = lines.next()?;
+    Some((root.to_string(), git_dir != common_dir))
+}
+
+/// `head` is a branch name or `(detached)`, as in `git status --porcelain=v2 --branch`.
+fn branch_name(head: Option<&str>, last_commit: Option<&GitCommitInfo>) -> String {
+    match head {
+        Some("(detached)") => last_commit
+            .map(|c| format!("detached@{}", c.hash))
+            .unwrap_or_default(),
+        Some(head) => head.to_string(),
+        None => String::new(),
+    }
+}
+
+fn sort_and_cap_files(mut files: Vec<GitFileChange>) -> Vec<GitFileChange> {
+    files.sort_by_key(|f| std::cmp::Reverse(f.additions + f.deletions));
+    files.truncate(MAX_CHANGED_FILES);
+    files
+}
+
+async fn run_git(working_directory: &str, args: &[&str]) -> Option<String> {
+    let output = tokio::process::Command::new("git")
+        .args(args)
+        .current_dir(working_directory)
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn parse_shortstat(output: &str) -> Option<(u64, u64)> {
+    let trimmed = output.trim();
+    if trimmed.is_empty() {
+        return Some((0, 0));
+    }
+    let mut additions: u64 = 0;
+    let mut deletions: u64 = 0;
+    // " 3 files changed, 42 insertions(+), 10 deletions(-)"
+    for part in trimmed.split(',') {
+        let part = part.trim();
+        if part.contains("insertion") {
+            if let Some(n) = part.split_whitespace().next().and_then(|s| s.parse().ok()) {
+                additions = n;
+            }
+        } else if part.contains("deletion") {
+            if let Some(n) = part.split_whitespace().next().and_then(|s| s.parse().ok()) {
+                deletions = n;
+            }
+        }
+    }
+    Some((additions, deletions))
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct PorcelainStatus {
+    /// `# branch.head`: the branch name, or `(detached)`.
+    head: Option<String>,
+    untracked: u64,
+    dirty_submodules: u32,
+    ahead: u64,
+    behind: u64,
+}
+
+/// Parse `git status --porcelain=v2 --branch`.
+/// `# branch.ab` is absent when there is no upstream, leaving ahead/behind at 0.
+fn parse_porcelain_v2(output: &str) -> PorcelainStatus {
+    let mut status = PorcelainStatus::default();
+    for line in output.lines() {
+        if let Some(head) = line.strip_prefix("# branch.head ") {
+            status.head = Some(head.trim().to_string());
+        } else if let Some(ab) = line.strip_prefix("# branch.ab ") {
+            // "+3 -1"
+            for part in ab.split_whitespace() {
+                if let Some(n) = part.strip_prefix('+').and_then(|n| n.parse().ok()) {
+                    status.ahead = n;
+                } else if let Some(n) = part.strip_prefix('-').and_then(|n| n.parse().ok()) {
+                    status.behind = n;
+                }
+            }
+        } else if line.starts_with("? ") {
+            status.untracked += 1;
+        } else if line.starts_with("1 ") || line.starts_with("2 ") {
+            // "1 XY <sub> ...": <sub> is "N..." for files, "S<c><m><u>" for submodules.
+            // Changed entries are only listed when something is dirty.
+            if line
+                .split(' ')
+                .nth(2)
+                .is_some_and(|sub| sub.starts_with('S'))
+            {
+                status.dirty_submodules += 1;
+            }
+        }
+    }
+    status
+}
+
+fn parse_last_commit(output: &str) -> Option<GitCommitInfo> {
+    let mut parts = output.trim_end_matches('\n').splitn(4, '\x1f');
+    let hash = parts.next()?.trim();
+    if hash.is_empty() {
+        return None;
+    }
+    Some(GitCommitInfo {
+        hash: hash.to_string(),
+        subject: parts.next()?.to_string(),
+        author: parts.next()?.to_string(),
+        timestamp: parse_timestamp(parts.next()?)
+            .map(format_timestamp)
+            .unwrap_or_default(),
+    })
+}
+
+/// Parse `git diff --numstat`, largest changes first and capped.
+fn parse_numstat(output: &str) -> Vec<GitFileChange> {
+    sort_and_cap_files(numstat_files(output))
+}
+
+/// Every file in `git diff --numstat`, in git's order. Binary files (`-\t-\tpath`)
+/// count as 0/0.
+pub(super) fn numstat_files(output: &str) -> Vec<GitFileChange> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, '\t');
+            let additions = parts.next()?.parse().unwrap_or(0);
+            let deletions = parts.next()?.parse().unwrap_or(0);
+            let path = parts.next()?.to_string();
+            Some(GitFileChange {
+                path,
+                additions,
+                deletions,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_shortstat_full() {
+        let output = " 3 files changed, 42 insertions(+), 10 deletions(-)";
+        assert_eq!(parse_shortstat(output), Some((42, 10)));
+    }
+
+    #[test]
+    fn test_parse_shortstat_insertions_only() {
+        let output = " 1 file changed, 5 insertions(+)";
+        assert_eq!(parse_shortstat(output), Some((5, 0)));
+    }
+
+    #[test]
+    fn test_parse_shortstat_deletions_only() {
+        let output = " 2 files changed, 3 deletions(-)";
+        assert_eq!(parse_shortstat(output), Some((0, 3)));
+    }
+
+    #[test]
+    fn test_parse_shortstat_empty() {
+        assert_eq!(parse_shortstat(""), Some((0, 0)));
+        assert_eq!(parse_shortstat("  "), Some((0, 0)));
+    }
+
+    #[test]
+    fn test_parse_porcelain_with_upstream() {
+        let output = "# branch.oid 1234abcd\n\
+                      # branch.head main\n\
+                      # branch.upstream origin/main\n\
+                      # branch.ab +3 -1\n\
+                      1 .M N... 100644 100644 100644 aaa bbb src/main.rs\n\
+                      ? new_file.rs\n\
+                      ? docs/notes.md\n";
+        assert_eq!(
+            parse_porcelain_v2(output),
+            PorcelainStatus {
+                head: Some("main".into()),
+                untracked: 2,
+                dirty_submodules: 0,
+                ahead: 3,
+                behind: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_porcelain_without_upstream() {
+        let output = "# branch.oid 1234abcd\n# branch.head feature\n? a.txt\n";
+        assert_eq!(
+            parse_porcelain_v2(output),
+            PorcelainStatus {
+                head: Some("feature".into()),
+                untracked: 1,
+                dirty_submodules: 0,
+                ahead: 0,
+                behind: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_porcelain_detached() {
+        let output = "# branch.oid 1234abcd\n# branch.head (detached)\n";
+        assert_eq!(
+            parse_porcelain_v2(output).head.as_deref(),
+            Some("(detached)")
+        );
+    }
+
+    #[test]
+    fn test_parse_porcelain_submodules() {
+        let output = "# branch.head main\n\
+                      1 .M SC.. 160000 160000 160000 aaa bbb vendor/lib\n\
+                      1 .M S.M. 160000 160000 160000 aaa aaa vendor/other\n\
+                      1 .M N... 100644 100644 100644 aaa bbb README.md\n";
+        assert_eq!(parse_porcelain_v2(output).dirty_submodules, 2);
+    }
+
+    #[test]
+    fn test_parse_rev_parse() {
+        assert_eq!(
+            parse_rev_parse("/repo\n/repo/.git\n/repo/.git\n"),
+            Some(("/repo".into(), false))
+        );
+        assert_eq!(
+            parse_rev_parse("/wt\n/repo/.git/worktrees/wt\n/repo/.git\n"),
+            Some(("/wt".into(), true))
+        );
+        assert_eq!(parse_rev_parse(""), None);
+    }
+
+    #[test]
+    fn test_parse_last_commit() {
+        let output = "abc1234\x1fFix: handle a, b | c\x1fJane Doe\x1f2025-03-01T10:00:00+09:00\n";
+        assert_eq!(
+            parse_last_commit(output),
+            Some(GitCommitInfo {
+                hash: "abc1234".into(),
+                subject: "Fix: handle a, b | c".into(),
+                author: "Jane Doe".into(),
+                timestamp: "2025-03-01T01:00:00.000Z".into(),
+            })
+        );
+        assert_eq!(parse_last_commit(""), None);
+    }
+
+    #[test]
+    fn test_parse_numstat_sorted_and_binary() {
+        let output = "1\t1\tsmall.rs\n-\t-\timage.png\n40\t2\tbig.rs\n";
+        let files = parse_numstat(output);
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[0].path, "big.rs");
+        assert_eq!(files[0].additions, 40);
+        assert_eq!(files[2].path, "image.png");
+        assert_eq!(files[2].additions, 0);
+    }
+
+    #[test]
+    fn test_parse_numstat_capped() {
+        let output: String = (0..80).map(|i| format!("{}\t0\tf{}.rs\n", i, i)).collect();
+        let files = parse_numstat(&output);
+        assert_eq!(files.len(), MAX_CHANGED_FILES);
+        assert_eq!(files[0].path, "f79.rs");
+    }
+
+    fn git(dir: &std::path::Path, args: &[&str]) {
+        let status = std::process::Command::new("git")
+            .args([
+                "-c",
+                "user.name=Test User",
+                "-c",
+                "user.email=test@example.com",
+                "-c",
+                "commit.gpgsign=false",
+                "-c",
+                "init.defaultBranch=main",
+            ])
+            .args(args)
+            .current_dir(dir)
+            .env("GIT_COMMITTER_DATE", "2025-03-01T10:00:00+09:00")
+            .env("GIT_AUTHOR_DATE", "2025-03-01T10:00:00+09:00")
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    /// A repository with a commit, staged and unstaged edits, and untracked files.
+    fn make_fixture_repo() -> Option<std::path::PathBuf> {
+        if std::process::Command::new("git")
+            .arg("--version")
+            .output()
+            .is_err()
+        {
+            return None;
+        }
+        let dir =
+            std::env::temp_dir().join(format!("agents-dashboard-git-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        git(&dir, &["init", "-q"]);
+        std::fs::write(dir.join("a.txt"), "one\ntwo\nthree\nfour\nfive\n").unwrap();
+        std::fs::write(dir.join("b.txt"), "keep\n").unwrap();
+        git(&dir, &["add", "."]);
+        git(&dir, &["commit", "-q", "-m", "Initial commit"]);
+
+        std::fs::write(dir.join("b.txt"), "keep\nstaged line\n").unwrap();
+        git(&dir, &["add", "b.txt"]);
+        std::fs::write(dir.join("a.txt"), "one\nTWO\nthree\nfive\nsix\nseven\n").unwrap();
+        std::fs::write(dir.join("new.txt"), "untracked\n").unwrap();
+        std::fs::create_dir_all(dir.join("newdir")).unwrap();
+        std::fs::write(dir.join("newdir/x.txt"), "x\n").unwrap();
+        std::fs::write(dir.join("newdir/y.txt"), "y\n").unwrap();
+        Some(dir)
+    }
+
+    #[tokio::test]
+    async fn test_libgit2_matches_cli() {
+        let Some(dir) = make_fixture_repo() else {
+            return;
+        };
+        let wd = dir.to_str().unwrap();
+        let cli = fetch_git_status_cli(wd).await.unwrap();
+        let lib = fetch_git_status_libgit2(wd).unwrap();
+
+        assert_eq!((cli.additions, cli.deletions), (3, 2));
+        assert_eq!((cli.staged_additions, cli.staged_deletions), (1, 0));
+        assert_eq!(cli.untracked, 2);
+        assert_eq!(cli.branch, "main");
+        assert_eq!(lib, cli);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_libgit2_detached_head_matches_cli() {
+        let Some(dir) = make_fixture_repo() else {
+            return;
+        };
+        git(&dir, &["checkout", "-q", "--detach"]);
+        let wd = dir.to_str().unwrap();
+        let cli = fetch_git_status_cli(wd).await.unwrap();
+        let lib = fetch_git_status_libgit2(wd).unwrap();
+
+        assert!(cli.branch.starts_with("detached@"));
+        assert_eq!(lib, cli);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_subdirectory_reports_whole_repo() {
+        let Some(dir) = make_fixture_repo() else {
+            return;
+        };
+        let root = fetch_git_status_cli(dir.to_str().unwrap()).await.unwrap();
+        let sub = dir.join("newdir");
+        let cli = fetch_git_status_cli(sub.to_str().unwrap()).await.unwrap();
+        let lib = fetch_git_status_libgit2(sub.to_str().unwrap()).unwrap();
+        assert_eq!(cli, root);
+        assert_eq!(lib, root);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_linked_worktree_branch() {
+        let Some(dir) = make_fixture_repo() else {
+            return;
+        };
+        let wt = dir.with_extension("wt");
+        git(
+            &dir,
+            &[
+                "worktree",
+                "add",
+                "-q",
+                "-b",
+                "feature",
+                wt.to_str().unwrap(),
+            ],
+        );
+        let cli = fetch_git_status_cli(wt.to_str().unwrap()).await.unwrap();
+        let lib = fetch_git_status_libgit2(wt.to_str().unwrap()).unwrap();
+        assert_eq!(cli.branch, "feature");
+        assert!(cli.is_worktree);
+        assert_eq!(lib, cli);
+
+        let main = fetch_git_status_cli(dir.to_str().unwrap()).await.unwrap();
+        assert_eq!(main.branch, "main");
+        assert!(!main.is_worktree);
+        let _ = std::fs::remove_dir_all(&wt);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_dirty_submodule_counted() {
+        let (Some(dir), Some(lib_repo)) = (make_fixture_repo(), make_fixture_repo()) else {
+            return;
+        };
+        git(
+            &dir,
+            &[
+                "-c",
+                "protocol.file.allow=always",
+                "submodule",
+                "add",
+                "-q",
+                lib_repo.to_str().unwrap(),
+                "vendor",
+            ],
+        );
+        git(&dir, &["commit", "-q", "-m", "Add submodule"]);
+        let clean = fetch_git_status_cli(dir.to_str().unwrap()).await.unwrap();
+        assert_eq!(clean.dirty_submodules, 0);
+
+        std::fs::write(dir.join("vendor/a.txt"), "changed\n").unwrap();
+        let cli = fetch_git_status_cli(dir.to_str().unwrap()).await.unwrap();
+        let lib = fetch_git_status_libgit2(dir.to_str().unwrap()).unwrap();
+        assert_eq!(cli.dirty_submodules, 1);
+        assert_eq!(lib.dirty_submodules, cli.dirty_submodules);
+        let _ = std::fs::remove_dir_all(&dir);
+        let _ = std::fs::remove_dir_all(&lib_repo);
+    }
+
+    #[tokio::test]
+    async fn test_non_git_directory_returns_none() {
+        let dir =
+            std::env::temp_dir().join(format!("agents-dashboard-nogit-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // Only meaningful when no parent of the temp dir is itself a repository.
+        let status = tokio::process::Command::new("git")
+            .args(["rev-parse", "--git-dir"])
+            .current_dir(&dir)
+            .output()
+            .await;
+        if matches!(status, Ok(ref o) if !o.status.success()) {
+            assert!(fetch_git_status_cli(dir.to_str().unwrap()).await.is_none());
+            assert!(fetch_git_status_libgit2(dir.to_str().unwrap()).is_none());
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
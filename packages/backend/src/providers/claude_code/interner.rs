@@ -0,0 +1,117 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Shorter text isn't worth looking up: the saving is smaller than the map entry.
+const MIN_INTERNED_BYTES: usize = 64;
+
+/// The distinct message texts of a session, so messages with the same text (a file
+/// read twice, a command re-run) share one allocation.
+///
+/// Texts are found by hash and compared in full, so different texts never share.
+/// Each text's uses are counted, so a shared text's bytes are counted toward the
+/// session's memory once.
+///
+/// Measured on two real session logs (6.5 MB with 479 messages held, 7 MB with
+/// 1,160), sharing cut the bytes counted for their messages by 0.5%: from 303,199 to
+/// 301,787 and from 926,867 to 921,870. Most repeats are short, and long tool output
+/// is cut to the text limits before it's held.
+#[derive(Debug, Clone, Default)]
+pub struct ContentInterner {
+    /// Held texts and how many messages use each.
+    contents: HashMap<Arc<str>, usize>,
+}
+
+impl ContentInterner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The held text equal to `content`, or `content` itself, now held; and whether
+    /// another message already uses it, in which case its bytes are already counted.
+    pub fn intern(&mut self, content: Arc<str>) -> (Arc<str>, bool) {
+        if content.len() < MIN_INTERNED_BYTES {
+            return (content, false);
+        }
+        match self.contents.entry(content) {
+            Entry::Occupied(mut held) => {
+                *held.get_mut() += 1;
+                (held.key().clone(), true)
+            }
+            Entry::Vacant(new) => {
+                let content = new.key().clone();
+                new.insert(1);
+                (content, false)
+            }
+        }
+    }
+
+    /// A message using `content` was dropped. Returns true if it was the last one, so
+    /// the text's bytes are no longer held.
+    pub fn release(&mut self, content: &str) -> bool {
+        match self.contents.get_mut(content) {
+            Some(uses) if *uses > 1 => {
+                *uses -= 1;
+                false
+            }
+            Some(_) => {
+                self.contents.remove(content);
+                true
+            }
+            None => true,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.contents = HashMap::new();
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.contents.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> Arc<str> {
+        s.repeat(20).into()
+    }
+
+    #[test]
+    fn test_same_text_shares_one_allocation() {
+        let mut interner = ContentInterner::new();
+        let (first, shared) = interner.intern(text("cargo build\n"));
+        assert!(!shared);
+        let (second, shared) = interner.intern(text("cargo build\n"));
+        assert!(shared);
+        let (other, _) = interner.intern(text("cargo test\n"));
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(!Arc::ptr_eq(&first, &other));
+        assert_eq!(interner.len(), 2);
+    }
+
+    #[test]
+    fn test_short_text_is_not_held() {
+        let mut interner = ContentInterner::new();
+        let (first, _) = interner.intern("ok".into());
+        let (second, shared) = interner.intern("ok".into());
+        assert!(!shared);
+        assert!(!Arc::ptr_eq(&first, &second));
+        assert_eq!(interner.len(), 0);
+        assert!(interner.release(&first));
+    }
+
+    #[test]
+    fn test_release_forgets_text_after_its_last_use() {
+        let mut interner = ContentInterner::new();
+        let (kept, _) = interner.intern(text("kept "));
+        interner.intern(text("kept "));
+        assert!(!interner.release(&kept));
+        assert_eq!(interner.len(), 1);
+        assert!(interner.release(&kept));
+        assert_eq!(interner.len(), 0);
+    }
+}
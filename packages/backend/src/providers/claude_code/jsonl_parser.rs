@@ -44,6 +44,19 @@ pub struct RawUsage {
     pub cache_read_input_tokens: Option<u64>,
     #[serde(default)]
     pub cache_creation_input_tokens: Option<u64>,
+    /// How the cache writes split by TTL; logged by newer CLI versions.
+    #[serde(default)]
+    pub cache_creation: Option<RawCacheCreation>,
+    #[serde(default)]
+    pub service_tier: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RawCacheCreation {
+    #[serde(default)]
+    pub ephemeral_5m_input_tokens: u64,
+    #[serde(default)]
+    pub ephemeral_1h_input_tokens: u64,
 }
 
 // ── Raw message types ──
@@ -92,6 +105,9 @@ pub struct RawUserMessage {
     pub timestamp: Option<String>,
     #[serde(default)]
     pub git_branch: Option<String>,
+    /// The mode the prompt was sent in, e.g. `plan` or `bypassPermissions`.
+    #[serde(default)]
+    pub permission_mode: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -116,6 +132,10 @@ pub struct RawAssistantMessage {
     pub timestamp: Option<String>,
     #[serde(default)]
     pub git_branch: Option<String>,
+    /// Set on the entry written in place of a response when a request failed for
+    /// good; its text is the error, e.g. `API Error: 529 {...}`.
+    #[serde(default)]
+    pub is_api_error_message: Option<bool>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -129,6 +149,31 @@ pub struct RawSystemEntry {
     pub timestamp: Option<String>,
     #[serde(default)]
     pub duration_ms: Option<u64>,
+    #[serde(default)]
+    pub permission_mode: Option<String>,
+    /// `info`, `warning` or `error`.
+    #[serde(default)]
+    pub level: Option<String>,
+    #[serde(default)]
+    pub content: Option<String>,
+    /// The failed request's error on `api_error` entries, written before each retry.
+    #[serde(default)]
+    pub error: Option<Value>,
+    #[serde(default)]
+    pub retry_attempt: Option<u32>,
+    #[serde(default)]
+    pub max_retries: Option<u32>,
+}
+
+/// Conversation summary. In a resumed session it points at the last message
+/// of the conversation it continues.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RawSummaryEntry {
+    #[serde(default)]
+    pub summary: Option<String>,
+    #[serde(default)]
+    pub leaf_uuid: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -148,12 +193,216 @@ pub struct RawProgressEntry {
     pub parent_uuid: Option<String>,
     #[serde(default)]
     pub data: Option<RawProgressData>,
+    /// The tool call this progress belongs to.
+    #[serde(default, rename = "parentToolUseID")]
+    pub parent_tool_use_id: Option<String>,
     #[serde(default)]
     pub uuid: Option<String>,
     #[serde(default)]
     pub timestamp: Option<String>,
 }
 
+/// Events hooks are configured for, used to tell hook output from other system
+/// messages.
+const HOOK_EVENTS: &[&str] = &[
+    "PreToolUse",
+    "PostToolUse",
+    "Notification",
+    "UserPromptSubmit",
+    "Stop",
+    "SubagentStop",
+    "PreCompact",
+    "SessionStart",
+    "SessionEnd",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookOutcome {
+    Started,
+    Succeeded,
+    /// Failed without stopping anything, e.g. a non-zero exit other than 2.
+    Failed,
+    /// Stopped a tool call or the agent.
+    Blocked,
+}
+
+/// A hook run, from a `hook_progress` progress entry, a `hook_*` attachment, a
+/// `stop_hook_summary`, or the system message older versions write, e.g.
+/// `PostToolUse:Edit [fmt.sh] failed with non-blocking status code 1: ...`.
+#[derive(Debug, Clone)]
+pub struct RawHookEntry {
+    /// `PreToolUse`, `Stop`, ...
+    pub event: String,
+    /// The tool the hook ran for, from names like `PreToolUse:Bash`.
+    pub tool: Option<String>,
+    pub outcome: HookOutcome,
+    /// Why it blocked or failed: the hook's reason or its error output.
+    pub reason: String,
+    pub tool_use_id: Option<String>,
+    pub timestamp: Option<String>,
+}
+
+impl RawHookEntry {
+    fn new(hook_name: &str, event: Option<&str>, outcome: HookOutcome, value: &Value) -> Self {
+        let (name_event, tool) = match hook_name.split_once(':') {
+            Some((event, tool)) => (event, Some(tool.to_string())),
+            None => (hook_name, None),
+        };
+        let field = |key: &str| value.get(key).and_then(|v| v.as_str()).map(str::to_string);
+        Self {
+            event: event.unwrap_or(name_event).to_string(),
+            tool: tool.filter(|t| !t.is_empty()),
+            outcome,
+            reason: String::new(),
+            tool_use_id: field("toolUseID").or_else(|| field("parentToolUseID")),
+            timestamp: field("timestamp"),
+        }
+    }
+
+    fn with_reason(mut self, reason: &str) -> Self {
+        self.reason = strip_ansi(reason)
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        self
+    }
+}
+
+fn str_at<'a>(value: &'a Value, key: &str) -> &'a str {
+    value.get(key).and_then(|v| v.as_str()).unwrap_or("")
+}
+
+fn parse_hook(entry_type: &str, value: &Value) -> Option<RawHookEntry> {
+    match entry_type {
+        "progress" => {
+            let data = value.get("data")?;
+            if str_at(data, "type") != "hook_progress" {
+                return None;
+            }
+            let event = Some(str_at(data, "hookEvent")).filter(|e| !e.is_empty());
+            Some(RawHookEntry::new(
+                str_at(data, "hookName"),
+                event,
+                HookOutcome::Started,
+                value,
+            ))
+        }
+        "attachment" => {
+            let attachment = value.get("attachment")?;
+            let (outcome, reason) = match str_at(attachment, "type") {
+                "hook_success" => (HookOutcome::Succeeded, String::new()),
+                "hook_blocking_error" => {
+                    let error = attachment.get("blockingError");
+                    let reason = match error {
+                        Some(Value::String(reason)) => reason.clone(),
+                        Some(error) => str_at(error, "blockingError").to_string(),
+                        None => String::new(),
+                    };
+                    (HookOutcome::Blocked, reason)
+                }
+                "hook_stopped_continuation" => (
+                    HookOutcome::Blocked,
+                    str_at(attachment, "message").to_string(),
+                ),
+                "hook_non_blocking_error" => {
+                    let reason = [str_at(attachment, "stderr"), str_at(attachment, "stdout")]
+                        .into_iter()
+                        .find(|s| !s.trim().is_empty())
+                        .map(str::to_string)
+                        .or_else(|| {
+                            let code = attachment.get("exitCode")?.as_i64()?;
+                            Some(format!("exit code {}", code))
+                        })
+                        .unwrap_or_default();
+                    (HookOutcome::Failed, reason)
+                }
+                "hook_error_during_execution" => (
+                    HookOutcome::Failed,
+                    str_at(attachment, "content").to_string(),
+                ),
+                "hook_cancelled" => (HookOutcome::Failed, "cancelled".to_string()),
+                _ => return None,
+            };
+            let event = Some(str_at(attachment, "hookEvent")).filter(|e| !e.is_empty());
+            let mut hook = RawHookEntry::new(str_at(attachment, "hookName"), event, outcome, value)
+                .with_reason(&reason);
+            if hook.tool_use_id.is_none() {
+                hook.tool_use_id =
+                    Some(str_at(attachment, "toolUseID").to_string()).filter(|id| !id.is_empty());
+            }
+            Some(hook)
+        }
+        "system" if str_at(value, "subtype") == "stop_hook_summary" => {
+            let errors: Vec<&str> = value
+                .get("hookErrors")
+                .and_then(|v| v.as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(|e| e.as_str())
+                .collect();
+            let stopped =
+                value.get("preventedContinuation").and_then(|v| v.as_bool()) == Some(true);
+            let (outcome, reason) = if stopped {
+                (
+                    HookOutcome::Blocked,
+                    str_at(value, "stopReason").to_string(),
+                )
+            } else if !errors.is_empty() {
+                (HookOutcome::Failed, errors.join("; "))
+            } else {
+                (HookOutcome::Succeeded, String::new())
+            };
+            let mut hook = RawHookEntry::new("Stop", None, outcome, value).with_reason(&reason);
+            // Here it points at the assistant message that ended the turn.
+            hook.tool_use_id = None;
+            Some(hook)
+        }
+        "system" => {
+            let content = strip_ansi(value.get("content")?.as_str()?);
+            let (hook_name, rest) = content.split_once(" [")?;
+            let (event, _) = hook_name.split_once(':').unwrap_or((hook_name, ""));
+            if !HOOK_EVENTS.contains(&event) {
+                return None;
+            }
+            let (_command, result) = rest.split_once("] ")?;
+            let reason = result.split_once(": ").map_or("", |(_, reason)| reason);
+            let outcome = if result.starts_with("completed successfully") {
+                HookOutcome::Succeeded
+            } else if result.starts_with("failed with non-blocking") {
+                HookOutcome::Failed
+            } else if result.contains("blocking error") || result.contains("status code 2") {
+                HookOutcome::Blocked
+            } else if result.starts_with("failed") || result.starts_with("error") {
+                HookOutcome::Failed
+            } else {
+                return None;
+            };
+            Some(RawHookEntry::new(hook_name, None, outcome, value).with_reason(reason))
+        }
+        _ => None,
+    }
+}
+
+/// Drop terminal escape sequences such as the bold hook names are written in.
+fn strip_ansi(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if c.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+            }
+            continue;
+        }
+        out.push(c);
+    }
+    out
+}
+
 // ── Unified entry ──
 
 #[derive(Debug, Clone)]
@@ -162,9 +411,20 @@ pub enum RawEntry {
     Assistant(RawAssistantMessage),
     System(RawSystemEntry),
     Progress(RawProgressEntry),
+    Summary(RawSummaryEntry),
+    Hook(RawHookEntry),
+    /// A line over the length limit, skipped without being parsed.
+    Oversized(OversizedLine),
     Other, // file-history-snapshot, queue-operation, etc.
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OversizedLine {
+    /// Length of the line, without its newline.
+    pub bytes: u64,
+    pub max_line_bytes: u64,
+}
+
 pub fn parse_jsonl_line(line: &str) -> Option<RawEntry> {
     let trimmed = line.trim();
     if trimmed.is_empty() {
@@ -175,6 +435,10 @@ pub fn parse_jsonl_line(line: &str) -> Option<RawEntry> {
     let value: Value = serde_json::from_str(trimmed).ok()?;
     let entry_type = value.get("type")?.as_str()?;
 
+    if let Some(hook) = parse_hook(entry_type, &value) {
+        return Some(RawEntry::Hook(hook));
+    }
+
     match entry_type {
         "user" => {
             let msg: RawUserMessage = serde_json::from_value(value).ok()?;
@@ -192,6 +456,10 @@ pub fn parse_jsonl_line(line: &str) -> Option<RawEntry> {
             let entry: RawProgressEntry = serde_json::from_value(value).ok()?;
             Some(RawEntry::Progress(entry))
         }
+        "summary" => {
+            let entry: RawSummaryEntry = serde_json::from_value(value).ok()?;
+            Some(RawEntry::Summary(entry))
+        }
         _ => Some(RawEntry::Other),
     }
 }
@@ -221,6 +489,106 @@ pub fn parse_jsonl_chunk(chunk: &str) -> ParseResult {
     ParseResult { entries, remainder }
 }
 
+/// Longest log line parsed by default. Longer ones are mostly tool results holding a
+/// whole file or image as base64.
+pub const DEFAULT_MAX_LINE_BYTES: usize = 8 * 1024 * 1024;
+
+/// Splits a log into entries as it is read, keeping only the line in progress between
+/// reads. Lines are decoded whole, so a character split across reads comes out
+/// intact. A line longer than `max_line_bytes` is dropped as it arrives rather than
+/// buffered, and becomes an `Oversized` entry once it ends.
+#[derive(Debug)]
+pub struct LineReader {
+    partial: Vec<u8>,
+    /// Bytes so far of an over-long line being skipped.
+    skipping: Option<u64>,
+    max_line_bytes: usize,
+    /// Entries read so far.
+    entries: u64,
+    /// Lines read so far that held no entry: not JSON, or not shaped like their `type`.
+    parse_errors: u64,
+}
+
+impl LineReader {
+    pub fn new(max_line_bytes: usize) -> Self {
+        Self {
+            partial: Vec::new(),
+            skipping: None,
+            max_line_bytes,
+            entries: 0,
+            parse_errors: 0,
+        }
+    }
+
+    pub fn entries_read(&self) -> u64 {
+        self.entries
+    }
+
+    pub fn parse_errors(&self) -> u64 {
+        self.parse_errors
+    }
+
+    /// Bytes read that don't end a line yet.
+    pub fn pending_bytes(&self) -> u64 {
+        self.partial.len() as u64 + self.skipping.unwrap_or(0)
+    }
+
+    /// The entries of the lines `bytes` completes.
+    pub fn push(&mut self, mut bytes: &[u8]) -> Vec<RawEntry> {
+        let mut entries = Vec::new();
+        while let Some(end) = bytes.iter().position(|&b| b == b'\n') {
+            let line = &bytes[..end];
+            let entry = if let Some(skipped) = self.skipping.take() {
+                Some(self.oversized(skipped + line.len() as u64))
+            } else if self.partial.is_empty() {
+                self.parse(line)
+            } else {
+                let mut partial = std::mem::take(&mut self.partial);
+                partial.extend_from_slice(line);
+                let entry = self.parse(&partial);
+                // Keep the allocation for the next partial line.
+                partial.clear();
+                self.partial = partial;
+                entry
+            };
+            if let Some(entry) = entry {
+                self.entries += 1;
+                entries.push(entry);
+            }
+            bytes = &bytes[end + 1..];
+        }
+
+        if let Some(skipped) = &mut self.skipping {
+            *skipped += bytes.len() as u64;
+        } else if self.partial.len() + bytes.len() > self.max_line_bytes {
+            self.skipping = Some((self.partial.len() + bytes.len()) as u64);
+            self.partial = Vec::new();
+        } else {
+            self.partial.extend_from_slice(bytes);
+        }
+        entries
+    }
+
+    fn parse(&mut self, line: &[u8]) -> Option<RawEntry> {
+        if line.len() > self.max_line_bytes {
+            return Some(self.oversized(line.len() as u64));
+        }
+        let line = String::from_utf8_lossy(line);
+        let entry = parse_jsonl_line(&line);
+        if entry.is_none() && !line.trim().is_empty() {
+            self.parse_errors += 1;
+        }
+        entry
+    }
+
+    fn oversized(&self, bytes: u64) -> RawEntry {
+        RawEntry::Oversized(OversizedLine {
+            bytes,
+            max_line_bytes: self.max_line_bytes as u64,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -264,6 +632,18 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_summary_entry() {
+        let line = r#"{"type":"summary","summary":"Fix login bug","leafUuid":"a9"}"#;
+        match parse_jsonl_line(line).unwrap() {
+            RawEntry::Summary(entry) => {
+                assert_eq!(entry.summary.as_deref(), Some("Fix login bug"));
+                assert_eq!(entry.leaf_uuid.as_deref(), Some("a9"));
+            }
+            _ => panic!("Expected Summary entry"),
+        }
+    }
+
     #[test]
     fn test_parse_chunk_with_remainder() {
         let chunk = "line1\nline2\npartial";
@@ -287,6 +667,161 @@ mod tests {
         assert!(matches!(entry, RawEntry::Other));
     }
 
+    #[test]
+    fn test_parse_cache_ttl_usage() {
+        let usages: Vec<RawUsage> = parse_jsonl_chunk(include_str!("testdata/cache_ttl.jsonl"))
+            .entries
+            .into_iter()
+            .filter_map(|entry| match entry {
+                RawEntry::Assistant(msg) => msg.message.usage,
+                _ => None,
+            })
+            .collect();
+        assert_eq!(usages.len(), 2);
+        let usage = &usages[0];
+        assert_eq!(usage.cache_creation_input_tokens, Some(12_000));
+        let split = usage.cache_creation.as_ref().unwrap();
+        assert_eq!(split.ephemeral_5m_input_tokens, 2_000);
+        assert_eq!(split.ephemeral_1h_input_tokens, 10_000);
+        assert_eq!(usage.service_tier.as_deref(), Some("standard"));
+
+        // Older lines have neither.
+        let line = r#"{"type":"assistant","message":{"content":[],"usage":{"input_tokens":4,"output_tokens":9,"cache_creation_input_tokens":10}},"uuid":"a1"}"#;
+        let RawEntry::Assistant(msg) = parse_jsonl_line(line).unwrap() else {
+            panic!("Expected Assistant entry");
+        };
+        let usage = msg.message.usage.unwrap();
+        assert!(usage.cache_creation.is_none());
+        assert!(usage.service_tier.is_none());
+    }
+
+    const HOOKS: &str = include_str!("testdata/hooks.jsonl");
+
+    #[test]
+    fn test_parse_hook_entries() {
+        let hooks: Vec<RawHookEntry> = parse_jsonl_chunk(HOOKS)
+            .entries
+            .into_iter()
+            .filter_map(|entry| match entry {
+                RawEntry::Hook(hook) => Some(hook),
+                _ => None,
+            })
+            .collect();
+        let summary: Vec<(&str, Option<&str>, HookOutcome, Option<&str>)> = hooks
+            .iter()
+            .map(|h| {
+                (
+                    h.event.as_str(),
+                    h.tool.as_deref(),
+                    h.outcome,
+                    h.tool_use_id.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (
+                    "PreToolUse",
+                    Some("Bash"),
+                    HookOutcome::Started,
+                    Some("toolu_01RmBuildDir")
+                ),
+                (
+                    "PreToolUse",
+                    Some("Bash"),
+                    HookOutcome::Blocked,
+                    Some("toolu_01RmBuildDir")
+                ),
+                (
+                    "PreToolUse",
+                    Some("Bash"),
+                    HookOutcome::Succeeded,
+                    Some("toolu_01MakeClean")
+                ),
+                (
+                    "PostToolUse",
+                    Some("Bash"),
+                    HookOutcome::Failed,
+                    Some("toolu_01MakeClean")
+                ),
+                ("Stop", None, HookOutcome::Failed, None),
+            ]
+        );
+        assert_eq!(
+            hooks[1].reason,
+            "rm -rf is not allowed in this repository; use `make clean`"
+        );
+        assert_eq!(
+            hooks[3].reason,
+            "/bin/sh: terminal-notifier: command not found"
+        );
+        assert_eq!(
+            hooks[4].reason,
+            "~/.claude/hooks/log-session.py: ModuleNotFoundError: No module named 'requests'"
+        );
+        assert_eq!(
+            hooks[1].timestamp.as_deref(),
+            Some("2025-10-14T09:12:05.017Z")
+        );
+
+        // Other progress and system entries are left alone.
+        let line = r#"{"type":"progress","data":{"type":"bash_progress","output":"ok"},"parentToolUseID":"t1"}"#;
+        assert!(matches!(
+            parse_jsonl_line(line),
+            Some(RawEntry::Progress(_))
+        ));
+        let line =
+            r#"{"type":"system","content":"Conversation compacted [auto] done","level":"info"}"#;
+        assert!(matches!(parse_jsonl_line(line), Some(RawEntry::System(_))));
+        let line =
+            r#"{"type":"attachment","attachment":{"type":"hook_additional_context","content":[]}}"#;
+        assert!(matches!(parse_jsonl_line(line), Some(RawEntry::Other)));
+    }
+
+    #[test]
+    fn test_hook_system_messages() {
+        let parse = |content: &str| {
+            let line = serde_json::json!({"type": "system", "content": content}).to_string();
+            match parse_jsonl_line(&line) {
+                Some(RawEntry::Hook(hook)) => {
+                    Some((hook.event, hook.tool, hook.outcome, hook.reason))
+                }
+                _ => None,
+            }
+        };
+        assert_eq!(
+            parse(
+                "\u{1b}[1mPostToolUse:Edit\u{1b}[22m [npx prettier --write] completed successfully"
+            ),
+            Some((
+                "PostToolUse".to_string(),
+                Some("Edit".to_string()),
+                HookOutcome::Succeeded,
+                String::new()
+            ))
+        );
+        assert_eq!(
+            parse("PreToolUse:Write [./check.sh] failed with status code 2: generated files\nare read-only"),
+            Some((
+                "PreToolUse".to_string(),
+                Some("Write".to_string()),
+                HookOutcome::Blocked,
+                "generated files are read-only".to_string()
+            ))
+        );
+        assert_eq!(
+            parse("UserPromptSubmit [audit.sh] failed with non-blocking status code 1: timeout"),
+            Some((
+                "UserPromptSubmit".to_string(),
+                None,
+                HookOutcome::Failed,
+                "timeout".to_string()
+            ))
+        );
+        assert_eq!(parse("Something [else] completed successfully"), None);
+    }
+
     #[test]
     fn test_parse_empty_line() {
         assert!(parse_jsonl_line("").is_none());
@@ -297,4 +832,114 @@ mod tests {
     fn test_parse_invalid_json() {
         assert!(parse_jsonl_line("not json").is_none());
     }
+
+    /// Entries as `Debug` strings, to compare runs.
+    fn describe(entries: &[RawEntry]) -> Vec<String> {
+        entries.iter().map(|e| format!("{:?}", e)).collect()
+    }
+
+    fn adversarial_log() -> String {
+        let long = "x".repeat(300);
+        [
+            r#"{"type":"user","uuid":"u1","message":{"role":"user","content":"héllo 日本語 🦀"}}"#.to_string(),
+            format!(
+                r#"{{"type":"user","uuid":"u2","message":{{"role":"user","content":"{}"}}}}"#,
+                long
+            ),
+            r#"{"type":"system","subtype":"turn_duration","durationMs":100}"#.to_string(),
+            String::new(),
+            r#"{"type":"assistant","uuid":"a1","message":{"content":[{"type":"text","text":"ça va 🦀🦀"}]}}"#.to_string(),
+        ]
+        .join("\n")
+            + "\n"
+    }
+
+    #[test]
+    fn test_line_reader_splits_anywhere() {
+        let log = adversarial_log();
+        let bytes = log.as_bytes();
+        let whole = LineReader::new(200).push(bytes);
+        let kinds: Vec<&str> = whole
+            .iter()
+            .map(|e| match e {
+                RawEntry::User(_) => "user",
+                RawEntry::Oversized(_) => "oversized",
+                RawEntry::System(_) => "system",
+                RawEntry::Assistant(_) => "assistant",
+                _ => "other",
+            })
+            .collect();
+        assert_eq!(kinds, ["user", "oversized", "system", "assistant"]);
+        let expected = describe(&whole);
+
+        // Every single split point, then many random multi-way splits.
+        for split in 0..=bytes.len() {
+            let mut reader = LineReader::new(200);
+            let mut entries = reader.push(&bytes[..split]);
+            entries.extend(reader.push(&bytes[split..]));
+            assert_eq!(describe(&entries), expected, "split at {}", split);
+            assert_eq!(reader.pending_bytes(), 0);
+        }
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        for _ in 0..500 {
+            let mut reader = LineReader::new(200);
+            let mut entries = Vec::new();
+            let mut at = 0;
+            while at < bytes.len() {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                let end = (at + 1 + (seed % 40) as usize).min(bytes.len());
+                entries.extend(reader.push(&bytes[at..end]));
+                at = end;
+            }
+            assert_eq!(describe(&entries), expected);
+        }
+    }
+
+    #[test]
+    fn test_line_reader_skips_long_lines_without_buffering() {
+        let mut reader = LineReader::new(16);
+        assert!(reader.push(b"{\"type\":").is_empty());
+        assert_eq!(reader.pending_bytes(), 8);
+        // Over the limit: dropped, only counted.
+        assert!(reader.push(b"\"user\",\"pad\":\"").is_empty());
+        assert!(reader.partial.is_empty());
+        assert!(reader.push(&[b'a'; 1000]).is_empty());
+        assert_eq!(reader.pending_bytes(), 1022);
+        let entries = reader.push(b"\"}\n{\"type\":\"x\"}\npart");
+        assert_eq!(entries.len(), 2);
+        assert!(matches!(
+            &entries[0],
+            RawEntry::Oversized(line) if line.bytes == 1024 && line.max_line_bytes == 16
+        ));
+        assert!(matches!(entries[1], RawEntry::Other));
+        assert_eq!(reader.pending_bytes(), 4);
+    }
+
+    #[test]
+    fn test_line_reader_counts_entries_and_parse_errors() {
+        let mut reader = LineReader::new(1024);
+        reader.push(b"{\"type\":\"x\"}\n\n{not json");
+        reader.push(b"}\n{\"no_type\":1}\n{\"type\":\"user\",\"message\":1}\n");
+        reader.push(b"   \n{\"type\":\"summary\",\"summary\":\"s\"}\n");
+        assert_eq!(reader.entries_read(), 2);
+        // Blank lines don't count; the rest do, however they broke.
+        assert_eq!(reader.parse_errors(), 3);
+    }
+
+    #[test]
+    fn test_line_reader_keeps_invalid_utf8_consistent() {
+        // A stray continuation byte and a truncated sequence, split mid-character.
+        let line =
+            b"{\"type\":\"user\",\"message\":{\"role\":\"user\",\"content\":\"a\x80b\xe6\x97\"}}\n";
+        let whole = describe(&LineReader::new(1024).push(line));
+        assert_eq!(whole.len(), 1);
+        for split in 0..line.len() {
+            let mut reader = LineReader::new(1024);
+            let mut entries = reader.push(&line[..split]);
+            entries.extend(reader.push(&line[split..]));
+            assert_eq!(describe(&entries), whole);
+        }
+    }
 }
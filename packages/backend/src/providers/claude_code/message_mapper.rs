@@ -1,25 +1,31 @@
+use crate::cost::{CacheCreationTtl, EntryUsage};
 use crate::types::{AgentMessage, MessageRole, MessageType};
-use super::jsonl_parser::{RawAssistantMessage, RawContentBlock, RawEntry, RawUserMessage};
+use super::failed_turns::{is_failed_turn, retry_error, Failure};
+use super::jsonl_parser::{
+    HookOutcome, OversizedLine, RawAssistantMessage, RawContentBlock, RawEntry, RawHookEntry,
+    RawSystemEntry, RawUserMessage,
+};
+use super::redact::Redactor;
+use super::text_util::{truncate, truncated, TextLimits};
+use super::timestamps::{normalize_timestamp, now_timestamp};
 use serde_json::json;
-use std::sync::atomic::{AtomicU64, Ordering};
 
-static MESSAGE_COUNTER: AtomicU64 = AtomicU64::new(0);
-
-fn next_id() -> String {
-    let id = MESSAGE_COUNTER.fetch_add(1, Ordering::Relaxed);
-    format!("msg_{}", id + 1)
+/// Where an entry sits: the session whose log it came from and its position in that
+/// log. Entries without a uuid get ids derived from it, so the same entry maps to the
+/// same id across sessions and server restarts.
+#[derive(Debug, Clone, Copy)]
+pub struct EntryPosition<'a> {
+    pub session_id: &'a str,
+    pub index: u64,
 }
 
-fn truncate(s: &str, max_len: usize) -> String {
-    if s.len() <= max_len {
-        return s.to_string();
-    }
-    // Find a valid char boundary at or before max_len
-    let mut end = max_len;
-    while !s.is_char_boundary(end) && end > 0 {
-        end -= 1;
+/// Id of the message for content block `block` of an entry. Entries can hold several
+/// blocks (text + tool_use), so the entry uuid alone isn't unique.
+fn block_id(uuid: Option<&str>, position: EntryPosition, block: usize) -> String {
+    match uuid {
+        Some(uuid) => format!("{}:{}", uuid, block),
+        None => format!("{}:{}:{}", position.session_id, position.index, block),
     }
-    format!("{}...", &s[..end])
 }
 
 const SYSTEM_XML_TAGS: &[&str] = &[
@@ -36,8 +42,7 @@ fn strip_system_xml_tags(text: &str) -> String {
     for tag in SYSTEM_XML_TAGS {
         let open = format!("<{}", tag);
         let close = format!("</{}>", tag);
-        loop {
-            let Some(start) = result.find(&open) else { break };
+        while let Some(start) = result.find(&open) {
             if let Some(end_offset) = result[start..].find(&close) {
                 let end = start + end_offset + close.len();
                 result.replace_range(start..end, "");
@@ -51,29 +56,51 @@ fn strip_system_xml_tags(text: &str) -> String {
     result.trim().to_string()
 }
 
-fn map_user_message(entry: &RawUserMessage) -> Vec<AgentMessage> {
+/// A mapped message, plus the original text when `content` had to be truncated.
+pub type MappedMessage = (AgentMessage, Option<String>);
+
+/// Truncate for storage, keeping the original if anything was cut.
+fn truncate_keeping(s: &str, max_graphemes: usize) -> (String, Option<String>) {
+    match truncated(s, max_graphemes) {
+        Some(stored) => (stored, Some(s.to_string())),
+        None => (s.to_string(), None),
+    }
+}
+
+fn map_user_message(
+    entry: &RawUserMessage,
+    position: EntryPosition,
+    redactor: &Redactor,
+    limits: &TextLimits,
+) -> Vec<MappedMessage> {
     let mut messages = Vec::new();
     let session_id = entry.session_id.clone().unwrap_or_default();
-    let timestamp = entry.timestamp.clone().unwrap_or_default();
-    let uuid = entry.uuid.clone();
+    let timestamp = entry_timestamp(entry.timestamp.as_deref());
+    let uuid = entry.uuid.as_deref();
 
     let content = &entry.message.content;
 
     // String content
     if let Some(text) = content.as_str() {
-        messages.push(AgentMessage {
-            id: uuid.clone().unwrap_or_else(next_id),
-            session_id: session_id.clone(),
-            timestamp: timestamp.clone(),
-            role: MessageRole::User,
-            msg_type: MessageType::Text,
-            content: truncate(text, 500),
-            metadata: None,
-        });
+        let (stored, full) = truncate_keeping(&redactor.redact(text), limits.message);
+        messages.push((
+            AgentMessage {
+                id: block_id(uuid, position, 0),
+                session_id: session_id.clone(),
+                timestamp: timestamp.clone(),
+                role: MessageRole::User,
+                msg_type: MessageType::Text,
+                content: stored.into(),
+                metadata: None,
+                parent_id: entry.parent_uuid.clone(),
+                tool_use_message_id: None,
+            },
+            full,
+        ));
     }
     // Array content (tool results)
     else if let Some(arr) = content.as_array() {
-        for block in arr {
+        for (index, block) in arr.iter().enumerate() {
             if block.get("type").and_then(|t| t.as_str()) == Some("tool_result") {
                 let result_content = block.get("content").map(|c| {
                     if let Some(s) = c.as_str() {
@@ -90,15 +117,22 @@ fn map_user_message(entry: &RawUserMessage) -> Vec<AgentMessage> {
                 metadata.insert("toolUseId".to_string(), json!(tool_use_id));
                 metadata.insert("isError".to_string(), json!(is_error));
 
-                messages.push(AgentMessage {
-                    id: uuid.clone().unwrap_or_else(next_id),
-                    session_id: session_id.clone(),
-                    timestamp: timestamp.clone(),
-                    role: MessageRole::User,
-                    msg_type: MessageType::ToolResult,
-                    content: truncate(&result_content, 300),
-                    metadata: Some(metadata),
-                });
+                let (stored, full) =
+                    truncate_keeping(&redactor.redact(&result_content), limits.tool_result);
+                messages.push((
+                    AgentMessage {
+                        id: block_id(uuid, position, index),
+                        session_id: session_id.clone(),
+                        timestamp: timestamp.clone(),
+                        role: MessageRole::User,
+                        msg_type: MessageType::ToolResult,
+                        content: stored.into(),
+                        metadata: Some(metadata),
+                        parent_id: entry.parent_uuid.clone(),
+                        tool_use_message_id: None,
+                    },
+                    full,
+                ));
             }
         }
     }
@@ -106,104 +140,346 @@ fn map_user_message(entry: &RawUserMessage) -> Vec<AgentMessage> {
     messages
 }
 
-fn map_assistant_message(entry: &RawAssistantMessage) -> Vec<AgentMessage> {
+fn map_assistant_message(
+    entry: &RawAssistantMessage,
+    position: EntryPosition,
+    redactor: &Redactor,
+) -> Vec<MappedMessage> {
     let mut messages = Vec::new();
     let session_id = entry.session_id.clone().unwrap_or_default();
-    let timestamp = entry.timestamp.clone().unwrap_or_default();
-    let uuid = entry.uuid.clone();
+    let timestamp = entry_timestamp(entry.timestamp.as_deref());
+    let uuid = entry.uuid.as_deref();
+
+    // Written in place of a response: its text is the error.
+    if entry.is_api_error_message == Some(true) {
+        let text: Vec<&str> = entry
+            .message
+            .content
+            .iter()
+            .filter_map(|block| match block {
+                RawContentBlock::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        let mut message = failure_message(
+            block_id(uuid, position, 0),
+            &session_id,
+            timestamp,
+            Failure::FailedTurn,
+            &redactor.redact(&text.join("\n")),
+        );
+        message.parent_id = entry.parent_uuid.clone();
+        return vec![(message, None)];
+    }
 
-    for block in &entry.message.content {
+    for (index, block) in entry.message.content.iter().enumerate() {
         match block {
             RawContentBlock::Text { text } => {
-                messages.push(AgentMessage {
-                    id: uuid.clone().unwrap_or_else(next_id),
-                    session_id: session_id.clone(),
-                    timestamp: timestamp.clone(),
-                    role: MessageRole::Assistant,
-                    msg_type: MessageType::Text,
-                    content: text.clone(),
-                    metadata: None,
-                });
+                messages.push((
+                    AgentMessage {
+                        id: block_id(uuid, position, index),
+                        session_id: session_id.clone(),
+                        timestamp: timestamp.clone(),
+                        role: MessageRole::Assistant,
+                        msg_type: MessageType::Text,
+                        content: redactor.redact(text).into(),
+                        metadata: None,
+                        parent_id: entry.parent_uuid.clone(),
+                        tool_use_message_id: None,
+                    },
+                    None,
+                ));
             }
             RawContentBlock::ToolUse { id, name, input, .. } => {
                 let mut metadata = std::collections::HashMap::new();
                 metadata.insert("toolName".to_string(), json!(name));
                 metadata.insert("toolId".to_string(), json!(id));
-                metadata.insert("input".to_string(), input.clone());
-
-                messages.push(AgentMessage {
-                    id: uuid.clone().unwrap_or_else(next_id),
-                    session_id: session_id.clone(),
-                    timestamp: timestamp.clone(),
-                    role: MessageRole::Assistant,
-                    msg_type: MessageType::ToolUse,
-                    content: name.clone(),
-                    metadata: Some(metadata),
-                });
+                let mut input = input.clone();
+                redactor.redact_json(&mut input);
+                metadata.insert("input".to_string(), input);
+
+                messages.push((
+                    AgentMessage {
+                        id: block_id(uuid, position, index),
+                        session_id: session_id.clone(),
+                        timestamp: timestamp.clone(),
+                        role: MessageRole::Assistant,
+                        msg_type: MessageType::ToolUse,
+                        content: name.as_str().into(),
+                        metadata: Some(metadata),
+                        parent_id: entry.parent_uuid.clone(),
+                        tool_use_message_id: None,
+                    },
+                    None,
+                ));
             }
             // Skip thinking blocks
             _ => {}
         }
     }
 
+    if is_failed_turn(entry) {
+        let reason = entry.message.stop_reason.as_deref().unwrap_or_default();
+        let mut message = failure_message(
+            block_id(uuid, position, entry.message.content.len()),
+            &session_id,
+            timestamp,
+            Failure::FailedTurn,
+            &format!("Response stopped: {}", reason),
+        );
+        message.parent_id = entry.parent_uuid.clone();
+        messages.push((message, None));
+    }
+
     messages
 }
 
-pub fn map_entry(entry: &RawEntry, session_id: &str) -> Vec<AgentMessage> {
+/// An Error message for a failed turn or a retried request.
+fn failure_message(
+    id: String,
+    session_id: &str,
+    timestamp: String,
+    failure: Failure,
+    text: &str,
+) -> AgentMessage {
+    let mut metadata = std::collections::HashMap::new();
+    let failure = match failure {
+        Failure::FailedTurn => "failed_turn",
+        Failure::Retry => "retry",
+    };
+    metadata.insert("failure".to_string(), json!(failure));
+    AgentMessage {
+        id,
+        session_id: session_id.to_string(),
+        timestamp,
+        role: MessageRole::System,
+        msg_type: MessageType::Error,
+        content: text.into(),
+        metadata: Some(metadata),
+        parent_id: None,
+        tool_use_message_id: None,
+    }
+}
+
+/// A retried request: the error it failed with, and which attempt comes next.
+fn map_retry(sys: &RawSystemEntry, error: &str, position: EntryPosition) -> AgentMessage {
+    let text = match (sys.retry_attempt, sys.max_retries) {
+        (Some(attempt), Some(max)) => format!("{} · retry {}/{}", error, attempt, max),
+        (Some(attempt), None) => format!("{} · retry {}", error, attempt),
+        _ => error.to_string(),
+    };
+    let mut message = failure_message(
+        block_id(None, position, 0),
+        position.session_id,
+        entry_timestamp(sys.timestamp.as_deref()),
+        Failure::Retry,
+        &text,
+    );
+    if let Some(metadata) = &mut message.metadata {
+        if let Some(attempt) = sys.retry_attempt {
+            metadata.insert("retryAttempt".to_string(), json!(attempt));
+        }
+        if let Some(max) = sys.max_retries {
+            metadata.insert("maxRetries".to_string(), json!(max));
+        }
+    }
+    message
+}
+
+pub fn map_entry(
+    entry: &RawEntry,
+    position: EntryPosition,
+    redactor: &Redactor,
+    limits: &TextLimits,
+) -> Vec<AgentMessage> {
+    map_entry_full(entry, position, redactor, limits)
+        .into_iter()
+        .map(|(message, _)| message)
+        .collect()
+}
+
+/// Like `map_entry`, but also returns the untruncated text of truncated messages.
+/// Secrets are masked in both before anything is truncated.
+pub fn map_entry_full(
+    entry: &RawEntry,
+    position: EntryPosition,
+    redactor: &Redactor,
+    limits: &TextLimits,
+) -> Vec<MappedMessage> {
     match entry {
-        RawEntry::User(user_msg) => map_user_message(user_msg),
-        RawEntry::Assistant(assistant_msg) => map_assistant_message(assistant_msg),
+        RawEntry::User(user_msg) => map_user_message(user_msg, position, redactor, limits),
+        RawEntry::Assistant(assistant_msg) => {
+            map_assistant_message(assistant_msg, position, redactor)
+        }
         RawEntry::System(sys) => {
             if sys.subtype.as_deref() == Some("turn_duration") {
                 let duration_ms = sys.duration_ms.unwrap_or(0);
-                let ts = sys.timestamp.clone().unwrap_or_else(|| chrono::Utc::now().to_rfc3339());
+                let ts = entry_timestamp(sys.timestamp.as_deref());
                 let mut metadata = std::collections::HashMap::new();
                 metadata.insert("durationMs".to_string(), json!(duration_ms));
-                vec![AgentMessage {
-                    id: next_id(),
-                    session_id: session_id.to_string(),
-                    timestamp: ts,
-                    role: MessageRole::System,
-                    msg_type: MessageType::StateChange,
-                    content: format!("Turn completed ({}ms)", duration_ms),
-                    metadata: Some(metadata),
-                }]
+                vec![(
+                    AgentMessage {
+                        id: block_id(None, position, 0),
+                        session_id: position.session_id.to_string(),
+                        timestamp: ts,
+                        role: MessageRole::System,
+                        msg_type: MessageType::StateChange,
+                        content: format!("Turn completed ({}ms)", duration_ms).into(),
+                        metadata: Some(metadata),
+                        parent_id: None,
+                        tool_use_message_id: None,
+                    },
+                    None,
+                )]
+            } else if let Some(error) = retry_error(sys) {
+                vec![(map_retry(sys, &redactor.redact(&error), position), None)]
             } else {
                 vec![]
             }
         }
+        RawEntry::Hook(hook) => map_hook(hook, position, redactor, limits)
+            .into_iter()
+            .collect(),
+        RawEntry::Oversized(line) => vec![(map_oversized(line, position), None)],
         _ => vec![],
     }
 }
 
-pub fn extract_session_metadata(entry: &RawUserMessage) -> (String, String, String) {
+/// An entry's timestamp, normalized; entries without one are stamped when read.
+fn entry_timestamp(raw: Option<&str>) -> String {
+    raw.map_or_else(now_timestamp, normalize_timestamp)
+}
+
+/// Says a log line was left out rather than leaving a silent gap in the transcript.
+fn map_oversized(line: &OversizedLine, position: EntryPosition) -> AgentMessage {
+    let mb = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+    let mut metadata = std::collections::HashMap::new();
+    metadata.insert("lineBytes".to_string(), json!(line.bytes));
+    metadata.insert("maxLineBytes".to_string(), json!(line.max_line_bytes));
+    AgentMessage {
+        id: block_id(None, position, 0),
+        session_id: position.session_id.to_string(),
+        timestamp: now_timestamp(),
+        role: MessageRole::System,
+        msg_type: MessageType::Error,
+        content: format!(
+            "Skipped a {:.1} MB log line (the limit is {:.1} MB)",
+            mb(line.bytes),
+            mb(line.max_line_bytes)
+        )
+        .into(),
+        metadata: Some(metadata),
+        parent_id: None,
+        tool_use_message_id: None,
+    }
+}
+
+/// Hooks that failed or blocked something, e.g. `Hook PreToolUse(Bash) blocked:
+/// <reason>`. Hooks that ran fine aren't shown.
+fn map_hook(
+    hook: &RawHookEntry,
+    position: EntryPosition,
+    redactor: &Redactor,
+    limits: &TextLimits,
+) -> Option<MappedMessage> {
+    let (verb, outcome) = match hook.outcome {
+        HookOutcome::Blocked => ("blocked", "blocked"),
+        HookOutcome::Failed => ("failed", "failed"),
+        HookOutcome::Started | HookOutcome::Succeeded => return None,
+    };
+    let name = match &hook.tool {
+        Some(tool) => format!("{}({})", hook.event, tool),
+        None => hook.event.clone(),
+    };
+    let text = if hook.reason.is_empty() {
+        format!("Hook {} {}", name, verb)
+    } else {
+        format!("Hook {} {}: {}", name, verb, hook.reason)
+    };
+    let (content, full) = truncate_keeping(&redactor.redact(&text), limits.message);
+    let mut metadata = std::collections::HashMap::new();
+    metadata.insert("hookEvent".to_string(), json!(hook.event));
+    metadata.insert("hookOutcome".to_string(), json!(outcome));
+    if let Some(tool) = &hook.tool {
+        metadata.insert("toolName".to_string(), json!(tool));
+    }
+    if let Some(id) = &hook.tool_use_id {
+        metadata.insert("toolUseId".to_string(), json!(id));
+    }
+    Some((
+        AgentMessage {
+            id: block_id(None, position, 0),
+            session_id: position.session_id.to_string(),
+            timestamp: entry_timestamp(hook.timestamp.as_deref()),
+            role: MessageRole::System,
+            msg_type: MessageType::Error,
+            content: content.into(),
+            metadata: Some(metadata),
+            parent_id: None,
+            tool_use_message_id: None,
+        },
+        full,
+    ))
+}
+
+pub fn extract_session_metadata(
+    entry: &RawUserMessage,
+    redactor: &Redactor,
+    limits: &TextLimits,
+) -> (String, String, String) {
     let session_id = entry.session_id.clone().unwrap_or_default();
     let cwd = entry.cwd.clone().unwrap_or_default();
-    let current_task = if let Some(text) = entry.message.content.as_str() {
-        let cleaned = strip_system_xml_tags(text);
+    let initial_task = if let Some(text) = entry.message.content.as_str() {
+        let cleaned = strip_system_xml_tags(&redactor.redact(text));
         if cleaned.is_empty() {
             String::new()
         } else {
-            truncate(&cleaned, 200)
+            truncate(&cleaned, limits.task)
         }
     } else {
         String::new()
     };
-    (session_id, cwd, current_task)
+    (session_id, cwd, initial_task)
+}
+
+/// The start of an assistant reply as one line of plain text: emphasis markers
+/// (`*`, `_`, `~`, backticks) dropped, whitespace collapsed, cut to `max`
+/// graphemes. An underscore inside a word, as in `snake_case`, is kept.
+pub fn reply_preview(text: &str, max: usize) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let plain: String = chars
+        .iter()
+        .enumerate()
+        .filter(|&(i, &c)| match c {
+            '*' | '~' | '`' => false,
+            '_' => {
+                i > 0
+                    && chars[i - 1].is_alphanumeric()
+                    && chars.get(i + 1).is_some_and(|next| next.is_alphanumeric())
+            }
+            _ => true,
+        })
+        .map(|(_, &c)| c)
+        .collect();
+    let line = plain.split_whitespace().collect::<Vec<_>>().join(" ");
+    truncate(&line, max)
 }
 
 pub fn extract_model(entry: &RawAssistantMessage) -> &str {
     entry.message.model.as_deref().unwrap_or("unknown")
 }
 
-pub fn extract_usage(entry: &RawAssistantMessage) -> Option<(u64, u64, u64, u64)> {
-    entry.message.usage.as_ref().map(|u| {
-        (
-            u.input_tokens,
-            u.output_tokens,
-            u.cache_read_input_tokens.unwrap_or(0),
-            u.cache_creation_input_tokens.unwrap_or(0),
-        )
+pub fn extract_usage(entry: &RawAssistantMessage) -> Option<EntryUsage> {
+    entry.message.usage.as_ref().map(|u| EntryUsage {
+        input_tokens: u.input_tokens,
+        output_tokens: u.output_tokens,
+        cache_read_tokens: u.cache_read_input_tokens.unwrap_or(0),
+        cache_creation_tokens: u.cache_creation_input_tokens.unwrap_or(0),
+        cache_creation_ttl: u.cache_creation.as_ref().map(|c| CacheCreationTtl {
+            five_minute_tokens: c.ephemeral_5m_input_tokens,
+            one_hour_tokens: c.ephemeral_1h_input_tokens,
+        }),
+        service_tier: u.service_tier.clone(),
     })
 }
 
@@ -213,6 +489,33 @@ mod tests {
     use crate::providers::claude_code::jsonl_parser::*;
     use serde_json::json;
 
+    fn at(session_id: &str, index: u64) -> EntryPosition<'_> {
+        EntryPosition { session_id, index }
+    }
+
+    /// The mapping functions with the default redaction and limits.
+    fn map(entry: &RawEntry, position: EntryPosition) -> Vec<AgentMessage> {
+        map_entry(
+            entry,
+            position,
+            &Redactor::default(),
+            &TextLimits::default(),
+        )
+    }
+
+    fn map_full(entry: &RawEntry, position: EntryPosition) -> Vec<MappedMessage> {
+        map_entry_full(
+            entry,
+            position,
+            &Redactor::default(),
+            &TextLimits::default(),
+        )
+    }
+
+    fn metadata(entry: &RawUserMessage) -> (String, String, String) {
+        extract_session_metadata(entry, &Redactor::default(), &TextLimits::default())
+    }
+
     #[test]
     fn test_map_user_text_message() {
         let entry = RawEntry::User(RawUserMessage {
@@ -229,12 +532,13 @@ mod tests {
             uuid: Some("u1".into()),
             timestamp: Some("2025-01-01T00:00:00Z".into()),
             git_branch: None,
+            permission_mode: None,
         });
-        let msgs = map_entry(&entry, "s1");
+        let msgs = map(&entry, at("s1", 0));
         assert_eq!(msgs.len(), 1);
         assert_eq!(msgs[0].role, MessageRole::User);
         assert_eq!(msgs[0].msg_type, MessageType::Text);
-        assert_eq!(msgs[0].content, "hello world");
+        assert_eq!(&*msgs[0].content, "hello world");
     }
 
     #[test]
@@ -256,8 +560,9 @@ mod tests {
             uuid: Some("u1".into()),
             timestamp: Some("2025-01-01T00:00:00Z".into()),
             git_branch: None,
+            permission_mode: None,
         });
-        let msgs = map_entry(&entry, "s1");
+        let msgs = map(&entry, at("s1", 0));
         assert_eq!(msgs.len(), 1);
         assert_eq!(msgs[0].msg_type, MessageType::ToolResult);
     }
@@ -283,11 +588,12 @@ mod tests {
             uuid: Some("a1".into()),
             timestamp: Some("2025-01-01T00:00:00Z".into()),
             git_branch: None,
+            is_api_error_message: None,
         });
-        let msgs = map_entry(&entry, "s1");
+        let msgs = map(&entry, at("s1", 0));
         assert_eq!(msgs.len(), 1);
         assert_eq!(msgs[0].role, MessageRole::Assistant);
-        assert_eq!(msgs[0].content, "hi there");
+        assert_eq!(&*msgs[0].content, "hi there");
     }
 
     #[test]
@@ -314,15 +620,60 @@ mod tests {
             uuid: Some("a1".into()),
             timestamp: Some("2025-01-01T00:00:00Z".into()),
             git_branch: None,
+            is_api_error_message: None,
         });
-        let msgs = map_entry(&entry, "s1");
+        let msgs = map(&entry, at("s1", 0));
         assert_eq!(msgs.len(), 1);
         assert_eq!(msgs[0].msg_type, MessageType::ToolUse);
-        assert_eq!(msgs[0].content, "Read");
+        assert_eq!(&*msgs[0].content, "Read");
         let meta = msgs[0].metadata.as_ref().unwrap();
         assert_eq!(meta["toolName"], "Read");
     }
 
+    #[test]
+    fn test_block_ids_are_unique_within_entry() {
+        let entry = RawEntry::Assistant(RawAssistantMessage {
+            parent_uuid: None,
+            is_sidechain: None,
+            session_id: Some("s1".into()),
+            version: None,
+            cwd: None,
+            slug: None,
+            message: RawAssistantMessageBody {
+                model: Some("claude-sonnet-4-20250514".into()),
+                id: None,
+                content: vec![
+                    RawContentBlock::Text {
+                        text: "Let me check".into(),
+                    },
+                    RawContentBlock::ToolUse {
+                        id: "t1".into(),
+                        name: "Read".into(),
+                        input: json!({}),
+                        caller: None,
+                    },
+                    RawContentBlock::ToolUse {
+                        id: "t2".into(),
+                        name: "Grep".into(),
+                        input: json!({}),
+                        caller: None,
+                    },
+                ],
+                stop_reason: None,
+                usage: None,
+            },
+            uuid: Some("a1".into()),
+            timestamp: Some("2025-01-01T00:00:00Z".into()),
+            git_branch: None,
+            is_api_error_message: None,
+        });
+        let ids: Vec<String> = map(&entry, at("s1", 0)).into_iter().map(|m| m.id).collect();
+        assert_eq!(ids, ["a1:0", "a1:1", "a1:2"]);
+        // Stable across re-reads of the same entry.
+        let again: Vec<String> = map(&entry, at("s1", 0)).into_iter().map(|m| m.id).collect();
+        assert_eq!(ids, again);
+    }
+
     #[test]
     fn test_map_system_turn_duration() {
         let entry = RawEntry::System(RawSystemEntry {
@@ -330,13 +681,100 @@ mod tests {
             session_id: Some("s1".into()),
             timestamp: Some("2025-01-01T00:00:00Z".into()),
             duration_ms: Some(1500),
+            permission_mode: None,
+            level: None,
+            content: None,
+            error: None,
+            retry_attempt: None,
+            max_retries: None,
         });
-        let msgs = map_entry(&entry, "s1");
+        let msgs = map(&entry, at("s1", 0));
         assert_eq!(msgs.len(), 1);
         assert_eq!(msgs[0].msg_type, MessageType::StateChange);
         assert!(msgs[0].content.contains("1500ms"));
     }
 
+    #[test]
+    fn test_extract_usage_with_cache_ttl() {
+        let entries = parse_jsonl_chunk(include_str!("testdata/cache_ttl.jsonl")).entries;
+        let usages: Vec<EntryUsage> = entries
+            .iter()
+            .filter_map(|entry| match entry {
+                RawEntry::Assistant(msg) => extract_usage(msg),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(usages[0].cache_creation_tokens, 12_000);
+        assert_eq!(
+            usages[0].cache_creation_ttl,
+            Some(CacheCreationTtl {
+                five_minute_tokens: 2_000,
+                one_hour_tokens: 10_000,
+            })
+        );
+        assert_eq!(usages[0].service_tier.as_deref(), Some("standard"));
+        assert_eq!(usages[1].cache_read_tokens, 27_000);
+    }
+
+    #[test]
+    fn test_map_hook_failures() {
+        let entries = parse_jsonl_chunk(include_str!("testdata/hooks.jsonl")).entries;
+        let messages: Vec<AgentMessage> = entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| matches!(entry, RawEntry::Hook(_)))
+            .flat_map(|(i, entry)| map(entry, at("s1", i as u64)))
+            .collect();
+        let contents: Vec<&str> = messages.iter().map(|m| &*m.content).collect();
+        assert_eq!(
+            contents,
+            [
+                "Hook PreToolUse(Bash) blocked: rm -rf is not allowed in this repository; use `make clean`",
+                "Hook PostToolUse(Bash) failed: /bin/sh: terminal-notifier: command not found",
+                "Hook Stop failed: ~/.claude/hooks/log-session.py: ModuleNotFoundError: No module named 'requests'",
+            ]
+        );
+        assert!(messages
+            .iter()
+            .all(|m| m.role == MessageRole::System && m.msg_type == MessageType::Error));
+        let metadata = messages[0].metadata.as_ref().unwrap();
+        assert_eq!(metadata["hookEvent"], "PreToolUse");
+        assert_eq!(metadata["hookOutcome"], "blocked");
+        assert_eq!(metadata["toolName"], "Bash");
+        assert_eq!(metadata["toolUseId"], "toolu_01RmBuildDir");
+        assert_eq!(messages[0].timestamp, "2025-10-14T09:12:05.017Z");
+    }
+
+    #[test]
+    fn test_map_retries_and_failed_turn() {
+        let entries = parse_jsonl_chunk(include_str!("testdata/retried_turn.jsonl")).entries;
+        let messages: Vec<AgentMessage> = entries[..4]
+            .iter()
+            .enumerate()
+            .flat_map(|(i, entry)| map(entry, at("s1", i as u64)))
+            .filter(|m| m.msg_type == MessageType::Error)
+            .collect();
+        let contents: Vec<&str> = messages.iter().map(|m| &*m.content).collect();
+        assert_eq!(
+            contents,
+            [
+                "API Error: 529 Overloaded · retry 1/10",
+                "API Error: 529 Overloaded · retry 2/10",
+                r#"API Error: 529 {"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#,
+            ]
+        );
+        assert_eq!(messages[0].id, "s1:1:0");
+        let metadata = messages[1].metadata.as_ref().unwrap();
+        assert_eq!(metadata["failure"], "retry");
+        assert_eq!(metadata["retryAttempt"], 2);
+        assert_eq!(metadata["maxRetries"], 10);
+        // The error stands in for the response; there is no assistant text.
+        let metadata = messages[2].metadata.as_ref().unwrap();
+        assert_eq!(metadata["failure"], "failed_turn");
+        assert_eq!(messages[2].role, MessageRole::System);
+        assert_eq!(map(&entries[3], at("s1", 3)).len(), 1);
+    }
+
     #[test]
     fn test_extract_model() {
         let msg = RawAssistantMessage {
@@ -356,6 +794,7 @@ mod tests {
             uuid: None,
             timestamp: None,
             git_branch: None,
+            is_api_error_message: None,
         };
         assert_eq!(extract_model(&msg), "claude-opus-4-20250514");
     }
@@ -377,10 +816,49 @@ mod tests {
             uuid: Some("u1".into()),
             timestamp: Some("2025-01-01T00:00:00Z".into()),
             git_branch: None,
+            permission_mode: None,
         });
-        let msgs = map_entry(&entry, "s1");
+        let msgs = map(&entry, at("s1", 0));
         assert_eq!(msgs.len(), 1);
         assert!(msgs[0].content.len() <= 503); // 500 + "..."
+
+        let full = map_full(&entry, at("s1", 0));
+        assert_eq!(full[0].1.as_deref(), Some(long_text.as_str()));
+    }
+
+    #[test]
+    fn test_secrets_masked_before_truncation() {
+        let output = format!("{}\nOPENAI_API_KEY=sk-abcdefghijklmnopqrstuvwxyz", "x".repeat(400));
+        let content = json!([
+            {"type": "tool_result", "tool_use_id": "t1", "content": output, "is_error": false}
+        ]);
+        let entry = RawEntry::User(RawUserMessage {
+            parent_uuid: None,
+            is_sidechain: None,
+            session_id: Some("s1".into()),
+            version: None,
+            cwd: None,
+            slug: None,
+            message: RawUserMessageBody {
+                role: "user".into(),
+                content,
+            },
+            uuid: Some("u1".into()),
+            timestamp: Some("2025-01-01T00:00:00Z".into()),
+            git_branch: None,
+            permission_mode: None,
+        });
+        let full = map_full(&entry, at("s1", 0));
+        let full_text = full[0].1.as_deref().unwrap();
+        assert!(full_text.ends_with("OPENAI_API_KEY=sk-a…wxyz"));
+
+        let unredacted = map_entry_full(
+            &entry,
+            at("s1", 0),
+            &Redactor::new(false, &[]).unwrap(),
+            &TextLimits::default(),
+        );
+        assert!(unredacted[0].1.as_deref().unwrap().contains("sk-abcdefgh"));
     }
 
     #[test]
@@ -449,8 +927,9 @@ mod tests {
             uuid: None,
             timestamp: None,
             git_branch: None,
+            permission_mode: None,
         };
-        let (sid, cwd, task) = extract_session_metadata(&entry);
+        let (sid, cwd, task) = metadata(&entry);
         assert_eq!(sid, "s1");
         assert_eq!(cwd, "/home/user/project");
         assert_eq!(task, "Fix the bug");
@@ -472,8 +951,23 @@ mod tests {
             uuid: None,
             timestamp: None,
             git_branch: None,
+            permission_mode: None,
         };
-        let (_, _, task) = extract_session_metadata(&entry);
+        let (_, _, task) = metadata(&entry);
         assert_eq!(task, "");
     }
+
+    #[test]
+    fn test_reply_preview_is_plain_text() {
+        assert_eq!(
+            reply_preview("**Done.** The `parse_args` fix\n\nis _in_ ~~place~~.", 200),
+            "Done. The parse_args fix is in place."
+        );
+        assert_eq!(
+            reply_preview("__bold__ and snake_case", 200),
+            "bold and snake_case"
+        );
+        assert_eq!(reply_preview("*All tests pass*", 8), "All test...");
+        assert_eq!(reply_preview("***", 200), "");
+    }
 }
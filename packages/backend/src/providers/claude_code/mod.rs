@@ -1,42 +1,820 @@
+pub mod cli_version;
+pub mod clock;
+pub mod content_index;
+pub mod environment;
+pub mod failed_turns;
 #[allow(dead_code)]
 pub mod file_watcher;
+pub mod git_diff;
+pub mod git_status;
+pub mod interner;
 #[allow(dead_code)]
 pub mod jsonl_parser;
 #[allow(dead_code)]
 pub mod message_mapper;
+pub mod model_history;
+pub mod outline;
+pub mod permission_mode;
+pub mod process_probe;
+pub mod project_settings;
+pub mod redact;
+pub mod replay;
+pub mod resume;
+pub mod search_query;
+pub mod seen_entries;
 #[allow(dead_code)]
 pub mod session_discovery;
+pub mod sources;
+pub mod spend_rate;
+pub mod startup;
+pub mod suspend;
 #[allow(dead_code)]
 pub mod state_machine;
+pub mod text_util;
+pub mod timeline;
+pub mod timestamps;
+pub mod tool_stats;
+pub mod usage_series;
 
-use crate::cost::add_usage;
+use crate::analysis::{self, ActivityRule, RECENT_PROMPTS};
+use crate::cost::{ContextLimits, Pricing};
+use crate::days;
+use crate::notifications::NotifyRules;
+use arc_swap::ArcSwap;
+use chrono_tz::Tz;
 use crate::providers::ProviderEvent;
 use crate::types::{
     AgentMessage, AgentSessionDetail, AgentSessionSummary, AgentStateType, CumulativeUsage,
-    GitStatus, MessageRole, MessageType, SearchMatch, SearchScope, SessionSearchResult,
+    DashboardStats, DismissPlan, GitStatus, MessageRole, MessageType, SearchMatch, SearchScope, SessionSearchResult,
+    SessionConfig, SessionOutline, SessionSources, SessionTimeline, SessionToolStats, StartupSummary, StopReplayPlan, TaskPrompt, ToolCount, TranscriptMemory, UsageTimeline, WatcherCounts, WatcherMode,
 };
-use file_watcher::FileWatcher;
-use jsonl_parser::RawEntry;
-use message_mapper::{extract_model, extract_session_metadata, extract_usage, map_entry};
-use session_discovery::{DiscoveredSession, DiscoveryEvent, SessionDiscovery};
-use state_machine::{check_time_based_transitions, process_entry, StateContext};
-use std::collections::{HashMap, HashSet};
+use cli_version::warn_if_untested;
+use content_index::ContentIndex;
+use environment::REMOTE_OR_CONTAINER;
+use failed_turns::{Failure, FailureTracker};
+use file_watcher::{read_tail, FileWatcher, WatchEvent};
+use git_status::{fetch_git_status, GitBackend};
+use interner::ContentInterner;
+use jsonl_parser::{HookOutcome, RawEntry};
+use message_mapper::{
+    extract_model, extract_session_metadata, extract_usage, map_entry_full, reply_preview,
+    EntryPosition,
+};
+use outline::{build_outline, TurnMarks};
+use model_history::{is_real_model, ModelTracker, UNKNOWN_MODEL};
+use permission_mode::{PermissionModeTracker, BYPASS_PERMISSIONS};
+use process_probe::ProcessLookup;
+use project_settings::ProjectSettings;
+use redact::Redactor;
+use replay::{restamp, ReplayStep};
+use resume::{resolve_predecessor, ResumeTracker};
+use search_query::SearchQuery;
+use seen_entries::SeenEntries;
+use session_discovery::{
+    encode_project_path, local_path, project_key, project_name, same_project, scan_project,
+    DehydratedSessions, DiscoveredSession, DiscoveryConfig, DiscoveryEvent, DismissedSessions,
+    SessionDiscovery,
+};
+use sources::SourcesTracker;
+use spend_rate::{rate_changed, SpendRate};
+use startup::{StartupScan, STARTUP_TIMEOUT};
+use suspend::SuspendDetector;
+use text_util::{make_snippet, truncate, TextLimits};
+use state_machine::{
+    check_time_based_transitions, get_entry_timestamp, is_user_prompt, process_entry,
+    process_late_entry, shorten_tool_summary, StateContext, StateTimeouts, TransitionResult,
+};
+use std::borrow::{Borrow, Cow};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
+use timeline::ActivityTimeline;
+use timestamps::{format_timestamp, normalize_timestamp, now_timestamp, parse_timestamp};
+use tool_stats::{sort_tools, ToolStatsTracker};
 use tokio::sync::{mpsc, RwLock};
+use tracing::{info, warn};
+use usage_series::UsageSeries;
 
 struct TrackedSession {
     summary: AgentSessionSummary,
-    messages: Vec<AgentMessage>,
+    /// Shared with whoever asked for them, so handing them out copies no content.
+    messages: Vec<Arc<AgentMessage>>,
+    /// Number of messages ever added; the last message in `messages` is `message_seq - 1`.
+    message_seq: u64,
+    /// Sequence number of each message in `messages`. They increase, except where a
+    /// late entry's messages were put in timestamp order.
+    message_seqs: Vec<u64>,
+    /// About how much memory `messages` take, for the transcript memory budget.
+    retained_bytes: u64,
+    /// Full text of messages whose stored content was truncated, for search.
+    content_index: ContentIndex,
+    /// Texts shared by the messages in `messages` and the content index.
+    interner: ContentInterner,
     state_ctx: StateContext,
     watcher: FileWatcher,
     model: String,
     emitted: bool,
     last_git_diff_check: i64,
+    /// A git status check is running for this session; don't start another.
+    git_check_in_flight: bool,
+    /// The branch came from the repository itself. Claude's gitBranch can name the
+    /// main checkout's branch when running in a linked worktree, so stop using it.
+    branch_from_git: bool,
     last_process_check: i64,
     /// The project path from session discovery (decoded from directory name).
-    /// Unlike summary.project_path which gets updated from JSONL cwd,
+    /// Unlike summary.project_path which gets corrected from JSONL cwd,
     /// this stays stable and is used to group sessions by project.
     discovery_project_path: String,
+    /// summary.project_path has been taken from a cwd naming the project directory
+    /// itself; later cwds (subdirectories the agent moved into) only update current_cwd.
+    project_root_known: bool,
+    /// When a newer session appeared in the same project while this one was still
+    /// writing. Cleared if it keeps writing (it runs in parallel); stopped if it stays
+    /// quiet for the grace period.
+    superseded_at: Option<i64>,
+    timeline: ActivityTimeline,
+    usage_series: UsageSeries,
+    spend_rate: SpendRate,
+    resume: ResumeTracker,
+    title_source: TitleSource,
+    seen_entries: SeenEntries,
+    /// Entries read from the log so far, counting duplicates. The log is re-read from
+    /// the start after a restart, so an entry's index here is stable.
+    entries_read: u64,
+    tool_stats: ToolStatsTracker,
+    sources: SourcesTracker,
+    turn_marks: TurnMarks,
+    permission_mode: PermissionModeTracker,
+    failures: FailureTracker,
+    models: ModelTracker,
+    /// Times the session went back to Idle after working.
+    turns: u64,
+    /// Highest context warning threshold sent since the context was last compacted.
+    context_warned: u32,
+    /// False once the session has been stopped long enough that its watcher was stopped
+    /// and its messages dropped; see `dehydrate`.
+    hydrated: bool,
+    /// When the timer first saw the session Stopped; None while it isn't.
+    stopped_at: Option<i64>,
+    last_classified_at: i64,
+    /// The watcher was found to have stopped reading while the log grew, and the
+    /// session was told. Cleared once it reads again.
+    watcher_stall_reported: bool,
+    /// When a historical session is dropped again.
+    evict_at: Option<i64>,
+    settings: SharedSettings,
+}
+
+impl TrackedSession {
+    /// A session nothing has been read for yet; it isn't shown until its model is known.
+    fn new(
+        summary: AgentSessionSummary,
+        watcher: FileWatcher,
+        discovery_project_path: String,
+        settings: SharedSettings,
+    ) -> Self {
+        let state_ctx = StateContext::new();
+        Self {
+            summary,
+            messages: Vec::new(),
+            message_seq: 0,
+            message_seqs: Vec::new(),
+            retained_bytes: 0,
+            content_index: ContentIndex::new(),
+            interner: ContentInterner::new(),
+            state_ctx,
+            watcher,
+            model: "unknown".to_string(),
+            emitted: false,
+            last_git_diff_check: 0,
+            git_check_in_flight: false,
+            branch_from_git: false,
+            last_process_check: 0,
+            discovery_project_path,
+            project_root_known: false,
+            superseded_at: None,
+            timeline: ActivityTimeline::new(),
+            usage_series: UsageSeries::new(),
+            spend_rate: SpendRate::new(),
+            resume: ResumeTracker::new(),
+            title_source: TitleSource::None,
+            seen_entries: SeenEntries::new(),
+            entries_read: 0,
+            tool_stats: ToolStatsTracker::new(),
+            sources: SourcesTracker::new(),
+            turn_marks: TurnMarks::new(),
+            permission_mode: PermissionModeTracker::new(),
+            failures: FailureTracker::new(),
+            models: ModelTracker::new(),
+            turns: 0,
+            context_warned: 0,
+            hydrated: true,
+            stopped_at: None,
+            last_classified_at: 0,
+            watcher_stall_reported: false,
+            evict_at: None,
+            settings,
+        }
+    }
+}
+
+/// Where a session's title came from, worst first. A title is only replaced by one
+/// from an equal or better source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum TitleSource {
+    None,
+    /// The `slug` Claude Code stamps on every entry.
+    Slug,
+    /// A `summary` entry, written when Claude Code summarizes the conversation.
+    Summary,
+}
+
+impl TrackedSession {
+    /// The settings this session is judged by, resolved from the current global ones
+    /// each time so a config reload applies, project sections included.
+    fn settings(&self) -> Arc<SessionSettings> {
+        self.settings
+            .load()
+            .for_project(&self.discovery_project_path)
+    }
+
+    /// Claim the next git status check. Returns the directory to check, or None if a
+    /// check is already in flight or there is no working directory yet.
+    fn begin_git_check(&mut self, now_ms: i64) -> Option<String> {
+        if self.git_check_in_flight || self.summary.working_directory.is_empty() {
+            return None;
+        }
+        self.git_check_in_flight = true;
+        self.last_git_diff_check = now_ms;
+        Some(self.summary.working_directory.clone())
+    }
+
+    /// Use `title` unless the current one comes from a better source. Returns true if
+    /// the title changed.
+    fn offer_title(&mut self, title: &str, source: TitleSource) -> bool {
+        let title = title.trim();
+        if title.is_empty()
+            || source < self.title_source
+            || self.summary.title.as_deref() == Some(title)
+        {
+            return false;
+        }
+        self.summary.title = Some(title.to_string());
+        self.title_source = source;
+        true
+    }
+
+    /// Whether `cwd` is the project directory rather than somewhere inside it. The
+    /// discovery path is decoded lossily (`agents-dashboard` comes back as
+    /// `agents/dashboard`), so the real path has to come from a cwd. If the decoded
+    /// path doesn't exist there is nothing to compare against; trust the first cwd.
+    fn is_project_root(&self, cwd: &str, checks: &CwdChecks) -> bool {
+        encode_project_path(&project_key(cwd))
+            == encode_project_path(&project_key(&self.discovery_project_path))
+            || checks.discovery_path_missing
+    }
+
+    /// Copy the state machine's state, and the tool call awaiting approval, to the
+    /// summary. The call's summary can hold a command line, so it is redacted.
+    fn sync_state(&mut self) {
+        self.summary.state = self.state_ctx.state;
+        // Redacted before it's shortened, so a secret isn't cut short of matching.
+        self.summary.pending_tool = self.state_ctx.pending_tool.clone().map(|mut tool| {
+            let redactor = self.settings().redactor.clone();
+            tool.summary = shorten_tool_summary(&redactor.redact(&tool.summary));
+            tool
+        });
+    }
+
+    /// Run the activity rules over the tool stats and latest prompts. Returns true if
+    /// the activity changed. A dehydrated session has no prompts to go on, so it
+    /// keeps the activity it had.
+    fn classify_activity(&mut self, now_ms: i64) -> bool {
+        self.last_classified_at = now_ms;
+        if !self.hydrated {
+            return false;
+        }
+        let prompts: Vec<&str> = self
+            .messages
+            .iter()
+            .rev()
+            .filter(|m| m.role == MessageRole::User && m.msg_type == MessageType::Text)
+            .take(RECENT_PROMPTS)
+            .map(|m| &*m.content)
+            .collect();
+        let rules = self.settings().activity_rules.clone();
+        let activity = analysis::classify(&rules, &self.tool_stats.sorted(), &prompts);
+        if activity == self.summary.activity_type {
+            return false;
+        }
+        self.summary.activity_type = activity;
+        true
+    }
+
+    fn is_active(&self) -> bool {
+        matches!(
+            self.state_ctx.state,
+            AgentStateType::Running
+                | AgentStateType::Idle
+                | AgentStateType::PermissionWaiting
+                | AgentStateType::Error
+        )
+    }
+
+    /// Move to Stopped, e.g. because a newer session replaced this one.
+    fn mark_stopped(&mut self, session_id: &str, event_tx: &mpsc::UnboundedSender<ProviderEvent>) {
+        let prev = self.state_ctx.state;
+        self.state_ctx.state = AgentStateType::Stopped;
+        self.state_ctx.pending_tool = None;
+        self.sync_state();
+        self.superseded_at = None;
+        self.record_transition(prev);
+        if self.emitted {
+            let _ = event_tx.send(ProviderEvent::StateChanged {
+                session_id: session_id.to_string(),
+                previous: prev,
+                current: AgentStateType::Stopped,
+            });
+        }
+    }
+
+    /// Stop watching the log and drop the message buffer, keeping the summary, usage
+    /// and transitions. The log is watched again once it grows; until then messages are
+    /// re-read from it when asked for.
+    fn dehydrate(&mut self) {
+        self.watcher.stop();
+        self.messages = Vec::new();
+        self.message_seqs = Vec::new();
+        self.retained_bytes = 0;
+        self.interner.clear();
+        self.hydrated = false;
+    }
+
+    /// Where to re-read the messages of a dehydrated session from.
+    fn log_tail(&self) -> LogTail {
+        let settings = self.settings();
+        LogTail {
+            path: self.watcher.file_path().to_path_buf(),
+            end: self.watcher.offset(),
+            entries_read: self.entries_read,
+            redactor: settings.redactor.clone(),
+            text_limits: settings.text_limits,
+            max_messages: settings.max_messages,
+            max_line_bytes: self.watcher.max_line_bytes(),
+        }
+    }
+
+    /// Add a message the dashboard produced itself rather than read from the log.
+    fn record_message(
+        &mut self,
+        msg_type: MessageType,
+        content: String,
+        metadata: HashMap<String, serde_json::Value>,
+        event_tx: &mpsc::UnboundedSender<ProviderEvent>,
+    ) {
+        let message = AgentMessage {
+            id: format!("dashboard:{}", uuid::Uuid::new_v4()),
+            session_id: self.summary.session_id.clone(),
+            timestamp: now_timestamp(),
+            role: MessageRole::System,
+            msg_type,
+            content: content.into(),
+            metadata: Some(metadata),
+            parent_id: None,
+            tool_use_message_id: None,
+        };
+        // A dehydrated session's messages are read back from the log, which this
+        // message isn't in; clients still get it as an event.
+        if self.hydrated {
+            self.push_message(message.clone());
+        }
+        if self.emitted {
+            send_new_messages(event_tx, &self.summary.session_id, vec![message]);
+        }
+    }
+
+    /// Append a message, trimming the oldest beyond `max_messages`. Trimmed messages
+    /// stay searchable through the content index.
+    fn push_message(&mut self, msg: AgentMessage) {
+        self.insert_message(self.messages.len(), msg);
+    }
+
+    /// Add a message from a late entry where its timestamp puts it instead of last,
+    /// so trimming still drops the oldest.
+    fn push_late_message(&mut self, msg: AgentMessage) {
+        self.insert_message(in_order_position(&self.messages, &msg.timestamp), msg);
+    }
+
+    fn insert_message(&mut self, at: usize, mut msg: AgentMessage) {
+        let (content, shared) = self.interner.intern(msg.content);
+        msg.content = content;
+        self.retained_bytes += message_bytes(&msg, !shared);
+        self.messages.insert(at, Arc::new(msg));
+        self.message_seqs.insert(at, self.message_seq);
+        self.message_seq += 1;
+        let max = self.settings().max_messages;
+        if self.messages.len() > max {
+            // Trim a fifth at once rather than one message per push.
+            self.drop_oldest_messages(self.messages.len() - (max - max / 5));
+        }
+    }
+
+    /// Drop the oldest `count` messages. They stay searchable through the content index.
+    fn drop_oldest_messages(&mut self, count: usize) {
+        let count = count.min(self.messages.len());
+        let drained = self.messages.drain(..count);
+        for (seq, old) in self.message_seqs.drain(..count).zip(drained) {
+            let freed = message_bytes(&old, self.interner.release(&old.content));
+            self.retained_bytes = self.retained_bytes.saturating_sub(freed);
+            self.content_index.keep_trimmed(seq, old);
+        }
+    }
+
+    /// Recompute the rolling spend rate. Returns true if it moved enough to publish.
+    fn refresh_spend_rate(&mut self, now_ms: i64) -> bool {
+        let (per_hour, per_minute) = self.spend_rate.rates(now_ms);
+        let changed = rate_changed(self.summary.spend_rate_per_hour, per_hour)
+            || rate_changed(self.summary.tokens_per_minute, per_minute);
+        self.summary.spend_rate_per_hour = per_hour;
+        self.summary.tokens_per_minute = per_minute;
+        changed
+    }
+
+    fn usage_event(&self) -> ProviderEvent {
+        ProviderEvent::UsageUpdated {
+            session_id: self.summary.session_id.clone(),
+            usage: self.summary.cumulative_usage.clone(),
+            spend_rate_per_hour: self.summary.spend_rate_per_hour,
+            tokens_per_minute: self.summary.tokens_per_minute,
+            context_tokens: self.summary.context_tokens,
+            context_limit: self.summary.context_limit,
+        }
+    }
+
+    /// The highest warning threshold the context has reached without a warning since
+    /// it was last compacted, with the percentage used. Marks it warned.
+    fn cross_context_threshold(&mut self) -> Option<(u32, u32)> {
+        let limit = self.summary.context_limit.filter(|&limit| limit > 0)?;
+        let percent = (self.summary.context_tokens * 100 / limit) as u32;
+        let threshold = self
+            .settings
+            .load()
+            .context_warning_percents
+            .iter()
+            .copied()
+            .filter(|&t| t <= percent && t > self.context_warned)
+            .max()?;
+        self.context_warned = threshold;
+        Some((threshold, percent))
+    }
+
+    /// Count the log's modification time as activity if it is newer than the last
+    /// entry read, e.g. when it was written just before a suspend.
+    fn refresh_activity_from_log(&mut self) {
+        let modified_ms = std::fs::metadata(self.watcher.file_path())
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_millis() as i64);
+        if let Some(ms) = modified_ms.filter(|&ms| ms > self.state_ctx.last_activity_at) {
+            self.state_ctx.mark_activity(ms);
+        }
+    }
+
+    /// Run the time-based state transitions with the current timeouts.
+    fn check_time_transitions(&mut self) -> TransitionResult {
+        self.state_ctx.timeouts = self.settings().timeouts;
+        check_time_based_transitions(&mut self.state_ctx)
+    }
+
+    /// Run the time-based state transitions and publish a resulting change.
+    fn apply_time_transitions(
+        &mut self,
+        session_id: &str,
+        event_tx: &mpsc::UnboundedSender<ProviderEvent>,
+    ) -> bool {
+        let prev_state = self.state_ctx.state;
+        let result = self.check_time_transitions();
+        if result.changed {
+            self.sync_state();
+            self.record_transition(prev_state);
+            if self.emitted {
+                let _ = event_tx.send(ProviderEvent::StateChanged {
+                    session_id: session_id.to_string(),
+                    previous: prev_state,
+                    current: self.state_ctx.state,
+                });
+            }
+        }
+        result.changed
+    }
+
+    /// When the session last did something: its latest log entry, else now.
+    fn last_entry_ms(&self) -> i64 {
+        if self.state_ctx.last_entry_timestamp > 0 {
+            self.state_ctx.last_entry_timestamp
+        } else {
+            self.state_ctx.clock.now_millis()
+        }
+    }
+
+    /// Feed a state change into the activity timeline, timestamped at the last log entry.
+    /// Every path that changes state goes through here, so active time is counted the
+    /// same whether entries, a timeout or a forced stop ended the running period.
+    fn record_transition(&mut self, previous: AgentStateType) {
+        if self.state_ctx.state == AgentStateType::Idle
+            && matches!(
+                previous,
+                AgentStateType::Running | AgentStateType::PermissionWaiting
+            )
+        {
+            self.turns += 1;
+        }
+        let at_ms = self.last_entry_ms();
+        self.timeline.record(
+            previous,
+            self.state_ctx.state,
+            at_ms,
+            &self.summary.cumulative_usage,
+            self.settings().timezone,
+        );
+        self.summary.active_duration_ms = self.timeline.active_ms(at_ms);
+    }
+}
+
+/// The part of a log a dehydrated session's messages are re-read from: up to where
+/// its watcher stopped, at most `REREAD_MAX_BYTES` back.
+struct LogTail {
+    path: PathBuf,
+    end: u64,
+    /// Entries read before the watcher stopped, to number the re-read ones.
+    entries_read: u64,
+    redactor: Arc<Redactor>,
+    text_limits: TextLimits,
+    max_messages: usize,
+    max_line_bytes: usize,
+}
+
+impl LogTail {
+    async fn messages(&self, session_id: &str) -> Vec<Arc<AgentMessage>> {
+        let entries =
+            match read_tail(&self.path, self.end, REREAD_MAX_BYTES, self.max_line_bytes).await {
+                Ok((entries, _)) => entries,
+                Err(e) => {
+                    warn!("Could not re-read {}: {}", self.path.display(), e);
+                    return Vec::new();
+                }
+            };
+        let first_index = self.entries_read.saturating_sub(entries.len() as u64);
+        recent_messages(
+            session_id,
+            &entries,
+            first_index,
+            &self.redactor,
+            &self.text_limits,
+            self.max_messages,
+        )
+        .into_iter()
+        .map(Arc::new)
+        .collect()
+    }
+}
+
+/// The last `max_messages` messages of `entries`, the log's entries from `first_index`
+/// on, mapped as `handle_entries` maps them.
+fn recent_messages(
+    session_id: &str,
+    entries: &[RawEntry],
+    first_index: u64,
+    redactor: &Redactor,
+    limits: &TextLimits,
+    max_messages: usize,
+) -> Vec<AgentMessage> {
+    let mut seen = HashSet::new();
+    let mut tool_stats = ToolStatsTracker::new();
+    let mut permission_mode = PermissionModeTracker::new();
+    let mut messages = Vec::new();
+    let mut latest_ms = 0;
+    for (i, entry) in entries.iter().enumerate() {
+        if entry_uuid(entry).is_some_and(|uuid| !seen.insert(uuid)) {
+            continue;
+        }
+        let late = is_late(entry, latest_ms);
+        latest_ms = latest_ms.max(get_entry_timestamp(entry).unwrap_or(0));
+        let position = EntryPosition {
+            session_id,
+            index: first_index + i as u64,
+        };
+        for (mut msg, full_text) in map_entry_full(entry, position, redactor, limits) {
+            tool_stats.link_result(&mut msg);
+            tool_stats.record(&msg, full_text.as_deref());
+            if late {
+                messages.insert(in_order_position(&messages, &msg.timestamp), msg);
+            } else {
+                messages.push(msg);
+            }
+        }
+        if late {
+            continue;
+        }
+        if let Some(change) = permission_mode.observe(entry) {
+            if change.is_notable() {
+                messages.push(change.message(position, &entry_time(entry)));
+            }
+        }
+    }
+    let excess = messages.len().saturating_sub(max_messages);
+    messages.drain(..excess);
+    messages
+}
+
+/// When `entry` was written, or now if it doesn't say.
+fn entry_time(entry: &RawEntry) -> String {
+    get_entry_timestamp(entry)
+        .and_then(chrono::DateTime::from_timestamp_millis)
+        .map_or_else(now_timestamp, format_timestamp)
+}
+
+/// Whether `entry` was written well before `latest_ms`, the newest entry read so far,
+/// e.g. when a backfill delivers it after entries tailed live.
+fn is_late(entry: &RawEntry, latest_ms: i64) -> bool {
+    get_entry_timestamp(entry).is_some_and(|ts| latest_ms - ts > LATE_ENTRY_TOLERANCE_MS)
+}
+
+/// Where a message stamped `timestamp` goes among `messages`, which are in timestamp
+/// order: after every message stamped no later.
+fn in_order_position(messages: &[impl Borrow<AgentMessage>], timestamp: &str) -> usize {
+    messages.partition_point(|m| m.borrow().timestamp.as_str() <= timestamp)
+}
+
+/// About how much memory `msg` takes: its text and metadata, plus a fixed overhead.
+/// Text shared with other messages is counted with only one of them, so `content`
+/// is false for the rest.
+fn message_bytes(msg: &AgentMessage, content: bool) -> u64 {
+    fn json_bytes(value: &serde_json::Value) -> u64 {
+        match value {
+            serde_json::Value::String(s) => s.len() as u64,
+            serde_json::Value::Array(items) => items.iter().map(json_bytes).sum(),
+            serde_json::Value::Object(map) => map
+                .iter()
+                .map(|(k, v)| k.len() as u64 + json_bytes(v))
+                .sum(),
+            _ => 8,
+        }
+    }
+    let metadata: u64 = msg
+        .metadata
+        .iter()
+        .flatten()
+        .map(|(k, v)| k.len() as u64 + json_bytes(v))
+        .sum();
+    let content_bytes = if content { msg.content.len() as u64 } else { 0 };
+    MESSAGE_OVERHEAD_BYTES
+        + content_bytes
+        + (msg.id.len() + msg.timestamp.len()) as u64
+        + metadata
+}
+
+fn entry_uuid(entry: &RawEntry) -> Option<&str> {
+    match entry {
+        RawEntry::User(m) => m.uuid.as_deref(),
+        RawEntry::Assistant(m) => m.uuid.as_deref(),
+        _ => None,
+    }
+}
+
+/// Where the provider finds logs and how often it checks on them. The defaults
+/// watch `~/.claude/projects`; tests point this at a fixture tree with short intervals.
+#[derive(Debug, Clone)]
+pub struct ProviderConfig {
+    pub discovery: DiscoveryConfig,
+    /// How often time-based transitions, git status and process checks run.
+    pub timer_interval: Duration,
+    /// How often each watched log is polled in case a change notification was missed.
+    pub poll_interval: Duration,
+    /// Log lines longer than this are skipped, leaving a note in the transcript.
+    pub max_line_bytes: usize,
+    /// Set when process detection is on.
+    pub process_lookup: Option<ProcessLookup>,
+    pub session: SharedSettings,
+    /// Names this machine on every session summary.
+    pub host: String,
+}
+
+impl Default for ProviderConfig {
+    fn default() -> Self {
+        Self {
+            discovery: DiscoveryConfig::default(),
+            timer_interval: TIMER_INTERVAL,
+            poll_interval: file_watcher::POLL_INTERVAL,
+            max_line_bytes: jsonl_parser::DEFAULT_MAX_LINE_BYTES,
+            process_lookup: None,
+            session: SharedSettings::default(),
+            host: DEFAULT_HOST.to_string(),
+        }
+    }
+}
+
+/// Session settings that can change while the server runs (on config reload).
+/// Sessions hold the same handle and read it where the setting is used.
+pub type SharedSettings = Arc<ArcSwap<SessionSettings>>;
+
+/// How every tracked session is judged and priced.
+#[derive(Debug, Clone)]
+pub struct SessionSettings {
+    pub timeouts: StateTimeouts,
+    pub pricing: Arc<Pricing>,
+    pub context_limits: Arc<ContextLimits>,
+    /// Percentages of the context window at which `ContextWarning` is sent.
+    pub context_warning_percents: Vec<u32>,
+    pub git_backend: GitBackend,
+    /// How long an older session must have been silent for a new session in the same
+    /// project to count as its replacement (e.g. after "clear session"). Zero stops
+    /// it as soon as the new session appears.
+    pub supersede_grace: Duration,
+    /// Masks secrets in message content; applies to entries read after a change.
+    pub redactor: Arc<Redactor>,
+    /// How long a session stays stopped before its log is no longer watched and its
+    /// messages are dropped from memory.
+    pub dehydrate_after: Duration,
+    /// Bytes all sessions' messages may take together before the largest stopped and
+    /// idle sessions drop theirs; 0 for no limit.
+    pub transcript_memory_budget: u64,
+    /// Checked in order to tag a session's activity; empty turns tagging off.
+    pub activity_rules: Arc<Vec<ActivityRule>>,
+    /// How much of prompts, tool results and search matches is kept; applies to
+    /// entries read after a change.
+    pub text_limits: TextLimits,
+    /// Days are counted in this zone; days already counted keep their date.
+    pub timezone: Tz,
+    /// Between git checks of an idle or permission-waiting session.
+    pub git_check_interval: Duration,
+    /// Between git checks of a running session, which changes constantly.
+    pub git_check_interval_running: Duration,
+    /// Messages a session keeps in memory; older ones are only searchable.
+    pub max_messages: usize,
+    pub notify: NotifyRules,
+    /// Settings in place of these for sessions under a project; see [`Self::for_project`].
+    pub projects: Arc<Vec<ProjectSettings>>,
+    /// The project these settings are for, if they came from a `[projects]` section.
+    pub project: Option<String>,
+}
+
+impl Default for SessionSettings {
+    fn default() -> Self {
+        Self {
+            timeouts: StateTimeouts::default(),
+            pricing: Arc::new(Pricing::default()),
+            context_limits: Arc::new(ContextLimits::default()),
+            context_warning_percents: DEFAULT_CONTEXT_WARNING_PERCENTS.to_vec(),
+            git_backend: GitBackend::default(),
+            supersede_grace: DEFAULT_SUPERSEDE_GRACE,
+            redactor: Arc::new(Redactor::default()),
+            dehydrate_after: DEFAULT_DEHYDRATE_AFTER,
+            transcript_memory_budget: DEFAULT_TRANSCRIPT_MEMORY_BUDGET,
+            activity_rules: Arc::new(analysis::default_rules()),
+            text_limits: TextLimits::default(),
+            timezone: Tz::UTC,
+            git_check_interval: DEFAULT_GIT_CHECK_INTERVAL,
+            git_check_interval_running: DEFAULT_GIT_CHECK_INTERVAL_RUNNING,
+            max_messages: DEFAULT_MAX_MESSAGES,
+            notify: NotifyRules::default(),
+            projects: Arc::new(Vec::new()),
+            project: None,
+        }
+    }
+}
+
+impl SessionSettings {
+    /// The settings for a session discovered in `project_path`: those of the most
+    /// specific project section it is in, or these.
+    pub fn for_project(self: &Arc<Self>, project_path: &str) -> Arc<SessionSettings> {
+        match project_settings::matching(&self.projects, project_path) {
+            Some(project) => project.settings.clone(),
+            None => self.clone(),
+        }
+    }
+
+    fn config(&self) -> SessionConfig {
+        let secs = |ms: i64| ms.max(0) as u64 / 1000;
+        SessionConfig {
+            project_override: self.project.clone(),
+            permission_wait_secs: secs(self.timeouts.permission_wait_ms),
+            idle_secs: secs(self.timeouts.idle_ms),
+            running_stopped_secs: secs(self.timeouts.running_stopped_ms),
+            idle_stopped_secs: secs(self.timeouts.idle_stopped_ms),
+            process_gone_secs: secs(self.timeouts.process_gone_ms),
+            notify_states: self.notify.states.clone(),
+            notify_context_warnings: self.notify.context_warnings,
+            git_check_interval_secs: self.git_check_interval.as_secs(),
+            git_check_interval_running_secs: self.git_check_interval_running.as_secs(),
+            max_messages: self.max_messages as u64,
+            dehydrate_after_secs: self.dehydrate_after.as_secs(),
+        }
+    }
 }
 
 pub struct ClaudeCodeProvider {
@@ -44,35 +822,169 @@ pub struct ClaudeCodeProvider {
     event_tx: mpsc::UnboundedSender<ProviderEvent>,
     shutdown: tokio::sync::watch::Sender<bool>,
     discovery: tokio::sync::Mutex<Option<SessionDiscovery>>,
+    dismissed: DismissedSessions,
+    dehydrated: DehydratedSessions,
+    config: ProviderConfig,
+    /// Tasks feeding replayed sessions, by session id.
+    replays: std::sync::Mutex<HashMap<String, tokio::task::AbortHandle>>,
+    startup: Arc<StartupScan>,
+}
+
+/// What a session has cost so far and where it was discovered.
+#[derive(Debug, Clone)]
+pub struct ProjectUsageSample {
+    pub project_path: String,
+    pub project_name: String,
+    pub usage: CumulativeUsage,
+    /// Cost per day (days since the epoch), dated in the configured zone.
+    pub daily_cost: BTreeMap<i64, f64>,
+    /// Tokens per day, like `daily_cost`.
+    pub daily_tokens: BTreeMap<i64, u64>,
+    pub model: String,
+    /// The title, else the current task.
+    pub title: String,
+    pub started_at: String,
+    pub last_activity_at: String,
+    pub top_tools: Vec<ToolCount>,
+}
+
+/// What a tracked session did on one day, for project digests.
+#[derive(Debug, Clone)]
+pub struct DaySample {
+    pub session_id: String,
+    pub project_name: String,
+    pub title: Option<String>,
+    pub model: String,
+    pub state: AgentStateType,
+    pub started_at: String,
+    pub last_activity_at: String,
+    pub cost: f64,
+    pub tokens: u64,
+    pub active_ms: u64,
+    /// Files edited or written that day; None when the messages were dropped.
+    pub files: Option<Vec<String>>,
+    /// None when the working directory isn't a git repository.
+    pub git: Option<GitStatus>,
+    pub top_tools: Vec<ToolCount>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DismissError {
+    NotFound,
+    Running,
 }
 
 impl ClaudeCodeProvider {
+    #[cfg(test)]
     pub fn new(event_tx: mpsc::UnboundedSender<ProviderEvent>) -> Self {
+        Self::with_config(event_tx, ProviderConfig::default())
+    }
+
+    pub fn with_config(
+        event_tx: mpsc::UnboundedSender<ProviderEvent>,
+        config: ProviderConfig,
+    ) -> Self {
         let (shutdown, _) = tokio::sync::watch::channel(false);
         Self {
             sessions: Arc::new(RwLock::new(HashMap::new())),
             event_tx,
             shutdown,
             discovery: tokio::sync::Mutex::new(None),
+            dismissed: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            dehydrated: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            config,
+            replays: std::sync::Mutex::new(HashMap::new()),
+            startup: Arc::default(),
         }
     }
 
+    pub fn session_settings(&self) -> &SharedSettings {
+        &self.config.session
+    }
+
     pub async fn start(&self) {
-        let sessions = self.sessions.clone();
-        let event_tx = self.event_tx.clone();
         let mut shutdown_rx = self.shutdown.subscribe();
+        self.startup.start();
+        self.start_discovery().await;
+
+        let startup = self.startup.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(STARTUP_TIMEOUT).await;
+            startup.time_out();
+        });
+
+        // Periodic timer check
+        let sessions_timer = self.sessions.clone();
+        let event_tx_timer = self.event_tx.clone();
+        let dehydrated_timer = self.dehydrated.clone();
+        let process_lookup = self.config.process_lookup;
+        let timer_interval = self.config.timer_interval;
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(timer_interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            let mut suspend = SuspendDetector::new(timer_interval);
+
+            loop {
+                tokio::select! {
+                    _ = shutdown_rx.changed() => break,
+                    _ = interval.tick() => {
+                        let gap = suspend.tick(chrono::Utc::now().timestamp_millis());
+                        if let Some(gap) = gap {
+                            info!(
+                                "No timer tick for {}s (system suspended?); holding time-based transitions for one tick",
+                                gap.as_secs()
+                            );
+                        }
+                        let after_suspend = gap.is_some();
+                        check_timers(
+                            &sessions_timer,
+                            &event_tx_timer,
+                            &dehydrated_timer,
+                            process_lookup,
+                            after_suspend,
+                        )
+                        .await;
+                    }
+                }
+            }
+        });
+    }
+
+    pub async fn stop(&self) {
+        let _ = self.shutdown.send(true);
+        if let Some(discovery) = self.discovery.lock().await.take() {
+            discovery.stop();
+        }
+        for (_, task) in self.replays.lock().unwrap().drain() {
+            task.abort();
+        }
+        let mut sessions = self.sessions.write().await;
+        for (_, session) in sessions.drain() {
+            session.watcher.stop();
+        }
+    }
 
+    /// Scan for session logs now and every `scan_interval`, following the logs found.
+    async fn start_discovery(&self) {
         // Discovery channel
         let (discovery_tx, mut discovery_rx) = mpsc::unbounded_channel();
-        let mut discovery = SessionDiscovery::new(discovery_tx);
+        let mut discovery = SessionDiscovery::new(
+            self.config.discovery.clone(),
+            discovery_tx,
+            self.dismissed.clone(),
+            self.dehydrated.clone(),
+        );
         discovery.start().await;
 
         // Store discovery to keep its shutdown channel alive
         *self.discovery.lock().await = Some(discovery);
 
         // Handle discovery events
-        let sessions_clone = sessions.clone();
-        let event_tx_clone = event_tx.clone();
+        let sessions_clone = self.sessions.clone();
+        let event_tx_clone = self.event_tx.clone();
+        let config = self.config.clone();
+        let dehydrated = self.dehydrated.clone();
+        let startup = self.startup.clone();
         tokio::spawn(async move {
             while let Some(event) = discovery_rx.recv().await {
                 match event {
@@ -80,11 +992,15 @@ impl ClaudeCodeProvider {
                         handle_session_found(
                             &sessions_clone,
                             &event_tx_clone,
+                            &startup,
                             discovered,
+                            &config,
+                            LogMode::Watched,
                         )
                         .await;
                     }
                     DiscoveryEvent::Removed(session_id) => {
+                        dehydrated.lock().unwrap().remove(&session_id);
                         let mut sessions = sessions_clone.write().await;
                         if let Some(session) = sessions.remove(&session_id) {
                             session.watcher.stop();
@@ -93,37 +1009,63 @@ impl ClaudeCodeProvider {
                             });
                         }
                     }
+                    DiscoveryEvent::Written(session_id) => {
+                        rehydrate(&sessions_clone, &session_id).await;
+                    }
+                    DiscoveryEvent::Scanned => startup.scanned(),
                 }
             }
         });
+    }
 
-        // Periodic timer check (3s)
-        let sessions_timer = self.sessions.clone();
-        let event_tx_timer = self.event_tx.clone();
-        tokio::spawn(async move {
-            let mut interval = tokio::time::interval(std::time::Duration::from_secs(3));
-            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    /// When discovery last finished a scan, in Unix milliseconds. None until started.
+    pub async fn discovery_last_scan(&self) -> Option<i64> {
+        self.discovery.lock().await.as_ref()?.last_scan_ms()
+    }
 
-            loop {
-                tokio::select! {
-                    _ = shutdown_rx.changed() => break,
-                    _ = interval.tick() => {
-                        check_timers(&sessions_timer, &event_tx_timer).await;
-                    }
-                }
-            }
-        });
+    pub fn discovery_scan_interval(&self) -> Duration {
+        self.config.discovery.scan_interval
     }
 
-    pub async fn stop(&self) {
-        let _ = self.shutdown.send(true);
+    /// What was read at startup. None until every log the first scan found has been
+    /// read once, or `STARTUP_TIMEOUT` passed.
+    pub fn startup_summary(&self) -> Option<StartupSummary> {
+        self.startup.summary()
+    }
+
+    /// Changes once, when startup is over.
+    pub fn subscribe_startup(&self) -> tokio::sync::watch::Receiver<Option<StartupSummary>> {
+        self.startup.subscribe()
+    }
+
+    /// Replace discovery with a fresh one, for when it stopped scanning. Sessions
+    /// already tracked are found again and left as they are.
+    pub async fn restart_discovery(&self) {
+        if *self.shutdown.borrow() {
+            return;
+        }
         if let Some(discovery) = self.discovery.lock().await.take() {
             discovery.stop();
         }
+        self.start_discovery().await;
+    }
+
+    /// Restart the watchers that stopped reading while their log kept growing, at
+    /// the offset they had reached. Returns the sessions whose watcher was restarted.
+    pub async fn restart_stalled_watchers(&self) -> Vec<String> {
         let mut sessions = self.sessions.write().await;
-        for (_, session) in sessions.drain() {
+        let mut restarted = Vec::new();
+        for (session_id, session) in sessions.iter_mut() {
+            if !session.watcher_stall_reported || !session.hydrated {
+                continue;
+            }
             session.watcher.stop();
+            session.watcher.start().await;
+            session.watcher_stall_reported = false;
+            restarted.push(session_id.clone());
         }
+        restarted.sort();
+        restarted
     }
 
     pub async fn get_sessions(&self) -> Vec<AgentSessionSummary> {
@@ -135,198 +1077,980 @@ impl ClaudeCodeProvider {
             .collect()
     }
 
-    pub async fn get_session_detail(&self, session_id: &str) -> Option<AgentSessionDetail> {
+    /// The project each shown session was discovered in, by session id. It can
+    /// differ from the summary's `project_path`, which comes from the cwd it logged.
+    pub async fn discovery_project_paths(&self) -> HashMap<String, String> {
         let sessions = self.sessions.read().await;
-        sessions.get(session_id).map(|s| AgentSessionDetail {
-            summary: s.summary.clone(),
-            messages: s.messages.clone(),
-        })
+        sessions
+            .iter()
+            .filter(|(_, s)| s.emitted)
+            .map(|(id, s)| (id.clone(), s.discovery_project_path.clone()))
+            .collect()
     }
 
-    pub async fn get_session_messages(&self, session_id: &str) -> Option<Vec<AgentMessage>> {
+    /// Summaries of those of `session_ids` that are tracked, read under one lock.
+    pub async fn get_summaries(&self, session_ids: &[String]) -> Vec<AgentSessionSummary> {
         let sessions = self.sessions.read().await;
-        sessions.get(session_id).map(|s| s.messages.clone())
+        session_ids
+            .iter()
+            .filter_map(|id| sessions.get(id).filter(|s| s.emitted))
+            .map(|s| s.summary.clone())
+            .collect()
     }
 
-    pub async fn search_sessions(
-        &self,
-        query: &str,
-        scopes: &[SearchScope],
-    ) -> Vec<SessionSearchResult> {
-        let query_lower = query.to_lowercase();
-        let sessions = self.sessions.read().await;
-        let mut results: Vec<SessionSearchResult> = Vec::new();
-
-        for session in sessions.values().filter(|s| s.emitted) {
-            let mut matches: Vec<SearchMatch> = Vec::new();
-
-            for scope in scopes {
-                match scope {
-                    SearchScope::ProjectName => {
-                        if session.summary.project_name.to_lowercase().contains(&query_lower) {
-                            matches.push(SearchMatch {
-                                content: session.summary.project_name.clone(),
-                                scope: SearchScope::ProjectName,
-                                message_role: MessageRole::System,
-                                message_type: MessageType::Text,
-                                timestamp: session.summary.started_at.clone(),
-                            });
-                        }
-                    }
-                    SearchScope::CurrentTask => {
-                        if session.summary.current_task.to_lowercase().contains(&query_lower) {
-                            matches.push(SearchMatch {
-                                content: make_snippet(
-                                    &session.summary.current_task,
-                                    &query_lower,
-                                ),
-                                scope: SearchScope::CurrentTask,
-                                message_role: MessageRole::System,
-                                message_type: MessageType::Text,
-                                timestamp: session.summary.started_at.clone(),
-                            });
-                        }
-                    }
-                    SearchScope::WorkingDirectory => {
-                        let wd = &session.summary.working_directory;
-                        let pp = &session.summary.project_path;
-                        if wd.to_lowercase().contains(&query_lower)
-                            || pp.to_lowercase().contains(&query_lower)
-                        {
-                            matches.push(SearchMatch {
-                                content: wd.clone(),
-                                scope: SearchScope::WorkingDirectory,
-                                message_role: MessageRole::System,
-                                message_type: MessageType::Text,
-                                timestamp: session.summary.started_at.clone(),
-                            });
-                        }
-                    }
-                    SearchScope::Content => {
-                        for msg in &session.messages {
-                            if msg.content.to_lowercase().contains(&query_lower) {
-                                matches.push(SearchMatch {
-                                    content: make_snippet(&msg.content, &query_lower),
-                                    scope: SearchScope::Content,
-                                    message_role: msg.role,
-                                    message_type: msg.msg_type,
-                                    timestamp: msg.timestamp.clone(),
-                                });
-                            }
-                        }
-                    }
-                }
-            }
-
-            if !matches.is_empty() {
-                let match_count = matches.len() as u32;
-                matches.truncate(3);
-                results.push(SessionSearchResult {
-                    session: session.summary.clone(),
-                    match_count,
-                    matches,
-                });
-            }
-        }
-
-        results.sort_by(|a, b| b.match_count.cmp(&a.match_count));
-        results
+    /// Stop watching a session and drop it from the dashboard.
+    /// Discovery will not re-add it unless its log file is written to again.
+    /// What dismissing a session would do, or why it can't be dismissed.
+    pub async fn plan_dismiss(&self, session_id: &str) -> Result<DismissPlan, DismissError> {
+        dismiss_plan(&*self.sessions.read().await, session_id)
     }
-}
 
-fn make_snippet(text: &str, query_lower: &str) -> String {
-    let text_lower = text.to_lowercase();
-    let Some(pos) = text_lower.find(query_lower) else {
-        // Shouldn't happen, but fall back to truncation
-        return truncate_str(text, 100);
-    };
+    pub async fn dismiss_session(&self, session_id: &str) -> Result<(), DismissError> {
+        let mut sessions = self.sessions.write().await;
+        dismiss_plan(&sessions, session_id)?;
 
-    let context = 40;
-    let start = if pos > context {
-        // Find a safe char boundary
-        let mut s = pos - context;
-        while s > 0 && !text.is_char_boundary(s) {
-            s -= 1;
-        }
-        s
-    } else {
-        0
-    };
+        let Some(session) = sessions.remove(session_id) else {
+            return Err(DismissError::NotFound);
+        };
+        self.forget_removed(session_id, session);
+        Ok(())
+    }
 
-    let end = {
-        let mut e = (pos + query_lower.len() + context).min(text.len());
-        while e < text.len() && !text.is_char_boundary(e) {
-            e += 1;
+    /// Stop a session taken out of `sessions` and keep discovery from adding it back
+    /// until its log is written to again.
+    fn forget_removed(&self, session_id: &str, session: TrackedSession) {
+        session.watcher.stop();
+        self.dehydrated.lock().unwrap().remove(session_id);
+        self.dismissed
+            .lock()
+            .unwrap()
+            .insert(session_id.to_string(), std::time::SystemTime::now());
+        if session.emitted {
+            let _ = self.event_tx.send(ProviderEvent::SessionRemoved {
+                session_id: session_id.to_string(),
+            });
         }
-        e
-    };
-
-    let mut snippet = String::new();
-    if start > 0 {
-        snippet.push_str("...");
     }
-    snippet.push_str(&text[start..end]);
-    if end < text.len() {
-        snippet.push_str("...");
-    }
-    snippet
-}
 
-fn truncate_str(s: &str, max_len: usize) -> String {
-    if s.len() <= max_len {
-        return s.to_string();
-    }
-    let mut end = max_len;
-    while end > 0 && !s.is_char_boundary(end) {
-        end -= 1;
-    }
-    format!("{}...", &s[..end])
+    /// Sessions stopped with no activity since `before`, with their last activity.
+    /// Replayed and historical sessions aren't archived.
+    pub async fn archive_candidates(
+        &self,
+        before: chrono::DateTime<chrono::Utc>,
+    ) -> Vec<(String, String)> {
+        let sessions = self.sessions.read().await;
+        sessions
+            .values()
+            .filter(|s| {
+                s.emitted
+                    && s.state_ctx.state == AgentStateType::Stopped
+                    && !s.summary.replay
+                    && !s.summary.historical
+                    && parse_timestamp(&s.summary.last_activity_at).is_some_and(|at| at < before)
+            })
+            .map(|s| {
+                (
+                    s.summary.session_id.clone(),
+                    s.summary.last_activity_at.clone(),
+                )
+            })
+            .collect()
+    }
+
+    /// Drop an archived session as dismissing it would, unless it has been active
+    /// since `last_activity_at`. A log that's written to again brings it back.
+    pub async fn release_archived(&self, session_id: &str, last_activity_at: &str) -> bool {
+        let mut sessions = self.sessions.write().await;
+        match sessions.get(session_id) {
+            Some(s)
+                if s.state_ctx.state == AgentStateType::Stopped
+                    && s.summary.last_activity_at == last_activity_at => {}
+            _ => return false,
+        }
+        let Some(session) = sessions.remove(session_id) else {
+            return false;
+        };
+        self.forget_removed(session_id, session);
+        true
+    }
+
+    /// Play back a recorded log as a new session, feeding its entries through the
+    /// same path as a live log. Returns the new session's id.
+    pub async fn start_replay(&self, steps: Vec<ReplayStep>) -> String {
+        let session_id = format!("replay-{}", uuid::Uuid::new_v4());
+        let now = now_timestamp();
+        let summary = AgentSessionSummary {
+            session_id: session_id.clone(),
+            provider: "claude-code".to_string(),
+            state: AgentStateType::Stopped,
+            model: "unknown".to_string(),
+            last_activity_at: now.clone(),
+            started_at: now,
+            replay: true,
+            host: self.config.host.clone(),
+            ..Default::default()
+        };
+        // Never started: entries come from the replay task. The session id stands in
+        // for the project so replays are not superseded by, or linked to, other sessions.
+        let (entries_tx, _) = mpsc::unbounded_channel();
+        let watcher = FileWatcher::new(std::path::PathBuf::new(), entries_tx);
+        let tracked = TrackedSession::new(
+            summary,
+            watcher,
+            session_id.clone(),
+            self.config.session.clone(),
+        );
+        self.sessions
+            .write()
+            .await
+            .insert(session_id.clone(), tracked);
+
+        let sessions = self.sessions.clone();
+        let event_tx = self.event_tx.clone();
+        let id = session_id.clone();
+        let task = tokio::spawn(async move {
+            for ReplayStep { delay, mut entry } in steps {
+                if !delay.is_zero() {
+                    tokio::time::sleep(delay).await;
+                }
+                restamp(&mut entry, &id, &now_timestamp());
+                handle_entries(&sessions, &event_tx, &id, vec![entry]).await;
+            }
+        });
+        self.replays
+            .lock()
+            .unwrap()
+            .insert(session_id.clone(), task.abort_handle());
+        session_id
+    }
+
+    /// Stop a replay and remove its session. Returns false if `session_id` is not a replay.
+    /// What stopping a replay would do; None if `session_id` isn't one.
+    pub async fn plan_stop_replay(&self, session_id: &str) -> Option<StopReplayPlan> {
+        let sessions = self.sessions.read().await;
+        let session = sessions.get(session_id).filter(|s| s.summary.replay)?;
+        Some(StopReplayPlan {
+            session_id: session_id.to_string(),
+            message_count: session.summary.message_count,
+        })
+    }
+
+    pub async fn stop_replay(&self, session_id: &str) -> bool {
+        let mut sessions = self.sessions.write().await;
+        if !sessions.get(session_id).is_some_and(|s| s.summary.replay) {
+            return false;
+        }
+        if let Some(task) = self.replays.lock().unwrap().remove(session_id) {
+            task.abort();
+        }
+        if let Some(session) = sessions.remove(session_id) {
+            if session.emitted {
+                let _ = self.event_tx.send(ProviderEvent::SessionRemoved {
+                    session_id: session_id.to_string(),
+                });
+            }
+        }
+        true
+    }
+
+    /// Load old sessions of a project that discovery passed over, reading each log once
+    /// without watching it: those of `session_ids`, or if empty the most recent ones.
+    /// Sessions already tracked are returned as they are. None if the project has no
+    /// logs directory.
+    pub async fn load_history(
+        &self,
+        project_path: &str,
+        session_ids: &[String],
+    ) -> Option<Vec<AgentSessionSummary>> {
+        let logs = self.history_logs(project_path, session_ids).await?;
+        let ids: Vec<String> = logs.iter().map(|log| log.session_id.clone()).collect();
+        for log in logs {
+            handle_session_found(
+                &self.sessions,
+                &self.event_tx,
+                &self.startup,
+                log,
+                &self.config,
+                LogMode::Historical,
+            )
+            .await;
+        }
+        Some(self.get_summaries(&ids).await)
+    }
+
+    /// Where Claude Code's logs are discovered.
+    pub fn projects_dir(&self) -> &std::path::Path {
+        &self.config.discovery.projects_dir
+    }
+
+    /// The sessions `load_history` would return, most recent first.
+    pub async fn plan_history(
+        &self,
+        project_path: &str,
+        session_ids: &[String],
+    ) -> Option<Vec<String>> {
+        let logs = self.history_logs(project_path, session_ids).await?;
+        Some(logs.into_iter().map(|log| log.session_id).collect())
+    }
+
+    async fn history_logs(
+        &self,
+        project_path: &str,
+        session_ids: &[String],
+    ) -> Option<Vec<DiscoveredSession>> {
+        let logs = scan_project(&self.config.discovery.projects_dir, project_path).await?;
+        Some(if session_ids.is_empty() {
+            logs.into_iter().take(HISTORY_MAX_SESSIONS).collect()
+        } else {
+            logs.into_iter()
+                .filter(|log| session_ids.contains(&log.session_id))
+                .collect()
+        })
+    }
+
+    pub async fn has_session(&self, session_id: &str) -> bool {
+        self.sessions.read().await.contains_key(session_id)
+    }
+
+    /// A dehydrated session's messages are re-read from its log, without the lock held.
+    pub async fn get_session_detail(&self, session_id: &str) -> Option<AgentSessionDetail> {
+        let (mut detail, tail) = {
+            let sessions = self.sessions.read().await;
+            let s = sessions.get(session_id)?;
+            let detail = AgentSessionDetail {
+                summary: s.summary.clone(),
+                messages: s.messages.clone(),
+                usage_points: s.usage_series.points(Some(DETAIL_USAGE_POINTS)),
+                model_history: s.models.history().to_vec(),
+                watcher: s.watcher.health(),
+                settings: Some(s.settings().config()),
+            };
+            (detail, (!s.hydrated).then(|| s.log_tail()))
+        };
+        if let Some(tail) = tail {
+            detail.messages = tail.messages(session_id).await;
+        }
+        Some(detail)
+    }
+
+    /// Which of a session's events are pushed, by its project's rules if it has them.
+    pub async fn notify_rules(&self, session_id: &str) -> Option<NotifyRules> {
+        let sessions = self.sessions.read().await;
+        Some(sessions.get(session_id)?.settings().notify.clone())
+    }
+
+    pub async fn get_usage_timeline(&self, session_id: &str) -> Option<UsageTimeline> {
+        let sessions = self.sessions.read().await;
+        let session = sessions.get(session_id)?;
+        Some(UsageTimeline {
+            session_id: session_id.to_string(),
+            points: session.usage_series.points(None),
+        })
+    }
+
+    /// A session's usage with the project it was discovered in, for per-project
+    /// totals. None for unknown, not yet shown and replayed sessions.
+    pub async fn project_usage_sample(&self, session_id: &str) -> Option<ProjectUsageSample> {
+        let sessions = self.sessions.read().await;
+        let session = sessions
+            .get(session_id)
+            .filter(|s| s.emitted && !s.summary.replay && !s.summary.historical)?;
+        Some(ProjectUsageSample {
+            project_path: session.discovery_project_path.clone(),
+            project_name: session.summary.project_name.clone(),
+            usage: session.summary.cumulative_usage.clone(),
+            daily_cost: session.usage_series.daily_costs(),
+            daily_tokens: session.usage_series.daily_tokens(),
+            model: session.summary.model.clone(),
+            title: session
+                .summary
+                .title
+                .clone()
+                .unwrap_or_else(|| session.summary.initial_task.clone()),
+            started_at: session.summary.started_at.clone(),
+            last_activity_at: session.summary.last_activity_at.clone(),
+            top_tools: session.summary.top_tools.clone(),
+        })
+    }
+
+    /// The sessions discovered in `project_path` (a discovery project path, as in
+    /// project usage) that did anything on `day` (days since the epoch), or None if
+    /// none is tracked.
+    pub async fn project_day_samples(
+        &self,
+        project_path: &str,
+        day: i64,
+        now_ms: i64,
+    ) -> Option<Vec<DaySample>> {
+        let tz = self.config.session.load().timezone;
+        let sessions = self.sessions.read().await;
+        let mut known = false;
+        let mut samples = Vec::new();
+        for (session_id, session) in sessions.iter().filter(|(_, s)| {
+            s.emitted
+                && !s.summary.replay
+                && !s.summary.historical
+                && s.discovery_project_path == project_path
+        }) {
+            known = true;
+            let on_day = |timestamp: &str| {
+                chrono::DateTime::parse_from_rfc3339(timestamp)
+                    .is_ok_and(|dt| days::day_of(dt.timestamp_millis(), tz) == day)
+            };
+            let cost = session.usage_series.daily_costs().get(&day).copied();
+            let tokens = session.usage_series.daily_tokens().get(&day).copied();
+            let up_to_ms = session.last_entry_ms().min(now_ms);
+            let active_ms = session.timeline.active_ms_on(day, up_to_ms, tz);
+            if cost.is_none()
+                && tokens.is_none()
+                && active_ms == 0
+                && !on_day(&session.summary.started_at)
+                && !on_day(&session.summary.last_activity_at)
+            {
+                continue;
+            }
+            let files = session.hydrated.then(|| {
+                let mut files: Vec<String> = Vec::new();
+                for msg in session
+                    .messages
+                    .iter()
+                    .filter(|m| m.msg_type == MessageType::ToolUse && on_day(&m.timestamp))
+                {
+                    let meta = msg.metadata.as_ref();
+                    let edits = meta
+                        .and_then(|m| m.get("toolName"))
+                        .and_then(|v| v.as_str())
+                        .is_some_and(|name| analysis::EDIT_TOOLS.contains(&name));
+                    let file = meta.and_then(|m| m.get("input")).and_then(|input| {
+                        input
+                            .get("file_path")
+                            .or_else(|| input.get("notebook_path"))
+                            .and_then(|v| v.as_str())
+                    });
+                    if let Some(file) =
+                        file.filter(|f| edits && !files.iter().any(|seen| seen == f))
+                    {
+                        files.push(file.to_string());
+                    }
+                }
+                files
+            });
+            let git = &session.summary.git_status;
+            samples.push(DaySample {
+                session_id: session_id.clone(),
+                project_name: session.summary.project_name.clone(),
+                title: session.summary.title.clone(),
+                model: session.summary.model.clone(),
+                state: session.summary.state,
+                started_at: session.summary.started_at.clone(),
+                last_activity_at: session.summary.last_activity_at.clone(),
+                cost: cost.unwrap_or(0.0),
+                tokens: tokens.unwrap_or(0),
+                active_ms,
+                files,
+                git: (!git.branch.is_empty() || git.last_commit.is_some()).then(|| git.clone()),
+                top_tools: session.summary.top_tools.clone(),
+            });
+        }
+        known.then_some(samples)
+    }
+
+    /// Totals across every session for the dashboard header, as of `now`, with days
+    /// in the configured zone.
+    pub async fn dashboard_stats(&self, now: chrono::DateTime<chrono::Utc>) -> DashboardStats {
+        let tz = self.config.session.load().timezone;
+        let today = days::today(now, tz);
+        let week = days::day_index(today.week(chrono::Weekday::Mon).first_day());
+        let today = days::day_index(today);
+
+        let sessions = self.sessions.read().await;
+        let mut stats = DashboardStats {
+            generated_at: format_timestamp(now),
+            ..Default::default()
+        };
+        let mut project_tokens: HashMap<&str, u64> = HashMap::new();
+        let mut tools = HashMap::new();
+        let mut durations = Vec::new();
+        // Historical sessions were only loaded to be looked at.
+        for session in sessions
+            .values()
+            .filter(|s| s.emitted && !s.summary.historical)
+        {
+            let summary = &session.summary;
+            stats.total_sessions += 1;
+            if matches!(
+                summary.state,
+                AgentStateType::Running | AgentStateType::PermissionWaiting
+            ) {
+                stats.active_sessions += 1;
+            }
+            stats.cost_today += session.usage_series.cost_since(today);
+            stats.cost_this_week += session.usage_series.cost_since(week);
+            let usage = &summary.cumulative_usage;
+            let tokens = usage.input_tokens
+                + usage.output_tokens
+                + usage.cache_read_tokens
+                + usage.cache_creation_tokens;
+            stats.total_tokens += tokens;
+            *project_tokens.entry(&summary.project_name).or_default() += tokens;
+            stats.total_turns += session.turns;
+            if session.hydrated {
+                stats.hydrated_sessions += 1;
+            } else {
+                stats.dehydrated_sessions += 1;
+            }
+            session.tool_stats.add_to(&mut tools);
+            stats.active_duration_ms_today += session
+                .timeline
+                .active_ms_on(today, session.last_entry_ms(), tz);
+
+            let parse = |ts: &str| chrono::DateTime::parse_from_rfc3339(ts).ok();
+            if let (Some(start), Some(end)) =
+                (parse(&summary.started_at), parse(&summary.last_activity_at))
+            {
+                durations.push((end - start).num_milliseconds().max(0) as u64);
+            }
+        }
+        stats.busiest_project = project_tokens
+            .into_iter()
+            .filter(|(name, tokens)| !name.is_empty() && *tokens > 0)
+            .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(a.0)))
+            .map(|(name, _)| name.to_string());
+        if !durations.is_empty() {
+            stats.average_session_duration_ms =
+                durations.iter().sum::<u64>() / durations.len() as u64;
+        }
+        stats.tools = sort_tools(tools.into_values());
+        stats.tool_calls = stats.tools.iter().map(|t| t.count).sum();
+        stats
+    }
+
+    pub async fn get_tool_stats(&self, session_id: &str) -> Option<SessionToolStats> {
+        let sessions = self.sessions.read().await;
+        let session = sessions.get(session_id)?;
+        Some(SessionToolStats {
+            session_id: session_id.to_string(),
+            tools: session.tool_stats.sorted(),
+        })
+    }
+
+    pub async fn get_sources(&self, session_id: &str) -> Option<SessionSources> {
+        let sessions = self.sessions.read().await;
+        let session = sessions.get(session_id)?;
+        Some(SessionSources {
+            session_id: session_id.to_string(),
+            sources: session.sources.sources().to_vec(),
+        })
+    }
+
+    /// The kept messages of a session split into turns.
+    pub async fn get_outline(&self, session_id: &str) -> Option<SessionOutline> {
+        let (marks, usage, tail) = {
+            let sessions = self.sessions.read().await;
+            let session = sessions.get(session_id)?;
+            if session.hydrated {
+                return Some(SessionOutline {
+                    session_id: session_id.to_string(),
+                    turns: build_outline(
+                        &session.messages,
+                        &session.turn_marks,
+                        &session.summary.cumulative_usage,
+                    ),
+                });
+            }
+            // Turn marks outlive dehydration; the messages are read back from the log.
+            (
+                session.turn_marks.clone(),
+                session.summary.cumulative_usage.clone(),
+                session.log_tail(),
+            )
+        };
+        let messages = tail.messages(session_id).await;
+        Some(SessionOutline {
+            session_id: session_id.to_string(),
+            turns: build_outline(&messages, &marks, &usage),
+        })
+    }
+
+    /// The directory a session was started in, whose repository its diff covers.
+    pub async fn working_directory(&self, session_id: &str) -> Option<String> {
+        let sessions = self.sessions.read().await;
+        Some(sessions.get(session_id)?.summary.working_directory.clone())
+    }
+
+    /// Add a message the dashboard produced itself rather than read from the log, e.g.
+    /// that it interrupted the session. Returns false if the session isn't tracked.
+    pub async fn record_message(
+        &self,
+        session_id: &str,
+        msg_type: MessageType,
+        content: String,
+        metadata: HashMap<String, serde_json::Value>,
+    ) -> bool {
+        let mut sessions = self.sessions.write().await;
+        let Some(session) = sessions.get_mut(session_id) else {
+            return false;
+        };
+        session.record_message(msg_type, content, metadata, &self.event_tx);
+        true
+    }
+
+    /// Memory held by messages across sessions, against the budget.
+    pub async fn transcript_memory(&self) -> TranscriptMemory {
+        let retained_bytes = self
+            .sessions
+            .read()
+            .await
+            .values()
+            .map(|s| s.retained_bytes)
+            .sum();
+        TranscriptMemory {
+            retained_bytes,
+            budget_bytes: self.config.session.load().transcript_memory_budget,
+            sheds: SHEDS.load(Ordering::Relaxed),
+            shed_messages: SHED_MESSAGES.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Counts of the watchers of shown sessions by mode, and the sessions whose
+    /// watcher is failing.
+    pub async fn watcher_counts(&self) -> WatcherCounts {
+        let sessions = self.sessions.read().await;
+        let mut counts = WatcherCounts::default();
+        for (session_id, session) in sessions.iter().filter(|(_, s)| s.emitted) {
+            match session.watcher.health().map(|h| h.mode) {
+                Some(WatcherMode::Notify) => counts.notify += 1,
+                Some(WatcherMode::PollOnly) => counts.poll_only += 1,
+                _ => continue,
+            }
+            if session.watcher.failing() || session.watcher_stall_reported {
+                counts.failing.push(session_id.clone());
+            }
+        }
+        counts.failing.sort();
+        counts
+    }
+
+    /// The log file a session is read from. Replays have none.
+    pub async fn log_file(&self, session_id: &str) -> Option<PathBuf> {
+        let sessions = self.sessions.read().await;
+        let path = sessions.get(session_id)?.watcher.file_path();
+        (!path.as_os_str().is_empty()).then(|| path.to_path_buf())
+    }
+
+    pub async fn get_session_timeline(&self, session_id: &str) -> Option<SessionTimeline> {
+        let sessions = self.sessions.read().await;
+        let session = sessions.get(session_id)?;
+        let intervals = session.timeline.intervals(
+            chrono::Utc::now().timestamp_millis(),
+            &session.summary.cumulative_usage,
+        );
+        Some(SessionTimeline {
+            session_id: session_id.to_string(),
+            started_at: session.summary.started_at.clone(),
+            total_active_ms: intervals.iter().map(|i| i.duration_ms).sum(),
+            intervals,
+        })
+    }
+
+    /// All sessions linked to `session_id` by resumption, oldest first.
+    pub async fn get_session_chain(&self, session_id: &str) -> Option<Vec<AgentSessionSummary>> {
+        let sessions = self.sessions.read().await;
+        sessions.get(session_id)?;
+        let root = chain_root(&sessions, session_id);
+        let mut chain: Vec<AgentSessionSummary> = sessions
+            .iter()
+            .filter(|(id, s)| (s.emitted || *id == session_id) && chain_root(&sessions, id) == root)
+            .map(|(_, s)| s.summary.clone())
+            .collect();
+        chain.sort_by(|a, b| {
+            a.started_at
+                .cmp(&b.started_at)
+                .then_with(|| a.session_id.cmp(&b.session_id))
+        });
+        Some(chain)
+    }
+
+    /// The oldest session of each session's resume chain, for sessions in a chain of
+    /// more than one.
+    pub async fn chain_roots(&self) -> HashMap<String, String> {
+        let sessions = self.sessions.read().await;
+        let mut roots = HashMap::new();
+        for id in sessions.keys() {
+            let root = chain_root(&sessions, id);
+            if root != id {
+                roots.insert(id.clone(), root.to_string());
+                roots.insert(root.to_string(), root.to_string());
+            }
+        }
+        roots
+    }
+
+    pub async fn get_session_messages(&self, session_id: &str) -> Option<Vec<Arc<AgentMessage>>> {
+        let tail = {
+            let sessions = self.sessions.read().await;
+            let s = sessions.get(session_id)?;
+            if s.hydrated {
+                return Some(s.messages.clone());
+            }
+            s.log_tail()
+        };
+        Some(tail.messages(session_id).await)
+    }
+
+    /// Messages after `message_id` (sent at `read_until`), or None if that isn't
+    /// known: a dehydrated session's messages aren't in memory, so it only counts as
+    /// read if it went quiet before then.
+    pub async fn messages_after(
+        &self,
+        session_id: &str,
+        message_id: &str,
+        read_until: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Option<u64> {
+        let sessions = self.sessions.read().await;
+        let s = sessions.get(session_id)?;
+        if s.hydrated {
+            return Some(messages_after(&s.messages, message_id, read_until));
+        }
+        let last_activity =
+            chrono::DateTime::parse_from_rfc3339(&s.summary.last_activity_at).ok()?;
+        (last_activity <= read_until?).then_some(0)
+    }
+
+    /// Sessions matching `query` in any of `scopes`, most matches first. The sessions
+    /// are copied out cheaply (their messages are shared) and scanned without the
+    /// lock held, so a slow search doesn't hold up reading new log entries.
+    pub async fn search_sessions(
+        &self,
+        query: &SearchQuery,
+        scopes: &[SearchScope],
+    ) -> Vec<SessionSearchResult> {
+        self.search_sessions_with(query, scopes, SearchQuery::find_needle)
+            .await
+    }
+
+    async fn search_sessions_with(
+        &self,
+        query: &SearchQuery,
+        scopes: &[SearchScope],
+        find: NeedleFinder,
+    ) -> Vec<SessionSearchResult> {
+        if query.is_empty() {
+            return Vec::new();
+        }
+        let content = scopes.contains(&SearchScope::Content);
+        let snapshots: Vec<SearchSnapshot> = {
+            let sessions = self.sessions.read().await;
+            sessions
+                .values()
+                .filter(|s| s.emitted)
+                .map(|s| SearchSnapshot::new(s, query, content))
+                .collect()
+        };
+        let query = query.clone();
+        let scopes = scopes.to_vec();
+        tokio::task::spawn_blocking(move || {
+            let mut results: Vec<SessionSearchResult> = snapshots
+                .iter()
+                .filter_map(|snapshot| search_snapshot(snapshot, &query, &scopes, find))
+                .collect();
+            results.sort_by_key(|r| std::cmp::Reverse(r.match_count));
+            results
+        })
+        .await
+        .unwrap_or_default()
+    }
+}
+
+/// Where a needle occurs in a message's content; `SearchQuery::find_needle` outside
+/// of tests.
+type NeedleFinder = fn(&SearchQuery, &str, &str) -> Option<Range<usize>>;
+
+/// What a search needs of a session, taken under the lock and scanned after it.
+struct SearchSnapshot {
+    summary: AgentSessionSummary,
+    redactor: Arc<Redactor>,
+    text_limits: TextLimits,
+    /// Empty unless content is searched, like the fields below.
+    messages: Vec<Arc<AgentMessage>>,
+    /// Sequence number of each of `messages`.
+    seqs: Vec<u64>,
+    trimmed: Vec<(u64, Arc<AgentMessage>)>,
+    /// Per needle, the messages the content index says contain it in full.
+    indexed: Vec<Option<HashSet<u64>>>,
+}
+
+impl SearchSnapshot {
+    fn new(session: &TrackedSession, query: &SearchQuery, content: bool) -> Self {
+        let mut snapshot = Self {
+            summary: session.summary.clone(),
+            redactor: session.settings().redactor.clone(),
+            text_limits: session.settings().text_limits,
+            messages: Vec::new(),
+            seqs: Vec::new(),
+            trimmed: Vec::new(),
+            indexed: Vec::new(),
+        };
+        if content {
+            snapshot.messages = session.messages.clone();
+            snapshot.seqs = session.message_seqs.clone();
+            snapshot.trimmed = session
+                .content_index
+                .trimmed()
+                .map(|(seq, msg)| (seq, msg.clone()))
+                .collect();
+            // The index only knows text lowercased, so it can't tell a case-sensitive
+            // hit in truncated text from a miss.
+            snapshot.indexed = query
+                .needles()
+                .map(|needle| {
+                    (!query.case_sensitive)
+                        .then(|| session.content_index.candidates(needle))
+                        .flatten()
+                })
+                .collect();
+        }
+        snapshot
+    }
+}
+
+fn search_snapshot(
+    snapshot: &SearchSnapshot,
+    query: &SearchQuery,
+    scopes: &[SearchScope],
+    find: NeedleFinder,
+) -> Option<SessionSearchResult> {
+    let summary = &snapshot.summary;
+    let context = snapshot.text_limits.snippet_context;
+    let mut matches: Vec<SearchMatch> = Vec::new();
+    let summary_match = |content: String, scope: SearchScope| SearchMatch {
+        content,
+        scope,
+        message_role: MessageRole::System,
+        message_type: MessageType::Text,
+        timestamp: summary.started_at.clone(),
+        truncated_source: false,
+        message_id: None,
+        message_index: None,
+        message_trimmed: false,
+        task: None,
+    };
+
+    for scope in scopes {
+        match scope {
+            SearchScope::ProjectName => {
+                if query.find(&summary.project_name).is_some() {
+                    matches.push(summary_match(
+                        summary.project_name.clone(),
+                        SearchScope::ProjectName,
+                    ));
+                }
+            }
+            SearchScope::Title => {
+                if let Some(title) = &summary.title {
+                    if let Some(span) = query.find(title) {
+                        matches.push(summary_match(
+                            make_snippet(title, span, context),
+                            SearchScope::Title,
+                        ));
+                    }
+                }
+            }
+            SearchScope::CurrentTask => {
+                let prompts = [
+                    (&summary.initial_task, TaskPrompt::Initial),
+                    (&summary.latest_task, TaskPrompt::Latest),
+                ];
+                for (task, prompt) in prompts {
+                    // A session with one prompt has it in both.
+                    if prompt == TaskPrompt::Latest && *task == summary.initial_task {
+                        continue;
+                    }
+                    if let Some(span) = query.find(task) {
+                        matches.push(SearchMatch {
+                            task: Some(prompt),
+                            ..summary_match(
+                                make_snippet(task, span, context),
+                                SearchScope::CurrentTask,
+                            )
+                        });
+                    }
+                }
+            }
+            SearchScope::WorkingDirectory => {
+                let wd = &summary.working_directory;
+                let pp = &summary.project_path;
+                if query.find(wd).is_some() || query.find(pp).is_some() {
+                    matches.push(summary_match(wd.clone(), SearchScope::WorkingDirectory));
+                }
+            }
+            // Dashboard-owned fields; matched by the session manager.
+            SearchScope::Note | SearchScope::Labels => {}
+            SearchScope::Content => {
+                matches.extend(search_messages(snapshot, query, find));
+            }
+        }
+    }
+
+    if matches.is_empty() {
+        return None;
+    }
+    let match_count = matches.len() as u32;
+    matches.truncate(3);
+    // Titles and paths aren't redacted when read, and a snippet can show
+    // them around the match.
+    for m in &mut matches {
+        if let Cow::Owned(content) = snapshot.redactor.redact(&m.content) {
+            m.content = content;
+        }
+    }
+    Some(SessionSearchResult {
+        session: summary.clone(),
+        match_count,
+        matches,
+        earlier_in_chain: Vec::new(),
+    })
+}
+
+/// Content-scope matches in a session's messages, oldest first. Needles missing from
+/// a message's stored content can still be found in its truncated-away text via the
+/// index, which also covers truncated messages trimmed out of the buffer.
+fn search_messages(
+    snapshot: &SearchSnapshot,
+    query: &SearchQuery,
+    find: NeedleFinder,
+) -> Vec<SearchMatch> {
+    let trimmed = snapshot
+        .trimmed
+        .iter()
+        .map(|(seq, msg)| (*seq, None, msg.as_ref()));
+    let buffered = snapshot
+        .messages
+        .iter()
+        .enumerate()
+        .zip(&snapshot.seqs)
+        .map(|((i, msg), &seq)| (seq, Some(i as u32), msg.as_ref()));
+
+    let limits = &snapshot.text_limits;
+    let mut matches = Vec::new();
+    for (seq, message_index, msg) in trimmed.chain(buffered) {
+        let mut span = None;
+        let mut truncated_source = false;
+        let all_found = query
+            .needles()
+            .zip(&snapshot.indexed)
+            .all(|(needle, hits)| {
+                if let Some(found) = find(query, &msg.content, needle) {
+                    span.get_or_insert(found);
+                    true
+                } else if hits.as_ref().is_some_and(|h| h.contains(&seq)) {
+                    truncated_source = true;
+                    true
+                } else {
+                    false
+                }
+            });
+        if !all_found {
+            continue;
+        }
+        matches.push(SearchMatch {
+            content: match span {
+                Some(span) => make_snippet(&msg.content, span, limits.snippet_context),
+                None => truncate(&msg.content, limits.preview),
+            },
+            scope: SearchScope::Content,
+            message_role: msg.role,
+            message_type: msg.msg_type,
+            timestamp: msg.timestamp.clone(),
+            truncated_source,
+            message_id: Some(msg.id.clone()),
+            message_index,
+            message_trimmed: message_index.is_none(),
+            task: None,
+        });
+    }
+    matches
+}
+
+/// Messages after `message_id`. Counted from that message while it is still among
+/// `messages`, otherwise by timestamp; everything if neither is known.
+pub fn messages_after(
+    messages: &[Arc<AgentMessage>],
+    message_id: &str,
+    read_until: Option<chrono::DateTime<chrono::Utc>>,
+) -> u64 {
+    if let Some(i) = messages.iter().rposition(|m| m.id == message_id) {
+        return (messages.len() - i - 1) as u64;
+    }
+    let Some(read_until) = read_until else {
+        return messages.len() as u64;
+    };
+    messages
+        .iter()
+        .filter(|m| chrono::DateTime::parse_from_rfc3339(&m.timestamp).is_ok_and(|at| at > read_until))
+        .count() as u64
+}
+
+/// How a found session's log is read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LogMode {
+    /// Watched for new entries, as discovery finds live sessions.
+    Watched,
+    /// Read once, for a look at an old session.
+    Historical,
 }
 
 async fn handle_session_found(
     sessions: &Arc<RwLock<HashMap<String, TrackedSession>>>,
     event_tx: &mpsc::UnboundedSender<ProviderEvent>,
+    startup: &Arc<StartupScan>,
     discovered: DiscoveredSession,
+    config: &ProviderConfig,
+    mode: LogMode,
 ) {
+    let session_id = discovered.session_id.clone();
     {
-        let sessions_read = sessions.read().await;
-        if sessions_read.contains_key(&discovered.session_id) {
+        let mut sessions_write = sessions.write().await;
+        let Some(existing) = sessions_write.get_mut(&session_id) else {
+            drop(sessions_write);
+            return add_found_session(sessions, event_tx, startup, discovered, config, mode).await;
+        };
+        // A historical session whose log is written to again is followed from now on.
+        if mode == LogMode::Historical || !existing.summary.historical {
             return;
         }
+        info!("[Session] {}: old log written to, watching it", session_id);
+        existing.summary.historical = false;
+        existing.evict_at = None;
+        if existing.hydrated {
+            existing.watcher.start().await;
+        }
+        let _ = event_tx.send(ProviderEvent::SessionUpdated {
+            session: existing.summary.clone(),
+        });
     }
+    rehydrate(sessions, &session_id).await;
+}
 
-    // Stop older active sessions for the same project.
-    // When a new session appears (e.g. user selected "clear session"),
-    // the old session's JSONL stops receiving entries, so we mark it Stopped
-    // immediately rather than waiting for the 30-minute timeout.
-    {
-        let mut sessions_write = sessions.write().await;
-        for (sid, session) in sessions_write.iter_mut() {
-            if session.discovery_project_path == discovered.project_path
-                && *sid != discovered.session_id
-                && matches!(
-                    session.state_ctx.state,
-                    AgentStateType::Running
-                        | AgentStateType::Idle
-                        | AgentStateType::PermissionWaiting
-                        | AgentStateType::Error
-                )
-            {
-                let prev = session.state_ctx.state;
-                session.state_ctx.state = AgentStateType::Stopped;
-                session.summary.state = AgentStateType::Stopped;
-                if session.emitted {
-                    let _ = event_tx.send(ProviderEvent::StateChanged {
-                        session_id: sid.clone(),
-                        previous: prev,
-                        current: AgentStateType::Stopped,
-                    });
-                }
-            }
-        }
+async fn add_found_session(
+    sessions: &Arc<RwLock<HashMap<String, TrackedSession>>>,
+    event_tx: &mpsc::UnboundedSender<ProviderEvent>,
+    startup: &Arc<StartupScan>,
+    discovered: DiscoveredSession,
+    config: &ProviderConfig,
+    mode: LogMode,
+) {
+    // An old log says nothing about the sessions running now.
+    if mode == LogMode::Watched {
+        supersede_sessions(
+            &mut *sessions.write().await,
+            event_tx,
+            &discovered.project_path,
+            chrono::Utc::now().timestamp_millis(),
+            config.session.load().supersede_grace.as_millis() as i64,
+        );
     }
 
-    let state_ctx = StateContext::new();
     let summary = AgentSessionSummary {
         session_id: discovered.session_id.clone(),
         provider: "claude-code".to_string(),
@@ -334,30 +2058,64 @@ async fn handle_session_found(
         project_path: discovered.project_path.clone(),
         project_name: discovered.project_name.clone(),
         working_directory: discovered.project_path.clone(),
-        current_task: String::new(),
+        current_cwd: discovered.project_path.clone(),
+        initial_task: String::new(),
+        latest_task: String::new(),
+        latest_assistant_text: None,
         model: "unknown".to_string(),
-        last_activity_at: chrono::Utc::now().to_rfc3339(),
-        started_at: chrono::Utc::now().to_rfc3339(),
+        last_activity_at: now_timestamp(),
+        started_at: now_timestamp(),
         cumulative_usage: CumulativeUsage::default(),
         git_status: GitStatus::default(),
+        pinned: false,
+        note: None,
+        labels: Vec::new(),
+        summary: None,
+        permission_mode: None,
+        resumed_from: None,
+        spend_rate_per_hour: 0.0,
+        tokens_per_minute: 0.0,
+        context_tokens: 0,
+        context_limit: None,
+        cli_version: None,
+        title: None,
+        top_tools: Vec::new(),
+        replay: false,
+        historical: mode == LogMode::Historical,
+        host: config.host.clone(),
+        activity_type: None,
+        pending_tool: None,
+        stop_reason: None,
+        hook_errors: 0,
+        failed_turns: 0,
+        retry_count: 0,
+        active_duration_ms: 0,
+        message_count: 0,
+        tool_call_count: 0,
+        transcript_bytes: 0,
+        source_count: 0,
+        environment: None,
     };
 
+    if mode == LogMode::Historical {
+        return add_historical_session(sessions, event_tx, startup, discovered, config, summary)
+            .await;
+    }
+
     // Create file watcher with entry channel
     let (entries_tx, mut entries_rx) = mpsc::unbounded_channel();
-    let watcher = FileWatcher::new(discovered.log_file.clone(), entries_tx);
+    let watcher = FileWatcher::new(discovered.log_file.clone(), entries_tx)
+        .with_poll_interval(config.poll_interval)
+        .with_max_line_bytes(config.max_line_bytes);
+    startup.found(&discovered.session_id);
     watcher.start().await;
 
-    let tracked = TrackedSession {
+    let tracked = TrackedSession::new(
         summary,
-        messages: Vec::new(),
-        state_ctx,
         watcher,
-        model: "unknown".to_string(),
-        emitted: false,
-        last_git_diff_check: 0,
-        last_process_check: 0,
-        discovery_project_path: discovered.project_path.clone(),
-    };
+        discovered.project_path.clone(),
+        config.session.clone(),
+    );
 
     {
         let mut sessions_write = sessions.write().await;
@@ -367,65 +2125,409 @@ async fn handle_session_found(
     // Spawn task to handle entries from file watcher
     let sessions_clone = sessions.clone();
     let event_tx_clone = event_tx.clone();
+    let startup = startup.clone();
     let session_id = discovered.session_id.clone();
     tokio::spawn(async move {
-        while let Some(entries) = entries_rx.recv().await {
-            handle_entries(&sessions_clone, &event_tx_clone, &session_id, entries).await;
+        while let Some(event) = entries_rx.recv().await {
+            handle_watch_event(
+                &sessions_clone,
+                &event_tx_clone,
+                &startup,
+                &session_id,
+                event,
+            )
+            .await;
         }
     });
 }
 
-async fn handle_entries(
+/// Read an old log in one go, without watching it. The session ends up stopped and is
+/// dropped after `HISTORY_TTL_MS`; the watcher is only started if the log is written
+/// to again, from where this read ended.
+async fn add_historical_session(
     sessions: &Arc<RwLock<HashMap<String, TrackedSession>>>,
     event_tx: &mpsc::UnboundedSender<ProviderEvent>,
-    session_id: &str,
-    entries: Vec<RawEntry>,
+    startup: &Arc<StartupScan>,
+    discovered: DiscoveredSession,
+    config: &ProviderConfig,
+    summary: AgentSessionSummary,
 ) {
-    let mut sessions = sessions.write().await;
-    let session = match sessions.get_mut(session_id) {
-        Some(s) => s,
-        None => return,
+    let session_id = discovered.session_id.clone();
+    // Of a very long log, only the end, as when a dehydrated session is re-read.
+    let read = match tokio::fs::metadata(&discovered.log_file).await {
+        Ok(metadata) => {
+            read_tail(
+                &discovered.log_file,
+                metadata.len(),
+                REREAD_MAX_BYTES,
+                config.max_line_bytes,
+            )
+            .await
+        }
+        Err(e) => Err(e),
+    };
+    let (entries, end) = match read {
+        Ok(read) => read,
+        Err(e) => {
+            warn!("Failed to read {}: {}", discovered.log_file.display(), e);
+            return;
+        }
     };
 
-    for entry in &entries {
-        // Extract metadata from user messages
-        if let RawEntry::User(user_msg) = entry {
-            if let Some(cwd) = &user_msg.cwd {
-                session.summary.working_directory = cwd.clone();
-                if let Some(name) = cwd.split('/').last() {
-                    if !name.is_empty() {
-                        session.summary.project_name = name.to_string();
-                    }
-                }
-                session.summary.project_path = cwd.clone();
+    // The channel is unused until the watcher is started.
+    let (entries_tx, mut entries_rx) = mpsc::unbounded_channel();
+    let watcher = FileWatcher::new(discovered.log_file.clone(), entries_tx)
+        .with_poll_interval(config.poll_interval)
+        .with_max_line_bytes(config.max_line_bytes)
+        .with_offset(end);
+    let mut tracked = TrackedSession::new(
+        summary,
+        watcher,
+        discovered.project_path.clone(),
+        config.session.clone(),
+    );
+    tracked.evict_at = Some(chrono::Utc::now().timestamp_millis() + HISTORY_TTL_MS);
+    sessions.write().await.insert(session_id.clone(), tracked);
+
+    let mut entries = entries.into_iter().peekable();
+    while entries.peek().is_some() {
+        let batch: Vec<RawEntry> = entries.by_ref().take(HISTORY_BATCH_ENTRIES).collect();
+        handle_entries(sessions, event_tx, &session_id, batch).await;
+    }
+    if let Some(session) = sessions.write().await.get_mut(&session_id) {
+        if session.state_ctx.state != AgentStateType::Stopped {
+            session.mark_stopped(&session_id, event_tx);
+        }
+    }
+
+    let sessions_clone = sessions.clone();
+    let event_tx_clone = event_tx.clone();
+    let startup = startup.clone();
+    tokio::spawn(async move {
+        while let Some(event) = entries_rx.recv().await {
+            handle_watch_event(
+                &sessions_clone,
+                &event_tx_clone,
+                &startup,
+                &session_id,
+                event,
+            )
+            .await;
+        }
+    });
+}
+
+/// Running sessions can't be dismissed: they would only be found again.
+fn dismiss_plan(
+    sessions: &HashMap<String, TrackedSession>,
+    session_id: &str,
+) -> Result<DismissPlan, DismissError> {
+    let session = sessions.get(session_id).ok_or(DismissError::NotFound)?;
+    if session.state_ctx.state == AgentStateType::Running {
+        return Err(DismissError::Running);
+    }
+    Ok(DismissPlan {
+        session_id: session_id.to_string(),
+        state: session.state_ctx.state,
+        log_file: session.watcher.file_path().display().to_string(),
+    })
+}
+
+/// A new session appeared in `project`: stop the project's other active sessions
+/// whose logs have been quiet for `grace_ms`. The rest may be running in parallel
+/// (two `claude` instances in one repo); they are re-checked on the timer.
+fn supersede_sessions(
+    sessions: &mut HashMap<String, TrackedSession>,
+    event_tx: &mpsc::UnboundedSender<ProviderEvent>,
+    project: &str,
+    now_ms: i64,
+    grace_ms: i64,
+) {
+    for (sid, session) in sessions.iter_mut() {
+        if session.discovery_project_path != project || !session.is_active() {
+            continue;
+        }
+        // Logs not read yet can't tell us anything; wait for them.
+        let last_write = session.state_ctx.last_entry_timestamp;
+        if grace_ms == 0 || (last_write > 0 && now_ms - last_write > grace_ms) {
+            session.mark_stopped(sid, event_tx);
+        } else {
+            session.superseded_at = Some(now_ms);
+        }
+    }
+}
+
+async fn handle_watch_event(
+    sessions: &Arc<RwLock<HashMap<String, TrackedSession>>>,
+    event_tx: &mpsc::UnboundedSender<ProviderEvent>,
+    startup: &StartupScan,
+    session_id: &str,
+    event: WatchEvent,
+) {
+    match event {
+        WatchEvent::Entries(entries) => {
+            handle_entries(sessions, event_tx, session_id, entries).await;
+        }
+        WatchEvent::FileGone => handle_log_deleted(sessions, event_tx, session_id).await,
+        WatchEvent::InitialRead {
+            entries,
+            parse_errors,
+        } => startup.read(session_id, entries, parse_errors),
+    }
+}
+
+/// The log disappeared while it was watched: stop the session, and remove it after
+/// `LOG_DELETED_TTL_MS` unless the log is written again. The watcher keeps polling
+/// for it.
+async fn handle_log_deleted(
+    sessions: &Arc<RwLock<HashMap<String, TrackedSession>>>,
+    event_tx: &mpsc::UnboundedSender<ProviderEvent>,
+    session_id: &str,
+) {
+    let mut sessions = sessions.write().await;
+    let Some(session) = sessions.get_mut(session_id) else {
+        return;
+    };
+    let log_file = session.watcher.file_path().display().to_string();
+    warn!("[Session] {}: log {} is gone", session_id, log_file);
+    if session.state_ctx.state != AgentStateType::Stopped {
+        session.mark_stopped(session_id, event_tx);
+    }
+    session.summary.stop_reason = Some(LOG_DELETED.to_string());
+    let evict_at = chrono::Utc::now().timestamp_millis() + LOG_DELETED_TTL_MS;
+    session.evict_at = Some(session.evict_at.map_or(evict_at, |at| at.min(evict_at)));
+    if session.emitted {
+        let _ = event_tx.send(ProviderEvent::LogDeleted {
+            session_id: session_id.to_string(),
+            log_file,
+        });
+    }
+}
+
+/// What the file system says about the directories a batch of entries was run in,
+/// while the session's project directory is still to be found among them. Looked up
+/// before the entries are applied, so the disk isn't touched under the sessions lock.
+#[derive(Debug, Default)]
+struct CwdChecks {
+    /// The cwds that aren't on this machine.
+    missing: HashSet<String>,
+    /// Whether the project path decoded from the log's directory doesn't exist.
+    discovery_path_missing: bool,
+}
+
+impl CwdChecks {
+    async fn run(
+        sessions: &RwLock<HashMap<String, TrackedSession>>,
+        session_id: &str,
+        entries: &[RawEntry],
+    ) -> Self {
+        let discovery_path = match sessions.read().await.get(session_id) {
+            Some(s) if !s.project_root_known => s.discovery_project_path.clone(),
+            _ => return Self::default(),
+        };
+        let cwds: HashSet<&str> = entries
+            .iter()
+            .filter_map(|entry| match entry {
+                RawEntry::User(m) => m.cwd.as_deref().filter(|c| !c.is_empty()),
+                _ => None,
+            })
+            .collect();
+        if cwds.is_empty() {
+            return Self::default();
+        }
+        let mut missing = HashSet::new();
+        for cwd in cwds {
+            if environment::working_directory_missing(cwd).await {
+                missing.insert(cwd.to_string());
+            }
+        }
+        Self {
+            missing,
+            discovery_path_missing: tokio::fs::metadata(local_path(&discovery_path))
+                .await
+                .is_err(),
+        }
+    }
+}
+
+async fn handle_entries(
+    sessions: &Arc<RwLock<HashMap<String, TrackedSession>>>,
+    event_tx: &mpsc::UnboundedSender<ProviderEvent>,
+    session_id: &str,
+    entries: Vec<RawEntry>,
+) {
+    let sessions_arc = sessions;
+    let cwd_checks = CwdChecks::run(sessions, session_id, &entries).await;
+    let mut sessions = sessions.write().await;
+    // Projects other sessions found, so a project logged both from Windows and from
+    // WSL is listed under one spelling.
+    let known_projects: Vec<String> = match sessions.get(session_id) {
+        Some(s) if !s.project_root_known => sessions
+            .values()
+            .filter(|other| other.project_root_known)
+            .map(|other| other.summary.project_path.clone())
+            .collect(),
+        _ => Vec::new(),
+    };
+    let session = match sessions.get_mut(session_id) {
+        Some(s) => s,
+        None => return,
+    };
+    let mut entered_idle = false;
+    let mut usage_changed = false;
+    let mut title_changed = false;
+    let mut tools_changed = false;
+    let mut state_changed = false;
+    let mut hooks_changed = false;
+    let mut failures_changed = false;
+    let mut mode_changed = false;
+    let mut reply_changed = false;
+    let mut new_messages: Vec<AgentMessage> = Vec::new();
+    let initial_read = session.entries_read == 0;
+    // A session first shown partway through the batch isn't updated for its reply.
+    let was_emitted = session.emitted;
+    // A deleted log was written again, e.g. restored from a backup.
+    if session.summary.stop_reason.as_deref() == Some(LOG_DELETED) {
+        session.summary.stop_reason = None;
+        session.evict_at = None;
+    }
+
+    for entry in &entries {
+        let position = EntryPosition {
+            session_id,
+            index: session.entries_read,
+        };
+        session.entries_read += 1;
+        if entry_uuid(entry).is_some_and(|uuid| !session.seen_entries.insert(uuid)) {
+            continue;
+        }
+        session.resume.observe(session_id, entry);
+        let late = is_late(entry, session.state_ctx.last_entry_timestamp);
+
+        let title = match entry {
+            RawEntry::Summary(s) => s.summary.as_deref().map(|t| (t, TitleSource::Summary)),
+            RawEntry::User(m) => m.slug.as_deref().map(|t| (t, TitleSource::Slug)),
+            RawEntry::Assistant(m) => m.slug.as_deref().map(|t| (t, TitleSource::Slug)),
+            _ => None,
+        };
+        if let Some((title, source)) = title {
+            title_changed |= session.offer_title(title, source);
+        }
+
+        let version = match entry {
+            RawEntry::User(m) => m.version.as_deref(),
+            RawEntry::Assistant(m) => m.version.as_deref(),
+            _ => None,
+        };
+        if let Some(version) = version.filter(|v| !v.is_empty()) {
+            if session.summary.cli_version.as_deref() != Some(version) {
+                warn_if_untested(version);
+                session.summary.cli_version = Some(version.to_string());
+            }
+        }
+
+        // Extract metadata from user messages
+        if let RawEntry::User(user_msg) = entry {
+            if let Some(cwd) = user_msg.cwd.as_deref().filter(|c| !c.is_empty()) {
+                session.summary.current_cwd = cwd.to_string();
+                if !session.project_root_known && session.is_project_root(cwd, &cwd_checks) {
+                    session.project_root_known = true;
+                    session.summary.working_directory = cwd.to_string();
+                    // Known before the session is shown, so the first git check
+                    // doesn't have to announce it.
+                    if environment::container_hint(cwd) || cwd_checks.missing.contains(cwd) {
+                        session.summary.environment = Some(REMOTE_OR_CONTAINER.to_string());
+                    }
+                    let project_path = known_projects
+                        .iter()
+                        .find(|known| same_project(known, cwd))
+                        .map_or(cwd, String::as_str);
+                    if let Some(name) = project_name(project_path) {
+                        session.summary.project_name = name.to_string();
+                    }
+                    session.summary.project_path = project_path.to_string();
+                }
             }
-            if session.summary.current_task.is_empty() {
-                let (_, _, current_task) = extract_session_metadata(user_msg);
-                session.summary.current_task = current_task;
+            if session.summary.initial_task.is_empty() {
+                let settings = session.settings();
+                let (_, _, initial_task) =
+                    extract_session_metadata(user_msg, &settings.redactor, &settings.text_limits);
+                session.summary.initial_task = initial_task;
                 if let Some(ts) = &user_msg.timestamp {
-                    session.summary.started_at = ts.clone();
+                    session.summary.started_at = normalize_timestamp(ts);
                 }
             }
+            if is_user_prompt(user_msg) && !late {
+                let settings = session.settings();
+                let (_, _, task) =
+                    extract_session_metadata(user_msg, &settings.redactor, &settings.text_limits);
+                if !task.is_empty() {
+                    session.summary.latest_task = task;
+                }
+                // The previous reply answered the previous prompt.
+                reply_changed |= session.summary.latest_assistant_text.take().is_some();
+            }
             // Extract git branch
             if let Some(branch) = &user_msg.git_branch {
-                if !branch.is_empty() && branch != "HEAD" {
+                if !branch.is_empty() && branch != "HEAD" && !session.branch_from_git {
                     session.summary.git_status.branch = branch.clone();
                 }
             }
         }
 
+        if let RawEntry::System(sys) = entry {
+            if sys.subtype.as_deref() == Some("compact_boundary") {
+                session.context_warned = 0;
+            }
+        }
+
+        if let RawEntry::Hook(hook) = entry {
+            if matches!(hook.outcome, HookOutcome::Failed | HookOutcome::Blocked) {
+                session.summary.hook_errors += 1;
+                hooks_changed = true;
+            }
+        }
+
+        if let Some(failure) = session.failures.observe(entry) {
+            match failure {
+                Failure::FailedTurn => session.summary.failed_turns += 1,
+                Failure::Retry => session.summary.retry_count += 1,
+            }
+            failures_changed = true;
+        }
+
         // Extract git branch and model from assistant messages
         if let RawEntry::Assistant(assistant_msg) = entry {
             if let Some(branch) = &assistant_msg.git_branch {
-                if !branch.is_empty() && branch != "HEAD" {
+                if !branch.is_empty() && branch != "HEAD" && !session.branch_from_git {
                     session.summary.git_status.branch = branch.clone();
                 }
             }
             let model = extract_model(assistant_msg);
-            if model != "unknown" {
-                let was_unknown = session.model == "unknown";
-                session.model = model.to_string();
-                session.summary.model = model.to_string();
+            if is_real_model(model) {
+                let was_unknown = session.model == UNKNOWN_MODEL;
+                // Subagents can run on a model of their own; only the main thread
+                // switches the session's.
+                if assistant_msg.is_sidechain != Some(true) && !late {
+                    let timestamp = entry_time(entry);
+                    if let Some(change) = session.models.observe(model, &timestamp) {
+                        let msg = change.message(position, &timestamp);
+                        session.summary.message_count += 1;
+                        session.push_message(msg.clone());
+                        new_messages.push(msg);
+                        if session.emitted {
+                            let _ = event_tx.send(ProviderEvent::ModelChanged {
+                                session_id: session_id.to_string(),
+                                previous: change.previous,
+                                current: change.current,
+                            });
+                        }
+                    }
+                }
+                if (assistant_msg.is_sidechain != Some(true) && !late) || was_unknown {
+                    session.model = model.to_string();
+                    session.summary.model = model.to_string();
+                }
 
                 if was_unknown && !session.emitted {
                     session.emitted = true;
@@ -435,405 +2537,3641 @@ async fn handle_entries(
                 }
             }
 
-            // Update usage
-            if let Some((input, output, cache_read, cache_creation)) = extract_usage(assistant_msg) {
-                session.summary.cumulative_usage = add_usage(
-                    &session.summary.cumulative_usage,
-                    &session.model,
-                    input,
-                    output,
-                    cache_read,
-                    cache_creation,
-                );
-                if session.emitted {
-                    let _ = event_tx.send(ProviderEvent::UsageUpdated {
-                        session_id: session_id.to_string(),
-                        usage: session.summary.cumulative_usage.clone(),
-                    });
-                }
-            }
-        }
+            // Update usage
+            if let Some(usage) = extract_usage(assistant_msg) {
+                // The whole prompt is sent each turn, so its size is what the context
+                // holds. Subagents have contexts of their own.
+                if assistant_msg.is_sidechain != Some(true) {
+                    let context_tokens =
+                        usage.input_tokens + usage.cache_read_tokens + usage.cache_creation_tokens;
+                    // Compaction shrinks the prompt; warn again as it refills.
+                    if context_tokens < session.summary.context_tokens / 2 {
+                        session.context_warned = 0;
+                    }
+                    let limit = session
+                        .settings
+                        .load()
+                        .context_limits
+                        .get(&session.model, context_tokens);
+                    session.summary.context_tokens = context_tokens;
+                    // Once a prompt has outgrown the usual window, the session keeps
+                    // the 1M context after it shrinks again.
+                    session.summary.context_limit =
+                        limit.map(|limit| limit.max(session.summary.context_limit.unwrap_or(0)));
+                }
+                session.summary.cumulative_usage = session.settings().pricing.add_usage(
+                    &session.summary.cumulative_usage,
+                    &session.model,
+                    &usage,
+                );
+                let at_ms = get_entry_timestamp(entry)
+                    .unwrap_or_else(|| chrono::Utc::now().timestamp_millis());
+                session.usage_series.record(
+                    at_ms,
+                    &session.summary.cumulative_usage,
+                    session.settings().timezone,
+                );
+                usage_changed = true;
+            }
+        }
+
+        // Process state machine. A late entry says nothing about what the session is
+        // doing now, and doesn't move its last activity back.
+        let prev_state = session.state_ctx.state;
+        let result = if late {
+            process_late_entry(&mut session.state_ctx, entry);
+            TransitionResult {
+                new_state: prev_state,
+                changed: false,
+            }
+        } else {
+            process_entry(&mut session.state_ctx, entry)
+        };
+
+        if result.changed {
+            session.sync_state();
+            session.record_transition(prev_state);
+            entered_idle = session.state_ctx.state == AgentStateType::Idle;
+            state_changed = true;
+            session.summary.last_activity_at =
+                chrono::DateTime::from_timestamp_millis(session.state_ctx.last_entry_timestamp)
+                    .map_or_else(now_timestamp, format_timestamp);
+            if session.emitted {
+                let _ = event_tx.send(ProviderEvent::StateChanged {
+                    session_id: session_id.to_string(),
+                    previous: prev_state,
+                    current: session.state_ctx.state,
+                });
+            }
+        }
+
+        // Map to AgentMessages
+        let settings = session.settings();
+        let messages = map_entry_full(entry, position, &settings.redactor, &settings.text_limits);
+        let main_reply =
+            !late && matches!(entry, RawEntry::Assistant(m) if m.is_sidechain != Some(true));
+        for (mut msg, full_text) in messages {
+            if main_reply && msg.role == MessageRole::Assistant && msg.msg_type == MessageType::Text
+            {
+                let preview = reply_preview(&msg.content, settings.text_limits.task);
+                if !preview.is_empty()
+                    && session.summary.latest_assistant_text.as_deref() != Some(preview.as_str())
+                {
+                    session.summary.latest_assistant_text = Some(preview);
+                    reply_changed = true;
+                }
+            }
+            session.tool_stats.link_result(&mut msg);
+            tools_changed |= session.tool_stats.record(&msg, full_text.as_deref());
+            if session.sources.record(&msg, full_text.as_deref()) {
+                session.summary.source_count = session.sources.count() as u64;
+            }
+            // The usage when a late prompt arrived isn't known.
+            if !late {
+                session
+                    .turn_marks
+                    .record(&msg, &session.summary.cumulative_usage);
+            }
+            if let Some(full_text) = &full_text {
+                session.content_index.add(session.message_seq, full_text);
+            }
+            session.summary.message_count += 1;
+            if msg.msg_type == MessageType::ToolUse {
+                session.summary.tool_call_count += 1;
+            }
+            if late {
+                session.push_late_message(msg.clone());
+            } else {
+                session.push_message(msg.clone());
+            }
+            new_messages.push(msg);
+        }
+
+        let mode_change = if late {
+            None
+        } else {
+            session.permission_mode.observe(entry)
+        };
+        if let Some(change) = mode_change {
+            session.state_ctx.bypass_permissions = change.to == BYPASS_PERMISSIONS;
+            session.summary.permission_mode = session.permission_mode.mode().map(String::from);
+            mode_changed = true;
+            if change.is_notable() {
+                let msg = change.message(position, &entry_time(entry));
+                session.summary.message_count += 1;
+                session.push_message(msg.clone());
+                new_messages.push(msg);
+            }
+        }
+    }
+
+    if session.emitted {
+        send_new_messages(event_tx, session_id, new_messages);
+    }
+
+    session.summary.last_activity_at =
+        chrono::DateTime::from_timestamp_millis(session.state_ctx.last_entry_timestamp)
+            .map_or_else(now_timestamp, format_timestamp);
+    session.summary.active_duration_ms = session.timeline.active_ms(session.last_entry_ms());
+    session.summary.transcript_bytes = session.watcher.file_size();
+
+    // Still writing after a newer session appeared: both are running.
+    if session
+        .superseded_at
+        .is_some_and(|at| session.state_ctx.last_entry_timestamp > at)
+    {
+        session.superseded_at = None;
+    }
+
+    // One spend sample per batch: a log replayed on startup arrives as a single
+    // batch and must not read as everything having been spent just now.
+    if usage_changed {
+        let now_ms = chrono::Utc::now().timestamp_millis();
+        session
+            .spend_rate
+            .record(now_ms, &session.summary.cumulative_usage);
+        session.refresh_spend_rate(now_ms);
+        if session.emitted {
+            let _ = event_tx.send(session.usage_event());
+        }
+        // A log read in at startup doesn't warn about how full it already was.
+        if let Some((threshold, percent_used)) = session.cross_context_threshold() {
+            if session.emitted && !initial_read {
+                let _ = event_tx.send(ProviderEvent::ContextWarning {
+                    session_id: session_id.to_string(),
+                    percent_used,
+                    threshold,
+                });
+            }
+        }
+    }
+
+    if tools_changed {
+        session.summary.top_tools = session.tool_stats.top(TOP_TOOLS);
+    }
+
+    // The activity is re-evaluated when the state changes, and every minute.
+    let activity_changed =
+        state_changed && session.classify_activity(chrono::Utc::now().timestamp_millis());
+
+    if (title_changed
+        || tools_changed
+        || activity_changed
+        || hooks_changed
+        || failures_changed
+        || mode_changed
+        || (reply_changed && was_emitted))
+        && session.emitted
+    {
+        let _ = event_tx.send(ProviderEvent::SessionUpdated {
+            session: session.summary.clone(),
+        });
+    }
+
+    // Run time-based check immediately after processing entries so that
+    // stale sessions (e.g. Idle for hours) get the correct state on startup
+    // instead of waiting for the next 3s timer tick.
+    let prev_state = session.state_ctx.state;
+    let result = session.check_time_transitions();
+    if result.changed {
+        session.sync_state();
+        session.record_transition(prev_state);
+        entered_idle = session.state_ctx.state == AgentStateType::Idle;
+        if session.emitted {
+            let _ = event_tx.send(ProviderEvent::StateChanged {
+                session_id: session_id.to_string(),
+                previous: prev_state,
+                current: session.state_ctx.state,
+            });
+        }
+    }
+
+    let project = session.discovery_project_path.clone();
+
+    // The agent just finished a turn: refresh git stats now instead of on the next interval.
+    if entered_idle && session.emitted && session.state_ctx.state == AgentStateType::Idle {
+        if let Some(wd) = session.begin_git_check(chrono::Utc::now().timestamp_millis()) {
+            spawn_git_check(
+                sessions_arc.clone(),
+                event_tx.clone(),
+                session_id.to_string(),
+                wd,
+                session.settings().git_backend,
+            );
+        }
+    }
+
+    // New entries can resolve this session's own link, or a newer session's link to this one.
+    link_resumed_sessions(&mut sessions, event_tx, &project);
+}
+
+/// A dehydrated session's log was written to: read its recent messages back in and
+/// watch the log again from where the watcher stopped.
+async fn rehydrate(sessions: &Arc<RwLock<HashMap<String, TrackedSession>>>, session_id: &str) {
+    let Some(tail) = sessions
+        .read()
+        .await
+        .get(session_id)
+        .filter(|s| !s.hydrated)
+        .map(TrackedSession::log_tail)
+    else {
+        return;
+    };
+    let mut messages = tail.messages(session_id).await;
+    let mut sessions = sessions.write().await;
+    let Some(session) = sessions.get_mut(session_id).filter(|s| !s.hydrated) else {
+        return;
+    };
+    info!(
+        "[Session] {}: log written to, watching it again",
+        session_id
+    );
+    // Numbered as the latest messages read, which re-reading found them to be.
+    let first_seq = session.message_seq.saturating_sub(messages.len() as u64);
+    session.message_seqs = (first_seq..first_seq + messages.len() as u64).collect();
+    session.message_seq = session.message_seq.max(first_seq + messages.len() as u64);
+    session.retained_bytes = 0;
+    for msg in &mut messages {
+        let msg = Arc::make_mut(msg);
+        let (content, shared) = session.interner.intern(msg.content.clone());
+        msg.content = content;
+        session.retained_bytes += message_bytes(msg, !shared);
+    }
+    session.messages = messages;
+    session.hydrated = true;
+    session.watcher.start().await;
+}
+
+/// Publish a batch's messages one by one, or as a single `MessagesAppended` when
+/// there are so many that per-message events would flood clients.
+fn send_new_messages(
+    event_tx: &mpsc::UnboundedSender<ProviderEvent>,
+    session_id: &str,
+    messages: Vec<AgentMessage>,
+) {
+    if messages.len() > MESSAGE_BATCH_THRESHOLD {
+        let _ = event_tx.send(ProviderEvent::MessagesAppended {
+            session_id: session_id.to_string(),
+            count: messages.len() as u32,
+            latest_timestamp: messages
+                .last()
+                .map(|m| m.timestamp.clone())
+                .unwrap_or_default(),
+        });
+        return;
+    }
+    for message in messages {
+        let _ = event_tx.send(ProviderEvent::NewMessage {
+            session_id: session_id.to_string(),
+            message,
+        });
+    }
+}
+
+/// Link sessions in `project` that continue an earlier conversation to their predecessor.
+/// References that can't be resolved yet stay pending, since the predecessor's log may
+/// not have been read yet.
+fn link_resumed_sessions(
+    sessions: &mut HashMap<String, TrackedSession>,
+    event_tx: &mpsc::UnboundedSender<ProviderEvent>,
+    project: &str,
+) {
+    let pending: Vec<String> = sessions
+        .iter()
+        .filter(|(_, s)| {
+            s.discovery_project_path == project
+                && s.summary.resumed_from.is_none()
+                && !s.resume.pending().is_empty()
+        })
+        .map(|(id, _)| id.clone())
+        .collect();
+
+    for session_id in pending {
+        let predecessor = {
+            let candidates: Vec<(&str, &ResumeTracker)> = sessions
+                .iter()
+                .filter(|(id, s)| {
+                    s.discovery_project_path == project
+                        && !chain_contains(sessions, id, &session_id)
+                })
+                .map(|(id, s)| (id.as_str(), &s.resume))
+                .collect();
+            resolve_predecessor(sessions[&session_id].resume.pending(), &candidates)
+                .map(str::to_string)
+        };
+        let (Some(predecessor), Some(session)) = (predecessor, sessions.get_mut(&session_id))
+        else {
+            continue;
+        };
+        session.summary.resumed_from = Some(predecessor);
+        session.resume.clear_pending();
+        if session.emitted {
+            let _ = event_tx.send(ProviderEvent::SessionUpdated {
+                session: session.summary.clone(),
+            });
+        }
+    }
+}
+
+/// Whether following `resumed_from` links from `start` (inclusive) reaches `target`.
+fn chain_contains(sessions: &HashMap<String, TrackedSession>, start: &str, target: &str) -> bool {
+    let mut seen = HashSet::new();
+    let mut current = Some(start);
+    while let Some(id) = current {
+        if id == target {
+            return true;
+        }
+        if !seen.insert(id) {
+            return false;
+        }
+        current = sessions
+            .get(id)
+            .and_then(|s| s.summary.resumed_from.as_deref());
+    }
+    false
+}
+
+/// The oldest known session of the chain `session_id` belongs to.
+fn chain_root<'a>(sessions: &'a HashMap<String, TrackedSession>, session_id: &'a str) -> &'a str {
+    let mut seen = HashSet::new();
+    let mut current = session_id;
+    while seen.insert(current) {
+        match sessions
+            .get(current)
+            .and_then(|s| s.summary.resumed_from.as_deref())
+        {
+            Some(prev) if sessions.contains_key(prev) => current = prev,
+            _ => break,
+        }
+    }
+    current
+}
+
+/// Run a git status check for one session and publish the result if it changed.
+/// Clears the session's in-flight flag when done. A session whose working directory
+/// isn't on this machine is marked `remote-or-container` and git isn't run until the
+/// directory appears.
+fn spawn_git_check(
+    sessions: Arc<RwLock<HashMap<String, TrackedSession>>>,
+    event_tx: mpsc::UnboundedSender<ProviderEvent>,
+    session_id: String,
+    working_directory: String,
+    backend: GitBackend,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let missing = environment::working_directory_missing(&working_directory).await;
+        let result = if missing {
+            environment::suppress_git_check();
+            None
+        } else {
+            fetch_git_status(backend, &working_directory).await
+        };
+        let mut sessions = sessions.write().await;
+        let Some(session) = sessions.get_mut(&session_id) else {
+            return;
+        };
+        session.git_check_in_flight = false;
+        let environment = missing.then(|| REMOTE_OR_CONTAINER.to_string());
+        if session.summary.environment != environment {
+            if missing {
+                info!(
+                    "[Git] {} isn't on this machine; skipping git checks for {} until it appears",
+                    working_directory, session_id
+                );
+            }
+            session.summary.environment = environment;
+            if session.emitted {
+                let _ = event_tx.send(ProviderEvent::SessionUpdated {
+                    session: session.summary.clone(),
+                });
+            }
+        }
+        if let Some(mut git_status) = result {
+            if git_status.branch.is_empty() {
+                git_status.branch = session.summary.git_status.branch.clone();
+            } else {
+                session.branch_from_git = true;
+            }
+            if session.summary.git_status != git_status {
+                session.summary.git_status = git_status;
+                let _ = event_tx.send(ProviderEvent::GitStatusUpdated {
+                    session_id: session_id.clone(),
+                    git_status: session.summary.git_status.clone(),
+                });
+            }
+        }
+    })
+}
+
+/// Messages a session keeps in memory, unless configured otherwise.
+pub const DEFAULT_MAX_MESSAGES: usize = 500;
+/// Memory all sessions' messages may take together, unless configured otherwise.
+pub const DEFAULT_TRANSCRIPT_MEMORY_BUDGET: u64 = 512 * 1024 * 1024;
+/// What a message takes besides its text and metadata, roughly.
+const MESSAGE_OVERHEAD_BYTES: u64 = 256;
+/// Messages a session still watching its log keeps when it's shed to save memory.
+const SHED_KEEP_MESSAGES: usize = 50;
+/// Most of a log re-read for the messages of a dehydrated session.
+const REREAD_MAX_BYTES: u64 = 8 * 1024 * 1024;
+/// Usage points included inline in session details, enough for a sparkline.
+const DETAIL_USAGE_POINTS: usize = 60;
+const TIMER_INTERVAL: Duration = Duration::from_secs(3);
+const PROCESS_CHECK_INTERVAL_MS: i64 = 10_000;
+const STOPPED_INDEX_TTL_MS: i64 = 60 * 60_000;
+const DEFAULT_SUPERSEDE_GRACE: Duration = Duration::from_secs(60);
+const DEFAULT_DEHYDRATE_AFTER: Duration = Duration::from_secs(10 * 60);
+/// Sessions loaded by one history request when none are named, newest first.
+const HISTORY_MAX_SESSIONS: usize = 20;
+/// How long a historical session stays loaded.
+const HISTORY_TTL_MS: i64 = 30 * 60_000;
+/// How far an entry may be behind the newest one read before it counts as late.
+/// Entries written at about the same moment are logged a little out of order.
+const LATE_ENTRY_TOLERANCE_MS: i64 = 5_000;
+/// How long a session whose log was deleted is kept before it's removed.
+const LOG_DELETED_TTL_MS: i64 = 5 * 60_000;
+/// `stop_reason` of a session whose log was deleted while it was watched.
+pub const LOG_DELETED: &str = "log_deleted";
+/// Entries of an old log handled per lock, so loading it doesn't stall everything else.
+const HISTORY_BATCH_ENTRIES: usize = 1_000;
+/// Percentages of the context window at which a `ContextWarning` is sent.
+pub const DEFAULT_CONTEXT_WARNING_PERCENTS: &[u32] = &[80, 95];
+/// Names the machine when its host name can't be read.
+pub const DEFAULT_HOST: &str = "localhost";
+/// Batches with more new messages than this are announced with one `MessagesAppended`.
+const MESSAGE_BATCH_THRESHOLD: usize = 50;
+/// Tools listed in a session summary's `top_tools`.
+const TOP_TOOLS: usize = 5;
+/// How often a session's activity is re-evaluated, besides on state changes.
+const CLASSIFY_INTERVAL_MS: i64 = 60_000;
+pub const DEFAULT_GIT_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+/// Running sessions change constantly; check less often to limit subprocess churn.
+pub const DEFAULT_GIT_CHECK_INTERVAL_RUNNING: Duration = Duration::from_secs(60);
+
+/// `after_suspend` marks the first tick after the machine woke up: logs may have
+/// been written without the watcher noticing yet, so each session's silence is
+/// re-based on its log's mtime and time-based transitions wait for the next tick.
+async fn check_timers(
+    sessions: &Arc<RwLock<HashMap<String, TrackedSession>>>,
+    event_tx: &mpsc::UnboundedSender<ProviderEvent>,
+    dehydrated: &DehydratedSessions,
+    process_lookup: Option<ProcessLookup>,
+    after_suspend: bool,
+) {
+    // Collect git diff targets and process check candidates while holding the lock
+    let mut git_diff_targets: Vec<(String, String, GitBackend)> = Vec::new();
+    // (session_id, directories) for sessions whose process should be looked for
+    let mut process_check_candidates: Vec<(String, [String; 2])> = Vec::new();
+    // (session_id, log, last read) for watchers that haven't read in a while
+    let mut stalled_watchers: Vec<(String, PathBuf, i64)> = Vec::new();
+
+    {
+        let mut sessions = sessions.write().await;
+        let now_ms = chrono::Utc::now().timestamp_millis();
+
+        // Historical sessions are dropped once they've been around for a while.
+        let expired: Vec<String> = sessions
+            .iter()
+            .filter(|(_, s)| s.evict_at.is_some_and(|at| now_ms >= at))
+            .map(|(id, _)| id.clone())
+            .collect();
+        for session_id in expired {
+            dehydrated.lock().unwrap().remove(&session_id);
+            if let Some(session) = sessions.remove(&session_id) {
+                if session.emitted {
+                    let _ = event_tx.send(ProviderEvent::SessionRemoved { session_id });
+                }
+            }
+        }
+
+        for (session_id, session) in sessions.iter_mut() {
+            if !session.emitted {
+                continue;
+            }
+            if after_suspend {
+                session.refresh_activity_from_log();
+            }
+
+            // Sessions due for a process check get their transitions once it's done.
+            let probe_due = process_lookup.is_some()
+                && !session.summary.replay
+                && session.is_active()
+                && (now_ms - session.last_process_check) > PROCESS_CHECK_INTERVAL_MS;
+            if probe_due {
+                session.last_process_check = now_ms;
+                process_check_candidates.push((
+                    session_id.clone(),
+                    [
+                        session.summary.working_directory.clone(),
+                        session.summary.current_cwd.clone(),
+                    ],
+                ));
+            }
+
+            // State transitions
+            let changed = !probe_due
+                && !after_suspend
+                && session.apply_time_transitions(session_id, event_tx);
+
+            // A newer session in the same project has taken over and this one went quiet.
+            if let Some(at) = session.superseded_at {
+                let last_write = session.state_ctx.last_entry_timestamp.max(at);
+                let grace_ms = session.settings().supersede_grace.as_millis() as i64;
+                if session.is_active() && now_ms - last_write > grace_ms {
+                    session.mark_stopped(session_id, event_tx);
+                }
+            }
+
+            if (changed || now_ms - session.last_classified_at >= CLASSIFY_INTERVAL_MS)
+                && session.classify_activity(now_ms)
+            {
+                let _ = event_tx.send(ProviderEvent::SessionUpdated {
+                    session: session.summary.clone(),
+                });
+            }
+
+            // Let the spend rate decay while the session is quiet.
+            if session.refresh_spend_rate(now_ms) && session.emitted {
+                let _ = event_tx.send(session.usage_event());
+            }
+
+            let state = session.state_ctx.state;
+
+            // Long-stopped sessions are rarely searched; don't hold their index.
+            if state == AgentStateType::Stopped
+                && !session.content_index.is_empty()
+                && now_ms - session.state_ctx.last_activity_at > STOPPED_INDEX_TTL_MS
+            {
+                session.content_index.clear();
+            }
+
+            // Nor are their messages read often; stop watching the log until it grows.
+            // Measured from when the session stopped (or last wrote, if it wrote since),
+            // not from its last activity: a session that timed out has been quiet for
+            // half an hour by the time it stops.
+            session.stopped_at = match state {
+                AgentStateType::Stopped => Some(session.stopped_at.unwrap_or(now_ms)),
+                _ => None,
+            };
+            let dehydrate_after = session.settings().dehydrate_after.as_millis() as i64;
+            let stopped_for = session
+                .stopped_at
+                .map(|at| now_ms - at.max(session.state_ctx.last_activity_at));
+            if session.hydrated
+                && !session.summary.replay
+                && stopped_for.is_some_and(|ms| ms >= dehydrate_after)
+            {
+                session.dehydrate();
+                dehydrated
+                    .lock()
+                    .unwrap()
+                    .insert(session_id.clone(), std::time::SystemTime::now());
+            }
+
+            // A watcher that hasn't read in a while is only a problem if the log grew;
+            // that's checked below, without the lock.
+            match session.watcher.stalled_since(now_ms) {
+                Some(since) if session.hydrated && !session.watcher_stall_reported => {
+                    let path = session.watcher.file_path().to_path_buf();
+                    stalled_watchers.push((session_id.clone(), path, since));
+                }
+                Some(_) => {}
+                None => session.watcher_stall_reported = false,
+            }
+
+            // Check if git diff is needed. Entering Idle means a turn just finished,
+            // so check right away.
+            let entered_idle = changed && state == AgentStateType::Idle;
+            let settings = session.settings();
+            let git_interval = match state {
+                AgentStateType::Idle | AgentStateType::PermissionWaiting => {
+                    Some(settings.git_check_interval)
+                }
+                AgentStateType::Running => Some(settings.git_check_interval_running),
+                _ => None,
+            };
+            if let Some(interval) = git_interval {
+                let interval = interval.as_millis() as i64;
+                if entered_idle || (now_ms - session.last_git_diff_check) > interval {
+                    if let Some(wd) = session.begin_git_check(now_ms) {
+                        git_diff_targets.push((session_id.clone(), wd, settings.git_backend));
+                    }
+                }
+            }
+        }
+
+        let budget = sessions
+            .values()
+            .map(|s| s.settings().transcript_memory_budget)
+            .min()
+            .unwrap_or(0);
+        shed_transcripts(&mut sessions, dehydrated, budget);
+    }
+
+    // Lock released — list processes, then run the held-back transitions with the
+    // result so a session whose CLI is gone stops within this tick.
+    if let (Some(lookup), false) = (process_lookup, process_check_candidates.is_empty()) {
+        let active_cwds = tokio::task::spawn_blocking(lookup).await.ok().flatten();
+        let mut sessions = sessions.write().await;
+        for (session_id, dirs) in &process_check_candidates {
+            if let Some(session) = sessions.get_mut(session_id) {
+                // A failed lookup leaves liveness unknown rather than as last seen.
+                session.state_ctx.process_alive = active_cwds
+                    .as_ref()
+                    .map(|cwds| dirs.iter().any(|d| cwds.contains(d)));
+                session.apply_time_transitions(session_id, event_tx);
+            }
+        }
+    }
+
+    // Lock released — look at when the quiet watchers' logs were last written.
+    let mut grown = Vec::new();
+    for (session_id, path, since) in stalled_watchers {
+        let modified = tokio::fs::metadata(&path).await.and_then(|m| m.modified());
+        let Ok(modified) = modified else { continue };
+        let modified_ms = chrono::DateTime::<chrono::Utc>::from(modified).timestamp_millis();
+        if modified_ms > since {
+            grown.push((session_id, path));
+        }
+    }
+    if !grown.is_empty() {
+        let mut sessions = sessions.write().await;
+        for (session_id, path) in grown {
+            if let Some(session) = sessions.get_mut(&session_id) {
+                warn!(
+                    "[Watcher] {} changed but hasn't been read for {}",
+                    path.display(),
+                    session_id
+                );
+                session.watcher_stall_reported = true;
+                let metadata = HashMap::from([
+                    ("level".to_string(), serde_json::json!("warning")),
+                    ("watcherStalled".to_string(), serde_json::json!(true)),
+                ]);
+                session.record_message(
+                    MessageType::Error,
+                    "The dashboard stopped reading this session's log while it was still \
+                     being written; updates may be missing"
+                        .to_string(),
+                    metadata,
+                    event_tx,
+                );
+            }
+        }
+    }
+
+    // Lock released — run git checks in the background. The in-flight flag keeps
+    // slow repositories from piling up processes across ticks.
+    for (session_id, wd, backend) in git_diff_targets {
+        spawn_git_check(sessions.clone(), event_tx.clone(), session_id, wd, backend);
+    }
+}
+
+/// Times a session's messages were dropped to stay within the transcript memory
+/// budget, and how many messages that dropped, since the server started.
+static SHEDS: AtomicU64 = AtomicU64::new(0);
+static SHED_MESSAGES: AtomicU64 = AtomicU64::new(0);
+
+/// Drop messages until all sessions' together fit in `budget` bytes, largest sessions
+/// first. Stopped sessions go first and are dehydrated, as if they had been stopped
+/// for a while; then idle ones, then those still working, which keep watching their
+/// logs and only their latest `SHED_KEEP_MESSAGES` messages.
+fn shed_transcripts(
+    sessions: &mut HashMap<String, TrackedSession>,
+    dehydrated: &DehydratedSessions,
+    budget: u64,
+) {
+    let mut retained: u64 = sessions.values().map(|s| s.retained_bytes).sum();
+    if budget == 0 || retained <= budget {
+        return;
+    }
+    let rank = |state: AgentStateType| match state {
+        AgentStateType::Stopped => 0,
+        AgentStateType::Idle | AgentStateType::Error => 1,
+        AgentStateType::Running | AgentStateType::PermissionWaiting => 2,
+    };
+    let mut order: Vec<(u8, u64, String)> = sessions
+        .iter()
+        .filter(|(_, s)| s.retained_bytes > 0)
+        .map(|(id, s)| (rank(s.state_ctx.state), s.retained_bytes, id.clone()))
+        .collect();
+    order.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+
+    for (_, _, session_id) in order {
+        if retained <= budget {
+            break;
+        }
+        let Some(session) = sessions.get_mut(&session_id) else {
+            continue;
+        };
+        let (bytes, count) = (session.retained_bytes, session.messages.len());
+        if session.state_ctx.state == AgentStateType::Stopped && !session.summary.replay {
+            session.dehydrate();
+            dehydrated
+                .lock()
+                .unwrap()
+                .insert(session_id.clone(), std::time::SystemTime::now());
+        } else {
+            session.drop_oldest_messages(count.saturating_sub(SHED_KEEP_MESSAGES));
+        }
+        let dropped = count - session.messages.len();
+        if dropped == 0 {
+            continue;
+        }
+        retained -= bytes - session.retained_bytes;
+        SHEDS.fetch_add(1, Ordering::Relaxed);
+        SHED_MESSAGES.fetch_add(dropped as u64, Ordering::Relaxed);
+        info!(
+            "[Session] {}: dropped {} messages to stay within the transcript memory budget",
+            session_id, dropped
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clock::{Clock, ManualClock};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    async fn insert_test_session(
+        provider: &ClaudeCodeProvider,
+        session_id: &str,
+        state: AgentStateType,
+    ) {
+        let (entries_tx, _entries_rx) = mpsc::unbounded_channel();
+        let mut state_ctx = StateContext::new();
+        state_ctx.state = state;
+        let summary = AgentSessionSummary {
+            session_id: session_id.to_string(),
+            state,
+            ..Default::default()
+        };
+        let watcher = FileWatcher::new(format!("/tmp/{}.jsonl", session_id).into(), entries_tx);
+        let mut tracked = TrackedSession::new(
+            summary,
+            watcher,
+            "/tmp/project".to_string(),
+            SharedSettings::default(),
+        );
+        tracked.state_ctx = state_ctx;
+        tracked.model = "claude-sonnet-4-20250514".to_string();
+        tracked.emitted = true;
+        provider
+            .sessions
+            .write()
+            .await
+            .insert(session_id.to_string(), tracked);
+    }
+
+    #[tokio::test]
+    async fn test_dismiss_session_removes_and_emits() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::Idle).await;
+
+        assert_eq!(provider.dismiss_session("s1").await, Ok(()));
+        assert!(!provider.has_session("s1").await);
+        assert!(provider.dismissed.lock().unwrap().contains_key("s1"));
+        match rx.try_recv() {
+            Ok(ProviderEvent::SessionRemoved { session_id }) => assert_eq!(session_id, "s1"),
+            other => panic!("Expected SessionRemoved, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_begin_git_check_skips_in_flight() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::Running).await;
+        let mut sessions = provider.sessions.write().await;
+        let session = sessions.get_mut("s1").unwrap();
+
+        assert_eq!(session.begin_git_check(1_000), None, "no working directory yet");
+        session.summary.working_directory = "/tmp/project".to_string();
+        assert_eq!(session.begin_git_check(1_000).as_deref(), Some("/tmp/project"));
+        assert_eq!(session.last_git_diff_check, 1_000);
+        assert_eq!(session.begin_git_check(2_000), None);
+        session.git_check_in_flight = false;
+        assert!(session.begin_git_check(3_000).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_git_checks_suppressed_while_directory_missing() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::Idle).await;
+        let dir =
+            std::env::temp_dir().join(format!("agents-dashboard-remote-{}", uuid::Uuid::new_v4()));
+        let wd = dir.to_str().unwrap().to_string();
+        let check = |wd: &str| {
+            spawn_git_check(
+                provider.sessions.clone(),
+                provider.event_tx.clone(),
+                "s1".to_string(),
+                wd.to_string(),
+                GitBackend::Cli,
+            )
+        };
+
+        let before = environment::suppressed_git_checks();
+        check(&wd).await.unwrap();
+        check(&wd).await.unwrap();
+        assert!(environment::suppressed_git_checks() >= before + 2);
+        let summary = provider.sessions.read().await["s1"].summary.clone();
+        assert_eq!(summary.environment.as_deref(), Some(REMOTE_OR_CONTAINER));
+        assert!(!provider.sessions.read().await["s1"].git_check_in_flight);
+        // Announced once, not on every check.
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(ProviderEvent::SessionUpdated { session }) if session.environment.is_some()
+        ));
+        assert!(rx.try_recv().is_err());
+
+        std::fs::create_dir_all(&dir).unwrap();
+        check(&wd).await.unwrap();
+        assert_eq!(
+            provider.sessions.read().await["s1"].summary.environment,
+            None
+        );
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(ProviderEvent::SessionUpdated { session }) if session.environment.is_none()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_dismiss_unknown_session() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        assert_eq!(
+            provider.dismiss_session("nope").await,
+            Err(DismissError::NotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missing_cwd_marks_the_session_before_any_git_check() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::Idle).await;
+        let gone =
+            std::env::temp_dir().join(format!("agents-dashboard-remote-{}", uuid::Uuid::new_v4()));
+        let cwd = gone.to_str().unwrap().to_string();
+        {
+            let mut sessions = provider.sessions.write().await;
+            let session = sessions.get_mut("s1").unwrap();
+            session.project_root_known = false;
+            session.discovery_project_path = cwd.clone();
+        }
+        let entry = serde_json::json!({
+            "type": "user",
+            "uuid": "u1",
+            "cwd": cwd,
+            "message": {"role": "user", "content": "hi"}
+        });
+        let entries = vec![jsonl_parser::parse_jsonl_line(&entry.to_string()).unwrap()];
+        handle_entries(&provider.sessions, &provider.event_tx, "s1", entries).await;
+
+        let summary = provider.sessions.read().await["s1"].summary.clone();
+        assert_eq!(summary.working_directory, cwd);
+        assert_eq!(summary.environment.as_deref(), Some(REMOTE_OR_CONTAINER));
+    }
+
+    #[tokio::test]
+    async fn test_dismiss_running_session_refused() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::Running).await;
+        assert_eq!(
+            provider.dismiss_session("s1").await,
+            Err(DismissError::Running)
+        );
+        assert!(provider.has_session("s1").await);
+    }
+
+    #[tokio::test]
+    async fn test_resumed_session_linked_once_predecessor_is_read() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "old", AgentStateType::Stopped).await;
+        insert_test_session(&provider, "new", AgentStateType::Idle).await;
+        let line = |sid: &str, uuid: &str, parent: &str, day: u32| {
+            jsonl_parser::parse_jsonl_line(&format!(
+                r#"{{"type":"user","message":{{"role":"user","content":"hi"}},"uuid":"{uuid}","parentUuid":{parent},"sessionId":"{sid}","timestamp":"2025-01-0{day}T00:00:00Z"}}"#
+            ))
+            .unwrap()
+        };
+
+        // The newer log is read first; its reference can't be resolved yet.
+        handle_entries(
+            &provider.sessions,
+            &provider.event_tx,
+            "new",
+            vec![line("new", "n1", "\"o2\"", 2)],
+        )
+        .await;
+        assert_eq!(
+            provider.sessions.read().await["new"].summary.resumed_from,
+            None
+        );
+
+        handle_entries(
+            &provider.sessions,
+            &provider.event_tx,
+            "old",
+            vec![line("old", "o1", "null", 1), line("old", "o2", "\"o1\"", 1)],
+        )
+        .await;
+        let chain = provider.get_session_chain("new").await.unwrap();
+        let ids: Vec<&str> = chain.iter().map(|s| s.session_id.as_str()).collect();
+        assert_eq!(ids, ["old", "new"]);
+        assert_eq!(chain[1].resumed_from.as_deref(), Some("old"));
+        assert_eq!(provider.get_session_chain("old").await.unwrap().len(), 2);
+
+        let mut updated = false;
+        while let Ok(event) = rx.try_recv() {
+            if let ProviderEvent::SessionUpdated { session } = event {
+                updated |= session.session_id == "new";
+            }
+        }
+        assert!(updated);
+    }
+
+    #[tokio::test]
+    async fn test_search_finds_text_beyond_truncation() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::Idle).await;
+        let output = format!(
+            "{}\nthread 'main' panicked at src/lib.rs",
+            "compiling...\n".repeat(40)
+        );
+        let entry = jsonl_parser::parse_jsonl_line(
+            &serde_json::json!({
+                "type": "user",
+                "uuid": "u1",
+                "message": {
+                    "role": "user",
+                    "content": [{"type": "tool_result", "tool_use_id": "t1", "content": output}]
+                }
+            })
+            .to_string(),
+        )
+        .unwrap();
+        handle_entries(&provider.sessions, &provider.event_tx, "s1", vec![entry]).await;
+
+        let results = provider
+            .search_sessions(
+                &SearchQuery::parse(r#""Panicked At""#, false),
+                &[SearchScope::Content],
+            )
+            .await;
+        assert_eq!(results.len(), 1);
+        assert!(results[0].matches[0].truncated_source);
+        assert!(results[0].matches[0].content.starts_with("compiling"));
+
+        let results = provider
+            .search_sessions(
+                &SearchQuery::parse("compiling", false),
+                &[SearchScope::Content],
+            )
+            .await;
+        assert!(!results[0].matches[0].truncated_source);
+    }
+
+    #[tokio::test]
+    async fn test_tracks_latest_cli_version() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::Idle).await;
+        let line = |version: &str| {
+            jsonl_parser::parse_jsonl_line(
+                &serde_json::json!({
+                    "type": "user",
+                    "uuid": format!("u-{version}"),
+                    "version": version,
+                    "message": {"role": "user", "content": "hi"}
+                })
+                .to_string(),
+            )
+            .unwrap()
+        };
+        handle_entries(
+            &provider.sessions,
+            &provider.event_tx,
+            "s1",
+            vec![line("2.0.9")],
+        )
+        .await;
+        handle_entries(
+            &provider.sessions,
+            &provider.event_tx,
+            "s1",
+            vec![line("2.0.14")],
+        )
+        .await;
+
+        let sessions = provider.get_sessions().await;
+        assert_eq!(sessions[0].cli_version.as_deref(), Some("2.0.14"));
+    }
+
+    #[tokio::test]
+    async fn test_reprocessed_entry_is_ignored() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::Idle).await;
+        let entry = || {
+            jsonl_parser::parse_jsonl_line(
+                &serde_json::json!({
+                    "type": "assistant",
+                    "uuid": "a1",
+                    "message": {
+                        "model": "claude-sonnet-4-20250514",
+                        "content": [
+                            {"type": "text", "text": "Reading the file"},
+                            {"type": "tool_use", "id": "t1", "name": "Read", "input": {}}
+                        ],
+                        "usage": {"input_tokens": 100, "output_tokens": 20}
+                    }
+                })
+                .to_string(),
+            )
+            .unwrap()
+        };
+
+        handle_entries(&provider.sessions, &provider.event_tx, "s1", vec![entry()]).await;
+        handle_entries(&provider.sessions, &provider.event_tx, "s1", vec![entry()]).await;
+
+        let messages = provider.get_session_messages("s1").await.unwrap();
+        let ids: Vec<&str> = messages.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, ["a1:0", "a1:1"]);
+        let sessions = provider.get_sessions().await;
+        assert_eq!(sessions[0].cumulative_usage.input_tokens, 100);
+    }
+
+    #[tokio::test]
+    async fn test_late_entries_ordered_without_moving_state() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::Stopped).await;
+        let parse = |v: serde_json::Value| jsonl_parser::parse_jsonl_line(&v.to_string()).unwrap();
+        let prompt = |uuid: &str, text: &str, at: &str| {
+            parse(serde_json::json!({
+                "type": "user",
+                "uuid": uuid,
+                "timestamp": at,
+                "message": {"role": "user", "content": text}
+            }))
+        };
+        let reply = |uuid: &str, text: &str, at: &str| {
+            parse(serde_json::json!({
+                "type": "assistant",
+                "uuid": uuid,
+                "timestamp": at,
+                "message": {
+                    "model": "claude-sonnet-4-20250514",
+                    "content": [{"type": "text", "text": text}],
+                    "usage": {"input_tokens": 100, "output_tokens": 20}
+                }
+            }))
+        };
+        let done = parse(serde_json::json!({
+            "type": "system",
+            "subtype": "turn_duration",
+            "timestamp": "2025-01-01T10:00:20Z",
+            "durationMs": 20000
+        }));
+        let feed = |entries: Vec<RawEntry>| {
+            handle_entries(&provider.sessions, &provider.event_tx, "s1", entries)
+        };
+
+        feed(vec![
+            prompt("u1", "fix the build", "2025-01-01T10:00:00Z"),
+            reply("a1", "Fixed.", "2025-01-01T10:00:10Z"),
+            done,
+        ])
+        .await;
+        // A backfill delivers an earlier turn, twice, mixed in with a live entry
+        // written a second before the last one.
+        feed(vec![
+            prompt("u0", "run the tests", "2025-01-01T09:59:00Z"),
+            reply("a2", "Still here.", "2025-01-01T10:00:19Z"),
+            reply("a0", "Tests pass.", "2025-01-01T09:59:30Z"),
+            prompt("u0", "run the tests", "2025-01-01T09:59:00Z"),
+        ])
+        .await;
+
+        let summary = provider.get_sessions().await.remove(0);
+        assert_eq!(summary.state, AgentStateType::Idle);
+        assert_eq!(summary.last_activity_at, "2025-01-01T10:00:20.000Z");
+        assert_eq!(summary.latest_task, "fix the build");
+        // Usage from every entry still counts, duplicates once.
+        assert_eq!(summary.cumulative_usage.input_tokens, 300);
+
+        let messages = provider.get_session_messages("s1").await.unwrap();
+        let texts: Vec<&AgentMessage> = messages
+            .iter()
+            .filter(|m| m.msg_type == MessageType::Text)
+            .map(|m| m.as_ref())
+            .collect();
+        assert_eq!(
+            texts.iter().map(|m| &*m.content).collect::<Vec<_>>(),
+            [
+                "run the tests",
+                "Tests pass.",
+                "fix the build",
+                "Fixed.",
+                "Still here."
+            ]
+        );
+        assert!(texts.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+
+        // Search still finds the full text of messages stored after the inserts.
+        let output = format!(
+            "{}\nthread 'main' panicked at src/lib.rs",
+            "compiling...\n".repeat(40)
+        );
+        feed(vec![parse(serde_json::json!({
+            "type": "user",
+            "uuid": "u2",
+            "timestamp": "2025-01-01T10:01:00Z",
+            "message": {
+                "role": "user",
+                "content": [{"type": "tool_result", "tool_use_id": "t1", "content": output}]
+            }
+        }))])
+        .await;
+        let results = provider
+            .search_sessions(
+                &SearchQuery::parse(r#""Panicked At""#, false),
+                &[SearchScope::Content],
+            )
+            .await;
+        assert_eq!(results[0].matches.len(), 1);
+        assert!(results[0].matches[0].content.starts_with("compiling"));
+    }
+
+    #[tokio::test]
+    async fn test_synthetic_ids_are_unique_across_sessions_and_restarts() {
+        let parse = |v: serde_json::Value| jsonl_parser::parse_jsonl_line(&v.to_string()).unwrap();
+        // Neither entry has a uuid, so both get synthetic ids.
+        let entries = || {
+            vec![
+                parse(serde_json::json!({
+                    "type": "user",
+                    "message": {"role": "user", "content": "run the tests"}
+                })),
+                parse(serde_json::json!({
+                    "type": "system",
+                    "subtype": "turn_duration",
+                    "durationMs": 1200
+                })),
+            ]
+        };
+        let read_all = |provider: ClaudeCodeProvider| async move {
+            let mut ids = Vec::new();
+            for sid in ["s1", "s2"] {
+                insert_test_session(&provider, sid, AgentStateType::Idle).await;
+                handle_entries(&provider.sessions, &provider.event_tx, sid, entries()).await;
+                let messages = provider.get_session_messages(sid).await.unwrap();
+                ids.extend(messages.iter().map(|m| m.id.clone()));
+            }
+            ids
+        };
+
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let before = read_all(ClaudeCodeProvider::new(tx)).await;
+        let unique: HashSet<&String> = before.iter().collect();
+        assert_eq!(before.len(), 4);
+        assert_eq!(unique.len(), 4);
+
+        // A restarted server reads the same logs and hands out the same ids.
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let after = read_all(ClaudeCodeProvider::new(tx)).await;
+        assert_eq!(before, after);
+        assert_eq!(before[0], "s1:0:0");
+    }
+
+    #[tokio::test]
+    async fn test_replay_feeds_normal_pipeline() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        let log = [
+            serde_json::json!({
+                "type": "user",
+                "uuid": "u1",
+                "timestamp": "2025-06-01T10:00:00Z",
+                "cwd": "/work/demo",
+                "message": {"role": "user", "content": "fix the build"}
+            }),
+            serde_json::json!({
+                "type": "assistant",
+                "uuid": "a1",
+                "timestamp": "2025-06-01T10:00:05Z",
+                "message": {
+                    "model": "claude-sonnet-4-20250514",
+                    "content": [{"type": "text", "text": "Done."}],
+                    "usage": {"input_tokens": 10, "output_tokens": 5}
+                }
+            }),
+        ]
+        .map(|v| v.to_string())
+        .join("\n");
+
+        let session_id = provider
+            .start_replay(replay::plan_replay(&log, 1_000.0))
+            .await;
+        assert!(session_id.starts_with("replay-"));
+
+        let mut discovered = None;
+        let mut saw_message = false;
+        let mut saw_usage = false;
+        while !(discovered.is_some() && saw_message && saw_usage) {
+            let event = tokio::time::timeout(std::time::Duration::from_secs(5), rx.recv())
+                .await
+                .expect("replay events")
+                .unwrap();
+            assert_eq!(event.session_id(), session_id);
+            match event {
+                ProviderEvent::SessionDiscovered { session } => discovered = Some(session),
+                ProviderEvent::NewMessage { .. } => saw_message = true,
+                ProviderEvent::UsageUpdated { .. } => saw_usage = true,
+                _ => {}
+            }
+        }
+        let discovered = discovered.unwrap();
+        assert!(discovered.replay);
+        assert_eq!(discovered.project_name, "demo");
+
+        assert!(!provider.stop_replay("s-unknown").await);
+        assert!(provider.stop_replay(&session_id).await);
+        assert!(!provider.has_session(&session_id).await);
+        let removed = std::iter::from_fn(|| rx.try_recv().ok())
+            .any(|e| matches!(e, ProviderEvent::SessionRemoved { .. }));
+        assert!(removed);
+    }
+
+    #[tokio::test]
+    async fn test_context_usage_from_latest_turn() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::Idle).await;
+        let turn = |uuid: &str, model: &str, input: u64, cache_read: u64, sidechain: bool| {
+            let entry = serde_json::json!({
+                "type": "assistant",
+                "uuid": uuid,
+                "isSidechain": sidechain,
+                "message": {
+                    "model": model,
+                    "content": [{"type": "text", "text": "ok"}],
+                    "usage": {
+                        "input_tokens": input,
+                        "output_tokens": 10,
+                        "cache_read_input_tokens": cache_read,
+                        "cache_creation_input_tokens": 1_000
+                    }
+                }
+            });
+            jsonl_parser::parse_jsonl_line(&entry.to_string()).unwrap()
+        };
+        let context = |provider: &ClaudeCodeProvider| {
+            let provider = provider.sessions.clone();
+            async move {
+                let sessions = provider.read().await;
+                let summary = &sessions["s1"].summary;
+                (summary.context_tokens, summary.context_limit)
+            }
+        };
+
+        let sonnet = "claude-sonnet-4-20250514";
+        handle_entries(
+            &provider.sessions,
+            &provider.event_tx,
+            "s1",
+            vec![
+                turn("a1", sonnet, 2_000, 20_000, false),
+                turn("a2", sonnet, 3_000, 140_000, false),
+            ],
+        )
+        .await;
+        // The latest prompt, not the sum over turns.
+        assert_eq!(context(&provider).await, (144_000, Some(200_000)));
+        let event = std::iter::from_fn(|| rx.try_recv().ok())
+            .find(|e| matches!(e, ProviderEvent::UsageUpdated { .. }))
+            .unwrap();
+        assert!(matches!(
+            event,
+            ProviderEvent::UsageUpdated {
+                context_tokens: 144_000,
+                context_limit: Some(200_000),
+                ..
+            }
+        ));
+
+        // A subagent's prompt says nothing about the main context.
+        handle_entries(
+            &provider.sessions,
+            &provider.event_tx,
+            "s1",
+            vec![turn("a3", sonnet, 500, 0, true)],
+        )
+        .await;
+        assert_eq!(context(&provider).await, (144_000, Some(200_000)));
+
+        let opus = "claude-opus-4-1-20250805";
+        handle_entries(
+            &provider.sessions,
+            &provider.event_tx,
+            "s1",
+            vec![turn("a4", opus, 1_000, 9_000, false)],
+        )
+        .await;
+        assert_eq!(context(&provider).await, (11_000, Some(200_000)));
+
+        // Past 200k only fits in the 1M context.
+        handle_entries(
+            &provider.sessions,
+            &provider.event_tx,
+            "s1",
+            vec![turn("a5", sonnet, 1_000, 400_000, false)],
+        )
+        .await;
+        assert_eq!(context(&provider).await, (402_000, Some(1_000_000)));
+        handle_entries(
+            &provider.sessions,
+            &provider.event_tx,
+            "s1",
+            vec![turn("a6", sonnet, 1_000, 50_000, false)],
+        )
+        .await;
+        assert_eq!(context(&provider).await, (52_000, Some(1_000_000)));
+
+        handle_entries(
+            &provider.sessions,
+            &provider.event_tx,
+            "s1",
+            vec![turn("a7", "gpt-5", 1_000, 0, false)],
+        )
+        .await;
+        assert_eq!(context(&provider).await, (2_000, None));
+    }
+
+    #[tokio::test]
+    async fn test_context_warnings_once_per_threshold_until_compacted() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::Running).await;
+        let turn = |uuid: &str, context: u64| {
+            let entry = serde_json::json!({
+                "type": "assistant",
+                "uuid": uuid,
+                "message": {
+                    "model": "claude-sonnet-4-20250514",
+                    "content": [{"type": "text", "text": "ok"}],
+                    "usage": {
+                        "input_tokens": 1_000,
+                        "output_tokens": 10,
+                        "cache_read_input_tokens": context - 1_000
+                    }
+                }
+            });
+            jsonl_parser::parse_jsonl_line(&entry.to_string()).unwrap()
+        };
+        let compaction = jsonl_parser::parse_jsonl_line(
+            r#"{"type":"system","subtype":"compact_boundary","content":"Conversation compacted"}"#,
+        )
+        .unwrap();
+        let batches = vec![
+            // How full a log already is when it's first read is not news.
+            vec![turn("a0", 196_000)],
+            vec![turn("a1", 90_000)],
+            vec![turn("a2", 162_000)],
+            vec![turn("a3", 170_000)],
+            vec![turn("a4", 192_000)],
+            vec![turn("a5", 196_000)],
+            // Compaction starts a new round of warnings.
+            vec![compaction, turn("a6", 150_000)],
+            vec![turn("a7", 164_000)],
+            // So does a prompt that shrank by half; one warning for both thresholds.
+            vec![turn("a8", 40_000)],
+            vec![turn("a9", 194_000)],
+        ];
+
+        let mut warnings = Vec::new();
+        for entries in batches {
+            handle_entries(&provider.sessions, &provider.event_tx, "s1", entries).await;
+            while let Ok(event) = rx.try_recv() {
+                if let ProviderEvent::ContextWarning {
+                    percent_used,
+                    threshold,
+                    ..
+                } = event
+                {
+                    warnings.push((threshold, percent_used));
+                }
+            }
+        }
+        assert_eq!(warnings, [(80, 81), (95, 96), (80, 82), (95, 97)]);
+    }
+
+    #[tokio::test]
+    async fn test_tool_result_links_to_its_call() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::Running).await;
+        let parse = |entry: serde_json::Value| {
+            jsonl_parser::parse_jsonl_line(&entry.to_string()).unwrap()
+        };
+        let call = parse(serde_json::json!({
+            "type": "assistant", "uuid": "a1", "parentUuid": "u1",
+            "message": {"content": [
+                {"type": "text", "text": "Checking."},
+                {"type": "tool_use", "id": "t1", "name": "Bash", "input": {"command": "ls"}}
+            ]}
+        }));
+        let result = parse(serde_json::json!({
+            "type": "user", "uuid": "u2", "parentUuid": "a1",
+            "message": {"role": "user", "content": [
+                {"type": "tool_result", "tool_use_id": "t1", "content": "src"},
+                {"type": "tool_result", "tool_use_id": "t0", "content": "orphan"}
+            ]}
+        }));
+        handle_entries(&provider.sessions, &provider.event_tx, "s1", vec![call]).await;
+        handle_entries(&provider.sessions, &provider.event_tx, "s1", vec![result]).await;
+
+        let sessions = provider.sessions.read().await;
+        let messages = &sessions["s1"].messages;
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[1].id, "a1:1");
+        assert_eq!(messages[1].parent_id.as_deref(), Some("u1"));
+        assert_eq!(messages[2].parent_id.as_deref(), Some("a1"));
+        assert_eq!(messages[2].tool_use_message_id.as_deref(), Some("a1:1"));
+        assert_eq!(messages[3].tool_use_message_id, None);
+    }
+
+    #[tokio::test]
+    async fn test_tool_stats_from_entry_stream() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::Idle).await;
+        let parse = |v: serde_json::Value| jsonl_parser::parse_jsonl_line(&v.to_string()).unwrap();
+        let call = |uuid: &str, id: &str, name: &str| {
+            parse(serde_json::json!({
+                "type": "assistant",
+                "uuid": uuid,
+                "message": {
+                    "model": "claude-sonnet-4-20250514",
+                    "content": [{"type": "tool_use", "id": id, "name": name, "input": {}}]
+                }
+            }))
+        };
+        let result = |uuid: &str, id: &str, output: &str, is_error: bool| {
+            parse(serde_json::json!({
+                "type": "user",
+                "uuid": uuid,
+                "message": {
+                    "role": "user",
+                    "content": [{
+                        "type": "tool_result",
+                        "tool_use_id": id,
+                        "content": output,
+                        "is_error": is_error
+                    }]
+                }
+            }))
+        };
+        let long_output = "x".repeat(1000);
+        let entries = vec![
+            call("a1", "t1", "Bash"),
+            result("u1", "t1", &long_output, false),
+            call("a2", "t2", "Edit"),
+            result("u2", "t2", "old_string not found", true),
+            call("a3", "t3", "Bash"),
+            result("u3", "t3", "ok", false),
+        ];
+        handle_entries(&provider.sessions, &provider.event_tx, "s1", entries).await;
+
+        let stats = provider.get_tool_stats("s1").await.unwrap();
+        let bash = &stats.tools[0];
+        assert_eq!(bash.name, "Bash");
+        assert_eq!((bash.count, bash.error_count), (2, 0));
+        // Counted before the stored copy is truncated.
+        assert_eq!(bash.output_bytes, 1002);
+        let edit = &stats.tools[1];
+        assert_eq!(
+            (edit.name.as_str(), edit.count, edit.error_count),
+            ("Edit", 1, 1)
+        );
+
+        let sessions = provider.get_sessions().await;
+        let top: Vec<(&str, u64)> = sessions[0]
+            .top_tools
+            .iter()
+            .map(|t| (t.name.as_str(), t.count))
+            .collect();
+        assert_eq!(top, [("Bash", 2), ("Edit", 1)]);
+    }
+
+    #[tokio::test]
+    async fn test_sources_from_entry_stream() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::Idle).await;
+        let parse = |v: serde_json::Value| jsonl_parser::parse_jsonl_line(&v.to_string()).unwrap();
+        let call = |uuid: &str, id: &str, name: &str, input: serde_json::Value| {
+            parse(serde_json::json!({
+                "type": "assistant",
+                "uuid": uuid,
+                "message": {
+                    "model": "claude-sonnet-4-20250514",
+                    "content": [{"type": "tool_use", "id": id, "name": name, "input": input}]
+                }
+            }))
+        };
+        let result = |uuid: &str, id: &str, output: &str, is_error: bool| {
+            parse(serde_json::json!({
+                "type": "user",
+                "uuid": uuid,
+                "message": {
+                    "role": "user",
+                    "content": [{
+                        "type": "tool_result",
+                        "tool_use_id": id,
+                        "content": output,
+                        "is_error": is_error
+                    }]
+                }
+            }))
+        };
+        let page = serde_json::json!({"url": "https://example.com/docs", "prompt": "summarize"});
+        let entries = vec![
+            call("a1", "t1", "WebSearch", serde_json::json!({"query": "axum routing"})),
+            result("u1", "t1", &"r".repeat(1000), false),
+            call("a2", "t2", "WebFetch", page.clone()),
+            result("u2", "t2", "404", true),
+            call("a3", "t3", "Read", serde_json::json!({"file_path": "/tmp/a"})),
+            result("u3", "t3", "ok", false),
+            call("a4", "t4", "WebFetch", page),
+            result("u4", "t4", "<html></html>", false),
+        ];
+        handle_entries(&provider.sessions, &provider.event_tx, "s1", entries).await;
+
+        let sources = provider.get_sources("s1").await.unwrap().sources;
+        assert_eq!(sources.len(), 2);
+        assert_eq!(sources[0].query.as_deref(), Some("axum routing"));
+        // Counted before the stored copy is truncated.
+        assert_eq!(sources[0].result_bytes, Some(1000));
+        assert_eq!(sources[1].url.as_deref(), Some("https://example.com/docs"));
+        assert_eq!(
+            (sources[1].result_bytes, sources[1].is_error),
+            (Some(13), false)
+        );
+        assert_eq!(provider.get_sessions().await[0].source_count, 2);
+        assert!(provider.get_sources("missing").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_activity_from_entry_stream() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::Idle).await;
+        let parse = |v: serde_json::Value| jsonl_parser::parse_jsonl_line(&v.to_string()).unwrap();
+        let prompt = parse(serde_json::json!({
+            "type": "user", "uuid": "u0",
+            "message": {"role": "user", "content": "Rename the config loader"}
+        }));
+        let call = |n: usize, name: &str| {
+            parse(serde_json::json!({
+                "type": "assistant", "uuid": format!("a{}", n),
+                "message": {
+                    "model": "claude-sonnet-4-20250514",
+                    "content": [{"type": "tool_use", "id": format!("t{}", n), "name": name, "input": {}}]
+                }
+            }))
+        };
+        let reads: Vec<_> = (1..=5).map(|n| call(n, "Read")).collect();
+        handle_entries(
+            &provider.sessions,
+            &provider.event_tx,
+            "s1",
+            [vec![prompt], reads].concat(),
+        )
+        .await;
+        let activity = |sessions: Vec<AgentSessionSummary>| sessions[0].activity_type.clone();
+        assert_eq!(
+            activity(provider.get_sessions().await).as_deref(),
+            Some("research")
+        );
+
+        // Re-evaluated on the next state change once files are being edited.
+        handle_entries(
+            &provider.sessions,
+            &provider.event_tx,
+            "s1",
+            vec![call(6, "Edit")],
+        )
+        .await;
+        handle_entries(
+            &provider.sessions,
+            &provider.event_tx,
+            "s1",
+            vec![parse(serde_json::json!({
+                "type": "system", "subtype": "turn_duration", "durationMs": 1000
+            }))],
+        )
+        .await;
+        assert_eq!(
+            activity(provider.get_sessions().await).as_deref(),
+            Some("refactor")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hook_errors_counted() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::Idle).await;
+        let entries = jsonl_parser::parse_jsonl_chunk(include_str!("testdata/hooks.jsonl")).entries;
+        let (before_block, rest) = entries.split_at(4);
+        handle_entries(
+            &provider.sessions,
+            &provider.event_tx,
+            "s1",
+            before_block.to_vec(),
+        )
+        .await;
+        let session = provider.get_sessions().await.remove(0);
+        assert_eq!(session.hook_errors, 1);
+        assert_eq!(session.state, AgentStateType::Error);
+        let mut updated = false;
+        while let Ok(event) = rx.try_recv() {
+            updated |= matches!(event, ProviderEvent::SessionUpdated { session } if session.hook_errors == 1);
+        }
+        assert!(updated);
+
+        handle_entries(&provider.sessions, &provider.event_tx, "s1", rest.to_vec()).await;
+        assert_eq!(provider.get_sessions().await[0].hook_errors, 3);
+        let detail = provider.get_session_detail("s1").await.unwrap();
+        let hook_messages = detail
+            .messages
+            .iter()
+            .filter(|m| m.content.starts_with("Hook "))
+            .count();
+        assert_eq!(hook_messages, 3);
+    }
+
+    #[tokio::test]
+    async fn test_failed_turns_and_retries_counted() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::Idle).await;
+        let entries =
+            jsonl_parser::parse_jsonl_chunk(include_str!("testdata/retried_turn.jsonl")).entries;
+        handle_entries(&provider.sessions, &provider.event_tx, "s1", entries).await;
+
+        let session = provider.get_sessions().await.remove(0);
+        assert_eq!(session.failed_turns, 1);
+        // Two retries logged, and a response logged twice.
+        assert_eq!(session.retry_count, 3);
+        let mut updated = false;
+        while let Ok(event) = rx.try_recv() {
+            updated |= matches!(event, ProviderEvent::SessionUpdated { session } if session.retry_count == 3);
+        }
+        assert!(updated);
+
+        let detail = provider.get_session_detail("s1").await.unwrap();
+        let errors: Vec<&str> = detail
+            .messages
+            .iter()
+            .filter(|m| m.msg_type == MessageType::Error)
+            .map(|m| &*m.content)
+            .collect();
+        assert_eq!(errors.len(), 3);
+        assert!(errors[2].starts_with("API Error: 529"));
+    }
+
+    #[tokio::test]
+    async fn test_permission_mode_from_entry_stream() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::Idle).await;
+        let entries =
+            jsonl_parser::parse_jsonl_chunk(include_str!("testdata/permission_modes.jsonl"))
+                .entries;
+        let (planning, rest) = entries.split_at(5);
+        handle_entries(
+            &provider.sessions,
+            &provider.event_tx,
+            "s1",
+            planning.to_vec(),
+        )
+        .await;
+        // Entered plan mode, then a rejected plan kept it there.
+        let session = provider.get_sessions().await.remove(0);
+        assert_eq!(session.permission_mode.as_deref(), Some("plan"));
+        let mut updated = false;
+        while let Ok(event) = rx.try_recv() {
+            updated |= matches!(event, ProviderEvent::SessionUpdated { session } if session.permission_mode.as_deref() == Some("plan"));
+        }
+        assert!(updated);
+
+        handle_entries(&provider.sessions, &provider.event_tx, "s1", rest.to_vec()).await;
+        let session = provider.get_sessions().await.remove(0);
+        assert_eq!(
+            session.permission_mode.as_deref(),
+            Some("bypassPermissions")
+        );
+        let detail = provider.get_session_detail("s1").await.unwrap();
+        let changes: Vec<_> = detail
+            .messages
+            .iter()
+            .filter(|m| {
+                m.msg_type == MessageType::StateChange && m.content.starts_with("Permission mode")
+            })
+            .map(|m| &*m.content)
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                "Permission mode: default → plan",
+                "Permission mode: plan → default",
+                "Permission mode: default → bypassPermissions",
+            ]
+        );
+        assert!(
+            provider.sessions.read().await["s1"]
+                .state_ctx
+                .bypass_permissions
+        );
+    }
+
+    #[tokio::test]
+    async fn test_model_switches_are_announced() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::Idle).await;
+        let assistant = |uuid: &str, model: &str, sidechain: bool| {
+            format!(
+                r#"{{"type":"assistant","uuid":"{}","isSidechain":{},"timestamp":"2025-01-01T10:00:0{}.000Z","message":{{"model":"{}","content":[{{"type":"text","text":"ok"}}],"usage":{{"input_tokens":1000000,"output_tokens":0}}}}}}"#,
+                uuid,
+                sidechain,
+                uuid.len(),
+                model
+            )
+        };
+        let log = [
+            assistant("a", "claude-sonnet-4-20250514", false),
+            // A subagent on another model doesn't switch the session.
+            assistant("bb", "claude-3-5-haiku-20241022", true),
+            assistant("ccc", "<synthetic>", false),
+            assistant("dddd", "claude-opus-4-20250514", false),
+        ]
+        .map(|line| line + "\n")
+        .concat();
+        let entries = jsonl_parser::parse_jsonl_chunk(&log).entries;
+        handle_entries(&provider.sessions, &provider.event_tx, "s1", entries).await;
+
+        let mut changes = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            if let ProviderEvent::ModelChanged {
+                previous, current, ..
+            } = event
+            {
+                changes.push((previous, current));
+            }
+        }
+        assert_eq!(
+            changes,
+            [(
+                "claude-sonnet-4-20250514".to_string(),
+                "claude-opus-4-20250514".to_string()
+            )]
+        );
+        let detail = provider.get_session_detail("s1").await.unwrap();
+        assert_eq!(detail.summary.model, "claude-opus-4-20250514");
+        let history: Vec<&str> = detail
+            .model_history
+            .iter()
+            .map(|s| s.model.as_str())
+            .collect();
+        assert_eq!(
+            history,
+            ["claude-sonnet-4-20250514", "claude-opus-4-20250514"]
+        );
+        assert_eq!(
+            detail.model_history[1].timestamp,
+            "2025-01-01T10:00:04.000Z"
+        );
+        assert!(detail
+            .messages
+            .iter()
+            .any(|m| m.msg_type == MessageType::StateChange
+                && &*m.content
+                    == "Model changed: claude-sonnet-4-20250514 → claude-opus-4-20250514"));
+    }
+
+    #[tokio::test]
+    async fn test_message_counts_outlive_trimming() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::Idle).await;
+        let rounds = DEFAULT_MAX_MESSAGES;
+        let log: String = (0..rounds)
+            .map(|i| {
+                let call = serde_json::json!({
+                    "type": "assistant",
+                    "uuid": format!("a{i}"),
+                    "timestamp": "2025-01-01T00:00:00Z",
+                    "message": {
+                        "model": "claude-sonnet-4-20250514",
+                        "content": [{"type": "tool_use", "id": format!("toolu_{i}"), "name": "Bash", "input": {"command": "ls"}}]
+                    }
+                });
+                let result = serde_json::json!({
+                    "type": "user",
+                    "uuid": format!("u{i}"),
+                    "timestamp": "2025-01-01T00:00:01Z",
+                    "message": {
+                        "role": "user",
+                        "content": [{"type": "tool_result", "tool_use_id": format!("toolu_{i}"), "content": "ok"}]
+                    }
+                });
+                format!("{}\n{}\n", call, result)
+            })
+            .collect();
+        let entries = jsonl_parser::parse_jsonl_chunk(&log).entries;
+        handle_entries(&provider.sessions, &provider.event_tx, "s1", entries).await;
+
+        let session = provider.get_sessions().await.remove(0);
+        assert_eq!(session.message_count, 2 * rounds as u64);
+        assert_eq!(session.tool_call_count, rounds as u64);
+        let detail = provider.get_session_detail("s1").await.unwrap();
+        assert!(detail.messages.len() < DEFAULT_MAX_MESSAGES);
+        let json = serde_json::to_value(&session).unwrap();
+        assert_eq!(json["messageCount"], 2 * rounds);
+        assert_eq!(json["toolCallCount"], rounds);
+    }
+
+    #[tokio::test]
+    async fn test_pending_tool_is_redacted_before_it_is_shortened() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::PermissionWaiting).await;
+        // The secret straddles the length a summary is shortened to.
+        let command = format!("{} sk-abcdefghijklmnopqrstuvwxyz", "x".repeat(180));
+        let mut sessions = provider.sessions.write().await;
+        let session = sessions.get_mut("s1").unwrap();
+        session.state_ctx.pending_tool = Some(crate::types::PendingTool {
+            tool_use_id: "toolu_1".to_string(),
+            name: "Bash".to_string(),
+            summary: state_machine::summarize_tool_input(
+                "Bash",
+                &serde_json::json!({ "command": command }),
+            ),
+        });
+        session.sync_state();
+
+        let summary = &session.summary.pending_tool.as_ref().unwrap().summary;
+        assert!(summary.ends_with("sk-a…wxyz"), "{summary}");
+        assert!(!summary.contains("sk-abcdefgh"));
+    }
+
+    #[tokio::test]
+    async fn test_repeated_tool_results_share_their_text() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::Idle).await;
+        let output = "test result: ok. 42 passed; 0 failed; 0 ignored\n".repeat(4);
+        let log: String = (0..3)
+            .map(|i| {
+                let result = serde_json::json!({
+                    "type": "user",
+                    "uuid": format!("u{i}"),
+                    "timestamp": "2025-01-01T00:00:01Z",
+                    "message": {
+                        "role": "user",
+                        "content": [{"type": "tool_result", "tool_use_id": format!("toolu_{i}"), "content": output}]
+                    }
+                });
+                format!("{}\n", result)
+            })
+            .collect();
+        let entries = jsonl_parser::parse_jsonl_chunk(&log).entries;
+        handle_entries(&provider.sessions, &provider.event_tx, "s1", entries).await;
+
+        let detail = provider.get_session_detail("s1").await.unwrap();
+        let results: Vec<_> = detail
+            .messages
+            .iter()
+            .filter(|m| m.msg_type == MessageType::ToolResult)
+            .collect();
+        assert_eq!(results.len(), 3);
+        assert!(Arc::ptr_eq(&results[0].content, &results[2].content));
+        // Serialized as before.
+        let json = serde_json::to_value(results[1]).unwrap();
+        assert_eq!(json["content"], output);
+
+        // The shared text counts toward the session's memory once, until its last
+        // message is dropped.
+        let mut sessions = provider.sessions.write().await;
+        let session = sessions.get_mut("s1").unwrap();
+        let separate: u64 = session.messages.iter().map(|m| message_bytes(m, true)).sum();
+        assert_eq!(session.retained_bytes, separate - 2 * output.len() as u64);
+        let count = session.messages.len();
+        session.drop_oldest_messages(count);
+        assert_eq!(session.retained_bytes, 0);
+    }
+
+    #[tokio::test]
+    async fn test_dashboard_stats() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        let parse = |v: serde_json::Value| jsonl_parser::parse_jsonl_line(&v.to_string()).unwrap();
+        let prompt = |uuid: &str, ts: &str| {
+            parse(serde_json::json!({
+                "type": "user", "uuid": uuid, "timestamp": ts,
+                "message": {"role": "user", "content": "fix the failing build"}
+            }))
+        };
+        let reply = |uuid: &str, ts: &str, input: u64, content: serde_json::Value| {
+            parse(serde_json::json!({
+                "type": "assistant", "uuid": uuid, "timestamp": ts,
+                "message": {
+                    "model": "claude-sonnet-4-20250514",
+                    "content": content,
+                    "usage": {"input_tokens": input, "output_tokens": input / 10}
+                }
+            }))
+        };
+        let turn_done = |ts: &str| {
+            parse(serde_json::json!({
+                "type": "system", "subtype": "turn_duration", "timestamp": ts, "durationMs": 1000
+            }))
+        };
+
+        // Monday and Wednesday of the current week.
+        insert_test_session(&provider, "s1", AgentStateType::Idle).await;
+        let bash =
+            serde_json::json!([{"type": "tool_use", "id": "t1", "name": "Bash", "input": {}}]);
+        let text = serde_json::json!([{"type": "text", "text": "Fixed."}]);
+        let entries = vec![
+            prompt("u1", "2025-06-09T10:00:00Z"),
+            reply("a1", "2025-06-09T10:01:00Z", 1000, bash),
+            parse(serde_json::json!({
+                "type": "user", "uuid": "u2", "timestamp": "2025-06-09T10:02:00Z",
+                "message": {"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "t1", "content": "ok"}
+                ]}
+            })),
+            turn_done("2025-06-09T10:03:00Z"),
+            prompt("u3", "2025-06-11T09:00:00Z"),
+            reply("a2", "2025-06-11T09:01:00Z", 2000, text.clone()),
+            turn_done("2025-06-11T09:02:00Z"),
+        ];
+        handle_entries(&provider.sessions, &provider.event_tx, "s1", entries).await;
+        // The week before.
+        insert_test_session(&provider, "s2", AgentStateType::Idle).await;
+        let entries = vec![
+            prompt("u4", "2025-06-05T10:00:00Z"),
+            reply("a3", "2025-06-05T10:01:00Z", 500, text),
+            turn_done("2025-06-05T10:02:00Z"),
+        ];
+        handle_entries(&provider.sessions, &provider.event_tx, "s2", entries).await;
+        insert_test_session(&provider, "s3", AgentStateType::Running).await;
+        {
+            let mut sessions = provider.sessions.write().await;
+            sessions.get_mut("s1").unwrap().summary.project_name = "alpha".into();
+            sessions.get_mut("s2").unwrap().summary.project_name = "beta".into();
+        }
+
+        let now = "2025-06-11T12:00:00Z".parse().unwrap();
+        let stats = provider.dashboard_stats(now).await;
+        assert_eq!(stats.total_sessions, 3);
+        assert_eq!(stats.active_sessions, 1);
+        assert_eq!(stats.total_tokens, 1100 + 2200 + 550);
+        assert_eq!(stats.busiest_project.as_deref(), Some("alpha"));
+        assert_eq!(stats.total_turns, 3);
+        assert_eq!(stats.tool_calls, 1);
+        assert_eq!(stats.tools[0].name, "Bash");
+        // s1 ran 47h02m, s2 2m; s3 has no activity yet.
+        assert_eq!(
+            stats.average_session_duration_ms,
+            (169_320_000 + 120_000) / 2
+        );
+
+        let sessions = provider.get_sessions().await;
+        let cost_of = |id: &str| {
+            sessions
+                .iter()
+                .find(|s| s.session_id == id)
+                .unwrap()
+                .cumulative_usage
+                .estimated_cost
+        };
+        assert!((stats.cost_this_week - cost_of("s1")).abs() < 1e-9);
+        assert!(stats.cost_today > 0.0 && stats.cost_today < stats.cost_this_week);
+    }
+
+    #[tokio::test]
+    async fn test_latest_task_follows_prompts() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::Idle).await;
+        let parse = |v: serde_json::Value| jsonl_parser::parse_jsonl_line(&v.to_string()).unwrap();
+        let prompt = |content: serde_json::Value| {
+            parse(serde_json::json!({
+                "type": "user",
+                "message": {"role": "user", "content": content}
+            }))
+        };
+
+        let entries = vec![prompt(serde_json::json!("fix the login redirect"))];
+        handle_entries(&provider.sessions, &provider.event_tx, "s1", entries).await;
+        let session = &provider.get_sessions().await[0];
+        assert_eq!(session.initial_task, "fix the login redirect");
+        assert_eq!(session.latest_task, "fix the login redirect");
+
+        let entries = vec![
+            prompt(serde_json::json!("now add a test for the logout flow")),
+            prompt(serde_json::json!(
+                [{"type": "tool_result", "tool_use_id": "t1", "content": "ok"}]
+            )),
+            prompt(serde_json::json!(
+                "<command-name>/clear</command-name><command-args></command-args>"
+            )),
+            parse(serde_json::json!({
+                "type": "user",
+                "isSidechain": true,
+                "message": {"role": "user", "content": "Search the repo for logout handlers"}
+            })),
+        ];
+        handle_entries(&provider.sessions, &provider.event_tx, "s1", entries).await;
+        let session = &provider.get_sessions().await[0];
+        assert_eq!(session.initial_task, "fix the login redirect");
+        assert_eq!(session.latest_task, "now add a test for the logout flow");
+
+        let search = |raw: &'static str| {
+            let provider = &provider;
+            async move {
+                let query = SearchQuery::parse(raw, false);
+                let results = provider
+                    .search_sessions(&query, &[SearchScope::CurrentTask])
+                    .await;
+                results
+                    .first()
+                    .map(|r| r.matches.iter().map(|m| m.task).collect::<Vec<_>>())
+                    .unwrap_or_default()
+            }
+        };
+        assert_eq!(search("login").await, [Some(TaskPrompt::Initial)]);
+        assert_eq!(search("logout").await, [Some(TaskPrompt::Latest)]);
+        assert!(search("handlers").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_latest_assistant_text_until_next_prompt() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::Idle).await;
+        let parse = |v: serde_json::Value| jsonl_parser::parse_jsonl_line(&v.to_string()).unwrap();
+        let prompt = |content: serde_json::Value| {
+            parse(serde_json::json!({
+                "type": "user",
+                "message": {"role": "user", "content": content}
+            }))
+        };
+        let reply = |content: serde_json::Value, sidechain: bool| {
+            parse(serde_json::json!({
+                "type": "assistant",
+                "isSidechain": sidechain,
+                "message": {"model": "claude-sonnet-4-20250514", "content": content}
+            }))
+        };
+        let entries = vec![
+            prompt(serde_json::json!("fix the login redirect")),
+            reply(
+                serde_json::json!([{"type": "text", "text": "**Fixed** the `redirect_to` check."}]),
+                false,
+            ),
+            reply(
+                serde_json::json!([{"type": "tool_use", "id": "t1", "name": "Bash", "input": {}}]),
+                false,
+            ),
+            prompt(serde_json::json!(
+                [{"type": "tool_result", "tool_use_id": "t1", "content": "ok"}]
+            )),
+            reply(
+                serde_json::json!([{"type": "text", "text": "Subagent findings"}]),
+                true,
+            ),
+        ];
+        handle_entries(&provider.sessions, &provider.event_tx, "s1", entries).await;
+        let session = &provider.get_sessions().await[0];
+        assert_eq!(
+            session.latest_assistant_text.as_deref(),
+            Some("Fixed the redirect_to check.")
+        );
+
+        let entries = vec![prompt(serde_json::json!("now the logout flow"))];
+        handle_entries(&provider.sessions, &provider.event_tx, "s1", entries).await;
+        let session = &provider.get_sessions().await[0];
+        assert_eq!(session.latest_assistant_text, None);
+    }
+
+    #[tokio::test]
+    async fn test_title_from_slug_then_summary() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::Idle).await;
+        let parse = |v: serde_json::Value| jsonl_parser::parse_jsonl_line(&v.to_string()).unwrap();
+        let user = |uuid: &str, slug: &str| {
+            parse(serde_json::json!({
+                "type": "user",
+                "uuid": uuid,
+                "slug": slug,
+                "message": {"role": "user", "content": "please look at the flaky watcher test"}
+            }))
+        };
+        let summary = parse(serde_json::json!({
+            "type": "summary",
+            "summary": "Fix flaky file watcher test",
+            "leafUuid": "u1"
+        }));
+
+        let entries = vec![user("u1", "jaunty-kettle")];
+        handle_entries(&provider.sessions, &provider.event_tx, "s1", entries).await;
+        let session = &provider.get_sessions().await[0];
+        assert_eq!(session.title.as_deref(), Some("jaunty-kettle"));
+        assert_eq!(
+            session.initial_task,
+            "please look at the flaky watcher test"
+        );
 
-        // Process state machine
-        let prev_state = session.state_ctx.state;
-        let result = process_entry(&mut session.state_ctx, entry);
+        let entries = vec![summary, user("u2", "other-slug")];
+        handle_entries(&provider.sessions, &provider.event_tx, "s1", entries).await;
+        let session = &provider.get_sessions().await[0];
+        assert_eq!(
+            session.title.as_deref(),
+            Some("Fix flaky file watcher test")
+        );
 
-        if result.changed {
-            session.summary.state = session.state_ctx.state;
-            session.summary.last_activity_at =
-                chrono::DateTime::from_timestamp_millis(session.state_ctx.last_entry_timestamp)
-                    .map(|dt| dt.to_rfc3339())
-                    .unwrap_or_else(|| chrono::Utc::now().to_rfc3339());
-            if session.emitted {
-                let _ = event_tx.send(ProviderEvent::StateChanged {
-                    session_id: session_id.to_string(),
-                    previous: prev_state,
-                    current: session.state_ctx.state,
-                });
+        let results = provider
+            .search_sessions(
+                &SearchQuery::parse("flaky watcher", false),
+                &[SearchScope::Title],
+            )
+            .await;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].matches[0].scope, SearchScope::Title);
+    }
+
+    #[tokio::test]
+    async fn test_cwd_moving_into_subdirectories_keeps_project() {
+        let base =
+            std::env::temp_dir().join(format!("agents-dashboard-cwd-{}", uuid::Uuid::new_v4()));
+        // What discovery decodes `<base>-agents-dashboard` to; it exists, so the
+        // project directory can only be confirmed by a matching cwd.
+        let decoded = base.join("agents/dashboard");
+        std::fs::create_dir_all(&decoded).unwrap();
+        let root = format!("{}/agents-dashboard", base.display());
+
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::Idle).await;
+        {
+            let mut sessions = provider.sessions.write().await;
+            let session = sessions.get_mut("s1").unwrap();
+            session.discovery_project_path = decoded.display().to_string();
+            session.summary.project_name = "dashboard".into();
+        }
+        let entry = |i: usize, cwd: &str| {
+            jsonl_parser::parse_jsonl_line(
+                &serde_json::json!({
+                    "type": "user",
+                    "uuid": format!("u{i}"),
+                    "cwd": cwd,
+                    "message": {"role": "user", "content": "hi"}
+                })
+                .to_string(),
+            )
+            .unwrap()
+        };
+        let cwds = [
+            format!("{root}/packages/backend"),
+            root.clone(),
+            format!("{root}/packages/backend"),
+            format!("{root}/packages/frontend"),
+        ];
+        for (i, cwd) in cwds.iter().enumerate() {
+            let entries = vec![entry(i, cwd)];
+            handle_entries(&provider.sessions, &provider.event_tx, "s1", entries).await;
+            let summary = &provider.get_sessions().await[0];
+            assert_eq!(&summary.current_cwd, cwd);
+            if i == 0 {
+                // Not the project directory: leave the decoded identity alone.
+                assert_eq!(summary.project_name, "dashboard");
+            } else {
+                assert_eq!(summary.project_name, "agents-dashboard");
+                assert_eq!(summary.project_path, root);
+                assert_eq!(summary.working_directory, root);
             }
         }
+        let sessions = provider.sessions.read().await;
+        assert_eq!(
+            sessions["s1"].discovery_project_path,
+            decoded.display().to_string()
+        );
+        drop(sessions);
+        let _ = std::fs::remove_dir_all(&base);
+    }
 
-        // Map to AgentMessages
-        let messages = map_entry(entry, session_id);
-        for msg in messages {
-            session.messages.push(msg.clone());
-            // Keep only last 500 messages
-            if session.messages.len() > 500 {
-                let drain_count = session.messages.len() - 400;
-                session.messages.drain(..drain_count);
-            }
-            if session.emitted {
-                let _ = event_tx.send(ProviderEvent::NewMessage {
-                    session_id: session_id.to_string(),
-                    message: msg,
-                });
+    #[tokio::test]
+    async fn test_new_session_only_stops_quiet_sessions_in_project() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        let now_ms = chrono::Utc::now().timestamp_millis();
+        for (id, quiet_ms) in [("quiet", 120_000), ("busy", 5_000), ("unread", 0)] {
+            insert_test_session(&provider, id, AgentStateType::Running).await;
+            if quiet_ms > 0 {
+                let mut sessions = provider.sessions.write().await;
+                sessions.get_mut(id).unwrap().state_ctx.last_entry_timestamp = now_ms - quiet_ms;
             }
         }
-    }
 
-    session.summary.last_activity_at =
-        chrono::DateTime::from_timestamp_millis(session.state_ctx.last_entry_timestamp)
-            .map(|dt| dt.to_rfc3339())
-            .unwrap_or_else(|| chrono::Utc::now().to_rfc3339());
+        let mut sessions = provider.sessions.write().await;
+        supersede_sessions(
+            &mut sessions,
+            &provider.event_tx,
+            "/tmp/project",
+            now_ms,
+            60_000,
+        );
+        assert_eq!(sessions["quiet"].summary.state, AgentStateType::Stopped);
+        assert_eq!(sessions["busy"].summary.state, AgentStateType::Running);
+        assert_eq!(sessions["busy"].superseded_at, Some(now_ms));
+        assert_eq!(sessions["unread"].summary.state, AgentStateType::Running);
+        drop(sessions);
 
-    // Run time-based check immediately after processing entries so that
-    // stale sessions (e.g. Idle for hours) get the correct state on startup
-    // instead of waiting for the next 3s timer tick.
-    let prev_state = session.state_ctx.state;
-    let result = check_time_based_transitions(&mut session.state_ctx);
-    if result.changed {
-        session.summary.state = session.state_ctx.state;
-        if session.emitted {
-            let _ = event_tx.send(ProviderEvent::StateChanged {
-                session_id: session_id.to_string(),
-                previous: prev_state,
-                current: session.state_ctx.state,
-            });
+        // The older session keeps writing: it runs in parallel.
+        let entry = jsonl_parser::parse_jsonl_line(
+            &serde_json::json!({
+                "type": "user",
+                "uuid": "u1",
+                "timestamp": chrono::DateTime::from_timestamp_millis(now_ms + 1_000)
+                    .unwrap()
+                    .to_rfc3339(),
+                "message": {"role": "user", "content": "keep going"}
+            })
+            .to_string(),
+        )
+        .unwrap();
+        handle_entries(&provider.sessions, &provider.event_tx, "busy", vec![entry]).await;
+        assert_eq!(provider.sessions.read().await["busy"].superseded_at, None);
+
+        // Old behavior: no grace period.
+        let mut sessions = provider.sessions.write().await;
+        supersede_sessions(&mut sessions, &provider.event_tx, "/tmp/project", now_ms, 0);
+        assert!(sessions
+            .values()
+            .all(|s| s.summary.state == AgentStateType::Stopped));
+    }
+
+    #[tokio::test]
+    async fn test_process_lookup_drives_stopped() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        for (id, quiet_secs) in [("crashed", 20), ("thinking", 60 * 60)] {
+            insert_test_session(&provider, id, AgentStateType::Idle).await;
+            let mut sessions = provider.sessions.write().await;
+            let session = sessions.get_mut(id).unwrap();
+            session.summary.working_directory = format!("/work/{id}");
+            let clock = Arc::new(ManualClock::new());
+            session.state_ctx = StateContext::with_clock(clock.clone());
+            session.state_ctx.state = AgentStateType::Idle;
+            session.state_ctx.mark_activity(clock.now_millis());
+            clock.advance(Duration::from_secs(quiet_secs));
         }
+
+        let lookup: ProcessLookup = || Some(HashSet::from(["/work/thinking".to_string()]));
+        check_timers(
+            &provider.sessions,
+            &provider.event_tx,
+            &provider.dehydrated,
+            Some(lookup),
+            false,
+        )
+        .await;
+
+        let sessions = provider.sessions.read().await;
+        assert_eq!(sessions["crashed"].summary.state, AgentStateType::Stopped);
+        // An hour of silence would normally time out; the process is still there.
+        assert_eq!(sessions["thinking"].summary.state, AgentStateType::Idle);
+        assert_eq!(sessions["thinking"].state_ctx.process_alive, Some(true));
+        drop(sessions);
+
+        // Processes can't be listed any more: the session times out as if never seen.
+        let mut sessions = provider.sessions.write().await;
+        sessions.get_mut("thinking").unwrap().last_process_check = 0;
+        drop(sessions);
+        let failed: ProcessLookup = || None;
+        check_timers(
+            &provider.sessions,
+            &provider.event_tx,
+            &provider.dehydrated,
+            Some(failed),
+            false,
+        )
+        .await;
+        let sessions = provider.sessions.read().await;
+        assert_eq!(sessions["thinking"].state_ctx.process_alive, None);
+        assert_eq!(sessions["thinking"].summary.state, AgentStateType::Stopped);
     }
-}
 
-async fn fetch_git_diff_stats(working_directory: &str) -> Option<(u64, u64)> {
-    let output = tokio::process::Command::new("git")
-        .args(["diff", "--shortstat"])
-        .current_dir(working_directory)
-        .output()
-        .await
-        .ok()?;
-    if !output.status.success() {
-        return None;
+    #[tokio::test]
+    async fn test_active_time_across_transition_paths() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "worker", AgentStateType::Idle).await;
+        let mut sessions = provider.sessions.write().await;
+        let session = sessions.get_mut("worker").unwrap();
+        let clock = Arc::new(ManualClock::new());
+        let start = chrono::DateTime::parse_from_rfc3339("2025-01-15T10:00:00Z")
+            .unwrap()
+            .timestamp_millis();
+        clock.jump_wall_clock(start - clock.now_millis());
+        session.state_ctx = StateContext::with_clock(clock.clone());
+        session.state_ctx.state = AgentStateType::Idle;
+
+        // An entry read `secs` into the session that leaves it in `state`.
+        let entry = |session: &mut TrackedSession, secs: i64, state| {
+            let at_ms = start + secs * 1000;
+            clock.advance(Duration::from_millis((at_ms - clock.now_millis()) as u64));
+            let prev = session.state_ctx.state;
+            session.state_ctx.last_entry_timestamp = at_ms;
+            session.state_ctx.mark_activity(at_ms);
+            session.state_ctx.state = state;
+            session.sync_state();
+            session.record_transition(prev);
+        };
+
+        entry(session, 0, AgentStateType::Running);
+        // More work in the same period, reported as a change: counted once.
+        entry(session, 60, AgentStateType::Running);
+        assert_eq!(session.summary.active_duration_ms, 60_000);
+        entry(session, 90, AgentStateType::Running);
+        session.state_ctx.last_assistant_tool_use = true;
+
+        // A timer moves it on, as of the last entry rather than when the timer fired.
+        clock.advance(Duration::from_secs(35));
+        assert!(session.apply_time_transitions("worker", &provider.event_tx));
+        assert_eq!(session.summary.state, AgentStateType::PermissionWaiting);
+        assert_eq!(session.summary.active_duration_ms, 90_000);
+
+        entry(session, 140, AgentStateType::Running);
+        entry(session, 170, AgentStateType::Running);
+        session.mark_stopped("worker", &provider.event_tx);
+        assert_eq!(session.summary.active_duration_ms, 120_000);
+        // Wall-clock time includes the 50s spent waiting for permission.
+        assert_eq!(clock.now_millis() - start, 170_000);
+        drop(sessions);
+
+        let now = chrono::DateTime::from_timestamp_millis(start + 3_600_000).unwrap();
+        let stats = provider.dashboard_stats(now).await;
+        assert_eq!(stats.active_duration_ms_today, 120_000);
+        let tomorrow = now + chrono::Duration::days(1);
+        let stats = provider.dashboard_stats(tomorrow).await;
+        assert_eq!(stats.active_duration_ms_today, 0);
     }
-    parse_shortstat(&String::from_utf8_lossy(&output.stdout))
-}
 
-fn parse_shortstat(output: &str) -> Option<(u64, u64)> {
-    let trimmed = output.trim();
-    if trimmed.is_empty() {
-        return Some((0, 0));
+    #[tokio::test]
+    async fn test_first_tick_after_suspend_is_a_grace_tick() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        let log = std::env::temp_dir().join(format!("{}.jsonl", uuid::Uuid::new_v4()));
+        std::fs::write(&log, "").unwrap();
+        let clock = Arc::new(ManualClock::new());
+        for (id, path) in [
+            ("written", log.clone()),
+            ("quiet", std::path::PathBuf::new()),
+        ] {
+            insert_test_session(&provider, id, AgentStateType::Idle).await;
+            let mut sessions = provider.sessions.write().await;
+            let session = sessions.get_mut(id).unwrap();
+            session.watcher = FileWatcher::new(path, mpsc::unbounded_channel().0);
+            session.state_ctx = StateContext::with_clock(clock.clone());
+            session.state_ctx.state = AgentStateType::Idle;
+            // Last entry read 40 minutes ago; the log was written to since.
+            session
+                .state_ctx
+                .mark_activity(clock.now_millis() - 40 * 60_000);
+        }
+
+        check_timers(
+            &provider.sessions,
+            &provider.event_tx,
+            &provider.dehydrated,
+            None,
+            true,
+        )
+        .await;
+        {
+            let sessions = provider.sessions.read().await;
+            assert!(sessions
+                .values()
+                .all(|s| s.summary.state == AgentStateType::Idle));
+        }
+
+        check_timers(
+            &provider.sessions,
+            &provider.event_tx,
+            &provider.dehydrated,
+            None,
+            false,
+        )
+        .await;
+        let sessions = provider.sessions.read().await;
+        assert_eq!(sessions["written"].summary.state, AgentStateType::Idle);
+        assert_eq!(sessions["quiet"].summary.state, AgentStateType::Stopped);
+        drop(sessions);
+        let _ = std::fs::remove_file(&log);
     }
-    let mut additions: u64 = 0;
-    let mut deletions: u64 = 0;
-    // " 3 files changed, 42 insertions(+), 10 deletions(-)"
-    for part in trimmed.split(',') {
-        let part = part.trim();
-        if part.contains("insertion") {
-            if let Some(n) = part.split_whitespace().next().and_then(|s| s.parse().ok()) {
-                additions = n;
-            }
-        } else if part.contains("deletion") {
-            if let Some(n) = part.split_whitespace().next().and_then(|s| s.parse().ok()) {
-                deletions = n;
+
+    #[tokio::test]
+    async fn test_stale_mtime_after_suspend_does_not_revive_a_session() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        let log = std::env::temp_dir().join(format!("{}.jsonl", uuid::Uuid::new_v4()));
+        std::fs::write(&log, "").unwrap();
+        let clock = Arc::new(ManualClock::new());
+        // Written 35 minutes ago: after the last entry read, but long before the wake-up.
+        let written_ms = clock.now_millis() - 35 * 60_000;
+        let written = std::time::UNIX_EPOCH + Duration::from_millis(written_ms as u64);
+        std::fs::File::options()
+            .write(true)
+            .open(&log)
+            .unwrap()
+            .set_modified(written)
+            .unwrap();
+        for (id, state) in [
+            ("idle", AgentStateType::Idle),
+            ("stopped", AgentStateType::Stopped),
+        ] {
+            insert_test_session(&provider, id, state).await;
+            let mut sessions = provider.sessions.write().await;
+            let session = sessions.get_mut(id).unwrap();
+            session.watcher = FileWatcher::new(log.clone(), mpsc::unbounded_channel().0);
+            session.state_ctx = StateContext::with_clock(clock.clone());
+            session.state_ctx.state = state;
+            session
+                .state_ctx
+                .mark_activity(clock.now_millis() - 40 * 60_000);
+        }
+
+        for after_suspend in [true, false] {
+            check_timers(
+                &provider.sessions,
+                &provider.event_tx,
+                &provider.dehydrated,
+                None,
+                after_suspend,
+            )
+            .await;
+            let sessions = provider.sessions.read().await;
+            assert!(sessions
+                .values()
+                .all(|s| s.summary.state != AgentStateType::Running));
+            assert_eq!(sessions["stopped"].summary.state, AgentStateType::Stopped);
+            assert_eq!(sessions["idle"].state_ctx.last_activity_at, written_ms);
+        }
+        // The mtime is still past the idle timeout, so the session times out as usual.
+        let sessions = provider.sessions.read().await;
+        assert_eq!(sessions["idle"].summary.state, AgentStateType::Stopped);
+        drop(sessions);
+        let _ = std::fs::remove_file(&log);
+    }
+
+    /// Handing out a full buffer shares its messages. Timed against copying them, as
+    /// was done before; `--nocapture` prints both.
+    #[tokio::test]
+    async fn test_message_retrieval_shares_buffer() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::Idle).await;
+        {
+            let mut sessions = provider.sessions.write().await;
+            let session = sessions.get_mut("s1").unwrap();
+            for i in 0..DEFAULT_MAX_MESSAGES {
+                let metadata = HashMap::from([
+                    ("toolName".to_string(), serde_json::json!("Bash")),
+                    (
+                        "input".to_string(),
+                        serde_json::json!({"command": "cargo test"}),
+                    ),
+                ]);
+                session.push_message(AgentMessage {
+                    id: format!("m{i}"),
+                    session_id: "s1".to_string(),
+                    timestamp: "2025-01-01T00:00:00Z".to_string(),
+                    role: MessageRole::Assistant,
+                    msg_type: MessageType::ToolUse,
+                    content: "output line\n".repeat(100).into(),
+                    metadata: Some(metadata),
+                    parent_id: Some(format!("u{i}")),
+                    tool_use_message_id: None,
+                });
             }
         }
+
+        // Both reads hand out the stored messages rather than copies of them.
+        let detail = provider.get_session_detail("s1").await.unwrap();
+        let sessions = provider.sessions.read().await;
+        let stored = &sessions["s1"].messages;
+        assert_eq!(detail.messages.len(), DEFAULT_MAX_MESSAGES);
+        assert!(detail.messages.iter().zip(stored).all(|(a, b)| Arc::ptr_eq(a, b)));
+
+        let messages = provider.get_session_messages("s1").await.unwrap();
+        assert!(messages.iter().zip(stored).all(|(a, b)| Arc::ptr_eq(a, b)));
+        // Serialized exactly as the messages themselves.
+        let plain: Vec<&AgentMessage> = stored.iter().map(|m| m.as_ref()).collect();
+        assert_eq!(
+            serde_json::to_string(&messages).unwrap(),
+            serde_json::to_string(&plain).unwrap()
+        );
     }
-    Some((additions, deletions))
-}
 
-/// Parse `ps -eo pid,comm` output and return PIDs of processes whose comm contains "claude".
-fn parse_ps_claude_pids(output: &str) -> Vec<u32> {
-    let mut pids = Vec::new();
-    for line in output.lines() {
-        let line = line.trim();
-        let mut parts = line.splitn(2, char::is_whitespace);
-        let pid_str = match parts.next() {
-            Some(s) => s.trim(),
-            None => continue,
+    #[tokio::test]
+    async fn test_historical_session_watched_when_written_then_evicted() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        let log = std::env::temp_dir().join(format!("{}.jsonl", uuid::Uuid::new_v4()));
+        let line = |uuid: &str, text: &str| {
+            format!(
+                "{}\n",
+                serde_json::json!({
+                    "type": "assistant",
+                    "uuid": uuid,
+                    "timestamp": "2025-01-01T00:00:00Z",
+                    "message": {
+                        "model": "claude-sonnet-4-20250514",
+                        "content": [{"type": "text", "text": text}]
+                    }
+                })
+            )
         };
-        let comm = match parts.next() {
-            Some(s) => s.trim(),
-            None => continue,
+        std::fs::write(&log, line("a1", "old")).unwrap();
+        let discovered = DiscoveredSession {
+            session_id: "s1".to_string(),
+            log_file: log.clone(),
+            project_path: "/work/demo".to_string(),
+            project_name: "demo".to_string(),
         };
-        if let Ok(pid) = pid_str.parse::<u32>() {
-            if comm.contains("claude") {
-                pids.push(pid);
-            }
+        let found = |mode| {
+            handle_session_found(
+                &provider.sessions,
+                &provider.event_tx,
+                &provider.startup,
+                discovered.clone(),
+                &provider.config,
+                mode,
+            )
+        };
+        found(LogMode::Historical).await;
+        {
+            let sessions = provider.sessions.read().await;
+            let session = &sessions["s1"];
+            assert!(session.summary.historical);
+            assert_eq!(session.state_ctx.state, AgentStateType::Stopped);
+            assert_eq!(session.messages.len(), 1);
+            assert!(session.watcher.health().is_none());
         }
-    }
-    pids
-}
 
-/// Parse `lsof -Fn` output and return the set of cwd paths.
-/// lsof -Fn output format: lines starting with 'p' (PID) and 'n' (name/path).
-fn parse_lsof_cwds(output: &str) -> HashSet<String> {
-    let mut cwds = HashSet::new();
-    for line in output.lines() {
-        if let Some(path) = line.strip_prefix('n') {
-            let path = path.trim();
-            if !path.is_empty() {
-                cwds.insert(path.to_string());
+        // Written to again: discovery finds it and it's watched from where it was read.
+        std::io::Write::write_all(
+            &mut std::fs::OpenOptions::new().append(true).open(&log).unwrap(),
+            line("a2", "new").as_bytes(),
+        )
+        .unwrap();
+        found(LogMode::Watched).await;
+        for _ in 0..200 {
+            if provider.sessions.read().await["s1"].messages.len() == 2 {
+                break;
             }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        {
+            let sessions = provider.sessions.read().await;
+            let session = &sessions["s1"];
+            assert!(!session.summary.historical);
+            assert_eq!(session.evict_at, None);
+            let contents: Vec<&str> = session.messages.iter().map(|m| &*m.content).collect();
+            assert_eq!(contents, ["old", "new"]);
         }
-    }
-    cwds
-}
 
-/// Get the set of working directories for all running Claude processes.
-/// Returns None if commands fail (safe fallback — skip process check).
-/// Returns Some(empty set) if no claude processes found.
-async fn get_active_claude_cwds() -> Option<HashSet<String>> {
-    // Step 1: Get PIDs of claude processes
-    let ps_output = tokio::process::Command::new("ps")
-        .args(["-eo", "pid,comm"])
-        .output()
-        .await
-        .ok()?;
-    if !ps_output.status.success() {
-        return None;
-    }
-    let ps_str = String::from_utf8_lossy(&ps_output.stdout);
-    let pids = parse_ps_claude_pids(&ps_str);
-    if pids.is_empty() {
-        return Some(HashSet::new());
-    }
+        // A historical session is dropped once its time is up.
+        found(LogMode::Historical).await;
+        assert!(!provider.sessions.read().await["s1"].summary.historical);
+        provider
+            .sessions
+            .write()
+            .await
+            .get_mut("s1")
+            .unwrap()
+            .evict_at = Some(0);
+        check_timers(
+            &provider.sessions,
+            &provider.event_tx,
+            &provider.dehydrated,
+            None,
+            false,
+        )
+        .await;
+        assert!(!provider.has_session("s1").await);
 
-    // Step 2: Get cwds via lsof
-    let pid_args: Vec<String> = pids.iter().map(|p| p.to_string()).collect();
-    let pid_csv = pid_args.join(",");
-    let lsof_output = tokio::process::Command::new("lsof")
-        .args(["-a", "-d", "cwd", "-p", &pid_csv, "-Fn"])
-        .output()
-        .await
-        .ok()?;
-    if !lsof_output.status.success() {
-        return None;
+        provider.stop().await;
+        let _ = std::fs::remove_file(&log);
     }
-    let lsof_str = String::from_utf8_lossy(&lsof_output.stdout);
-    Some(parse_lsof_cwds(&lsof_str))
-}
-
-const PROCESS_CHECK_INTERVAL_MS: i64 = 10_000;
-
-async fn check_timers(
-    sessions: &Arc<RwLock<HashMap<String, TrackedSession>>>,
-    event_tx: &mpsc::UnboundedSender<ProviderEvent>,
-) {
-    // Collect git diff targets and process check candidates while holding the lock
-    let mut git_diff_targets: Vec<(String, String)> = Vec::new();
-    let mut needs_process_check = false;
-    // (session_id, working_directory) for sessions that may need process-based stop
-    let mut process_check_candidates: Vec<(String, String)> = Vec::new();
 
-    {
-        let mut sessions = sessions.write().await;
-        let now_ms = chrono::Utc::now().timestamp_millis();
+    #[tokio::test]
+    async fn test_historical_session_reads_only_the_end_of_a_long_log() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        let log = std::env::temp_dir().join(format!("{}.jsonl", uuid::Uuid::new_v4()));
+        let line = |uuid: &str, text: &str| {
+            format!(
+                "{}\n",
+                serde_json::json!({
+                    "type": "assistant",
+                    "uuid": uuid,
+                    "timestamp": "2025-01-01T00:00:00Z",
+                    "message": {
+                        "model": "claude-sonnet-4-20250514",
+                        "content": [{"type": "text", "text": text}]
+                    }
+                })
+            )
+        };
+        let padding = line("a2", &"x".repeat(1024));
+        let mut content = line("a1", "too far back");
+        for _ in 0..REREAD_MAX_BYTES as usize / padding.len() + 1 {
+            content.push_str(&padding);
+        }
+        content.push_str(&line("a3", "recent"));
+        // A line still being written.
+        let late = line("a4", "late");
+        let (written, rest) = late.split_at(20);
+        content.push_str(written);
+        std::fs::write(&log, content).unwrap();
+        let discovered = DiscoveredSession {
+            session_id: "s1".to_string(),
+            log_file: log.clone(),
+            project_path: "/work/demo".to_string(),
+            project_name: "demo".to_string(),
+        };
+        let found = |mode| {
+            handle_session_found(
+                &provider.sessions,
+                &provider.event_tx,
+                &provider.startup,
+                discovered.clone(),
+                &provider.config,
+                mode,
+            )
+        };
+        found(LogMode::Historical).await;
+        {
+            let sessions = provider.sessions.read().await;
+            let contents: Vec<&str> = sessions["s1"]
+                .messages
+                .iter()
+                .map(|m| &*m.content)
+                .filter(|c| !c.starts_with('x'))
+                .collect();
+            assert_eq!(contents, ["recent"]);
+        }
 
-        for (session_id, session) in sessions.iter_mut() {
-            if !session.emitted {
-                continue;
+        // The unfinished line is read whole once it is.
+        std::io::Write::write_all(
+            &mut std::fs::OpenOptions::new().append(true).open(&log).unwrap(),
+            rest.as_bytes(),
+        )
+        .unwrap();
+        found(LogMode::Watched).await;
+        for _ in 0..200 {
+            let sessions = provider.sessions.read().await;
+            if sessions["s1"].messages.iter().any(|m| &*m.content == "late") {
+                break;
             }
+            drop(sessions);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let sessions = provider.sessions.read().await;
+        let last = sessions["s1"].messages.last().unwrap();
+        assert_eq!(&*last.content, "late");
+        drop(sessions);
 
-            // State transitions
-            let prev_state = session.state_ctx.state;
-            let result = check_time_based_transitions(&mut session.state_ctx);
+        provider.stop().await;
+        let _ = std::fs::remove_file(&log);
+    }
 
-            if result.changed {
-                session.summary.state = session.state_ctx.state;
-                let _ = event_tx.send(ProviderEvent::StateChanged {
-                    session_id: session_id.clone(),
-                    previous: prev_state,
-                    current: session.state_ctx.state,
-                });
+    #[tokio::test]
+    async fn test_watcher_health_and_stall_warning() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        let dir = std::env::temp_dir().join(format!("watcher-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir(&dir).unwrap();
+        let log = dir.join("s1.jsonl");
+        std::fs::write(&log, "{\"type\":\"user\"}\n").unwrap();
+        insert_test_session(&provider, "s1", AgentStateType::Idle).await;
+        insert_test_session(&provider, "s2", AgentStateType::Idle).await;
+        {
+            let mut sessions = provider.sessions.write().await;
+            // A directory can't be read as a log, so s2's watcher never reads successfully.
+            for (id, path) in [("s1", log.clone()), ("s2", dir.clone())] {
+                let (entries_tx, _) = mpsc::unbounded_channel();
+                let session = sessions.get_mut(id).unwrap();
+                session.state_ctx.last_activity_at = chrono::Utc::now().timestamp_millis();
+                session.watcher = FileWatcher::new(path, entries_tx)
+                    .with_poll_interval(Duration::from_millis(10));
+                session.watcher.start().await;
             }
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
 
-            let state = session.state_ctx.state;
+        let health = provider
+            .get_session_detail("s1")
+            .await
+            .unwrap()
+            .watcher
+            .unwrap();
+        assert_ne!(health.mode, WatcherMode::Stopped);
+        assert_eq!(health.bytes_read, 16);
+        assert!(health.last_read_at.is_some());
+        assert_eq!(health.last_error, None);
+        let health = provider
+            .get_session_detail("s2")
+            .await
+            .unwrap()
+            .watcher
+            .unwrap();
+        assert_eq!(health.last_read_at, None);
+        assert!(health.last_error.is_some() && health.last_error_at.is_some());
+        let counts = provider.watcher_counts().await;
+        assert_eq!(counts.notify + counts.poll_only, 2);
+        assert_eq!(counts.failing, ["s2"]);
 
-            // Check if git diff is needed
-            if (state == AgentStateType::Idle || state == AgentStateType::PermissionWaiting)
-                && !session.summary.working_directory.is_empty()
-                && (now_ms - session.last_git_diff_check) > 30_000
-            {
-                session.last_git_diff_check = now_ms;
-                git_diff_targets.push((
-                    session_id.clone(),
-                    session.summary.working_directory.clone(),
-                ));
+        // s2's "log" changes without being read: warned about once.
+        std::fs::write(dir.join("other"), "").unwrap();
+        for _ in 0..2 {
+            check_timers(
+                &provider.sessions,
+                &provider.event_tx,
+                &provider.dehydrated,
+                None,
+                false,
+            )
+            .await;
+        }
+        let warnings = |id: &'static str| {
+            let provider = &provider;
+            async move {
+                provider
+                    .get_session_messages(id)
+                    .await
+                    .unwrap()
+                    .iter()
+                    .filter(|m| {
+                        m.metadata
+                            .as_ref()
+                            .is_some_and(|m| m.contains_key("watcherStalled"))
+                    })
+                    .map(|m| (m.msg_type, m.metadata.as_ref().unwrap()["level"].clone()))
+                    .collect::<Vec<_>>()
             }
+        };
+        assert_eq!(
+            warnings("s2").await,
+            [(MessageType::Error, serde_json::json!("warning"))]
+        );
+        assert!(warnings("s1").await.is_empty());
 
-            // Collect candidates for process-based stop detection
-            if matches!(
-                state,
-                AgentStateType::Running
-                    | AgentStateType::Idle
-                    | AgentStateType::PermissionWaiting
-                    | AgentStateType::Error
-            ) && !session.summary.working_directory.is_empty()
-                && (now_ms - session.last_process_check) > PROCESS_CHECK_INTERVAL_MS
-            {
-                session.last_process_check = now_ms;
-                needs_process_check = true;
-                process_check_candidates.push((
-                    session_id.clone(),
-                    session.summary.working_directory.clone(),
-                ));
-            }
-        }
+        provider.sessions.read().await["s2"].watcher.stop();
+        let health = provider
+            .get_session_detail("s2")
+            .await
+            .unwrap()
+            .watcher
+            .unwrap();
+        assert_eq!(health.mode, WatcherMode::Stopped);
+        let counts = provider.watcher_counts().await;
+        assert_eq!(counts.notify + counts.poll_only, 1);
+        assert!(counts.failing.is_empty());
+
+        provider.stop().await;
+        let _ = std::fs::remove_dir_all(&dir);
     }
 
-    // Lock released — run process check
-    if needs_process_check {
-        if let Some(active_cwds) = get_active_claude_cwds().await {
-            let mut sessions = sessions.write().await;
-            for (session_id, wd) in &process_check_candidates {
-                if !active_cwds.contains(wd.as_str()) {
-                    if let Some(session) = sessions.get_mut(session_id) {
-                        // Double-check state hasn't changed while lock was released
-                        let prev = session.state_ctx.state;
-                        if matches!(
-                            prev,
-                            AgentStateType::Running
-                                | AgentStateType::Idle
-                                | AgentStateType::PermissionWaiting
-                                | AgentStateType::Error
-                        ) {
-                            session.state_ctx.state = AgentStateType::Stopped;
-                            session.summary.state = AgentStateType::Stopped;
-                            if session.emitted {
-                                let _ = event_tx.send(ProviderEvent::StateChanged {
-                                    session_id: session_id.clone(),
-                                    previous: prev,
-                                    current: AgentStateType::Stopped,
-                                });
-                            }
-                        }
+    #[tokio::test]
+    async fn test_stopped_discovery_and_watchers_restart() {
+        let dir = std::env::temp_dir().join(format!("watchdog-{}", uuid::Uuid::new_v4()));
+        let project = dir.join("-work-demo");
+        std::fs::create_dir_all(&project).unwrap();
+        let scan_interval = Duration::from_millis(20);
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::with_config(
+            tx,
+            ProviderConfig {
+                discovery: DiscoveryConfig {
+                    projects_dir: dir.clone(),
+                    scan_interval,
+                    max_log_age: Duration::from_secs(60),
+                },
+                poll_interval: Duration::from_millis(10),
+                ..Default::default()
+            },
+        );
+        provider.start().await;
+        let first_scan = provider.discovery_last_scan().await.unwrap();
+        let entry = |entry: serde_json::Value| {
+            let mut file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(project.join("s1.jsonl"))
+                .unwrap();
+            std::io::Write::write_all(&mut file, format!("{}\n", entry).as_bytes()).unwrap();
+        };
+        let eventually = |what: &'static str| {
+            let provider = &provider;
+            async move {
+                for _ in 0..100 {
+                    let found = match what {
+                        "session" => provider.has_session("s1").await,
+                        _ => provider
+                            .get_session_messages("s1")
+                            .await
+                            .unwrap_or_default()
+                            .iter()
+                            .any(|m| &*m.content == what),
+                    };
+                    if found {
+                        return;
                     }
+                    tokio::time::sleep(Duration::from_millis(10)).await;
                 }
+                panic!("never saw {}", what);
             }
+        };
+
+        // Discovery quietly stops: a new log goes unnoticed until it's restarted.
+        provider.discovery.lock().await.as_ref().unwrap().stop();
+        tokio::time::sleep(scan_interval * 5).await;
+        entry(serde_json::json!({
+            "type": "user",
+            "uuid": "u1",
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+            "message": {"role": "user", "content": "fix the build"}
+        }));
+        tokio::time::sleep(scan_interval * 5).await;
+        assert!(!provider.has_session("s1").await);
+        let now_ms = chrono::Utc::now().timestamp_millis();
+        assert!(crate::session::watchdog::scan_overdue(
+            provider.discovery_last_scan().await,
+            provider.discovery_scan_interval(),
+            now_ms
+        ));
+        provider.restart_discovery().await;
+        eventually("session").await;
+        assert!(provider.discovery_last_scan().await.unwrap() > first_scan);
+
+        // The watcher stops reading; once that's noticed it picks up where it was.
+        eventually("fix the build").await;
+        {
+            let mut sessions = provider.sessions.write().await;
+            let session = sessions.get_mut("s1").unwrap();
+            session.watcher.stop();
+            session.watcher_stall_reported = true;
         }
+        entry(serde_json::json!({
+            "type": "assistant",
+            "uuid": "a1",
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+            "message": {
+                "model": "claude-sonnet-4-20250514",
+                "content": [{"type": "text", "text": "Fixed."}]
+            }
+        }));
+        assert_eq!(provider.restart_stalled_watchers().await, ["s1"]);
+        eventually("Fixed.").await;
+        assert!(provider.restart_stalled_watchers().await.is_empty());
+        let messages = provider.get_session_messages("s1").await.unwrap();
+        assert_eq!(
+            messages
+                .iter()
+                .filter(|m| &*m.content == "fix the build")
+                .count(),
+            1
+        );
+
+        provider.stop().await;
+        let _ = std::fs::remove_dir_all(&dir);
     }
 
-    // Lock released — run git diff concurrently
-    if !git_diff_targets.is_empty() {
-        let mut handles = Vec::new();
-        for (session_id, wd) in git_diff_targets {
-            let event_tx = event_tx.clone();
-            let sessions = sessions.clone();
-            handles.push(tokio::spawn(async move {
-                if let Some((additions, deletions)) = fetch_git_diff_stats(&wd).await {
-                    let mut sessions = sessions.write().await;
-                    if let Some(session) = sessions.get_mut(&session_id) {
-                        let changed = session.summary.git_status.additions != additions
-                            || session.summary.git_status.deletions != deletions;
-                        if changed {
-                            session.summary.git_status.additions = additions;
-                            session.summary.git_status.deletions = deletions;
-                            let _ = event_tx.send(ProviderEvent::GitStatusUpdated {
-                                session_id: session_id.clone(),
-                                git_status: session.summary.git_status.clone(),
-                            });
-                        }
+    #[tokio::test]
+    async fn test_deleted_log_stops_session() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        let dir = std::env::temp_dir().join(format!("deleted-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir(&dir).unwrap();
+        let log = dir.join("s1.jsonl");
+        let line = |i: usize| {
+            format!(
+                "{}\n",
+                serde_json::json!({
+                    "type": "user",
+                    "uuid": format!("u{i}"),
+                    "message": {"role": "user", "content": format!("message {i}")}
+                })
+            )
+        };
+        std::fs::write(&log, line(0)).unwrap();
+
+        insert_test_session(&provider, "s1", AgentStateType::Idle).await;
+        let (entries_tx, mut entries_rx) = mpsc::unbounded_channel();
+        let sessions = provider.sessions.clone();
+        let event_tx = provider.event_tx.clone();
+        let startup = provider.startup.clone();
+        tokio::spawn(async move {
+            while let Some(event) = entries_rx.recv().await {
+                handle_watch_event(&sessions, &event_tx, &startup, "s1", event).await;
+            }
+        });
+        {
+            let mut sessions = provider.sessions.write().await;
+            let session = sessions.get_mut("s1").unwrap();
+            session.watcher = FileWatcher::new(log.clone(), entries_tx)
+                .with_poll_interval(Duration::from_millis(10));
+            session.watcher.start().await;
+        }
+        let wait_for = |check: fn(&TrackedSession) -> bool| {
+            let sessions = provider.sessions.clone();
+            async move {
+                for _ in 0..200 {
+                    if check(&sessions.read().await["s1"]) {
+                        return true;
                     }
+                    tokio::time::sleep(Duration::from_millis(10)).await;
                 }
-            }));
+                false
+            }
+        };
+        assert!(wait_for(|s| s.messages.len() == 1).await);
+
+        std::fs::remove_file(&log).unwrap();
+        assert!(wait_for(|s| s.summary.stop_reason.is_some()).await);
+        {
+            let sessions = provider.sessions.read().await;
+            let session = &sessions["s1"];
+            assert_eq!(session.summary.state, AgentStateType::Stopped);
+            assert_eq!(session.summary.stop_reason.as_deref(), Some(LOG_DELETED));
+            assert!(session.evict_at.is_some());
+        }
+        let mut deleted = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            if let ProviderEvent::LogDeleted { log_file, .. } = event {
+                deleted.push(log_file);
+            }
         }
-        for handle in handles {
-            let _ = handle.await;
+        assert_eq!(deleted, [log.display().to_string()]);
+
+        // Written again: followed as before and no longer up for removal.
+        std::fs::write(&log, format!("{}{}", line(0), line(1))).unwrap();
+        assert!(wait_for(|s| s.messages.len() == 2).await);
+        {
+            let sessions = provider.sessions.read().await;
+            assert_eq!(sessions["s1"].summary.stop_reason, None);
+            assert_eq!(sessions["s1"].evict_at, None);
         }
+        provider.sessions.read().await["s1"].watcher.stop();
+        std::fs::remove_dir_all(&dir).unwrap();
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[tokio::test]
+    async fn test_transcripts_shed_largest_quiet_sessions_first() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        // About 2 KB per message; the stopped session holds the most.
+        for (id, state, count) in [
+            ("stopped", AgentStateType::Stopped, 120),
+            ("idle", AgentStateType::Idle, 100),
+            ("running", AgentStateType::Running, 100),
+            ("small", AgentStateType::Stopped, 0),
+        ] {
+            insert_test_session(&provider, id, state).await;
+            let mut sessions = provider.sessions.write().await;
+            let session = sessions.get_mut(id).unwrap();
+            for i in 0..count {
+                session.push_message(AgentMessage {
+                    id: format!("{id}:{i}"),
+                    session_id: id.to_string(),
+                    timestamp: format!("2025-01-01T00:00:{:02}.000Z", i % 60),
+                    role: MessageRole::Assistant,
+                    msg_type: MessageType::ToolResult,
+                    content: format!("{id}:{i} {}", "x".repeat(2_000)).into(),
+                    metadata: None,
+                    parent_id: None,
+                    tool_use_message_id: None,
+                });
+            }
+        }
+        let retained = || async {
+            let sessions = provider.sessions.read().await;
+            let bytes = |id: &str| sessions[id].retained_bytes;
+            (bytes("stopped"), bytes("idle"), bytes("running"))
+        };
+        let (stopped, idle, running) = retained().await;
+        assert!(stopped > 240_000 && idle > 200_000 && idle < 240_000);
+        let sheds = SHEDS.load(Ordering::Relaxed);
 
-    #[test]
-    fn test_parse_shortstat_full() {
-        let output = " 3 files changed, 42 insertions(+), 10 deletions(-)";
-        assert_eq!(parse_shortstat(output), Some((42, 10)));
-    }
+        // Under budget: nothing is dropped.
+        let budget = stopped + idle + running;
+        shed_transcripts(
+            &mut *provider.sessions.write().await,
+            &provider.dehydrated,
+            budget,
+        );
+        assert_eq!(retained().await, (stopped, idle, running));
 
-    #[test]
-    fn test_parse_shortstat_insertions_only() {
-        let output = " 1 file changed, 5 insertions(+)";
-        assert_eq!(parse_shortstat(output), Some((5, 0)));
+        // Dropping the stopped session isn't enough; the idle one is cut down next,
+        // and the running one is left alone.
+        let budget = idle + running - 1;
+        shed_transcripts(
+            &mut *provider.sessions.write().await,
+            &provider.dehydrated,
+            budget,
+        );
+        {
+            let sessions = provider.sessions.read().await;
+            assert!(!sessions["stopped"].hydrated);
+            assert!(provider.dehydrated.lock().unwrap().contains_key("stopped"));
+            assert_eq!(sessions["idle"].messages.len(), SHED_KEEP_MESSAGES);
+            assert_eq!(sessions["idle"].messages[0].id, "idle:50");
+            assert_eq!(sessions["running"].messages.len(), 100);
+            assert!(sessions["small"].hydrated);
+        }
+        let memory = provider.transcript_memory().await;
+        assert!(memory.retained_bytes <= budget);
+        assert!(SHEDS.load(Ordering::Relaxed) >= sheds + 2);
+
+        // Only the running session is left to shed from, and it keeps watching.
+        shed_transcripts(
+            &mut *provider.sessions.write().await,
+            &provider.dehydrated,
+            running / 2,
+        );
+        let sessions = provider.sessions.read().await;
+        assert_eq!(sessions["running"].messages.len(), SHED_KEEP_MESSAGES);
+        assert!(sessions["running"].hydrated);
     }
 
-    #[test]
-    fn test_parse_shortstat_deletions_only() {
-        let output = " 2 files changed, 3 deletions(-)";
-        assert_eq!(parse_shortstat(output), Some((0, 3)));
+    #[tokio::test]
+    async fn test_spend_rate_of_unannounced_session_is_not_sent() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::Running).await;
+        let spend = |emitted: bool| {
+            let provider = &provider;
+            async move {
+                let mut sessions = provider.sessions.write().await;
+                let session = sessions.get_mut("s1").unwrap();
+                session.emitted = emitted;
+                session.summary.spend_rate_per_hour = 0.0;
+                let now_ms = chrono::Utc::now().timestamp_millis();
+                let mut usage = CumulativeUsage::default();
+                session.spend_rate.record(now_ms - 60_000, &usage);
+                usage.estimated_cost = 1.0;
+                usage.output_tokens = 1_000;
+                session.spend_rate.record(now_ms, &usage);
+            }
+        };
+        let tick = || {
+            check_timers(
+                &provider.sessions,
+                &provider.event_tx,
+                &provider.dehydrated,
+                None,
+                false,
+            )
+        };
+        let usage_sent = |rx: &mut mpsc::UnboundedReceiver<ProviderEvent>| {
+            std::iter::from_fn(|| rx.try_recv().ok())
+                .any(|e| matches!(e, ProviderEvent::UsageUpdated { .. }))
+        };
+
+        spend(false).await;
+        tick().await;
+        assert!(!usage_sent(&mut rx));
+
+        spend(true).await;
+        tick().await;
+        assert!(usage_sent(&mut rx));
     }
 
-    #[test]
-    fn test_parse_shortstat_empty() {
-        assert_eq!(parse_shortstat(""), Some((0, 0)));
-        assert_eq!(parse_shortstat("  "), Some((0, 0)));
+    #[tokio::test]
+    async fn test_dehydrate_counts_from_when_the_session_stopped() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::Stopped).await;
+        // Timed out after a long silence: its last activity is well past the delay.
+        provider.sessions.write().await.get_mut("s1").unwrap().state_ctx.last_activity_at =
+            chrono::Utc::now().timestamp_millis() - 40 * 60_000;
+        let tick = || {
+            check_timers(
+                &provider.sessions,
+                &provider.event_tx,
+                &provider.dehydrated,
+                None,
+                false,
+            )
+        };
+
+        tick().await;
+        {
+            let sessions = provider.sessions.read().await;
+            assert!(sessions["s1"].hydrated);
+            assert!(sessions["s1"].stopped_at.is_some());
+        }
+
+        // Stopped for longer than the delay.
+        let delay = SessionSettings::default().dehydrate_after.as_millis() as i64;
+        {
+            let mut sessions = provider.sessions.write().await;
+            let session = sessions.get_mut("s1").unwrap();
+            session.stopped_at = session.stopped_at.map(|at| at - delay);
+        }
+        tick().await;
+        assert!(!provider.sessions.read().await["s1"].hydrated);
     }
 
-    #[test]
-    fn test_parse_ps_claude_pids_typical() {
-        let output = "  PID COMM\n  501 zsh\n 1234 claude\n 5678 node\n 9012 claude\n";
-        let pids = parse_ps_claude_pids(output);
-        assert_eq!(pids, vec![1234, 9012]);
+    #[tokio::test]
+    async fn test_stopped_session_dehydrates_and_rehydrates() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        let log = std::env::temp_dir().join(format!("{}.jsonl", uuid::Uuid::new_v4()));
+        let line = |i: usize| {
+            format!(
+                "{}\n",
+                serde_json::json!({
+                    "type": "user",
+                    "uuid": format!("u{i}"),
+                    "timestamp": format!("2025-01-01T00:{:02}:00Z", i),
+                    "message": {"role": "user", "content": format!("message {i}")}
+                })
+            )
+        };
+        let turn = r#"{"type":"system","subtype":"turn_duration","durationMs":5,"timestamp":"2025-01-01T00:05:00Z"}"#;
+        std::fs::write(&log, format!("{}{}\n{}", line(0), turn, line(1))).unwrap();
+
+        insert_test_session(&provider, "s1", AgentStateType::Stopped).await;
+        let (entries_tx, mut entries_rx) = mpsc::unbounded_channel();
+        let sessions = provider.sessions.clone();
+        let event_tx = provider.event_tx.clone();
+        let startup = provider.startup.clone();
+        tokio::spawn(async move {
+            while let Some(event) = entries_rx.recv().await {
+                handle_watch_event(&sessions, &event_tx, &startup, "s1", event).await;
+            }
+        });
+        {
+            let mut sessions = provider.sessions.write().await;
+            let session = sessions.get_mut("s1").unwrap();
+            session.watcher = FileWatcher::new(log.clone(), entries_tx)
+                .with_poll_interval(Duration::from_millis(20));
+            session.watcher.start().await;
+            session.settings = Arc::new(ArcSwap::from_pointee(SessionSettings {
+                dehydrate_after: Duration::ZERO,
+                ..Default::default()
+            }));
+        }
+        let message_count = || async {
+            let sessions = provider.sessions.read().await;
+            sessions["s1"].messages.len()
+        };
+        for _ in 0..200 {
+            if message_count().await == 3 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let ids = |messages: &[Arc<AgentMessage>]| -> Vec<String> {
+            messages.iter().map(|m| m.id.clone()).collect()
+        };
+        let before = ids(&provider.get_session_messages("s1").await.unwrap());
+        assert_eq!(before.len(), 3);
+        let outline = provider.get_outline("s1").await.unwrap();
+        assert_eq!(outline.turns.len(), 2);
+
+        check_timers(
+            &provider.sessions,
+            &provider.event_tx,
+            &provider.dehydrated,
+            None,
+            false,
+        )
+        .await;
+        {
+            let sessions = provider.sessions.read().await;
+            assert!(!sessions["s1"].hydrated);
+            assert!(sessions["s1"].messages.is_empty());
+        }
+        assert!(provider.dehydrated.lock().unwrap().contains_key("s1"));
+        let stats = provider.dashboard_stats(chrono::Utc::now()).await;
+        assert_eq!((stats.hydrated_sessions, stats.dehydrated_sessions), (0, 1));
+        // Details are re-read from the log, with the same message ids.
+        let detail = provider.get_session_detail("s1").await.unwrap();
+        assert_eq!(ids(&detail.messages), before);
+        // So is the outline.
+        let dehydrated_outline = provider.get_outline("s1").await.unwrap();
+        assert_eq!(
+            serde_json::to_value(&dehydrated_outline.turns).unwrap(),
+            serde_json::to_value(&outline.turns).unwrap()
+        );
+        assert!(!provider.sessions.read().await["s1"].hydrated);
+
+        // The log grows: its messages are read back in and only the new line is added.
+        std::io::Write::write_all(
+            &mut std::fs::OpenOptions::new().append(true).open(&log).unwrap(),
+            line(6).as_bytes(),
+        )
+        .unwrap();
+        rehydrate(&provider.sessions, "s1").await;
+        for _ in 0..200 {
+            if message_count().await == 4 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let after = provider.get_session_messages("s1").await.unwrap();
+        assert_eq!(ids(&after[..3]), before);
+        assert_eq!(after.len(), 4);
+        assert_eq!(&*after[3].content, "message 6");
+        assert!(provider.sessions.read().await["s1"].hydrated);
+
+        provider.sessions.read().await["s1"].watcher.stop();
+        let _ = std::fs::remove_file(&log);
     }
 
-    #[test]
-    fn test_parse_ps_claude_pids_no_match() {
-        let output = "  PID COMM\n  501 zsh\n 5678 node\n";
-        let pids = parse_ps_claude_pids(output);
-        assert!(pids.is_empty());
+    #[tokio::test]
+    async fn test_late_entry_written_while_dehydrated_goes_in_order() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        let log = std::env::temp_dir().join(format!("{}.jsonl", uuid::Uuid::new_v4()));
+        let line = |i: usize| {
+            format!(
+                "{}\n",
+                serde_json::json!({
+                    "type": "user",
+                    "uuid": format!("u{i}"),
+                    "timestamp": format!("2025-01-01T00:{:02}:00Z", i),
+                    "message": {"role": "user", "content": format!("message {i}")}
+                })
+            )
+        };
+        let turn = r#"{"type":"system","subtype":"turn_duration","durationMs":5,"timestamp":"2025-01-01T00:05:00Z"}"#;
+        std::fs::write(&log, format!("{}{}\n{}", line(0), turn, line(1))).unwrap();
+
+        insert_test_session(&provider, "s1", AgentStateType::Stopped).await;
+        let (entries_tx, mut entries_rx) = mpsc::unbounded_channel();
+        let sessions = provider.sessions.clone();
+        let event_tx = provider.event_tx.clone();
+        let startup = provider.startup.clone();
+        tokio::spawn(async move {
+            while let Some(event) = entries_rx.recv().await {
+                handle_watch_event(&sessions, &event_tx, &startup, "s1", event).await;
+            }
+        });
+        {
+            let mut sessions = provider.sessions.write().await;
+            let session = sessions.get_mut("s1").unwrap();
+            session.watcher = FileWatcher::new(log.clone(), entries_tx)
+                .with_poll_interval(Duration::from_millis(20));
+            session.watcher.start().await;
+            session.settings = Arc::new(ArcSwap::from_pointee(SessionSettings {
+                dehydrate_after: Duration::ZERO,
+                ..Default::default()
+            }));
+        }
+        let message_count = || async {
+            let sessions = provider.sessions.read().await;
+            sessions["s1"].messages.len()
+        };
+        for _ in 0..200 {
+            if message_count().await == 3 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let ids = |messages: &[Arc<AgentMessage>]| -> Vec<String> {
+            messages.iter().map(|m| m.id.clone()).collect()
+        };
+        let before = ids(&provider.get_session_messages("s1").await.unwrap());
+        // The second prompt was already late: it's stored ahead of the turn marker.
+        assert_eq!(before, ["u0:0", "u1:0", "s1:1:0"]);
+
+        check_timers(
+            &provider.sessions,
+            &provider.event_tx,
+            &provider.dehydrated,
+            None,
+            false,
+        )
+        .await;
+        assert!(!provider.sessions.read().await["s1"].hydrated);
+        // Re-read from the log, the messages come back in the same order.
+        let detail = provider.get_session_detail("s1").await.unwrap();
+        assert_eq!(ids(&detail.messages), before);
+
+        // Another late prompt is written: once read back in, it too goes by its
+        // timestamp rather than last.
+        std::io::Write::write_all(
+            &mut std::fs::OpenOptions::new().append(true).open(&log).unwrap(),
+            line(2).as_bytes(),
+        )
+        .unwrap();
+        rehydrate(&provider.sessions, "s1").await;
+        for _ in 0..200 {
+            if message_count().await == 4 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let after = ids(&provider.get_session_messages("s1").await.unwrap());
+        assert_eq!(after, ["u0:0", "u1:0", "u2:0", "s1:1:0"]);
+
+        provider.sessions.read().await["s1"].watcher.stop();
+        let _ = std::fs::remove_file(&log);
     }
 
-    #[test]
-    fn test_parse_ps_claude_pids_empty() {
-        let pids = parse_ps_claude_pids("");
-        assert!(pids.is_empty());
+    #[tokio::test]
+    async fn test_large_batch_sends_one_messages_appended() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::Idle).await;
+        let entry = |i: usize| {
+            jsonl_parser::parse_jsonl_line(
+                &serde_json::json!({
+                    "type": "user",
+                    "uuid": format!("u{i}"),
+                    "timestamp": format!("2025-01-01T00:{:02}:00Z", i),
+                    "message": {"role": "user", "content": format!("message {i}")}
+                })
+                .to_string(),
+            )
+            .unwrap()
+        };
+        let drain = |rx: &mut mpsc::UnboundedReceiver<ProviderEvent>| {
+            let mut events = Vec::new();
+            while let Ok(event) = rx.try_recv() {
+                match event {
+                    ProviderEvent::NewMessage { .. } => events.push("new".to_string()),
+                    ProviderEvent::MessagesAppended {
+                        count,
+                        latest_timestamp,
+                        ..
+                    } => events.push(format!("appended {count} {latest_timestamp}")),
+                    _ => {}
+                }
+            }
+            events
+        };
+
+        let entries = (0..MESSAGE_BATCH_THRESHOLD + 1).map(entry).collect();
+        handle_entries(&provider.sessions, &provider.event_tx, "s1", entries).await;
+        assert_eq!(drain(&mut rx), ["appended 51 2025-01-01T00:50:00.000Z"]);
+
+        let entries = vec![entry(51), entry(52)];
+        handle_entries(&provider.sessions, &provider.event_tx, "s1", entries).await;
+        assert_eq!(drain(&mut rx), ["new", "new"]);
+        assert_eq!(provider.get_session_messages("s1").await.unwrap().len(), 53);
     }
 
-    #[test]
-    fn test_parse_ps_claude_pids_path_comm() {
-        // On some systems comm may show the full path
-        let output = "  PID COMM\n 1234 /usr/local/bin/claude\n";
-        let pids = parse_ps_claude_pids(output);
-        assert_eq!(pids, vec![1234]);
+    #[tokio::test]
+    async fn test_slow_search_does_not_block_writes() {
+        static SCANNING: AtomicBool = AtomicBool::new(false);
+        fn slow_find(query: &SearchQuery, text: &str, needle: &str) -> Option<Range<usize>> {
+            SCANNING.store(true, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(50));
+            query.find_needle(text, needle)
+        }
+
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::Idle).await;
+        {
+            let mut sessions = provider.sessions.write().await;
+            let session = sessions.get_mut("s1").unwrap();
+            for i in 0..6 {
+                session.push_message(AgentMessage {
+                    id: format!("m{i}"),
+                    session_id: "s1".to_string(),
+                    timestamp: "2025-01-01T00:00:00Z".to_string(),
+                    role: MessageRole::User,
+                    msg_type: MessageType::Text,
+                    content: format!("message {i} about the watcher").into(),
+                    metadata: None,
+                    parent_id: None,
+                    tool_use_message_id: None,
+                });
+            }
+        }
+        let query = SearchQuery::parse("watcher", false);
+        let scopes = [SearchScope::Content];
+
+        let search = async {
+            let results = provider
+                .search_sessions_with(&query, &scopes, slow_find)
+                .await;
+            (results, std::time::Instant::now())
+        };
+        let write = async {
+            while !SCANNING.load(Ordering::SeqCst) {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+            let _sessions = provider.sessions.write().await;
+            std::time::Instant::now()
+        };
+        let ((results, searched_at), written_at) = tokio::join!(search, write);
+        // The write went ahead while the messages were still being scanned.
+        assert!(written_at < searched_at);
+
+        let expected = provider.search_sessions(&query, &scopes).await;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].match_count, 6);
+        assert_eq!(
+            serde_json::to_value(&results).unwrap(),
+            serde_json::to_value(&expected).unwrap()
+        );
     }
 
-    #[test]
-    fn test_parse_lsof_cwds_typical() {
-        let output = "p1234\nn/Users/daiki/Projects/my-app\np5678\nn/Users/daiki/Projects/other\n";
-        let cwds = parse_lsof_cwds(output);
-        assert_eq!(cwds.len(), 2);
-        assert!(cwds.contains("/Users/daiki/Projects/my-app"));
-        assert!(cwds.contains("/Users/daiki/Projects/other"));
+    #[tokio::test]
+    async fn test_search_match_locates_message() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::Idle).await;
+        let line = |uuid: String, content: String| {
+            jsonl_parser::parse_jsonl_line(
+                &serde_json::json!({
+                    "type": "user",
+                    "uuid": uuid,
+                    "message": {
+                        "role": "user",
+                        "content": [{"type": "tool_result", "tool_use_id": "t1", "content": content}]
+                    }
+                })
+                .to_string(),
+            )
+            .unwrap()
+        };
+        let long = format!("{}needle in the haystack", "filler ".repeat(100));
+        let mut entries = vec![line("old".into(), long)];
+        entries.extend((0..500).map(|i| line(format!("u{i}"), format!("output {i}"))));
+        entries.push(line("new".into(), "another needle".into()));
+        handle_entries(&provider.sessions, &provider.event_tx, "s1", entries).await;
+
+        let results = provider
+            .search_sessions(
+                &SearchQuery::parse("needle", false),
+                &[SearchScope::Content],
+            )
+            .await;
+        let matches = &results[0].matches;
+        assert_eq!(matches.len(), 2);
+
+        assert!(matches[0].message_trimmed);
+        assert!(matches[0].truncated_source);
+        assert_eq!(matches[0].message_index, None);
+        assert!(matches[0].message_id.is_some());
+
+        let sessions = provider.sessions.read().await;
+        let messages = &sessions["s1"].messages;
+        let index = matches[1].message_index.unwrap() as usize;
+        assert!(!matches[1].message_trimmed);
+        assert_eq!(index, messages.len() - 1);
+        assert_eq!(matches[1].message_id.as_ref(), Some(&messages[index].id));
     }
 
-    #[test]
-    fn test_parse_lsof_cwds_empty() {
-        let cwds = parse_lsof_cwds("");
-        assert!(cwds.is_empty());
+    #[tokio::test]
+    async fn test_search_case_sensitive_phrase() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "s1", AgentStateType::Idle).await;
+        let texts = [
+            "Wrap it in a PathBuf before calling read_new_content(), then restart the watcher.",
+            "the pathbuf conversion is fine",
+        ];
+        let entries = texts
+            .iter()
+            .map(|text| {
+                jsonl_parser::parse_jsonl_line(
+                    &serde_json::json!({
+                        "type": "assistant",
+                        "message": {"model": "claude-sonnet-4-20250514", "content": [{"type": "text", "text": text}]}
+                    })
+                    .to_string(),
+                )
+                .unwrap()
+            })
+            .collect();
+        handle_entries(&provider.sessions, &provider.event_tx, "s1", entries).await;
+        // Only the lowercase spelling, and only past where the result is cut off.
+        let output = format!("{}the pathbuf conversion failed", "compiling...\n".repeat(40));
+        let tool_result = serde_json::json!({
+            "type": "user",
+            "uuid": "u1",
+            "message": {
+                "role": "user",
+                "content": [{"type": "tool_result", "tool_use_id": "t1", "content": output}]
+            }
+        });
+        let entry = jsonl_parser::parse_jsonl_line(&tool_result.to_string()).unwrap();
+        handle_entries(&provider.sessions, &provider.event_tx, "s1", vec![entry]).await;
+        let search = |raw: &'static str, case_sensitive: bool| {
+            let provider = &provider;
+            async move {
+                let query = SearchQuery::parse(raw, case_sensitive);
+                let results = provider
+                    .search_sessions(&query, &[SearchScope::Content])
+                    .await;
+                results.first().map_or(0, |r| r.match_count)
+            }
+        };
+
+        assert_eq!(search("PathBuf", false).await, 3);
+        assert_eq!(search("PathBuf", true).await, 1);
+        let results = provider
+            .search_sessions(
+                &SearchQuery::parse("PathBuf", true),
+                &[SearchScope::Content],
+            )
+            .await;
+        assert!(results[0].matches.iter().all(|m| !m.truncated_source));
+        assert_eq!(search(r#""read_new_content()," watcher"#, false).await, 1);
+        assert_eq!(search(r#""read_new_content() watcher""#, false).await, 0);
     }
 
-    #[test]
-    fn test_parse_lsof_cwds_no_n_lines() {
-        let output = "p1234\np5678\n";
-        let cwds = parse_lsof_cwds(output);
-        assert!(cwds.is_empty());
+    #[tokio::test]
+    async fn test_resume_links_never_form_a_cycle() {
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let provider = ClaudeCodeProvider::new(tx);
+        insert_test_session(&provider, "a", AgentStateType::Stopped).await;
+        insert_test_session(&provider, "b", AgentStateType::Stopped).await;
+        {
+            let mut sessions = provider.sessions.write().await;
+            sessions.get_mut("b").unwrap().summary.resumed_from = Some("a".into());
+            let a = sessions.get_mut("a").unwrap();
+            let entry = jsonl_parser::parse_jsonl_line(
+                r#"{"type":"user","message":{"role":"user","content":"hi"},"uuid":"x","sessionId":"b"}"#,
+            )
+            .unwrap();
+            a.resume.observe("a", &entry);
+            link_resumed_sessions(&mut sessions, &provider.event_tx, "/tmp/project");
+            assert_eq!(sessions["a"].summary.resumed_from, None);
+            assert_eq!(chain_root(&sessions, "b"), "a");
+        }
     }
 
     #[test]
-    fn test_parse_lsof_cwds_deduplicates() {
-        let output = "p1234\nn/Users/daiki/Projects/app\np5678\nn/Users/daiki/Projects/app\n";
-        let cwds = parse_lsof_cwds(output);
-        assert_eq!(cwds.len(), 1);
-        assert!(cwds.contains("/Users/daiki/Projects/app"));
+    fn test_messages_after() {
+        let message = |id: &str, timestamp: &str| {
+            Arc::new(AgentMessage {
+                id: id.to_string(),
+                session_id: "s1".to_string(),
+                timestamp: timestamp.to_string(),
+                role: MessageRole::Assistant,
+                msg_type: MessageType::Text,
+                content: "".into(),
+                metadata: None,
+                parent_id: None,
+                tool_use_message_id: None,
+            })
+        };
+        let messages = vec![
+            message("m1", "2025-01-01T10:00:00Z"),
+            message("m2", "2025-01-01T10:01:00Z"),
+            // Same time as m2, but after it.
+            message("m3", "2025-01-01T10:01:00Z"),
+            message("m4", "2025-01-01T10:02:00Z"),
+        ];
+        let at = |ts: &str| Some(ts.parse().unwrap());
+        assert_eq!(
+            messages_after(&messages, "m2", at("2025-01-01T10:01:00Z")),
+            2
+        );
+        assert_eq!(messages_after(&messages, "m4", None), 0);
+        // Trimmed from memory: whatever is newer.
+        assert_eq!(
+            messages_after(&messages, "m0", at("2025-01-01T10:00:30Z")),
+            3
+        );
+        assert_eq!(messages_after(&messages, "m0", None), 4);
     }
 }
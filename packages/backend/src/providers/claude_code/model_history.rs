@@ -0,0 +1,144 @@
+//! The models a session has run on. `/model` or an automatic fallback switches a
+//! session between models mid-way; each main-thread assistant entry names the
+//! model that wrote it.
+
+use super::message_mapper::EntryPosition;
+use crate::types::{AgentMessage, MessageRole, MessageType, ModelSwitch};
+use std::collections::HashMap;
+
+/// What an assistant entry names when it doesn't name a model.
+pub const UNKNOWN_MODEL: &str = "unknown";
+/// Claude Code's model for entries it writes itself, such as API errors.
+const SYNTHETIC_MODEL: &str = "<synthetic>";
+/// Oldest switches are dropped beyond this many, so a session that flips back and
+/// forth for days keeps a bounded history.
+const MAX_HISTORY: usize = 100;
+
+/// Whether `model` names a model that answered, rather than a placeholder.
+pub fn is_real_model(model: &str) -> bool {
+    !model.is_empty() && model != UNKNOWN_MODEL && model != SYNTHETIC_MODEL
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ModelTracker {
+    /// The first model, then every switch; the latest `MAX_HISTORY` of them.
+    history: Vec<ModelSwitch>,
+}
+
+/// The model changed with an entry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelChange {
+    pub previous: String,
+    pub current: String,
+}
+
+impl ModelTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn history(&self) -> &[ModelSwitch] {
+        &self.history
+    }
+
+    /// Note that an entry at `timestamp` came from `model`, returning the change if
+    /// it differs from the model before. The first model isn't a change.
+    pub fn observe(&mut self, model: &str, timestamp: &str) -> Option<ModelChange> {
+        if !is_real_model(model) {
+            return None;
+        }
+        let previous = self.history.last().map(|switch| switch.model.clone());
+        if previous.as_deref() == Some(model) {
+            return None;
+        }
+        self.history.push(ModelSwitch {
+            timestamp: timestamp.to_string(),
+            model: model.to_string(),
+        });
+        if self.history.len() > MAX_HISTORY {
+            self.history.remove(0);
+        }
+        previous.map(|previous| ModelChange {
+            previous,
+            current: model.to_string(),
+        })
+    }
+}
+
+impl ModelChange {
+    /// A message marking the change in the transcript, with an id derived from the
+    /// entry that brought it.
+    pub fn message(&self, position: EntryPosition, timestamp: &str) -> AgentMessage {
+        let mut metadata = HashMap::new();
+        metadata.insert("model".to_string(), serde_json::json!(self.current));
+        metadata.insert("previousModel".to_string(), serde_json::json!(self.previous));
+        AgentMessage {
+            id: format!("{}:{}:model", position.session_id, position.index),
+            session_id: position.session_id.to_string(),
+            timestamp: timestamp.to_string(),
+            role: MessageRole::System,
+            msg_type: MessageType::StateChange,
+            content: format!("Model changed: {} → {}", self.previous, self.current).into(),
+            metadata: Some(metadata),
+            parent_id: None,
+            tool_use_message_id: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_switches_are_recorded_once() {
+        let mut tracker = ModelTracker::new();
+        assert_eq!(tracker.observe("claude-sonnet-4", "t1"), None);
+        assert_eq!(tracker.observe("claude-sonnet-4", "t2"), None);
+        assert_eq!(tracker.observe(UNKNOWN_MODEL, "t3"), None);
+        assert_eq!(tracker.observe(SYNTHETIC_MODEL, "t3"), None);
+        let change = tracker.observe("claude-opus-4", "t4").unwrap();
+        assert_eq!(change.previous, "claude-sonnet-4");
+        assert_eq!(change.current, "claude-opus-4");
+        assert!(tracker.observe("claude-sonnet-4", "t5").is_some());
+
+        let history: Vec<(&str, &str)> = tracker
+            .history()
+            .iter()
+            .map(|s| (s.timestamp.as_str(), s.model.as_str()))
+            .collect();
+        assert_eq!(
+            history,
+            [
+                ("t1", "claude-sonnet-4"),
+                ("t4", "claude-opus-4"),
+                ("t5", "claude-sonnet-4")
+            ]
+        );
+
+        let position = EntryPosition {
+            session_id: "s1",
+            index: 7,
+        };
+        let message = change.message(position, "t4");
+        assert_eq!(message.id, "s1:7:model");
+        assert_eq!(message.msg_type, MessageType::StateChange);
+        assert_eq!(
+            &*message.content,
+            "Model changed: claude-sonnet-4 → claude-opus-4"
+        );
+    }
+
+    #[test]
+    fn test_history_is_capped() {
+        let mut tracker = ModelTracker::new();
+        for i in 0..MAX_HISTORY + 10 {
+            let model = if i % 2 == 0 { "claude-sonnet-4" } else { "claude-opus-4" };
+            tracker.observe(model, &i.to_string());
+        }
+        let history = tracker.history();
+        assert_eq!(history.len(), MAX_HISTORY);
+        assert_eq!(history[0].timestamp, "10");
+        assert_eq!(history[MAX_HISTORY - 1].timestamp, (MAX_HISTORY + 9).to_string());
+    }
+}
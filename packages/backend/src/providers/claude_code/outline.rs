@@ -0,0 +1,325 @@
+use crate::types::{
+    AgentMessage, CumulativeUsage, MessageRole, MessageType, ToolCount, TurnOutline,
+};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+/// Prompt text kept in an outline.
+const PROMPT_SNIPPET_CHARS: usize = 200;
+/// Oldest marks are dropped beyond this many; there can't be more prompts among the
+/// kept messages.
+const MAX_MARKS: usize = 500;
+
+/// The cumulative usage when each prompt arrived, by message id. Messages only carry
+/// text, so this is what lets an outline put a cost on each turn.
+#[derive(Debug, Clone, Default)]
+pub struct TurnMarks {
+    usage: HashMap<String, CumulativeUsage>,
+    order: VecDeque<String>,
+}
+
+impl TurnMarks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Note the usage so far if `msg` starts a turn. Call as messages are stored.
+    pub fn record(&mut self, msg: &AgentMessage, usage: &CumulativeUsage) {
+        if !is_prompt(msg) || self.usage.contains_key(&msg.id) {
+            return;
+        }
+        self.usage.insert(msg.id.clone(), usage.clone());
+        self.order.push_back(msg.id.clone());
+        if self.order.len() > MAX_MARKS {
+            if let Some(oldest) = self.order.pop_front() {
+                self.usage.remove(&oldest);
+            }
+        }
+    }
+
+    fn get(&self, message_id: &str) -> Option<&CumulativeUsage> {
+        self.usage.get(message_id)
+    }
+}
+
+/// Split `messages` into turns. Messages before the first prompt belong to a turn
+/// that began before them. The last turn is costed up to `current_usage`.
+pub fn build_outline(
+    messages: &[Arc<AgentMessage>],
+    marks: &TurnMarks,
+    current_usage: &CumulativeUsage,
+) -> Vec<TurnOutline> {
+    let mut turns: Vec<TurnBuilder> = Vec::new();
+    for msg in messages {
+        if is_prompt(msg) {
+            turns.push(TurnBuilder::new(msg, Some(&*msg.content)));
+            turns.last_mut().unwrap().start_usage = marks.get(&msg.id).cloned();
+        } else if turns.is_empty() {
+            turns.push(TurnBuilder::new(msg, None));
+        }
+        turns.last_mut().unwrap().add(msg);
+    }
+
+    let mut outline = Vec::with_capacity(turns.len());
+    let mut turns = turns.into_iter().peekable();
+    while let Some(turn) = turns.next() {
+        let end_usage = match turns.peek() {
+            Some(next) => next.start_usage.as_ref(),
+            None => Some(current_usage),
+        };
+        outline.push(turn.finish(end_usage));
+    }
+    outline
+}
+
+/// A user's own message, as opposed to a tool result or a slash command's output.
+fn is_prompt(msg: &AgentMessage) -> bool {
+    msg.role == MessageRole::User
+        && msg.msg_type == MessageType::Text
+        && !msg.content.contains("<command-name>")
+        && !msg.content.contains("<local-command-")
+        && !msg.content.starts_with("[Request interrupted")
+}
+
+struct TurnBuilder {
+    prompt: Option<String>,
+    started_at: String,
+    ended_at: Option<String>,
+    duration_ms: Option<u64>,
+    tools: Vec<ToolCount>,
+    files: Vec<String>,
+    message_count: u64,
+    start_usage: Option<CumulativeUsage>,
+}
+
+impl TurnBuilder {
+    fn new(first: &AgentMessage, prompt: Option<&str>) -> Self {
+        Self {
+            prompt: prompt.map(snippet),
+            started_at: first.timestamp.clone(),
+            ended_at: None,
+            duration_ms: None,
+            tools: Vec::new(),
+            files: Vec::new(),
+            message_count: 0,
+            start_usage: None,
+        }
+    }
+
+    fn add(&mut self, msg: &AgentMessage) {
+        self.message_count += 1;
+        let meta = msg.metadata.as_ref();
+        match msg.msg_type {
+            MessageType::ToolUse => {
+                let name = meta
+                    .and_then(|m| m.get("toolName"))
+                    .and_then(|v| v.as_str())
+                    .unwrap_or(&msg.content);
+                match self.tools.iter_mut().find(|t| t.name == name) {
+                    Some(tool) => tool.count += 1,
+                    None => self.tools.push(ToolCount {
+                        name: name.to_string(),
+                        count: 1,
+                    }),
+                }
+                let file = meta.and_then(|m| m.get("input")).and_then(|input| {
+                    input
+                        .get("file_path")
+                        .or_else(|| input.get("notebook_path"))
+                        .and_then(|v| v.as_str())
+                });
+                if let Some(file) = file.filter(|f| !self.files.iter().any(|seen| seen == f)) {
+                    self.files.push(file.to_string());
+                }
+            }
+            MessageType::StateChange => {
+                let duration = meta
+                    .and_then(|m| m.get("durationMs"))
+                    .and_then(|v| v.as_u64());
+                if let Some(duration) = duration {
+                    self.duration_ms = Some(duration);
+                    self.ended_at = Some(msg.timestamp.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn finish(mut self, end_usage: Option<&CumulativeUsage>) -> TurnOutline {
+        self.tools
+            .sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+        let usage = match (&self.start_usage, end_usage) {
+            (Some(start), Some(end)) => Some(usage_between(start, end)),
+            _ => None,
+        };
+        TurnOutline {
+            prompt: self.prompt,
+            started_at: self.started_at,
+            ended_at: self.ended_at,
+            duration_ms: self.duration_ms,
+            tools: self.tools,
+            files: self.files,
+            message_count: self.message_count,
+            usage,
+        }
+    }
+}
+
+fn usage_between(start: &CumulativeUsage, end: &CumulativeUsage) -> CumulativeUsage {
+    CumulativeUsage {
+        input_tokens: end.input_tokens.saturating_sub(start.input_tokens),
+        output_tokens: end.output_tokens.saturating_sub(start.output_tokens),
+        cache_read_tokens: end
+            .cache_read_tokens
+            .saturating_sub(start.cache_read_tokens),
+        cache_creation_tokens: end
+            .cache_creation_tokens
+            .saturating_sub(start.cache_creation_tokens),
+        cache_creation_5m_tokens: end
+            .cache_creation_5m_tokens
+            .map(|end| end.saturating_sub(start.cache_creation_5m_tokens.unwrap_or(0))),
+        cache_creation_1h_tokens: end
+            .cache_creation_1h_tokens
+            .map(|end| end.saturating_sub(start.cache_creation_1h_tokens.unwrap_or(0))),
+        estimated_cost: (end.estimated_cost - start.estimated_cost).max(0.0),
+    }
+}
+
+fn snippet(text: &str) -> String {
+    let text = text.trim();
+    match text.char_indices().nth(PROMPT_SNIPPET_CHARS) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn message(
+        id: &str,
+        role: MessageRole,
+        msg_type: MessageType,
+        content: &str,
+        metadata: Option<serde_json::Value>,
+    ) -> Arc<AgentMessage> {
+        Arc::new(AgentMessage {
+            id: id.to_string(),
+            session_id: "s1".to_string(),
+            timestamp: "2026-01-01T00:00:00Z".to_string(),
+            role,
+            msg_type,
+            content: content.into(),
+            metadata: metadata.map(|m| serde_json::from_value(m).unwrap()),
+            parent_id: None,
+            tool_use_message_id: None,
+        })
+    }
+
+    fn prompt(id: &str, text: &str) -> Arc<AgentMessage> {
+        message(id, MessageRole::User, MessageType::Text, text, None)
+    }
+
+    fn tool(id: &str, name: &str, file: &str) -> Arc<AgentMessage> {
+        message(
+            id,
+            MessageRole::Assistant,
+            MessageType::ToolUse,
+            name,
+            Some(json!({"toolName": name, "toolId": id, "input": {"file_path": file}})),
+        )
+    }
+
+    fn done(id: &str, duration_ms: u64) -> Arc<AgentMessage> {
+        message(
+            id,
+            MessageRole::System,
+            MessageType::StateChange,
+            "Turn completed",
+            Some(json!({"durationMs": duration_ms})),
+        )
+    }
+
+    fn usage(output_tokens: u64) -> CumulativeUsage {
+        CumulativeUsage {
+            output_tokens,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_turns_split_at_prompts() {
+        let messages = vec![
+            prompt("p1", "fix the parser"),
+            tool("t1", "Read", "src/a.rs"),
+            tool("t2", "Edit", "src/a.rs"),
+            tool("t3", "Edit", "src/b.rs"),
+            done("d1", 4_000),
+            prompt("p2", "now run the tests"),
+            tool("t4", "Bash", ""),
+        ];
+        let mut marks = TurnMarks::new();
+        marks.record(&messages[0], &usage(0));
+        marks.record(&messages[5], &usage(300));
+
+        let turns = build_outline(&messages, &marks, &usage(350));
+        assert_eq!(turns.len(), 2);
+        assert_eq!(turns[0].prompt.as_deref(), Some("fix the parser"));
+        assert_eq!(turns[0].duration_ms, Some(4_000));
+        assert!(turns[0].ended_at.is_some());
+        assert_eq!(
+            turns[0].tools[0],
+            ToolCount {
+                name: "Edit".into(),
+                count: 2
+            }
+        );
+        assert_eq!(turns[0].files, vec!["src/a.rs", "src/b.rs"]);
+        assert_eq!(turns[0].usage.as_ref().unwrap().output_tokens, 300);
+        assert_eq!(turns[0].message_count, 5);
+        // Still running.
+        assert!(turns[1].ended_at.is_none());
+        assert_eq!(turns[1].usage.as_ref().unwrap().output_tokens, 50);
+    }
+
+    #[test]
+    fn test_messages_before_first_prompt_form_a_partial_turn() {
+        let messages = vec![
+            tool("t1", "Grep", "src/a.rs"),
+            done("d1", 9_000),
+            prompt("p1", "thanks"),
+        ];
+        let mut marks = TurnMarks::new();
+        marks.record(&messages[2], &usage(1_000));
+
+        let turns = build_outline(&messages, &marks, &usage(1_000));
+        assert_eq!(turns.len(), 2);
+        assert!(turns[0].prompt.is_none());
+        assert_eq!(turns[0].started_at, messages[0].timestamp);
+        assert_eq!(turns[0].duration_ms, Some(9_000));
+        // Nothing says what was spent before the log's tail.
+        assert!(turns[0].usage.is_none());
+        assert_eq!(turns[1].prompt.as_deref(), Some("thanks"));
+    }
+
+    #[test]
+    fn test_command_output_does_not_start_a_turn() {
+        let messages = vec![
+            prompt("p1", "hello"),
+            prompt("c1", "<local-command-stdout>ok</local-command-stdout>"),
+        ];
+        let turns = build_outline(&messages, &TurnMarks::new(), &usage(0));
+        assert_eq!(turns.len(), 1);
+        assert_eq!(turns[0].message_count, 2);
+    }
+
+    #[test]
+    fn test_long_prompt_is_cut() {
+        let messages = vec![prompt("p1", &"é".repeat(500))];
+        let turns = build_outline(&messages, &TurnMarks::new(), &usage(0));
+        let prompt = turns[0].prompt.as_deref().unwrap();
+        assert_eq!(prompt.chars().count(), PROMPT_SNIPPET_CHARS + 3);
+    }
+}
@@ -0,0 +1,200 @@
+//! The permission mode a session runs in: `default`, `acceptEdits`, `plan` or
+//! `bypassPermissions`. User entries record the mode they were sent in, as do some
+//! system entries; approved plan mode tool calls switch it in between.
+
+use super::jsonl_parser::{RawContentBlock, RawEntry};
+use super::message_mapper::EntryPosition;
+use crate::types::{AgentMessage, MessageRole, MessageType};
+use std::collections::HashMap;
+
+/// Tool calls are never approved in this mode, so nothing waits for permission.
+pub const BYPASS_PERMISSIONS: &str = "bypassPermissions";
+const DEFAULT_MODE: &str = "default";
+const PLAN_MODE: &str = "plan";
+
+#[derive(Debug, Clone, Default)]
+pub struct PermissionModeTracker {
+    mode: Option<String>,
+    /// Plan mode tool calls waiting for their result, with the mode each switches to.
+    pending: HashMap<String, &'static str>,
+}
+
+/// The mode changed with an entry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModeChange {
+    pub from: Option<String>,
+    pub to: String,
+}
+
+impl PermissionModeTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn mode(&self) -> Option<&str> {
+        self.mode.as_deref()
+    }
+
+    /// Follow the mode through `entry`, returning the change if there was one.
+    pub fn observe(&mut self, entry: &RawEntry) -> Option<ModeChange> {
+        let before = self.mode.clone();
+        match entry {
+            RawEntry::Assistant(msg) if msg.is_sidechain != Some(true) => {
+                for block in &msg.message.content {
+                    let RawContentBlock::ToolUse { id, name, .. } = block else {
+                        continue;
+                    };
+                    let to = match name.as_str() {
+                        "EnterPlanMode" => PLAN_MODE,
+                        "ExitPlanMode" => DEFAULT_MODE,
+                        _ => continue,
+                    };
+                    self.pending.insert(id.clone(), to);
+                }
+            }
+            RawEntry::User(msg) if msg.is_sidechain != Some(true) => {
+                // A rejected plan keeps the session in plan mode.
+                let results = msg.message.content.as_array().into_iter().flatten();
+                for block in results {
+                    let Some(id) = block.get("tool_use_id").and_then(|v| v.as_str()) else {
+                        continue;
+                    };
+                    let Some(to) = self.pending.remove(id) else {
+                        continue;
+                    };
+                    if block.get("is_error").and_then(|v| v.as_bool()) != Some(true) {
+                        self.mode = Some(to.to_string());
+                    }
+                }
+                // What the entry says beats what the tool calls suggest.
+                if let Some(mode) = msg.permission_mode.as_deref().filter(|m| !m.is_empty()) {
+                    self.mode = Some(mode.to_string());
+                }
+            }
+            RawEntry::System(sys) => {
+                if let Some(mode) = sys.permission_mode.as_deref().filter(|m| !m.is_empty()) {
+                    self.mode = Some(mode.to_string());
+                }
+            }
+            _ => {}
+        }
+        let to = self.mode.clone()?;
+        (before.as_deref() != Some(to.as_str())).then_some(ModeChange { from: before, to })
+    }
+}
+
+impl ModeChange {
+    /// Whether the change is worth a message: a session found in the default mode
+    /// didn't switch to it.
+    pub fn is_notable(&self) -> bool {
+        self.from.is_some() || self.to != DEFAULT_MODE
+    }
+
+    /// A message marking the change in the transcript, with an id derived from the
+    /// entry that brought it.
+    pub fn message(&self, position: EntryPosition, timestamp: &str) -> AgentMessage {
+        let mut metadata = HashMap::new();
+        metadata.insert("permissionMode".to_string(), serde_json::json!(self.to));
+        if let Some(from) = &self.from {
+            metadata.insert("previousMode".to_string(), serde_json::json!(from));
+        }
+        AgentMessage {
+            id: format!("{}:{}:mode", position.session_id, position.index),
+            session_id: position.session_id.to_string(),
+            timestamp: timestamp.to_string(),
+            role: MessageRole::System,
+            msg_type: MessageType::StateChange,
+            content: match &self.from {
+                Some(from) => format!("Permission mode: {} → {}", from, self.to),
+                None => format!("Permission mode: {}", self.to),
+            }
+            .into(),
+            metadata: Some(metadata),
+            parent_id: None,
+            tool_use_message_id: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::claude_code::jsonl_parser::parse_jsonl_line;
+
+    fn entry(line: &str) -> RawEntry {
+        parse_jsonl_line(line).unwrap()
+    }
+
+    fn prompt(mode: &str) -> RawEntry {
+        entry(&format!(
+            r#"{{"type":"user","permissionMode":"{}","message":{{"role":"user","content":"go"}}}}"#,
+            mode
+        ))
+    }
+
+    fn tool_call(id: &str, name: &str) -> RawEntry {
+        entry(&format!(
+            r#"{{"type":"assistant","message":{{"content":[{{"type":"tool_use","id":"{}","name":"{}","input":{{}}}}]}}}}"#,
+            id, name
+        ))
+    }
+
+    fn tool_result(id: &str, is_error: bool) -> RawEntry {
+        entry(&format!(
+            r#"{{"type":"user","message":{{"role":"user","content":[{{"type":"tool_result","tool_use_id":"{}","content":"ok","is_error":{}}}]}}}}"#,
+            id, is_error
+        ))
+    }
+
+    #[test]
+    fn test_mode_from_user_entries() {
+        let mut tracker = PermissionModeTracker::new();
+        let change = tracker.observe(&prompt("default")).unwrap();
+        assert_eq!(change.from, None);
+        assert!(!change.is_notable());
+        assert_eq!(tracker.observe(&prompt("default")), None);
+
+        let change = tracker.observe(&prompt("acceptEdits")).unwrap();
+        assert_eq!(change.from.as_deref(), Some("default"));
+        assert!(change.is_notable());
+        assert_eq!(tracker.mode(), Some("acceptEdits"));
+    }
+
+    #[test]
+    fn test_plan_mode_enter_and_exit() {
+        let mut tracker = PermissionModeTracker::new();
+        tracker.observe(&prompt("default"));
+
+        // Nothing changes until the call is approved.
+        assert_eq!(tracker.observe(&tool_call("t1", "EnterPlanMode")), None);
+        let change = tracker.observe(&tool_result("t1", false)).unwrap();
+        assert_eq!(change.to, "plan");
+
+        // A rejected plan stays in plan mode.
+        tracker.observe(&tool_call("t2", "ExitPlanMode"));
+        assert_eq!(tracker.observe(&tool_result("t2", true)), None);
+        assert_eq!(tracker.mode(), Some("plan"));
+
+        tracker.observe(&tool_call("t3", "ExitPlanMode"));
+        let change = tracker.observe(&tool_result("t3", false)).unwrap();
+        assert_eq!(change.from.as_deref(), Some("plan"));
+        assert_eq!(change.to, "default");
+    }
+
+    #[test]
+    fn test_session_starting_in_bypass_mode_is_notable() {
+        let mut tracker = PermissionModeTracker::new();
+        let change = tracker.observe(&prompt(BYPASS_PERMISSIONS)).unwrap();
+        assert!(change.is_notable());
+        let message = change.message(
+            EntryPosition {
+                session_id: "s1",
+                index: 3,
+            },
+            "2026-01-01T00:00:00Z",
+        );
+        assert_eq!(message.id, "s1:3:mode");
+        assert_eq!(message.msg_type, MessageType::StateChange);
+        assert_eq!(&*message.content, "Permission mode: bypassPermissions");
+    }
+}
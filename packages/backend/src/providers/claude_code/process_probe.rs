@@ -0,0 +1,231 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::process::Command;
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, Signal, System, UpdateKind};
+
+/// Returns the working directories of running Claude processes, or None if processes
+/// can't be listed (in which case liveness is left unknown). On by default; inside a
+/// container the host's processes aren't visible, so it's turned off there.
+pub type ProcessLookup = fn() -> Option<HashSet<String>>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClaudeProcess {
+    pub pid: u32,
+    pub cwd: String,
+}
+
+/// Signals `POST /api/sessions/{id}/interrupt` may send.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum InterruptSignal {
+    /// What Ctrl+C sends; Claude Code stops the current turn.
+    #[default]
+    Sigint,
+    Sigterm,
+    Sighup,
+}
+
+impl InterruptSignal {
+    fn to_sysinfo(self) -> Signal {
+        match self {
+            InterruptSignal::Sigint => Signal::Interrupt,
+            InterruptSignal::Sigterm => Signal::Term,
+            InterruptSignal::Sighup => Signal::Hangup,
+        }
+    }
+}
+
+impl std::fmt::Display for InterruptSignal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InterruptSignal::Sigint => write!(f, "SIGINT"),
+            InterruptSignal::Sigterm => write!(f, "SIGTERM"),
+            InterruptSignal::Sighup => write!(f, "SIGHUP"),
+        }
+    }
+}
+
+/// Working directories of processes whose name contains "claude" (the CLI sets its
+/// process title to `claude`), asking `ps` and `lsof` where processes can't be listed
+/// directly. Blocking; run it off the async workers.
+pub fn claude_process_cwds() -> Option<HashSet<String>> {
+    match claude_processes() {
+        Some(processes) => Some(processes.into_iter().map(|process| process.cwd).collect()),
+        None => ps_claude_cwds(),
+    }
+}
+
+/// Working directories of Claude processes from `ps` and `lsof`. None if either
+/// command fails; an empty set if no Claude process is running.
+fn ps_claude_cwds() -> Option<HashSet<String>> {
+    let ps_output = Command::new("ps").args(["-eo", "pid,comm"]).output().ok()?;
+    if !ps_output.status.success() {
+        return None;
+    }
+    let pids = parse_ps_claude_pids(&String::from_utf8_lossy(&ps_output.stdout));
+    if pids.is_empty() {
+        return Some(HashSet::new());
+    }
+
+    let pid_csv = pids
+        .iter()
+        .map(|pid| pid.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    let lsof_output = Command::new("lsof")
+        .args(["-a", "-d", "cwd", "-p", &pid_csv, "-Fn"])
+        .output()
+        .ok()?;
+    if !lsof_output.status.success() {
+        return None;
+    }
+    Some(parse_lsof_cwds(&String::from_utf8_lossy(&lsof_output.stdout)))
+}
+
+/// Parse `ps -eo pid,comm` output and return PIDs of processes whose comm contains "claude".
+fn parse_ps_claude_pids(output: &str) -> Vec<u32> {
+    let mut pids = Vec::new();
+    for line in output.lines() {
+        let line = line.trim();
+        let mut parts = line.splitn(2, char::is_whitespace);
+        let pid_str = match parts.next() {
+            Some(s) => s.trim(),
+            None => continue,
+        };
+        let comm = match parts.next() {
+            Some(s) => s.trim(),
+            None => continue,
+        };
+        if let Ok(pid) = pid_str.parse::<u32>() {
+            if comm.contains("claude") {
+                pids.push(pid);
+            }
+        }
+    }
+    pids
+}
+
+/// Parse `lsof -Fn` output and return the set of cwd paths.
+/// lsof -Fn output format: lines starting with 'p' (PID) and 'n' (name/path).
+fn parse_lsof_cwds(output: &str) -> HashSet<String> {
+    let mut cwds = HashSet::new();
+    for line in output.lines() {
+        if let Some(path) = line.strip_prefix('n') {
+            let path = path.trim();
+            if !path.is_empty() {
+                cwds.insert(path.to_string());
+            }
+        }
+    }
+    cwds
+}
+
+/// Running Claude processes with their working directories. Blocking, like
+/// [`claude_process_cwds`].
+pub fn claude_processes() -> Option<Vec<ClaudeProcess>> {
+    let mut system = System::new();
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::All,
+        true,
+        ProcessRefreshKind::nothing().with_cwd(UpdateKind::Always),
+    );
+    if system.processes().is_empty() {
+        return None;
+    }
+    Some(
+        system
+            .processes()
+            .iter()
+            .filter(|(_, p)| p.name().to_string_lossy().contains("claude"))
+            .filter_map(|(pid, p)| {
+                Some(ClaudeProcess {
+                    pid: pid.as_u32(),
+                    cwd: p.cwd()?.to_string_lossy().into_owned(),
+                })
+            })
+            .collect(),
+    )
+}
+
+/// Send `signal` to `pid`, provided it is still a Claude process. Blocking.
+pub fn signal_claude_process(pid: u32, signal: InterruptSignal) -> Result<(), String> {
+    let pid = Pid::from_u32(pid);
+    let mut system = System::new();
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&[pid]),
+        true,
+        ProcessRefreshKind::nothing(),
+    );
+    let process = system
+        .process(pid)
+        .filter(|p| p.name().to_string_lossy().contains("claude"))
+        .ok_or_else(|| format!("process {} has exited", pid))?;
+    match process.kill_with(signal.to_sysinfo()) {
+        Some(true) => Ok(()),
+        Some(false) => Err(format!("could not signal process {}", pid)),
+        None => Err(format!("{} is not supported on this platform", signal)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ps_claude_pids_typical() {
+        let output = "  PID COMM\n  501 zsh\n 1234 claude\n 5678 node\n 9012 claude\n";
+        let pids = parse_ps_claude_pids(output);
+        assert_eq!(pids, vec![1234, 9012]);
+    }
+
+    #[test]
+    fn test_parse_ps_claude_pids_no_match() {
+        let output = "  PID COMM\n  501 zsh\n 5678 node\n";
+        let pids = parse_ps_claude_pids(output);
+        assert!(pids.is_empty());
+    }
+
+    #[test]
+    fn test_parse_ps_claude_pids_empty() {
+        let pids = parse_ps_claude_pids("");
+        assert!(pids.is_empty());
+    }
+
+    #[test]
+    fn test_parse_ps_claude_pids_path_comm() {
+        // On some systems comm may show the full path
+        let output = "  PID COMM\n 1234 /usr/local/bin/claude\n";
+        let pids = parse_ps_claude_pids(output);
+        assert_eq!(pids, vec![1234]);
+    }
+
+    #[test]
+    fn test_parse_lsof_cwds_typical() {
+        let output = "p1234\nn/Users/daiki/Projects/my-app\np5678\nn/Users/daiki/Projects/other\n";
+        let cwds = parse_lsof_cwds(output);
+        assert_eq!(cwds.len(), 2);
+        assert!(cwds.contains("/Users/daiki/Projects/my-app"));
+        assert!(cwds.contains("/Users/daiki/Projects/other"));
+    }
+
+    #[test]
+    fn test_parse_lsof_cwds_empty() {
+        let cwds = parse_lsof_cwds("");
+        assert!(cwds.is_empty());
+    }
+
+    #[test]
+    fn test_parse_lsof_cwds_no_n_lines() {
+        let output = "p1234\np5678\n";
+        let cwds = parse_lsof_cwds(output);
+        assert!(cwds.is_empty());
+    }
+
+    #[test]
+    fn test_parse_lsof_cwds_deduplicates() {
+        let output = "p1234\nn/Users/daiki/Projects/app\np5678\nn/Users/daiki/Projects/app\n";
+        let cwds = parse_lsof_cwds(output);
+        assert_eq!(cwds.len(), 1);
+        assert!(cwds.contains("/Users/daiki/Projects/app"));
+    }
+}
@@ -0,0 +1,67 @@
+//! Settings for the sessions of one project, from a `[projects."<path>"]` section of
+//! the config file, in place of the global ones.
+
+use super::SessionSettings;
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+pub struct ProjectSettings {
+    /// The project directory the section is for, without a trailing separator
+    /// unless it is the root.
+    pub path: String,
+    pub settings: Arc<SessionSettings>,
+}
+
+/// Whether `path` is `project` or a directory inside it.
+pub fn covers(project: &str, path: &str) -> bool {
+    path.strip_prefix(project).is_some_and(|rest| {
+        rest.is_empty() || rest.starts_with(['/', '\\']) || project.ends_with(['/', '\\'])
+    })
+}
+
+/// The section for the most specific project `path` is in, if any.
+pub fn matching<'a>(projects: &'a [ProjectSettings], path: &str) -> Option<&'a ProjectSettings> {
+    projects
+        .iter()
+        .filter(|project| covers(&project.path, path))
+        .max_by_key(|project| project.path.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project(path: &str) -> ProjectSettings {
+        ProjectSettings {
+            path: path.to_string(),
+            settings: Arc::new(SessionSettings::default()),
+        }
+    }
+
+    #[test]
+    fn test_covers_whole_components_only() {
+        assert!(covers("/Users/me/infra", "/Users/me/infra"));
+        assert!(covers("/Users/me/infra", "/Users/me/infra/terraform"));
+        assert!(covers("C:\\work", "C:\\work\\api"));
+        assert!(!covers("/Users/me/infra", "/Users/me/infra-old"));
+        assert!(!covers("/Users/me/infra", "/Users/me"));
+        assert!(covers("/", "/Users/me"));
+    }
+
+    #[test]
+    fn test_longest_prefix_wins() {
+        let projects = [
+            project("/Users/me"),
+            project("/Users/me/infra/terraform"),
+            project("/Users/me/infra"),
+        ];
+        let path = |p: &str| matching(&projects, p).map(|m| m.path.as_str());
+        assert_eq!(
+            path("/Users/me/infra/terraform/modules"),
+            Some("/Users/me/infra/terraform")
+        );
+        assert_eq!(path("/Users/me/infra/ansible"), Some("/Users/me/infra"));
+        assert_eq!(path("/Users/me/scratch"), Some("/Users/me"));
+        assert_eq!(path("/srv/app"), None);
+    }
+}
@@ -0,0 +1,96 @@
+use super::jsonl_parser::{parse_jsonl_chunk, RawEntry};
+use super::state_machine::get_entry_timestamp;
+use std::time::Duration;
+
+/// Longest pause between two replayed entries, whatever the gap in the recording.
+const MAX_DELAY: Duration = Duration::from_secs(10);
+
+/// One entry of a recorded log and how long to wait before feeding it.
+#[derive(Debug, Clone)]
+pub struct ReplayStep {
+    pub delay: Duration,
+    pub entry: RawEntry,
+}
+
+/// Parse a recorded Claude Code log into steps spaced like the original, sped up by
+/// `speed` (2.0 plays twice as fast). Entries without a timestamp follow immediately.
+pub fn plan_replay(text: &str, speed: f64) -> Vec<ReplayStep> {
+    let mut text = text.to_string();
+    if !text.ends_with('\n') {
+        text.push('\n');
+    }
+    let mut previous: Option<i64> = None;
+    parse_jsonl_chunk(&text)
+        .entries
+        .into_iter()
+        .map(|entry| {
+            let at = get_entry_timestamp(&entry);
+            let gap_ms = match (previous, at) {
+                (Some(prev), Some(at)) => (at - prev).max(0),
+                _ => 0,
+            };
+            if at.is_some() {
+                previous = at;
+            }
+            let delay = Duration::from_secs_f64(gap_ms as f64 / 1000.0 / speed).min(MAX_DELAY);
+            ReplayStep { delay, entry }
+        })
+        .collect()
+}
+
+/// Make a recorded entry look as if it was just written by `session_id`, so that
+/// time-based transitions treat the replay as live.
+pub fn restamp(entry: &mut RawEntry, session_id: &str, now: &str) {
+    let (timestamp, entry_session) = match entry {
+        RawEntry::User(m) => (&mut m.timestamp, Some(&mut m.session_id)),
+        RawEntry::Assistant(m) => (&mut m.timestamp, Some(&mut m.session_id)),
+        RawEntry::System(m) => (&mut m.timestamp, Some(&mut m.session_id)),
+        RawEntry::Progress(m) => (&mut m.timestamp, None),
+        RawEntry::Hook(m) => (&mut m.timestamp, None),
+        RawEntry::Summary(_) | RawEntry::Oversized(_) | RawEntry::Other => return,
+    };
+    if timestamp.is_some() {
+        *timestamp = Some(now.to_string());
+    }
+    if let Some(entry_session) = entry_session.filter(|s| s.is_some()) {
+        *entry_session = Some(session_id.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = r#"{"type":"user","message":{"role":"user","content":"hi"},"uuid":"u1","timestamp":"2025-01-01T00:00:00Z","sessionId":"orig"}
+{"type":"file-history-snapshot"}
+{"type":"assistant","message":{"model":"claude-sonnet-4-20250514","content":[]},"uuid":"a1","timestamp":"2025-01-01T00:00:04Z","sessionId":"orig"}
+{"type":"assistant","message":{"model":"claude-sonnet-4-20250514","content":[]},"uuid":"a2","timestamp":"2025-01-01T01:00:00Z","sessionId":"orig"}"#;
+
+    #[test]
+    fn test_delays_are_scaled_and_capped() {
+        let steps = plan_replay(LOG, 2.0);
+        let delays: Vec<Duration> = steps.iter().map(|s| s.delay).collect();
+        assert_eq!(
+            delays,
+            [
+                Duration::ZERO,
+                Duration::ZERO,
+                Duration::from_secs(2),
+                MAX_DELAY
+            ]
+        );
+    }
+
+    #[test]
+    fn test_restamp() {
+        let mut steps = plan_replay(LOG, 1.0);
+        restamp(&mut steps[0].entry, "replay-1", "2026-01-01T00:00:00Z");
+        match &steps[0].entry {
+            RawEntry::User(m) => {
+                assert_eq!(m.timestamp.as_deref(), Some("2026-01-01T00:00:00Z"));
+                assert_eq!(m.session_id.as_deref(), Some("replay-1"));
+            }
+            other => panic!("Expected user entry, got {:?}", other),
+        }
+    }
+}
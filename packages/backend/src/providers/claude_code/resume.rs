@@ -0,0 +1,205 @@
+use super::jsonl_parser::RawEntry;
+use std::collections::HashSet;
+
+/// Only the first entries of a log are checked for links to an earlier session.
+const RESUME_LOOKAHEAD_ENTRIES: usize = 50;
+/// Message uuids remembered per session, enough to cover any realistic resume point.
+const MAX_KNOWN_UUIDS: usize = 20_000;
+
+/// Something near the start of a log that points at an earlier conversation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResumeRef {
+    /// An entry written under another session id (history carried over on resume).
+    Session(String),
+    /// A parentUuid that does not belong to any earlier entry of this log.
+    Parent(String),
+    /// The leafUuid of a summary entry. Weaker: summaries can describe other conversations.
+    Leaf(String),
+}
+
+/// Message uuids of one session, plus unresolved references to the session it may continue.
+#[derive(Debug, Clone, Default)]
+pub struct ResumeTracker {
+    known_uuids: HashSet<String>,
+    entries_seen: usize,
+    pending: Vec<ResumeRef>,
+}
+
+impl ResumeTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an entry from the log of `session_id`.
+    pub fn observe(&mut self, session_id: &str, entry: &RawEntry) {
+        let early = self.entries_seen < RESUME_LOOKAHEAD_ENTRIES;
+        self.entries_seen += 1;
+
+        let (uuid, parent_uuid, entry_session) = match entry {
+            RawEntry::User(m) => (&m.uuid, &m.parent_uuid, &m.session_id),
+            RawEntry::Assistant(m) => (&m.uuid, &m.parent_uuid, &m.session_id),
+            RawEntry::Summary(s) => {
+                if let (true, Some(leaf)) = (early, &s.leaf_uuid) {
+                    self.add_ref(ResumeRef::Leaf(leaf.clone()));
+                }
+                return;
+            }
+            _ => return,
+        };
+
+        if early {
+            if let Some(sid) = entry_session.as_deref() {
+                if !sid.is_empty() && sid != session_id {
+                    self.add_ref(ResumeRef::Session(sid.to_string()));
+                }
+            }
+            if let Some(parent) = parent_uuid {
+                if !self.known_uuids.contains(parent) {
+                    self.add_ref(ResumeRef::Parent(parent.clone()));
+                }
+            }
+        }
+        if let Some(uuid) = uuid {
+            if self.known_uuids.len() < MAX_KNOWN_UUIDS {
+                self.known_uuids.insert(uuid.clone());
+            }
+        }
+    }
+
+    fn add_ref(&mut self, r: ResumeRef) {
+        if !self.pending.contains(&r) {
+            self.pending.push(r);
+        }
+    }
+
+    pub fn knows(&self, uuid: &str) -> bool {
+        self.known_uuids.contains(uuid)
+    }
+
+    pub fn pending(&self) -> &[ResumeRef] {
+        &self.pending
+    }
+
+    /// The session has been linked (or can no longer be); stop retrying.
+    pub fn clear_pending(&mut self) {
+        self.pending.clear();
+    }
+}
+
+/// Pick the session that `refs` point to among `candidates` (id, tracker), which the
+/// caller limits to the same project. Carried-over session ids and dangling parent
+/// uuids are trusted as-is; summary leaves only count when they all name one session.
+pub fn resolve_predecessor<'a>(
+    refs: &[ResumeRef],
+    candidates: &[(&'a str, &'a ResumeTracker)],
+) -> Option<&'a str> {
+    for r in refs {
+        let owner = match r {
+            ResumeRef::Session(id) => candidates.iter().find(|(cid, _)| cid == id),
+            ResumeRef::Parent(uuid) => candidates.iter().find(|(_, t)| t.knows(uuid)),
+            ResumeRef::Leaf(_) => continue,
+        };
+        if let Some((id, _)) = owner {
+            return Some(id);
+        }
+    }
+
+    let mut leaf_owners: Vec<&str> = refs
+        .iter()
+        .filter_map(|r| match r {
+            ResumeRef::Leaf(uuid) => candidates
+                .iter()
+                .find(|(_, t)| t.knows(uuid))
+                .map(|(id, _)| *id),
+            _ => None,
+        })
+        .collect();
+    leaf_owners.sort_unstable();
+    leaf_owners.dedup();
+    match leaf_owners.as_slice() {
+        [only] => Some(only),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::claude_code::jsonl_parser::parse_jsonl_line;
+
+    fn user(session_id: &str, uuid: &str, parent: Option<&str>) -> RawEntry {
+        let parent = parent.map_or("null".to_string(), |p| format!("\"{p}\""));
+        parse_jsonl_line(&format!(
+            r#"{{"type":"user","message":{{"role":"user","content":"hi"}},"uuid":"{uuid}","parentUuid":{parent},"sessionId":"{session_id}"}}"#
+        ))
+        .unwrap()
+    }
+
+    fn tracker(session_id: &str, entries: &[RawEntry]) -> ResumeTracker {
+        let mut t = ResumeTracker::new();
+        for e in entries {
+            t.observe(session_id, e);
+        }
+        t
+    }
+
+    #[test]
+    fn test_fresh_session_has_no_refs() {
+        let t = tracker(
+            "new",
+            &[user("new", "u1", None), user("new", "u2", Some("u1"))],
+        );
+        assert!(t.pending().is_empty());
+        assert!(t.knows("u2"));
+    }
+
+    #[test]
+    fn test_carried_over_history_and_dangling_parent() {
+        let t = tracker(
+            "new",
+            &[user("old", "u1", None), user("new", "u9", Some("x7"))],
+        );
+        assert_eq!(
+            t.pending(),
+            &[
+                ResumeRef::Session("old".into()),
+                ResumeRef::Parent("x7".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_only_early_entries_count() {
+        let mut entries: Vec<RawEntry> = (0..RESUME_LOOKAHEAD_ENTRIES)
+            .map(|i| user("new", &format!("u{i}"), None))
+            .collect();
+        entries.push(user("new", "late", Some("elsewhere")));
+        assert!(tracker("new", &entries).pending().is_empty());
+    }
+
+    #[test]
+    fn test_resolve_prefers_strong_refs() {
+        let old = tracker("old", &[user("old", "a1", None)]);
+        let other = tracker("other", &[user("other", "b1", None)]);
+        let candidates = [("old", &old), ("other", &other)];
+
+        let refs = [ResumeRef::Leaf("b1".into()), ResumeRef::Parent("a1".into())];
+        assert_eq!(resolve_predecessor(&refs, &candidates), Some("old"));
+        let refs = [ResumeRef::Session("other".into())];
+        assert_eq!(resolve_predecessor(&refs, &candidates), Some("other"));
+        let refs = [ResumeRef::Parent("zz".into())];
+        assert_eq!(resolve_predecessor(&refs, &candidates), None);
+    }
+
+    #[test]
+    fn test_ambiguous_summary_leaves_are_ignored() {
+        let old = tracker("old", &[user("old", "a1", None)]);
+        let other = tracker("other", &[user("other", "b1", None)]);
+        let candidates = [("old", &old), ("other", &other)];
+
+        let refs = [ResumeRef::Leaf("a1".into())];
+        assert_eq!(resolve_predecessor(&refs, &candidates), Some("old"));
+        let refs = [ResumeRef::Leaf("a1".into()), ResumeRef::Leaf("b1".into())];
+        assert_eq!(resolve_predecessor(&refs, &candidates), None);
+    }
+}
@@ -0,0 +1,148 @@
+use std::ops::Range;
+
+/// A parsed search query: every term and phrase must occur in the searched text.
+///
+/// `"read_new_content" watcher` is the phrase `read_new_content` plus the term
+/// `watcher`. An unterminated quote runs to the end of the query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchQuery {
+    pub terms: Vec<String>,
+    pub phrases: Vec<String>,
+    pub case_sensitive: bool,
+}
+
+impl SearchQuery {
+    pub fn parse(raw: &str, case_sensitive: bool) -> Self {
+        let mut terms = Vec::new();
+        let mut phrases = Vec::new();
+        let mut rest = raw;
+        while let Some(quote) = rest.find('"') {
+            terms.extend(rest[..quote].split_whitespace().map(str::to_string));
+            let after = &rest[quote + 1..];
+            let (phrase, next) = match after.find('"') {
+                Some(end) => (&after[..end], &after[end + 1..]),
+                None => (after, ""),
+            };
+            if !phrase.trim().is_empty() {
+                phrases.push(phrase.to_string());
+            }
+            rest = next;
+        }
+        terms.extend(rest.split_whitespace().map(str::to_string));
+
+        let normalize = |s: String| if case_sensitive { s } else { s.to_lowercase() };
+        Self {
+            terms: terms.into_iter().map(normalize).collect(),
+            phrases: phrases.into_iter().map(normalize).collect(),
+            case_sensitive,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty() && self.phrases.is_empty()
+    }
+
+    /// Phrases first, then terms.
+    pub fn needles(&self) -> impl Iterator<Item = &str> {
+        self.phrases.iter().chain(&self.terms).map(String::as_str)
+    }
+
+    /// Where `needle` (one of `needles()`) occurs in `text`, as a byte range of `text`.
+    pub fn find_needle(&self, text: &str, needle: &str) -> Option<Range<usize>> {
+        if self.case_sensitive {
+            text.find(needle).map(|start| start..start + needle.len())
+        } else {
+            find_ignore_case(text, needle)
+        }
+    }
+
+    /// If every needle occurs in `text`, the span of the first one.
+    pub fn find(&self, text: &str) -> Option<Range<usize>> {
+        let mut first = None;
+        for needle in self.needles() {
+            let span = self.find_needle(text, needle)?;
+            first.get_or_insert(span);
+        }
+        first
+    }
+}
+
+/// Case-insensitive search for an already-lowercased needle, returning byte offsets
+/// into the original text (lowercasing can change the length of some characters).
+fn find_ignore_case(text: &str, needle_lower: &str) -> Option<Range<usize>> {
+    let mut lowered = String::with_capacity(text.len());
+    // Original offset of the character each lowered byte came from.
+    let mut origin: Vec<usize> = Vec::with_capacity(text.len());
+    for (offset, c) in text.char_indices() {
+        let before = lowered.len();
+        lowered.extend(c.to_lowercase());
+        origin.resize(origin.len() + lowered.len() - before, offset);
+    }
+
+    let start = lowered.find(needle_lower)?;
+    let end = start + needle_lower.len();
+    let orig_start = origin[start];
+    let orig_end = match origin.get(end) {
+        Some(&o) if o != origin[end - 1] => o,
+        _ => {
+            // Ends at the text's end, or inside a character that lowercases to several.
+            let last = origin[end - 1];
+            last + text[last..].chars().next().map_or(0, char::len_utf8)
+        }
+    };
+    Some(orig_start..orig_end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_terms_and_phrases() {
+        let q = SearchQuery::parse(r#""read_new_content" Watcher  tail"#, false);
+        assert_eq!(q.phrases, vec!["read_new_content"]);
+        assert_eq!(q.terms, vec!["watcher", "tail"]);
+    }
+
+    #[test]
+    fn test_parse_phrase_with_punctuation_and_unterminated_quote() {
+        let q = SearchQuery::parse(r#"err "cannot find `Foo`, in scope" "half open"#, true);
+        assert_eq!(q.terms, vec!["err"]);
+        assert_eq!(q.phrases, vec!["cannot find `Foo`, in scope", "half open"]);
+        assert!(SearchQuery::parse(r#" "" "#, false).is_empty());
+    }
+
+    #[test]
+    fn test_case_sensitive_identifier() {
+        let q = SearchQuery::parse("PathBuf", true);
+        assert_eq!(q.find("use std::path::PathBuf;"), Some(15..22));
+        assert_eq!(q.find("convert the pathbuf to a string"), None);
+
+        let q = SearchQuery::parse("PathBuf", false);
+        assert_eq!(q.find("convert the pathbuf"), Some(12..19));
+    }
+
+    #[test]
+    fn test_phrase_requires_exact_sequence() {
+        let q = SearchQuery::parse(r#""read new content" watcher"#, false);
+        assert!(q.find("the watcher calls read new content").is_some());
+        assert!(q.find("the watcher will read the new content").is_none());
+        assert!(q.find("read new content").is_none(), "term missing");
+    }
+
+    #[test]
+    fn test_span_of_first_needle() {
+        let q = SearchQuery::parse(r#"beta "alpha one""#, false);
+        assert_eq!(q.find("Alpha One, then beta"), Some(0..9));
+    }
+
+    #[test]
+    fn test_ignore_case_offsets_with_multibyte_text() {
+        // 'İ' lowercases to two characters, shifting lowered offsets.
+        let text = "İstanbul ÄRGER";
+        let span = find_ignore_case(text, "ärger").unwrap();
+        assert_eq!(&text[span], "ÄRGER");
+        let span = find_ignore_case(text, "i̇stan").unwrap();
+        assert_eq!(&text[span], "İstan");
+    }
+}
@@ -0,0 +1,57 @@
+use std::collections::{HashSet, VecDeque};
+
+/// Entry uuids remembered per session. Re-reads only overlap the recent end of the
+/// log, so the oldest uuids are forgotten first.
+const MAX_SEEN_ENTRIES: usize = 20_000;
+
+/// The entry uuids a session has already processed, so re-reading part of the log
+/// (after a truncation reset or an overlapping startup read) doesn't add them twice.
+#[derive(Debug, Clone, Default)]
+pub struct SeenEntries {
+    uuids: HashSet<String>,
+    order: VecDeque<String>,
+}
+
+impl SeenEntries {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record `uuid`; false if it was already seen.
+    pub fn insert(&mut self, uuid: &str) -> bool {
+        if self.uuids.contains(uuid) {
+            return false;
+        }
+        if self.order.len() >= MAX_SEEN_ENTRIES {
+            if let Some(oldest) = self.order.pop_front() {
+                self.uuids.remove(&oldest);
+            }
+        }
+        self.uuids.insert(uuid.to_string());
+        self.order.push_back(uuid.to_string());
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_reports_duplicates() {
+        let mut seen = SeenEntries::new();
+        assert!(seen.insert("u1"));
+        assert!(!seen.insert("u1"));
+        assert!(seen.insert("u2"));
+    }
+
+    #[test]
+    fn test_oldest_uuids_are_forgotten() {
+        let mut seen = SeenEntries::new();
+        for i in 0..MAX_SEEN_ENTRIES + 1 {
+            seen.insert(&format!("u{}", i));
+        }
+        assert!(seen.insert("u0"));
+        assert!(!seen.insert(&format!("u{}", MAX_SEEN_ENTRIES)));
+    }
+}
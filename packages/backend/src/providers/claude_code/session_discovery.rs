@@ -1,5 +1,8 @@
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
 use tokio::sync::mpsc;
 use tracing::{debug, info};
 
@@ -14,43 +17,94 @@ pub struct DiscoveredSession {
 pub enum DiscoveryEvent {
     Found(DiscoveredSession),
     Removed(String),
+    /// The log of a dehydrated session was written to again.
+    Written(String),
+    /// The first scan is done; every session it found was sent before this.
+    Scanned,
 }
 
+const SCAN_INTERVAL: Duration = Duration::from_secs(5);
+/// Logs not written to for this long are left alone.
+const MAX_LOG_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Where to look for session logs and how often.
+#[derive(Debug, Clone)]
+pub struct DiscoveryConfig {
+    /// One directory per project, holding a `<session id>.jsonl` log per session.
+    pub projects_dir: PathBuf,
+    pub scan_interval: Duration,
+    /// Logs last modified longer ago than this are not discovered.
+    pub max_log_age: Duration,
+}
+
+impl Default for DiscoveryConfig {
+    /// `~/.claude/projects`, where Claude Code writes its logs.
+    fn default() -> Self {
+        let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("/"));
+        Self {
+            projects_dir: home.join(".claude").join("projects"),
+            scan_interval: SCAN_INTERVAL,
+            max_log_age: MAX_LOG_AGE,
+        }
+    }
+}
+
+/// Sessions the user dismissed, with the time of dismissal.
+/// Shared with the provider so discovery can tell when a dismissed log gets new content.
+pub type DismissedSessions = Arc<Mutex<HashMap<String, SystemTime>>>;
+
+/// Sessions no longer watching their log, with the time they stopped. Shared with the
+/// provider so discovery can tell it when such a log grows.
+pub type DehydratedSessions = Arc<Mutex<HashMap<String, SystemTime>>>;
+
 pub struct SessionDiscovery {
-    claude_projects_dir: PathBuf,
+    config: DiscoveryConfig,
     known_sessions: HashMap<String, DiscoveredSession>,
+    dismissed: DismissedSessions,
+    dehydrated: DehydratedSessions,
     tx: mpsc::UnboundedSender<DiscoveryEvent>,
     shutdown: tokio::sync::watch::Sender<bool>,
+    /// When the last scan finished, in Unix milliseconds; 0 before the first.
+    last_scan: Arc<AtomicI64>,
 }
 
 impl SessionDiscovery {
-    pub fn new(tx: mpsc::UnboundedSender<DiscoveryEvent>) -> Self {
-        let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("/"));
-        let claude_projects_dir = home.join(".claude").join("projects");
-
+    pub fn new(
+        config: DiscoveryConfig,
+        tx: mpsc::UnboundedSender<DiscoveryEvent>,
+        dismissed: DismissedSessions,
+        dehydrated: DehydratedSessions,
+    ) -> Self {
         let (shutdown, _) = tokio::sync::watch::channel(false);
 
         Self {
-            claude_projects_dir,
+            config,
             known_sessions: HashMap::new(),
+            dismissed,
+            dehydrated,
             tx,
             shutdown,
+            last_scan: Arc::new(AtomicI64::new(0)),
         }
     }
 
     pub async fn start(&mut self) {
         // Initial scan
         self.scan_all().await;
+        let _ = self.tx.send(DiscoveryEvent::Scanned);
 
-        let claude_projects_dir = self.claude_projects_dir.clone();
+        let config = self.config.clone();
+        let dismissed = self.dismissed.clone();
+        let dehydrated = self.dehydrated.clone();
         let tx = self.tx.clone();
+        let last_scan = self.last_scan.clone();
         let mut shutdown_rx = self.shutdown.subscribe();
 
         // Keep track of known sessions in the scan loop
         let mut known_sessions: HashMap<String, DiscoveredSession> = self.known_sessions.clone();
 
         tokio::spawn(async move {
-            let mut interval = tokio::time::interval(std::time::Duration::from_secs(5));
+            let mut interval = tokio::time::interval(config.scan_interval);
             interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
 
             loop {
@@ -60,7 +114,8 @@ impl SessionDiscovery {
                         break;
                     }
                     _ = interval.tick() => {
-                        scan_all_inner(&claude_projects_dir, &mut known_sessions, &tx).await;
+                        scan_all_inner(&config, &mut known_sessions, &dismissed, &dehydrated, &tx).await;
+                        last_scan.store(chrono::Utc::now().timestamp_millis(), Ordering::Relaxed);
                     }
                 }
             }
@@ -71,29 +126,39 @@ impl SessionDiscovery {
         let _ = self.shutdown.send(true);
     }
 
+    /// When the last scan finished, in Unix milliseconds.
+    pub fn last_scan_ms(&self) -> Option<i64> {
+        Some(self.last_scan.load(Ordering::Relaxed)).filter(|&ms| ms > 0)
+    }
+
     async fn scan_all(&mut self) {
         scan_all_inner(
-            &self.claude_projects_dir,
+            &self.config,
             &mut self.known_sessions,
+            &self.dismissed,
+            &self.dehydrated,
             &self.tx,
         )
         .await;
+        self.last_scan
+            .store(chrono::Utc::now().timestamp_millis(), Ordering::Relaxed);
     }
 }
 
 async fn scan_all_inner(
-    claude_projects_dir: &Path,
+    config: &DiscoveryConfig,
     known_sessions: &mut HashMap<String, DiscoveredSession>,
+    dismissed: &DismissedSessions,
+    dehydrated: &DehydratedSessions,
     tx: &mpsc::UnboundedSender<DiscoveryEvent>,
 ) {
-    let projects_dir = match tokio::fs::read_dir(claude_projects_dir).await {
+    let projects_dir = match tokio::fs::read_dir(&config.projects_dir).await {
         Ok(d) => d,
         Err(_) => return,
     };
 
     let mut projects_dir = projects_dir;
     let now = std::time::SystemTime::now();
-    let twenty_four_hours = std::time::Duration::from_secs(24 * 60 * 60);
 
     while let Ok(Some(project_entry)) = projects_dir.next_entry().await {
         let project_path = project_entry.path();
@@ -104,11 +169,9 @@ async fn scan_all_inner(
         // Decode project path from directory name
         let dir_name = project_entry.file_name().to_string_lossy().to_string();
         let decoded_project_path = decode_project_path(&dir_name);
-        let project_name = decoded_project_path
-            .split('/')
-            .last()
-            .unwrap_or(&dir_name)
-            .to_string();
+        let project_name = project_name(&decoded_project_path)
+        .unwrap_or(&dir_name)
+        .to_string();
 
         let mut session_dir = match tokio::fs::read_dir(&project_path).await {
             Ok(d) => d,
@@ -123,20 +186,30 @@ async fn scan_all_inner(
                 continue;
             }
 
-            // Check if modified within last 24 hours
-            if let Ok(metadata) = file_entry.metadata().await {
-                if let Ok(modified) = metadata.modified() {
-                    if let Ok(age) = now.duration_since(modified) {
-                        if age > twenty_four_hours {
-                            continue;
-                        }
+            // Skip logs nobody has written to recently
+            let modified = match file_entry.metadata().await {
+                Ok(metadata) => metadata.modified().ok(),
+                Err(_) => None,
+            };
+            if let Some(modified) = modified {
+                if let Ok(age) = now.duration_since(modified) {
+                    if age > config.max_log_age {
+                        continue;
                     }
                 }
             }
 
             let session_id = file_name.trim_end_matches(".jsonl").to_string();
 
-            if known_sessions.contains_key(&session_id) {
+            if let Some(known) = known_sessions.get(&session_id) {
+                // A dismissed session only comes back once its log has new content.
+                if take_if_updated(dismissed, &session_id, modified) {
+                    info!("Re-discovered dismissed session: {}", session_id);
+                    let _ = tx.send(DiscoveryEvent::Found(known.clone()));
+                } else if take_if_updated(dehydrated, &session_id, modified) {
+                    debug!("Log of dehydrated session {} was written to", session_id);
+                    let _ = tx.send(DiscoveryEvent::Written(session_id));
+                }
                 continue;
             }
 
@@ -147,10 +220,7 @@ async fn scan_all_inner(
                 project_name: project_name.clone(),
             };
 
-            info!(
-                "Discovered session: {} ({})",
-                session_id, project_name
-            );
+            info!("Discovered session: {} ({})", session_id, project_name);
 
             known_sessions.insert(session_id, discovered.clone());
             let _ = tx.send(DiscoveryEvent::Found(discovered));
@@ -158,17 +228,152 @@ async fn scan_all_inner(
     }
 }
 
+/// Every log in the directory of `project_path`, however old, newest first. None if
+/// the project has no directory.
+pub async fn scan_project(
+    projects_dir: &Path,
+    project_path: &str,
+) -> Option<Vec<DiscoveredSession>> {
+    let dir_name = encode_project_path(project_path);
+    let mut session_dir = tokio::fs::read_dir(projects_dir.join(&dir_name))
+        .await
+        .ok()?;
+    let decoded_project_path = decode_project_path(&dir_name);
+    let project_name = project_name(&decoded_project_path)
+        .unwrap_or(&dir_name)
+        .to_string();
+
+    let mut logs = Vec::new();
+    while let Ok(Some(file_entry)) = session_dir.next_entry().await {
+        let file_name = file_entry.file_name().to_string_lossy().to_string();
+        let Some(session_id) = file_name.strip_suffix(".jsonl") else {
+            continue;
+        };
+        let modified = match file_entry.metadata().await {
+            Ok(metadata) => metadata.modified().ok(),
+            Err(_) => None,
+        };
+        let discovered = DiscoveredSession {
+            session_id: session_id.to_string(),
+            log_file: file_entry.path(),
+            project_path: decoded_project_path.clone(),
+            project_name: project_name.clone(),
+        };
+        logs.push((modified, discovered));
+    }
+    logs.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+    Some(logs.into_iter().map(|(_, discovered)| discovered).collect())
+}
+
+/// Returns true (and forgets the session) if the session is in `sessions` (dismissed
+/// or dehydrated) and its log file has been modified since the time recorded there.
+fn take_if_updated(
+    sessions: &Arc<Mutex<HashMap<String, SystemTime>>>,
+    session_id: &str,
+    modified: Option<SystemTime>,
+) -> bool {
+    let mut sessions = sessions.lock().unwrap();
+    let Some(since) = sessions.get(session_id) else {
+        return false;
+    };
+    match modified {
+        Some(modified) if modified > *since => {
+            sessions.remove(session_id);
+            true
+        }
+        _ => false,
+    }
+}
+
 /// Decode an encoded project path from the directory name.
-/// Claude Code encodes paths like `-Users-john-Projects-foo` → `/Users/john/Projects/foo`
+/// Claude Code encodes paths like `-Users-john-Projects-foo` → `/Users/john/Projects/foo`,
+/// and on Windows `C:\Users\john\foo` as `C--Users-john-foo`.
 fn decode_project_path(encoded: &str) -> String {
-    if encoded.starts_with('-') {
+    if let Some((drive, rest)) = split_drive(encoded, "--") {
+        return format!("{}:\\{}", drive, rest.replace('-', "\\"));
+    }
+    if let Some(rest) = encoded.strip_prefix('-') {
         // Replace leading dash and internal dashes with /
-        format!("/{}", encoded[1..].replace('-', "/"))
+        format!("/{}", rest.replace('-', "/"))
     } else {
         encoded.replace('-', "/")
     }
 }
 
+/// Encode a path the way Claude Code names its project directories: every character
+/// other than an ASCII letter or digit becomes `-`. Lossy, so two paths encoding the
+/// same way is as close as we can get to them naming the same project.
+pub fn encode_project_path(path: &str) -> String {
+    path.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect()
+}
+
+/// The drive letter of a path starting `<letter><separator>`, and the rest.
+fn split_drive<'a>(path: &'a str, separator: &str) -> Option<(char, &'a str)> {
+    let mut chars = path.chars();
+    let drive = chars.next().filter(char::is_ascii_alphabetic)?;
+    Some((drive, chars.as_str().strip_prefix(separator)?))
+}
+
+/// The drive letter (lowercase) and the rest, `/`-separated, of a Windows path or a
+/// WSL mount of one (`/mnt/c/...`).
+fn windows_parts(path: &str) -> Option<(char, String)> {
+    let (drive, rest) = match path.strip_prefix("/mnt/") {
+        Some(mount) => {
+            let (drive, rest) = split_drive(mount, "")?;
+            if !(rest.is_empty() || rest.starts_with('/')) {
+                return None;
+            }
+            (drive, rest)
+        }
+        None => split_drive(path, ":")?,
+    };
+    let rest = rest.trim_start_matches(['/', '\\']).replace('\\', "/");
+    Some((drive.to_ascii_lowercase(), rest))
+}
+
+/// The last component of a project path, `/`- or `\`-separated. std's `Path` only
+/// knows the separators of the machine it runs on, and a dashboard on Linux can be
+/// shown Windows paths.
+pub fn project_name(path: &str) -> Option<&str> {
+    path.trim_end_matches(['/', '\\'])
+        .rsplit(['/', '\\'])
+        .next()
+        .filter(|name| !name.is_empty())
+}
+
+/// What `path` is compared by to tell whether two spellings name one project: a
+/// Windows path and its WSL mount (`C:\Users\me` and `/mnt/c/Users/me`) match, as do
+/// Windows paths differing only in case or separators.
+pub fn project_key(path: &str) -> String {
+    match windows_parts(path) {
+        Some((drive, rest)) => format!("{}:/{}", drive, rest.trim_end_matches('/')).to_lowercase(),
+        None => path.trim_end_matches('/').to_string(),
+    }
+}
+
+pub fn same_project(a: &str, b: &str) -> bool {
+    project_key(a) == project_key(b)
+}
+
+/// `path` as this machine can open it: Windows paths become WSL mounts on Linux
+/// and WSL mounts become Windows paths on Windows, so git and existence checks work
+/// on sessions logged from the other side.
+pub fn local_path(path: &str) -> PathBuf {
+    match windows_parts(path) {
+        Some((drive, rest)) if cfg!(windows) => PathBuf::from(format!(
+            "{}:\\{}",
+            drive.to_ascii_uppercase(),
+            rest.replace('/', "\\")
+        )),
+        Some((drive, rest)) if !path.starts_with('/') => {
+            PathBuf::from(format!("/mnt/{}/{}", drive, rest))
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -181,6 +386,93 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_take_if_updated() {
+        let dismissed: DismissedSessions = Arc::new(Mutex::new(HashMap::new()));
+        let t0 = SystemTime::now();
+        let earlier = t0 - std::time::Duration::from_secs(10);
+        let later = t0 + std::time::Duration::from_secs(10);
+
+        // Not dismissed → never re-added here
+        assert!(!take_if_updated(&dismissed, "s1", Some(later)));
+
+        dismissed.lock().unwrap().insert("s1".into(), t0);
+        assert!(!take_if_updated(&dismissed, "s1", Some(earlier)));
+        assert!(!take_if_updated(&dismissed, "s1", None));
+        assert!(take_if_updated(&dismissed, "s1", Some(later)));
+        // The dismissal is consumed
+        assert!(dismissed.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_encode_matches_lossy_decode() {
+        let cwd = "/Users/john/agents-dashboard";
+        let decoded = decode_project_path(&encode_project_path(cwd));
+        assert_eq!(decoded, "/Users/john/agents/dashboard");
+        assert_eq!(encode_project_path(&decoded), encode_project_path(cwd));
+        assert_ne!(
+            encode_project_path("/Users/john/agents-dashboard/packages"),
+            encode_project_path(cwd)
+        );
+    }
+
+    #[test]
+    fn test_windows_paths() {
+        let cases = [
+            ("C--Users-me-proj", "C:\\Users\\me\\proj"),
+            ("d--work", "d:\\work"),
+            ("C--", "C:\\"),
+            ("-mnt-c-Users-me-proj", "/mnt/c/Users/me/proj"),
+        ];
+        for (encoded, decoded) in cases {
+            assert_eq!(decode_project_path(encoded), decoded, "{}", encoded);
+            assert_eq!(encode_project_path(decoded), encoded);
+        }
+
+        for (path, name) in [
+            ("C:\\Users\\me\\proj", Some("proj")),
+            ("C:\\Users\\me\\proj\\", Some("proj")),
+            ("C:/Users/me/proj", Some("proj")),
+            ("/home/me/proj/", Some("proj")),
+            ("/", None),
+        ] {
+            assert_eq!(project_name(path), name, "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_wsl_mounts_are_the_same_project() {
+        let native = "C:\\Users\\me\\proj";
+        for other in [
+            "/mnt/c/Users/me/proj",
+            "/mnt/c/Users/me/proj/",
+            "c:\\users\\Me\\Proj",
+            "C:/Users/me/proj",
+        ] {
+            assert!(same_project(native, other), "{}", other);
+        }
+        for other in [
+            "/mnt/d/Users/me/proj",
+            "/mnt/c/Users/me/proj2",
+            "/mnt/cdrom/Users/me/proj",
+            "/home/me/proj",
+        ] {
+            assert!(!same_project(native, other), "{}", other);
+        }
+        // Case matters outside Windows.
+        assert!(!same_project("/home/me/proj", "/home/me/Proj"));
+        assert!(same_project("/home/me/proj", "/home/me/proj/"));
+
+        if !cfg!(windows) {
+            assert_eq!(local_path(native), PathBuf::from("/mnt/c/Users/me/proj"));
+            assert_eq!(
+                local_path("/mnt/c/Users/me/proj"),
+                PathBuf::from("/mnt/c/Users/me/proj")
+            );
+            assert_eq!(local_path("/home/me"), PathBuf::from("/home/me"));
+        }
+    }
+
     #[test]
     fn test_decode_project_path_no_leading_dash() {
         assert_eq!(
@@ -0,0 +1,202 @@
+use crate::types::{AgentMessage, MessageType, SourceKind, SourceRef};
+
+/// Sources kept per session; the least recently requested go first.
+pub const MAX_SOURCES: usize = 200;
+
+/// The pages and searches one session pulled in with WebFetch and WebSearch, one
+/// entry per URL or query. Results are matched to their call through
+/// `tool_use_message_id`, which `ToolStatsTracker::link_result` sets.
+#[derive(Debug, Clone, Default)]
+pub struct SourcesTracker {
+    sources: Vec<SourceRef>,
+}
+
+impl SourcesTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Note a web call or its result; `full_text` is the untruncated result, if
+    /// any. Returns true if the sources changed.
+    pub fn record(&mut self, msg: &AgentMessage, full_text: Option<&str>) -> bool {
+        match msg.msg_type {
+            MessageType::ToolUse => self.record_call(msg),
+            MessageType::ToolResult => {
+                let Some(call_id) = &msg.tool_use_message_id else {
+                    return false;
+                };
+                let Some(source) = self.sources.iter_mut().find(|s| &s.message_id == call_id)
+                else {
+                    return false;
+                };
+                source.result_bytes = Some(full_text.unwrap_or(&msg.content).len() as u64);
+                source.is_error = msg
+                    .metadata
+                    .as_ref()
+                    .and_then(|meta| meta.get("isError"))
+                    .and_then(|v| v.as_bool())
+                    == Some(true);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn record_call(&mut self, msg: &AgentMessage) -> bool {
+        let Some(meta) = &msg.metadata else {
+            return false;
+        };
+        let (kind, key) = match meta.get("toolName").and_then(|v| v.as_str()) {
+            Some("WebFetch") => (SourceKind::Fetch, "url"),
+            Some("WebSearch") => (SourceKind::Search, "query"),
+            _ => return false,
+        };
+        let Some(target) = meta
+            .get("input")
+            .and_then(|input| input.get(key))
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+        else {
+            return false;
+        };
+
+        let target = Some(target.to_string());
+        let existing = self.sources.iter().position(|s| {
+            s.kind == kind
+                && match kind {
+                    SourceKind::Fetch => s.url == target,
+                    SourceKind::Search => s.query == target,
+                }
+        });
+        if let Some(i) = existing {
+            self.sources.remove(i);
+        } else if self.sources.len() == MAX_SOURCES {
+            self.sources.remove(0);
+        }
+        let (url, query) = match kind {
+            SourceKind::Fetch => (target, None),
+            SourceKind::Search => (None, target),
+        };
+        self.sources.push(SourceRef {
+            kind,
+            url,
+            query,
+            timestamp: msg.timestamp.clone(),
+            message_id: msg.id.clone(),
+            result_bytes: None,
+            is_error: false,
+        });
+        true
+    }
+
+    /// Oldest first.
+    pub fn sources(&self) -> &[SourceRef] {
+        &self.sources
+    }
+
+    pub fn count(&self) -> usize {
+        self.sources.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::MessageRole;
+    use serde_json::json;
+
+    fn message(
+        id: &str,
+        msg_type: MessageType,
+        content: &str,
+        meta: serde_json::Value,
+    ) -> AgentMessage {
+        AgentMessage {
+            id: id.into(),
+            session_id: "s1".into(),
+            timestamp: "2025-03-10T00:00:00.000Z".into(),
+            role: MessageRole::Assistant,
+            msg_type,
+            content: content.into(),
+            metadata: serde_json::from_value(meta).ok(),
+            parent_id: None,
+            tool_use_message_id: None,
+        }
+    }
+
+    fn call(id: &str, name: &str, input: serde_json::Value) -> AgentMessage {
+        message(
+            id,
+            MessageType::ToolUse,
+            name,
+            json!({"toolName": name, "toolId": id, "input": input}),
+        )
+    }
+
+    fn result(call_id: &str, content: &str, is_error: bool) -> AgentMessage {
+        let mut msg = message(
+            "r",
+            MessageType::ToolResult,
+            content,
+            json!({"toolUseId": call_id, "isError": is_error}),
+        );
+        msg.tool_use_message_id = Some(call_id.into());
+        msg
+    }
+
+    #[test]
+    fn test_web_calls_and_results_are_recorded() {
+        let mut tracker = SourcesTracker::new();
+        let docs = json!({"url": "https://docs.rs/tokio", "prompt": "find select!"});
+        assert!(tracker.record(&call("m1", "WebFetch", docs.clone()), None));
+        assert!(tracker.record(
+            &call("m2", "WebSearch", json!({"query": "tokio select"})),
+            None
+        ));
+        assert!(!tracker.record(&call("m3", "Bash", json!({"command": "ls"})), None));
+        assert!(tracker.record(&result("m1", "<html>...", false), Some("<html></html>")));
+        assert!(tracker.record(&result("m2", "rate limited", true), None));
+        assert!(!tracker.record(&result("m3", "Cargo.toml", false), None));
+
+        let sources = tracker.sources();
+        assert_eq!(sources.len(), 2);
+        assert_eq!(sources[0].kind, SourceKind::Fetch);
+        assert_eq!(sources[0].url.as_deref(), Some("https://docs.rs/tokio"));
+        assert_eq!(
+            (sources[0].result_bytes, sources[0].is_error),
+            (Some(13), false)
+        );
+        assert_eq!(sources[1].query.as_deref(), Some("tokio select"));
+        assert_eq!(
+            (sources[1].result_bytes, sources[1].is_error),
+            (Some(12), true)
+        );
+
+        // Fetched again: one entry, moved to the end, waiting for the new result.
+        assert!(tracker.record(&call("m4", "WebFetch", docs), None));
+        let sources = tracker.sources();
+        assert_eq!(sources.len(), 2);
+        assert_eq!(sources[1].message_id, "m4");
+        assert_eq!(sources[1].result_bytes, None);
+        // A result for the earlier call no longer updates anything.
+        assert!(!tracker.record(&result("m1", "late", false), None));
+    }
+
+    #[test]
+    fn test_sources_are_capped() {
+        let mut tracker = SourcesTracker::new();
+        for i in 0..MAX_SOURCES + 5 {
+            let url = format!("https://example.com/{}", i);
+            tracker.record(
+                &call(&format!("m{}", i), "WebFetch", json!({"url": url})),
+                None,
+            );
+        }
+        assert_eq!(tracker.count(), MAX_SOURCES);
+        assert_eq!(
+            tracker.sources()[0].url.as_deref(),
+            Some("https://example.com/5")
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use crate::types::CumulativeUsage;
+use std::collections::VecDeque;
+
+/// Rates are measured over this much recent history.
+const WINDOW_MS: i64 = 10 * 60_000;
+/// Floor on the measured span, so a single turn right after startup doesn't read as a spike.
+const MIN_SPAN_MS: i64 = 60_000;
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    at_ms: i64,
+    cost: f64,
+    tokens: u64,
+}
+
+/// Rolling spend rate of a session.
+///
+/// Samples are stamped with the time they were ingested rather than the entry
+/// timestamps, which can be skewed or replayed from an old log on startup.
+#[derive(Debug, Clone, Default)]
+pub struct SpendRate {
+    samples: VecDeque<Sample>,
+}
+
+impl SpendRate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the session's cumulative usage as of `now_ms`.
+    pub fn record(&mut self, now_ms: i64, usage: &CumulativeUsage) {
+        self.samples.push_back(Sample {
+            at_ms: now_ms,
+            cost: usage.estimated_cost,
+            tokens: usage.input_tokens + usage.output_tokens,
+        });
+        self.prune(now_ms);
+    }
+
+    /// Drop samples that can no longer serve as the window's baseline.
+    fn prune(&mut self, now_ms: i64) {
+        let window_start = now_ms - WINDOW_MS;
+        while self.samples.len() > 1 && self.samples[1].at_ms <= window_start {
+            self.samples.pop_front();
+        }
+    }
+
+    /// (cost per hour, input+output tokens per minute) over the last window.
+    /// Decays towards zero as the session stays quiet, reaching it once the
+    /// last increase is older than the window.
+    pub fn rates(&mut self, now_ms: i64) -> (f64, f64) {
+        self.prune(now_ms);
+        let (Some(baseline), Some(latest)) = (self.samples.front(), self.samples.back()) else {
+            return (0.0, 0.0);
+        };
+        // Everything we have predates the window: nothing was spent within it.
+        if latest.at_ms <= now_ms - WINDOW_MS {
+            return (0.0, 0.0);
+        }
+        let span_ms = (now_ms - baseline.at_ms).max(MIN_SPAN_MS) as f64;
+        let cost = (latest.cost - baseline.cost).max(0.0);
+        let tokens = latest.tokens.saturating_sub(baseline.tokens) as f64;
+        (cost * 3_600_000.0 / span_ms, tokens * 60_000.0 / span_ms)
+    }
+}
+
+/// Whether a rate moved enough to be worth publishing.
+pub fn rate_changed(old: f64, new: f64) -> bool {
+    if old == 0.0 || new == 0.0 {
+        return old != new;
+    }
+    (new - old).abs() > old * 0.05
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIN: i64 = 60_000;
+
+    fn usage(cost: f64, tokens: u64) -> CumulativeUsage {
+        CumulativeUsage {
+            input_tokens: tokens,
+            estimated_cost: cost,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_steady_spend() {
+        let mut r = SpendRate::new();
+        // $0.01 and 1000 tokens per minute for 20 minutes.
+        for i in 0..=20 {
+            r.record(i * MIN, &usage(0.01 * i as f64, 1_000 * i as u64));
+        }
+        let (per_hour, per_minute) = r.rates(20 * MIN);
+        assert!((per_hour - 0.6).abs() < 1e-9, "{per_hour}");
+        assert!((per_minute - 1_000.0).abs() < 1e-9, "{per_minute}");
+    }
+
+    #[test]
+    fn test_rate_decays_to_zero_when_idle() {
+        let mut r = SpendRate::new();
+        for i in 0..=10 {
+            r.record(i * MIN, &usage(0.01 * i as f64, 0));
+        }
+        let (active, _) = r.rates(10 * MIN);
+        let (quieter, _) = r.rates(15 * MIN);
+        assert!(quieter < active);
+        assert!(quieter > 0.0);
+        assert_eq!(r.rates(21 * MIN), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_single_burst_uses_minimum_span() {
+        let mut r = SpendRate::new();
+        r.record(0, &usage(0.0, 0));
+        r.record(1_000, &usage(0.5, 0));
+        let (per_hour, _) = r.rates(2_000);
+        assert!((per_hour - 30.0).abs() < 1e-9, "{per_hour}");
+    }
+
+    #[test]
+    fn test_empty_and_single_sample() {
+        let mut r = SpendRate::new();
+        assert_eq!(r.rates(0), (0.0, 0.0));
+        r.record(0, &usage(1.0, 100));
+        assert_eq!(r.rates(MIN), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_rate_changed() {
+        assert!(!rate_changed(0.0, 0.0));
+        assert!(rate_changed(0.0, 0.1));
+        assert!(rate_changed(0.4, 0.0));
+        assert!(!rate_changed(1.0, 1.04));
+        assert!(rate_changed(1.0, 1.2));
+    }
+}
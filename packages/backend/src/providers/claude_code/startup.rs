@@ -0,0 +1,162 @@
+//! Tells when the work done at startup is over: discovery's first scan, and the
+//! first read of every log that scan found.
+
+use crate::types::StartupSummary;
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+/// How long the first reads may take before startup is reported without them.
+pub const STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub struct StartupScan {
+    state: Mutex<State>,
+    /// None until startup is over; set once.
+    done: watch::Sender<Option<StartupSummary>>,
+}
+
+#[derive(Default)]
+struct State {
+    started_at: Option<Instant>,
+    /// The first scan is over, so no more sessions are waited for.
+    scanned: bool,
+    /// Sessions whose first read hasn't been handled yet.
+    pending: HashSet<String>,
+    sessions_discovered: u32,
+    entries_processed: u64,
+    parse_errors: u64,
+}
+
+impl Default for StartupScan {
+    fn default() -> Self {
+        Self {
+            state: Mutex::default(),
+            done: watch::channel(None).0,
+        }
+    }
+}
+
+impl StartupScan {
+    /// The provider is starting; durations are counted from here.
+    pub fn start(&self) {
+        self.state.lock().unwrap().started_at = Some(Instant::now());
+    }
+
+    /// The first scan found `session_id`, whose log is about to be read. Sessions
+    /// found later don't count.
+    pub fn found(&self, session_id: &str) {
+        let mut state = self.state.lock().unwrap();
+        if state.scanned || self.done.borrow().is_some() {
+            return;
+        }
+        if state.pending.insert(session_id.to_string()) {
+            state.sessions_discovered += 1;
+        }
+    }
+
+    /// The first scan is over: every session it found was passed to `found`.
+    pub fn scanned(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.scanned = true;
+        if state.pending.is_empty() {
+            self.finish(&state, false);
+        }
+    }
+
+    /// The entries of `session_id`'s first read were handled.
+    pub fn read(&self, session_id: &str, entries: u64, parse_errors: u64) {
+        let mut state = self.state.lock().unwrap();
+        if !state.pending.remove(session_id) {
+            return;
+        }
+        state.entries_processed += entries;
+        state.parse_errors += parse_errors;
+        if state.scanned && state.pending.is_empty() {
+            self.finish(&state, false);
+        }
+    }
+
+    /// `STARTUP_TIMEOUT` passed: report what was read so far, if startup isn't over.
+    pub fn time_out(&self) {
+        let state = self.state.lock().unwrap();
+        self.finish(&state, true);
+    }
+
+    /// None while startup is still going on.
+    pub fn summary(&self) -> Option<StartupSummary> {
+        self.done.borrow().clone()
+    }
+
+    /// Changes once, when startup is over.
+    pub fn subscribe(&self) -> watch::Receiver<Option<StartupSummary>> {
+        self.done.subscribe()
+    }
+
+    fn finish(&self, state: &State, timed_out: bool) {
+        let duration_ms = state
+            .started_at
+            .map_or(0, |started_at| started_at.elapsed().as_millis() as u64);
+        self.done.send_if_modified(|done| {
+            if done.is_some() {
+                return false;
+            }
+            *done = Some(StartupSummary {
+                sessions_discovered: state.sessions_discovered,
+                entries_processed: state.entries_processed,
+                parse_errors: state.parse_errors,
+                duration_ms,
+                timed_out,
+            });
+            true
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_done_when_scan_and_first_reads_are() {
+        let startup = StartupScan::default();
+        startup.start();
+        startup.found("s1");
+        startup.found("s2");
+        startup.read("s1", 10, 1);
+        assert!(startup.summary().is_none());
+        startup.scanned();
+        assert!(startup.summary().is_none(), "s2 is still being read");
+        // Found after the first scan, or read again: not counted.
+        startup.found("s3");
+        startup.read("s1", 5, 5);
+        startup.read("s2", 7, 0);
+
+        let summary = startup.summary().unwrap();
+        assert_eq!(summary.sessions_discovered, 2);
+        assert_eq!(summary.entries_processed, 17);
+        assert_eq!(summary.parse_errors, 1);
+        assert!(!summary.timed_out);
+
+        // Reported once.
+        startup.time_out();
+        assert!(!startup.summary().unwrap().timed_out);
+    }
+
+    #[test]
+    fn test_empty_scan_and_timeout() {
+        let startup = StartupScan::default();
+        startup.scanned();
+        assert_eq!(startup.summary().unwrap().sessions_discovered, 0);
+
+        let startup = StartupScan::default();
+        let mut updates = startup.subscribe();
+        startup.found("s1");
+        startup.scanned();
+        startup.time_out();
+        assert!(updates.has_changed().unwrap());
+        let summary = updates.borrow_and_update().clone().unwrap();
+        assert!(summary.timed_out);
+        assert_eq!(summary.sessions_discovered, 1);
+    }
+}
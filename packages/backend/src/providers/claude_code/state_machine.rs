@@ -1,11 +1,48 @@
-use crate::types::AgentStateType;
-use super::jsonl_parser::{RawContentBlock, RawEntry, RawUserMessage};
-use chrono::{DateTime, Utc};
+use crate::types::{AgentStateType, PendingTool};
+use super::clock::{Clock, SystemClock};
+use super::failed_turns::{is_failed_turn, FAILED_TURNS_FOR_ERROR};
+use super::jsonl_parser::{HookOutcome, RawContentBlock, RawEntry, RawUserMessage};
+use super::timestamps::parse_timestamp;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 const PERMISSION_WAIT_TIMEOUT_MS: i64 = 30_000;
 const IDLE_TIMEOUT_MS: i64 = 10_000;
 const STOPPED_TIMEOUT_MS: i64 = 1_800_000; // 30 minutes
 const IDLE_STOPPED_TIMEOUT_MS: i64 = 1_800_000; // 30 minutes
+/// With no Claude process left, this much silence means the session is over.
+const PROCESS_GONE_TIMEOUT_MS: i64 = 10_000;
+/// Tool calls without a result kept per session; older ones are forgotten.
+const MAX_UNRESOLVED_TOOLS: usize = 32;
+/// Longest `PendingTool::summary`, in characters.
+const MAX_TOOL_SUMMARY_CHARS: usize = 200;
+
+/// How much silence each time-based transition waits for, in millis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StateTimeouts {
+    /// A tool call with no result yet → PermissionWaiting.
+    pub permission_wait_ms: i64,
+    /// A text-only reply with nothing after it → Idle.
+    pub idle_ms: i64,
+    /// Running with no activity → Stopped.
+    pub running_stopped_ms: i64,
+    /// Idle with no activity → Stopped.
+    pub idle_stopped_ms: i64,
+    /// No Claude process left (process detection only) → Stopped.
+    pub process_gone_ms: i64,
+}
+
+impl Default for StateTimeouts {
+    fn default() -> Self {
+        Self {
+            permission_wait_ms: PERMISSION_WAIT_TIMEOUT_MS,
+            idle_ms: IDLE_TIMEOUT_MS,
+            running_stopped_ms: STOPPED_TIMEOUT_MS,
+            idle_stopped_ms: IDLE_STOPPED_TIMEOUT_MS,
+            process_gone_ms: PROCESS_GONE_TIMEOUT_MS,
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct StateContext {
@@ -14,16 +51,80 @@ pub struct StateContext {
     pub last_assistant_tool_use: bool,
     pub last_assistant_text_only: bool,
     pub last_entry_timestamp: i64,
+    /// Tool calls of the current turn with no result or progress yet, oldest first.
+    pub unresolved_tools: Vec<PendingTool>,
+    /// The call awaiting approval; set while the state is PermissionWaiting.
+    pub pending_tool: Option<PendingTool>,
+    /// A hook blocked a tool call or stopped the agent, and it hasn't carried on
+    /// since. The session stays in Error until it does.
+    pub hook_blocked: bool,
+    /// The session bypasses permissions, so a quiet tool call is running, not waiting.
+    pub bypass_permissions: bool,
+    /// Turns in a row that ended in failure; a response that went through resets it.
+    pub failed_turns_in_a_row: u32,
+    /// Whether a Claude process is running in the session's directory; None when
+    /// process detection is off or hasn't run yet.
+    pub process_alive: Option<bool>,
+    /// The last activity on the monotonic clock, which timeouts are measured from.
+    activity_mark: Option<ActivityMark>,
+    pub clock: Arc<dyn Clock>,
+    pub timeouts: StateTimeouts,
+}
+
+/// When an entry was read, and how old its timestamp already was at that point.
+#[derive(Debug, Clone, Copy)]
+struct ActivityMark {
+    read_at: Instant,
+    age_when_read: Duration,
 }
 
 impl StateContext {
     pub fn new() -> Self {
+        Self::with_clock(Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
         Self {
             state: AgentStateType::Stopped,
             last_activity_at: 0,
             last_assistant_tool_use: false,
             last_assistant_text_only: false,
             last_entry_timestamp: 0,
+            unresolved_tools: Vec::new(),
+            pending_tool: None,
+            hook_blocked: false,
+            bypass_permissions: false,
+            failed_turns_in_a_row: 0,
+            process_alive: None,
+            activity_mark: None,
+            clock,
+            timeouts: StateTimeouts::default(),
+        }
+    }
+
+    /// Record activity at wall-clock time `at_ms` (an entry's timestamp). Entries
+    /// from an old log read on startup count as long past.
+    pub fn mark_activity(&mut self, at_ms: i64) {
+        self.last_activity_at = at_ms;
+        let age_ms = (self.clock.now_millis() - at_ms).max(0);
+        self.activity_mark = Some(ActivityMark {
+            read_at: self.clock.now_instant(),
+            age_when_read: Duration::from_millis(age_ms as u64),
+        });
+    }
+
+    /// Millis since the last activity. Measured on the monotonic clock once an
+    /// entry has been read, so wall-clock jumps don't fire or delay timeouts.
+    pub fn elapsed_ms(&self) -> i64 {
+        match self.activity_mark {
+            Some(mark) => {
+                let since_read = self
+                    .clock
+                    .now_instant()
+                    .saturating_duration_since(mark.read_at);
+                (mark.age_when_read + since_read).as_millis() as i64
+            }
+            None => self.clock.now_millis() - self.last_activity_at,
         }
     }
 }
@@ -38,13 +139,16 @@ fn has_tool_use_block(blocks: &[RawContentBlock]) -> bool {
 }
 
 fn has_error_pattern(entry: &RawEntry) -> bool {
+    if let RawEntry::Hook(hook) = entry {
+        return hook.outcome == HookOutcome::Blocked;
+    }
     if let RawEntry::User(user_msg) = entry {
         if let Some(arr) = user_msg.message.content.as_array() {
             for block in arr {
-                if block.get("type").and_then(|t| t.as_str()) == Some("tool_result") {
-                    if block.get("is_error").and_then(|v| v.as_bool()) == Some(true) {
-                        return true;
-                    }
+                if block.get("type").and_then(|t| t.as_str()) == Some("tool_result")
+                    && block.get("is_error").and_then(|v| v.as_bool()) == Some(true)
+                {
+                    return true;
                 }
             }
         }
@@ -52,6 +156,15 @@ fn has_error_pattern(entry: &RawEntry) -> bool {
     false
 }
 
+fn is_tool_result_entry(msg: &RawUserMessage) -> bool {
+    msg.message.content.as_array().is_some_and(|blocks| {
+        !blocks.is_empty()
+            && blocks
+                .iter()
+                .all(|b| b.get("type").and_then(|t| t.as_str()) == Some("tool_result"))
+    })
+}
+
 fn is_exit_command_entry(msg: &RawUserMessage) -> bool {
     if let Some(content_str) = msg.message.content.as_str() {
         return content_str.contains("<command-name>/exit</command-name>");
@@ -68,36 +181,175 @@ fn is_local_command_entry(msg: &RawUserMessage) -> bool {
     false
 }
 
-fn get_entry_timestamp(entry: &RawEntry) -> Option<i64> {
+/// Something the user typed to the main agent: not a tool result, a slash command
+/// or its output, or a subagent's prompt.
+pub fn is_user_prompt(msg: &RawUserMessage) -> bool {
+    msg.is_sidechain != Some(true) && !is_tool_result_entry(msg) && !is_local_command_entry(msg)
+}
+
+/// A one-line description of a tool call's input: the command for Bash, the file
+/// for the file tools, the pattern for searches, else the input as compact JSON.
+pub fn summarize_tool_input(name: &str, input: &serde_json::Value) -> String {
+    let key = match name {
+        "Bash" => Some("command"),
+        "Edit" | "MultiEdit" | "Write" | "Read" => Some("file_path"),
+        "NotebookEdit" => Some("notebook_path"),
+        "Glob" | "Grep" => Some("pattern"),
+        "WebFetch" => Some("url"),
+        "WebSearch" => Some("query"),
+        "Task" => Some("description"),
+        _ => None,
+    };
+    let text = match key.and_then(|key| input.get(key)).and_then(|v| v.as_str()) {
+        Some(text) => text.to_string(),
+        None if input.as_object().is_some_and(|o| o.is_empty()) || input.is_null() => String::new(),
+        None => input.to_string(),
+    };
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// `summary` cut to `MAX_TOOL_SUMMARY_CHARS` for showing. Summaries are kept whole
+/// until then, so redaction sees every secret in full before anything is cut.
+pub fn shorten_tool_summary(summary: &str) -> String {
+    if summary.chars().count() <= MAX_TOOL_SUMMARY_CHARS {
+        return summary.to_string();
+    }
+    let mut cut: String = summary.chars().take(MAX_TOOL_SUMMARY_CHARS - 1).collect();
+    cut.push('…');
+    cut
+}
+
+/// Keep `unresolved_tools` in step with the log: calls are added as the assistant
+/// makes them and dropped once a result or progress for them shows up. A new prompt
+/// or the end of a turn leaves nothing outstanding.
+fn track_tool_calls(ctx: &mut StateContext, entry: &RawEntry) {
+    match entry {
+        RawEntry::Assistant(msg) => {
+            for block in &msg.message.content {
+                if let RawContentBlock::ToolUse {
+                    id, name, input, ..
+                } = block
+                {
+                    ctx.unresolved_tools.push(PendingTool {
+                        tool_use_id: id.clone(),
+                        name: name.clone(),
+                        summary: summarize_tool_input(name, input),
+                    });
+                }
+            }
+            let excess = ctx
+                .unresolved_tools
+                .len()
+                .saturating_sub(MAX_UNRESOLVED_TOOLS);
+            ctx.unresolved_tools.drain(..excess);
+        }
+        RawEntry::User(msg) => {
+            let results: Vec<&str> = msg
+                .message
+                .content
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter(|b| b.get("type").and_then(|t| t.as_str()) == Some("tool_result"))
+                .filter_map(|b| b.get("tool_use_id").and_then(|v| v.as_str()))
+                .collect();
+            if !results.is_empty() {
+                ctx.unresolved_tools
+                    .retain(|t| !results.contains(&t.tool_use_id.as_str()));
+            } else if !is_local_command_entry(msg) {
+                ctx.unresolved_tools.clear();
+            }
+        }
+        RawEntry::Progress(progress) => match &progress.parent_tool_use_id {
+            Some(id) => ctx.unresolved_tools.retain(|t| &t.tool_use_id != id),
+            // Older logs don't say which call; progress means it was approved.
+            None => ctx.unresolved_tools.clear(),
+        },
+        RawEntry::System(sys) if sys.subtype.as_deref() == Some("turn_duration") => {
+            ctx.unresolved_tools.clear()
+        }
+        // A blocked call won't run, so it can't be what a later prompt waits on.
+        RawEntry::Hook(hook) if hook.outcome == HookOutcome::Blocked => {
+            if let Some(id) = &hook.tool_use_id {
+                ctx.unresolved_tools.retain(|t| &t.tool_use_id != id);
+            }
+        }
+        _ => {}
+    }
+}
+
+pub fn get_entry_timestamp(entry: &RawEntry) -> Option<i64> {
     let ts_str = match entry {
         RawEntry::User(m) => m.timestamp.as_deref(),
         RawEntry::Assistant(m) => m.timestamp.as_deref(),
         RawEntry::System(m) => m.timestamp.as_deref(),
         RawEntry::Progress(m) => m.timestamp.as_deref(),
-        RawEntry::Other => None,
+        RawEntry::Hook(m) => m.timestamp.as_deref(),
+        RawEntry::Summary(_) | RawEntry::Oversized(_) | RawEntry::Other => None,
     };
 
-    ts_str.and_then(|s| {
-        s.parse::<DateTime<Utc>>()
-            .ok()
-            .map(|dt| dt.timestamp_millis())
-    })
+    ts_str
+        .and_then(parse_timestamp)
+        .map(|dt| dt.timestamp_millis())
+}
+
+/// An entry written before ones already processed, e.g. from a backfill. It can
+/// settle tool calls still outstanding, but doesn't change the state or start any.
+pub fn process_late_entry(ctx: &mut StateContext, entry: &RawEntry) {
+    let settled: Vec<&str> = match entry {
+        RawEntry::User(msg) => msg
+            .message
+            .content
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|b| b.get("type").and_then(|t| t.as_str()) == Some("tool_result"))
+            .filter_map(|b| b.get("tool_use_id").and_then(|v| v.as_str()))
+            .collect(),
+        RawEntry::Progress(progress) => {
+            progress.parent_tool_use_id.as_deref().into_iter().collect()
+        }
+        _ => Vec::new(),
+    };
+    ctx.unresolved_tools
+        .retain(|t| !settled.contains(&t.tool_use_id.as_str()));
 }
 
 pub fn process_entry(ctx: &mut StateContext, entry: &RawEntry) -> TransitionResult {
     let prev_state = ctx.state;
-    let now = Utc::now().timestamp_millis();
+    track_tool_calls(ctx, entry);
+    let mut result = apply_entry(ctx, entry);
+    // Turn after turn failing: the session is stuck, whatever comes in between.
+    if ctx.failed_turns_in_a_row >= FAILED_TURNS_FOR_ERROR && ctx.state != AgentStateType::Stopped {
+        ctx.state = AgentStateType::Error;
+        result = TransitionResult {
+            new_state: ctx.state,
+            changed: prev_state != ctx.state,
+        };
+    }
+    if ctx.state != AgentStateType::PermissionWaiting {
+        ctx.pending_tool = None;
+    }
+    result
+}
+
+fn apply_entry(ctx: &mut StateContext, entry: &RawEntry) -> TransitionResult {
+    let prev_state = ctx.state;
 
-    let entry_ts = get_entry_timestamp(entry);
+    // An entry a little older than the last one (entries are written out of order
+    // by up to a few seconds) doesn't move activity back.
+    let entry_ts = get_entry_timestamp(entry).filter(|&ts| ts >= ctx.last_entry_timestamp);
     if let Some(ts) = entry_ts {
-        ctx.last_activity_at = ts;
+        ctx.mark_activity(ts);
         ctx.last_entry_timestamp = ts;
     }
 
     // Handle system:turn_duration → Idle
     if let RawEntry::System(sys) = entry {
         if sys.subtype.as_deref() == Some("turn_duration") {
-            ctx.state = AgentStateType::Idle;
+            if !ctx.hook_blocked {
+                ctx.state = AgentStateType::Idle;
+            }
             ctx.last_assistant_tool_use = false;
             ctx.last_assistant_text_only = false;
             return TransitionResult {
@@ -124,6 +376,14 @@ pub fn process_entry(ctx: &mut StateContext, entry: &RawEntry) -> TransitionResu
                 changed: false,
             };
         }
+        // The result the blocked call gets back, not the agent moving on.
+        if ctx.hook_blocked && is_tool_result_entry(user_msg) {
+            return TransitionResult {
+                new_state: ctx.state,
+                changed: false,
+            };
+        }
+        ctx.hook_blocked = false;
         ctx.state = AgentStateType::Running;
         ctx.last_assistant_tool_use = false;
         ctx.last_assistant_text_only = false;
@@ -136,6 +396,15 @@ pub fn process_entry(ctx: &mut StateContext, entry: &RawEntry) -> TransitionResu
     // Handle assistant message
     if let RawEntry::Assistant(assistant_msg) = entry {
         ctx.state = AgentStateType::Running;
+        ctx.hook_blocked = false;
+
+        if assistant_msg.is_sidechain != Some(true) {
+            if is_failed_turn(assistant_msg) {
+                ctx.failed_turns_in_a_row += 1;
+            } else {
+                ctx.failed_turns_in_a_row = 0;
+            }
+        }
 
         if has_tool_use_block(&assistant_msg.message.content) {
             ctx.last_assistant_tool_use = true;
@@ -155,6 +424,18 @@ pub fn process_entry(ctx: &mut StateContext, entry: &RawEntry) -> TransitionResu
         };
     }
 
+    // A hook blocked a tool call or stopped the agent → Error
+    if has_error_pattern(entry) {
+        ctx.state = AgentStateType::Error;
+        ctx.hook_blocked = true;
+        ctx.last_assistant_tool_use = false;
+        ctx.last_assistant_text_only = false;
+        return TransitionResult {
+            new_state: ctx.state,
+            changed: prev_state != ctx.state,
+        };
+    }
+
     // Progress entries → Running
     // Progress entries indicate a tool is actively executing (approved),
     // so clear tool_use flag to prevent false PermissionWaiting detection.
@@ -176,13 +457,26 @@ pub fn process_entry(ctx: &mut StateContext, entry: &RawEntry) -> TransitionResu
 
 pub fn check_time_based_transitions(ctx: &mut StateContext) -> TransitionResult {
     let prev_state = ctx.state;
-    let now = Utc::now().timestamp_millis();
-    let elapsed = now - ctx.last_activity_at;
+    let elapsed = ctx.elapsed_ms();
+    let timeouts = ctx.timeouts;
+
+    // The CLI exited (or crashed) and the log went quiet → Stopped
+    if ctx.process_alive == Some(false)
+        && elapsed >= timeouts.process_gone_ms
+        && ctx.state != AgentStateType::Stopped
+    {
+        ctx.state = AgentStateType::Stopped;
+        ctx.pending_tool = None;
+        return TransitionResult {
+            new_state: ctx.state,
+            changed: true,
+        };
+    }
 
     // If last entry was text-only assistant and silence > 10s → Idle
     if ctx.state == AgentStateType::Running
         && ctx.last_assistant_text_only
-        && elapsed >= IDLE_TIMEOUT_MS
+        && elapsed >= timeouts.idle_ms
     {
         ctx.state = AgentStateType::Idle;
         ctx.last_assistant_text_only = false;
@@ -195,17 +489,23 @@ pub fn check_time_based_transitions(ctx: &mut StateContext) -> TransitionResult
     // If last entry was tool_use and silence > 10s → PermissionWaiting
     if ctx.state == AgentStateType::Running
         && ctx.last_assistant_tool_use
-        && elapsed >= PERMISSION_WAIT_TIMEOUT_MS
+        && !ctx.bypass_permissions
+        && elapsed >= timeouts.permission_wait_ms
     {
         ctx.state = AgentStateType::PermissionWaiting;
+        // Calls run in order, so the earliest one without a result is the one held up.
+        ctx.pending_tool = ctx.unresolved_tools.first().cloned();
         return TransitionResult {
             new_state: ctx.state,
             changed: prev_state != ctx.state,
         };
     }
 
-    // If no activity for 60s AND was running → Stopped
-    if elapsed >= STOPPED_TIMEOUT_MS && ctx.state == AgentStateType::Running {
+    // A live process can sit quiet for as long as it likes.
+    let may_time_out = ctx.process_alive != Some(true);
+
+    // If no activity for 30min AND was running → Stopped
+    if may_time_out && elapsed >= timeouts.running_stopped_ms && ctx.state == AgentStateType::Running {
         ctx.state = AgentStateType::Stopped;
         return TransitionResult {
             new_state: ctx.state,
@@ -213,8 +513,8 @@ pub fn check_time_based_transitions(ctx: &mut StateContext) -> TransitionResult
         };
     }
 
-    // If no activity for 5min AND was idle → Stopped
-    if elapsed >= IDLE_STOPPED_TIMEOUT_MS && ctx.state == AgentStateType::Idle {
+    // If no activity for 30min AND was idle → Stopped
+    if may_time_out && elapsed >= timeouts.idle_stopped_ms && ctx.state == AgentStateType::Idle {
         ctx.state = AgentStateType::Stopped;
         return TransitionResult {
             new_state: ctx.state,
@@ -231,7 +531,9 @@ pub fn check_time_based_transitions(ctx: &mut StateContext) -> TransitionResult
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::providers::claude_code::clock::ManualClock;
     use crate::providers::claude_code::jsonl_parser::*;
+    use chrono::Utc;
     use serde_json::json;
 
     fn make_user_entry(content: &str) -> RawEntry {
@@ -249,6 +551,7 @@ mod tests {
             uuid: Some("u1".into()),
             timestamp: Some(Utc::now().to_rfc3339()),
             git_branch: None,
+            permission_mode: None,
         })
     }
 
@@ -270,15 +573,59 @@ mod tests {
             uuid: Some("a1".into()),
             timestamp: Some(Utc::now().to_rfc3339()),
             git_branch: None,
+            is_api_error_message: None,
+        })
+    }
+
+    fn make_tool_use(id: &str, name: &str, input: serde_json::Value) -> RawContentBlock {
+        RawContentBlock::ToolUse {
+            id: id.into(),
+            name: name.into(),
+            input,
+            caller: None,
+        }
+    }
+
+    fn make_tool_result_entry(tool_use_id: &str) -> RawEntry {
+        let RawEntry::User(mut msg) = make_user_entry("") else {
+            unreachable!()
+        };
+        msg.message.content = json!([
+            {"type": "tool_result", "tool_use_id": tool_use_id, "content": "ok"}
+        ]);
+        RawEntry::User(msg)
+    }
+
+    fn make_progress_entry(parent_tool_use_id: Option<&str>) -> RawEntry {
+        RawEntry::Progress(RawProgressEntry {
+            parent_uuid: None,
+            data: None,
+            parent_tool_use_id: parent_tool_use_id.map(String::from),
+            uuid: None,
+            timestamp: Some(Utc::now().to_rfc3339()),
         })
     }
 
+    /// A context with activity just now, on a clock only the test moves.
+    fn manual_context() -> (StateContext, Arc<ManualClock>) {
+        let clock = Arc::new(ManualClock::new());
+        let mut ctx = StateContext::with_clock(clock.clone());
+        ctx.mark_activity(clock.now_millis());
+        (ctx, clock)
+    }
+
     fn make_system_turn_duration() -> RawEntry {
         RawEntry::System(RawSystemEntry {
             subtype: Some("turn_duration".into()),
             session_id: Some("s1".into()),
             timestamp: Some(Utc::now().to_rfc3339()),
             duration_ms: Some(1500),
+            permission_mode: None,
+            level: None,
+            content: None,
+            error: None,
+            retry_attempt: None,
+            max_retries: None,
         })
     }
 
@@ -363,24 +710,37 @@ mod tests {
 
     #[test]
     fn test_permission_wait_timeout() {
-        let mut ctx = StateContext::new();
+        let (mut ctx, clock) = manual_context();
         ctx.state = AgentStateType::Running;
         ctx.last_assistant_tool_use = true;
-        // Set last_activity_at to 35 seconds ago (past 30s threshold)
-        ctx.last_activity_at = Utc::now().timestamp_millis() - 35_000;
+        // 35 seconds of silence (past 30s threshold)
+        clock.advance(Duration::from_secs(35));
 
         let result = check_time_based_transitions(&mut ctx);
         assert_eq!(result.new_state, AgentStateType::PermissionWaiting);
         assert!(result.changed);
     }
 
+    #[test]
+    fn test_no_permission_wait_when_bypassing_permissions() {
+        let (mut ctx, clock) = manual_context();
+        ctx.state = AgentStateType::Running;
+        ctx.last_assistant_tool_use = true;
+        ctx.bypass_permissions = true;
+        clock.advance(Duration::from_secs(35));
+
+        let result = check_time_based_transitions(&mut ctx);
+        assert_eq!(result.new_state, AgentStateType::Running);
+        assert!(!result.changed);
+    }
+
     #[test]
     fn test_no_permission_wait_before_timeout() {
-        let mut ctx = StateContext::new();
+        let (mut ctx, clock) = manual_context();
         ctx.state = AgentStateType::Running;
         ctx.last_assistant_tool_use = true;
-        // Set last_activity_at to 20 seconds ago (within 30s threshold)
-        ctx.last_activity_at = Utc::now().timestamp_millis() - 20_000;
+        // 20 seconds of silence (within 30s threshold)
+        clock.advance(Duration::from_secs(20));
 
         let result = check_time_based_transitions(&mut ctx);
         assert_eq!(result.new_state, AgentStateType::Running);
@@ -388,12 +748,232 @@ mod tests {
     }
 
     #[test]
-    fn test_stopped_timeout() {
+    fn test_hook_block_is_an_error() {
+        let (mut ctx, _clock) = manual_context();
+        let entries = parse_jsonl_chunk(include_str!("testdata/hooks.jsonl")).entries;
+        let states: Vec<AgentStateType> = entries
+            .iter()
+            .map(|entry| {
+                process_entry(&mut ctx, entry);
+                ctx.state
+            })
+            .collect();
+        use AgentStateType::*;
+        assert_eq!(
+            states,
+            [
+                Running, Running, Running,
+                // The guard hook blocks `rm -rf`; the error result it gets back
+                // doesn't clear that.
+                Error, Error,
+                // The agent carries on; a hook failing without blocking anything leaves
+                // the state alone.
+                Running, Running, Running, Running, Running, Running, Idle,
+            ]
+        );
+        assert!(ctx.unresolved_tools.is_empty());
+
+        // A block that stops the agent lasts past the end of the turn.
+        let (mut ctx, _clock) = manual_context();
+        for line in [
+            r#"{"type":"user","message":{"role":"user","content":"Ship it"}}"#,
+            r#"{"type":"system","subtype":"stop_hook_summary","hookErrors":[],"preventedContinuation":true,"stopReason":"CI is red"}"#,
+            r#"{"type":"system","subtype":"turn_duration","durationMs":100}"#,
+        ] {
+            process_entry(&mut ctx, &parse_jsonl_line(line).unwrap());
+        }
+        assert_eq!(ctx.state, AgentStateType::Error);
+        process_entry(&mut ctx, &make_user_entry("Fix CI first"));
+        assert_eq!(ctx.state, AgentStateType::Running);
+        assert!(!ctx.hook_blocked);
+    }
+
+    #[test]
+    fn test_repeated_failed_turns_are_an_error() {
+        // One failed turn is retried by the user and goes through.
+        let (mut ctx, _clock) = manual_context();
+        let entries = parse_jsonl_chunk(include_str!("testdata/retried_turn.jsonl")).entries;
+        for entry in &entries[..4] {
+            process_entry(&mut ctx, entry);
+        }
+        assert_eq!(ctx.failed_turns_in_a_row, 1);
+        assert_eq!(ctx.state, AgentStateType::Running);
+        for entry in &entries[4..] {
+            process_entry(&mut ctx, entry);
+        }
+        assert_eq!(ctx.failed_turns_in_a_row, 0);
+        assert_eq!(ctx.state, AgentStateType::Idle);
+
+        // Three in a row: Error, and prompting again doesn't leave it.
+        let (mut ctx, _clock) = manual_context();
+        let failed_turn = parse_jsonl_line(
+            r#"{"type":"assistant","isApiErrorMessage":true,"message":{"model":"<synthetic>","content":[{"type":"text","text":"API Error: 500 Internal server error"}]}}"#,
+        )
+        .unwrap();
+        for _ in 0..FAILED_TURNS_FOR_ERROR {
+            process_entry(&mut ctx, &make_user_entry("Try again"));
+            process_entry(&mut ctx, &failed_turn);
+        }
+        assert_eq!(ctx.state, AgentStateType::Error);
+        let result = process_entry(&mut ctx, &make_user_entry("Try again"));
+        assert_eq!(result.new_state, AgentStateType::Error);
+        assert!(!result.changed);
+        process_entry(&mut ctx, &make_assistant_entry(vec![]));
+        assert_eq!(ctx.state, AgentStateType::Running);
+    }
+
+    #[test]
+    fn test_permission_wait_names_earliest_unresolved_tool() {
+        let (mut ctx, clock) = manual_context();
+        let entry = make_assistant_entry(vec![
+            make_tool_use("t1", "Read", json!({"file_path": "/work/README.md"})),
+            make_tool_use("t2", "Bash", json!({"command": "rm -rf node_modules"})),
+            make_tool_use("t3", "Edit", json!({"file_path": "/work/src/main.rs"})),
+        ]);
+        process_entry(&mut ctx, &entry);
+        process_entry(&mut ctx, &make_tool_result_entry("t1"));
+        // A later assistant turn keeps the flag that makes the session wait.
+        process_entry(
+            &mut ctx,
+            &make_assistant_entry(vec![make_tool_use(
+                "t4",
+                "Glob",
+                json!({"pattern": "*.rs"}),
+            )]),
+        );
+        assert!(ctx.pending_tool.is_none());
+
+        clock.advance(Duration::from_secs(35));
+        check_time_based_transitions(&mut ctx);
+        assert_eq!(ctx.state, AgentStateType::PermissionWaiting);
+        let pending = ctx.pending_tool.clone().unwrap();
+        assert_eq!(pending.tool_use_id, "t2");
+        assert_eq!(pending.name, "Bash");
+        assert_eq!(pending.summary, "rm -rf node_modules");
+
+        // Approved: the result arrives and the session runs again.
+        process_entry(&mut ctx, &make_tool_result_entry("t2"));
+        assert_eq!(ctx.state, AgentStateType::Running);
+        assert!(ctx.pending_tool.is_none());
+        let ids: Vec<&str> = ctx
+            .unresolved_tools
+            .iter()
+            .map(|t| t.tool_use_id.as_str())
+            .collect();
+        assert_eq!(ids, ["t3", "t4"]);
+    }
+
+    #[test]
+    fn test_progress_and_new_prompt_resolve_tools() {
+        let (mut ctx, _clock) = manual_context();
+        let entry = make_assistant_entry(vec![
+            make_tool_use("t1", "Bash", json!({"command": "npm test"})),
+            make_tool_use("t2", "Bash", json!({"command": "npm run lint"})),
+        ]);
+        process_entry(&mut ctx, &entry);
+        ctx.state = AgentStateType::PermissionWaiting;
+        ctx.pending_tool = ctx.unresolved_tools.first().cloned();
+
+        process_entry(&mut ctx, &make_progress_entry(Some("t1")));
+        assert!(ctx.pending_tool.is_none());
+        assert_eq!(ctx.unresolved_tools.len(), 1);
+        assert_eq!(ctx.unresolved_tools[0].tool_use_id, "t2");
+
+        // The user interrupted and typed something else.
+        process_entry(&mut ctx, &make_user_entry("never mind"));
+        assert!(ctx.unresolved_tools.is_empty());
+
+        // Progress that doesn't name its call settles everything outstanding.
+        process_entry(&mut ctx, &entry);
+        process_entry(&mut ctx, &make_progress_entry(None));
+        assert!(ctx.unresolved_tools.is_empty());
+    }
+
+    #[test]
+    fn test_late_entries_only_settle_tool_calls() {
+        let mut ctx = StateContext::new();
+        let calls = make_assistant_entry(vec![
+            make_tool_use("t1", "Bash", json!({"command": "npm test"})),
+            make_tool_use("t2", "Bash", json!({"command": "npm run lint"})),
+        ]);
+        process_entry(&mut ctx, &calls);
+        let latest = ctx.last_entry_timestamp;
+        assert_eq!(ctx.state, AgentStateType::Running);
+
+        process_late_entry(&mut ctx, &make_tool_result_entry("t1"));
+        process_late_entry(&mut ctx, &make_progress_entry(Some("t2")));
+        assert!(ctx.unresolved_tools.is_empty());
+
+        // A late prompt or tool call neither clears nor adds calls, nor moves state.
+        process_entry(&mut ctx, &calls);
+        process_late_entry(&mut ctx, &make_user_entry("old prompt"));
+        process_late_entry(&mut ctx, &calls);
+        assert_eq!(ctx.unresolved_tools.len(), 2);
+        process_late_entry(&mut ctx, &make_system_turn_duration());
+        assert_eq!(ctx.state, AgentStateType::Running);
+        assert!(ctx.last_entry_timestamp >= latest);
+    }
+
+    #[test]
+    fn test_slightly_older_entry_keeps_latest_timestamp() {
         let mut ctx = StateContext::new();
+        let now = Utc::now();
+        let at = |ms: i64| {
+            let RawEntry::User(mut msg) = make_user_entry("hi") else {
+                unreachable!()
+            };
+            msg.timestamp = Some((now + chrono::TimeDelta::milliseconds(ms)).to_rfc3339());
+            RawEntry::User(msg)
+        };
+        process_entry(&mut ctx, &at(2_000));
+        let latest = ctx.last_entry_timestamp;
+        process_entry(&mut ctx, &at(0));
+        assert_eq!(ctx.last_entry_timestamp, latest);
+        assert_eq!(ctx.last_activity_at, latest);
+        process_entry(&mut ctx, &at(3_000));
+        assert_eq!(ctx.last_entry_timestamp, latest + 1_000);
+    }
+
+    #[test]
+    fn test_summarize_tool_input() {
+        assert_eq!(
+            summarize_tool_input(
+                "Bash",
+                &json!({"command": "cargo test\n  --workspace", "description": "Run tests"})
+            ),
+            "cargo test --workspace"
+        );
+        assert_eq!(
+            summarize_tool_input(
+                "Write",
+                &json!({"file_path": "/work/a.rs", "content": "fn main() {}"})
+            ),
+            "/work/a.rs"
+        );
+        assert_eq!(
+            summarize_tool_input("Grep", &json!({"pattern": "TODO"})),
+            "TODO"
+        );
+        assert_eq!(
+            summarize_tool_input("mcp__db__query", &json!({"sql": "select 1"})),
+            r#"{"sql":"select 1"}"#
+        );
+        assert_eq!(summarize_tool_input("ExitPlanMode", &json!({})), "");
+
+        let long = summarize_tool_input("Bash", &json!({"command": "x".repeat(500)}));
+        assert_eq!(long.len(), 500);
+        let long = shorten_tool_summary(&long);
+        assert_eq!(long.chars().count(), MAX_TOOL_SUMMARY_CHARS);
+        assert!(long.ends_with('…'));
+    }
+
+    #[test]
+    fn test_stopped_timeout() {
+        let (mut ctx, clock) = manual_context();
         ctx.state = AgentStateType::Running;
         ctx.last_assistant_tool_use = false;
-        // Set last_activity_at to 31 minutes ago
-        ctx.last_activity_at = Utc::now().timestamp_millis() - 1_860_000;
+        // 31 minutes of silence
+        clock.advance(Duration::from_secs(31 * 60));
 
         let result = check_time_based_transitions(&mut ctx);
         assert_eq!(result.new_state, AgentStateType::Stopped);
@@ -402,10 +982,10 @@ mod tests {
 
     #[test]
     fn test_idle_does_not_transition_to_stopped_before_30min() {
-        let mut ctx = StateContext::new();
+        let (mut ctx, clock) = manual_context();
         ctx.state = AgentStateType::Idle;
-        // 20 minutes ago — still within 30min threshold
-        ctx.last_activity_at = Utc::now().timestamp_millis() - 1_200_000;
+        // 20 minutes of silence — still within 30min threshold
+        clock.advance(Duration::from_secs(20 * 60));
 
         let result = check_time_based_transitions(&mut ctx);
         assert_eq!(result.new_state, AgentStateType::Idle);
@@ -414,11 +994,40 @@ mod tests {
 
     #[test]
     fn test_idle_transitions_to_stopped_after_30min() {
-        let mut ctx = StateContext::new();
+        let (mut ctx, clock) = manual_context();
+        ctx.state = AgentStateType::Idle;
+        // 31 minutes of silence
+        clock.advance(Duration::from_secs(31 * 60));
+
+        let result = check_time_based_transitions(&mut ctx);
+        assert_eq!(result.new_state, AgentStateType::Stopped);
+        assert!(result.changed);
+    }
+
+    #[test]
+    fn test_live_process_suppresses_stopped_timeout() {
+        let (mut ctx, clock) = manual_context();
         ctx.state = AgentStateType::Idle;
-        // 31 minutes ago
-        ctx.last_activity_at = Utc::now().timestamp_millis() - 1_860_000;
+        ctx.process_alive = Some(true);
+        clock.advance(Duration::from_secs(60 * 60));
 
+        let result = check_time_based_transitions(&mut ctx);
+        assert_eq!(result.new_state, AgentStateType::Idle);
+        assert!(!result.changed);
+    }
+
+    #[test]
+    fn test_gone_process_stops_quiet_session() {
+        let (mut ctx, clock) = manual_context();
+        ctx.state = AgentStateType::PermissionWaiting;
+        ctx.process_alive = Some(false);
+        clock.advance(Duration::from_secs(2));
+        assert!(
+            !check_time_based_transitions(&mut ctx).changed,
+            "entries are recent"
+        );
+
+        clock.advance(Duration::from_secs(13));
         let result = check_time_based_transitions(&mut ctx);
         assert_eq!(result.new_state, AgentStateType::Stopped);
         assert!(result.changed);
@@ -468,6 +1077,7 @@ mod tests {
         let entry = RawEntry::Progress(RawProgressEntry {
             parent_uuid: None,
             data: None,
+            parent_tool_use_id: None,
             uuid: None,
             timestamp: Some(Utc::now().to_rfc3339()),
         });
@@ -483,6 +1093,7 @@ mod tests {
         let entry = RawEntry::Progress(RawProgressEntry {
             parent_uuid: None,
             data: None,
+            parent_tool_use_id: None,
             uuid: None,
             timestamp: Some(Utc::now().to_rfc3339()),
         });
@@ -493,7 +1104,7 @@ mod tests {
 
     #[test]
     fn test_progress_prevents_permission_wait_false_positive() {
-        let mut ctx = StateContext::new();
+        let (mut ctx, clock) = manual_context();
         // Simulate: assistant sent tool_use, then progress entry arrived
         let tool_entry = make_assistant_entry(vec![RawContentBlock::ToolUse {
             id: "t1".into(),
@@ -508,6 +1119,7 @@ mod tests {
         let progress_entry = RawEntry::Progress(RawProgressEntry {
             parent_uuid: None,
             data: None,
+            parent_tool_use_id: None,
             uuid: None,
             timestamp: Some(Utc::now().to_rfc3339()),
         });
@@ -515,18 +1127,18 @@ mod tests {
         assert!(!ctx.last_assistant_tool_use);
 
         // Even after 35s of silence, should NOT transition to PermissionWaiting
-        ctx.last_activity_at = Utc::now().timestamp_millis() - 35_000;
+        clock.advance(Duration::from_secs(35));
         let result = check_time_based_transitions(&mut ctx);
         assert_ne!(result.new_state, AgentStateType::PermissionWaiting);
     }
 
     #[test]
     fn test_text_only_idle_timeout() {
-        let mut ctx = StateContext::new();
+        let (mut ctx, clock) = manual_context();
         ctx.state = AgentStateType::Running;
         ctx.last_assistant_text_only = true;
-        // 15 seconds ago — past the 10s threshold
-        ctx.last_activity_at = Utc::now().timestamp_millis() - 15_000;
+        // 15 seconds of silence — past the 10s threshold
+        clock.advance(Duration::from_secs(15));
 
         let result = check_time_based_transitions(&mut ctx);
         assert_eq!(result.new_state, AgentStateType::Idle);
@@ -534,13 +1146,39 @@ mod tests {
         assert!(!ctx.last_assistant_text_only);
     }
 
+    #[test]
+    fn test_wall_clock_jump_does_not_fire_timeouts() {
+        let (mut ctx, clock) = manual_context();
+        ctx.state = AgentStateType::Idle;
+        // The clock is corrected forward by an hour: no time has actually passed.
+        clock.jump_wall_clock(60 * 60 * 1000);
+        assert!(!check_time_based_transitions(&mut ctx).changed);
+
+        // Corrected backwards: the silence is still measured as it passes.
+        clock.jump_wall_clock(-2 * 60 * 60 * 1000);
+        clock.advance(Duration::from_secs(31 * 60));
+        let result = check_time_based_transitions(&mut ctx);
+        assert_eq!(result.new_state, AgentStateType::Stopped);
+    }
+
+    #[test]
+    fn test_old_entry_counts_as_past_activity() {
+        let clock = Arc::new(ManualClock::new());
+        let mut ctx = StateContext::with_clock(clock.clone());
+        ctx.state = AgentStateType::Idle;
+        // Read on startup from a log last written 40 minutes ago.
+        ctx.mark_activity(clock.now_millis() - 40 * 60 * 1000);
+        let result = check_time_based_transitions(&mut ctx);
+        assert_eq!(result.new_state, AgentStateType::Stopped);
+    }
+
     #[test]
     fn test_text_only_no_idle_before_timeout() {
-        let mut ctx = StateContext::new();
+        let (mut ctx, clock) = manual_context();
         ctx.state = AgentStateType::Running;
         ctx.last_assistant_text_only = true;
-        // 5 seconds ago — before the 10s threshold
-        ctx.last_activity_at = Utc::now().timestamp_millis() - 5_000;
+        // 5 seconds of silence — before the 10s threshold
+        clock.advance(Duration::from_secs(5));
 
         let result = check_time_based_transitions(&mut ctx);
         assert_eq!(result.new_state, AgentStateType::Running);
@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+/// A gap between timer ticks this many times the interval means the machine was
+/// suspended (or the process stalled) in between.
+const GAP_FACTOR: u32 = 2;
+
+/// Spots the first timer tick after the machine wakes from sleep, by watching the
+/// wall clock between ticks: the timer itself does not see the time spent asleep.
+#[derive(Debug, Clone)]
+pub struct SuspendDetector {
+    interval: Duration,
+    last_tick_ms: Option<i64>,
+}
+
+impl SuspendDetector {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last_tick_ms: None,
+        }
+    }
+
+    /// Record a tick at wall-clock `now_ms`. Returns the time since the previous tick
+    /// if it is long enough to have been a suspend.
+    pub fn tick(&mut self, now_ms: i64) -> Option<Duration> {
+        let previous = self.last_tick_ms.replace(now_ms)?;
+        let gap = Duration::from_millis((now_ms - previous).max(0) as u64);
+        (gap > self.interval * GAP_FACTOR).then_some(gap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detects_long_gap_once() {
+        let mut detector = SuspendDetector::new(Duration::from_secs(3));
+        assert_eq!(detector.tick(0), None);
+        assert_eq!(detector.tick(3_000), None);
+        // A late tick under load is not a suspend.
+        assert_eq!(detector.tick(8_500), None);
+        assert_eq!(
+            detector.tick(8_500 + 8 * 3_600_000),
+            Some(Duration::from_secs(8 * 3_600))
+        );
+        assert_eq!(detector.tick(8_500 + 8 * 3_600_000 + 3_000), None);
+    }
+
+    #[test]
+    fn test_clock_set_backwards_is_not_a_gap() {
+        let mut detector = SuspendDetector::new(Duration::from_secs(3));
+        detector.tick(100_000);
+        assert_eq!(detector.tick(10_000), None);
+    }
+}
@@ -0,0 +1,156 @@
+//! Cutting text down for storage and display. Lengths count grapheme clusters, what
+//! a reader sees as one character, so a Japanese or emoji-heavy prompt is cut at the
+//! same visible length as an ASCII one. Bytes only cap what one cluster can add.
+
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// A limit of `n` graphemes allows at most `n` times this many bytes. Well above any
+/// real cluster (a family emoji is 25 bytes), but it stops runs of combining marks.
+pub const MAX_GRAPHEME_BYTES: usize = 32;
+const ELLIPSIS: &str = "...";
+
+/// How much text is kept, in graphemes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextLimits {
+    /// Prompts and hook messages, as stored.
+    pub message: usize,
+    /// Tool results, as stored.
+    pub tool_result: usize,
+    /// A session's initial and latest task, and its latest reply preview.
+    pub task: usize,
+    /// A search match whose hit isn't in the stored text, e.g. only in the part
+    /// truncated away.
+    pub preview: usize,
+    /// Shown on each side of a search hit.
+    pub snippet_context: usize,
+}
+
+impl Default for TextLimits {
+    fn default() -> Self {
+        Self {
+            message: 500,
+            tool_result: 300,
+            task: 200,
+            preview: 100,
+            snippet_context: 40,
+        }
+    }
+}
+
+/// `s` cut to `max` graphemes with "..." appended, or None when it fits.
+pub fn truncated(s: &str, max: usize) -> Option<String> {
+    // Every grapheme is at least a byte, so this many bytes always fit.
+    if s.len() <= max {
+        return None;
+    }
+    let ceiling = max.saturating_mul(MAX_GRAPHEME_BYTES);
+    let mut end = 0;
+    for (count, (i, grapheme)) in s.grapheme_indices(true).enumerate() {
+        if count == max || i + grapheme.len() > ceiling {
+            return Some(format!("{}{}", &s[..end], ELLIPSIS));
+        }
+        end = i + grapheme.len();
+    }
+    None
+}
+
+/// `s` cut to `max` graphemes, with "..." appended when anything was cut.
+pub fn truncate(s: &str, max: usize) -> String {
+    truncated(s, max).unwrap_or_else(|| s.to_string())
+}
+
+/// A short excerpt of `text` around the matched byte range `span`, with `context`
+/// graphemes on each side.
+pub fn make_snippet(text: &str, span: Range<usize>, context: usize) -> String {
+    let ceiling = context.saturating_mul(MAX_GRAPHEME_BYTES);
+    let before: usize = text[..span.start]
+        .graphemes(true)
+        .rev()
+        .take(context)
+        .map(str::len)
+        .scan(0, |bytes, len| {
+            *bytes += len;
+            (*bytes <= ceiling).then_some(len)
+        })
+        .sum();
+    let after: usize = text[span.end..]
+        .graphemes(true)
+        .take(context)
+        .map(str::len)
+        .scan(0, |bytes, len| {
+            *bytes += len;
+            (*bytes <= ceiling).then_some(len)
+        })
+        .sum();
+    let (start, end) = (span.start - before, span.end + after);
+
+    let mut snippet = String::new();
+    if start > 0 {
+        snippet.push_str(ELLIPSIS);
+    }
+    snippet.push_str(&text[start..end]);
+    if end < text.len() {
+        snippet.push_str(ELLIPSIS);
+    }
+    snippet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grapheme_count(s: &str) -> usize {
+        s.graphemes(true).count()
+    }
+
+    #[test]
+    fn test_truncate_counts_graphemes() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("abcdef", 3), "abc...");
+        assert_eq!(truncated("abc", 3), None);
+
+        // The same visible length whatever the script.
+        let ascii = truncate(&"a".repeat(600), 500);
+        let japanese = truncate(&"日本語".repeat(200), 500);
+        let emoji = truncate(&"👨‍👩‍👧🎉".repeat(300), 500);
+        assert_eq!(grapheme_count(&ascii), 503);
+        assert_eq!(grapheme_count(&japanese), 503);
+        assert_eq!(grapheme_count(&emoji), 503);
+        assert!(japanese.starts_with("日本語日本語"));
+        // Clusters are never split.
+        assert!(emoji.trim_end_matches(ELLIPSIS).ends_with("👨‍👩‍👧🎉"));
+        assert_eq!(truncated(&"日本語".repeat(100), 300), None);
+
+        // A pile of combining marks is one grapheme, but bytes still cap it.
+        let zalgo = format!("a{}b", "\u{301}".repeat(1000));
+        assert_eq!(grapheme_count(&zalgo), 2);
+        assert_eq!(truncate(&zalgo, 5), ELLIPSIS);
+    }
+
+    #[test]
+    fn test_snippets_keep_context_in_graphemes() {
+        let text = format!("{}needle{}", "a".repeat(100), "b".repeat(100));
+        let span = 100..106;
+        let snippet = make_snippet(&text, span, 40);
+        assert_eq!(
+            snippet,
+            format!("...{}needle{}...", "a".repeat(40), "b".repeat(40))
+        );
+        assert_eq!(make_snippet("a needle b", 2..8, 40), "a needle b");
+
+        let text = format!("{}検索{}", "前".repeat(100), "後".repeat(100));
+        let start = text.find("検索").unwrap();
+        let snippet = make_snippet(&text, start..start + "検索".len(), 40);
+        assert_eq!(
+            snippet,
+            format!("...{}検索{}...", "前".repeat(40), "後".repeat(40))
+        );
+
+        let text = format!("{}🔍{}", "😀".repeat(50), "👍🏽".repeat(50));
+        let start = text.find("🔍").unwrap();
+        let snippet = make_snippet(&text, start..start + "🔍".len(), 10);
+        assert_eq!(grapheme_count(&snippet), 3 + 10 + 1 + 10 + 3);
+        assert!(snippet.contains(&"👍🏽".repeat(10)));
+    }
+}
@@ -0,0 +1,359 @@
+use crate::days;
+use crate::types::{ActivityInterval, AgentStateType, CumulativeUsage};
+use chrono_tz::Tz;
+use std::collections::BTreeMap;
+
+/// Running periods separated by less than this are merged into one interval.
+const MERGE_GAP_MS: i64 = 5_000;
+/// Isolated Running periods shorter than this are folded into the previous interval.
+const MIN_INTERVAL_MS: i64 = 3_000;
+/// Oldest intervals are dropped beyond this many.
+const MAX_INTERVALS: usize = 300;
+/// Running time per day is kept for this many days.
+const ACTIVE_DAYS: i64 = 8;
+
+#[derive(Debug, Clone)]
+struct Interval {
+    start_ms: i64,
+    end_ms: i64,
+    start_usage: CumulativeUsage,
+    end_usage: CumulativeUsage,
+}
+
+#[derive(Debug, Clone)]
+struct OpenInterval {
+    start_ms: i64,
+    start_usage: CumulativeUsage,
+}
+
+/// Contiguous Running periods of a session, derived from its state transitions.
+#[derive(Debug, Clone, Default)]
+pub struct ActivityTimeline {
+    closed: Vec<Interval>,
+    open: Option<OpenInterval>,
+    /// Time spent Running, including periods merged or folded away above.
+    active_ms: i64,
+    /// The same by day (days since the epoch) in the zone it was counted in, for the
+    /// latest `ACTIVE_DAYS`.
+    active_days: BTreeMap<i64, i64>,
+}
+
+impl ActivityTimeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a state transition that happened at `at_ms`, with the session's usage at
+    /// that point. Running time it ends is counted on days in `tz`.
+    pub fn record(
+        &mut self,
+        previous: AgentStateType,
+        current: AgentStateType,
+        at_ms: i64,
+        usage: &CumulativeUsage,
+        tz: Tz,
+    ) {
+        let was_running = previous == AgentStateType::Running;
+        let is_running = current == AgentStateType::Running;
+
+        if is_running && !was_running && self.open.is_none() {
+            self.open = Some(OpenInterval {
+                start_ms: at_ms,
+                start_usage: usage.clone(),
+            });
+        } else if was_running && !is_running {
+            if let Some(open) = self.open.take() {
+                self.add_active(open.start_ms, at_ms, tz);
+                self.close(open, at_ms, usage);
+            }
+        }
+    }
+
+    fn add_active(&mut self, start_ms: i64, end_ms: i64, tz: Tz) {
+        if end_ms <= start_ms {
+            return;
+        }
+        self.active_ms += end_ms - start_ms;
+        for (day, ms) in days::split_by_day(start_ms, end_ms, tz) {
+            *self.active_days.entry(day).or_default() += ms;
+        }
+        if let Some(&latest) = self.active_days.keys().next_back() {
+            self.active_days = self.active_days.split_off(&(latest - ACTIVE_DAYS + 1));
+        }
+    }
+
+    /// Total time spent Running, with a still-running period counted up to `up_to_ms`.
+    pub fn active_ms(&self, up_to_ms: i64) -> u64 {
+        let open = self
+            .open
+            .as_ref()
+            .map_or(0, |open| (up_to_ms - open.start_ms).max(0));
+        (self.active_ms + open) as u64
+    }
+
+    /// Time spent Running on `day` (days since the epoch), like `active_ms`; a
+    /// still-running period is split by day in `tz`.
+    pub fn active_ms_on(&self, day: i64, up_to_ms: i64, tz: Tz) -> u64 {
+        let closed = self.active_days.get(&day).copied().unwrap_or(0);
+        let open = self.open.as_ref().map_or(0, |open| {
+            days::split_by_day(open.start_ms, up_to_ms, tz)
+                .into_iter()
+                .find(|(d, _)| *d == day)
+                .map_or(0, |(_, ms)| ms)
+        });
+        (closed + open) as u64
+    }
+
+    fn close(&mut self, open: OpenInterval, end_ms: i64, usage: &CumulativeUsage) {
+        let end_ms = end_ms.max(open.start_ms);
+        if let Some(last) = self.closed.last_mut() {
+            if open.start_ms - last.end_ms < MERGE_GAP_MS {
+                last.end_ms = last.end_ms.max(end_ms);
+                last.end_usage = usage.clone();
+                return;
+            }
+            // Too short to be worth drawing: keep its tokens, but not the idle gap before it.
+            if end_ms - open.start_ms < MIN_INTERVAL_MS {
+                last.end_usage = usage.clone();
+                return;
+            }
+        }
+
+        self.closed.push(Interval {
+            start_ms: open.start_ms,
+            end_ms,
+            start_usage: open.start_usage,
+            end_usage: usage.clone(),
+        });
+        if self.closed.len() > MAX_INTERVALS {
+            let excess = self.closed.len() - MAX_INTERVALS;
+            self.closed.drain(..excess);
+        }
+    }
+
+    /// All intervals, oldest first. A still-running interval is reported with `end: None`,
+    /// measured up to `now_ms` against `current_usage`.
+    pub fn intervals(&self, now_ms: i64, current_usage: &CumulativeUsage) -> Vec<ActivityInterval> {
+        let mut out: Vec<ActivityInterval> = self
+            .closed
+            .iter()
+            .map(|i| {
+                to_interval(
+                    i.start_ms,
+                    Some(i.end_ms),
+                    i.end_ms,
+                    &i.start_usage,
+                    &i.end_usage,
+                )
+            })
+            .collect();
+        if let Some(open) = &self.open {
+            out.push(to_interval(
+                open.start_ms,
+                None,
+                now_ms.max(open.start_ms),
+                &open.start_usage,
+                current_usage,
+            ));
+        }
+        out
+    }
+}
+
+fn to_interval(
+    start_ms: i64,
+    end_ms: Option<i64>,
+    effective_end_ms: i64,
+    start_usage: &CumulativeUsage,
+    end_usage: &CumulativeUsage,
+) -> ActivityInterval {
+    ActivityInterval {
+        start: format_ms(start_ms),
+        end: end_ms.map(format_ms),
+        duration_ms: (effective_end_ms - start_ms).max(0) as u64,
+        input_tokens: end_usage
+            .input_tokens
+            .saturating_sub(start_usage.input_tokens),
+        output_tokens: end_usage
+            .output_tokens
+            .saturating_sub(start_usage.output_tokens),
+        cache_read_tokens: end_usage
+            .cache_read_tokens
+            .saturating_sub(start_usage.cache_read_tokens),
+        cache_creation_tokens: end_usage
+            .cache_creation_tokens
+            .saturating_sub(start_usage.cache_creation_tokens),
+        estimated_cost: (end_usage.estimated_cost - start_usage.estimated_cost).max(0.0),
+    }
+}
+
+fn format_ms(ms: i64) -> String {
+    chrono::DateTime::from_timestamp_millis(ms)
+        .map(|dt| dt.to_rfc3339())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::days::DAY_MS;
+    use chrono_tz::Tz::UTC;
+    use AgentStateType::*;
+
+    fn usage(output_tokens: u64) -> CumulativeUsage {
+        CumulativeUsage {
+            output_tokens,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_running_period_becomes_interval() {
+        let mut t = ActivityTimeline::new();
+        t.record(Stopped, Running, 0, &usage(0), UTC);
+        t.record(Running, Idle, 60_000, &usage(500), UTC);
+        let intervals = t.intervals(100_000, &usage(500));
+        assert_eq!(intervals.len(), 1);
+        assert_eq!(intervals[0].duration_ms, 60_000);
+        assert_eq!(intervals[0].output_tokens, 500);
+        assert!(intervals[0].end.is_some());
+    }
+
+    #[test]
+    fn test_open_interval_reported_without_end() {
+        let mut t = ActivityTimeline::new();
+        t.record(Idle, Running, 10_000, &usage(100), UTC);
+        let intervals = t.intervals(25_000, &usage(250));
+        assert_eq!(intervals.len(), 1);
+        assert!(intervals[0].end.is_none());
+        assert_eq!(intervals[0].duration_ms, 15_000);
+        assert_eq!(intervals[0].output_tokens, 150);
+    }
+
+    #[test]
+    fn test_small_gap_merges() {
+        let mut t = ActivityTimeline::new();
+        t.record(Idle, Running, 0, &usage(0), UTC);
+        t.record(Running, PermissionWaiting, 20_000, &usage(10), UTC);
+        t.record(PermissionWaiting, Running, 22_000, &usage(10), UTC);
+        t.record(Running, Idle, 40_000, &usage(30), UTC);
+        let intervals = t.intervals(50_000, &usage(30));
+        assert_eq!(intervals.len(), 1);
+        assert_eq!(intervals[0].duration_ms, 40_000);
+        assert_eq!(intervals[0].output_tokens, 30);
+    }
+
+    #[test]
+    fn test_short_interval_folds_into_previous() {
+        let mut t = ActivityTimeline::new();
+        t.record(Idle, Running, 0, &usage(0), UTC);
+        t.record(Running, Idle, 20_000, &usage(10), UTC);
+        t.record(Idle, Running, 60_000, &usage(10), UTC);
+        t.record(Running, Idle, 61_000, &usage(12), UTC);
+        let intervals = t.intervals(70_000, &usage(12));
+        assert_eq!(intervals.len(), 1);
+        assert_eq!(intervals[0].output_tokens, 12);
+        assert_eq!(intervals[0].duration_ms, 20_000);
+    }
+
+    #[test]
+    fn test_distinct_periods_stay_separate() {
+        let mut t = ActivityTimeline::new();
+        t.record(Idle, Running, 0, &usage(0), UTC);
+        t.record(Running, Idle, 20_000, &usage(10), UTC);
+        t.record(Idle, Running, 60_000, &usage(10), UTC);
+        t.record(Running, Stopped, 90_000, &usage(40), UTC);
+        let intervals = t.intervals(100_000, &usage(40));
+        assert_eq!(intervals.len(), 2);
+        assert_eq!(intervals[1].output_tokens, 30);
+        assert_eq!(intervals[1].duration_ms, 30_000);
+    }
+
+    #[test]
+    fn test_interval_cap() {
+        let mut t = ActivityTimeline::new();
+        for i in 0..(MAX_INTERVALS as i64 + 50) {
+            let start = i * 60_000;
+            t.record(Idle, Running, start, &usage(0), UTC);
+            t.record(Running, Idle, start + 10_000, &usage(0), UTC);
+        }
+        let intervals = t.intervals(i64::MAX / 2, &usage(0));
+        assert_eq!(intervals.len(), MAX_INTERVALS);
+        assert_eq!(intervals[0].start, format_ms(50 * 60_000));
+    }
+
+    #[test]
+    fn test_active_time_counts_every_running_period() {
+        let mut t = ActivityTimeline::new();
+        t.record(Stopped, Running, 0, &usage(0), UTC);
+        t.record(Running, PermissionWaiting, 20_000, &usage(0), UTC);
+        t.record(PermissionWaiting, Running, 22_000, &usage(0), UTC);
+        t.record(Running, Idle, 40_000, &usage(0), UTC);
+        // Folded into the interval before for drawing, but still time worked.
+        t.record(Idle, Running, 60_000, &usage(0), UTC);
+        t.record(Running, Idle, 61_000, &usage(0), UTC);
+        assert_eq!(t.active_ms(100_000), 39_000);
+        assert_eq!(t.intervals(100_000, &usage(0))[0].duration_ms, 40_000);
+
+        // Reported as a change even though nothing changed: not counted twice.
+        t.record(Idle, Running, 100_000, &usage(0), UTC);
+        t.record(Running, Running, 110_000, &usage(0), UTC);
+        assert_eq!(t.active_ms(130_000), 69_000);
+        t.record(Running, Stopped, 130_000, &usage(0), UTC);
+        assert_eq!(t.active_ms(500_000), 69_000);
+    }
+
+    #[test]
+    fn test_active_time_split_at_midnight() {
+        let mut t = ActivityTimeline::new();
+        let day = 20_000;
+        let midnight = day * DAY_MS;
+        t.record(Idle, Running, midnight - 60_000, &usage(0), UTC);
+        t.record(Running, Idle, midnight + 30_000, &usage(0), UTC);
+        t.record(Idle, Running, midnight + 100_000, &usage(0), UTC);
+        assert_eq!(t.active_ms_on(day - 1, midnight + 110_000, UTC), 60_000);
+        assert_eq!(t.active_ms_on(day, midnight + 110_000, UTC), 40_000);
+        assert_eq!(t.active_ms(midnight + 110_000), 100_000);
+
+        // Only the latest days are kept.
+        t.record(Running, Idle, midnight + 110_000, &usage(0), UTC);
+        let later = midnight + (ACTIVE_DAYS - 1) * DAY_MS;
+        t.record(Idle, Running, later, &usage(0), UTC);
+        t.record(Running, Idle, later + 1_000, &usage(0), UTC);
+        assert_eq!(t.active_ms_on(day - 1, later, UTC), 0);
+        assert_eq!(t.active_ms_on(day, later, UTC), 40_000);
+        assert_eq!(t.active_ms(later), 101_000);
+    }
+
+    #[test]
+    fn test_active_time_by_zoned_day() {
+        let tokyo = chrono_tz::Tz::Asia__Tokyo;
+        let mut t = ActivityTimeline::new();
+        // 23:00 to 00:30 in Tokyo.
+        let midnight = 20_000 * DAY_MS - 9 * 3_600_000;
+        t.record(Idle, Running, midnight - 3_600_000, &usage(0), tokyo);
+        t.record(Running, Idle, midnight + 1_800_000, &usage(0), tokyo);
+        assert_eq!(
+            t.active_ms_on(19_999, midnight + 1_800_000, tokyo),
+            3_600_000
+        );
+        assert_eq!(
+            t.active_ms_on(20_000, midnight + 1_800_000, tokyo),
+            1_800_000
+        );
+
+        // Counted in UTC from now on; the days already counted stay as they were.
+        t.record(Idle, Running, midnight + 3_600_000, &usage(0), UTC);
+        t.record(Running, Idle, midnight + 7_200_000, &usage(0), UTC);
+        assert_eq!(t.active_ms_on(19_999, midnight + 7_200_000, UTC), 7_200_000);
+        assert_eq!(t.active_ms_on(20_000, midnight + 7_200_000, UTC), 1_800_000);
+    }
+
+    #[test]
+    fn test_non_running_transitions_ignored() {
+        let mut t = ActivityTimeline::new();
+        t.record(Idle, PermissionWaiting, 0, &usage(0), UTC);
+        t.record(PermissionWaiting, Stopped, 10_000, &usage(0), UTC);
+        assert!(t.intervals(20_000, &usage(0)).is_empty());
+    }
+}
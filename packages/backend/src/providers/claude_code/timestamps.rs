@@ -0,0 +1,124 @@
+//! Timestamps as clients see them. Logs may carry local offsets and any number of
+//! fractional digits; everything stored on messages and summaries is UTC RFC 3339
+//! with milliseconds (`2025-01-01T10:00:00.000Z`), so they sort as strings.
+
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Timestamps that could not be read and were replaced by the time they were seen.
+static UNPARSEABLE: AtomicU64 = AtomicU64::new(0);
+
+/// Read a log timestamp. Besides RFC 3339 this takes offsets without a colon
+/// (`+0900`) and, as UTC, times without an offset.
+pub fn parse_timestamp(raw: &str) -> Option<DateTime<Utc>> {
+    let raw = raw.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(raw) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    for format in ["%Y-%m-%dT%H:%M:%S%.f%z", "%Y-%m-%d %H:%M:%S%.f%z"] {
+        if let Ok(dt) = DateTime::parse_from_str(raw, format) {
+            return Some(dt.with_timezone(&Utc));
+        }
+    }
+    ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(raw, format).ok())
+        .map(|naive| naive.and_utc())
+}
+
+pub fn format_timestamp(dt: DateTime<Utc>) -> String {
+    dt.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// The current time, formatted like every other timestamp.
+pub fn now_timestamp() -> String {
+    format_timestamp(Utc::now())
+}
+
+/// `raw` in UTC with milliseconds. A missing or unreadable timestamp becomes the
+/// current time, and is counted.
+pub fn normalize_timestamp(raw: &str) -> String {
+    match parse_timestamp(raw) {
+        Some(dt) => format_timestamp(dt),
+        None => {
+            UNPARSEABLE.fetch_add(1, Ordering::Relaxed);
+            now_timestamp()
+        }
+    }
+}
+
+/// Timestamps replaced since the server started.
+pub fn unparseable_timestamps() -> u64 {
+    UNPARSEABLE.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offsets_and_precision() {
+        let cases = [
+            ("2025-03-01T10:00:00Z", "2025-03-01T10:00:00.000Z"),
+            ("2025-03-01T10:00:00.5Z", "2025-03-01T10:00:00.500Z"),
+            ("2025-03-01T10:00:00.123456789Z", "2025-03-01T10:00:00.123Z"),
+            ("2025-03-01T19:00:00.250+09:00", "2025-03-01T10:00:00.250Z"),
+            ("2025-03-01T05:30:00-04:30", "2025-03-01T10:00:00.000Z"),
+            ("2025-03-01T10:00:00+00:00", "2025-03-01T10:00:00.000Z"),
+            ("2025-03-01t10:00:00z", "2025-03-01T10:00:00.000Z"),
+            ("2025-03-01 10:00:00Z", "2025-03-01T10:00:00.000Z"),
+            ("2025-03-01T19:00:00+0900", "2025-03-01T10:00:00.000Z"),
+            ("2025-03-01T10:00:00.042", "2025-03-01T10:00:00.042Z"),
+            ("2025-03-02T01:00:00+15:00", "2025-03-01T10:00:00.000Z"),
+            (" 2025-03-01T10:00:00Z\n", "2025-03-01T10:00:00.000Z"),
+        ];
+        for (raw, expected) in cases {
+            assert_eq!(normalize_timestamp(raw), expected, "{:?}", raw);
+        }
+    }
+
+    #[test]
+    fn test_round_trip_and_sort_order() {
+        // Every instant comes out the same however it was written, and formatted
+        // timestamps sort as the instants do.
+        let offsets = ["Z", "+00:00", "+05:45", "-11:00", "+14:00"];
+        let mut previous: Option<String> = None;
+        for seconds in (0..86_400 * 3).step_by(7_919) {
+            let millis = seconds as i64 * 1000 + (seconds as i64 % 1000);
+            let instant = DateTime::from_timestamp_millis(1_735_689_600_000 + millis).unwrap();
+            let expected = format_timestamp(instant);
+            for offset in offsets {
+                let tz = if offset == "Z" {
+                    chrono::FixedOffset::east_opt(0).unwrap()
+                } else {
+                    offset.parse().unwrap()
+                };
+                let local = instant.with_timezone(&tz);
+                for raw in [
+                    local.to_rfc3339(),
+                    local.to_rfc3339_opts(SecondsFormat::Nanos, offset == "Z"),
+                    local.format("%Y-%m-%dT%H:%M:%S%.3f%z").to_string(),
+                ] {
+                    assert_eq!(normalize_timestamp(&raw), expected, "{:?}", raw);
+                }
+            }
+            assert_eq!(parse_timestamp(&expected), Some(instant));
+            if let Some(previous) = &previous {
+                assert!(previous < &expected);
+            }
+            previous = Some(expected);
+        }
+    }
+
+    #[test]
+    fn test_unparseable_falls_back_to_now() {
+        for raw in ["", "yesterday", "2025-13-01T00:00:00Z", "1735689600"] {
+            let before = unparseable_timestamps();
+            let start = Utc::now() - chrono::Duration::milliseconds(1);
+            let normalized = normalize_timestamp(raw);
+            let at = parse_timestamp(&normalized).unwrap();
+            assert!(at >= start && at <= Utc::now(), "{:?}", raw);
+            assert!(unparseable_timestamps() > before);
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use crate::types::{AgentMessage, MessageType, ToolCount, ToolStats};
+use std::collections::HashMap;
+
+/// Per-tool call counts for one session, built from its mapped messages. Tool results
+/// are matched to their call by tool_use id to attribute errors and output size.
+#[derive(Debug, Clone, Default)]
+pub struct ToolStatsTracker {
+    tools: HashMap<String, ToolStats>,
+    /// Tool name and message id of each call still waiting for its result, by
+    /// tool_use id.
+    pending: HashMap<String, (String, String)>,
+}
+
+impl ToolStatsTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a tool call or result; `full_text` is the untruncated result, if any.
+    /// Returns true if the stats changed.
+    pub fn record(&mut self, msg: &AgentMessage, full_text: Option<&str>) -> bool {
+        let meta = match &msg.metadata {
+            Some(meta) => meta,
+            None => return false,
+        };
+        let str_field = |key: &str| meta.get(key).and_then(|v| v.as_str());
+        match msg.msg_type {
+            MessageType::ToolUse => {
+                let name = str_field("toolName").unwrap_or(&msg.content);
+                let stats = self
+                    .tools
+                    .entry(name.to_string())
+                    .or_insert_with(|| ToolStats {
+                        name: name.to_string(),
+                        ..Default::default()
+                    });
+                stats.count += 1;
+                if let Some(id) = str_field("toolId").filter(|id| !id.is_empty()) {
+                    self.pending
+                        .insert(id.to_string(), (name.to_string(), msg.id.clone()));
+                }
+                true
+            }
+            MessageType::ToolResult => {
+                let name = match str_field("toolUseId").and_then(|id| self.pending.remove(id)) {
+                    Some((name, _)) => name,
+                    None => return false,
+                };
+                let Some(stats) = self.tools.get_mut(&name) else {
+                    return false;
+                };
+                if meta.get("isError").and_then(|v| v.as_bool()) == Some(true) {
+                    stats.error_count += 1;
+                }
+                stats.output_bytes += full_text.unwrap_or(&msg.content).len() as u64;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Point a tool result at the message with the call it answers. Call before
+    /// `record`, which forgets the call once its result is counted.
+    pub fn link_result(&self, msg: &mut AgentMessage) {
+        if !matches!(msg.msg_type, MessageType::ToolResult) {
+            return;
+        }
+        let call = msg
+            .metadata
+            .as_ref()
+            .and_then(|meta| meta.get("toolUseId"))
+            .and_then(|id| id.as_str())
+            .and_then(|id| self.pending.get(id));
+        if let Some((_, message_id)) = call {
+            msg.tool_use_message_id = Some(message_id.clone());
+        }
+    }
+
+    /// All tools, most calls first (ties by name).
+    pub fn sorted(&self) -> Vec<ToolStats> {
+        sort_tools(self.tools.values().cloned())
+    }
+
+    /// Add this session's stats to totals across sessions.
+    pub fn add_to(&self, totals: &mut HashMap<String, ToolStats>) {
+        for stats in self.tools.values() {
+            let total = totals
+                .entry(stats.name.clone())
+                .or_insert_with(|| ToolStats {
+                    name: stats.name.clone(),
+                    ..Default::default()
+                });
+            total.count += stats.count;
+            total.error_count += stats.error_count;
+            total.output_bytes += stats.output_bytes;
+        }
+    }
+
+    /// The `n` most used tools.
+    pub fn top(&self, n: usize) -> Vec<ToolCount> {
+        self.sorted()
+            .into_iter()
+            .take(n)
+            .map(|t| ToolCount {
+                name: t.name,
+                count: t.count,
+            })
+            .collect()
+    }
+}
+
+/// Most calls first, ties by name.
+pub fn sort_tools(tools: impl IntoIterator<Item = ToolStats>) -> Vec<ToolStats> {
+    let mut tools: Vec<ToolStats> = tools.into_iter().collect();
+    tools.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+    tools
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::MessageRole;
+    use serde_json::json;
+
+    fn message(msg_type: MessageType, content: &str, meta: serde_json::Value) -> AgentMessage {
+        AgentMessage {
+            id: "m".into(),
+            session_id: "s1".into(),
+            timestamp: String::new(),
+            role: MessageRole::Assistant,
+            msg_type,
+            content: content.into(),
+            metadata: serde_json::from_value(meta).ok(),
+            parent_id: None,
+            tool_use_message_id: None,
+        }
+    }
+
+    fn call(name: &str, id: &str) -> AgentMessage {
+        message(
+            MessageType::ToolUse,
+            name,
+            json!({"toolName": name, "toolId": id}),
+        )
+    }
+
+    fn result(id: &str, content: &str, is_error: bool) -> AgentMessage {
+        message(
+            MessageType::ToolResult,
+            content,
+            json!({"toolUseId": id, "isError": is_error}),
+        )
+    }
+
+    #[test]
+    fn test_results_are_attributed_to_their_call() {
+        let mut tracker = ToolStatsTracker::new();
+        tracker.record(&call("Bash", "t1"), None);
+        tracker.record(&call("Read", "t2"), None);
+        tracker.record(
+            &result("t1", "exit 1...", true),
+            Some("exit 1: no such file"),
+        );
+        tracker.record(&result("t2", "fn main() {}", false), None);
+
+        let tools = tracker.sorted();
+        assert_eq!(
+            tools,
+            [
+                ToolStats {
+                    name: "Bash".into(),
+                    count: 1,
+                    error_count: 1,
+                    output_bytes: 20,
+                },
+                ToolStats {
+                    name: "Read".into(),
+                    count: 1,
+                    error_count: 0,
+                    output_bytes: 12,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unmatched_result_is_ignored() {
+        let mut tracker = ToolStatsTracker::new();
+        assert!(!tracker.record(&result("t9", "orphan", true), None));
+        assert!(tracker.sorted().is_empty());
+    }
+
+    #[test]
+    fn test_top_orders_by_count() {
+        let mut tracker = ToolStatsTracker::new();
+        for (i, name) in ["Edit", "Bash", "Bash", "Grep", "Bash", "Edit"]
+            .iter()
+            .enumerate()
+        {
+            tracker.record(&call(name, &format!("t{}", i)), None);
+        }
+        let top = tracker.top(2);
+        assert_eq!(
+            top,
+            [
+                ToolCount {
+                    name: "Bash".into(),
+                    count: 3
+                },
+                ToolCount {
+                    name: "Edit".into(),
+                    count: 2
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,229 @@
+use crate::days;
+use crate::types::{CumulativeUsage, UsagePoint};
+use chrono_tz::Tz;
+use std::collections::{BTreeMap, VecDeque};
+
+/// Snapshots falling in the same bucket replace each other.
+const BUCKET_MS: i64 = 60_000;
+/// Two days of one-per-minute points; older points are dropped.
+const MAX_POINTS: usize = 2_880;
+
+/// Cumulative usage of a session over time, at most one point per minute.
+/// Kept apart from the message list so trimming messages doesn't lose history.
+#[derive(Debug, Clone, Default)]
+pub struct UsageSeries {
+    points: VecDeque<(i64, CumulativeUsage)>,
+    /// Cumulative usage at the end of each day (days since the epoch) with activity,
+    /// dated in the zone it was recorded in. Never trimmed: one entry per active day.
+    days: BTreeMap<i64, CumulativeUsage>,
+}
+
+impl UsageSeries {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the session's cumulative usage as of `at_ms`, on its day in `tz`.
+    pub fn record(&mut self, at_ms: i64, usage: &CumulativeUsage, tz: Tz) {
+        let latest_ms = self.points.back().map_or(at_ms, |(ms, _)| at_ms.max(*ms));
+        // A zone further west may date it before days already counted; never reorder.
+        let counted = self.days.keys().next_back().copied();
+        let day = days::day_of(latest_ms, tz).max(counted.unwrap_or(i64::MIN));
+        self.days.insert(day, usage.clone());
+
+        if let Some((last_ms, last_usage)) = self.points.back_mut() {
+            // Entry timestamps can go slightly backwards; never reorder the series.
+            if at_ms.div_euclid(BUCKET_MS) <= last_ms.div_euclid(BUCKET_MS) {
+                *last_ms = (*last_ms).max(at_ms);
+                *last_usage = usage.clone();
+                return;
+            }
+        }
+        self.points.push_back((at_ms, usage.clone()));
+        if self.points.len() > MAX_POINTS {
+            self.points.pop_front();
+        }
+    }
+
+    /// Cost accrued since the start of `from_day` (days since the epoch).
+    pub fn cost_since(&self, from_day: i64) -> f64 {
+        let Some(latest) = self.days.values().next_back() else {
+            return 0.0;
+        };
+        let before = self
+            .days
+            .range(..from_day)
+            .next_back()
+            .map_or(0.0, |(_, usage)| usage.estimated_cost);
+        (latest.estimated_cost - before).max(0.0)
+    }
+
+    /// Cost accrued on each day with activity (days since the epoch).
+    pub fn daily_costs(&self) -> BTreeMap<i64, f64> {
+        let mut previous = 0.0;
+        self.days
+            .iter()
+            .map(|(day, usage)| {
+                let cost = (usage.estimated_cost - previous).max(0.0);
+                previous = usage.estimated_cost;
+                (*day, cost)
+            })
+            .collect()
+    }
+
+    /// Input, output, cache read and cache creation tokens used on each day with
+    /// activity (days since the epoch).
+    pub fn daily_tokens(&self) -> BTreeMap<i64, u64> {
+        let mut previous = 0;
+        self.days
+            .iter()
+            .map(|(day, usage)| {
+                let total = usage.input_tokens
+                    + usage.output_tokens
+                    + usage.cache_read_tokens
+                    + usage.cache_creation_tokens;
+                let used = total.saturating_sub(previous);
+                previous = total;
+                (*day, used)
+            })
+            .collect()
+    }
+
+    /// The most recent `limit` points (all of them if None), oldest first.
+    pub fn points(&self, limit: Option<usize>) -> Vec<UsagePoint> {
+        let skip = limit.map_or(0, |n| self.points.len().saturating_sub(n));
+        self.points
+            .iter()
+            .skip(skip)
+            .map(|(at_ms, usage)| UsagePoint {
+                timestamp: chrono::DateTime::from_timestamp_millis(*at_ms)
+                    .map(|dt| dt.to_rfc3339())
+                    .unwrap_or_default(),
+                input_tokens: usage.input_tokens,
+                output_tokens: usage.output_tokens,
+                cache_read_tokens: usage.cache_read_tokens,
+                cache_creation_tokens: usage.cache_creation_tokens,
+                estimated_cost: usage.estimated_cost,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::days::DAY_MS;
+    use chrono_tz::Tz::UTC;
+
+    fn usage(output_tokens: u64) -> CumulativeUsage {
+        CumulativeUsage {
+            output_tokens,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_same_minute_coalesces() {
+        let mut s = UsageSeries::new();
+        s.record(60_000, &usage(10), UTC);
+        s.record(90_000, &usage(20), UTC);
+        s.record(125_000, &usage(30), UTC);
+        let points = s.points(None);
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0].output_tokens, 20);
+        assert_eq!(points[1].output_tokens, 30);
+    }
+
+    #[test]
+    fn test_out_of_order_timestamp_updates_last_point() {
+        let mut s = UsageSeries::new();
+        s.record(120_000, &usage(10), UTC);
+        s.record(30_000, &usage(15), UTC);
+        let points = s.points(None);
+        assert_eq!(points.len(), 1);
+        assert_eq!(points[0].output_tokens, 15);
+    }
+
+    #[test]
+    fn test_daily_costs() {
+        let cost = |estimated_cost| CumulativeUsage {
+            estimated_cost,
+            ..Default::default()
+        };
+        let mut s = UsageSeries::new();
+        s.record(1_000, &cost(1.0), UTC);
+        s.record(DAY_MS + 1_000, &cost(1.5), UTC);
+        s.record(3 * DAY_MS, &cost(4.0), UTC);
+        let days: Vec<(i64, f64)> = s.daily_costs().into_iter().collect();
+        assert_eq!(days, vec![(0, 1.0), (1, 0.5), (3, 2.5)]);
+    }
+
+    #[test]
+    fn test_daily_tokens() {
+        let tokens = |output_tokens, cache_read_tokens| CumulativeUsage {
+            output_tokens,
+            cache_read_tokens,
+            ..Default::default()
+        };
+        let mut s = UsageSeries::new();
+        s.record(1_000, &tokens(10, 100), UTC);
+        s.record(DAY_MS + 1_000, &tokens(15, 100), UTC);
+        s.record(DAY_MS + 2_000, &tokens(20, 300), UTC);
+        let days: Vec<(i64, u64)> = s.daily_tokens().into_iter().collect();
+        assert_eq!(days, vec![(0, 110), (1, 210)]);
+    }
+
+    #[test]
+    fn test_cap_and_limit() {
+        let mut s = UsageSeries::new();
+        for i in 0..(MAX_POINTS as i64 + 10) {
+            s.record(i * BUCKET_MS, &usage(i as u64), UTC);
+        }
+        let points = s.points(None);
+        assert_eq!(points.len(), MAX_POINTS);
+        assert_eq!(points[0].output_tokens, 10);
+
+        let recent = s.points(Some(3));
+        assert_eq!(recent.len(), 3);
+        assert_eq!(recent[2].output_tokens, MAX_POINTS as u64 + 9);
+    }
+
+    #[test]
+    fn test_cost_since_day_start() {
+        let cost = |estimated_cost: f64| CumulativeUsage {
+            estimated_cost,
+            ..Default::default()
+        };
+        let mut s = UsageSeries::new();
+        s.record(DAY_MS - 60_000, &cost(1.0), UTC);
+        s.record(2 * DAY_MS + 60_000, &cost(1.5), UTC);
+        s.record(2 * DAY_MS + 120_000, &cost(2.5), UTC);
+
+        assert_eq!(s.cost_since(2), 1.5);
+        assert_eq!(s.cost_since(1), 1.5);
+        assert_eq!(s.cost_since(0), 2.5);
+        assert_eq!(UsageSeries::new().cost_since(0), 0.0);
+    }
+
+    #[test]
+    fn test_days_in_zone() {
+        let cost = |estimated_cost: f64| CumulativeUsage {
+            estimated_cost,
+            ..Default::default()
+        };
+        let tokyo = chrono_tz::Tz::Asia__Tokyo;
+        let mut s = UsageSeries::new();
+        // 20:00 and 23:30 in Tokyo, either side of midnight UTC.
+        s.record(10 * DAY_MS + 11 * 3_600_000, &cost(1.0), tokyo);
+        s.record(10 * DAY_MS + 14 * 3_600_000 + 1_800_000, &cost(3.0), tokyo);
+        // 00:30 the next day.
+        s.record(10 * DAY_MS + 15 * 3_600_000 + 1_800_000, &cost(3.5), tokyo);
+        let days: Vec<(i64, f64)> = s.daily_costs().into_iter().collect();
+        assert_eq!(days, vec![(10, 3.0), (11, 0.5)]);
+
+        // Back to UTC: 16:00 UTC is still day 10 there, but day 11 is already counted.
+        s.record(10 * DAY_MS + 16 * 3_600_000, &cost(4.0), UTC);
+        let days: Vec<(i64, f64)> = s.daily_costs().into_iter().collect();
+        assert_eq!(days, vec![(10, 3.0), (11, 1.0)]);
+    }
+}
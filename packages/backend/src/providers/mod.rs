@@ -1,8 +1,12 @@
 pub mod claude_code;
 
 use crate::types::{AgentMessage, AgentSessionSummary, AgentStateType, CumulativeUsage, GitStatus};
+use serde::Serialize;
 
-#[derive(Debug, Clone)]
+/// Events are low-volume, so boxing the large payloads is not worth the indirection.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case", rename_all_fields = "camelCase")]
 pub enum ProviderEvent {
     SessionDiscovered {
         session: AgentSessionSummary,
@@ -19,12 +23,65 @@ pub enum ProviderEvent {
         session_id: String,
         message: AgentMessage,
     },
+    /// Sent instead of `NewMessage` for large batches, e.g. the initial read of a long log.
+    MessagesAppended {
+        session_id: String,
+        count: u32,
+        latest_timestamp: String,
+    },
     UsageUpdated {
         session_id: String,
         usage: CumulativeUsage,
+        spend_rate_per_hour: f64,
+        tokens_per_minute: f64,
+        context_tokens: u64,
+        context_limit: Option<u64>,
     },
     GitStatusUpdated {
         session_id: String,
         git_status: GitStatus,
     },
+    /// The context passed a warning threshold for the first time since it was last
+    /// compacted.
+    ContextWarning {
+        session_id: String,
+        percent_used: u32,
+        threshold: u32,
+    },
+    /// The main thread switched from the `previous` model to `current`.
+    ModelChanged {
+        session_id: String,
+        previous: String,
+        current: String,
+    },
+    /// The log file has been missing for several reads in a row; the session was
+    /// stopped.
+    LogDeleted {
+        session_id: String,
+        log_file: String,
+    },
+    /// Summary fields without a dedicated event changed (e.g. the session was linked
+    /// to the one it resumes).
+    SessionUpdated {
+        session: AgentSessionSummary,
+    },
+}
+
+impl ProviderEvent {
+    pub fn session_id(&self) -> &str {
+        match self {
+            Self::SessionDiscovered { session } | Self::SessionUpdated { session } => {
+                &session.session_id
+            }
+            Self::SessionRemoved { session_id }
+            | Self::StateChanged { session_id, .. }
+            | Self::NewMessage { session_id, .. }
+            | Self::MessagesAppended { session_id, .. }
+            | Self::UsageUpdated { session_id, .. }
+            | Self::GitStatusUpdated { session_id, .. }
+            | Self::ContextWarning { session_id, .. }
+            | Self::ModelChanged { session_id, .. }
+            | Self::LogDeleted { session_id, .. } => session_id,
+        }
+    }
 }
@@ -0,0 +1,421 @@
+//! JSON schema for the API types, generated from their definitions. The
+//! `gen-schema` binary writes it to `schema/all.json` for the frontend's types; the
+//! server also serves it at `GET /api/schema`, so it always matches the running
+//! binary.
+
+use crate::types;
+use schemars::schema_for;
+use serde_json::{Map, Value};
+
+const DEFINITIONS_PREFIX: &str = "#/definitions/";
+
+fn extract_and_collect(name: &str, schema: Value, definitions: &mut Map<String, Value>) {
+    // Collect definitions from this schema
+    if let Some(Value::Object(defs)) = schema.get("definitions") {
+        for (k, v) in defs {
+            definitions.insert(k.clone(), v.clone());
+        }
+    }
+
+    // Build the top-level entry: strip definitions and $schema, keep the rest
+    let mut entry = schema;
+    if let Value::Object(ref mut obj) = entry {
+        obj.remove("definitions");
+        obj.remove("$schema");
+    }
+    definitions.insert(name.to_string(), entry);
+}
+
+/// Every API type, with the types they refer to, under one `definitions` map.
+pub fn combined() -> Value {
+    let mut definitions = Map::new();
+
+    let types: Vec<(&str, Value)> = vec![
+        (
+            "AgentSessionSummary",
+            serde_json::to_value(schema_for!(types::AgentSessionSummary)).unwrap(),
+        ),
+        (
+            "AgentSessionDetail",
+            serde_json::to_value(schema_for!(types::AgentSessionDetail)).unwrap(),
+        ),
+        (
+            "AgentMessage",
+            serde_json::to_value(schema_for!(types::AgentMessage)).unwrap(),
+        ),
+        // WebSocket frames. Each connection opens with `server:hello` (protocol
+        // version, server version, capabilities); clients may reply `client:hello`.
+        (
+            "ServerEvent",
+            serde_json::to_value(schema_for!(types::ServerEvent)).unwrap(),
+        ),
+        (
+            "ClientEvent",
+            serde_json::to_value(schema_for!(types::ClientEvent)).unwrap(),
+        ),
+        (
+            "CumulativeUsage",
+            serde_json::to_value(schema_for!(types::CumulativeUsage)).unwrap(),
+        ),
+        (
+            "AgentStateType",
+            serde_json::to_value(schema_for!(types::AgentStateType)).unwrap(),
+        ),
+        (
+            "SearchScope",
+            serde_json::to_value(schema_for!(types::SearchScope)).unwrap(),
+        ),
+        (
+            "SearchMatch",
+            serde_json::to_value(schema_for!(types::SearchMatch)).unwrap(),
+        ),
+        (
+            "SessionSearchResult",
+            serde_json::to_value(schema_for!(types::SessionSearchResult)).unwrap(),
+        ),
+        (
+            "SearchResponse",
+            serde_json::to_value(schema_for!(types::SearchResponse)).unwrap(),
+        ),
+        (
+            "GitStatus",
+            serde_json::to_value(schema_for!(types::GitStatus)).unwrap(),
+        ),
+        (
+            "GitFileChange",
+            serde_json::to_value(schema_for!(types::GitFileChange)).unwrap(),
+        ),
+        (
+            "HealthResponse",
+            serde_json::to_value(schema_for!(types::HealthResponse)).unwrap(),
+        ),
+        (
+            "WatcherCounts",
+            serde_json::to_value(schema_for!(types::WatcherCounts)).unwrap(),
+        ),
+        (
+            "WatchdogStatus",
+            serde_json::to_value(schema_for!(types::WatchdogStatus)).unwrap(),
+        ),
+        (
+            "TranscriptMemory",
+            serde_json::to_value(schema_for!(types::TranscriptMemory)).unwrap(),
+        ),
+        (
+            "StartupSummary",
+            serde_json::to_value(schema_for!(types::StartupSummary)).unwrap(),
+        ),
+        (
+            "WatcherHealth",
+            serde_json::to_value(schema_for!(types::WatcherHealth)).unwrap(),
+        ),
+        (
+            "GitDiff",
+            serde_json::to_value(schema_for!(types::GitDiff)).unwrap(),
+        ),
+        (
+            "DiffSnapshotInfo",
+            serde_json::to_value(schema_for!(types::DiffSnapshotInfo)).unwrap(),
+        ),
+        (
+            "DiffSnapshot",
+            serde_json::to_value(schema_for!(types::DiffSnapshot)).unwrap(),
+        ),
+        (
+            "OpenSessionResponse",
+            serde_json::to_value(schema_for!(types::OpenSessionResponse)).unwrap(),
+        ),
+        (
+            "InterruptRequest",
+            serde_json::to_value(schema_for!(types::InterruptRequest)).unwrap(),
+        ),
+        (
+            "InterruptResponse",
+            serde_json::to_value(schema_for!(types::InterruptResponse)).unwrap(),
+        ),
+        (
+            "InterruptPlan",
+            serde_json::to_value(schema_for!(types::InterruptPlan)).unwrap(),
+        ),
+        (
+            "DismissPlan",
+            serde_json::to_value(schema_for!(types::DismissPlan)).unwrap(),
+        ),
+        (
+            "StopReplayPlan",
+            serde_json::to_value(schema_for!(types::StopReplayPlan)).unwrap(),
+        ),
+        (
+            "ReplayPlan",
+            serde_json::to_value(schema_for!(types::ReplayPlan)).unwrap(),
+        ),
+        (
+            "OpenPlan",
+            serde_json::to_value(schema_for!(types::OpenPlan)).unwrap(),
+        ),
+        (
+            "SummarizePlan",
+            serde_json::to_value(schema_for!(types::SummarizePlan)).unwrap(),
+        ),
+        (
+            "HistoryPlan",
+            serde_json::to_value(schema_for!(types::HistoryPlan)).unwrap(),
+        ),
+        (
+            "SessionBatchPlan",
+            serde_json::to_value(schema_for!(types::SessionBatchPlan)).unwrap(),
+        ),
+        (
+            "GitCommitInfo",
+            serde_json::to_value(schema_for!(types::GitCommitInfo)).unwrap(),
+        ),
+        (
+            "SessionPrefs",
+            serde_json::to_value(schema_for!(types::SessionPrefs)).unwrap(),
+        ),
+        (
+            "SessionPrefsPatch",
+            serde_json::to_value(schema_for!(types::SessionPrefsPatch)).unwrap(),
+        ),
+        (
+            "ActivityInterval",
+            serde_json::to_value(schema_for!(types::ActivityInterval)).unwrap(),
+        ),
+        (
+            "SessionTimeline",
+            serde_json::to_value(schema_for!(types::SessionTimeline)).unwrap(),
+        ),
+        (
+            "UsagePoint",
+            serde_json::to_value(schema_for!(types::UsagePoint)).unwrap(),
+        ),
+        (
+            "UsageTimeline",
+            serde_json::to_value(schema_for!(types::UsageTimeline)).unwrap(),
+        ),
+        (
+            "SessionChain",
+            serde_json::to_value(schema_for!(types::SessionChain)).unwrap(),
+        ),
+        (
+            "HistoryRequest",
+            serde_json::to_value(schema_for!(types::HistoryRequest)).unwrap(),
+        ),
+        (
+            "SessionBatchRequest",
+            serde_json::to_value(schema_for!(types::SessionBatchRequest)).unwrap(),
+        ),
+        (
+            "SessionBatchResponse",
+            serde_json::to_value(schema_for!(types::SessionBatchResponse)).unwrap(),
+        ),
+        (
+            "ToolCount",
+            serde_json::to_value(schema_for!(types::ToolCount)).unwrap(),
+        ),
+        (
+            "PendingTool",
+            serde_json::to_value(schema_for!(types::PendingTool)).unwrap(),
+        ),
+        (
+            "ToolStats",
+            serde_json::to_value(schema_for!(types::ToolStats)).unwrap(),
+        ),
+        (
+            "SessionToolStats",
+            serde_json::to_value(schema_for!(types::SessionToolStats)).unwrap(),
+        ),
+        (
+            "SourceKind",
+            serde_json::to_value(schema_for!(types::SourceKind)).unwrap(),
+        ),
+        (
+            "SourceRef",
+            serde_json::to_value(schema_for!(types::SourceRef)).unwrap(),
+        ),
+        (
+            "SessionSources",
+            serde_json::to_value(schema_for!(types::SessionSources)).unwrap(),
+        ),
+        (
+            "GeneratedSummary",
+            serde_json::to_value(schema_for!(types::GeneratedSummary)).unwrap(),
+        ),
+        (
+            "SessionOutline",
+            serde_json::to_value(schema_for!(types::SessionOutline)).unwrap(),
+        ),
+        (
+            "TurnOutline",
+            serde_json::to_value(schema_for!(types::TurnOutline)).unwrap(),
+        ),
+        (
+            "DashboardStats",
+            serde_json::to_value(schema_for!(types::DashboardStats)).unwrap(),
+        ),
+        (
+            "ProjectUsage",
+            serde_json::to_value(schema_for!(types::ProjectUsage)).unwrap(),
+        ),
+        (
+            "ProjectSessions",
+            serde_json::to_value(schema_for!(types::ProjectSessions)).unwrap(),
+        ),
+        (
+            "ReportPeriod",
+            serde_json::to_value(schema_for!(types::ReportPeriod)).unwrap(),
+        ),
+        (
+            "UsageReport",
+            serde_json::to_value(schema_for!(types::UsageReport)).unwrap(),
+        ),
+        (
+            "ReportCoverage",
+            serde_json::to_value(schema_for!(types::ReportCoverage)).unwrap(),
+        ),
+        (
+            "ReportProject",
+            serde_json::to_value(schema_for!(types::ReportProject)).unwrap(),
+        ),
+        (
+            "ReportModel",
+            serde_json::to_value(schema_for!(types::ReportModel)).unwrap(),
+        ),
+        (
+            "ReportDay",
+            serde_json::to_value(schema_for!(types::ReportDay)).unwrap(),
+        ),
+        (
+            "ReportSession",
+            serde_json::to_value(schema_for!(types::ReportSession)).unwrap(),
+        ),
+        (
+            "DayDigest",
+            serde_json::to_value(schema_for!(types::DayDigest)).unwrap(),
+        ),
+        (
+            "DigestSession",
+            serde_json::to_value(schema_for!(types::DigestSession)).unwrap(),
+        ),
+        (
+            "JournalEntry",
+            serde_json::to_value(schema_for!(types::JournalEntry)).unwrap(),
+        ),
+        (
+            "ConnectionInfo",
+            serde_json::to_value(schema_for!(types::ConnectionInfo)).unwrap(),
+        ),
+        (
+            "ConfigReload",
+            serde_json::to_value(schema_for!(types::ConfigReload)).unwrap(),
+        ),
+        (
+            "PublicSessionSummary",
+            serde_json::to_value(schema_for!(types::PublicSessionSummary)).unwrap(),
+        ),
+        (
+            "PublicStatusEvent",
+            serde_json::to_value(schema_for!(types::PublicStatusEvent)).unwrap(),
+        ),
+        (
+            "ArchivedSessionInfo",
+            serde_json::to_value(schema_for!(types::ArchivedSessionInfo)).unwrap(),
+        ),
+        (
+            "ArchivedSession",
+            serde_json::to_value(schema_for!(types::ArchivedSession)).unwrap(),
+        ),
+    ];
+
+    for (name, schema) in types {
+        extract_and_collect(name, schema, &mut definitions);
+    }
+
+    serde_json::json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "AgentsDashboard",
+        "definitions": definitions,
+        "type": "object",
+    })
+}
+
+/// One definition from `combined` as a schema of its own, carrying the definitions
+/// it refers to. None for names not defined there.
+pub fn definition(combined: &Value, name: &str) -> Option<Value> {
+    let all = combined.get("definitions")?.as_object()?;
+    let mut schema = all.get(name)?.clone();
+
+    let mut definitions = Map::new();
+    let mut pending = vec![schema.clone()];
+    while let Some(value) = pending.pop() {
+        let mut refs = Vec::new();
+        collect_refs(&value, &mut refs);
+        for name in refs {
+            if definitions.contains_key(&name) {
+                continue;
+            }
+            if let Some(def) = all.get(&name) {
+                definitions.insert(name, def.clone());
+                pending.push(def.clone());
+            }
+        }
+    }
+
+    if let Value::Object(obj) = &mut schema {
+        obj.insert(
+            "$schema".to_string(),
+            Value::from("http://json-schema.org/draft-07/schema#"),
+        );
+        obj.insert("title".to_string(), Value::from(name));
+        if !definitions.is_empty() {
+            obj.insert("definitions".to_string(), Value::Object(definitions));
+        }
+    }
+    Some(schema)
+}
+
+/// Names of the definitions `value` refers to with `$ref`.
+fn collect_refs(value: &Value, refs: &mut Vec<String>) {
+    match value {
+        Value::Object(obj) => {
+            for (key, value) in obj {
+                match (key.as_str(), value) {
+                    ("$ref", Value::String(r)) => {
+                        if let Some(name) = r.strip_prefix(DEFINITIONS_PREFIX) {
+                            refs.push(name.to_string());
+                        }
+                    }
+                    _ => collect_refs(value, refs),
+                }
+            }
+        }
+        Value::Array(items) => items.iter().for_each(|v| collect_refs(v, refs)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_definition_is_self_contained() {
+        let combined = combined();
+        let summary = definition(&combined, "AgentSessionSummary").unwrap();
+        let definitions = summary["definitions"].as_object().unwrap();
+        assert!(definitions.contains_key("CumulativeUsage"));
+        assert!(definitions.contains_key("GitStatus"));
+        // Nothing it doesn't refer to.
+        assert!(!definitions.contains_key("ServerEvent"));
+
+        let mut refs = Vec::new();
+        collect_refs(&summary, &mut refs);
+        for name in refs {
+            assert!(definitions.contains_key(&name), "{} missing", name);
+        }
+
+        let scope = definition(&combined, "SearchScope").unwrap();
+        assert!(scope.get("definitions").is_none());
+        assert!(definition(&combined, "NoSuchType").is_none());
+    }
+}
@@ -0,0 +1,206 @@
+//! Holds back usage and git status updates on their way to the broadcast channel. A
+//! busy turn can update a session's usage dozens of times a second, and clients only
+//! need the latest, so each session gets at most one of each per flush interval.
+//! Everything else passes straight through, in order.
+
+use crate::providers::ProviderEvent;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_FLUSH_INTERVAL_MS: u64 = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Kind {
+    Usage,
+    GitStatus,
+}
+
+struct Pending {
+    event: ProviderEvent,
+    due: Instant,
+}
+
+pub struct EventCoalescer {
+    interval: Duration,
+    pending: HashMap<(String, Kind), Pending>,
+}
+
+impl EventCoalescer {
+    /// A zero interval turns coalescing off.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// The event to send now, if any. A held-back update replaces the one waiting
+    /// for the same session, which keeps its place in the queue.
+    pub fn push(&mut self, event: ProviderEvent, now: Instant) -> Option<ProviderEvent> {
+        let kind = match &event {
+            _ if self.interval.is_zero() => return Some(event),
+            ProviderEvent::UsageUpdated { .. } => Kind::Usage,
+            ProviderEvent::GitStatusUpdated { .. } => Kind::GitStatus,
+            ProviderEvent::SessionRemoved { session_id } => {
+                // Nothing is left to update.
+                self.pending.retain(|(id, _), _| id != session_id);
+                return Some(event);
+            }
+            _ => return Some(event),
+        };
+        let key = (event.session_id().to_string(), kind);
+        match self.pending.get_mut(&key) {
+            Some(pending) => pending.event = event,
+            None => {
+                let due = now + self.interval;
+                self.pending.insert(key, Pending { event, due });
+            }
+        }
+        None
+    }
+
+    /// When the next held-back update is due.
+    pub fn next_due_at(&self) -> Option<Instant> {
+        self.pending.values().map(|p| p.due).min()
+    }
+
+    /// The update that has waited longest, if it is due by `now`.
+    pub fn take_due(&mut self, now: Instant) -> Option<ProviderEvent> {
+        let key = self
+            .pending
+            .iter()
+            .filter(|(_, p)| p.due <= now)
+            .min_by_key(|(_, p)| p.due)
+            .map(|(key, _)| key.clone())?;
+        self.pending.remove(&key).map(|p| p.event)
+    }
+
+    /// Wait for the next held-back update to come due. Never finishes while nothing
+    /// is held back; meant to be raced against the incoming events.
+    pub async fn next_due(&mut self) -> ProviderEvent {
+        loop {
+            match self.next_due_at() {
+                Some(due) => tokio::time::sleep_until(due.into()).await,
+                None => std::future::pending().await,
+            }
+            if let Some(event) = self.take_due(Instant::now()) {
+                return event;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{AgentStateType, CumulativeUsage, GitStatus};
+
+    fn usage(session_id: &str, output_tokens: u64) -> ProviderEvent {
+        ProviderEvent::UsageUpdated {
+            session_id: session_id.to_string(),
+            usage: CumulativeUsage {
+                output_tokens,
+                ..Default::default()
+            },
+            spend_rate_per_hour: 0.0,
+            tokens_per_minute: 0.0,
+            context_tokens: 0,
+            context_limit: None,
+        }
+    }
+
+    fn output_tokens(event: &ProviderEvent) -> u64 {
+        match event {
+            ProviderEvent::UsageUpdated { usage, .. } => usage.output_tokens,
+            other => panic!("expected a usage update, got {:?}", other),
+        }
+    }
+
+    fn interval() -> Duration {
+        Duration::from_millis(DEFAULT_FLUSH_INTERVAL_MS)
+    }
+
+    #[test]
+    fn test_rapid_updates_become_one() {
+        let mut coalescer = EventCoalescer::new(interval());
+        let start = Instant::now();
+        for i in 0..10 {
+            let at = start + Duration::from_millis(i * 10);
+            assert!(coalescer.push(usage("s1", (i + 1) * 100), at).is_none());
+        }
+        assert_eq!(coalescer.next_due_at(), Some(start + interval()));
+        assert!(coalescer
+            .take_due(start + interval() - Duration::from_millis(1))
+            .is_none());
+
+        let flushed = coalescer.take_due(start + interval()).unwrap();
+        assert_eq!(output_tokens(&flushed), 1000);
+        assert!(coalescer.take_due(start + interval() * 10).is_none());
+        assert_eq!(coalescer.next_due_at(), None);
+    }
+
+    #[test]
+    fn test_sessions_and_kinds_held_separately() {
+        let mut coalescer = EventCoalescer::new(interval());
+        let start = Instant::now();
+        coalescer.push(usage("s1", 1), start);
+        let later = start + Duration::from_millis(100);
+        coalescer.push(usage("s2", 2), later);
+        let git = ProviderEvent::GitStatusUpdated {
+            session_id: "s1".to_string(),
+            git_status: GitStatus::default(),
+        };
+        coalescer.push(git, later);
+
+        let end = start + interval() * 2;
+        let flushed: Vec<_> = std::iter::from_fn(|| coalescer.take_due(end)).collect();
+        assert_eq!(flushed.len(), 3);
+        assert_eq!(output_tokens(&flushed[0]), 1);
+        assert_eq!(flushed[0].session_id(), "s1");
+    }
+
+    #[test]
+    fn test_other_events_pass_through() {
+        let mut coalescer = EventCoalescer::new(interval());
+        let now = Instant::now();
+        coalescer.push(usage("s1", 1), now);
+        let changed = ProviderEvent::StateChanged {
+            session_id: "s1".to_string(),
+            previous: AgentStateType::Running,
+            current: AgentStateType::Idle,
+        };
+        assert!(coalescer.push(changed, now).is_some());
+        assert!(coalescer.next_due_at().is_some());
+
+        // A removed session's pending update is dropped.
+        let removed = ProviderEvent::SessionRemoved {
+            session_id: "s1".to_string(),
+        };
+        assert!(coalescer.push(removed, now).is_some());
+        assert_eq!(coalescer.next_due_at(), None);
+    }
+
+    #[test]
+    fn test_zero_interval_disables() {
+        let mut coalescer = EventCoalescer::new(Duration::ZERO);
+        let now = Instant::now();
+        for i in 1..=10 {
+            let event = coalescer.push(usage("s1", i), now).unwrap();
+            assert_eq!(output_tokens(&event), i);
+        }
+        assert_eq!(coalescer.next_due_at(), None);
+    }
+
+    #[tokio::test]
+    async fn test_next_due_waits_for_interval() {
+        let interval = Duration::from_millis(20);
+        let mut coalescer = EventCoalescer::new(interval);
+        let start = Instant::now();
+        for i in 1..=10 {
+            coalescer.push(usage("s1", i), Instant::now());
+        }
+        let flushed = coalescer.next_due().await;
+        assert_eq!(output_tokens(&flushed), 10);
+        assert!(start.elapsed() >= interval);
+    }
+}
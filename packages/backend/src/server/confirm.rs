@@ -0,0 +1,118 @@
+//! Endpoints that change something share one convention: `?dry_run=true` answers with what
+//! would happen without doing it, and acting needs an `X-Confirm: yes` header unless
+//! `server.require_confirm` is off. Handlers take a [`Confirm`] and, for a dry run,
+//! respond with their plan.
+
+use crate::server::http::AppState;
+use axum::{
+    extract::{FromRequestParts, Query},
+    http::{request::Parts, HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+pub const CONFIRM_HEADER: &str = "x-confirm";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Confirm {
+    /// Report the plan and leave everything as it is.
+    DryRun,
+    /// Go ahead.
+    Confirmed,
+}
+
+#[derive(Deserialize, Default)]
+struct DryRunQuery {
+    #[serde(default)]
+    dry_run: bool,
+}
+
+impl Confirm {
+    fn from_request(parts: &Parts, require_confirm: bool) -> Result<Self, (StatusCode, String)> {
+        let query = Query::<DryRunQuery>::try_from_uri(&parts.uri)
+            .map_err(|e| (StatusCode::BAD_REQUEST, e.body_text()))?;
+        if query.dry_run {
+            Ok(Confirm::DryRun)
+        } else if !require_confirm || confirmed(&parts.headers) {
+            Ok(Confirm::Confirmed)
+        } else {
+            Err((
+                StatusCode::PRECONDITION_REQUIRED,
+                "Send `X-Confirm: yes` to go ahead, or `?dry_run=true` to see what would happen"
+                    .to_string(),
+            ))
+        }
+    }
+
+    /// For a dry run, `plan` as the response; None to go ahead.
+    pub fn preview<T: Serialize>(self, plan: &T) -> Option<Response> {
+        (self == Confirm::DryRun).then(|| Json(plan).into_response())
+    }
+}
+
+fn confirmed(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONFIRM_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.trim().eq_ignore_ascii_case("yes"))
+}
+
+impl FromRequestParts<Arc<AppState>> for Confirm {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        Confirm::from_request(parts, state.config.load().server.require_confirm).map_err(
+            |(status, error)| (status, Json(serde_json::json!({ "error": error }))).into_response(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request;
+
+    fn check(
+        uri: &str,
+        confirm: Option<&str>,
+        require_confirm: bool,
+    ) -> Result<Confirm, StatusCode> {
+        let mut request = Request::delete(uri);
+        if let Some(value) = confirm {
+            request = request.header(CONFIRM_HEADER, value);
+        }
+        let (parts, _) = request.body(()).unwrap().into_parts();
+        Confirm::from_request(&parts, require_confirm).map_err(|(status, _)| status)
+    }
+
+    #[test]
+    fn test_dry_run_and_confirmation() {
+        let uri = "/api/sessions/s1";
+        assert_eq!(
+            check(uri, None, true),
+            Err(StatusCode::PRECONDITION_REQUIRED)
+        );
+        assert_eq!(
+            check(uri, Some("no"), true),
+            Err(StatusCode::PRECONDITION_REQUIRED)
+        );
+        assert_eq!(check(uri, Some("Yes"), true), Ok(Confirm::Confirmed));
+        assert_eq!(check(uri, None, false), Ok(Confirm::Confirmed));
+        // A dry run needs no confirmation, and wins over one.
+        let dry_run = "/api/sessions/s1?dry_run=true";
+        assert_eq!(check(dry_run, None, true), Ok(Confirm::DryRun));
+        assert_eq!(check(dry_run, Some("yes"), true), Ok(Confirm::DryRun));
+        assert_eq!(
+            check("/api/sessions/s1?dry_run=false", None, true),
+            Err(StatusCode::PRECONDITION_REQUIRED)
+        );
+        assert_eq!(
+            check("/api/sessions/s1?dry_run=maybe", Some("yes"), true),
+            Err(StatusCode::BAD_REQUEST)
+        );
+    }
+}
@@ -0,0 +1,252 @@
+use crate::types::ConnectionInfo;
+use std::collections::{BTreeMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+/// Open WebSocket connections, for spotting slow or abandoned clients.
+#[derive(Default)]
+pub struct ConnectionRegistry {
+    next_id: AtomicU64,
+    connections: RwLock<BTreeMap<u64, Arc<ConnectionStats>>>,
+    /// Lags of connections that have since closed.
+    closed_lags: AtomicU64,
+    closed_missed: AtomicU64,
+}
+
+impl ConnectionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Track a new connection until the returned guard is dropped.
+    pub fn register(self: &Arc<Self>, remote_addr: Option<SocketAddr>) -> ConnectionGuard {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let now_ms = chrono::Utc::now().timestamp_millis();
+        let stats = Arc::new(ConnectionStats {
+            id,
+            remote_addr,
+            connected_at_ms: now_ms,
+            events_sent: AtomicU64::new(0),
+            events_dropped: AtomicU64::new(0),
+            lags: AtomicU64::new(0),
+            events_missed: AtomicU64::new(0),
+            last_activity_ms: AtomicI64::new(now_ms),
+            subscriptions: Mutex::new(HashSet::new()),
+            project_subscriptions: Mutex::new(HashSet::new()),
+        });
+        self.connections.write().unwrap().insert(id, stats.clone());
+        ConnectionGuard {
+            registry: self.clone(),
+            stats,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.connections.read().unwrap().len()
+    }
+
+    /// Times connections fell behind since the server started, and the events they
+    /// missed.
+    pub fn lag_totals(&self) -> (u64, u64) {
+        let connections = self.connections.read().unwrap();
+        connections.values().fold(
+            (
+                self.closed_lags.load(Ordering::Relaxed),
+                self.closed_missed.load(Ordering::Relaxed),
+            ),
+            |(lags, missed), c| {
+                (
+                    lags + c.lags.load(Ordering::Relaxed),
+                    missed + c.events_missed.load(Ordering::Relaxed),
+                )
+            },
+        )
+    }
+
+    /// Every open connection, oldest first.
+    pub fn snapshot(&self) -> Vec<ConnectionInfo> {
+        self.connections
+            .read()
+            .unwrap()
+            .values()
+            .map(|c| c.info())
+            .collect()
+    }
+}
+
+/// Counters for one connection. Updated from the connection's own send loop, so
+/// they are atomics rather than anything that takes the registry's lock.
+pub struct ConnectionStats {
+    id: u64,
+    remote_addr: Option<SocketAddr>,
+    connected_at_ms: i64,
+    events_sent: AtomicU64,
+    events_dropped: AtomicU64,
+    lags: AtomicU64,
+    events_missed: AtomicU64,
+    last_activity_ms: AtomicI64,
+    /// Changes only on subscribe/unsubscribe, never per event.
+    subscriptions: Mutex<HashSet<String>>,
+    project_subscriptions: Mutex<HashSet<String>>,
+}
+
+impl ConnectionStats {
+    pub fn record_sent(&self) {
+        self.events_sent.fetch_add(1, Ordering::Relaxed);
+        self.touch();
+    }
+
+    /// An event was dropped or coalesced because the client is not keeping up.
+    pub fn record_dropped(&self) {
+        self.events_dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The connection fell behind a broadcast channel and missed `missed` events.
+    pub fn record_lag(&self, missed: u64) {
+        self.lags.fetch_add(1, Ordering::Relaxed);
+        self.events_missed.fetch_add(missed, Ordering::Relaxed);
+    }
+
+    pub fn record_received(&self) {
+        self.touch();
+    }
+
+    pub fn subscribe(&self, session_id: &str) {
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .insert(session_id.to_string());
+    }
+
+    pub fn unsubscribe(&self, session_id: &str) {
+        self.subscriptions.lock().unwrap().remove(session_id);
+    }
+
+    pub fn subscribe_project(&self, project_path: &str) {
+        self.project_subscriptions
+            .lock()
+            .unwrap()
+            .insert(project_path.to_string());
+    }
+
+    pub fn unsubscribe_project(&self, project_path: &str) {
+        self.project_subscriptions
+            .lock()
+            .unwrap()
+            .remove(project_path);
+    }
+
+    fn touch(&self) {
+        self.last_activity_ms
+            .store(chrono::Utc::now().timestamp_millis(), Ordering::Relaxed);
+    }
+
+    fn info(&self) -> ConnectionInfo {
+        let mut subscriptions: Vec<String> =
+            self.subscriptions.lock().unwrap().iter().cloned().collect();
+        subscriptions.sort();
+        let mut project_subscriptions: Vec<String> = self
+            .project_subscriptions
+            .lock()
+            .unwrap()
+            .iter()
+            .cloned()
+            .collect();
+        project_subscriptions.sort();
+        ConnectionInfo {
+            id: self.id,
+            remote_addr: self.remote_addr.map(|a| a.to_string()),
+            connected_at: rfc3339(self.connected_at_ms),
+            subscriptions,
+            project_subscriptions,
+            events_sent: self.events_sent.load(Ordering::Relaxed),
+            events_dropped: self.events_dropped.load(Ordering::Relaxed),
+            lags: self.lags.load(Ordering::Relaxed),
+            events_missed: self.events_missed.load(Ordering::Relaxed),
+            last_activity_at: rfc3339(self.last_activity_ms.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+fn rfc3339(ms: i64) -> String {
+    chrono::DateTime::from_timestamp_millis(ms)
+        .map(|dt| dt.to_rfc3339())
+        .unwrap_or_default()
+}
+
+/// Keeps a connection listed; unregisters it when dropped.
+pub struct ConnectionGuard {
+    registry: Arc<ConnectionRegistry>,
+    stats: Arc<ConnectionStats>,
+}
+
+impl ConnectionGuard {
+    pub fn stats(&self) -> &Arc<ConnectionStats> {
+        &self.stats
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let registry = &self.registry;
+        registry.connections.write().unwrap().remove(&self.stats.id);
+        registry
+            .closed_lags
+            .fetch_add(self.stats.lags.load(Ordering::Relaxed), Ordering::Relaxed);
+        registry.closed_missed.fetch_add(
+            self.stats.events_missed.load(Ordering::Relaxed),
+            Ordering::Relaxed,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_update_and_drop() {
+        let registry = Arc::new(ConnectionRegistry::new());
+        let first = registry.register(Some("127.0.0.1:50000".parse().unwrap()));
+        let second = registry.register(None);
+        assert_eq!(registry.count(), 2);
+
+        first.stats().record_sent();
+        first.stats().record_sent();
+        first.stats().record_dropped();
+        first.stats().record_lag(40);
+        second.stats().record_lag(3);
+        second.stats().record_lag(2);
+        first.stats().subscribe("s2");
+        first.stats().subscribe("s1");
+        second.stats().subscribe("s1");
+        second.stats().unsubscribe("s1");
+        second.stats().subscribe_project("/work/b");
+        second.stats().subscribe_project("/work/a");
+        second.stats().unsubscribe_project("/work/b");
+
+        let connections = registry.snapshot();
+        assert_eq!(
+            connections[0].remote_addr.as_deref(),
+            Some("127.0.0.1:50000")
+        );
+        assert_eq!(connections[0].events_sent, 2);
+        assert_eq!(connections[0].events_dropped, 1);
+        assert_eq!(connections[0].lags, 1);
+        assert_eq!(connections[0].events_missed, 40);
+        assert_eq!(connections[1].lags, 2);
+        assert_eq!(registry.lag_totals(), (3, 45));
+        assert_eq!(connections[0].subscriptions, ["s1", "s2"]);
+        assert_eq!(connections[1].remote_addr, None);
+        assert!(connections[1].subscriptions.is_empty());
+        assert_eq!(connections[1].project_subscriptions, ["/work/a"]);
+
+        drop(first);
+        let connections = registry.snapshot();
+        assert_eq!(connections.len(), 1);
+        assert_eq!(connections[0].id, second.stats().id);
+        // Closed connections still count.
+        assert_eq!(registry.lag_totals(), (3, 45));
+    }
+}
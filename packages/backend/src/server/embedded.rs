@@ -0,0 +1,74 @@
+//! The frontend build baked into the binary (the `embed-frontend` feature), so the
+//! server can be copied anywhere and still serve the UI.
+
+use axum::{
+    http::{header, HeaderMap, HeaderValue, StatusCode, Uri},
+    response::{IntoResponse, Response},
+};
+use rust_embed::Embed;
+
+#[derive(Embed)]
+#[folder = "../frontend/build"]
+struct Assets;
+
+/// SvelteKit puts content-hashed bundles here; their names change whenever their
+/// contents do, so they can be cached forever.
+const IMMUTABLE_PREFIX: &str = "_app/immutable/";
+
+/// Serve a frontend file, falling back to `index.html` for client-side routes.
+pub async fn serve_embedded(uri: Uri, headers: HeaderMap) -> Response {
+    let path = uri.path().trim_start_matches('/');
+    let (path, file) = match Assets::get(path).filter(|_| !path.is_empty()) {
+        Some(file) => (path, file),
+        None => match Assets::get("index.html") {
+            Some(file) => ("index.html", file),
+            None => return StatusCode::NOT_FOUND.into_response(),
+        },
+    };
+
+    let etag =
+        HeaderValue::from_str(&format!("\"{}\"", hex(&file.metadata.sha256_hash()[..16]))).unwrap();
+    let caching = [
+        (header::ETAG, etag.clone()),
+        (
+            header::CACHE_CONTROL,
+            HeaderValue::from_static(cache_control(path)),
+        ),
+    ];
+    if headers.get(header::IF_NONE_MATCH) == Some(&etag) {
+        return (StatusCode::NOT_MODIFIED, caching).into_response();
+    }
+
+    let content_type = HeaderValue::from_str(file.metadata.mimetype())
+        .unwrap_or(HeaderValue::from_static("application/octet-stream"));
+    (caching, [(header::CONTENT_TYPE, content_type)], file.data).into_response()
+}
+
+/// Hashed bundles never change; everything else (index.html above all) must be
+/// revalidated so a new build is picked up.
+fn cache_control(path: &str) -> &'static str {
+    if path.starts_with(IMMUTABLE_PREFIX) {
+        "public, max-age=31536000, immutable"
+    } else {
+        "no-cache"
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_control() {
+        assert_eq!(
+            cache_control("_app/immutable/chunks/app.3f2a1c.js"),
+            "public, max-age=31536000, immutable"
+        );
+        assert_eq!(cache_control("index.html"), "no-cache");
+        assert_eq!(cache_control("favicon.png"), "no-cache");
+    }
+}
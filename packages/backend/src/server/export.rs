@@ -0,0 +1,304 @@
+use crate::types::{
+    AgentMessage, AgentSessionDetail, AgentSessionSummary, SessionTimeline, UsageTimeline,
+};
+use axum::{
+    body::Body,
+    http::{header, HeaderValue},
+    response::{IntoResponse, Response},
+};
+use futures::stream;
+use serde::Serialize;
+use std::convert::Infallible;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    Jsonl,
+}
+
+impl ExportFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim() {
+            "json" => Some(ExportFormat::Json),
+            "jsonl" | "ndjson" => Some(ExportFormat::Jsonl),
+            _ => None,
+        }
+    }
+}
+
+/// What an export holds: the session's detail, and when it was running and what it
+/// used, as `ArchivedSession` keeps them.
+pub struct SessionExport {
+    pub detail: AgentSessionDetail,
+    /// When the session was running, as its state transitions show.
+    pub timeline: Option<SessionTimeline>,
+    pub usage: Option<UsageTimeline>,
+}
+
+/// The JSON export: an `AgentSessionDetail` with the timelines alongside.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportDocument<'a> {
+    #[serde(flatten)]
+    detail: &'a AgentSessionDetail,
+    timeline: &'a Option<SessionTimeline>,
+    usage: &'a Option<UsageTimeline>,
+}
+
+/// First line of a JSONL export. Every following line is one `AgentMessage`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportHeader<'a> {
+    #[serde(rename = "type")]
+    pub record_type: &'static str,
+    pub message_count: usize,
+    pub session: &'a AgentSessionSummary,
+    pub timeline: &'a Option<SessionTimeline>,
+    pub usage: &'a Option<UsageTimeline>,
+}
+
+pub fn export_response(export: SessionExport, format: ExportFormat) -> Response {
+    let filename = format!(
+        "{}.{}",
+        export.detail.summary.session_id,
+        match format {
+            ExportFormat::Json => "json",
+            ExportFormat::Jsonl => "jsonl",
+        }
+    );
+
+    let (body, content_type) = match format {
+        ExportFormat::Json => (Body::from_stream(json_stream(export)), "application/json"),
+        ExportFormat::Jsonl => (
+            Body::from_stream(jsonl_stream(export)),
+            "application/x-ndjson",
+        ),
+    };
+    let mut response = body.into_response();
+    response
+        .headers_mut()
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    if let Ok(value) = HeaderValue::from_str(&format!("attachment; filename=\"{}\"", filename)) {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_DISPOSITION, value);
+    }
+    response
+}
+
+/// Serialize the document around its messages, then the messages one at a time, so
+/// large sessions are never rendered into a single string.
+fn json_stream(export: SessionExport) -> impl futures::Stream<Item = Result<String, Infallible>> {
+    let SessionExport {
+        mut detail,
+        timeline,
+        usage,
+    } = export;
+    let messages = std::mem::take(&mut detail.messages);
+    let document = ExportDocument {
+        detail: &detail,
+        timeline: &timeline,
+        usage: &usage,
+    };
+    // The rest of the document, with its closing brace left off for the messages.
+    let mut fields = match serde_json::to_value(&document) {
+        Ok(serde_json::Value::Object(fields)) => fields,
+        _ => serde_json::Map::new(),
+    };
+    fields.remove("messages");
+    let mut open = serde_json::to_string(&fields).unwrap_or_else(|_| "{}".to_string());
+    open.pop();
+    if !fields.is_empty() {
+        open.push(',');
+    }
+    open.push_str("\"messages\":[");
+    let messages = messages.into_iter().enumerate().map(|(i, message)| {
+        let json = serde_json::to_string(&message).unwrap_or_default();
+        if i == 0 {
+            json
+        } else {
+            format!(",{}", json)
+        }
+    });
+    let lines = std::iter::once(open)
+        .chain(messages)
+        .chain(std::iter::once("]}".to_string()));
+    stream::iter(lines.map(Ok))
+}
+
+/// Serialize the header and then one message per line, lazily, so large sessions
+/// are never rendered into a single string.
+fn jsonl_stream(export: SessionExport) -> impl futures::Stream<Item = Result<String, Infallible>> {
+    let SessionExport {
+        detail,
+        timeline,
+        usage,
+    } = export;
+    let header = header_line(&detail.summary, detail.messages.len(), &timeline, &usage);
+    let lines = std::iter::once(header).chain(detail.messages.into_iter().map(message_line));
+    stream::iter(lines.map(Ok))
+}
+
+fn header_line(
+    summary: &AgentSessionSummary,
+    message_count: usize,
+    timeline: &Option<SessionTimeline>,
+    usage: &Option<UsageTimeline>,
+) -> String {
+    let header = ExportHeader {
+        record_type: "header",
+        message_count,
+        session: summary,
+        timeline,
+        usage,
+    };
+    let mut line = serde_json::to_string(&header).unwrap_or_default();
+    line.push('\n');
+    line
+}
+
+fn message_line(message: Arc<AgentMessage>) -> String {
+    let mut line = serde_json::to_string(&message).unwrap_or_default();
+    line.push('\n');
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{MessageRole, MessageType};
+    use futures::StreamExt;
+    use std::collections::HashMap;
+
+    fn make_detail() -> AgentSessionDetail {
+        let mut metadata = HashMap::new();
+        metadata.insert("toolName".to_string(), serde_json::json!("Bash"));
+        AgentSessionDetail {
+            summary: AgentSessionSummary {
+                session_id: "s1".into(),
+                provider: "claude-code".into(),
+                project_name: "agents-dashboard".into(),
+                ..Default::default()
+            },
+            messages: vec![
+                AgentMessage {
+                    id: "m1".into(),
+                    session_id: "s1".into(),
+                    timestamp: "2025-01-01T00:00:00Z".into(),
+                    role: MessageRole::User,
+                    msg_type: MessageType::Text,
+                    content: "Fix the bug\nplease".into(),
+                    metadata: None,
+                    parent_id: None,
+                    tool_use_message_id: None,
+                },
+                AgentMessage {
+                    id: "m2".into(),
+                    session_id: "s1".into(),
+                    timestamp: "2025-01-01T00:00:01Z".into(),
+                    role: MessageRole::Assistant,
+                    msg_type: MessageType::ToolUse,
+                    content: "ls".into(),
+                    metadata: Some(metadata),
+                    parent_id: None,
+                    tool_use_message_id: None,
+                },
+            ]
+            .into_iter()
+            .map(Arc::new)
+            .collect(),
+            usage_points: Vec::new(),
+            model_history: Vec::new(),
+            watcher: None,
+            settings: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!(ExportFormat::parse("json"), Some(ExportFormat::Json));
+        assert_eq!(ExportFormat::parse("jsonl"), Some(ExportFormat::Jsonl));
+        assert_eq!(ExportFormat::parse("xml"), None);
+    }
+
+    fn make_export() -> SessionExport {
+        SessionExport {
+            detail: make_detail(),
+            timeline: Some(SessionTimeline {
+                session_id: "s1".into(),
+                started_at: "2025-01-01T00:00:00Z".into(),
+                total_active_ms: 1_000,
+                intervals: Vec::new(),
+            }),
+            usage: None,
+        }
+    }
+
+    async fn collect(
+        stream: impl futures::Stream<Item = Result<String, Infallible>>,
+    ) -> Vec<String> {
+        stream.map(|chunk| chunk.unwrap()).collect().await
+    }
+
+    #[tokio::test]
+    async fn test_json_round_trip() {
+        let json = collect(json_stream(make_export())).await.concat();
+        let back: AgentSessionDetail = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.summary.session_id, "s1");
+        assert_eq!(back.summary.project_name, "agents-dashboard");
+        assert_eq!(back.messages.len(), 2);
+        assert_eq!(back.messages[1].msg_type, MessageType::ToolUse);
+        assert_eq!(
+            back.messages[1].metadata.as_ref().unwrap()["toolName"],
+            "Bash"
+        );
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["timeline"]["totalActiveMs"], 1_000);
+        assert!(value["usage"].is_null());
+
+        // A session without messages is still one document.
+        let mut export = make_export();
+        export.detail.messages.clear();
+        let json = collect(json_stream(export)).await.concat();
+        let back: AgentSessionDetail = serde_json::from_str(&json).unwrap();
+        assert!(back.messages.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_jsonl_round_trip() {
+        let lines = collect(jsonl_stream(make_export())).await;
+        assert_eq!(lines.len(), 3);
+        assert!(lines
+            .iter()
+            .all(|l| l.ends_with('\n') && l.matches('\n').count() == 1));
+
+        let header: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+        assert_eq!(header["type"], "header");
+        assert_eq!(header["messageCount"], 2);
+        assert_eq!(header["timeline"]["sessionId"], "s1");
+        let summary: AgentSessionSummary =
+            serde_json::from_value(header["session"].clone()).unwrap();
+        assert_eq!(summary.session_id, "s1");
+
+        let messages: Vec<AgentMessage> = lines[1..]
+            .iter()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(&*messages[0].content, "Fix the bug\nplease");
+        assert_eq!(messages[1].id, "m2");
+    }
+
+    #[tokio::test]
+    async fn test_jsonl_response_headers() {
+        let response = export_response(make_export(), ExportFormat::Jsonl);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/x-ndjson"
+        );
+        assert_eq!(
+            response.headers()[header::CONTENT_DISPOSITION],
+            "attachment; filename=\"s1.jsonl\""
+        );
+    }
+}
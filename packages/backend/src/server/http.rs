@@ -1,78 +1,1219 @@
-use crate::session::manager::SessionManager;
+use crate::config::{Config, ConfigSource};
+use crate::notifications::Notifier;
+use crate::schema;
+use crate::providers::claude_code::git_diff::{DiffError, DiffOptions, DEFAULT_DIFF_CONTEXT};
+use crate::providers::claude_code::{environment, timestamps};
+use crate::providers::claude_code::DismissError;
+use crate::server::confirm::Confirm;
+use crate::server::connections::ConnectionRegistry;
+use crate::server::export::{export_response, ExportFormat, SessionExport};
+use crate::server::ingest::ingest_handler;
+use crate::server::limits::{rate_limit, too_many_requests, ApiLimits};
+use crate::server::open;
+use crate::server::public_status::{public_events_handler, public_sessions_handler};
+use crate::server::raw_log::{raw_response, read_chunk};
+use crate::server::reload::{plan_reload, reload};
+use crate::server::report_markdown;
+use crate::server::usage_csv::build_usage_csv;
+use crate::session::archive::ArchiveFilter;
+use crate::session::journal::EventJournal;
+use crate::session::manager::{InterruptError, SessionManager};
+use crate::session::prefs::{normalize_labels, normalize_note};
+use crate::session::summarizer::SummaryError;
 use crate::server::ws::handle_ws;
-use crate::types::{SearchScope, ServerEvent};
+use crate::types::{ConnectionInfo, DashboardStats, HealthResponse, HistoryRequest, InterruptRequest, InterruptResponse, OpenPlan, ProjectUsage, ReportPeriod, SearchScope, ServerEvent, SessionBatchPlan, SessionBatchRequest, SessionPrefsPatch};
 use axum::{
-    extract::{Path, Query, State, WebSocketUpgrade},
-    http::StatusCode,
+    extract::{ConnectInfo, DefaultBodyLimit, Path, Query, State, WebSocketUpgrade},
+    http::{header, HeaderMap, StatusCode},
+    middleware,
     response::{Html, IntoResponse, Json, Response},
-    routing::get,
-    Router,
+    routing::{delete, get, patch, post, put},
+    Extension, Router,
 };
+use arc_swap::ArcSwap;
+use chrono::Datelike;
 use serde::Deserialize;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::sync::broadcast;
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::services::ServeDir;
+use tracing::{debug, info};
+
+/// Uploaded logs for `POST /api/replay` can be far larger than axum's 2 MB default.
+const REPLAY_MAX_BODY_BYTES: usize = 64 * 1024 * 1024;
+/// Most ids one `POST /api/sessions/batch` may ask for.
+const MAX_BATCH_SESSION_IDS: usize = 200;
 
 pub struct AppState {
     pub session_manager: Arc<SessionManager>,
     pub broadcast_tx: broadcast::Sender<ServerEvent>,
     pub message_tx: broadcast::Sender<ServerEvent>,
+    pub limits: Arc<ApiLimits>,
+    /// Set when `EVENT_JOURNAL_PATH` is configured.
+    pub journal: Option<Arc<EventJournal>>,
+    /// Open WebSocket connections, listed by `GET /api/connections`.
+    pub connections: Arc<ConnectionRegistry>,
+    /// The effective configuration, shown (redacted) by `GET /api/config` and
+    /// replaced on reload.
+    pub config: ArcSwap<Config>,
+    pub config_source: ConfigSource,
+    pub notifier: Arc<Notifier>,
+    /// The API's JSON schema, generated at startup and served by `GET /api/schema`.
+    pub schema: Arc<serde_json::Value>,
+}
+
+pub fn create_router(state: Arc<AppState>, frontend_dist: Option<String>) -> Router {
+    let cors = CorsLayer::new()
+        .allow_origin(Any)
+        .allow_methods(Any)
+        .allow_headers(Any);
+
+    // Endpoints that scan every session or message, rate limited per client.
+    let limited = Router::new()
+        .route("/api/sessions/{session_id}/export", get(export_handler))
+        .route("/api/search", get(search_handler))
+        .route("/api/usage/export.csv", get(usage_csv_handler))
+        .route("/api/projects/history", post(history_handler))
+        .route_layer(middleware::from_fn_with_state(
+            state.limits.clone(),
+            rate_limit,
+        ));
+
+    let api = Router::new()
+        .route("/api/health", get(health_handler))
+        .route("/api/sessions", get(sessions_handler))
+        .route("/api/sessions/batch", post(sessions_batch_handler))
+        .route(
+            "/api/sessions/{session_id}",
+            get(session_detail_handler).delete(dismiss_session_handler),
+        )
+        .route("/api/sessions/{session_id}/prefs", patch(session_prefs_handler))
+        .route("/api/sessions/{session_id}/note", put(session_note_handler))
+        .route("/api/sessions/{session_id}/labels", put(session_labels_handler))
+        .route("/api/sessions/{session_id}/timeline", get(timeline_handler))
+        .route("/api/sessions/{session_id}/chain", get(chain_handler))
+        .route("/api/sessions/{session_id}/tools", get(tools_handler))
+        .route("/api/sessions/{session_id}/sources", get(sources_handler))
+        .route("/api/sessions/{session_id}/outline", get(outline_handler))
+        .route(
+            "/api/sessions/{session_id}/summarize",
+            post(summarize_handler),
+        )
+        .route("/api/sessions/{session_id}/events", get(events_handler))
+        .route("/api/sessions/{session_id}/raw", get(raw_log_handler))
+        .route("/api/sessions/{session_id}/open", post(open_session_handler))
+        .route(
+            "/api/sessions/{session_id}/interrupt",
+            post(interrupt_session_handler),
+        )
+        .route("/api/sessions/{session_id}/diff", get(diff_handler))
+        .route(
+            "/api/sessions/{session_id}/diff/snapshots",
+            get(diff_snapshots_handler),
+        )
+        .route(
+            "/api/sessions/{session_id}/diff/snapshots/{timestamp}",
+            get(diff_snapshot_handler),
+        )
+        .route(
+            "/api/sessions/{session_id}/usage/timeline",
+            get(usage_timeline_handler),
+        )
+        .route("/api/archive", get(archive_handler))
+        .route("/api/archive/{session_id}", get(archived_session_handler))
+        .route("/api/stats", get(stats_handler))
+        .route("/api/startup", get(startup_handler))
+        .route("/api/projects", get(projects_handler))
+        .route(
+            "/api/projects/{project_path}/digest",
+            get(project_digest_handler),
+        )
+        .route("/api/reports/usage", get(usage_report_handler))
+        .route("/api/schema", get(schema_handler))
+        .route("/api/schema/{type_name}", get(schema_type_handler))
+        .route(
+            "/api/replay",
+            post(start_replay_handler).layer(DefaultBodyLimit::max(REPLAY_MAX_BODY_BYTES)),
+        )
+        .route("/api/replay/{session_id}", delete(stop_replay_handler))
+        .route("/api/connections", get(connections_handler))
+        .route("/api/config", get(config_handler))
+        .route("/api/config/reload", post(config_reload_handler))
+        .route("/ws", get(ws_handler))
+        .route("/ingest", get(ingest_handler))
+        .route("/status/api/sessions", get(public_sessions_handler))
+        .route("/status/api/events", get(public_events_handler))
+        .merge(limited)
+        .layer(cors)
+        .with_state(state.clone());
+
+    if let Some(dist_path) = frontend_dist {
+        // Serve static files from the frontend build, with SPA fallback
+        let serve_dir = ServeDir::new(&dist_path);
+        let fallback_path = dist_path.clone();
+
+        api.fallback_service(
+            serve_dir.fallback(get(move || {
+                let path = fallback_path.clone();
+                async move {
+                    match tokio::fs::read_to_string(format!("{}/index.html", path)).await {
+                        Ok(html) => Html(html).into_response(),
+                        Err(_) => StatusCode::NOT_FOUND.into_response(),
+                    }
+                }
+            })),
+        )
+    } else {
+        without_frontend_dist(api)
+    }
+}
+
+/// With the `embed-frontend` feature the UI is served from the binary itself.
+#[cfg(feature = "embed-frontend")]
+fn without_frontend_dist(api: Router) -> Router {
+    api.fallback(crate::server::embedded::serve_embedded)
+}
+
+#[cfg(not(feature = "embed-frontend"))]
+fn without_frontend_dist(api: Router) -> Router {
+    api
+}
+
+async fn health_handler(State(state): State<Arc<AppState>>) -> Json<HealthResponse> {
+    let (connection_lags, events_missed) = state.connections.lag_totals();
+    Json(HealthResponse {
+        status: "ok".to_string(),
+        instance: state.config.load().instance_name(),
+        cli_versions: state.session_manager.cli_versions().await,
+        watchers: state.session_manager.watcher_counts().await,
+        connection_lags,
+        events_missed,
+        unparseable_timestamps: timestamps::unparseable_timestamps(),
+        suppressed_git_checks: environment::suppressed_git_checks(),
+        watchdog: state.session_manager.watchdog_status(),
+        transcript_memory: state.session_manager.transcript_memory().await,
+    })
+}
+
+async fn stats_handler(State(state): State<Arc<AppState>>) -> Json<DashboardStats> {
+    Json(state.session_manager.dashboard_stats().await)
+}
+
+/// What was read at startup; 503 until the logs found then have all been read once.
+async fn startup_handler(State(state): State<Arc<AppState>>) -> Response {
+    match state.session_manager.startup_summary() {
+        Some(summary) => Json(summary).into_response(),
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({ "error": "Startup still in progress" })),
+        )
+            .into_response(),
+    }
+}
+
+async fn projects_handler(State(state): State<Arc<AppState>>) -> Json<Vec<ProjectUsage>> {
+    Json(state.session_manager.project_usage())
+}
+
+async fn schema_handler(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
+    Json((*state.schema).clone())
+}
+
+async fn schema_type_handler(
+    State(state): State<Arc<AppState>>,
+    Path(type_name): Path<String>,
+) -> Response {
+    match schema::definition(&state.schema, &type_name) {
+        Some(definition) => Json(definition).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Unknown type" })),
+        )
+            .into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct UsageReportQuery {
+    period: Option<String>,
+    format: Option<String>,
+}
+
+/// `?period=week|month` (default week), `?format=json|markdown` (default json).
+async fn usage_report_handler(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<UsageReportQuery>,
+) -> Response {
+    let bad_request = |error: &str| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": error })),
+        )
+            .into_response()
+    };
+    let period = match params.period.as_deref() {
+        None | Some("") | Some("week") => ReportPeriod::Week,
+        Some("month") => ReportPeriod::Month,
+        Some(_) => return bad_request("Unsupported report period"),
+    };
+    let markdown = match params.format.as_deref() {
+        None | Some("") | Some("json") => false,
+        Some("markdown") | Some("md") => true,
+        Some(_) => return bad_request("Unsupported report format"),
+    };
+
+    let report = state.session_manager.usage_report(period);
+    if !markdown {
+        return Json(report).into_response();
+    }
+    (
+        [(header::CONTENT_TYPE, "text/markdown; charset=utf-8")],
+        report_markdown::render(&report),
+    )
+        .into_response()
+}
+
+#[derive(Deserialize)]
+struct DigestQuery {
+    date: Option<String>,
+    format: Option<String>,
+}
+
+/// `project_path` is the project's absolute path (the `projectPath` of
+/// `GET /api/projects`), percent-encoded into one segment. `?date=YYYY-MM-DD`
+/// (default today in the configured zone), `?format=json|markdown` (default json).
+async fn project_digest_handler(
+    State(state): State<Arc<AppState>>,
+    Path(project_path): Path<String>,
+    Query(params): Query<DigestQuery>,
+) -> Response {
+    let bad_request = |error: &str| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": error })),
+        )
+            .into_response()
+    };
+    if !std::path::Path::new(&project_path).is_absolute() {
+        return bad_request("Project path must be absolute and percent-encoded");
+    }
+    let date = match params.date.as_deref() {
+        None | Some("") => state.session_manager.today(),
+        Some(date) => match chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+            Ok(date) => date,
+            Err(_) => return bad_request("Date must be YYYY-MM-DD"),
+        },
+    };
+    let markdown = match params.format.as_deref() {
+        None | Some("") | Some("json") => false,
+        Some("markdown") | Some("md") => true,
+        Some(_) => return bad_request("Unsupported digest format"),
+    };
+
+    let Some(digest) = state.session_manager.day_digest(&project_path, date).await else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Unknown project" })),
+        )
+            .into_response();
+    };
+    if !markdown {
+        return Json(digest).into_response();
+    }
+    (
+        [(header::CONTENT_TYPE, "text/markdown; charset=utf-8")],
+        report_markdown::render_digest(&digest),
+    )
+        .into_response()
+}
+
+#[derive(Deserialize)]
+struct SessionsQuery {
+    include_archived: Option<bool>,
+    group_by: Option<String>,
+    #[serde(default)]
+    group_chains: bool,
+}
+
+/// `?group_by=project` nests the sessions under their projects; `&group_chains=true`
+/// lists each resume chain once.
+async fn sessions_handler(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<SessionsQuery>,
+) -> Response {
+    let manager = &state.session_manager;
+    let include_archived = params.include_archived.unwrap_or(false);
+    match params.group_by.as_deref() {
+        None | Some("") => Json(manager.get_sessions(include_archived).await).into_response(),
+        Some("project") => {
+            let grouped = manager
+                .get_sessions_by_project(include_archived, params.group_chains)
+                .await;
+            Json(grouped).into_response()
+        }
+        Some(_) => (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "Unsupported group_by" })),
+        )
+            .into_response(),
+    }
+}
+
+async fn sessions_batch_handler(
+    State(state): State<Arc<AppState>>,
+    confirm: Confirm,
+    Json(request): Json<SessionBatchRequest>,
+) -> Response {
+    if request.session_ids.len() > MAX_BATCH_SESSION_IDS {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "error": format!("At most {} session ids per request", MAX_BATCH_SESSION_IDS)
+            })),
+        )
+            .into_response();
+    }
+    let batch = state
+        .session_manager
+        .get_session_summaries(&request.session_ids)
+        .await;
+    let mut plan = SessionBatchPlan {
+        not_found: batch.not_found.clone(),
+        ..Default::default()
+    };
+    for id in &request.session_ids {
+        if batch.sessions.contains_key(id) && !plan.found.contains(id) {
+            plan.found.push(id.clone());
+        }
+    }
+    match confirm.preview(&plan) {
+        Some(preview) => preview,
+        None => Json(batch).into_response(),
+    }
+}
+
+async fn session_detail_handler(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<String>,
+) -> Response {
+    match state.session_manager.get_session_detail(&session_id).await {
+        Some(detail) => Json(detail).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Session not found" })),
+        )
+            .into_response(),
+    }
+}
+
+/// Load old sessions of a project, past discovery's window.
+async fn history_handler(
+    State(state): State<Arc<AppState>>,
+    confirm: Confirm,
+    Json(request): Json<HistoryRequest>,
+) -> Response {
+    if request.session_ids.len() > MAX_BATCH_SESSION_IDS {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "error": format!("At most {} session ids per request", MAX_BATCH_SESSION_IDS)
+            })),
+        )
+            .into_response();
+    }
+    if let Some(preview) = state
+        .session_manager
+        .plan_history(&request.project_path, &request.session_ids)
+        .await
+        .and_then(|plan| confirm.preview(&plan))
+    {
+        return preview;
+    }
+    match state
+        .session_manager
+        .load_history(&request.project_path, &request.session_ids)
+        .await
+    {
+        Some(sessions) => Json(sessions).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "No logs for this project" })),
+        )
+            .into_response(),
+    }
+}
+
+async fn dismiss_session_handler(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<String>,
+    confirm: Confirm,
+) -> Response {
+    let result = match state.session_manager.plan_dismiss(&session_id).await {
+        Ok(plan) => match confirm.preview(&plan) {
+            Some(preview) => return preview,
+            None => state.session_manager.dismiss_session(&session_id).await,
+        },
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(DismissError::NotFound) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Session not found" })),
+        )
+            .into_response(),
+        Err(DismissError::Running) => (
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({ "error": "Session is running" })),
+        )
+            .into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct ReplayRequest {
+    /// A JSONL log under the Claude projects directory on the server's disk.
+    path: Option<String>,
+    /// The log itself, when uploaded instead of referenced by path.
+    content: Option<String>,
+    /// Playback speed relative to the recording (default 1.0).
+    speed: Option<f64>,
+}
+
+async fn start_replay_handler(
+    State(state): State<Arc<AppState>>,
+    confirm: Confirm,
+    Json(request): Json<ReplayRequest>,
+) -> Response {
+    let bad_request = |error: String| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": error })),
+        )
+            .into_response()
+    };
+    let speed = request.speed.unwrap_or(1.0);
+    if !speed.is_finite() || speed <= 0.0 {
+        return bad_request("speed must be a positive number".to_string());
+    }
+    let log = match (request.content, request.path) {
+        (Some(content), _) => content,
+        (None, Some(path)) if path.ends_with(".jsonl") => {
+            // Only logs Claude Code wrote can be replayed by path, wherever links lead.
+            let projects_dir = tokio::fs::canonicalize(state.session_manager.projects_dir()).await;
+            let resolved = match tokio::fs::canonicalize(&path).await {
+                Ok(resolved) => resolved,
+                Err(e) => return bad_request(format!("Could not read {}: {}", path, e)),
+            };
+            if !projects_dir.is_ok_and(|dir| resolved.starts_with(dir)) {
+                return (
+                    StatusCode::FORBIDDEN,
+                    Json(serde_json::json!({
+                        "error": "path must be a log in the Claude projects directory"
+                    })),
+                )
+                    .into_response();
+            }
+            match tokio::fs::read_to_string(&resolved).await {
+                Ok(log) => log,
+                Err(e) => return bad_request(format!("Could not read {}: {}", path, e)),
+            }
+        }
+        (None, Some(_)) => return bad_request("path must be a .jsonl file".to_string()),
+        (None, None) => return bad_request("Expected path or content".to_string()),
+    };
+    if confirm == Confirm::DryRun {
+        return match state.session_manager.plan_start_replay(&log, speed) {
+            Some(plan) => Json(plan).into_response(),
+            None => bad_request("Log has no entries".to_string()),
+        };
+    }
+    match state.session_manager.start_replay(&log, speed).await {
+        Some(session_id) => (
+            StatusCode::CREATED,
+            Json(serde_json::json!({ "sessionId": session_id })),
+        )
+            .into_response(),
+        None => bad_request("Log has no entries".to_string()),
+    }
+}
+
+async fn stop_replay_handler(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<String>,
+    confirm: Confirm,
+) -> Response {
+    if let Some(preview) = state
+        .session_manager
+        .plan_stop_replay(&session_id)
+        .await
+        .and_then(|plan| confirm.preview(&plan))
+    {
+        return preview;
+    }
+    if state.session_manager.stop_replay(&session_id).await {
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Replay not found" })),
+        )
+            .into_response()
+    }
+}
+
+async fn session_prefs_handler(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<String>,
+    confirm: Confirm,
+    Json(patch): Json<SessionPrefsPatch>,
+) -> Response {
+    if let Some(preview) = state
+        .session_manager
+        .plan_prefs(&session_id, |prefs| patch.apply(prefs))
+        .await
+        .and_then(|plan| confirm.preview(&plan))
+    {
+        return preview;
+    }
+    let prefs = state.session_manager.update_prefs(&session_id, &patch).await;
+    prefs_response(&state, &session_id, prefs).await
+}
+
+/// Body is the note as plain text. An empty body clears it.
+async fn session_note_handler(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<String>,
+    confirm: Confirm,
+    body: String,
+) -> Response {
+    let note = match normalize_note(&body) {
+        Ok(note) => note,
+        Err(e) => {
+            return (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": e })))
+                .into_response()
+        }
+    };
+    if let Some(preview) = state
+        .session_manager
+        .plan_prefs(&session_id, |prefs| prefs.note = note.clone())
+        .await
+        .and_then(|plan| confirm.preview(&plan))
+    {
+        return preview;
+    }
+    let prefs = state.session_manager.set_note(&session_id, note).await;
+    prefs_response(&state, &session_id, prefs).await
+}
+
+async fn session_labels_handler(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<String>,
+    confirm: Confirm,
+    Json(labels): Json<Vec<String>>,
+) -> Response {
+    let labels = match normalize_labels(&labels) {
+        Ok(labels) => labels,
+        Err(e) => {
+            return (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": e })))
+                .into_response()
+        }
+    };
+    if let Some(preview) = state
+        .session_manager
+        .plan_prefs(&session_id, |prefs| prefs.labels = labels.clone())
+        .await
+        .and_then(|plan| confirm.preview(&plan))
+    {
+        return preview;
+    }
+    let prefs = state.session_manager.set_labels(&session_id, labels).await;
+    prefs_response(&state, &session_id, prefs).await
+}
+
+/// Respond with the updated prefs and let other dashboards know the session changed.
+async fn prefs_response(
+    state: &AppState,
+    session_id: &str,
+    prefs: Option<crate::types::SessionPrefs>,
+) -> Response {
+    let Some(prefs) = prefs else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Session not found" })),
+        )
+            .into_response();
+    };
+    // Archiving takes the session off the channels, so clients are told to drop it.
+    if prefs.archived {
+        let _ = state.broadcast_tx.send(ServerEvent::SessionRemoved {
+            session_id: session_id.to_string(),
+        });
+    } else if let Some(session) = state.session_manager.get_session_summary(session_id).await {
+        let _ = state
+            .broadcast_tx
+            .send(ServerEvent::SessionUpdated { session });
+    }
+    Json(prefs).into_response()
+}
+
+async fn timeline_handler(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<String>,
+) -> Response {
+    match state.session_manager.get_session_timeline(&session_id).await {
+        Some(timeline) => Json(timeline).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Session not found" })),
+        )
+            .into_response(),
+    }
+}
+
+async fn tools_handler(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<String>,
+) -> Response {
+    match state.session_manager.get_tool_stats(&session_id).await {
+        Some(stats) => Json(stats).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Session not found" })),
+        )
+            .into_response(),
+    }
+}
+
+async fn sources_handler(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<String>,
+) -> Response {
+    match state.session_manager.get_sources(&session_id).await {
+        Some(sources) => Json(sources).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Session not found" })),
+        )
+            .into_response(),
+    }
+}
+
+async fn outline_handler(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<String>,
+) -> Response {
+    match state.session_manager.get_outline(&session_id).await {
+        Some(outline) => Json(outline).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Session not found" })),
+        )
+            .into_response(),
+    }
+}
+
+async fn summarize_handler(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<String>,
+    confirm: Confirm,
+) -> Response {
+    let result = match state.session_manager.plan_summarize(&session_id).await {
+        Ok(plan) => match confirm.preview(&plan) {
+            Some(preview) => return preview,
+            None => state.session_manager.summarize(&session_id).await,
+        },
+        Err(e) => Err(e),
+    };
+    let error = match result {
+        Ok(summary) => {
+            if let Some(session) = state.session_manager.get_session_summary(&session_id).await {
+                let _ = state
+                    .broadcast_tx
+                    .send(ServerEvent::SessionUpdated { session });
+            }
+            return Json(summary).into_response();
+        }
+        Err(e) => e,
+    };
+    let body = Json(serde_json::json!({ "error": error.to_string() }));
+    let status = match error {
+        SummaryError::Disabled => StatusCode::SERVICE_UNAVAILABLE,
+        SummaryError::SessionNotFound => StatusCode::NOT_FOUND,
+        SummaryError::Empty => StatusCode::CONFLICT,
+        SummaryError::RateLimited(wait) => {
+            let secs = wait.as_secs().max(1).to_string();
+            return (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, secs)],
+                body,
+            )
+                .into_response();
+        }
+        SummaryError::Api(_) => StatusCode::BAD_GATEWAY,
+    };
+    (status, body).into_response()
+}
+
+#[derive(Deserialize)]
+struct EventsQuery {
+    since: Option<String>,
+}
+
+async fn events_handler(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<String>,
+    Query(params): Query<EventsQuery>,
+) -> Response {
+    let Some(journal) = &state.journal else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Event journal is not enabled" })),
+        )
+            .into_response();
+    };
+    let since =
+        match params.since.as_deref() {
+            None | Some("") => None,
+            Some(s) => match chrono::DateTime::parse_from_rfc3339(s) {
+                Ok(dt) => Some(dt.with_timezone(&chrono::Utc)),
+                Err(_) => return (
+                    StatusCode::BAD_REQUEST,
+                    Json(serde_json::json!({ "error": "Expected since as an RFC 3339 timestamp" })),
+                )
+                    .into_response(),
+            },
+        };
+    match journal.read_session(&session_id, since).await {
+        Ok(entries) => Json(entries).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": format!("Could not read journal: {}", e) })),
+        )
+            .into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct RawLogQuery {
+    #[serde(default)]
+    offset: u64,
+    limit: Option<u64>,
+}
+
+async fn raw_log_handler(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<String>,
+    Query(params): Query<RawLogQuery>,
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+    headers: HeaderMap,
+) -> Response {
+    let peer = connect_info.map(|Extension(ConnectInfo(addr))| addr);
+    let origin = headers.get(header::ORIGIN).and_then(|v| v.to_str().ok());
+    if !open::is_local(peer, origin) {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(serde_json::json!({
+                "error": "Raw logs can only be read from the machine the dashboard runs on"
+            })),
+        )
+            .into_response();
+    }
+    let Some(path) = state.session_manager.log_file(&session_id).await else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Session not found" })),
+        )
+            .into_response();
+    };
+    let max = state.config.load().server.raw_log_max_bytes;
+    let limit = params.limit.unwrap_or(max).min(max);
+    match read_chunk(&path, params.offset, limit).await {
+        Ok(chunk) => {
+            let redactor = state
+                .session_manager
+                .session_settings()
+                .load()
+                .redactor
+                .clone();
+            raw_response(chunk, &redactor)
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => (
+            StatusCode::GONE,
+            Json(serde_json::json!({ "error": "The log file no longer exists" })),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": format!("Could not read log: {}", e) })),
+        )
+            .into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct DiffQuery {
+    #[serde(default)]
+    staged: bool,
+    path: Option<String>,
+    context: Option<u32>,
+}
+
+/// Most context lines a diff request may ask for.
+const MAX_DIFF_CONTEXT: u32 = 1000;
+
+async fn diff_handler(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<String>,
+    Query(params): Query<DiffQuery>,
+) -> Response {
+    let path = params.path.filter(|p| !p.is_empty());
+    // Paths are relative to the repository root and stay inside it.
+    if path.as_deref().is_some_and(|p| {
+        std::path::Path::new(p)
+            .components()
+            .any(|c| !matches!(c, std::path::Component::Normal(_)))
+    }) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "path must be relative to the repository root" })),
+        )
+            .into_response();
+    }
+    let options = DiffOptions {
+        staged: params.staged,
+        path,
+        context: params
+            .context
+            .unwrap_or(DEFAULT_DIFF_CONTEXT)
+            .min(MAX_DIFF_CONTEXT),
+    };
+    let max_bytes = state.config.load().server.diff_max_bytes;
+    match state
+        .session_manager
+        .session_diff(&session_id, options, max_bytes)
+        .await
+    {
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Session not found" })),
+        )
+            .into_response(),
+        Some(Ok(diff)) => Json(diff).into_response(),
+        Some(Err(DiffError::NotARepository)) => (
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({
+                "error": "The session's working directory is not in a git repository"
+            })),
+        )
+            .into_response(),
+        Some(Err(DiffError::Git(e))) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": format!("git diff failed: {}", e) })),
+        )
+            .into_response(),
+    }
 }
 
-pub fn create_router(state: Arc<AppState>, frontend_dist: Option<String>) -> Router {
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any);
+/// Saved diffs outlive the session, so this lists them (possibly none) for any id.
+async fn diff_snapshots_handler(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<String>,
+) -> Response {
+    Json(state.session_manager.diff_snapshots(&session_id).await).into_response()
+}
 
-    let api = Router::new()
-        .route("/api/health", get(health_handler))
-        .route("/api/sessions", get(sessions_handler))
-        .route("/api/sessions/{session_id}", get(session_detail_handler))
-        .route("/api/search", get(search_handler))
-        .route("/ws", get(ws_handler))
-        .layer(cors)
-        .with_state(state.clone());
+async fn diff_snapshot_handler(
+    State(state): State<Arc<AppState>>,
+    Path((session_id, timestamp)): Path<(String, i64)>,
+) -> Response {
+    match state
+        .session_manager
+        .diff_snapshot(&session_id, timestamp)
+        .await
+    {
+        Some(snapshot) => Json(snapshot).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Snapshot not found" })),
+        )
+            .into_response(),
+    }
+}
 
-    if let Some(dist_path) = frontend_dist {
-        // Serve static files from the frontend build, with SPA fallback
-        let serve_dir = ServeDir::new(&dist_path);
-        let fallback_path = dist_path.clone();
+#[derive(Deserialize)]
+struct ArchiveQuery {
+    project: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+}
 
-        api.fallback_service(
-            serve_dir.fallback(get(move || {
-                let path = fallback_path.clone();
-                async move {
-                    match tokio::fs::read_to_string(format!("{}/index.html", path)).await {
-                        Ok(html) => Html(html).into_response(),
-                        Err(_) => StatusCode::NOT_FOUND.into_response(),
-                    }
-                }
+fn archive_off() -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(serde_json::json!({
+            "error": "Archiving is turned off; set ARCHIVE=1 to archive stopped sessions"
+        })),
+    )
+        .into_response()
+}
+
+/// `from` / `to` are UTC days (YYYY-MM-DD) of the sessions' last activity.
+async fn archive_handler(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<ArchiveQuery>,
+) -> Response {
+    let parse = |value: &Option<String>| match value.as_deref() {
+        None | Some("") => Ok(None),
+        Some(s) => chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d").map(Some),
+    };
+    let filter = match (parse(&params.from), parse(&params.to)) {
+        (Ok(from), Ok(to)) if from.zip(to).is_none_or(|(from, to)| from <= to) => ArchiveFilter {
+            project: params.project.filter(|p| !p.is_empty()),
+            from,
+            to,
+        },
+        _ => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": "Expected from <= to as YYYY-MM-DD" })),
+            )
+                .into_response()
+        }
+    };
+    match state.session_manager.archived_sessions(&filter) {
+        Some(sessions) => Json(sessions).into_response(),
+        None => archive_off(),
+    }
+}
+
+async fn archived_session_handler(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<String>,
+) -> Response {
+    if !state.session_manager.archiving() {
+        return archive_off();
+    }
+    match state.session_manager.archived_session(&session_id).await {
+        Some(session) => Json(session).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Archived session not found" })),
+        )
+            .into_response(),
+    }
+}
+
+async fn open_session_handler(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<String>,
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+    headers: HeaderMap,
+    confirm: Confirm,
+) -> Response {
+    let forbidden = |error: &str| {
+        (
+            StatusCode::FORBIDDEN,
+            Json(serde_json::json!({ "error": error })),
+        )
+            .into_response()
+    };
+    let config = state.config.load();
+    if !config.server.allow_open_commands {
+        return forbidden("Opening sessions is turned off; set ALLOW_OPEN_COMMANDS=1 to allow it");
+    }
+    let peer = connect_info.map(|Extension(ConnectInfo(addr))| addr);
+    let origin = headers.get(header::ORIGIN).and_then(|v| v.to_str().ok());
+    if !open::is_local(peer, origin) {
+        return forbidden("Sessions can only be opened from the machine the dashboard runs on");
+    }
+    let Some(template) = &config.server.open_command else {
+        return (
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({ "error": "No command configured; set OPEN_COMMAND" })),
+        )
+            .into_response();
+    };
+    let Some(working_directory) = state.session_manager.working_directory(&session_id).await else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Session not found" })),
+        )
+            .into_response();
+    };
+    let dir = std::path::Path::new(&working_directory);
+    if !dir.is_dir() {
+        return (
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({
+                "error": format!("{} is not a directory on this machine", working_directory)
             })),
         )
-    } else {
-        api
+            .into_response();
+    }
+    // Checked when the configuration was loaded.
+    let Ok(words) = open::split_template(template) else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let command = open::command_for(&words, &working_directory);
+    let plan = OpenPlan {
+        session_id: session_id.clone(),
+        command: command.clone(),
+        working_directory: working_directory.clone(),
+    };
+    if let Some(preview) = confirm.preview(&plan) {
+        return preview;
+    }
+    info!("[Open] {}: running {:?}", session_id, command);
+    Json(open::run(command, dir).await).into_response()
+}
+
+async fn interrupt_session_handler(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<String>,
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+    headers: HeaderMap,
+    confirm: Confirm,
+    body: Option<Json<InterruptRequest>>,
+) -> Response {
+    let forbidden = |error: &str| {
+        (
+            StatusCode::FORBIDDEN,
+            Json(serde_json::json!({ "error": error })),
+        )
+            .into_response()
+    };
+    let config = state.config.load();
+    if !config.server.allow_session_control {
+        return forbidden("Session control is turned off; set ALLOW_SESSION_CONTROL=1 to allow it");
+    }
+    let peer = connect_info.map(|Extension(ConnectInfo(addr))| addr);
+    let origin = headers.get(header::ORIGIN).and_then(|v| v.to_str().ok());
+    if !open::is_local(peer, origin) {
+        return forbidden(
+            "Sessions can only be interrupted from the machine the dashboard runs on",
+        );
+    }
+    let pid = body.and_then(|Json(body)| body.pid);
+    let signal = config.server.interrupt_signal;
+    let result = match state
+        .session_manager
+        .plan_interrupt(&session_id, pid, signal)
+        .await
+    {
+        Ok(plan) => match confirm.preview(&plan) {
+            Some(preview) => return preview,
+            None => {
+                state
+                    .session_manager
+                    .interrupt_session(&session_id, Some(plan.pid), signal)
+                    .await
+            }
+        },
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(pid) => Json(InterruptResponse {
+            pid,
+            signal: signal.to_string(),
+        })
+        .into_response(),
+        Err(InterruptError::SessionNotFound) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Session not found" })),
+        )
+            .into_response(),
+        Err(InterruptError::NoProcess) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({
+                "error": "No matching Claude process runs in the session's directory"
+            })),
+        )
+            .into_response(),
+        Err(InterruptError::Ambiguous(pids)) => (
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({
+                "error": "Several Claude processes run in the session's directory; pass a pid",
+                "pids": pids,
+            })),
+        )
+            .into_response(),
+        Err(InterruptError::Unavailable) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({ "error": "Processes can't be listed on this machine" })),
+        )
+            .into_response(),
+        Err(InterruptError::Signal(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e })),
+        )
+            .into_response(),
     }
 }
 
-async fn health_handler() -> Json<serde_json::Value> {
-    Json(serde_json::json!({ "status": "ok" }))
+async fn usage_timeline_handler(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<String>,
+) -> Response {
+    match state.session_manager.get_usage_timeline(&session_id).await {
+        Some(timeline) => Json(timeline).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Session not found" })),
+        )
+            .into_response(),
+    }
 }
 
-async fn sessions_handler(
+async fn chain_handler(
     State(state): State<Arc<AppState>>,
-) -> Json<Vec<crate::types::AgentSessionSummary>> {
-    let sessions = state.session_manager.get_sessions().await;
-    Json(sessions)
+    Path(session_id): Path<String>,
+) -> Response {
+    match state.session_manager.get_session_chain(&session_id).await {
+        Some(chain) => Json(chain).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Session not found" })),
+        )
+            .into_response(),
+    }
 }
 
-async fn session_detail_handler(
+#[derive(Deserialize)]
+struct ExportQuery {
+    format: Option<String>,
+}
+
+async fn export_handler(
     State(state): State<Arc<AppState>>,
     Path(session_id): Path<String>,
+    Query(params): Query<ExportQuery>,
 ) -> Response {
-    match state.session_manager.get_session_detail(&session_id).await {
-        Some(detail) => Json(detail).into_response(),
+    let format = match params.format.as_deref() {
+        None | Some("") => ExportFormat::Json,
+        Some(f) => match ExportFormat::parse(f) {
+            Some(format) => format,
+            None => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(serde_json::json!({ "error": "Unsupported export format" })),
+                )
+                    .into_response()
+            }
+        },
+    };
+
+    let manager = &state.session_manager;
+    match manager.get_session_detail(&session_id).await {
+        Some(detail) => {
+            let export = SessionExport {
+                detail,
+                timeline: manager.get_session_timeline(&session_id).await,
+                usage: manager.get_usage_timeline(&session_id).await,
+            };
+            export_response(export, format)
+        }
         None => (
             StatusCode::NOT_FOUND,
             Json(serde_json::json!({ "error": "Session not found" })),
@@ -81,23 +1222,106 @@ async fn session_detail_handler(
     }
 }
 
+#[derive(Deserialize)]
+struct UsageExportQuery {
+    from: Option<String>,
+    to: Option<String>,
+}
+
+/// `from` / `to` are dates in the configured zone; they default to the current month.
+async fn usage_csv_handler(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<UsageExportQuery>,
+) -> Response {
+    let today = state.session_manager.today();
+    let parse = |value: &Option<String>, default: chrono::NaiveDate| match value.as_deref() {
+        None | Some("") => Ok(default),
+        Some(s) => chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d"),
+    };
+    let (from, to) = match (
+        parse(&params.from, today.with_day(1).unwrap_or(today)),
+        parse(&params.to, today),
+    ) {
+        (Ok(from), Ok(to)) if from <= to => (from, to),
+        _ => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": "Expected from <= to as YYYY-MM-DD" })),
+            )
+                .into_response()
+        }
+    };
+
+    let sessions = state.session_manager.get_sessions(true).await;
+    let csv = build_usage_csv(&sessions, from, to, state.session_manager.timezone());
+    let disposition = format!("attachment; filename=\"usage-{}-{}.csv\"", from, to);
+    (
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        csv,
+    )
+        .into_response()
+}
+
 async fn ws_handler(
     ws: WebSocketUpgrade,
     State(state): State<Arc<AppState>>,
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
 ) -> impl IntoResponse {
     let session_manager = state.session_manager.clone();
+    // Subscribed before `handle_ws` takes its snapshot, so no event is missed.
     let broadcast_rx = state.broadcast_tx.subscribe();
     let message_rx = state.message_tx.subscribe();
+    let remote_addr = connect_info.map(|Extension(ConnectInfo(addr))| addr);
+    let connections = state.connections.clone();
 
     ws.on_upgrade(move |socket| {
-        handle_ws(socket, session_manager, broadcast_rx, message_rx)
+        let connection = connections.register(remote_addr);
+        debug!("WebSocket connected ({} open)", connections.count());
+        handle_ws(socket, session_manager, broadcast_rx, message_rx, connection)
     })
 }
 
+async fn connections_handler(State(state): State<Arc<AppState>>) -> Json<Vec<ConnectionInfo>> {
+    Json(state.connections.snapshot())
+}
+
+/// The configuration in effect, file and environment combined, with secrets redacted.
+async fn config_handler(State(state): State<Arc<AppState>>) -> Json<Config> {
+    Json(state.config.load().redacted())
+}
+
+async fn config_reload_handler(State(state): State<Arc<AppState>>, confirm: Confirm) -> Response {
+    let result = match plan_reload(&state) {
+        Ok(plan) => match confirm.preview(&plan) {
+            Some(preview) => return preview,
+            None => reload(&state),
+        },
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(result) => Json(result).into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        )
+            .into_response(),
+    }
+}
+
 #[derive(Deserialize)]
 struct SearchQuery {
     q: String,
     scope: Option<String>,
+    #[serde(default)]
+    case_sensitive: bool,
+    /// Only sessions on this host.
+    host: Option<String>,
+    /// One result per resume chain.
+    #[serde(default)]
+    group_chains: bool,
 }
 
 fn parse_scopes(scope_str: &str) -> Vec<SearchScope> {
@@ -105,19 +1329,25 @@ fn parse_scopes(scope_str: &str) -> Vec<SearchScope> {
         .split(',')
         .filter_map(|s| match s.trim() {
             "project_name" => Some(SearchScope::ProjectName),
+            "title" => Some(SearchScope::Title),
             "current_task" => Some(SearchScope::CurrentTask),
             "working_directory" => Some(SearchScope::WorkingDirectory),
             "content" => Some(SearchScope::Content),
+            "note" => Some(SearchScope::Note),
+            "labels" => Some(SearchScope::Labels),
             _ => None,
         })
         .collect()
 }
 
-const ALL_SCOPES: [SearchScope; 4] = [
+const ALL_SCOPES: [SearchScope; 7] = [
     SearchScope::ProjectName,
+    SearchScope::Title,
     SearchScope::CurrentTask,
     SearchScope::WorkingDirectory,
     SearchScope::Content,
+    SearchScope::Note,
+    SearchScope::Labels,
 ];
 
 async fn search_handler(
@@ -131,6 +1361,18 @@ async fn search_handler(
         )
             .into_response();
     }
+    if params.q.len() > state.limits.max_query_len() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "error": format!("Query is longer than {} bytes", state.limits.max_query_len())
+            })),
+        )
+            .into_response();
+    }
+    let Some(_slot) = state.limits.try_search_slot() else {
+        return too_many_requests(std::time::Duration::from_secs(1));
+    };
 
     let scopes = match &params.scope {
         Some(s) if !s.is_empty() => {
@@ -146,7 +1388,475 @@ async fn search_handler(
 
     let response = state
         .session_manager
-        .search_sessions(&params.q, &scopes)
+        .search_sessions(
+            &params.q,
+            params.case_sensitive,
+            &scopes,
+            params.host.as_deref(),
+            params.group_chains,
+        )
         .await;
     Json(response).into_response()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::relay::RelayConfig;
+    use crate::session::prefs::PrefsStore;
+    use axum::body::Body;
+    use axum::http::Request;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    fn state(limits: ApiLimits) -> Arc<AppState> {
+        state_with_source(limits, ConfigSource::default())
+    }
+
+    fn state_with_source(limits: ApiLimits, config_source: ConfigSource) -> Arc<AppState> {
+        let (broadcast_tx, _) = broadcast::channel(16);
+        let (message_tx, _) = broadcast::channel(16);
+        Arc::new(AppState {
+            session_manager: Arc::new(SessionManager::with_prefs(PrefsStore::in_memory())),
+            broadcast_tx,
+            message_tx,
+            limits: Arc::new(limits),
+            journal: None,
+            connections: Arc::new(ConnectionRegistry::new()),
+            config: ArcSwap::from_pointee(Config::default()),
+            config_source,
+            notifier: Arc::new(Notifier::new(None)),
+            schema: Arc::new(schema::combined()),
+        })
+    }
+
+    fn router(limits: ApiLimits) -> Router {
+        create_router(state(limits), None)
+    }
+
+    async fn get(router: &Router, uri: &str) -> Response {
+        let request = Request::get(uri).body(Body::empty()).unwrap();
+        router.clone().oneshot(request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_search_rate_limited() {
+        let router = router(ApiLimits::new(2, 100, 4));
+        assert_eq!(
+            get(&router, "/api/search?q=a").await.status(),
+            StatusCode::OK
+        );
+        assert_eq!(
+            get(&router, "/api/search?q=b").await.status(),
+            StatusCode::OK
+        );
+
+        let response = get(&router, "/api/search?q=c").await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "30");
+        // Exports share the budget; unlimited endpoints are unaffected.
+        let response = get(&router, "/api/usage/export.csv").await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(get(&router, "/api/health").await.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_search_concurrency_and_query_length() {
+        let limits = ApiLimits::new(0, 5, 1);
+        let slot = limits.try_search_slot();
+        let router = router(limits);
+        let response = get(&router, "/api/search?q=a").await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(response.headers().contains_key(header::RETRY_AFTER));
+        drop(slot);
+
+        assert_eq!(
+            get(&router, "/api/search?q=a").await.status(),
+            StatusCode::OK
+        );
+        let response = get(&router, "/api/search?q=abcdef").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_replay_start_and_stop() {
+        let router = router(ApiLimits::new(0, 100, 4));
+        let post_to = |uri: &str, body: serde_json::Value, confirm: bool| {
+            let mut request = Request::post(uri).header(header::CONTENT_TYPE, "application/json");
+            if confirm {
+                request = request.header("X-Confirm", "yes");
+            }
+            router.clone().oneshot(request.body(Body::from(body.to_string())).unwrap())
+        };
+        let post = |body: serde_json::Value| {
+            let request = Request::post("/api/replay")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header("X-Confirm", "yes")
+                .body(Body::from(body.to_string()))
+                .unwrap();
+            router.clone().oneshot(request)
+        };
+        let log = r#"{"type":"user","uuid":"u1","message":{"role":"user","content":"hi"}}"#;
+
+        let status = |body| async { post(body).await.unwrap().status() };
+        assert_eq!(
+            status(serde_json::json!({ "content": log, "speed": 0 })).await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(serde_json::json!({ "path": "/etc/passwd" })).await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(serde_json::json!({ "content": "not json" })).await,
+            StatusCode::BAD_REQUEST
+        );
+
+        // Unconfirmed, nothing plays; a dry run counts the entries.
+        let content = serde_json::json!({ "content": log });
+        let response = post_to("/api/replay", content.clone(), false).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PRECONDITION_REQUIRED);
+        let response = post_to("/api/replay?dry_run=true", content.clone(), false)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let plan: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            plan,
+            serde_json::json!({ "entryCount": 1, "speed": 1.0, "durationMs": 0 })
+        );
+        let empty = serde_json::json!({ "content": "" });
+        assert_eq!(
+            post_to("/api/replay?dry_run=true", empty, false)
+                .await
+                .unwrap()
+                .status(),
+            StatusCode::BAD_REQUEST
+        );
+
+        let response = post(content).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let created: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let uri = format!("/api/replay/{}", created["sessionId"].as_str().unwrap());
+
+        let delete = |uri: String, confirm: bool| {
+            let mut request = Request::delete(uri);
+            if confirm {
+                request = request.header("X-Confirm", "yes");
+            }
+            router.clone().oneshot(request.body(Body::empty()).unwrap())
+        };
+        let response = delete(format!("{}?dry_run=true", uri), false)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let plan: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(plan["sessionId"], created["sessionId"]);
+        assert_eq!(
+            delete(uri.clone(), false).await.unwrap().status(),
+            StatusCode::PRECONDITION_REQUIRED
+        );
+        assert_eq!(
+            delete(uri.clone(), true).await.unwrap().status(),
+            StatusCode::NO_CONTENT
+        );
+        assert_eq!(
+            delete(uri.clone(), true).await.unwrap().status(),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            delete(format!("{}?dry_run=true", uri), false)
+                .await
+                .unwrap()
+                .status(),
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connections_listed() {
+        let state = state(ApiLimits::new(0, 100, 4));
+        let router = create_router(state.clone(), None);
+        let connection = state
+            .connections
+            .register(Some("192.168.1.20:51000".parse().unwrap()));
+        connection.stats().subscribe("s1");
+        connection.stats().record_sent();
+
+        let response = get(&router, "/api/connections").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let connections: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(connections[0]["remoteAddr"], "192.168.1.20:51000");
+        assert_eq!(connections[0]["subscriptions"], serde_json::json!(["s1"]));
+        assert_eq!(connections[0]["eventsSent"], 1);
+
+        drop(connection);
+        let response = get(&router, "/api/connections").await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"[]");
+    }
+
+    #[tokio::test]
+    async fn test_config_redacts_token() {
+        let mut config = Config::default();
+        config.notifications.ntfy.topic = Some("agents".to_string());
+        config.notifications.ntfy.token = Some("tk_secret".to_string());
+        let state = state(ApiLimits::new(0, 100, 4));
+        state.config.store(Arc::new(config));
+        let router = create_router(state, None);
+
+        let response = get(&router, "/api/config").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let config: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(config["server"]["port"], 3001);
+        assert_eq!(config["notifications"]["ntfy"]["topic"], "agents");
+        assert_ne!(config["notifications"]["ntfy"]["token"], "tk_secret");
+    }
+
+    #[tokio::test]
+    async fn test_config_reload() {
+        let dir =
+            std::env::temp_dir().join(format!("agents-dashboard-reload-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        std::fs::write(&path, "").unwrap();
+        let state = state_with_source(ApiLimits::new(0, 100, 4), ConfigSource::file(path.clone()));
+        let router = create_router(state.clone(), None);
+        let send = |router: Router, uri: &'static str, confirm: bool| async move {
+            let mut request = Request::post(uri);
+            if confirm {
+                request = request.header("X-Confirm", "yes");
+            }
+            let response = router
+                .oneshot(request.body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            let status = response.status();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (status, serde_json::from_slice::<serde_json::Value>(&body).unwrap_or_default())
+        };
+        let reload = |router: Router| send(router, "/api/config/reload", true);
+
+        std::fs::write(
+            &path,
+            "[server]\nport = 4000\nsearch_max_query_len = 10\n[state_machine]\nidle_secs = 20\n",
+        )
+        .unwrap();
+        // Unconfirmed, nothing changes; a dry run lists what would.
+        let (status, _) = send(router.clone(), "/api/config/reload", false).await;
+        assert_eq!(status, StatusCode::PRECONDITION_REQUIRED);
+        let (status, plan) = send(router.clone(), "/api/config/reload?dry_run=true", false).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            plan["applied"],
+            serde_json::json!(["server.search_max_query_len", "state_machine.idle_secs"])
+        );
+        assert_eq!(plan["requiresRestart"], serde_json::json!(["server.port"]));
+        assert_ne!(state.config.load().state_machine.idle_secs, 20);
+
+        let (status, result) = reload(router.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            result["applied"],
+            serde_json::json!(["server.search_max_query_len", "state_machine.idle_secs"])
+        );
+        assert_eq!(result["requiresRestart"], serde_json::json!(["server.port"]));
+        assert_eq!(state.limits.max_query_len(), 10);
+        assert_eq!(
+            state.session_manager.session_settings().load().timeouts.idle_ms,
+            20_000
+        );
+        // The port in effect is still the one the server started with.
+        assert_eq!(state.config.load().server.port, 3001);
+        assert_eq!(state.config.load().state_machine.idle_secs, 20);
+
+        // A bad file is reported and changes nothing.
+        std::fs::write(&path, "[state_machine]\nidle_secs = 0\n").unwrap();
+        let (status, result) = reload(router).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(result["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("state_machine.idle_secs"));
+        assert_eq!(state.config.load().state_machine.idle_secs, 20);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn state_with_ingest_token(token: &str) -> Arc<AppState> {
+        let state = state(ApiLimits::new(0, 100, 4));
+        let mut config = Config::default();
+        config.server.ingest_token = Some(token.to_string());
+        state.config.store(Arc::new(config));
+        state
+    }
+
+    #[tokio::test]
+    async fn test_health_reports_instance_name() {
+        let state = state(ApiLimits::new(0, 100, 4));
+        let mut config = Config::default();
+        config.apply_env(|name| (name == "INSTANCE_NAME").then(|| "desk".to_string())).unwrap();
+        state.config.store(Arc::new(config));
+        let response = get(&create_router(state, None), "/api/health").await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let health: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(health["status"], "ok");
+        assert_eq!(health["instance"], "desk");
+        assert_eq!(health["watchers"]["failing"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_schema_served() {
+        let router = router(ApiLimits::new(0, 100, 4));
+        let json = |response: Response| async move {
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+        };
+
+        let all = json(get(&router, "/api/schema").await).await;
+        assert!(all["definitions"]["AgentSessionSummary"].is_object());
+
+        let server_event = json(get(&router, "/api/schema/ServerEvent").await).await;
+        assert_eq!(server_event["title"], "ServerEvent");
+        let text = server_event.to_string();
+        for tag in crate::types::SERVER_EVENT_TYPES {
+            assert!(text.contains(&format!("\"{}\"", tag)), "{} missing", tag);
+        }
+        // Types it refers to come along.
+        assert!(server_event["definitions"]["AgentSessionSummary"].is_object());
+
+        let response = get(&router, "/api/schema/NoSuchType").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_ingest_requires_token() {
+        let status = |router: Router, auth: Option<&'static str>| async move {
+            let mut request = Request::get("/ingest?host=laptop");
+            if let Some(auth) = auth {
+                request = request.header(header::AUTHORIZATION, auth);
+            }
+            let request = request.body(Body::empty()).unwrap();
+            router.oneshot(request).await.unwrap().status()
+        };
+        // Off unless a token is configured.
+        let router = router(ApiLimits::new(0, 100, 4));
+        assert_eq!(status(router, Some("Bearer x")).await, StatusCode::NOT_FOUND);
+
+        let router = create_router(state_with_ingest_token("secret"), None);
+        assert_eq!(status(router.clone(), None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            status(router.clone(), Some("Bearer wrong")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        // Authorized, but not a WebSocket request.
+        assert_eq!(
+            status(router, Some("Bearer secret")).await,
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[tokio::test]
+    async fn test_relayed_sessions_namespaced_by_host() {
+        let central = state_with_ingest_token("secret");
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = create_router(central.clone(), None);
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        // A session on the relaying instance, fed from a recorded log.
+        let relaying = Arc::new(SessionManager::with_prefs(PrefsStore::in_memory()));
+        let log = [
+            r#"{"type":"user","uuid":"u1","message":{"role":"user","content":"hi"}}"#,
+            r#"{"type":"assistant","uuid":"a1","message":{"model":"claude-sonnet-4-20250514","content":[{"type":"text","text":"Hello."}]}}"#,
+        ]
+        .join("\n");
+        let session_id = relaying.start_replay(&log, 1.0).await.unwrap();
+        wait_for(|| async { !relaying.get_sessions(true).await.is_empty() }).await;
+
+        let (broadcast_tx, _) = broadcast::channel(16);
+        let (message_tx, _) = broadcast::channel(16);
+        let config = RelayConfig {
+            url: format!("ws://{}/ingest", addr),
+            token: Some("secret".to_string()),
+            host: "laptop".to_string(),
+            anonymize: false,
+        };
+        crate::server::relay::spawn(config, relaying, &broadcast_tx, &message_tx);
+
+        let relayed_id = format!("laptop:{}", session_id);
+        let manager = &central.session_manager;
+        wait_for(|| async { manager.get_session_summary(&relayed_id).await.is_some() }).await;
+        let summary = manager.get_session_summary(&relayed_id).await.unwrap();
+        assert_eq!(summary.host, "laptop");
+        wait_for(|| async {
+            manager
+                .get_session_messages(&relayed_id)
+                .await
+                .is_some_and(|m| m.len() == 2)
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_archived_relayed_sessions_not_broadcast() {
+        let manager = SessionManager::with_prefs(PrefsStore::in_memory());
+        let session = crate::types::AgentSessionSummary {
+            session_id: "s1".to_string(),
+            ..Default::default()
+        };
+        let discovered = ServerEvent::SessionDiscovered {
+            session: session.clone(),
+        };
+        assert_eq!(manager.ingest("laptop", discovered).await.len(), 1);
+        let archive = crate::types::SessionPrefsPatch {
+            archived: Some(true),
+            ..Default::default()
+        };
+        manager.update_prefs("laptop:s1", &archive).await.unwrap();
+
+        let updated = ServerEvent::SessionUpdated { session };
+        assert!(manager.ingest("laptop", updated).await.is_empty());
+        let removed = ServerEvent::SessionRemoved {
+            session_id: "s1".to_string(),
+        };
+        assert_eq!(manager.ingest("laptop", removed).await.len(), 1);
+    }
+
+    async fn wait_for<F, Fut>(check: F)
+    where
+        F: Fn() -> Fut,
+        Fut: std::future::Future<Output = bool>,
+    {
+        for _ in 0..100 {
+            if check().await {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("timed out");
+    }
+}
@@ -0,0 +1,127 @@
+//! `/ingest`: the central side of relay mode. Other instances connect here and
+//! send their events; their sessions are shown alongside this instance's own.
+
+use crate::server::http::AppState;
+use crate::session::remote::{is_valid_host, OFFLINE_TIMEOUT};
+use crate::types::ServerEvent;
+use axum::extract::ws::rejection::WebSocketUpgradeRejection;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Json, Response};
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::debug;
+
+#[derive(Deserialize)]
+pub struct IngestQuery {
+    host: String,
+}
+
+pub async fn ingest_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<IngestQuery>,
+    ws: Result<WebSocketUpgrade, WebSocketUpgradeRejection>,
+) -> Response {
+    let Some(token) = state.config.load().server.ingest_token.clone() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if !bearer.is_some_and(|bearer| token_matches(bearer, &token)) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    if !is_valid_host(&query.host) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "error": "host may only contain letters, digits, `.`, `_` and `-`"
+            })),
+        )
+            .into_response();
+    }
+    match ws {
+        Ok(ws) => ws
+            .on_upgrade(move |socket| handle_ingest(socket, state, query.host))
+            .into_response(),
+        Err(rejection) => rejection.into_response(),
+    }
+}
+
+/// Compare without stopping at the first difference, so response times don't
+/// reveal how much of a guess was right.
+fn token_matches(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+async fn handle_ingest(mut socket: WebSocket, state: Arc<AppState>, host: String) {
+    let manager = state.session_manager.clone();
+    let connection = manager.remote_connected(&host);
+    while let Some(Ok(message)) = socket.recv().await {
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        match serde_json::from_str::<ServerEvent>(&text) {
+            Ok(event) => {
+                for event in manager.ingest(&host, event).await {
+                    route(&state, event).await;
+                }
+            }
+            Err(e) => debug!("[Ingest] Ignoring event from {}: {}", host, e),
+        }
+    }
+    manager.remote_disconnected(&host, connection);
+
+    tokio::spawn(async move {
+        tokio::time::sleep(OFFLINE_TIMEOUT).await;
+        for event in manager.remote_offline(&host, connection).await {
+            route(&state, event).await;
+        }
+    });
+}
+
+/// Send a relayed event on the same channel, with the same notifications, as a
+/// local one.
+async fn route(state: &AppState, event: ServerEvent) {
+    match event {
+        ServerEvent::NewMessage { .. } | ServerEvent::MessagesAppended { .. } => {
+            let _ = state.message_tx.send(event);
+        }
+        event => {
+            let muted = match &event {
+                ServerEvent::StateChanged { session_id, .. }
+                | ServerEvent::ContextWarning { session_id, .. } => {
+                    state.session_manager.is_muted(session_id).await
+                }
+                _ => false,
+            };
+            if !muted {
+                state.notifier.notify(&event, None);
+            }
+            let _ = state.broadcast_tx.send(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_matches() {
+        assert!(token_matches("secret", "secret"));
+        assert!(!token_matches("secreT", "secret"));
+        assert!(!token_matches("secret2", "secret"));
+        assert!(!token_matches("", "secret"));
+    }
+}
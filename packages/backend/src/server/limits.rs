@@ -0,0 +1,217 @@
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use arc_swap::ArcSwap;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Requests per minute per client on the expensive endpoints (search, exports).
+pub const DEFAULT_RATE_PER_MINUTE: u32 = 120;
+pub const DEFAULT_MAX_QUERY_LEN: usize = 1_000;
+pub const DEFAULT_SEARCH_CONCURRENCY: usize = 4;
+/// Forget clients whose buckets have refilled once this many are tracked.
+const PRUNE_CLIENTS_ABOVE: usize = 10_000;
+
+/// Limits on the API endpoints that scan every session. Adjustable while the
+/// server runs (on config reload).
+pub struct ApiLimits {
+    pub rate: RateLimiter,
+    max_query_len: AtomicUsize,
+    search_concurrency: AtomicUsize,
+    search_slots: ArcSwap<Semaphore>,
+}
+
+impl ApiLimits {
+    pub fn new(rate_per_minute: u32, max_query_len: usize, search_concurrency: usize) -> Self {
+        let search_concurrency = search_concurrency.max(1);
+        Self {
+            rate: RateLimiter::new(rate_per_minute),
+            max_query_len: AtomicUsize::new(max_query_len),
+            search_concurrency: AtomicUsize::new(search_concurrency),
+            search_slots: ArcSwap::from_pointee(Semaphore::new(search_concurrency)),
+        }
+    }
+
+    pub fn max_query_len(&self) -> usize {
+        self.max_query_len.load(Ordering::Relaxed)
+    }
+
+    /// Apply new limits. Searches already running keep their slots; the new
+    /// concurrency applies to those started from now on.
+    pub fn reconfigure(
+        &self,
+        rate_per_minute: u32,
+        max_query_len: usize,
+        search_concurrency: usize,
+    ) {
+        self.rate.set_per_minute(rate_per_minute);
+        self.max_query_len.store(max_query_len, Ordering::Relaxed);
+        let search_concurrency = search_concurrency.max(1);
+        if self
+            .search_concurrency
+            .swap(search_concurrency, Ordering::Relaxed)
+            != search_concurrency
+        {
+            self.search_slots
+                .store(Arc::new(Semaphore::new(search_concurrency)));
+        }
+    }
+
+    /// A slot to run one search, or None if the maximum number are already running.
+    pub fn try_search_slot(&self) -> Option<OwnedSemaphorePermit> {
+        self.search_slots.load_full().try_acquire_owned().ok()
+    }
+}
+
+/// Token bucket per client IP: bursts up to the per-minute limit, refilling evenly.
+pub struct RateLimiter {
+    per_minute: AtomicU32,
+    buckets: Mutex<HashMap<IpAddr, (f64, Instant)>>,
+}
+
+impl RateLimiter {
+    pub fn new(per_minute: u32) -> Self {
+        Self {
+            per_minute: AtomicU32::new(per_minute),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Change the limit. Existing buckets keep their tokens, capped at the new limit.
+    pub fn set_per_minute(&self, per_minute: u32) {
+        self.per_minute.store(per_minute, Ordering::Relaxed);
+    }
+
+    /// Take one request from `client`'s bucket, or return how long until one is available.
+    pub fn check(&self, client: IpAddr, now: Instant) -> Result<(), Duration> {
+        let per_minute = self.per_minute.load(Ordering::Relaxed);
+        if per_minute == 0 {
+            return Ok(());
+        }
+        let capacity = per_minute as f64;
+        let per_sec = capacity / 60.0;
+        let refill = |(tokens, at): (f64, Instant)| {
+            (tokens + now.duration_since(at).as_secs_f64() * per_sec).min(capacity)
+        };
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() > PRUNE_CLIENTS_ABOVE {
+            buckets.retain(|_, bucket| refill(*bucket) < capacity);
+        }
+        let bucket = buckets.entry(client).or_insert((capacity, now));
+        let tokens = refill(*bucket);
+        if tokens >= 1.0 {
+            *bucket = (tokens - 1.0, now);
+            Ok(())
+        } else {
+            *bucket = (tokens, now);
+            Err(Duration::from_secs_f64((1.0 - tokens) / per_sec))
+        }
+    }
+}
+
+/// 429 with `Retry-After` in whole seconds (at least 1).
+pub fn too_many_requests(retry_after: Duration) -> Response {
+    let secs = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, secs.to_string())],
+        Json(serde_json::json!({ "error": "Too many requests" })),
+    )
+        .into_response()
+}
+
+/// Middleware applying the per-client rate limit. Requests without connection info
+/// (only possible in tests) share one bucket.
+pub async fn rate_limit(
+    State(limits): State<Arc<ApiLimits>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let client = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED), |info| info.0.ip());
+    match limits.rate.check(client, Instant::now()) {
+        Ok(()) => next.run(request).await,
+        Err(retry_after) => too_many_requests(retry_after),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+    #[test]
+    fn test_burst_then_limited() {
+        let limiter = RateLimiter::new(3);
+        let now = Instant::now();
+        for _ in 0..3 {
+            assert!(limiter.check(CLIENT, now).is_ok());
+        }
+        let retry_after = limiter.check(CLIENT, now).unwrap_err();
+        assert_eq!(retry_after.as_secs_f64().round(), 20.0);
+
+        // Other clients have their own bucket.
+        assert!(limiter
+            .check(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), now)
+            .is_ok());
+        // One token back after 20s.
+        let later = now + Duration::from_secs(21);
+        assert!(limiter.check(CLIENT, later).is_ok());
+        assert!(limiter.check(CLIENT, later).is_err());
+    }
+
+    #[test]
+    fn test_zero_disables() {
+        let limiter = RateLimiter::new(0);
+        let now = Instant::now();
+        for _ in 0..1_000 {
+            assert!(limiter.check(CLIENT, now).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_search_slots() {
+        let limits = ApiLimits::new(10, 100, 1);
+        let slot = limits.try_search_slot();
+        assert!(slot.is_some());
+        assert!(limits.try_search_slot().is_none());
+        drop(slot);
+        assert!(limits.try_search_slot().is_some());
+    }
+
+    #[test]
+    fn test_reconfigure() {
+        let limits = ApiLimits::new(1, 100, 1);
+        let now = Instant::now();
+        let running = limits.try_search_slot();
+        assert!(limits.rate.check(CLIENT, now).is_ok());
+        assert!(limits.rate.check(CLIENT, now).is_err());
+
+        limits.reconfigure(0, 50, 2);
+        assert_eq!(limits.max_query_len(), 50);
+        assert!(limits.rate.check(CLIENT, now).is_ok());
+        // The search still running holds a slot of the old limit, not the new one.
+        let slots = [limits.try_search_slot(), limits.try_search_slot()];
+        assert!(slots.iter().all(Option::is_some));
+        assert!(limits.try_search_slot().is_none());
+        drop(running);
+    }
+
+    #[test]
+    fn test_retry_after_header() {
+        let response = too_many_requests(Duration::from_millis(1_500));
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "2");
+    }
+}
@@ -1,2 +1,20 @@
+pub mod coalesce;
+pub mod confirm;
+pub mod connections;
+#[cfg(feature = "embed-frontend")]
+pub mod embedded;
+pub mod export;
 pub mod http;
+pub mod ingest;
+pub mod limits;
+pub mod open;
+pub mod outbound;
+pub mod protocol;
+pub mod public_status;
+pub mod raw_log;
+pub mod relay;
+pub mod report_markdown;
+pub mod reload;
+pub mod routing;
+pub mod usage_csv;
 pub mod ws;
@@ -0,0 +1,200 @@
+//! `POST /api/sessions/{id}/open`: run a configured command, such as an editor or a
+//! terminal, on a session's working directory. The command runs on the server, so
+//! this is off unless `ALLOW_OPEN_COMMANDS` is set, and only requests from the same
+//! machine are served.
+
+use crate::types::OpenSessionResponse;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+
+/// Replaced by the working directory wherever it appears in the template.
+pub const PATH_PLACEHOLDER: &str = "{path}";
+/// Commands still running after this are reported as started.
+const WAIT: Duration = Duration::from_secs(5);
+
+/// Split a command template into words like a shell would: quotes group words and
+/// backslashes escape outside single quotes. Nothing else is interpreted, since the
+/// command is run directly rather than through a shell.
+pub fn split_template(template: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if let Some(word) = word.take() {
+                    words.push(word);
+                }
+            }
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("unclosed single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err("unclosed double quote".to_string()),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err("unclosed double quote".to_string()),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+                None => return Err("trailing backslash".to_string()),
+            },
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    if words.is_empty() {
+        return Err("empty command".to_string());
+    }
+    Ok(words)
+}
+
+/// The command line for `path`. Each word keeps the path inside it, so a path with
+/// spaces, quotes or `;` stays one argument.
+pub fn command_for(words: &[String], path: &str) -> Vec<String> {
+    words
+        .iter()
+        .map(|word| word.replace(PATH_PLACEHOLDER, path))
+        .collect()
+}
+
+/// Whether a request comes from this machine: a loopback peer and, for requests a
+/// browser sends, a page served from localhost. The second check keeps any website
+/// open in the user's browser from posting to the dashboard on their behalf.
+pub fn is_local(peer: Option<SocketAddr>, origin: Option<&str>) -> bool {
+    if !peer.is_some_and(|peer| peer.ip().is_loopback()) {
+        return false;
+    }
+    let Some(origin) = origin else {
+        return true;
+    };
+    let Ok(origin) = reqwest::Url::parse(origin) else {
+        return false;
+    };
+    match origin.host_str() {
+        Some("localhost") => true,
+        Some(host) => host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback()),
+        None => false,
+    }
+}
+
+/// Run `command` in `dir`, waiting a little for it to finish. Editors and terminals
+/// that keep running are reported as started.
+pub async fn run(command: Vec<String>, dir: &Path) -> OpenSessionResponse {
+    let spawned = tokio::process::Command::new(&command[0])
+        .args(&command[1..])
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    let mut child = match spawned {
+        Ok(child) => child,
+        Err(e) => {
+            return OpenSessionResponse {
+                success: false,
+                message: format!("Cannot run `{}`: {}", command[0], e),
+                command,
+            }
+        }
+    };
+    let (success, message) = match tokio::time::timeout(WAIT, child.wait()).await {
+        Ok(Ok(status)) if status.success() => (true, "Done".to_string()),
+        Ok(Ok(status)) => (false, format!("`{}` failed ({})", command[0], status)),
+        Ok(Err(e)) => (false, format!("`{}` failed: {}", command[0], e)),
+        Err(_) => (true, "Started".to_string()),
+    };
+    OpenSessionResponse {
+        success,
+        command,
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_and_substitute() {
+        let words = |t: &str| split_template(t).unwrap();
+        assert_eq!(words("code {path}"), ["code", "{path}"]);
+        assert_eq!(
+            words(r#"wezterm cli spawn --cwd "{path}""#),
+            ["wezterm", "cli", "spawn", "--cwd", "{path}"]
+        );
+        assert_eq!(
+            words(r#"open -a 'Visual Studio Code' {path}"#),
+            ["open", "-a", "Visual Studio Code", "{path}"]
+        );
+        assert_eq!(words(r#"a\ b "c \"d\" \n" ''"#), ["a b", r#"c "d" \n"#, ""]);
+        for bad in ["", "   ", "code 'x", "code \"x", "code \\"] {
+            assert!(split_template(bad).is_err(), "{:?}", bad);
+        }
+
+        // Whatever the path holds, it is one argument and nothing is interpreted.
+        let path = r#"/tmp/it's "here"; rm -rf ~ $(id)"#;
+        assert_eq!(
+            command_for(&words("code --goto {path}/README.md"), path),
+            ["code", "--goto", &format!("{}/README.md", path)]
+        );
+    }
+
+    #[test]
+    fn test_only_local_requests() {
+        let loopback: SocketAddr = "127.0.0.1:50000".parse().unwrap();
+        let loopback6: SocketAddr = "[::1]:50000".parse().unwrap();
+        let remote: SocketAddr = "192.168.1.20:50000".parse().unwrap();
+
+        assert!(is_local(Some(loopback), None));
+        assert!(is_local(Some(loopback6), None));
+        assert!(is_local(Some(loopback), Some("http://localhost:5173")));
+        assert!(is_local(Some(loopback), Some("http://127.0.0.1:3001")));
+        assert!(is_local(Some(loopback), Some("http://[::1]:3001")));
+
+        assert!(!is_local(None, None));
+        assert!(!is_local(Some(remote), None));
+        assert!(!is_local(Some(remote), Some("http://localhost:5173")));
+        // A page elsewhere posting to the local dashboard, or a rebound DNS name.
+        assert!(!is_local(Some(loopback), Some("https://example.com")));
+        assert!(!is_local(Some(loopback), Some("http://attacker.test:3001")));
+        assert!(!is_local(Some(loopback), Some("null")));
+    }
+
+    #[tokio::test]
+    async fn test_run_reports_outcome() {
+        let dir = std::env::temp_dir();
+        let ok = run(vec!["true".to_string()], &dir).await;
+        assert!(ok.success, "{}", ok.message);
+        let failed = run(vec!["false".to_string()], &dir).await;
+        assert!(!failed.success);
+        let missing = run(vec!["no-such-command-here".to_string()], &dir).await;
+        assert!(!missing.success);
+        assert!(missing.message.contains("no-such-command-here"));
+    }
+}
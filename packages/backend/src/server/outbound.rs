@@ -0,0 +1,376 @@
+use crate::types::ServerEvent;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use tokio::sync::Notify;
+
+/// Events queued for one client before it counts as falling behind.
+pub const SOFT_CAP: usize = 256;
+
+/// Events queued for one client, even after dropping and coalescing, before the
+/// connection is closed.
+pub const HARD_CAP: usize = 1024;
+
+/// WebSocket close code sent to a client that stopped reading.
+pub const CLOSE_TOO_SLOW: u16 = 4008;
+
+/// What happened to a pushed event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pushed {
+    Queued,
+    /// The client is behind; a newer event of the same kind replaced a queued one,
+    /// or a message was folded into a `session:messages_appended` marker.
+    Dropped,
+    /// The queue is over its hard cap or the connection is gone.
+    Closed,
+}
+
+/// Outgoing events for one WebSocket client, bounded so that a client which stops
+/// reading cannot make the server buffer every event for it.
+///
+/// Below the soft cap everything is queued in order. Above it, new messages are
+/// replaced by a `session:messages_appended` marker (which makes the client
+/// re-subscribe and fetch them in one go), and summary events replace any queued
+/// event of the same kind for the same session, so the client still ends up with
+/// the latest state. Past the hard cap the queue gives up and closes.
+pub struct OutboundQueue {
+    inner: Mutex<Inner>,
+    notify: Notify,
+    soft_cap: usize,
+    hard_cap: usize,
+}
+
+struct Inner {
+    events: VecDeque<ServerEvent>,
+    closed: bool,
+    overflowed: bool,
+}
+
+/// Events that can stand in for an older queued one: the newer carries everything
+/// the client needs from the older.
+#[derive(PartialEq, Eq)]
+enum Slot<'a> {
+    Init,
+    Session(&'static str, &'a str),
+}
+
+fn slot(event: &ServerEvent) -> Option<Slot<'_>> {
+    match event {
+        ServerEvent::SessionsInit { .. } => Some(Slot::Init),
+        ServerEvent::SessionUpdated { session } => {
+            Some(Slot::Session("updated", &session.session_id))
+        }
+        ServerEvent::StateChanged { session_id, .. } => Some(Slot::Session("state", session_id)),
+        ServerEvent::UsageUpdated { session_id, .. } => Some(Slot::Session("usage", session_id)),
+        ServerEvent::GitStatusUpdated { session_id, .. } => Some(Slot::Session("git", session_id)),
+        ServerEvent::MessagesAppended { session_id, .. } => {
+            Some(Slot::Session("appended", session_id))
+        }
+        ServerEvent::Hello { .. }
+        | ServerEvent::SessionDiscovered { .. }
+        | ServerEvent::SessionRemoved { .. }
+        | ServerEvent::NewMessage { .. }
+        | ServerEvent::MessagesInit { .. }
+        | ServerEvent::ContextWarning { .. }
+        | ServerEvent::ModelChanged { .. }
+        | ServerEvent::LogDeleted { .. }
+        | ServerEvent::BudgetExceeded { .. }
+        | ServerEvent::StartupComplete { .. }
+        | ServerEvent::Error { .. } => None,
+    }
+}
+
+impl OutboundQueue {
+    pub fn new() -> Self {
+        Self::with_caps(SOFT_CAP, HARD_CAP)
+    }
+
+    pub fn with_caps(soft_cap: usize, hard_cap: usize) -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                events: VecDeque::new(),
+                closed: false,
+                overflowed: false,
+            }),
+            notify: Notify::new(),
+            soft_cap,
+            hard_cap,
+        }
+    }
+
+    pub fn push(&self, event: ServerEvent) -> Pushed {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.closed {
+            return Pushed::Closed;
+        }
+        let pushed = if inner.events.len() < self.soft_cap {
+            inner.events.push_back(event);
+            Pushed::Queued
+        } else {
+            inner.push_behind(event)
+        };
+        if inner.events.len() > self.hard_cap {
+            inner.events.clear();
+            inner.closed = true;
+            inner.overflowed = true;
+            drop(inner);
+            self.notify.notify_one();
+            return Pushed::Closed;
+        }
+        drop(inner);
+        self.notify.notify_one();
+        pushed
+    }
+
+    /// The next event to send, waiting for one if needed. `None` once the queue has
+    /// overflowed. Cancel safe.
+    pub async fn pop(&self) -> Option<ServerEvent> {
+        loop {
+            {
+                let mut inner = self.inner.lock().unwrap();
+                if inner.overflowed {
+                    return None;
+                }
+                if let Some(event) = inner.events.pop_front() {
+                    return Some(event);
+                }
+            }
+            self.notify.notified().await;
+        }
+    }
+
+    /// Stop accepting events, e.g. because the connection ended.
+    pub fn close(&self) {
+        self.inner.lock().unwrap().closed = true;
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.inner.lock().unwrap().events.len()
+    }
+}
+
+impl Default for OutboundQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Inner {
+    /// Queue an event for a client that is behind.
+    fn push_behind(&mut self, event: ServerEvent) -> Pushed {
+        let event = match event {
+            ServerEvent::NewMessage {
+                session_id,
+                message,
+            } => {
+                let marker = ServerEvent::MessagesAppended {
+                    session_id,
+                    count: 1,
+                    latest_timestamp: message.timestamp,
+                };
+                self.coalesce(marker);
+                return Pushed::Dropped;
+            }
+            event => event,
+        };
+        if slot(&event).is_some() {
+            self.coalesce(event)
+        } else {
+            self.events.push_back(event);
+            Pushed::Queued
+        }
+    }
+
+    /// Queue `event` at the back, in place of a queued event in the same slot.
+    fn coalesce(&mut self, mut event: ServerEvent) -> Pushed {
+        let position = {
+            let key = slot(&event);
+            self.events.iter().position(|queued| slot(queued) == key)
+        };
+        let Some(position) = position else {
+            self.events.push_back(event);
+            return Pushed::Queued;
+        };
+        let replaced = self.events.remove(position).unwrap();
+        match (&mut event, replaced) {
+            // Counts add up, and the client should see the transition from the
+            // state it last saw.
+            (
+                ServerEvent::MessagesAppended { count, .. },
+                ServerEvent::MessagesAppended { count: older, .. },
+            ) => *count += older,
+            (
+                ServerEvent::StateChanged { previous, .. },
+                ServerEvent::StateChanged {
+                    previous: older, ..
+                },
+            ) => *previous = older,
+            _ => {}
+        }
+        self.events.push_back(event);
+        Pushed::Dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{
+        AgentMessage, AgentSessionSummary, AgentStateType, MessageRole, MessageType,
+    };
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn message(session_id: &str, n: usize) -> ServerEvent {
+        ServerEvent::NewMessage {
+            session_id: session_id.to_string(),
+            message: AgentMessage {
+                id: format!("m{}", n),
+                session_id: session_id.to_string(),
+                timestamp: format!("2025-01-01T00:00:{:02}Z", n % 60),
+                role: MessageRole::Assistant,
+                msg_type: MessageType::Text,
+                content: "x".repeat(1000).into(),
+                metadata: None,
+                parent_id: None,
+                tool_use_message_id: None,
+            },
+        }
+    }
+
+    fn removed(session_id: &str) -> ServerEvent {
+        ServerEvent::SessionRemoved {
+            session_id: session_id.to_string(),
+        }
+    }
+
+    fn appended(session_id: &str) -> ServerEvent {
+        ServerEvent::MessagesAppended {
+            session_id: session_id.to_string(),
+            count: 1,
+            latest_timestamp: String::new(),
+        }
+    }
+
+    fn drain(queue: &OutboundQueue) -> Vec<ServerEvent> {
+        queue.inner.lock().unwrap().events.drain(..).collect()
+    }
+
+    #[test]
+    fn test_behind_client_gets_markers_instead_of_messages() {
+        let queue = OutboundQueue::with_caps(2, 10);
+        assert_eq!(queue.push(message("s1", 0)), Pushed::Queued);
+        assert_eq!(queue.push(message("s1", 1)), Pushed::Queued);
+        for n in 2..100 {
+            assert_eq!(queue.push(message("s1", n)), Pushed::Dropped);
+            assert_eq!(queue.push(message("s2", n)), Pushed::Dropped);
+        }
+        // Other events are still queued in order.
+        assert_eq!(queue.push(removed("s3")), Pushed::Queued);
+        assert_eq!(queue.len(), 5);
+
+        let events = drain(&queue);
+        assert!(
+            matches!(&events[1], ServerEvent::NewMessage { message, .. } if message.id == "m1")
+        );
+        match &events[2] {
+            ServerEvent::MessagesAppended {
+                session_id,
+                count,
+                latest_timestamp,
+            } => {
+                assert_eq!(session_id, "s1");
+                assert_eq!(*count, 98);
+                assert_eq!(latest_timestamp, "2025-01-01T00:00:39Z");
+            }
+            other => panic!("Expected messages_appended, got {:?}", other),
+        }
+        assert!(
+            matches!(&events[3], ServerEvent::MessagesAppended { session_id, .. } if session_id == "s2")
+        );
+        assert!(matches!(&events[4], ServerEvent::SessionRemoved { .. }));
+    }
+
+    #[test]
+    fn test_state_changes_coalesce_per_session() {
+        let queue = OutboundQueue::with_caps(1, 10);
+        queue.push(removed("s0"));
+        let state_changed = |previous, current| ServerEvent::StateChanged {
+            session_id: "s1".to_string(),
+            previous,
+            current,
+            session: AgentSessionSummary {
+                session_id: "s1".to_string(),
+                ..Default::default()
+            },
+            unread_count: None,
+        };
+        assert_eq!(
+            queue.push(state_changed(AgentStateType::Idle, AgentStateType::Running)),
+            Pushed::Queued
+        );
+        assert_eq!(
+            queue.push(state_changed(
+                AgentStateType::Running,
+                AgentStateType::PermissionWaiting
+            )),
+            Pushed::Dropped
+        );
+        let events = drain(&queue);
+        assert_eq!(events.len(), 2);
+        match &events[1] {
+            ServerEvent::StateChanged {
+                previous, current, ..
+            } => {
+                assert_eq!(*previous, AgentStateType::Idle);
+                assert_eq!(*current, AgentStateType::PermissionWaiting);
+            }
+            other => panic!("Expected state_changed, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_overflow_closes_queue() {
+        let queue = OutboundQueue::with_caps(2, 4);
+        for n in 0..4 {
+            assert_eq!(queue.push(removed(&format!("s{}", n))), Pushed::Queued);
+        }
+        assert_eq!(queue.push(removed("s4")), Pushed::Closed);
+        assert_eq!(queue.pop().await.map(|_| ()), None);
+        assert_eq!(queue.push(appended("s1")), Pushed::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_slow_reader_stays_bounded() {
+        let queue = Arc::new(OutboundQueue::with_caps(64, 256));
+        let reader = {
+            let queue = queue.clone();
+            tokio::spawn(async move {
+                while let Some(event) = queue.pop().await {
+                    if matches!(event, ServerEvent::SessionRemoved { .. }) {
+                        break;
+                    }
+                    tokio::time::sleep(Duration::from_millis(1)).await;
+                }
+            })
+        };
+
+        let mut max_len = 0;
+        let mut dropped = 0;
+        for n in 0..20_000 {
+            if queue.push(message(&format!("s{}", n % 8), n)) == Pushed::Dropped {
+                dropped += 1;
+            }
+            max_len = max_len.max(queue.len());
+            if n % 1000 == 0 {
+                tokio::task::yield_now().await;
+            }
+        }
+        queue.push(removed("s0"));
+
+        reader.await.unwrap();
+        // The soft cap, plus one marker per session.
+        assert!(max_len <= 64 + 8, "queue grew to {}", max_len);
+        assert!(dropped > 19_000, "only {} dropped", dropped);
+    }
+}
@@ -0,0 +1,166 @@
+//! The WebSocket handshake. The server opens with `server:hello`; a client may
+//! answer with `client:hello` to say which protocol version and features it knows.
+
+use crate::types::{AgentMessage, MessageType, ServerEvent, SERVER_EVENT_TYPES};
+use std::collections::HashSet;
+
+/// Bumped when a change to the events would break clients written against the
+/// previous version. Version 1 is the protocol from before the handshake.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// `thinking` messages are sent in `session:new_message` and `session:messages_init`.
+pub const CAP_THINKING: &str = "thinking";
+
+/// `ack:read` is accepted, and `sessions:init` and `session:state_changed` carry
+/// unread counts for sessions the client has acked.
+pub const CAP_UNREAD: &str = "unread";
+
+/// `subscribe:project` and `unsubscribe:project` are accepted.
+pub const CAP_PROJECT_SUBSCRIPTIONS: &str = "project_subscriptions";
+
+/// Optional features this server supports, listed after the event types.
+const FEATURES: &[&str] = &[CAP_THINKING, CAP_UNREAD, CAP_PROJECT_SUBSCRIPTIONS];
+
+pub fn hello() -> ServerEvent {
+    ServerEvent::Hello {
+        protocol_version: PROTOCOL_VERSION,
+        server_version: env!("CARGO_PKG_VERSION").to_string(),
+        capabilities: SERVER_EVENT_TYPES
+            .iter()
+            .chain(FEATURES)
+            .map(|cap| cap.to_string())
+            .collect(),
+    }
+}
+
+/// What one client can handle. Clients on the current protocol (and those that
+/// never say hello) get everything; clients on an older one get only what they
+/// listed.
+#[derive(Debug, Default)]
+pub struct ClientCompat {
+    capabilities: Option<HashSet<String>>,
+}
+
+impl ClientCompat {
+    pub fn from_hello(protocol_version: u32, capabilities: Vec<String>) -> Self {
+        Self {
+            capabilities: (protocol_version < PROTOCOL_VERSION)
+                .then(|| capabilities.into_iter().collect()),
+        }
+    }
+
+    /// The event as this client should get it, or None to leave it out.
+    pub fn adapt(&self, event: ServerEvent) -> Option<ServerEvent> {
+        let Some(capabilities) = &self.capabilities else {
+            return Some(event);
+        };
+        if !capabilities.contains(event.event_type()) {
+            return None;
+        }
+        if capabilities.contains(CAP_THINKING) {
+            return Some(event);
+        }
+        let is_thinking = |message: &AgentMessage| message.msg_type == MessageType::Thinking;
+        match event {
+            ServerEvent::NewMessage { ref message, .. } if is_thinking(message) => None,
+            ServerEvent::MessagesInit {
+                session_id,
+                mut messages,
+            } => {
+                messages.retain(|message| !is_thinking(message));
+                Some(ServerEvent::MessagesInit {
+                    session_id,
+                    messages,
+                })
+            }
+            event => Some(event),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::MessageRole;
+    use std::sync::Arc;
+
+    fn message(id: &str, msg_type: MessageType) -> AgentMessage {
+        AgentMessage {
+            id: id.to_string(),
+            session_id: "s1".to_string(),
+            timestamp: "2025-01-01T00:00:00Z".to_string(),
+            role: MessageRole::Assistant,
+            msg_type,
+            content: "".into(),
+            metadata: None,
+            parent_id: None,
+            tool_use_message_id: None,
+        }
+    }
+
+    fn removed() -> ServerEvent {
+        ServerEvent::SessionRemoved {
+            session_id: "s1".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_hello_lists_event_types_and_features() {
+        let ServerEvent::Hello {
+            protocol_version,
+            capabilities,
+            ..
+        } = hello()
+        else {
+            panic!("Expected server:hello");
+        };
+        assert_eq!(protocol_version, PROTOCOL_VERSION);
+        assert!(capabilities.iter().any(|c| c == "session:messages_appended"));
+        assert!(capabilities.iter().any(|c| c == CAP_THINKING));
+        assert!(capabilities.iter().any(|c| c == CAP_UNREAD));
+        assert!(capabilities.iter().any(|c| c == CAP_PROJECT_SUBSCRIPTIONS));
+    }
+
+    #[test]
+    fn test_current_client_gets_everything() {
+        for compat in [
+            ClientCompat::default(),
+            ClientCompat::from_hello(PROTOCOL_VERSION, vec![]),
+        ] {
+            assert!(compat.adapt(removed()).is_some());
+        }
+    }
+
+    #[test]
+    fn test_older_client_gets_what_it_lists() {
+        let compat = ClientCompat::from_hello(
+            1,
+            vec![
+                "session:new_message".to_string(),
+                "session:messages_init".to_string(),
+            ],
+        );
+        assert!(compat.adapt(removed()).is_none());
+
+        let thinking = ServerEvent::NewMessage {
+            session_id: "s1".to_string(),
+            message: message("m1", MessageType::Thinking),
+        };
+        assert!(compat.adapt(thinking).is_none());
+
+        let init = ServerEvent::MessagesInit {
+            session_id: "s1".to_string(),
+            messages: vec![
+                Arc::new(message("m1", MessageType::Thinking)),
+                Arc::new(message("m2", MessageType::Text)),
+            ],
+        };
+        match compat.adapt(init) {
+            Some(ServerEvent::MessagesInit { messages, .. }) => {
+                assert_eq!(messages.len(), 1);
+                assert_eq!(messages[0].id, "m2");
+            }
+            other => panic!("Expected messages_init, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,196 @@
+//! The read-only status page API (`PUBLIC_STATUS`): which sessions are running,
+//! on which model and how many tokens they used, for embedding on a wiki or
+//! team page. Nothing that could show what a session is about leaves here:
+//! prompts, paths, messages, costs and search stay on the full API.
+
+use crate::server::http::AppState;
+use crate::session::manager::SessionManager;
+use crate::types::{AgentSessionSummary, PublicSessionSummary, PublicStatusEvent, ServerEvent};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::sse::{Event, KeepAlive, Sse},
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+
+/// The parts of `session` the status page may show.
+pub fn public_summary(session: &AgentSessionSummary) -> PublicSessionSummary {
+    let parse = |ts: &str| ts.parse::<DateTime<Utc>>().ok();
+    let duration_ms = match (parse(&session.started_at), parse(&session.last_activity_at)) {
+        (Some(start), Some(end)) => (end - start).num_milliseconds().max(0) as u64,
+        _ => 0,
+    };
+    PublicSessionSummary {
+        session_id: session.session_id.clone(),
+        project_name: session.project_name.clone(),
+        state: session.state,
+        model: session.model.clone(),
+        started_at: session.started_at.clone(),
+        last_activity_at: session.last_activity_at.clone(),
+        duration_ms,
+        input_tokens: session.cumulative_usage.input_tokens,
+        output_tokens: session.cumulative_usage.output_tokens,
+    }
+}
+
+/// Replays and sessions loaded from old logs aren't agent activity.
+fn listed(session: &AgentSessionSummary) -> bool {
+    !session.replay && !session.historical
+}
+
+async fn public_sessions(session_manager: &SessionManager) -> Vec<PublicSessionSummary> {
+    session_manager
+        .get_sessions(false)
+        .await
+        .iter()
+        .filter(|s| listed(s))
+        .map(public_summary)
+        .collect()
+}
+
+/// What the status page hears of `event`, if anything.
+fn public_event(event: &ServerEvent) -> Option<PublicStatusEvent> {
+    match event {
+        ServerEvent::SessionDiscovered { session }
+        | ServerEvent::SessionUpdated { session }
+        | ServerEvent::StateChanged { session, .. }
+            if listed(session) =>
+        {
+            Some(PublicStatusEvent::SessionUpdated {
+                session: public_summary(session),
+            })
+        }
+        ServerEvent::SessionRemoved { session_id } => Some(PublicStatusEvent::SessionRemoved {
+            session_id: session_id.clone(),
+        }),
+        _ => None,
+    }
+}
+
+fn turned_off() -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(serde_json::json!({
+            "error": "The public status page is turned off; set PUBLIC_STATUS=1 to serve it"
+        })),
+    )
+        .into_response()
+}
+
+pub async fn public_sessions_handler(State(state): State<Arc<AppState>>) -> Response {
+    if !state.config.load().server.public_status {
+        return turned_off();
+    }
+    Json(public_sessions(&state.session_manager).await).into_response()
+}
+
+/// Server-sent events: `sessions:init`, then sessions appearing, changing state
+/// and going away. A stream that falls behind gets `sessions:init` again.
+pub async fn public_events_handler(State(state): State<Arc<AppState>>) -> Response {
+    if !state.config.load().server.public_status {
+        return turned_off();
+    }
+    // Subscribe before taking the snapshot so no change falls between them.
+    let rx = state.broadcast_tx.subscribe();
+    let init = PublicStatusEvent::SessionsInit {
+        sessions: public_sessions(&state.session_manager).await,
+    };
+    let manager = state.session_manager.clone();
+    let updates = futures::stream::unfold((rx, manager), |(mut rx, manager)| async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) => {
+                    if let Some(event) = public_event(&event) {
+                        return Some((event, (rx, manager)));
+                    }
+                }
+                Err(RecvError::Lagged(_)) => {
+                    let init = PublicStatusEvent::SessionsInit {
+                        sessions: public_sessions(&manager).await,
+                    };
+                    return Some((init, (rx, manager)));
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    let stream = futures::stream::once(async move { init })
+        .chain(updates)
+        // Turning the page off on reload ends open streams too.
+        .take_while(move |_| std::future::ready(state.config.load().server.public_status))
+        .map(|event| {
+            let data = serde_json::to_string(&event).unwrap_or_default();
+            Ok::<_, Infallible>(Event::default().event(event.event_type()).data(data))
+        });
+    Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{AgentStateType, CumulativeUsage};
+
+    #[test]
+    fn test_public_summary_leaves_out_content() {
+        let session = AgentSessionSummary {
+            session_id: "s1".into(),
+            state: AgentStateType::Running,
+            project_path: "/home/me/secret-project".into(),
+            project_name: "secret-project".into(),
+            working_directory: "/home/me/secret-project".into(),
+            initial_task: "rotate the production database password".into(),
+            latest_task: "and update the vault".into(),
+            model: "claude-sonnet-4-20250514".into(),
+            started_at: "2025-01-01T10:00:00.000Z".into(),
+            last_activity_at: "2025-01-01T10:30:00.500Z".into(),
+            cumulative_usage: CumulativeUsage {
+                input_tokens: 1200,
+                output_tokens: 340,
+                estimated_cost: 1.25,
+                ..Default::default()
+            },
+            note: Some("ask ops".into()),
+            title: Some("Rotate DB password".into()),
+            ..Default::default()
+        };
+        let public = public_summary(&session);
+        assert_eq!(public.duration_ms, 30 * 60 * 1000 + 500);
+        assert_eq!(public.input_tokens, 1200);
+        assert_eq!(public.output_tokens, 340);
+
+        let json = serde_json::to_string(&public).unwrap();
+        for hidden in ["/home/me", "password", "vault", "ask ops", "Rotate", "1.25"] {
+            assert!(!json.contains(hidden), "{} in {}", hidden, json);
+        }
+    }
+
+    #[test]
+    fn test_replays_are_not_listed() {
+        let session = AgentSessionSummary {
+            session_id: "replay-1".into(),
+            replay: true,
+            ..Default::default()
+        };
+        let discovered = ServerEvent::SessionDiscovered {
+            session: session.clone(),
+        };
+        assert!(public_event(&discovered).is_none());
+        let discovered = ServerEvent::SessionDiscovered {
+            session: AgentSessionSummary {
+                replay: false,
+                ..session
+            },
+        };
+        assert!(matches!(
+            public_event(&discovered),
+            Some(PublicStatusEvent::SessionUpdated { .. })
+        ));
+    }
+}
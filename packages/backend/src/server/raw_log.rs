@@ -0,0 +1,133 @@
+//! `GET /api/sessions/{id}/raw`: a byte range of a session's log as written, for
+//! looking at what the parser was given.
+
+use crate::providers::claude_code::redact::Redactor;
+use axum::{
+    body::Body,
+    http::{header, HeaderValue},
+    response::{IntoResponse, Response},
+};
+use std::borrow::Cow;
+use std::io::SeekFrom;
+use std::path::Path;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, BufReader};
+
+/// Size of the whole file, so clients can page through it.
+pub const FILE_SIZE_HEADER: &str = "x-file-size";
+/// Offset the returned bytes start at, which is before the requested one when it
+/// fell inside a line.
+pub const OFFSET_HEADER: &str = "x-offset";
+/// Offset just past the returned bytes: where the next page starts.
+pub const NEXT_OFFSET_HEADER: &str = "x-next-offset";
+/// Default for the largest range returned by one request.
+pub const DEFAULT_MAX_CHUNK_BYTES: u64 = 1024 * 1024;
+
+pub struct RawChunk {
+    pub bytes: Vec<u8>,
+    pub offset: u64,
+    pub file_size: u64,
+}
+
+/// Up to `limit` bytes from `offset`, widened to whole lines: back to the start of
+/// the line `offset` falls in, and on to the end of the line the limit cuts through.
+/// Redaction only sees whole entries that way, so a secret split across two pages
+/// is still masked. An offset past the end reads nothing.
+pub async fn read_chunk(path: &Path, offset: u64, limit: u64) -> std::io::Result<RawChunk> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let file_size = file.metadata().await?.len();
+    let offset = offset.min(file_size);
+    if limit == 0 || offset == file_size {
+        return Ok(RawChunk {
+            bytes: Vec::new(),
+            offset,
+            file_size,
+        });
+    }
+    let end = offset.saturating_add(limit).min(file_size);
+    let offset = line_start(&mut file, offset).await?;
+
+    file.seek(SeekFrom::Start(offset)).await?;
+    let mut reader = BufReader::new(file);
+    let mut bytes = Vec::with_capacity((end - offset) as usize);
+    (&mut reader).take(end - offset).read_to_end(&mut bytes).await?;
+    if bytes.last() != Some(&b'\n') {
+        reader.read_until(b'\n', &mut bytes).await?;
+    }
+    Ok(RawChunk {
+        bytes,
+        offset,
+        file_size,
+    })
+}
+
+/// Offset of the first byte of the line holding `offset`.
+async fn line_start(file: &mut tokio::fs::File, offset: u64) -> std::io::Result<u64> {
+    const BLOCK: u64 = 8 * 1024;
+    let mut end = offset;
+    let mut block = Vec::new();
+    while end > 0 {
+        let start = end.saturating_sub(BLOCK);
+        file.seek(SeekFrom::Start(start)).await?;
+        block.clear();
+        (&mut *file).take(end - start).read_to_end(&mut block).await?;
+        if let Some(newline) = block.iter().rposition(|&b| b == b'\n') {
+            return Ok(start + newline as u64 + 1);
+        }
+        end = start;
+    }
+    Ok(0)
+}
+
+/// The chunk as the response body, with secrets masked as in messages (so the body
+/// can be shorter than the range it covers).
+pub fn raw_response(chunk: RawChunk, redactor: &Redactor) -> Response {
+    let next_offset = chunk.offset + chunk.bytes.len() as u64;
+    let redacted = match redactor.redact(&String::from_utf8_lossy(&chunk.bytes)) {
+        Cow::Owned(redacted) => Some(redacted),
+        Cow::Borrowed(_) => None,
+    };
+    let body = redacted.map(String::into_bytes).unwrap_or(chunk.bytes);
+
+    let mut response = Body::from(body).into_response();
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/plain; charset=utf-8"),
+    );
+    headers.insert(FILE_SIZE_HEADER, HeaderValue::from(chunk.file_size));
+    headers.insert(OFFSET_HEADER, HeaderValue::from(chunk.offset));
+    headers.insert(NEXT_OFFSET_HEADER, HeaderValue::from(next_offset));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_read_chunk_pages() {
+        let path = std::env::temp_dir().join(format!(
+            "agents-dashboard-raw-{}.jsonl",
+            uuid::Uuid::new_v4()
+        ));
+        std::fs::write(&path, "0123\n5678\nabc").unwrap();
+
+        let chunk = read_chunk(&path, 0, 5).await.unwrap();
+        assert_eq!(chunk.bytes, b"0123\n");
+        assert_eq!(chunk.file_size, 13);
+        // Both ends move out to the lines they cut through.
+        let chunk = read_chunk(&path, 2, 5).await.unwrap();
+        assert_eq!(chunk.bytes, b"0123\n5678\n");
+        assert_eq!(chunk.offset, 0);
+        let chunk = read_chunk(&path, 11, 5).await.unwrap();
+        assert_eq!(chunk.bytes, b"abc");
+        assert_eq!(chunk.offset, 10);
+        let chunk = read_chunk(&path, 20, 5).await.unwrap();
+        assert!(chunk.bytes.is_empty());
+        assert_eq!(chunk.offset, 13);
+
+        std::fs::remove_file(&path).unwrap();
+        let err = read_chunk(&path, 0, 5).await.err().unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,458 @@
+//! Relay mode: forward this instance's sessions to a central dashboard's `/ingest`
+//! over a WebSocket, reconnecting whenever it drops.
+
+use crate::session::manager::SessionManager;
+use crate::types::{AgentSessionSummary, GitStatus, ServerEvent};
+use futures::{SinkExt, StreamExt};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::{broadcast, Notify};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::{header, HeaderValue};
+use tokio_tungstenite::tungstenite::{Error, Message};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use tracing::{debug, info, warn};
+
+const RECONNECT_MIN: Duration = Duration::from_secs(1);
+const RECONNECT_MAX: Duration = Duration::from_secs(30);
+/// Events held while the connection is down or busy. Past this the oldest are
+/// dropped and the central instance is sent a fresh snapshot instead.
+const BUFFER_CAP: usize = 1024;
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelayConfig {
+    pub url: String,
+    pub token: Option<String>,
+    /// Names this instance on the central dashboard.
+    pub host: String,
+    /// Send what sessions do and cost, but not what they are about; see [`anonymized`].
+    pub anonymize: bool,
+}
+
+impl RelayConfig {
+    /// The ingest URL with this instance's host name added.
+    fn url_with_host(&self) -> String {
+        let separator = if self.url.contains('?') { '&' } else { '?' };
+        format!("{}{}host={}", self.url, separator, self.host)
+    }
+}
+
+#[derive(Default)]
+struct Buffer {
+    events: VecDeque<ServerEvent>,
+    /// Events were dropped; the central instance needs a new snapshot.
+    overflowed: bool,
+}
+
+/// Events waiting to be relayed.
+#[derive(Default)]
+struct Outbox {
+    buffer: Mutex<Buffer>,
+    notify: Notify,
+}
+
+impl Outbox {
+    fn push(&self, event: ServerEvent) {
+        let mut buffer = self.buffer.lock().unwrap();
+        if buffer.events.len() >= BUFFER_CAP {
+            buffer.events.pop_front();
+            buffer.overflowed = true;
+        }
+        buffer.events.push_back(event);
+        drop(buffer);
+        self.notify.notify_one();
+    }
+
+    fn overflow(&self) {
+        self.buffer.lock().unwrap().overflowed = true;
+        self.notify.notify_one();
+    }
+
+    /// Everything buffered so far, and whether a snapshot must be sent first.
+    fn take(&self) -> (Vec<ServerEvent>, bool) {
+        let mut buffer = self.buffer.lock().unwrap();
+        let overflowed = std::mem::take(&mut buffer.overflowed);
+        (buffer.events.drain(..).collect(), overflowed)
+    }
+}
+
+/// Start relaying the events sent on `broadcast_tx` and `message_tx`.
+pub fn spawn(
+    config: RelayConfig,
+    manager: Arc<SessionManager>,
+    broadcast_tx: &broadcast::Sender<ServerEvent>,
+    message_tx: &broadcast::Sender<ServerEvent>,
+) {
+    let outbox = Arc::new(Outbox::default());
+    for mut rx in [broadcast_tx.subscribe(), message_tx.subscribe()] {
+        let outbox = outbox.clone();
+        tokio::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => outbox.push(event),
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        debug!("[Relay] Lagged by {} events", n);
+                        outbox.overflow();
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    info!(
+        "[Relay] Relaying sessions to {} as {}",
+        config.url, config.host
+    );
+    tokio::spawn(async move {
+        let mut backoff = RECONNECT_MIN;
+        loop {
+            match connect(&config).await {
+                Ok(socket) => {
+                    info!("[Relay] Connected to {}", config.url);
+                    backoff = RECONNECT_MIN;
+                    if let Err(e) = run(socket, &manager, &outbox, config.anonymize).await {
+                        warn!("[Relay] Connection lost: {}", e);
+                    }
+                }
+                Err(e) => warn!("[Relay] Cannot connect to {}: {}", config.url, e),
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(RECONNECT_MAX);
+        }
+    });
+}
+
+async fn connect(config: &RelayConfig) -> Result<Socket, Error> {
+    let mut request = config.url_with_host().into_client_request()?;
+    if let Some(token) = &config.token {
+        let value = HeaderValue::from_str(&format!("Bearer {}", token))
+            .map_err(|e| Error::HttpFormat(e.into()))?;
+        request.headers_mut().insert(header::AUTHORIZATION, value);
+    }
+    let (socket, _) = tokio_tungstenite::connect_async(request).await?;
+    Ok(socket)
+}
+
+/// Relay over one connection until it fails or the central instance closes it.
+async fn run(
+    socket: Socket,
+    manager: &SessionManager,
+    outbox: &Outbox,
+    anonymize: bool,
+) -> Result<(), Error> {
+    let (mut tx, mut rx) = socket.split();
+    // The snapshot covers everything buffered before it.
+    outbox.take();
+    for event in snapshot(manager).await {
+        send(&mut tx, event, anonymize).await?;
+    }
+
+    loop {
+        tokio::select! {
+            _ = outbox.notify.notified() => {
+                let (events, overflowed) = outbox.take();
+                if overflowed {
+                    for event in snapshot(manager).await {
+                        send(&mut tx, event, anonymize).await?;
+                    }
+                }
+                for event in events {
+                    if let Some(event) = relayed(manager, event).await {
+                        send(&mut tx, event, anonymize).await?;
+                    }
+                }
+            }
+            message = rx.next() => match message {
+                Some(Ok(Message::Close(_))) | None => return Ok(()),
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(e),
+            },
+        }
+    }
+}
+
+async fn send(
+    tx: &mut futures::stream::SplitSink<Socket, Message>,
+    event: ServerEvent,
+    anonymize: bool,
+) -> Result<(), Error> {
+    let event = if anonymize {
+        match anonymized(event) {
+            Some(event) => event,
+            None => return Ok(()),
+        }
+    } else {
+        event
+    };
+    let Ok(json) = serde_json::to_string(&event) else {
+        return Ok(());
+    };
+    tx.send(Message::Text(json.into())).await
+}
+
+/// `event` as an anonymized relay sends it: sessions' state, model, usage and
+/// timing, with prompts, replies, notes, paths, branches and tool inputs left out.
+/// Messages aren't sent at all. Projects are known by name only.
+fn anonymized(event: ServerEvent) -> Option<ServerEvent> {
+    let event = match event {
+        ServerEvent::SessionsInit {
+            mut sessions,
+            unread_counts,
+        } => {
+            sessions.iter_mut().for_each(anonymize_summary);
+            ServerEvent::SessionsInit {
+                sessions,
+                unread_counts,
+            }
+        }
+        ServerEvent::SessionDiscovered { mut session } => {
+            anonymize_summary(&mut session);
+            ServerEvent::SessionDiscovered { session }
+        }
+        ServerEvent::SessionUpdated { mut session } => {
+            anonymize_summary(&mut session);
+            ServerEvent::SessionUpdated { session }
+        }
+        ServerEvent::StateChanged {
+            session_id,
+            previous,
+            current,
+            mut session,
+            unread_count,
+        } => {
+            anonymize_summary(&mut session);
+            ServerEvent::StateChanged {
+                session_id,
+                previous,
+                current,
+                session,
+                unread_count,
+            }
+        }
+        ServerEvent::ContextWarning {
+            session_id,
+            percent_used,
+            threshold,
+            mut session,
+        } => {
+            anonymize_summary(&mut session);
+            ServerEvent::ContextWarning {
+                session_id,
+                percent_used,
+                threshold,
+                session,
+            }
+        }
+        ServerEvent::GitStatusUpdated {
+            session_id,
+            git_status,
+        } => ServerEvent::GitStatusUpdated {
+            session_id,
+            git_status: anonymized_git_status(git_status),
+        },
+        ServerEvent::LogDeleted { session_id, .. } => ServerEvent::LogDeleted {
+            session_id,
+            log_file: String::new(),
+        },
+        ServerEvent::BudgetExceeded {
+            project_name,
+            period,
+            limit_usd,
+            spent_usd,
+            ..
+        } => ServerEvent::BudgetExceeded {
+            project_path: project_name.clone(),
+            project_name,
+            period,
+            limit_usd,
+            spent_usd,
+        },
+        ServerEvent::NewMessage { .. }
+        | ServerEvent::MessagesAppended { .. }
+        | ServerEvent::MessagesInit { .. } => return None,
+        event @ (ServerEvent::SessionRemoved { .. }
+        | ServerEvent::UsageUpdated { .. }
+        | ServerEvent::ModelChanged { .. }
+        | ServerEvent::Hello { .. }
+        | ServerEvent::StartupComplete { .. }
+        | ServerEvent::Error { .. }) => event,
+    };
+    Some(event)
+}
+
+fn anonymize_summary(session: &mut AgentSessionSummary) {
+    session.project_path = session.project_name.clone();
+    session.working_directory = session.project_name.clone();
+    session.current_cwd = session.project_name.clone();
+    session.initial_task = String::new();
+    session.latest_task = String::new();
+    session.latest_assistant_text = None;
+    session.title = None;
+    session.note = None;
+    session.labels = Vec::new();
+    session.summary = None;
+    session.pending_tool = None;
+    session.git_status = anonymized_git_status(std::mem::take(&mut session.git_status));
+}
+
+/// How much changed, but not on which branch or in which files.
+fn anonymized_git_status(git_status: GitStatus) -> GitStatus {
+    GitStatus {
+        branch: String::new(),
+        files: Vec::new(),
+        last_commit: None,
+        ..git_status
+    }
+}
+
+/// Every local session, then each session's messages.
+async fn snapshot(manager: &SessionManager) -> Vec<ServerEvent> {
+    let sessions: Vec<_> = manager
+        .get_sessions(true)
+        .await
+        .into_iter()
+        .filter(|s| !manager.is_relayed(&s.session_id))
+        .collect();
+    let mut events = Vec::with_capacity(sessions.len() + 1);
+    for session in &sessions {
+        if let Some(messages) = manager.get_session_messages(&session.session_id).await {
+            events.push(ServerEvent::MessagesInit {
+                session_id: session.session_id.clone(),
+                messages,
+            });
+        }
+    }
+    events.insert(
+        0,
+        ServerEvent::SessionsInit {
+            sessions,
+            unread_counts: None,
+        },
+    );
+    events
+}
+
+/// What to send for a local event. Batches of messages aren't sent one by one,
+/// so the session's messages are sent whole instead. Sessions this instance
+/// itself receives from relays are not passed on.
+async fn relayed(manager: &SessionManager, event: ServerEvent) -> Option<ServerEvent> {
+    let session_id = match &event {
+        ServerEvent::SessionDiscovered { session } | ServerEvent::SessionUpdated { session } => {
+            Some(&session.session_id)
+        }
+        ServerEvent::SessionRemoved { session_id }
+        | ServerEvent::StateChanged { session_id, .. }
+        | ServerEvent::NewMessage { session_id, .. }
+        | ServerEvent::MessagesAppended { session_id, .. }
+        | ServerEvent::MessagesInit { session_id, .. }
+        | ServerEvent::UsageUpdated { session_id, .. }
+        | ServerEvent::GitStatusUpdated { session_id, .. }
+        | ServerEvent::ContextWarning { session_id, .. }
+        | ServerEvent::ModelChanged { session_id, .. }
+        | ServerEvent::LogDeleted { session_id, .. } => Some(session_id),
+        ServerEvent::Hello { .. }
+        | ServerEvent::SessionsInit { .. }
+        | ServerEvent::StartupComplete { .. }
+        | ServerEvent::Error { .. } => return None,
+        ServerEvent::BudgetExceeded { .. } => None,
+    };
+    if session_id.is_some_and(|id| manager.is_relayed(id)) {
+        return None;
+    }
+    match event {
+        ServerEvent::MessagesAppended { session_id, .. } => {
+            let messages = manager.get_session_messages(&session_id).await?;
+            Some(ServerEvent::MessagesInit {
+                session_id,
+                messages,
+            })
+        }
+        event => Some(event),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url_with_host() {
+        let mut config = RelayConfig {
+            url: "ws://central:3001/ingest".to_string(),
+            token: None,
+            host: "laptop".to_string(),
+            anonymize: true,
+        };
+        assert_eq!(
+            config.url_with_host(),
+            "ws://central:3001/ingest?host=laptop"
+        );
+        config.url = "wss://central/ingest?v=1".to_string();
+        assert_eq!(
+            config.url_with_host(),
+            "wss://central/ingest?v=1&host=laptop"
+        );
+    }
+
+    #[test]
+    fn test_anonymized_events_leave_out_content() {
+        let session = AgentSessionSummary {
+            session_id: "s1".to_string(),
+            project_path: "/home/alice/work/app".to_string(),
+            project_name: "app".to_string(),
+            working_directory: "/home/alice/work/app".to_string(),
+            initial_task: "fix the login bug".to_string(),
+            latest_assistant_text: Some("Done.".to_string()),
+            model: "claude-sonnet-4-20250514".to_string(),
+            git_status: GitStatus {
+                branch: "fix/login".to_string(),
+                additions: 12,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let Some(ServerEvent::SessionDiscovered { session }) =
+            anonymized(ServerEvent::SessionDiscovered { session })
+        else {
+            panic!("session not relayed");
+        };
+        let json = serde_json::to_string(&session).unwrap();
+        for private in ["alice", "login", "Done."] {
+            assert!(!json.contains(private), "{} in {}", private, json);
+        }
+        assert_eq!(session.project_name, "app");
+        assert_eq!(session.project_path, "app");
+        assert_eq!(session.model, "claude-sonnet-4-20250514");
+        assert_eq!(session.git_status.additions, 12);
+
+        let messages = ServerEvent::MessagesInit {
+            session_id: "s1".to_string(),
+            messages: Vec::new(),
+        };
+        assert!(anonymized(messages).is_none());
+        let removed = ServerEvent::SessionRemoved {
+            session_id: "s1".to_string(),
+        };
+        assert!(anonymized(removed).is_some());
+    }
+
+    #[test]
+    fn test_outbox_overflow_asks_for_snapshot() {
+        let outbox = Outbox::default();
+        for i in 0..BUFFER_CAP + 1 {
+            outbox.push(ServerEvent::SessionRemoved {
+                session_id: i.to_string(),
+            });
+        }
+        let (events, overflowed) = outbox.take();
+        assert_eq!(events.len(), BUFFER_CAP);
+        assert!(overflowed);
+        let (events, overflowed) = outbox.take();
+        assert!(events.is_empty() && !overflowed);
+    }
+}
@@ -0,0 +1,144 @@
+//! Applying config file changes without a restart: on `SIGHUP`, when the file
+//! changes, or on `POST /api/config/reload`.
+
+use crate::config::{applies_live, Config, ConfigError};
+use crate::notifications::ntfy::NtfyConfig;
+use crate::server::http::AppState;
+use crate::types::ConfigReload;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+/// Editors often write a file in several steps; wait for them to finish.
+const SETTLE: Duration = Duration::from_millis(200);
+
+/// Read the config file again and apply what can change live. On error the
+/// current settings stay in place.
+pub fn reload(state: &AppState) -> Result<ConfigReload, ConfigError> {
+    let new = state.config_source.load()?;
+    let current = state.config.load_full();
+    let ConfigReload {
+        applied,
+        requires_restart,
+    } = changes(&current, &new);
+
+    if !applied.is_empty() {
+        let effective = current.with_live_from(&new);
+        state.limits.reconfigure(
+            effective.server.api_rate_limit,
+            effective.server.search_max_query_len,
+            effective.server.search_concurrency,
+        );
+        state
+            .session_manager
+            .session_settings()
+            .store(Arc::new(effective.session_settings()));
+        state
+            .session_manager
+            .set_budgets(effective.cost.budgets.clone());
+        state.notifier.reconfigure(NtfyConfig::from_section(
+            &effective.notifications.ntfy,
+            &effective.dashboard_url(),
+        ));
+        state.config.store(Arc::new(effective));
+        info!("[Config] Applied: {}", applied.join(", "));
+    }
+    for key in &requires_restart {
+        warn!("[Config] {} changed; requires restart", key);
+    }
+    Ok(ConfigReload {
+        applied,
+        requires_restart,
+    })
+}
+
+/// What `reload` would do with the config file as it is now, without doing it.
+pub fn plan_reload(state: &AppState) -> Result<ConfigReload, ConfigError> {
+    let new = state.config_source.load()?;
+    Ok(changes(&state.config.load(), &new))
+}
+
+fn changes(current: &Config, new: &Config) -> ConfigReload {
+    let (applied, requires_restart) = current
+        .changed_keys(new)
+        .into_iter()
+        .partition(|key| applies_live(key));
+    ConfigReload {
+        applied,
+        requires_restart,
+    }
+}
+
+fn reload_logged(state: &AppState, reason: &str) {
+    debug!("[Config] Reloading ({})", reason);
+    if let Err(e) = reload(state) {
+        warn!("[Config] Reload failed, keeping current settings: {}", e);
+    }
+}
+
+/// Reload on `SIGHUP` and whenever the config file is written.
+pub fn spawn_watchers(state: Arc<AppState>) {
+    #[cfg(unix)]
+    {
+        let state = state.clone();
+        tokio::spawn(async move {
+            use tokio::signal::unix::{signal, SignalKind};
+            let mut hangups = match signal(SignalKind::hangup()) {
+                Ok(hangups) => hangups,
+                Err(e) => {
+                    warn!("[Config] Cannot listen for SIGHUP: {}", e);
+                    return;
+                }
+            };
+            while hangups.recv().await.is_some() {
+                reload_logged(&state, "SIGHUP");
+            }
+        });
+    }
+
+    let Some(path) = state.config_source.path.clone() else {
+        return;
+    };
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return;
+    };
+    // Watch the directory rather than the file: editors and config management
+    // often replace the file, which would end a watch on the file itself.
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let watcher = {
+        use notify::{Config, Event, RecommendedWatcher, RecursiveMode, Watcher};
+        let name = name.to_os_string();
+        let mut watcher = match RecommendedWatcher::new(
+            move |res: Result<Event, notify::Error>| {
+                if let Ok(event) = res {
+                    if event.paths.iter().any(|p| p.file_name() == Some(&name)) {
+                        let _ = tx.send(());
+                    }
+                }
+            },
+            Config::default(),
+        ) {
+            Ok(watcher) => watcher,
+            Err(e) => {
+                warn!("[Config] Cannot watch {}: {}", path.display(), e);
+                return;
+            }
+        };
+        if let Err(e) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+            debug!("[Config] Not watching {}: {}", dir.display(), e);
+            return;
+        }
+        watcher
+    };
+    info!("[Config] Watching {} for changes", path.display());
+
+    tokio::spawn(async move {
+        let _watcher = watcher;
+        while rx.recv().await.is_some() {
+            tokio::time::sleep(SETTLE).await;
+            while rx.try_recv().is_ok() {}
+            reload_logged(&state, "file changed");
+        }
+    });
+}
@@ -0,0 +1,511 @@
+//! Markdown rendering of `GET /api/reports/usage?format=markdown` and
+//! `GET /api/projects/{path}/digest?format=markdown`, for pasting into a wiki page
+//! or a chat message.
+
+use crate::types::{DayDigest, ReportPeriod, UsageReport};
+use chrono::DateTime;
+use std::fmt::Write;
+
+pub fn render(report: &UsageReport) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# Usage report: {} to {}", report.from, report.to);
+    let _ = writeln!(out);
+    let period = match report.period {
+        ReportPeriod::Week => "Last 7 days",
+        ReportPeriod::Month => "Last 30 days",
+    };
+    let _ = writeln!(
+        out,
+        "{} (UTC), generated {}.",
+        period,
+        generated_at(&report.generated_at)
+    );
+    let _ = writeln!(out);
+    coverage(&mut out, report);
+
+    let _ = writeln!(out, "## Totals");
+    let _ = writeln!(out);
+    table(
+        &mut out,
+        &[("Cost", true), ("Tokens", true), ("Sessions", true)],
+        vec![vec![
+            cost(report.total_cost),
+            tokens(report.total_tokens),
+            report.sessions.to_string(),
+        ]],
+    );
+
+    let _ = writeln!(out, "## Projects");
+    let _ = writeln!(out);
+    table(
+        &mut out,
+        &[
+            ("Project", false),
+            ("Cost", true),
+            ("Tokens", true),
+            ("Sessions", true),
+        ],
+        report
+            .projects
+            .iter()
+            .map(|p| {
+                vec![
+                    p.project_name.clone(),
+                    cost(p.cost),
+                    tokens(p.tokens),
+                    p.sessions.to_string(),
+                ]
+            })
+            .collect(),
+    );
+
+    let _ = writeln!(out, "## Models");
+    let _ = writeln!(out);
+    table(
+        &mut out,
+        &[
+            ("Model", false),
+            ("Cost", true),
+            ("Tokens", true),
+            ("Sessions", true),
+        ],
+        report
+            .models
+            .iter()
+            .map(|m| {
+                vec![
+                    format!("`{}`", m.model),
+                    cost(m.cost),
+                    tokens(m.tokens),
+                    m.sessions.to_string(),
+                ]
+            })
+            .collect(),
+    );
+
+    let _ = writeln!(out, "## Busiest days");
+    let _ = writeln!(out);
+    table(
+        &mut out,
+        &[("Date", false), ("Cost", true), ("Tokens", true)],
+        report
+            .busiest_days
+            .iter()
+            .map(|d| vec![d.date.clone(), cost(d.cost), tokens(d.tokens)])
+            .collect(),
+    );
+
+    let _ = writeln!(out, "## Longest sessions");
+    let _ = writeln!(out);
+    table(
+        &mut out,
+        &[
+            ("Session", false),
+            ("Project", false),
+            ("Model", false),
+            ("Duration", true),
+            ("Cost", true),
+        ],
+        report
+            .longest_sessions
+            .iter()
+            .map(|s| {
+                let title = if s.title.is_empty() {
+                    format!("`{}`", s.session_id.chars().take(8).collect::<String>())
+                } else {
+                    s.title.clone()
+                };
+                vec![
+                    title,
+                    s.project_name.clone(),
+                    format!("`{}`", s.model),
+                    duration(s.duration_secs),
+                    cost(s.cost),
+                ]
+            })
+            .collect(),
+    );
+
+    let _ = writeln!(out, "## Top tools");
+    let _ = writeln!(out);
+    table(
+        &mut out,
+        &[("Tool", false), ("Calls", true)],
+        report
+            .top_tools
+            .iter()
+            .map(|t| vec![t.name.clone(), t.count.to_string()])
+            .collect(),
+    );
+    out.truncate(out.trim_end().len());
+    out.push('\n');
+    out
+}
+
+pub fn render_digest(digest: &DayDigest) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# {}: {}", digest.project_name, digest.date);
+    let _ = writeln!(out);
+    let _ = writeln!(
+        out,
+        "`{}` (UTC), generated {}.",
+        digest.project_path,
+        generated_at(&digest.generated_at)
+    );
+    let _ = writeln!(out);
+
+    let _ = writeln!(out, "## Totals");
+    let _ = writeln!(out);
+    table(
+        &mut out,
+        &[
+            ("Cost", true),
+            ("Tokens", true),
+            ("Active", true),
+            ("Started", true),
+            ("Ended", true),
+        ],
+        vec![vec![
+            cost(digest.total_cost),
+            tokens(digest.total_tokens),
+            duration(digest.active_ms / 1000),
+            digest.sessions_started.to_string(),
+            digest.sessions_ended.to_string(),
+        ]],
+    );
+
+    let _ = writeln!(out, "## Sessions");
+    let _ = writeln!(out);
+    table(
+        &mut out,
+        &[
+            ("Session", false),
+            ("Model", false),
+            ("State", false),
+            ("Active", true),
+            ("Cost", true),
+        ],
+        digest
+            .sessions
+            .iter()
+            .map(|s| {
+                let title = match &s.title {
+                    Some(title) => title.clone(),
+                    None => format!("`{}`", s.session_id.chars().take(8).collect::<String>()),
+                };
+                let state = match (s.state, &s.ended_at) {
+                    (_, Some(_)) => "ended".to_string(),
+                    (Some(state), None) => state.to_string(),
+                    (None, None) => "-".to_string(),
+                };
+                vec![
+                    title,
+                    format!("`{}`", s.model),
+                    state,
+                    s.active_ms
+                        .map_or("-".to_string(), |ms| duration(ms / 1000)),
+                    cost(s.cost),
+                ]
+            })
+            .collect(),
+    );
+
+    let _ = writeln!(out, "## Commits");
+    let _ = writeln!(out);
+    match &digest.commits {
+        None => {
+            let _ = writeln!(out, "_No git information_");
+            let _ = writeln!(out);
+        }
+        Some(commits) => table(
+            &mut out,
+            &[("Commit", false), ("Subject", false), ("Author", false)],
+            commits
+                .iter()
+                .map(|c| vec![format!("`{}`", c.hash), c.subject.clone(), c.author.clone()])
+                .collect(),
+        ),
+    }
+
+    let _ = writeln!(out, "## Files");
+    let _ = writeln!(out);
+    if digest.files.is_empty() {
+        let _ = writeln!(out, "_None_");
+    }
+    for file in &digest.files {
+        let _ = writeln!(out, "- `{}`", file);
+    }
+    out.truncate(out.trim_end().len());
+    out.push('\n');
+    out
+}
+
+/// Says which days the numbers come from when history doesn't span the period.
+fn coverage(out: &mut String, report: &UsageReport) {
+    let mut notes = Vec::new();
+    match &report.coverage.history_from {
+        None => notes.push("No usage has been recorded yet.".to_string()),
+        Some(from) if !report.coverage.complete => notes.push(format!(
+            "Usage history starts on {}; earlier days in this period are not included.",
+            from
+        )),
+        Some(_) => {}
+    }
+    match report.coverage.sessions_without_details {
+        0 => {}
+        1 => notes.push(
+            "1 session was recorded before tokens, models and durations were kept; \
+             only its cost is included."
+                .to_string(),
+        ),
+        n => notes.push(format!(
+            "{} sessions were recorded before tokens, models and durations were kept; \
+             only their cost is included.",
+            n
+        )),
+    }
+    for note in &notes {
+        let _ = writeln!(out, "> {}", note);
+    }
+    if !notes.is_empty() {
+        let _ = writeln!(out);
+    }
+}
+
+/// A table followed by a blank line; `_None_` when there are no rows.
+fn table(out: &mut String, columns: &[(&str, bool)], rows: Vec<Vec<String>>) {
+    if rows.is_empty() {
+        let _ = writeln!(out, "_None_");
+        let _ = writeln!(out);
+        return;
+    }
+    let header: Vec<&str> = columns.iter().map(|(name, _)| *name).collect();
+    let _ = writeln!(out, "| {} |", header.join(" | "));
+    let align: Vec<&str> = columns
+        .iter()
+        .map(|(_, right)| if *right { "---:" } else { "---" })
+        .collect();
+    let _ = writeln!(out, "| {} |", align.join(" | "));
+    for row in rows {
+        let cells: Vec<String> = row.iter().map(|cell| escape(cell)).collect();
+        let _ = writeln!(out, "| {} |", cells.join(" | "));
+    }
+    let _ = writeln!(out);
+}
+
+/// Keep a cell on one line and out of the table syntax.
+fn escape(cell: &str) -> String {
+    cell.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace('|', "\\|")
+}
+
+fn cost(dollars: f64) -> String {
+    format!("${:.2}", dollars)
+}
+
+/// `1,234,567`.
+fn tokens(count: u64) -> String {
+    let digits = count.to_string();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// `2h 05m`, `12m`, `40s`.
+fn duration(secs: u64) -> String {
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{}m", secs / 60),
+        _ => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+fn generated_at(timestamp: &str) -> String {
+    DateTime::parse_from_rfc3339(timestamp)
+        .map(|dt| dt.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_else(|_| timestamp.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{
+        ReportCoverage, ReportDay, ReportModel, ReportProject, ReportSession, ToolCount,
+    };
+
+    const GOLDEN: &str = include_str!("testdata/usage_report.md");
+
+    fn report() -> UsageReport {
+        UsageReport {
+            period: ReportPeriod::Week,
+            from: "2025-01-09".to_string(),
+            to: "2025-01-15".to_string(),
+            generated_at: "2025-01-15T12:00:00+00:00".to_string(),
+            coverage: ReportCoverage {
+                history_from: Some("2025-01-12".to_string()),
+                complete: false,
+                sessions_without_details: 2,
+            },
+            total_cost: 12.346,
+            total_tokens: 1_234_567,
+            sessions: 5,
+            projects: vec![
+                ReportProject {
+                    project_path: "/work/api".to_string(),
+                    project_name: "api".to_string(),
+                    cost: 10.0,
+                    tokens: 1_000_000,
+                    sessions: 3,
+                },
+                ReportProject {
+                    project_path: "/work/a|b".to_string(),
+                    project_name: "a|b".to_string(),
+                    cost: 2.346,
+                    tokens: 234_567,
+                    sessions: 2,
+                },
+            ],
+            models: vec![ReportModel {
+                model: "claude-sonnet-4-5".to_string(),
+                cost: 8.0,
+                tokens: 900_000,
+                sessions: 3,
+            }],
+            busiest_days: vec![
+                ReportDay {
+                    date: "2025-01-14".to_string(),
+                    cost: 7.5,
+                    tokens: 800_000,
+                },
+                ReportDay {
+                    date: "2025-01-13".to_string(),
+                    cost: 4.846,
+                    tokens: 434_567,
+                },
+            ],
+            longest_sessions: vec![
+                ReportSession {
+                    session_id: "a1b2c3d4-0000".to_string(),
+                    project_name: "api".to_string(),
+                    title: "Add pagination\nto the API".to_string(),
+                    model: "claude-sonnet-4-5".to_string(),
+                    started_at: "2025-01-14T09:00:00Z".to_string(),
+                    last_activity_at: "2025-01-14T11:05:00Z".to_string(),
+                    duration_secs: 2 * 3600 + 5 * 60,
+                    cost: 6.0,
+                },
+                ReportSession {
+                    session_id: "e5f6a7b8-1111".to_string(),
+                    project_name: "a|b".to_string(),
+                    title: String::new(),
+                    model: "claude-opus-4-1".to_string(),
+                    started_at: "2025-01-13T09:00:00Z".to_string(),
+                    last_activity_at: "2025-01-13T09:12:00Z".to_string(),
+                    duration_secs: 12 * 60,
+                    cost: 1.5,
+                },
+            ],
+            top_tools: vec![
+                ToolCount {
+                    name: "Read".to_string(),
+                    count: 1200,
+                },
+                ToolCount {
+                    name: "Edit".to_string(),
+                    count: 340,
+                },
+            ],
+        }
+    }
+
+    /// Set `UPDATE_GOLDEN=1` to rewrite the expected file after an intended change.
+    #[test]
+    fn test_golden() {
+        let rendered = render(&report());
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            let path = concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/src/server/testdata/usage_report.md"
+            );
+            std::fs::write(path, &rendered).unwrap();
+            return;
+        }
+        assert_eq!(rendered, GOLDEN);
+    }
+
+    #[test]
+    fn test_empty_report() {
+        let report = UsageReport {
+            coverage: ReportCoverage::default(),
+            total_cost: 0.0,
+            total_tokens: 0,
+            sessions: 0,
+            projects: Vec::new(),
+            models: Vec::new(),
+            busiest_days: Vec::new(),
+            longest_sessions: Vec::new(),
+            top_tools: Vec::new(),
+            ..report()
+        };
+        let rendered = render(&report);
+        assert!(rendered.contains("> No usage has been recorded yet.\n"));
+        assert!(rendered.contains("## Projects\n\n_None_\n\n## Models"));
+        assert!(rendered.ends_with("## Top tools\n\n_None_\n"));
+    }
+
+    #[test]
+    fn test_digest() {
+        let digest = DayDigest {
+            project_path: "/work/api".to_string(),
+            project_name: "api".to_string(),
+            date: "2025-01-14".to_string(),
+            generated_at: "2025-01-15T12:00:00+00:00".to_string(),
+            sessions_started: 1,
+            sessions_ended: 1,
+            total_cost: 1.5,
+            total_tokens: 12_000,
+            active_ms: 125_000,
+            files: vec!["src/lib.rs".to_string()],
+            commits: None,
+            sessions: vec![crate::types::DigestSession {
+                session_id: "a1b2c3d4-0000".to_string(),
+                title: None,
+                model: "claude-sonnet-4-5".to_string(),
+                state: None,
+                started_at: "2025-01-14T09:00:00Z".to_string(),
+                started_on_day: true,
+                ended_at: Some("2025-01-14T09:30:00Z".to_string()),
+                cost: 1.5,
+                tokens: 12_000,
+                active_ms: None,
+                files: None,
+                top_tools: Vec::new(),
+            }],
+        };
+        let rendered = render_digest(&digest);
+        assert!(rendered.starts_with("# api: 2025-01-14\n"));
+        assert!(rendered.contains("| $1.50 | 12,000 | 2m | 1 | 1 |"));
+        assert!(rendered.contains("| `a1b2c3d4` | `claude-sonnet-4-5` | ended | - | $1.50 |"));
+        assert!(rendered.contains("## Commits\n\n_No git information_\n"));
+        assert!(rendered.ends_with("- `src/lib.rs`\n"));
+    }
+
+    #[test]
+    fn test_formatting() {
+        assert_eq!(tokens(0), "0");
+        assert_eq!(tokens(999), "999");
+        assert_eq!(tokens(1000), "1,000");
+        assert_eq!(tokens(1_234_567), "1,234,567");
+        assert_eq!(duration(40), "40s");
+        assert_eq!(duration(720), "12m");
+        assert_eq!(duration(7500), "2h 05m");
+        assert_eq!(escape("a | b\nc"), "a \\| b c");
+    }
+}
@@ -0,0 +1,257 @@
+//! Routes provider events to the broadcast channels the WebSocket clients and the
+//! relay listen on, recording them in the journal and sending notifications on the way.
+
+use crate::notifications::Notifier;
+use crate::providers::ProviderEvent;
+use crate::server::coalesce::EventCoalescer;
+use crate::session::journal::EventJournal;
+use crate::session::manager::SessionManager;
+use crate::session::summarizer::SummaryError;
+use crate::session::watchdog::HEARTBEAT_INTERVAL;
+use crate::types::{AgentStateType, ServerEvent};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+/// Start routing `manager`'s events. Spawn this before starting the manager so the
+/// sessions found at startup are broadcast as they are discovered.
+pub fn spawn(
+    manager: Arc<SessionManager>,
+    broadcast_tx: broadcast::Sender<ServerEvent>,
+    message_tx: broadcast::Sender<ServerEvent>,
+    journal: Option<Arc<EventJournal>>,
+    notifier: Arc<Notifier>,
+    flush_interval: Duration,
+) {
+    tokio::spawn(async move {
+        let mut event_rx = manager.event_rx.lock().await;
+        let mut startup_rx = manager.subscribe_startup();
+        let mut coalescer = EventCoalescer::new(flush_interval);
+        // Beats tell the watchdog the loop is alive even when nothing happens.
+        let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+        heartbeat.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        // The journal and project totals see every event; clients get usage and git
+        // status updates coalesced.
+        loop {
+            let event = tokio::select! {
+                event = event_rx.recv() => {
+                    let Some(event) = event else { break };
+                    manager.heartbeat();
+                    if let Some(journal) = &journal {
+                        journal.record(&event);
+                    }
+                    if let ProviderEvent::UsageUpdated { session_id, .. } = &event {
+                        if let Some(exceeded) = manager.record_project_usage(session_id).await {
+                            let _ = broadcast_tx.send(exceeded);
+                        }
+                    }
+                    match coalescer.push(event, std::time::Instant::now()) {
+                        Some(event) => event,
+                        None => continue,
+                    }
+                }
+                event = coalescer.next_due() => event,
+                _ = heartbeat.tick() => {
+                    manager.heartbeat();
+                    continue;
+                }
+                Ok(()) = startup_rx.changed() => {
+                    let summary = startup_rx.borrow_and_update().clone();
+                    if let Some(summary) = summary {
+                        info!(
+                            "[Startup] Read {} sessions ({} entries, {} parse errors) in {}ms{}",
+                            summary.sessions_discovered,
+                            summary.entries_processed,
+                            summary.parse_errors,
+                            summary.duration_ms,
+                            if summary.timed_out { ", timed out" } else { "" }
+                        );
+                        let _ = broadcast_tx.send(ServerEvent::StartupComplete {
+                            sessions_discovered: summary.sessions_discovered,
+                            entries_processed: summary.entries_processed,
+                            parse_errors: summary.parse_errors,
+                            duration_ms: summary.duration_ms,
+                            timed_out: summary.timed_out,
+                        });
+                    }
+                    continue;
+                }
+            };
+            // Archived sessions stay off the channels, as they stay out of the session
+            // list; a removal still goes out so clients drop them.
+            if !matches!(event, ProviderEvent::SessionRemoved { .. })
+                && manager.is_archived(event.session_id()).await
+            {
+                continue;
+            }
+            let server_event = match &event {
+                ProviderEvent::SessionDiscovered { session } => {
+                    info!(
+                        "[Session] Discovered: {} ({})",
+                        session.session_id, session.project_name
+                    );
+                    let mut session = session.clone();
+                    manager.apply_prefs(&mut session).await;
+                    Some(ServerEvent::SessionDiscovered { session })
+                }
+                ProviderEvent::SessionRemoved { session_id } => {
+                    info!("[Session] Removed: {}", session_id);
+                    Some(ServerEvent::SessionRemoved {
+                        session_id: session_id.clone(),
+                    })
+                }
+                ProviderEvent::StateChanged {
+                    session_id,
+                    previous,
+                    current,
+                } => {
+                    info!("[Session] {}: {} → {}", session_id, previous, current);
+                    manager.snapshot_diff(session_id, *current);
+                    let session = manager.get_session_summary(session_id).await;
+                    if let Some(session) = &session {
+                        if *current == AgentStateType::Stopped
+                            && previous != current
+                            && manager.summarizes_on_stop(session)
+                        {
+                            summarize(manager.clone(), broadcast_tx.clone(), session_id.clone());
+                        }
+                    }
+                    session.map(|session| ServerEvent::StateChanged {
+                        session_id: session_id.clone(),
+                        previous: *previous,
+                        current: *current,
+                        session,
+                        unread_count: None,
+                    })
+                }
+                ProviderEvent::UsageUpdated {
+                    session_id,
+                    usage,
+                    spend_rate_per_hour,
+                    tokens_per_minute,
+                    context_tokens,
+                    context_limit,
+                } => Some(ServerEvent::UsageUpdated {
+                    session_id: session_id.clone(),
+                    usage: usage.clone(),
+                    spend_rate_per_hour: *spend_rate_per_hour,
+                    tokens_per_minute: *tokens_per_minute,
+                    context_tokens: *context_tokens,
+                    context_limit: *context_limit,
+                }),
+                ProviderEvent::NewMessage {
+                    session_id,
+                    message,
+                } => {
+                    let msg_event = ServerEvent::NewMessage {
+                        session_id: session_id.clone(),
+                        message: message.clone(),
+                    };
+                    let _ = message_tx.send(msg_event);
+                    None
+                }
+                ProviderEvent::MessagesAppended {
+                    session_id,
+                    count,
+                    latest_timestamp,
+                } => {
+                    let _ = message_tx.send(ServerEvent::MessagesAppended {
+                        session_id: session_id.clone(),
+                        count: *count,
+                        latest_timestamp: latest_timestamp.clone(),
+                    });
+                    None
+                }
+                ProviderEvent::GitStatusUpdated {
+                    session_id,
+                    git_status,
+                } => Some(ServerEvent::GitStatusUpdated {
+                    session_id: session_id.clone(),
+                    git_status: git_status.clone(),
+                }),
+                ProviderEvent::ContextWarning {
+                    session_id,
+                    percent_used,
+                    threshold,
+                } => {
+                    info!("[Session] {}: context {}% full", session_id, percent_used);
+                    manager
+                        .get_session_summary(session_id)
+                        .await
+                        .map(|session| ServerEvent::ContextWarning {
+                            session_id: session_id.clone(),
+                            percent_used: *percent_used,
+                            threshold: *threshold,
+                            session,
+                        })
+                }
+                ProviderEvent::ModelChanged {
+                    session_id,
+                    previous,
+                    current,
+                } => {
+                    info!(
+                        "[Session] {}: model changed from {} to {}",
+                        session_id, previous, current
+                    );
+                    Some(ServerEvent::ModelChanged {
+                        session_id: session_id.clone(),
+                        previous: previous.clone(),
+                        current: current.clone(),
+                    })
+                }
+                ProviderEvent::LogDeleted {
+                    session_id,
+                    log_file,
+                } => {
+                    warn!("[Session] {}: log {} was deleted", session_id, log_file);
+                    Some(ServerEvent::LogDeleted {
+                        session_id: session_id.clone(),
+                        log_file: log_file.clone(),
+                    })
+                }
+                ProviderEvent::SessionUpdated { session } => {
+                    let mut session = session.clone();
+                    manager.apply_prefs(&mut session).await;
+                    Some(ServerEvent::SessionUpdated { session })
+                }
+            };
+
+            if let Some(evt) = server_event {
+                let (muted, rules) = match &evt {
+                    ServerEvent::StateChanged { session_id, .. }
+                    | ServerEvent::ContextWarning { session_id, .. } => (
+                        manager.is_muted(session_id).await,
+                        manager.notify_rules(session_id).await,
+                    ),
+                    _ => (false, None),
+                };
+                if !muted {
+                    notifier.notify(&evt, rules.as_ref());
+                }
+                let _ = broadcast_tx.send(evt);
+            }
+        }
+    });
+}
+
+/// Summarize a session that stopped, off the routing loop, and tell clients once the
+/// summary is in.
+fn summarize(
+    manager: Arc<SessionManager>,
+    broadcast_tx: broadcast::Sender<ServerEvent>,
+    session_id: String,
+) {
+    tokio::spawn(async move {
+        match manager.summarize(&session_id).await {
+            Ok(_) => {
+                if let Some(session) = manager.get_session_summary(&session_id).await {
+                    let _ = broadcast_tx.send(ServerEvent::SessionUpdated { session });
+                }
+            }
+            Err(SummaryError::Empty | SummaryError::RateLimited(_)) => {}
+            Err(e) => warn!("[Summary] {}: {}", session_id, e),
+        }
+    });
+}
@@ -0,0 +1,196 @@
+use crate::types::AgentSessionSummary;
+use chrono::{DateTime, NaiveDate};
+use chrono_tz::Tz;
+
+const HEADER: [&str; 10] = [
+    "date",
+    "project",
+    "project_path",
+    "session_id",
+    "model",
+    "input_tokens",
+    "output_tokens",
+    "cache_read_tokens",
+    "cache_creation_tokens",
+    "estimated_cost",
+];
+
+/// Build a usage CSV for sessions whose last activity falls within `from..=to` (dates
+/// in `tz`).
+///
+/// Usage is only tracked as a per-session cumulative total, so each row is one session,
+/// dated by the day in `tz` of its last activity. A session spanning midnight is
+/// attributed entirely to the later day.
+pub fn build_usage_csv(
+    sessions: &[AgentSessionSummary],
+    from: NaiveDate,
+    to: NaiveDate,
+    tz: Tz,
+) -> String {
+    let mut rows: Vec<(NaiveDate, &AgentSessionSummary)> = sessions
+        .iter()
+        .filter_map(|s| {
+            let date = activity_date(&s.last_activity_at, tz)?;
+            (date >= from && date <= to).then_some((date, s))
+        })
+        .collect();
+    rows.sort_by(|a, b| {
+        a.0.cmp(&b.0)
+            .then_with(|| a.1.project_name.cmp(&b.1.project_name))
+            .then_with(|| a.1.session_id.cmp(&b.1.session_id))
+    });
+
+    let mut out = String::new();
+    push_row(&mut out, HEADER.iter().map(|h| h.to_string()));
+
+    let (mut input, mut output, mut cache_read, mut cache_creation, mut cost) = (0, 0, 0, 0, 0.0);
+    for (date, s) in &rows {
+        let usage = &s.cumulative_usage;
+        input += usage.input_tokens;
+        output += usage.output_tokens;
+        cache_read += usage.cache_read_tokens;
+        cache_creation += usage.cache_creation_tokens;
+        cost += usage.estimated_cost;
+        push_row(
+            &mut out,
+            [
+                date.to_string(),
+                s.project_name.clone(),
+                s.project_path.clone(),
+                s.session_id.clone(),
+                s.model.clone(),
+                usage.input_tokens.to_string(),
+                usage.output_tokens.to_string(),
+                usage.cache_read_tokens.to_string(),
+                usage.cache_creation_tokens.to_string(),
+                format!("{:.4}", usage.estimated_cost),
+            ],
+        );
+    }
+
+    push_row(
+        &mut out,
+        [
+            "TOTAL".to_string(),
+            String::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+            input.to_string(),
+            output.to_string(),
+            cache_read.to_string(),
+            cache_creation.to_string(),
+            format!("{:.4}", cost),
+        ],
+    );
+    out
+}
+
+fn activity_date(timestamp: &str, tz: Tz) -> Option<NaiveDate> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .ok()
+        .map(|dt| dt.with_timezone(&tz).date_naive())
+}
+
+fn push_row(out: &mut String, fields: impl IntoIterator<Item = String>) {
+    let line: Vec<String> = fields.into_iter().map(|f| escape_field(&f)).collect();
+    out.push_str(&line.join(","));
+    out.push_str("\r\n");
+}
+
+/// RFC 4180 quoting: wrap fields containing separators, quotes or newlines and double any quotes.
+fn escape_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::CumulativeUsage;
+    use chrono_tz::Tz::UTC;
+
+    fn make_session(
+        id: &str,
+        project_path: &str,
+        last_activity_at: &str,
+        cost: f64,
+    ) -> AgentSessionSummary {
+        AgentSessionSummary {
+            session_id: id.into(),
+            project_path: project_path.into(),
+            project_name: project_path.rsplit('/').next().unwrap_or_default().into(),
+            model: "claude-sonnet-4-20250514".into(),
+            last_activity_at: last_activity_at.into(),
+            cumulative_usage: CumulativeUsage {
+                input_tokens: 100,
+                output_tokens: 50,
+                cache_read_tokens: 10,
+                cache_creation_tokens: 5,
+                estimated_cost: cost,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_escape_field() {
+        assert_eq!(escape_field("plain"), "plain");
+        assert_eq!(escape_field("/a,b/c"), "\"/a,b/c\"");
+        assert_eq!(escape_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn test_rows_and_totals() {
+        let sessions = vec![
+            make_session("s2", "/work/beta", "2025-03-02T10:00:00Z", 0.5),
+            make_session("s1", "/work/al,pha", "2025-03-01T10:00:00Z", 0.25),
+        ];
+        let csv = build_usage_csv(&sessions, date("2025-03-01"), date("2025-03-31"), UTC);
+        let lines: Vec<&str> = csv.trim_end().split("\r\n").collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("date,project,project_path,session_id"));
+        assert_eq!(
+            lines[1],
+            "2025-03-01,\"al,pha\",\"/work/al,pha\",s1,claude-sonnet-4-20250514,100,50,10,5,0.2500"
+        );
+        assert!(lines[2].starts_with("2025-03-02,beta,/work/beta,s2,"));
+        assert_eq!(lines[3], "TOTAL,,,,,200,100,20,10,0.7500");
+    }
+
+    #[test]
+    fn test_zoned_date_boundaries() {
+        let sessions = vec![
+            // 23:30 at -05:00 is already the next day in UTC.
+            make_session("late", "/work/p", "2025-03-31T23:30:00-05:00", 1.0),
+            make_session("edge", "/work/p", "2025-03-31T23:59:59Z", 1.0),
+            make_session("before", "/work/p", "2025-02-28T23:59:59Z", 1.0),
+            make_session("bad", "/work/p", "not a timestamp", 1.0),
+        ];
+        let csv = build_usage_csv(&sessions, date("2025-03-01"), date("2025-03-31"), UTC);
+        assert!(csv.contains(",edge,"));
+        assert!(!csv.contains(",late,"));
+        assert!(!csv.contains(",before,"));
+        assert!(!csv.contains(",bad,"));
+        assert!(csv.contains("TOTAL,,,,,100,50,10,5,1.0000"));
+
+        // In Tokyo the same range starts and ends nine hours earlier.
+        let csv = build_usage_csv(
+            &sessions,
+            date("2025-03-01"),
+            date("2025-03-31"),
+            chrono_tz::Tz::Asia__Tokyo,
+        );
+        assert!(!csv.contains(",edge,"));
+        assert!(!csv.contains(",late,"));
+        assert!(csv.contains("\r\n2025-03-01,p,/work/p,before,"));
+    }
+}
@@ -1,11 +1,30 @@
+use crate::server::connections::{ConnectionGuard, ConnectionStats};
+use crate::server::outbound::{OutboundQueue, Pushed, CLOSE_TOO_SLOW};
+use crate::server::protocol::{self, ClientCompat};
 use crate::session::manager::SessionManager;
+use crate::session::read_marks::{valid_client_id, ReadMark};
 use crate::types::{ClientEvent, ServerEvent};
-use axum::extract::ws::{Message, WebSocket};
+use axum::extract::ws::{CloseFrame, Message, WebSocket};
 use futures::{SinkExt, StreamExt};
-use std::collections::HashSet;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::sync::{broadcast, mpsc};
-use tracing::debug;
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+/// How long to wait for a client that stopped reading to take its close frame.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+/// Session events held for clients that are behind.
+pub const DEFAULT_BROADCAST_CAPACITY: usize = 256;
+/// Messages held for clients that are behind. Reading in a long log produces
+/// thousands at once.
+pub const DEFAULT_MESSAGE_CAPACITY: usize = 4096;
+/// A client that keeps falling behind the messages is sent its sessions again at
+/// most this often.
+const LAG_REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+/// Messages sent per session when a project is subscribed to, the most recent.
+const PROJECT_INIT_MESSAGES: usize = 100;
 
 /// Handle a single WebSocket connection.
 pub async fn handle_ws(
@@ -13,37 +32,69 @@ pub async fn handle_ws(
     session_manager: Arc<SessionManager>,
     broadcast_rx: broadcast::Receiver<ServerEvent>,
     message_rx: broadcast::Receiver<ServerEvent>,
+    connection: ConnectionGuard,
 ) {
+    let stats = connection.stats();
     let (mut ws_tx, mut ws_rx) = socket.split();
     let mut subscribed: HashSet<String> = HashSet::new();
+    let mut compat = ClientCompat::default();
+    // What this client has read, by session. Kept across reconnects once the client
+    // names itself in its hello.
+    let mut client_id: Option<String> = None;
+    let marks: Arc<Mutex<HashMap<String, ReadMark>>> = Arc::default();
 
-    // Send initial sessions list
-    let sessions = session_manager.get_sessions().await;
-    let init_event = ServerEvent::SessionsInit { sessions };
-    if let Ok(json) = serde_json::to_string(&init_event) {
-        if ws_tx.send(Message::Text(json.into())).await.is_err() {
-            return;
+    // Say hello, then send the initial sessions list. The receivers were subscribed
+    // before this snapshot was taken, so nothing falls between the two, but sessions
+    // discovered just before it may have their discovery on the way as well.
+    let sessions = session_manager.get_sessions(false).await;
+    let mut in_snapshot: HashSet<String> = sessions.iter().map(|s| s.session_id.clone()).collect();
+    let init = ServerEvent::SessionsInit {
+        sessions,
+        unread_counts: None,
+    };
+    for event in [protocol::hello(), init] {
+        if let Ok(json) = serde_json::to_string(&event) {
+            if ws_tx.send(Message::Text(json.into())).await.is_err() {
+                return;
+            }
+            stats.record_sent();
         }
     }
 
-    // Channel for messages to send to this client
-    let (client_tx, mut client_rx) = mpsc::unbounded_channel::<String>();
+    // Events waiting to be sent to this client
+    let queue = Arc::new(OutboundQueue::new());
+
+    let (sub_update_tx, mut sub_update_rx) = mpsc::unbounded_channel::<SubUpdate>();
 
     // Task: forward broadcast events to client
-    let client_tx_broadcast = client_tx.clone();
+    let queue_broadcast = queue.clone();
+    let stats_broadcast = stats.clone();
+    let manager_broadcast = session_manager.clone();
+    let marks_broadcast = marks.clone();
+    // Tells the message task where sessions belong, for project subscriptions. Weak,
+    // so the message task still stops once the connection is gone.
+    let membership_tx = sub_update_tx.downgrade();
     let mut broadcast_rx = broadcast_rx;
     tokio::spawn(async move {
         loop {
             match broadcast_rx.recv().await {
                 Ok(event) => {
-                    if let Ok(json) = serde_json::to_string(&event) {
-                        if client_tx_broadcast.send(json).is_err() {
-                            break;
-                        }
+                    if let (Some(update), Some(tx)) = (membership(&event), membership_tx.upgrade())
+                    {
+                        let _ = tx.send(update);
+                    }
+                    if already_sent(&mut in_snapshot, &event) {
+                        continue;
+                    }
+                    let event =
+                        with_unread_count(&manager_broadcast, &marks_broadcast, event).await;
+                    if !enqueue(&queue_broadcast, &stats_broadcast, event) {
+                        break;
                     }
                 }
                 Err(broadcast::error::RecvError::Lagged(n)) => {
                     debug!("Broadcast lagged by {} messages", n);
+                    stats_broadcast.record_lag(n);
                 }
                 Err(broadcast::error::RecvError::Closed) => break,
             }
@@ -51,12 +102,18 @@ pub async fn handle_ws(
     });
 
     // Task: forward message events (filtered by subscription) to client
-    let client_tx_message = client_tx.clone();
+    let queue_message = queue.clone();
+    let stats_message = stats.clone();
+    let manager_message = session_manager.clone();
     let mut message_rx = message_rx;
-    let (sub_update_tx, mut sub_update_rx) = mpsc::unbounded_channel::<SubUpdate>();
 
     tokio::spawn(async move {
         let mut local_subs: HashSet<String> = HashSet::new();
+        // Subscribed projects and their sessions.
+        let mut projects: HashMap<String, HashSet<String>> = HashMap::new();
+        // After missing messages, the subscribed sessions are sent again.
+        let mut last_refresh: Option<Instant> = None;
+        let mut refresh_at: Option<Instant> = None;
 
         loop {
             tokio::select! {
@@ -64,29 +121,63 @@ pub async fn handle_ws(
                     match update {
                         Some(SubUpdate::Add(id)) => { local_subs.insert(id); }
                         Some(SubUpdate::Remove(id)) => { local_subs.remove(&id); }
+                        Some(SubUpdate::AddProject { project_path, sessions }) => {
+                            projects.insert(project_path, sessions);
+                        }
+                        Some(SubUpdate::RemoveProject(project_path)) => { projects.remove(&project_path); }
+                        Some(SubUpdate::Session { session_id, project_path }) => {
+                            let mut joined = false;
+                            for (project, members) in projects.iter_mut() {
+                                if project_path.as_deref() == Some(project.as_str()) {
+                                    joined |= members.insert(session_id.clone());
+                                } else {
+                                    members.remove(&session_id);
+                                }
+                            }
+                            // Its first messages may have gone by before it joined.
+                            if joined && !local_subs.contains(&session_id) {
+                                let init = messages_init(&manager_message, &session_id, Some(PROJECT_INIT_MESSAGES)).await;
+                                if init.is_some_and(|init| !enqueue(&queue_message, &stats_message, init)) {
+                                    break;
+                                }
+                            }
+                        }
                         None => break,
                     }
                 }
+                _ = tokio::time::sleep_until(refresh_at.unwrap_or_else(Instant::now)), if refresh_at.is_some() => {
+                    refresh_at = None;
+                    last_refresh = Some(Instant::now());
+                    if !resend_messages(&manager_message, &local_subs, &projects, &queue_message, &stats_message).await {
+                        break;
+                    }
+                }
                 result = message_rx.recv() => {
                     match result {
                         Ok(event) => {
                             // Check if this message is for a subscribed session
                             let session_id = match &event {
-                                ServerEvent::NewMessage { session_id, .. } => Some(session_id.as_str()),
+                                ServerEvent::NewMessage { session_id, .. }
+                                | ServerEvent::MessagesAppended { session_id, .. } => Some(session_id.as_str()),
                                 _ => None,
                             };
                             if let Some(sid) = session_id {
-                                if local_subs.contains(sid) {
-                                    if let Ok(json) = serde_json::to_string(&event) {
-                                        if client_tx_message.send(json).is_err() {
-                                            break;
-                                        }
-                                    }
+                                let subscribed = local_subs.contains(sid)
+                                    || projects.values().any(|members| members.contains(sid));
+                                if subscribed
+                                    && !enqueue(&queue_message, &stats_message, event)
+                                {
+                                    break;
                                 }
                             }
                         }
                         Err(broadcast::error::RecvError::Lagged(n)) => {
                             debug!("Message broadcast lagged by {} messages", n);
+                            stats_message.record_lag(n);
+                            if (!local_subs.is_empty() || !projects.is_empty()) && refresh_at.is_none() {
+                                let now = Instant::now();
+                                refresh_at = Some(last_refresh.map_or(now, |at| (at + LAG_REFRESH_INTERVAL).max(now)));
+                            }
                         }
                         Err(broadcast::error::RecvError::Closed) => break,
                     }
@@ -95,43 +186,113 @@ pub async fn handle_ws(
         }
     });
 
-    // Main loop: read from client + write from client_rx
+    // Main loop: read from client + write from the queue
     loop {
         tokio::select! {
             // Outgoing messages
-            Some(msg) = client_rx.recv() => {
-                if ws_tx.send(Message::Text(msg.into())).await.is_err() {
+            event = queue.pop() => {
+                let Some(event) = event else {
+                    warn!("WebSocket client is not reading; closing the connection");
+                    let close = Message::Close(Some(CloseFrame {
+                        code: CLOSE_TOO_SLOW,
+                        reason: "client too slow".into(),
+                    }));
+                    let _ = tokio::time::timeout(CLOSE_TIMEOUT, ws_tx.send(close)).await;
+                    break;
+                };
+                let Some(event) = compat.adapt(event) else {
+                    continue;
+                };
+                let Ok(json) = serde_json::to_string(&event) else {
+                    continue;
+                };
+                if ws_tx.send(Message::Text(json.into())).await.is_err() {
                     break;
                 }
+                stats.record_sent();
             }
             // Incoming messages from client
             result = ws_rx.next() => {
+                if let Some(Ok(_)) = &result {
+                    stats.record_received();
+                }
                 match result {
                     Some(Ok(Message::Text(text))) => {
-                        if let Ok(event) = serde_json::from_str::<ClientEvent>(&text) {
-                            match event {
+                        match serde_json::from_str::<ClientEvent>(&text) {
+                            Err(e) => {
+                                debug!("Rejecting WebSocket frame: {}", e);
+                                enqueue(&queue, stats, client_error(&text, e.to_string()));
+                            }
+                            Ok(event) => match event {
+                                ClientEvent::Hello { protocol_version, capabilities, client_id: id } => {
+                                    debug!("WebSocket client speaks protocol {}", protocol_version);
+                                    compat = ClientCompat::from_hello(protocol_version, capabilities);
+                                    if let Some(id) = id.filter(|id| valid_client_id(id)) {
+                                        let saved = session_manager.read_marks(&id).await;
+                                        client_id = Some(id);
+                                        if !saved.is_empty() {
+                                            // The snapshot went out before the client said who it
+                                            // is; send it again with what it has read.
+                                            let all = {
+                                                let mut marks = marks.lock().unwrap();
+                                                for (session_id, mark) in saved {
+                                                    marks.entry(session_id).or_insert(mark);
+                                                }
+                                                marks.clone()
+                                            };
+                                            let init = ServerEvent::SessionsInit {
+                                                sessions: session_manager.get_sessions(false).await,
+                                                unread_counts: Some(session_manager.unread_counts(&all).await),
+                                            };
+                                            enqueue(&queue, stats, init);
+                                        }
+                                    }
+                                }
                                 ClientEvent::Subscribe { session_id } => {
                                     subscribed.insert(session_id.clone());
+                                    stats.subscribe(&session_id);
                                     let _ = sub_update_tx.send(SubUpdate::Add(session_id.clone()));
 
                                     // Send existing messages for this session
-                                    if let Some(messages) = session_manager.get_session_messages(&session_id).await {
-                                        if !messages.is_empty() {
-                                            let init = ServerEvent::MessagesInit {
-                                                session_id: session_id.clone(),
-                                                messages,
-                                            };
-                                            if let Ok(json) = serde_json::to_string(&init) {
-                                                let _ = client_tx.send(json);
-                                            }
-                                        }
+                                    if let Some(init) = messages_init(&session_manager, &session_id, None).await {
+                                        enqueue(&queue, stats, init);
                                     }
                                 }
                                 ClientEvent::Unsubscribe { session_id } => {
                                     subscribed.remove(&session_id);
+                                    stats.unsubscribe(&session_id);
                                     let _ = sub_update_tx.send(SubUpdate::Remove(session_id));
                                 }
-                            }
+                                ClientEvent::SubscribeProject { project_path } => {
+                                    stats.subscribe_project(&project_path);
+                                    let sessions = session_manager.project_session_ids(&project_path).await;
+                                    let _ = sub_update_tx.send(SubUpdate::AddProject {
+                                        project_path,
+                                        sessions: sessions.iter().cloned().collect(),
+                                    });
+                                    for session_id in &sessions {
+                                        if let Some(init) = messages_init(&session_manager, session_id, Some(PROJECT_INIT_MESSAGES)).await {
+                                            enqueue(&queue, stats, init);
+                                        }
+                                    }
+                                }
+                                ClientEvent::UnsubscribeProject { project_path } => {
+                                    stats.unsubscribe_project(&project_path);
+                                    let _ = sub_update_tx.send(SubUpdate::RemoveProject(project_path));
+                                }
+                                ClientEvent::AckRead { session_id, message_id } => {
+                                    match session_manager.mark_read(client_id.as_deref(), &session_id, &message_id).await {
+                                        Some(mark) => {
+                                            marks.lock().unwrap().insert(session_id, mark);
+                                        }
+                                        None => debug!("Ignoring ack of unknown message {} in {}", message_id, session_id),
+                                    }
+                                }
+                                ClientEvent::Unknown => {
+                                    debug!("Rejecting WebSocket frame of unknown type");
+                                    enqueue(&queue, stats, client_error(&text, "unknown event type".into()));
+                                }
+                            },
                         }
                     }
                     Some(Ok(Message::Close(_))) | None => break,
@@ -141,10 +302,149 @@ pub async fn handle_ws(
         }
     }
 
+    queue.close();
     debug!("WebSocket connection closed");
 }
 
+/// Queue an event for the client, counting it if it had to be dropped. False once
+/// the connection is gone.
+fn enqueue(queue: &OutboundQueue, stats: &ConnectionStats, event: ServerEvent) -> bool {
+    match queue.push(event) {
+        Pushed::Queued => true,
+        Pushed::Dropped => {
+            stats.record_dropped();
+            true
+        }
+        Pushed::Closed => false,
+    }
+}
+
+/// A session's messages for a client that subscribes to it, the most recent `limit`
+/// if given. None if there are none.
+async fn messages_init(
+    session_manager: &SessionManager,
+    session_id: &str,
+    limit: Option<usize>,
+) -> Option<ServerEvent> {
+    let mut messages = session_manager.get_session_messages(session_id).await?;
+    if messages.is_empty() {
+        return None;
+    }
+    if let Some(limit) = limit {
+        messages.drain(..messages.len().saturating_sub(limit));
+    }
+    Some(ServerEvent::MessagesInit {
+        session_id: session_id.to_string(),
+        messages,
+    })
+}
+
+/// Send the client its subscribed sessions' messages again, after it missed some.
+/// False once the connection is gone.
+async fn resend_messages(
+    session_manager: &SessionManager,
+    sessions: &HashSet<String>,
+    projects: &HashMap<String, HashSet<String>>,
+    queue: &OutboundQueue,
+    stats: &ConnectionStats,
+) -> bool {
+    let by_project: HashSet<&String> = projects
+        .values()
+        .flatten()
+        .filter(|id| !sessions.contains(*id))
+        .collect();
+    let all = sessions.iter().map(|id| (id, None)).chain(
+        by_project
+            .into_iter()
+            .map(|id| (id, Some(PROJECT_INIT_MESSAGES))),
+    );
+    for (session_id, limit) in all {
+        let Some(init) = messages_init(session_manager, session_id, limit).await else {
+            continue;
+        };
+        if !enqueue(queue, stats, init) {
+            return false;
+        }
+    }
+    true
+}
+
+/// The `server:error` answer to a frame the client sent, `text`.
+fn client_error(text: &str, message: String) -> ServerEvent {
+    let event_type = serde_json::from_str::<serde_json::Value>(text)
+        .ok()
+        .and_then(|frame| frame.get("type")?.as_str().map(str::to_string));
+    ServerEvent::Error {
+        message,
+        event_type,
+    }
+}
+
+/// Where a session belongs as of `event`, for following projects: its project, or
+/// None once it's gone.
+fn membership(event: &ServerEvent) -> Option<SubUpdate> {
+    let (session_id, project_path) = match event {
+        ServerEvent::SessionDiscovered { session }
+        | ServerEvent::SessionUpdated { session }
+        | ServerEvent::StateChanged { session, .. } => {
+            (&session.session_id, Some(session.project_path.clone()))
+        }
+        ServerEvent::SessionRemoved { session_id } => (session_id, None),
+        _ => return None,
+    };
+    Some(SubUpdate::Session {
+        session_id: session_id.clone(),
+        project_path,
+    })
+}
+
+/// A state change with this client's unread count for the session, if it has read
+/// any of it.
+async fn with_unread_count(
+    session_manager: &SessionManager,
+    marks: &Mutex<HashMap<String, ReadMark>>,
+    mut event: ServerEvent,
+) -> ServerEvent {
+    if let ServerEvent::StateChanged {
+        session_id,
+        unread_count,
+        ..
+    } = &mut event
+    {
+        let mark = marks.lock().unwrap().get(session_id.as_str()).cloned();
+        if let Some(mark) = mark {
+            *unread_count = session_manager.unread_count(session_id, &mark).await;
+        }
+    }
+    event
+}
+
+/// Whether this is the discovery of a session the client got in its initial
+/// snapshot. Each is skipped once; after the session is removed, a new discovery
+/// goes through.
+fn already_sent(in_snapshot: &mut HashSet<String>, event: &ServerEvent) -> bool {
+    match event {
+        ServerEvent::SessionDiscovered { session } => in_snapshot.remove(&session.session_id),
+        ServerEvent::SessionRemoved { session_id } => {
+            in_snapshot.remove(session_id);
+            false
+        }
+        _ => false,
+    }
+}
+
 enum SubUpdate {
     Add(String),
     Remove(String),
+    /// Follow a project, whose sessions are currently `sessions`.
+    AddProject {
+        project_path: String,
+        sessions: HashSet<String>,
+    },
+    RemoveProject(String),
+    /// A session was seen in a project, or removed.
+    Session {
+        session_id: String,
+        project_path: Option<String>,
+    },
 }
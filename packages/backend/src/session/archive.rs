@@ -0,0 +1,283 @@
+//! Sessions that have been stopped for a while, moved out of memory into
+//! zstd-compressed files in the data directory. A small index lists them without
+//! opening every file; `GET /api/archive/{id}` reads one back.
+
+use crate::providers::claude_code::timestamps::parse_timestamp;
+use crate::types::{ArchivedSession, ArchivedSessionInfo};
+use chrono::NaiveDate;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::{info, warn};
+
+pub const ARCHIVE_DIR: &str = "archive";
+pub const DEFAULT_AFTER_HOURS: u64 = 24;
+/// Longest archive delay accepted: ten years.
+pub const MAX_AFTER_HOURS: u64 = 10 * 365 * 24;
+const INDEX_FILE: &str = "index.json";
+const EXTENSION: &str = ".json.zst";
+const COMPRESSION_LEVEL: i32 = 3;
+
+#[derive(Debug, Clone)]
+pub struct ArchiveConfig {
+    pub dir: PathBuf,
+    /// How long a session stays stopped before it's archived.
+    pub after: Duration,
+}
+
+/// Narrows `GET /api/archive`. Dates are the UTC day of a session's last activity.
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveFilter {
+    /// Matches the project's path or name.
+    pub project: Option<String>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+impl ArchiveFilter {
+    fn matches(&self, info: &ArchivedSessionInfo) -> bool {
+        if let Some(project) = &self.project {
+            if info.project_path != *project && info.project_name != *project {
+                return false;
+            }
+        }
+        if self.from.is_none() && self.to.is_none() {
+            return true;
+        }
+        let Some(day) = parse_timestamp(&info.last_activity_at).map(|at| at.date_naive()) else {
+            return false;
+        };
+        self.from.is_none_or(|from| day >= from) && self.to.is_none_or(|to| day <= to)
+    }
+}
+
+pub struct ArchiveStore {
+    config: ArchiveConfig,
+    index: Mutex<Vec<ArchivedSessionInfo>>,
+    /// One archive writes the index at a time.
+    writing: tokio::sync::Mutex<()>,
+}
+
+impl ArchiveStore {
+    /// Open the archive in `config.dir`, reading its index.
+    pub fn load(config: ArchiveConfig) -> Self {
+        let path = config.dir.join(INDEX_FILE);
+        let index = match std::fs::read_to_string(&path) {
+            Ok(text) => match serde_json::from_str::<Vec<ArchivedSessionInfo>>(&text) {
+                Ok(index) => {
+                    info!(
+                        "[Archive] {} archived sessions in {}",
+                        index.len(),
+                        config.dir.display()
+                    );
+                    index
+                }
+                Err(e) => {
+                    warn!("[Archive] Ignoring unreadable {}: {}", path.display(), e);
+                    Vec::new()
+                }
+            },
+            Err(_) => Vec::new(),
+        };
+        Self {
+            config,
+            index: Mutex::new(index),
+            writing: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    pub fn after(&self) -> Duration {
+        self.config.after
+    }
+
+    /// Write `session` to its file and list it. Archiving a session again replaces
+    /// it. Returns None for ids that can't name a file.
+    pub async fn archive(
+        &self,
+        session: &ArchivedSession,
+    ) -> std::io::Result<Option<ArchivedSessionInfo>> {
+        let summary = &session.detail.summary;
+        if !is_safe_id(&summary.session_id) {
+            return Ok(None);
+        }
+        let json = serde_json::to_vec(session)?;
+        let compressed = zstd::encode_all(json.as_slice(), COMPRESSION_LEVEL)?;
+        let info = ArchivedSessionInfo {
+            session_id: summary.session_id.clone(),
+            project_path: summary.project_path.clone(),
+            project_name: summary.project_name.clone(),
+            title: summary.title.clone(),
+            initial_task: summary.initial_task.clone(),
+            model: summary.model.clone(),
+            started_at: summary.started_at.clone(),
+            last_activity_at: summary.last_activity_at.clone(),
+            archived_at: session.archived_at.clone(),
+            compressed_bytes: compressed.len() as u64,
+        };
+
+        let _writing = self.writing.lock().await;
+        tokio::fs::create_dir_all(&self.config.dir).await?;
+        write_atomic(&self.file(&info.session_id), &compressed).await?;
+        let index = {
+            let mut index = self.index.lock().unwrap();
+            index.retain(|i| i.session_id != info.session_id);
+            index.push(info.clone());
+            serde_json::to_vec(&*index)?
+        };
+        write_atomic(&self.config.dir.join(INDEX_FILE), &index).await?;
+        Ok(Some(info))
+    }
+
+    /// Archived sessions matching `filter`, most recently active first.
+    pub fn list(&self, filter: &ArchiveFilter) -> Vec<ArchivedSessionInfo> {
+        let mut sessions: Vec<ArchivedSessionInfo> = self
+            .index
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|info| filter.matches(info))
+            .cloned()
+            .collect();
+        sessions.sort_by(|a, b| b.last_activity_at.cmp(&a.last_activity_at));
+        sessions
+    }
+
+    pub fn contains(&self, session_id: &str) -> bool {
+        self.index
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|info| info.session_id == session_id)
+    }
+
+    pub async fn get(&self, session_id: &str) -> Option<ArchivedSession> {
+        if !is_safe_id(session_id) || !self.contains(session_id) {
+            return None;
+        }
+        let compressed = tokio::fs::read(self.file(session_id)).await.ok()?;
+        let json = zstd::decode_all(compressed.as_slice()).ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+
+    fn file(&self, session_id: &str) -> PathBuf {
+        self.config.dir.join(format!("{}{}", session_id, EXTENSION))
+    }
+}
+
+/// Write to a temp file and rename so a crash never leaves a truncated file.
+async fn write_atomic(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    tokio::fs::write(&tmp, bytes).await?;
+    tokio::fs::rename(&tmp, path).await
+}
+
+/// Session ids name files; anything that could leave the archive directory
+/// (relayed ids contain `:`, a request could contain `..`) is refused.
+fn is_safe_id(session_id: &str) -> bool {
+    !session_id.is_empty()
+        && session_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{AgentMessage, AgentSessionDetail, AgentSessionSummary, UsageTimeline};
+    use std::sync::Arc;
+
+    fn archived(session_id: &str, project: &str, last_activity_at: &str) -> ArchivedSession {
+        ArchivedSession {
+            archived_at: "2025-03-10T00:00:00.000Z".into(),
+            detail: AgentSessionDetail {
+                summary: AgentSessionSummary {
+                    session_id: session_id.into(),
+                    project_path: format!("/work/{}", project),
+                    project_name: project.into(),
+                    initial_task: "fix the build".into(),
+                    last_activity_at: last_activity_at.into(),
+                    ..Default::default()
+                },
+                messages: vec![Arc::new(
+                    serde_json::from_value::<AgentMessage>(serde_json::json!({
+                        "id": "m1",
+                        "sessionId": session_id,
+                        "timestamp": last_activity_at,
+                        "role": "user",
+                        "type": "text",
+                        "content": "fix the build",
+                    }))
+                    .unwrap(),
+                )],
+                usage_points: Vec::new(),
+                model_history: Vec::new(),
+                watcher: None,
+                settings: None,
+            },
+            timeline: None,
+            usage: Some(UsageTimeline {
+                session_id: session_id.into(),
+                points: Vec::new(),
+            }),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_archive_list_get_and_reload() {
+        let root =
+            std::env::temp_dir().join(format!("agents-dashboard-archive-{}", uuid::Uuid::new_v4()));
+        let config = ArchiveConfig {
+            dir: root.join(ARCHIVE_DIR),
+            after: Duration::from_secs(60),
+        };
+        let store = ArchiveStore::load(config.clone());
+        let info = store
+            .archive(&archived("s1", "api", "2025-03-01T10:00:00.000Z"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(info.compressed_bytes > 0);
+        store
+            .archive(&archived("s2", "web", "2025-03-05T10:00:00.000Z"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(store
+            .archive(&archived("../s3", "web", "2025-03-05T10:00:00.000Z"))
+            .await
+            .unwrap()
+            .is_none());
+
+        let ids = |sessions: Vec<ArchivedSessionInfo>| -> Vec<String> {
+            sessions.into_iter().map(|s| s.session_id).collect()
+        };
+        assert_eq!(ids(store.list(&ArchiveFilter::default())), ["s2", "s1"]);
+        let web = ArchiveFilter {
+            project: Some("/work/web".into()),
+            ..Default::default()
+        };
+        assert_eq!(ids(store.list(&web)), ["s2"]);
+        let early = ArchiveFilter {
+            to: NaiveDate::from_ymd_opt(2025, 3, 1),
+            ..Default::default()
+        };
+        assert_eq!(ids(store.list(&early)), ["s1"]);
+
+        let session = store.get("s1").await.unwrap();
+        assert_eq!(&*session.detail.messages[0].content, "fix the build");
+        assert_eq!(session.usage.unwrap().session_id, "s1");
+        assert!(store.get("s3").await.is_none());
+        assert!(store.get("../s1").await.is_none());
+
+        // Archiving again replaces the entry; the index survives a restart.
+        store
+            .archive(&archived("s1", "api", "2025-03-07T10:00:00.000Z"))
+            .await
+            .unwrap();
+        let reloaded = ArchiveStore::load(config);
+        assert_eq!(ids(reloaded.list(&ArchiveFilter::default())), ["s1", "s2"]);
+        assert!(reloaded.get("s2").await.is_some());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
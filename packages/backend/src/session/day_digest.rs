@@ -0,0 +1,263 @@
+//! One project's day, from the per-project tallies and whatever the provider still
+//! tracks. Tallies outlive sessions but only keep usage; tracked sessions add their
+//! state, active time, edited files and latest commit.
+
+use crate::days;
+use crate::providers::claude_code::timestamps::format_timestamp;
+use crate::providers::claude_code::DaySample;
+use crate::session::project_usage::SessionTally;
+use crate::session::usage_report::{date, parse_time};
+use crate::types::{AgentStateType, DayDigest, DigestSession};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use std::collections::HashSet;
+
+/// Tracked sessions take precedence over their tallies, which may be a little behind.
+/// Timestamps are placed on days in `tz`.
+pub fn build(
+    project_path: &str,
+    project_name: &str,
+    day: i64,
+    tallies: &[(String, SessionTally)],
+    live: &[DaySample],
+    now: DateTime<Utc>,
+    tz: Tz,
+) -> DayDigest {
+    let on_day = |timestamp: &str| {
+        parse_time(timestamp).is_some_and(|at| days::day_of(at.timestamp_millis(), tz) == day)
+    };
+    let mut digest = DayDigest {
+        project_path: project_path.to_string(),
+        project_name: project_name.to_string(),
+        date: date(day),
+        generated_at: format_timestamp(now),
+        sessions_started: 0,
+        sessions_ended: 0,
+        total_cost: 0.0,
+        total_tokens: 0,
+        active_ms: 0,
+        files: Vec::new(),
+        commits: None,
+        sessions: Vec::new(),
+    };
+    for sample in live {
+        let stopped = sample.state == AgentStateType::Stopped;
+        digest.sessions.push(DigestSession {
+            session_id: sample.session_id.clone(),
+            title: sample.title.clone().filter(|t| !t.is_empty()),
+            model: sample.model.clone(),
+            state: Some(sample.state),
+            started_at: sample.started_at.clone(),
+            started_on_day: on_day(&sample.started_at),
+            ended_at: (stopped && on_day(&sample.last_activity_at))
+                .then(|| sample.last_activity_at.clone()),
+            cost: sample.cost,
+            tokens: sample.tokens,
+            active_ms: Some(sample.active_ms),
+            files: sample.files.clone(),
+            top_tools: sample.top_tools.clone(),
+        });
+        if let Some(git) = &sample.git {
+            let commits = digest.commits.get_or_insert_with(Vec::new);
+            let made_that_day = git.last_commit.as_ref().filter(|c| on_day(&c.timestamp));
+            if let Some(commit) =
+                made_that_day.filter(|c| !commits.iter().any(|seen| seen.hash == c.hash))
+            {
+                commits.push(commit.clone());
+            }
+        }
+    }
+
+    let tracked: HashSet<&str> = live.iter().map(|s| s.session_id.as_str()).collect();
+    for (session_id, tally) in tallies {
+        if tracked.contains(session_id.as_str()) {
+            continue;
+        }
+        let cost = tally.daily_cost.get(&day).copied();
+        let tokens = tally.daily_tokens.get(&day).copied();
+        if cost.is_none()
+            && tokens.is_none()
+            && !on_day(&tally.started_at)
+            && !on_day(&tally.last_activity_at)
+        {
+            continue;
+        }
+        digest.sessions.push(DigestSession {
+            session_id: session_id.clone(),
+            title: Some(tally.title.clone()).filter(|t| !t.is_empty()),
+            model: tally.model.clone(),
+            state: None,
+            started_at: tally.started_at.clone(),
+            started_on_day: on_day(&tally.started_at),
+            ended_at: on_day(&tally.last_activity_at).then(|| tally.last_activity_at.clone()),
+            cost: cost.unwrap_or(0.0),
+            tokens: tokens.unwrap_or(0),
+            active_ms: None,
+            files: None,
+            top_tools: tally.top_tools.clone(),
+        });
+    }
+
+    // Sessions recorded before start times were kept sort first.
+    digest.sessions.sort_by(|a, b| {
+        a.started_at
+            .cmp(&b.started_at)
+            .then_with(|| a.session_id.cmp(&b.session_id))
+    });
+    for session in &digest.sessions {
+        digest.sessions_started += session.started_on_day as usize;
+        digest.sessions_ended += session.ended_at.is_some() as usize;
+        digest.total_cost += session.cost;
+        digest.total_tokens += session.tokens;
+        digest.active_ms += session.active_ms.unwrap_or(0);
+        for file in session.files.iter().flatten() {
+            if !digest.files.contains(file) {
+                digest.files.push(file.clone());
+            }
+        }
+    }
+    if let Some(commits) = &mut digest.commits {
+        commits.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{GitCommitInfo, GitStatus, ToolCount};
+    use chrono_tz::Tz::UTC;
+    use std::collections::BTreeMap;
+
+    const DAY: &str = "2025-01-14";
+
+    fn now() -> DateTime<Utc> {
+        "2025-01-15T12:00:00Z".parse().unwrap()
+    }
+
+    fn day() -> i64 {
+        days::day_index(DAY.parse().unwrap())
+    }
+
+    fn sample(id: &str, state: AgentStateType, started_at: &str) -> DaySample {
+        DaySample {
+            session_id: id.to_string(),
+            project_name: "api".to_string(),
+            title: Some("Add pagination".to_string()),
+            model: "claude-sonnet-4-5".to_string(),
+            state,
+            started_at: started_at.to_string(),
+            last_activity_at: "2025-01-14T11:00:00Z".to_string(),
+            cost: 1.5,
+            tokens: 1_000,
+            active_ms: 60_000,
+            files: Some(vec!["src/lib.rs".to_string(), "src/api.rs".to_string()]),
+            git: None,
+            top_tools: vec![ToolCount {
+                name: "Edit".to_string(),
+                count: 4,
+            }],
+        }
+    }
+
+    fn commit(hash: &str, timestamp: &str) -> GitStatus {
+        GitStatus {
+            branch: "main".to_string(),
+            last_commit: Some(GitCommitInfo {
+                hash: hash.to_string(),
+                subject: "Paginate".to_string(),
+                author: "dev".to_string(),
+                timestamp: timestamp.to_string(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn tally(started_at: &str, last_activity_at: &str, cost: &[(i64, f64)]) -> SessionTally {
+        SessionTally {
+            daily_cost: cost.iter().copied().collect(),
+            daily_tokens: BTreeMap::new(),
+            model: "claude-opus-4-1".to_string(),
+            started_at: started_at.to_string(),
+            last_activity_at: last_activity_at.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_tracked_and_untracked_sessions() {
+        let mut running = sample("s1", AgentStateType::Running, "2025-01-14T09:00:00Z");
+        running.git = Some(commit("abc1234", "2025-01-14T10:30:00Z"));
+        let mut stopped = sample("s2", AgentStateType::Stopped, "2025-01-13T22:00:00Z");
+        stopped.files = Some(vec!["src/api.rs".to_string(), "README.md".to_string()]);
+        // The same repository, seen from another session.
+        stopped.git = Some(commit("abc1234", "2025-01-14T10:30:00Z"));
+        let tallies = vec![
+            // Stale tally of a tracked session: ignored.
+            (
+                "s1".to_string(),
+                tally("2025-01-14T09:00:00Z", "", &[(day(), 99.0)]),
+            ),
+            (
+                "s3".to_string(),
+                tally(
+                    "2025-01-14T08:00:00Z",
+                    "2025-01-14T08:30:00Z",
+                    &[(day(), 0.25)],
+                ),
+            ),
+            // Another day.
+            (
+                "s4".to_string(),
+                tally(
+                    "2025-01-10T08:00:00Z",
+                    "2025-01-10T08:30:00Z",
+                    &[(day() - 4, 3.0)],
+                ),
+            ),
+        ];
+
+        let digest = build(
+            "/work/api",
+            "api",
+            day(),
+            &tallies,
+            &[running, stopped],
+            now(),
+            UTC,
+        );
+        assert_eq!(digest.date, DAY);
+        let ids: Vec<&str> = digest
+            .sessions
+            .iter()
+            .map(|s| s.session_id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["s2", "s3", "s1"]);
+        assert_eq!(digest.sessions_started, 2);
+        // s2 stopped and s3 is no longer tracked.
+        assert_eq!(digest.sessions_ended, 2);
+        assert!((digest.total_cost - 3.25).abs() < 1e-9);
+        assert_eq!(digest.active_ms, 120_000);
+        assert_eq!(digest.files, vec!["src/api.rs", "README.md", "src/lib.rs"]);
+        assert_eq!(digest.commits.as_ref().unwrap().len(), 1);
+
+        let untracked = &digest.sessions[1];
+        assert_eq!(untracked.state, None);
+        assert_eq!(untracked.active_ms, None);
+        assert_eq!(untracked.files, None);
+        assert_eq!(untracked.ended_at.as_deref(), Some("2025-01-14T08:30:00Z"));
+    }
+
+    #[test]
+    fn test_missing_git_and_old_commits() {
+        let digest = build("/work/api", "api", day(), &[], &[], now(), UTC);
+        assert!(digest.sessions.is_empty());
+        assert_eq!(digest.commits, None);
+
+        let mut session = sample("s1", AgentStateType::Idle, "2025-01-14T09:00:00Z");
+        session.git = Some(commit("abc1234", "2025-01-12T10:30:00Z"));
+        let digest = build("/work/api", "api", day(), &[], &[session], now(), UTC);
+        assert_eq!(digest.commits, Some(Vec::new()));
+        assert_eq!(digest.sessions[0].ended_at, None);
+    }
+}
@@ -0,0 +1,335 @@
+//! Diffs captured when a session changes state (by default when a turn ends), kept
+//! gzipped in the data directory so what each turn produced can be looked at after
+//! it was committed or reverted.
+
+use crate::providers::claude_code::git_diff::{fetch_git_diff, DiffError, DiffOptions};
+use crate::types::{AgentStateType, DiffSnapshot, DiffSnapshotInfo};
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::debug;
+
+pub const SNAPSHOTS_DIR: &str = "diff-snapshots";
+pub const DEFAULT_MAX_COUNT: usize = 500;
+pub const DEFAULT_MAX_TOTAL_BYTES: u64 = 100 * 1024 * 1024;
+const EXTENSION: &str = ".json.gz";
+
+#[derive(Debug, Clone)]
+pub struct DiffSnapshotConfig {
+    /// Holds a directory of snapshots per session.
+    pub dir: PathBuf,
+    /// States whose start triggers a snapshot.
+    pub states: Vec<AgentStateType>,
+    /// Oldest snapshots (across all sessions) are deleted beyond this many...
+    pub max_count: usize,
+    /// ...or this many bytes on disk.
+    pub max_total_bytes: u64,
+    /// Diffs larger than this aren't stored.
+    pub max_diff_bytes: u64,
+}
+
+pub struct DiffSnapshotStore {
+    config: DiffSnapshotConfig,
+    /// Hash of each session's last stored patch, so turns that changed nothing
+    /// aren't stored again.
+    last_patch: Mutex<HashMap<String, u64>>,
+    /// One capture writes and prunes at a time.
+    writing: tokio::sync::Mutex<()>,
+}
+
+impl DiffSnapshotStore {
+    pub fn new(config: DiffSnapshotConfig) -> Self {
+        Self {
+            config,
+            last_patch: Mutex::new(HashMap::new()),
+            writing: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    pub fn wants(&self, state: AgentStateType) -> bool {
+        self.config.states.contains(&state)
+    }
+
+    /// Store the diff of `working_directory` as it is now. Returns None when there
+    /// was nothing to store: no repository, no changes, the same changes as the last
+    /// snapshot, or a diff over the size cap.
+    pub async fn capture(
+        &self,
+        session_id: &str,
+        working_directory: &str,
+        state: AgentStateType,
+        at: DateTime<Utc>,
+    ) -> std::io::Result<Option<DiffSnapshotInfo>> {
+        if !is_safe_id(session_id) {
+            return Ok(None);
+        }
+        let options = DiffOptions::default();
+        let diff =
+            match fetch_git_diff(working_directory, &options, self.config.max_diff_bytes).await {
+                Ok(diff) => diff,
+                Err(DiffError::NotARepository) => return Ok(None),
+                Err(DiffError::Git(e)) => return Err(std::io::Error::other(e)),
+            };
+        if diff.truncated {
+            debug!(
+                "[DiffSnapshots] {}: diff over {} bytes, not stored",
+                session_id, self.config.max_diff_bytes
+            );
+            return Ok(None);
+        }
+        if diff.patch.is_empty() {
+            return Ok(None);
+        }
+        let mut hasher = DefaultHasher::new();
+        diff.patch.hash(&mut hasher);
+        let hash = hasher.finish();
+        if self.last_patch.lock().unwrap().get(session_id) == Some(&hash) {
+            return Ok(None);
+        }
+
+        let snapshot = DiffSnapshot {
+            timestamp: at.timestamp_millis(),
+            taken_at: at.to_rfc3339(),
+            state,
+            diff,
+        };
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&serde_json::to_vec(&snapshot)?)?;
+        let compressed = encoder.finish()?;
+
+        let _writing = self.writing.lock().await;
+        let dir = self.config.dir.join(session_id);
+        tokio::fs::create_dir_all(&dir).await?;
+        tokio::fs::write(dir.join(file_name(snapshot.timestamp)), &compressed).await?;
+        self.last_patch
+            .lock()
+            .unwrap()
+            .insert(session_id.to_string(), hash);
+        self.prune().await?;
+        Ok(Some(DiffSnapshotInfo {
+            timestamp: snapshot.timestamp,
+            taken_at: snapshot.taken_at,
+            compressed_bytes: compressed.len() as u64,
+        }))
+    }
+
+    /// A session's snapshots, oldest first.
+    pub async fn list(&self, session_id: &str) -> Vec<DiffSnapshotInfo> {
+        if !is_safe_id(session_id) {
+            return Vec::new();
+        }
+        let mut snapshots: Vec<DiffSnapshotInfo> = list_dir(&self.config.dir.join(session_id))
+            .await
+            .into_iter()
+            .map(|(timestamp, _, bytes)| DiffSnapshotInfo {
+                timestamp,
+                taken_at: DateTime::from_timestamp_millis(timestamp)
+                    .map(|at| at.to_rfc3339())
+                    .unwrap_or_default(),
+                compressed_bytes: bytes,
+            })
+            .collect();
+        snapshots.sort_by_key(|s| s.timestamp);
+        snapshots
+    }
+
+    pub async fn get(&self, session_id: &str, timestamp: i64) -> Option<DiffSnapshot> {
+        if !is_safe_id(session_id) {
+            return None;
+        }
+        let path = self.config.dir.join(session_id).join(file_name(timestamp));
+        let compressed = tokio::fs::read(path).await.ok()?;
+        let mut json = Vec::new();
+        GzDecoder::new(compressed.as_slice())
+            .read_to_end(&mut json)
+            .ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+
+    /// Delete the oldest snapshots until the count and size caps hold.
+    async fn prune(&self) -> std::io::Result<()> {
+        let mut all = Vec::new();
+        let mut sessions = match tokio::fs::read_dir(&self.config.dir).await {
+            Ok(sessions) => sessions,
+            Err(_) => return Ok(()),
+        };
+        while let Some(session) = sessions.next_entry().await? {
+            all.extend(list_dir(&session.path()).await);
+        }
+        all.sort_by_key(|(timestamp, _, _)| *timestamp);
+        let mut count = all.len();
+        let mut total: u64 = all.iter().map(|(_, _, bytes)| bytes).sum();
+        for (_, path, bytes) in all {
+            if count <= self.config.max_count && total <= self.config.max_total_bytes {
+                break;
+            }
+            tokio::fs::remove_file(&path).await?;
+            count -= 1;
+            total -= bytes;
+            // Fails, as it should, while the session has other snapshots.
+            if let Some(dir) = path.parent() {
+                let _ = tokio::fs::remove_dir(dir).await;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn file_name(timestamp: i64) -> String {
+    format!("{}{}", timestamp, EXTENSION)
+}
+
+/// Snapshots in one session's directory: timestamp, path and size.
+async fn list_dir(dir: &Path) -> Vec<(i64, PathBuf, u64)> {
+    let mut snapshots = Vec::new();
+    let Ok(mut entries) = tokio::fs::read_dir(dir).await else {
+        return snapshots;
+    };
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let name = entry.file_name();
+        let Some(timestamp) = name
+            .to_str()
+            .and_then(|name| name.strip_suffix(EXTENSION))
+            .and_then(|ts| ts.parse().ok())
+        else {
+            continue;
+        };
+        if let Ok(metadata) = entry.metadata().await {
+            snapshots.push((timestamp, entry.path(), metadata.len()));
+        }
+    }
+    snapshots
+}
+
+/// Session ids name directories; anything that could leave the snapshot directory
+/// (relayed ids contain `:`, a request could contain `..`) is refused.
+fn is_safe_id(session_id: &str) -> bool {
+    !session_id.is_empty()
+        && session_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = std::process::Command::new("git")
+            .args([
+                "-c",
+                "user.name=Test User",
+                "-c",
+                "user.email=test@example.com",
+                "-c",
+                "commit.gpgsign=false",
+            ])
+            .args(args)
+            .current_dir(dir)
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    fn store(root: &Path, max_count: usize, max_diff_bytes: u64) -> DiffSnapshotStore {
+        DiffSnapshotStore::new(DiffSnapshotConfig {
+            dir: root.join("snapshots"),
+            states: vec![AgentStateType::Idle],
+            max_count,
+            max_total_bytes: DEFAULT_MAX_TOTAL_BYTES,
+            max_diff_bytes,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_capture_list_get_and_prune() {
+        if std::process::Command::new("git")
+            .arg("--version")
+            .output()
+            .is_err()
+        {
+            return;
+        }
+        let root = std::env::temp_dir().join(format!(
+            "agents-dashboard-snapshots-{}",
+            uuid::Uuid::new_v4()
+        ));
+        let repo = root.join("repo");
+        std::fs::create_dir_all(&repo).unwrap();
+        git(&repo, &["init", "-q"]);
+        std::fs::write(repo.join("a.txt"), "one\n").unwrap();
+        git(&repo, &["add", "."]);
+        git(&repo, &["commit", "-q", "-m", "Initial commit"]);
+        let wd = repo.to_str().unwrap();
+        let at = |ms: i64| DateTime::from_timestamp_millis(ms).unwrap();
+
+        let store = store(&root, 2, 1 << 20);
+        // Nothing changed yet.
+        assert!(store
+            .capture("s1", wd, AgentStateType::Idle, at(1_000))
+            .await
+            .unwrap()
+            .is_none());
+
+        std::fs::write(repo.join("a.txt"), "one\ntwo\n").unwrap();
+        let first = store
+            .capture("s1", wd, AgentStateType::Idle, at(2_000))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(first.timestamp, 2_000);
+        // The same changes at the end of the next turn aren't stored twice.
+        assert!(store
+            .capture("s1", wd, AgentStateType::Idle, at(3_000))
+            .await
+            .unwrap()
+            .is_none());
+
+        std::fs::write(repo.join("a.txt"), "one\ntwo\nthree\n").unwrap();
+        store
+            .capture("s1", wd, AgentStateType::Idle, at(4_000))
+            .await
+            .unwrap()
+            .unwrap();
+        let listed: Vec<i64> = store.list("s1").await.iter().map(|s| s.timestamp).collect();
+        assert_eq!(listed, [2_000, 4_000]);
+
+        let snapshot = store.get("s1", 2_000).await.unwrap();
+        assert!(snapshot.diff.patch.contains("+two\n"));
+        assert!(!snapshot.diff.patch.contains("+three\n"));
+        assert_eq!(snapshot.state, AgentStateType::Idle);
+        assert!(store.get("s1", 9_999).await.is_none());
+        assert!(store.get("../s1", 2_000).await.is_none());
+
+        // Over the count cap, the oldest snapshot of any session goes first.
+        std::fs::write(repo.join("b.txt"), "new\n").unwrap();
+        git(&repo, &["add", "b.txt"]);
+        std::fs::write(repo.join("a.txt"), "changed\n").unwrap();
+        store
+            .capture("s2", wd, AgentStateType::Idle, at(5_000))
+            .await
+            .unwrap()
+            .unwrap();
+        let listed: Vec<i64> = store.list("s1").await.iter().map(|s| s.timestamp).collect();
+        assert_eq!(listed, [4_000]);
+        assert_eq!(store.list("s2").await.len(), 1);
+
+        // Diffs over the size cap are skipped.
+        let small = super::tests::store(&root, 10, 10);
+        std::fs::write(repo.join("a.txt"), "changed again\n").unwrap();
+        assert!(small
+            .capture("s3", wd, AgentStateType::Idle, at(6_000))
+            .await
+            .unwrap()
+            .is_none());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,280 @@
+use crate::providers::ProviderEvent;
+use crate::types::JournalEntry;
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+pub const DEFAULT_MAX_BYTES: u64 = 10 * 1024 * 1024;
+pub const DEFAULT_KEEP_FILES: usize = 5;
+/// Events waiting to be written. When the writer falls this far behind, new events
+/// are dropped rather than slowing down event routing.
+const QUEUE_CAPACITY: usize = 4096;
+
+#[derive(Debug, Clone)]
+pub struct JournalConfig {
+    pub path: PathBuf,
+    /// The current file is rotated before it would grow past this size.
+    pub max_bytes: u64,
+    /// Files kept, including the current one (`path`, `path.1`, ... oldest last).
+    pub keep_files: usize,
+}
+
+impl JournalConfig {
+    /// `path` for generation 0, `path.N` for older ones.
+    fn file(&self, generation: usize) -> PathBuf {
+        if generation == 0 {
+            return self.path.clone();
+        }
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", generation));
+        PathBuf::from(name)
+    }
+}
+
+/// Append-only JSONL record of the provider events the backend routed, for working
+/// out after the fact why a session was shown the way it was.
+pub struct EventJournal {
+    config: JournalConfig,
+    tx: mpsc::Sender<String>,
+}
+
+impl EventJournal {
+    /// Start the writer task.
+    pub fn start(config: JournalConfig) -> Self {
+        info!("[Journal] Recording events to {}", config.path.display());
+        let (tx, mut rx) = mpsc::channel::<String>(QUEUE_CAPACITY);
+        let mut writer = JournalWriter::new(config.clone());
+        tokio::spawn(async move {
+            while let Some(line) = rx.recv().await {
+                let mut result = writer.write(&line).await;
+                // Drain whatever queued up meanwhile before flushing.
+                while let (Ok(()), Ok(line)) = (&result, rx.try_recv()) {
+                    result = writer.write(&line).await;
+                }
+                if let Err(e) = result.and(writer.flush().await) {
+                    warn!(
+                        "[Journal] Write to {} failed: {}",
+                        writer.config.path.display(),
+                        e
+                    );
+                    writer.file = None;
+                }
+            }
+        });
+        Self { config, tx }
+    }
+
+    /// Queue an event for writing. Never waits: the event is dropped if the writer is
+    /// backed up.
+    pub fn record(&self, event: &ProviderEvent) {
+        let line = match journal_line(event, Utc::now()) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("[Journal] Could not serialize event: {}", e);
+                return;
+            }
+        };
+        if self.tx.try_send(line).is_err() {
+            warn!("[Journal] Writer is behind; dropped an event");
+        }
+    }
+
+    /// A session's entries recorded at or after `since`, oldest first.
+    pub async fn read_session(
+        &self,
+        session_id: &str,
+        since: Option<DateTime<Utc>>,
+    ) -> std::io::Result<Vec<JournalEntry>> {
+        read_entries(&self.config, session_id, since).await
+    }
+}
+
+fn journal_line(event: &ProviderEvent, ingested_at: DateTime<Utc>) -> serde_json::Result<String> {
+    let entry = JournalEntry {
+        ingested_at: ingested_at.to_rfc3339(),
+        session_id: event.session_id().to_string(),
+        event: serde_json::to_value(event)?,
+    };
+    let mut line = serde_json::to_string(&entry)?;
+    line.push('\n');
+    Ok(line)
+}
+
+struct JournalWriter {
+    config: JournalConfig,
+    file: Option<tokio::fs::File>,
+    size: u64,
+}
+
+impl JournalWriter {
+    fn new(config: JournalConfig) -> Self {
+        Self {
+            config,
+            file: None,
+            size: 0,
+        }
+    }
+
+    async fn write(&mut self, line: &str) -> std::io::Result<()> {
+        if self.file.is_none() {
+            self.open().await?;
+        }
+        if self.size > 0 && self.size + line.len() as u64 > self.config.max_bytes {
+            self.rotate().await?;
+        }
+        let file = self.file.as_mut().expect("journal file opened above");
+        file.write_all(line.as_bytes()).await?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> std::io::Result<()> {
+        match self.file.as_mut() {
+            Some(file) => file.flush().await,
+            None => Ok(()),
+        }
+    }
+
+    async fn open(&mut self) -> std::io::Result<()> {
+        if let Some(dir) = self
+            .config
+            .path
+            .parent()
+            .filter(|d| !d.as_os_str().is_empty())
+        {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        let file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.config.path)
+            .await?;
+        self.size = file.metadata().await?.len();
+        self.file = Some(file);
+        Ok(())
+    }
+
+    /// Shift every file up one generation, dropping the oldest, and start a new one.
+    async fn rotate(&mut self) -> std::io::Result<()> {
+        self.flush().await?;
+        self.file = None;
+        let keep = self.config.keep_files;
+        remove_if_exists(&self.config.file(keep.saturating_sub(1))).await?;
+        for generation in (0..keep.saturating_sub(1)).rev() {
+            let from = self.config.file(generation);
+            if tokio::fs::try_exists(&from).await? {
+                tokio::fs::rename(&from, self.config.file(generation + 1)).await?;
+            }
+        }
+        self.open().await
+    }
+}
+
+async fn remove_if_exists(path: &Path) -> std::io::Result<()> {
+    match tokio::fs::remove_file(path).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+async fn read_entries(
+    config: &JournalConfig,
+    session_id: &str,
+    since: Option<DateTime<Utc>>,
+) -> std::io::Result<Vec<JournalEntry>> {
+    let mut entries = Vec::new();
+    for generation in (0..config.keep_files).rev() {
+        let text = match tokio::fs::read_to_string(config.file(generation)).await {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        for line in text.lines() {
+            // Skip a line cut short by a crash rather than failing the whole read.
+            let Ok(entry) = serde_json::from_str::<JournalEntry>(line) else {
+                continue;
+            };
+            if entry.session_id != session_id {
+                continue;
+            }
+            let recent = since.is_none_or(|since| {
+                DateTime::parse_from_rfc3339(&entry.ingested_at).is_ok_and(|at| at >= since)
+            });
+            if recent {
+                entries.push(entry);
+            }
+        }
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_config(max_bytes: u64, keep_files: usize) -> JournalConfig {
+        JournalConfig {
+            path: std::env::temp_dir()
+                .join(format!("agents-dashboard-journal-{}", uuid::Uuid::new_v4()))
+                .join("events.jsonl"),
+            max_bytes,
+            keep_files,
+        }
+    }
+
+    fn removed(session_id: &str) -> ProviderEvent {
+        ProviderEvent::SessionRemoved {
+            session_id: session_id.to_string(),
+        }
+    }
+
+    fn at(ts: &str) -> DateTime<Utc> {
+        ts.parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_read_back_by_session_and_time() {
+        let config = temp_config(DEFAULT_MAX_BYTES, 2);
+        let mut writer = JournalWriter::new(config.clone());
+        for (session_id, ts) in [
+            ("s1", "2025-06-01T10:00:00Z"),
+            ("s2", "2025-06-01T10:01:00Z"),
+            ("s1", "2025-06-01T10:02:00Z"),
+        ] {
+            let line = journal_line(&removed(session_id), at(ts)).unwrap();
+            writer.write(&line).await.unwrap();
+        }
+        writer.flush().await.unwrap();
+
+        let entries = read_entries(&config, "s1", None).await.unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].event["type"], "session_removed");
+        assert_eq!(entries[0].event["sessionId"], "s1");
+
+        let since = Some(at("2025-06-01T10:01:00Z"));
+        let entries = read_entries(&config, "s1", since).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(at(&entries[0].ingested_at), at("2025-06-01T10:02:00Z"));
+        let _ = std::fs::remove_dir_all(config.path.parent().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_rotation_keeps_last_files() {
+        let line = journal_line(&removed("s1"), at("2025-06-01T10:00:00Z")).unwrap();
+        // Two lines per file, three files.
+        let config = temp_config(line.len() as u64 * 2, 3);
+        let mut writer = JournalWriter::new(config.clone());
+        for _ in 0..9 {
+            writer.write(&line).await.unwrap();
+        }
+        writer.flush().await.unwrap();
+
+        assert!(config.file(2).exists());
+        assert!(!config.file(3).exists());
+        let entries = read_entries(&config, "s1", None).await.unwrap();
+        assert_eq!(entries.len(), 5);
+        let _ = std::fs::remove_dir_all(config.path.parent().unwrap());
+    }
+}
@@ -1,24 +1,104 @@
-use crate::providers::claude_code::ClaudeCodeProvider;
+use crate::cost::EntryUsage;
+use crate::config::ProjectBudget;
+use crate::days;
+use crate::notifications::NotifyRules;
+use crate::providers::claude_code::git_diff::{fetch_git_diff, DiffError, DiffOptions};
+use crate::providers::claude_code::process_probe::{
+    claude_processes, signal_claude_process, InterruptSignal,
+};
+use crate::providers::claude_code::replay::plan_replay;
+use crate::providers::claude_code::search_query::SearchQuery;
+use crate::providers::claude_code::timestamps::{format_timestamp, now_timestamp};
+use crate::providers::claude_code::text_util::make_snippet;
+use crate::providers::claude_code::{messages_after, ClaudeCodeProvider, DismissError, ProviderConfig, SharedSettings};
 use crate::providers::ProviderEvent;
-use crate::types::{AgentMessage, AgentSessionDetail, AgentSessionSummary, SearchResponse, SearchScope, SessionSearchResult};
-use std::sync::Arc;
+use crate::session::archive::{ArchiveFilter, ArchiveStore};
+use crate::session::day_digest;
+use crate::session::diff_snapshots::DiffSnapshotStore;
+use crate::session::prefs::PrefsStore;
+use crate::session::project_usage::{ProjectUsageStore, SessionTally};
+use crate::session::read_marks::{ReadMark, ReadMarkStore};
+use crate::session::remote::RemoteSessions;
+use crate::session::summarizer::{Summarizer, SummaryError, DASHBOARD_PROJECT};
+use crate::session::usage_report::parse_time;
+use crate::session::watchdog::{self, Findings, Watchdog};
+use crate::types::{AgentMessage, AgentStateType, ArchivedSession, ArchivedSessionInfo, CumulativeUsage, AgentSessionDetail, AgentSessionSummary, DayDigest, DiffSnapshot, DismissPlan, DiffSnapshotInfo, GeneratedSummary, GitDiff, HistoryPlan, InterruptPlan, MessageRole, MessageType, ReplayPlan, SearchMatch, SearchResponse, SearchScope, SessionPrefs, SessionPrefsPatch, SessionBatchResponse, SessionChain, SessionOutline, SessionSearchResult, SessionSources, SessionTimeline, SessionToolStats, StartupSummary, StopReplayPlan, SummarizePlan, TranscriptMemory, UsageTimeline, DashboardStats, ProjectSessions, ProjectUsage, ReportPeriod, ServerEvent, UsageReport, WatchdogStatus, WatcherCounts};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
-use tracing::info;
+use tracing::{error, info, warn};
+
+/// How long `dashboard_stats` reuses its last result, so polling widgets don't
+/// take the sessions lock on every request.
+const STATS_CACHE_TTL: Duration = Duration::from_secs(5);
+/// How long a diff is reused, so a panel refreshing it doesn't run git each time.
+const DIFF_CACHE_TTL: Duration = Duration::from_secs(3);
+
+type DiffKey = (String, DiffOptions);
+
+#[derive(Debug, PartialEq)]
+pub enum InterruptError {
+    SessionNotFound,
+    /// No Claude process runs in the session's directory (or `pid` isn't one of them).
+    NoProcess,
+    /// Several do, and no pid was given to pick one.
+    Ambiguous(Vec<u32>),
+    /// Processes can't be listed here, e.g. inside a container.
+    Unavailable,
+    Signal(String),
+}
 
 /// SessionManager wraps the provider and exposes an event channel.
 pub struct SessionManager {
     provider: Arc<ClaudeCodeProvider>,
+    prefs: PrefsStore,
+    projects: ProjectUsageStore,
+    read_marks: ReadMarkStore,
+    remote: RemoteSessions,
     pub event_rx: tokio::sync::Mutex<mpsc::UnboundedReceiver<ProviderEvent>>,
+    stats_cache: Mutex<Option<(Instant, DashboardStats)>>,
+    diff_cache: Mutex<HashMap<DiffKey, (Instant, GitDiff)>>,
+    diff_snapshots: Mutex<Option<Arc<DiffSnapshotStore>>>,
+    archive: Mutex<Option<Arc<ArchiveStore>>>,
+    summarizer: Mutex<Option<Arc<Summarizer>>>,
+    watchdog: Watchdog,
 }
 
 impl SessionManager {
-    pub fn new() -> Self {
+    #[cfg(test)]
+    pub fn with_prefs(prefs: PrefsStore) -> Self {
+        Self::with_config(
+            prefs,
+            ProjectUsageStore::in_memory(),
+            ReadMarkStore::in_memory(),
+            ProviderConfig::default(),
+        )
+    }
+
+    pub fn with_config(
+        prefs: PrefsStore,
+        projects: ProjectUsageStore,
+        read_marks: ReadMarkStore,
+        config: ProviderConfig,
+    ) -> Self {
         let (event_tx, event_rx) = mpsc::unbounded_channel();
-        let provider = Arc::new(ClaudeCodeProvider::new(event_tx));
+        let provider = Arc::new(ClaudeCodeProvider::with_config(event_tx, config));
 
         Self {
             provider,
+            prefs,
+            projects,
+            read_marks,
+            remote: RemoteSessions::new(),
             event_rx: tokio::sync::Mutex::new(event_rx),
+            stats_cache: Mutex::new(None),
+            diff_cache: Mutex::new(HashMap::new()),
+            diff_snapshots: Mutex::new(None),
+            archive: Mutex::new(None),
+            summarizer: Mutex::new(None),
+            watchdog: Watchdog::new(),
         }
     }
 
@@ -29,28 +109,995 @@ impl SessionManager {
 
     pub async fn stop(&self) {
         self.provider.stop().await;
+        self.projects.save().await;
         info!("[SessionManager] Stopped");
     }
 
-    pub async fn get_sessions(&self) -> Vec<AgentSessionSummary> {
-        self.provider.get_sessions().await
+    /// The settings tracked sessions read, for replacing on config reload.
+    pub fn session_settings(&self) -> &SharedSettings {
+        self.provider.session_settings()
+    }
+
+    /// The zone days are counted in.
+    pub fn timezone(&self) -> chrono_tz::Tz {
+        self.provider.session_settings().load().timezone
+    }
+
+    /// The current date in the configured zone.
+    pub fn today(&self) -> chrono::NaiveDate {
+        days::today(chrono::Utc::now(), self.timezone())
+    }
+
+    /// List sessions, including those relayed from other hosts. Archived sessions are
+    /// left out unless `include_archived` is set.
+    pub async fn get_sessions(&self, include_archived: bool) -> Vec<AgentSessionSummary> {
+        let prefs = self.prefs.snapshot().await;
+        self.provider
+            .get_sessions()
+            .await
+            .into_iter()
+            .chain(self.remote.summaries())
+            .filter_map(|mut s| {
+                let p = prefs.get(&s.session_id).cloned().unwrap_or_default();
+                if p.archived && !include_archived {
+                    return None;
+                }
+                apply(&mut s, &p);
+                Some(s)
+            })
+            .collect()
     }
 
     pub async fn get_session_detail(&self, session_id: &str) -> Option<AgentSessionDetail> {
-        self.provider.get_session_detail(session_id).await
+        let mut detail = match self.provider.get_session_detail(session_id).await {
+            Some(detail) => detail,
+            None => AgentSessionDetail {
+                summary: self.remote.summary(session_id)?,
+                messages: self.remote.messages(session_id)?,
+                usage_points: Vec::new(),
+                model_history: Vec::new(),
+                watcher: None,
+                settings: None,
+            },
+        };
+        self.apply_prefs(&mut detail.summary).await;
+        Some(detail)
+    }
+
+    pub async fn get_session_messages(&self, session_id: &str) -> Option<Vec<Arc<AgentMessage>>> {
+        match self.provider.get_session_messages(session_id).await {
+            Some(messages) => Some(messages),
+            None => self.remote.messages(session_id),
+        }
+    }
+
+    /// Sessions listed under `project_path`.
+    pub async fn project_session_ids(&self, project_path: &str) -> Vec<String> {
+        self.get_sessions(false)
+            .await
+            .into_iter()
+            .filter(|s| s.project_path == project_path)
+            .map(|s| s.session_id)
+            .collect()
+    }
+
+    /// What `client_id` has read, by session.
+    pub async fn read_marks(&self, client_id: &str) -> HashMap<String, ReadMark> {
+        self.read_marks.get(client_id).await
+    }
+
+    /// Mark a session read up to `message_id`, and remember it for `client_id` if
+    /// there is one. None if the session has no such message.
+    pub async fn mark_read(
+        &self,
+        client_id: Option<&str>,
+        session_id: &str,
+        message_id: &str,
+    ) -> Option<ReadMark> {
+        let messages = self.get_session_messages(session_id).await?;
+        let message = messages.iter().rev().find(|m| m.id == message_id)?;
+        let mark = ReadMark {
+            message_id: message_id.to_string(),
+            timestamp: message.timestamp.clone(),
+            acked_at: chrono::Utc::now().timestamp_millis(),
+        };
+        if let Some(client_id) = client_id {
+            self.read_marks
+                .set(client_id, session_id, mark.clone())
+                .await;
+        }
+        Some(mark)
+    }
+
+    /// Messages in a session since `mark`, if that can be told.
+    pub async fn unread_count(&self, session_id: &str, mark: &ReadMark) -> Option<u64> {
+        let read_until = parse_time(&mark.timestamp);
+        if let Some(count) = self
+            .provider
+            .messages_after(session_id, &mark.message_id, read_until)
+            .await
+        {
+            return Some(count);
+        }
+        let messages = self.remote.messages(session_id)?;
+        Some(messages_after(&messages, &mark.message_id, read_until))
+    }
+
+    pub async fn unread_counts(&self, marks: &HashMap<String, ReadMark>) -> HashMap<String, u64> {
+        let mut counts = HashMap::new();
+        for (session_id, mark) in marks {
+            if let Some(count) = self.unread_count(session_id, mark).await {
+                counts.insert(session_id.clone(), count);
+            }
+        }
+        counts
+    }
+
+    pub async fn get_session_timeline(&self, session_id: &str) -> Option<SessionTimeline> {
+        self.provider.get_session_timeline(session_id).await
+    }
+
+    pub async fn get_usage_timeline(&self, session_id: &str) -> Option<UsageTimeline> {
+        self.provider.get_usage_timeline(session_id).await
+    }
+
+    pub async fn dashboard_stats(&self) -> DashboardStats {
+        if let Some((at, stats)) = self.stats_cache.lock().unwrap().as_ref() {
+            if at.elapsed() < STATS_CACHE_TTL {
+                return stats.clone();
+            }
+        }
+        let stats = self.provider.dashboard_stats(chrono::Utc::now()).await;
+        *self.stats_cache.lock().unwrap() = Some((Instant::now(), stats.clone()));
+        stats
+    }
+
+    /// Usage per project, including sessions no longer tracked, most expensive first.
+    pub fn project_usage(&self) -> Vec<ProjectUsage> {
+        self.projects.snapshot(chrono::Utc::now(), self.timezone())
+    }
+
+    /// Sessions by the project they were discovered in, the key project usage is
+    /// totalled by, so a session that moved to another directory stays under the
+    /// project it was found in. The project with the latest activity comes first.
+    /// With `group_chains`, a resume chain is listed once, as its latest session.
+    pub async fn get_sessions_by_project(
+        &self,
+        include_archived: bool,
+        group_chains: bool,
+    ) -> Vec<ProjectSessions> {
+        let mut sessions = self.get_sessions(include_archived).await;
+        if group_chains {
+            sessions = latest_of_chains(sessions, &self.provider.chain_roots().await);
+        }
+        let discovered = self.provider.discovery_project_paths().await;
+        let mut usage: HashMap<String, ProjectUsage> = self
+            .project_usage()
+            .into_iter()
+            .map(|project| (project.project_path.clone(), project))
+            .collect();
+        let mut groups: HashMap<String, Vec<AgentSessionSummary>> = HashMap::new();
+        for session in sessions {
+            // Relayed sessions weren't discovered here; their project path is all there is.
+            let project_path = discovered
+                .get(&session.session_id)
+                .unwrap_or(&session.project_path)
+                .clone();
+            groups.entry(project_path).or_default().push(session);
+        }
+        let mut grouped: Vec<ProjectSessions> = groups
+            .into_iter()
+            .map(|(project_path, mut sessions)| {
+                sessions.sort_by(|a, b| b.last_activity_at.cmp(&a.last_activity_at));
+                // Named as recording the usage would name it.
+                let project = usage.remove(&project_path).unwrap_or_else(|| ProjectUsage {
+                    project_path,
+                    project_name: sessions[0].project_name.clone(),
+                    sessions: sessions.len(),
+                    usage: CumulativeUsage::default(),
+                    cost_today: 0.0,
+                    cost_this_week: 0.0,
+                    cost_this_month: 0.0,
+                    budget: None,
+                });
+                ProjectSessions { project, sessions }
+            })
+            .collect();
+        grouped.sort_by(|a, b| {
+            b.sessions[0]
+                .last_activity_at
+                .cmp(&a.sessions[0].last_activity_at)
+        });
+        grouped
+    }
+
+    pub fn usage_report(&self, period: ReportPeriod) -> UsageReport {
+        self.projects
+            .report(period, chrono::Utc::now(), self.timezone())
+    }
+
+    /// What agents did in a project on `date` (in the configured zone). None if the
+    /// project is neither tracked nor in the usage tallies.
+    pub async fn day_digest(
+        &self,
+        project_path: &str,
+        date: chrono::NaiveDate,
+    ) -> Option<DayDigest> {
+        let now = chrono::Utc::now();
+        let day = days::day_index(date);
+        let live = self
+            .provider
+            .project_day_samples(project_path, day, now.timestamp_millis())
+            .await;
+        let recorded = self.projects.project(project_path);
+        if live.is_none() && recorded.is_none() {
+            return None;
+        }
+        let live = live.unwrap_or_default();
+        let (name, tallies) = recorded.unwrap_or_default();
+        let name = match live.first() {
+            Some(sample) if name.is_empty() => sample.project_name.clone(),
+            _ => name,
+        };
+        Some(day_digest::build(
+            project_path,
+            &name,
+            day,
+            &tallies,
+            &live,
+            now,
+            self.timezone(),
+        ))
+    }
+
+    /// Fold a session's latest usage into its project's totals. Returns the event
+    /// to send if this took the project over its budget.
+    pub async fn record_project_usage(&self, session_id: &str) -> Option<ServerEvent> {
+        let sample = self.provider.project_usage_sample(session_id).await?;
+        let tally = SessionTally {
+            usage: sample.usage,
+            daily_cost: sample.daily_cost,
+            daily_tokens: sample.daily_tokens,
+            model: sample.model,
+            title: sample.title,
+            started_at: sample.started_at,
+            last_activity_at: sample.last_activity_at,
+            top_tools: sample.top_tools,
+            ..Default::default()
+        };
+        let exceeded = self.projects.record(
+            &sample.project_path,
+            &sample.project_name,
+            session_id,
+            tally,
+            chrono::Utc::now(),
+            self.timezone(),
+        )?;
+        info!(
+            "[Projects] {} is over its {:?} budget: ${:.2} of ${:.2}",
+            exceeded.project_path,
+            exceeded.status.period,
+            exceeded.status.spent_usd,
+            exceeded.status.limit_usd
+        );
+        Some(ServerEvent::BudgetExceeded {
+            project_path: exceeded.project_path,
+            project_name: exceeded.project_name,
+            period: exceeded.status.period,
+            limit_usd: exceeded.status.limit_usd,
+            spent_usd: exceeded.status.spent_usd,
+        })
+    }
+
+    pub fn set_budgets(&self, budgets: Vec<ProjectBudget>) {
+        self.projects.set_budgets(budgets);
+    }
+
+    /// Write project totals to disk if they changed.
+    pub async fn save_project_usage(&self) {
+        self.projects.save().await;
+    }
+
+    pub async fn get_tool_stats(&self, session_id: &str) -> Option<SessionToolStats> {
+        self.provider.get_tool_stats(session_id).await
+    }
+
+    pub async fn get_sources(&self, session_id: &str) -> Option<SessionSources> {
+        self.provider.get_sources(session_id).await
+    }
+
+    pub async fn get_outline(&self, session_id: &str) -> Option<SessionOutline> {
+        self.provider.get_outline(session_id).await
+    }
+
+    /// The uncommitted changes in a session's repository. None if the session isn't
+    /// tracked here (relayed sessions' repositories are on another machine).
+    pub async fn session_diff(
+        &self,
+        session_id: &str,
+        options: DiffOptions,
+        max_bytes: u64,
+    ) -> Option<Result<GitDiff, DiffError>> {
+        let key = (session_id.to_string(), options);
+        if let Some((at, diff)) = self.diff_cache.lock().unwrap().get(&key) {
+            if at.elapsed() < DIFF_CACHE_TTL {
+                return Some(Ok(diff.clone()));
+            }
+        }
+        let working_directory = self.provider.working_directory(session_id).await?;
+        let result = fetch_git_diff(&working_directory, &key.1, max_bytes).await;
+        if let Ok(diff) = &result {
+            let mut cache = self.diff_cache.lock().unwrap();
+            cache.retain(|_, (at, _)| at.elapsed() < DIFF_CACHE_TTL);
+            cache.insert(key, (Instant::now(), diff.clone()));
+        }
+        Some(result)
+    }
+
+    pub fn set_diff_snapshots(&self, store: Option<DiffSnapshotStore>) {
+        *self.diff_snapshots.lock().unwrap() = store.map(Arc::new);
+    }
+
+    /// Save a session's diff in the background if entering `state` calls for it.
+    /// git can take a while in a large repository, so the event loop doesn't wait.
+    pub fn snapshot_diff(&self, session_id: &str, state: AgentStateType) {
+        let Some(store) = self.diff_snapshots.lock().unwrap().clone() else {
+            return;
+        };
+        if !store.wants(state) {
+            return;
+        }
+        let provider = self.provider.clone();
+        let session_id = session_id.to_string();
+        let at = chrono::Utc::now();
+        tokio::spawn(async move {
+            let Some(working_directory) = provider.working_directory(&session_id).await else {
+                return;
+            };
+            if let Err(e) = store
+                .capture(&session_id, &working_directory, state, at)
+                .await
+            {
+                warn!(
+                    "[SessionManager] Diff snapshot of {} failed: {}",
+                    session_id, e
+                );
+            }
+        });
+    }
+
+    /// A session's saved diffs, oldest first. Empty when snapshots are off.
+    pub async fn diff_snapshots(&self, session_id: &str) -> Vec<DiffSnapshotInfo> {
+        let store = self.diff_snapshots.lock().unwrap().clone();
+        match store {
+            Some(store) => store.list(session_id).await,
+            None => Vec::new(),
+        }
+    }
+
+    pub async fn diff_snapshot(&self, session_id: &str, timestamp: i64) -> Option<DiffSnapshot> {
+        let store = self.diff_snapshots.lock().unwrap().clone()?;
+        store.get(session_id, timestamp).await
+    }
+
+    pub fn set_archive(&self, store: Option<ArchiveStore>) {
+        *self.archive.lock().unwrap() = store.map(Arc::new);
+    }
+
+    pub fn archiving(&self) -> bool {
+        self.archive.lock().unwrap().is_some()
+    }
+
+    /// Archive sessions that have been stopped long enough and drop them from
+    /// memory. Pinned sessions stay. Returns how many were archived.
+    pub async fn archive_stopped(&self) -> usize {
+        let Some(store) = self.archive.lock().unwrap().clone() else {
+            return 0;
+        };
+        // A delay too long to subtract from now archives nothing.
+        let Some(before) = chrono::Duration::from_std(store.after())
+            .ok()
+            .and_then(|after| chrono::Utc::now().checked_sub_signed(after))
+        else {
+            return 0;
+        };
+        let mut archived = 0;
+        for (session_id, last_activity_at) in self.provider.archive_candidates(before).await {
+            if self.prefs.get(&session_id).await.pinned {
+                continue;
+            }
+            let Some(detail) = self.get_session_detail(&session_id).await else {
+                continue;
+            };
+            let session = ArchivedSession {
+                archived_at: now_timestamp(),
+                detail,
+                timeline: self.get_session_timeline(&session_id).await,
+                usage: self.get_usage_timeline(&session_id).await,
+            };
+            match store.archive(&session).await {
+                Ok(Some(_)) => {
+                    if self
+                        .provider
+                        .release_archived(&session_id, &last_activity_at)
+                        .await
+                    {
+                        archived += 1;
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => warn!("[SessionManager] Archiving {} failed: {}", session_id, e),
+            }
+        }
+        if archived > 0 {
+            info!("[SessionManager] Archived {} stopped sessions", archived);
+        }
+        archived
     }
 
-    pub async fn get_session_messages(&self, session_id: &str) -> Option<Vec<AgentMessage>> {
-        self.provider.get_session_messages(session_id).await
+    /// Archived sessions matching `filter`, most recently active first. None when
+    /// archiving is off.
+    pub fn archived_sessions(&self, filter: &ArchiveFilter) -> Option<Vec<ArchivedSessionInfo>> {
+        let store = self.archive.lock().unwrap().clone()?;
+        Some(store.list(filter))
+    }
+
+    pub async fn archived_session(&self, session_id: &str) -> Option<ArchivedSession> {
+        let store = self.archive.lock().unwrap().clone()?;
+        store.get(session_id).await
+    }
+
+    pub fn set_summarizer(&self, summarizer: Option<Summarizer>) {
+        *self.summarizer.lock().unwrap() = summarizer.map(Arc::new);
+    }
+
+    /// Whether a session that just stopped should be summarized without being asked.
+    pub fn summarizes_on_stop(&self, session: &AgentSessionSummary) -> bool {
+        self.summarizer
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|summarizer| summarizer.wants_on_stop(session))
+    }
+
+    /// Which model `summarize` would ask, and about how much of the session.
+    pub async fn plan_summarize(&self, session_id: &str) -> Result<SummarizePlan, SummaryError> {
+        let model = self
+            .summarizer
+            .lock()
+            .unwrap()
+            .as_ref()
+            .ok_or(SummaryError::Disabled)?
+            .model()
+            .to_string();
+        let outline = self
+            .provider
+            .get_outline(session_id)
+            .await
+            .ok_or(SummaryError::SessionNotFound)?;
+        if outline.turns.is_empty() {
+            return Err(SummaryError::Empty);
+        }
+        Ok(SummarizePlan {
+            session_id: session_id.to_string(),
+            model,
+            turn_count: outline.turns.len() as u64,
+        })
+    }
+
+    /// Have a model summarize a local session from its outline, and keep the result
+    /// with the session's prefs. What it cost is counted under the `dashboard` project.
+    pub async fn summarize(&self, session_id: &str) -> Result<GeneratedSummary, SummaryError> {
+        let summarizer = self
+            .summarizer
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or(SummaryError::Disabled)?;
+        let outline = self
+            .provider
+            .get_outline(session_id)
+            .await
+            .ok_or(SummaryError::SessionNotFound)?;
+        if outline.turns.is_empty() {
+            return Err(SummaryError::Empty);
+        }
+        let session = self
+            .get_session_summary(session_id)
+            .await
+            .ok_or(SummaryError::SessionNotFound)?;
+        let now = chrono::Utc::now();
+        let summarized_at = session.summary.as_ref().map(|s| s.generated_at.as_str());
+        summarizer.claim(session_id, now.timestamp_millis(), summarized_at)?;
+
+        let completion = summarizer.complete(&session, &outline).await?;
+        let model = summarizer.model();
+        let usage = self.provider.session_settings().load().pricing.add_usage(
+            &CumulativeUsage::default(),
+            model,
+            &EntryUsage {
+                input_tokens: completion.input_tokens,
+                output_tokens: completion.output_tokens,
+                ..Default::default()
+            },
+        );
+        self.projects.add(
+            DASHBOARD_PROJECT,
+            session_id,
+            &usage,
+            model,
+            now,
+            self.timezone(),
+        );
+        let summary = GeneratedSummary {
+            text: completion.text,
+            model: model.to_string(),
+            generated_at: format_timestamp(now),
+            estimated_cost: usage.estimated_cost,
+        };
+        self.prefs.set_summary(session_id, summary.clone()).await;
+        info!(
+            "[Summary] {}: summarized for ${:.4}",
+            session_id, summary.estimated_cost
+        );
+        Ok(summary)
+    }
+
+    /// The directory a local session was started in. None for relayed sessions, whose
+    /// directories are on another machine.
+    pub async fn working_directory(&self, session_id: &str) -> Option<String> {
+        self.provider.working_directory(session_id).await
+    }
+
+    /// The log file of a local session, for `GET /api/sessions/{id}/raw`.
+    pub async fn log_file(&self, session_id: &str) -> Option<PathBuf> {
+        self.provider.log_file(session_id).await
+    }
+
+    /// The resume chain containing a session, with usage totalled across it.
+    pub async fn get_session_chain(&self, session_id: &str) -> Option<SessionChain> {
+        let mut sessions = self.provider.get_session_chain(session_id).await?;
+        let mut total_usage = CumulativeUsage::default();
+        for s in &mut sessions {
+            self.apply_prefs(s).await;
+            let u = &s.cumulative_usage;
+            total_usage.input_tokens += u.input_tokens;
+            total_usage.output_tokens += u.output_tokens;
+            total_usage.cache_read_tokens += u.cache_read_tokens;
+            total_usage.cache_creation_tokens += u.cache_creation_tokens;
+            total_usage.estimated_cost += u.estimated_cost;
+        }
+        Some(SessionChain {
+            session_id: session_id.to_string(),
+            sessions,
+            total_usage,
+        })
+    }
+
+    /// Number of sessions per Claude Code version, for spotting outdated installs.
+    pub async fn cli_versions(&self) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for s in self.provider.get_sessions().await {
+            if let Some(version) = s.cli_version {
+                *counts.entry(version).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+
+    pub async fn transcript_memory(&self) -> TranscriptMemory {
+        self.provider.transcript_memory().await
+    }
+
+    pub async fn watcher_counts(&self) -> WatcherCounts {
+        self.provider.watcher_counts().await
+    }
+
+    /// None until the logs found at startup have all been read once.
+    pub fn startup_summary(&self) -> Option<StartupSummary> {
+        self.provider.startup_summary()
+    }
+
+    pub fn subscribe_startup(&self) -> tokio::sync::watch::Receiver<Option<StartupSummary>> {
+        self.provider.subscribe_startup()
+    }
+
+    /// Tell the watchdog the routing loop is still draining events.
+    pub fn heartbeat(&self) {
+        self.watchdog.beat();
+    }
+
+    pub fn watchdog_status(&self) -> WatchdogStatus {
+        self.watchdog.status()
+    }
+
+    /// Check that discovery is still scanning, that watchers read logs that grow and
+    /// that events are still routed, restarting discovery or watchers that stopped.
+    pub async fn run_watchdog(&self) {
+        let now_ms = chrono::Utc::now().timestamp_millis();
+        let last_scan_ms = self.provider.discovery_last_scan().await;
+        let interval = self.provider.discovery_scan_interval();
+        let discovery_restarted = watchdog::scan_overdue(last_scan_ms, interval, now_ms);
+        if discovery_restarted {
+            error!(
+                "[Watchdog] Discovery hasn't scanned for {}s; restarting it",
+                (now_ms - last_scan_ms.unwrap_or(now_ms)) / 1000
+            );
+            self.provider.restart_discovery().await;
+        }
+        let watchers_restarted = self.provider.restart_stalled_watchers().await;
+        for session_id in &watchers_restarted {
+            error!(
+                "[Watchdog] Watcher of {} stopped reading its growing log; restarted it",
+                session_id
+            );
+        }
+        let routing_stalled = !self.watchdog.routing_moved();
+        if routing_stalled {
+            error!("[Watchdog] Event routing hasn't moved since the last check; the server needs a restart");
+        }
+        self.watchdog.record(
+            now_ms,
+            Findings {
+                last_scan_ms,
+                discovery_restarted,
+                watchers_restarted,
+                routing_stalled,
+            },
+        );
     }
 
     pub async fn get_session_summary(&self, session_id: &str) -> Option<AgentSessionSummary> {
         let sessions = self.provider.get_sessions().await;
-        sessions.into_iter().find(|s| s.session_id == session_id)
+        let mut summary = match sessions.into_iter().find(|s| s.session_id == session_id) {
+            Some(summary) => summary,
+            None => self.remote.summary(session_id)?,
+        };
+        self.apply_prefs(&mut summary).await;
+        Some(summary)
+    }
+
+    /// Summaries of the given sessions, archived or not, with the ids not found.
+    pub async fn get_session_summaries(&self, session_ids: &[String]) -> SessionBatchResponse {
+        let prefs = self.prefs.snapshot().await;
+        let mut sessions: HashMap<String, AgentSessionSummary> = self
+            .provider
+            .get_summaries(session_ids)
+            .await
+            .into_iter()
+            .map(|s| (s.session_id.clone(), s))
+            .collect();
+        let mut not_found = Vec::new();
+        for id in session_ids {
+            if sessions.contains_key(id) {
+                continue;
+            }
+            match self.remote.summary(id) {
+                Some(summary) => {
+                    sessions.insert(id.clone(), summary);
+                }
+                None if !not_found.contains(id) => not_found.push(id.clone()),
+                None => {}
+            }
+        }
+        for summary in sessions.values_mut() {
+            apply(
+                summary,
+                &prefs.get(&summary.session_id).cloned().unwrap_or_default(),
+            );
+        }
+        SessionBatchResponse {
+            sessions,
+            not_found,
+        }
+    }
+
+    async fn has_session(&self, session_id: &str) -> bool {
+        self.provider.has_session(session_id).await || self.remote.contains(session_id)
+    }
+
+    /// Whether a session came from another host through `/ingest`.
+    pub fn is_relayed(&self, session_id: &str) -> bool {
+        self.remote.contains(session_id)
+    }
+
+    /// A relay for `host` connected to `/ingest`. Returns the connection's number.
+    pub fn remote_connected(&self, host: &str) -> u64 {
+        info!("[Ingest] {} connected", host);
+        self.remote.connected(host)
+    }
+
+    pub fn remote_disconnected(&self, host: &str, connection: u64) {
+        info!("[Ingest] {} disconnected", host);
+        self.remote.disconnected(host, connection);
     }
 
-    pub async fn search_sessions(&self, query: &str, scopes: &[SearchScope]) -> SearchResponse {
-        let results: Vec<SessionSearchResult> = self.provider.search_sessions(query, scopes).await;
+    /// Apply an event relayed by `host`, returning the events to send clients.
+    pub async fn ingest(&self, host: &str, event: ServerEvent) -> Vec<ServerEvent> {
+        let events = self.remote.apply(host, event);
+        self.prepare_relayed(events).await
+    }
+
+    /// Stop the sessions of a host that has not reconnected since `connection` closed.
+    pub async fn remote_offline(&self, host: &str, connection: u64) -> Vec<ServerEvent> {
+        let events = self.remote.mark_offline(host, connection);
+        if !events.is_empty() {
+            info!("[Ingest] {} is offline; its sessions are stopped", host);
+        }
+        self.prepare_relayed(events).await
+    }
+
+    /// Drop the relayed events of archived sessions and fill in the prefs of the rest.
+    async fn prepare_relayed(&self, events: Vec<ServerEvent>) -> Vec<ServerEvent> {
+        let mut kept = Vec::with_capacity(events.len());
+        for mut event in events {
+            if let Some(session_id) = broadcast_session_id(&event) {
+                if self.is_archived(session_id).await {
+                    continue;
+                }
+            }
+            self.apply_event_prefs(&mut event).await;
+            kept.push(event);
+        }
+        kept
+    }
+
+    async fn apply_event_prefs(&self, event: &mut ServerEvent) {
+        match event {
+            ServerEvent::SessionDiscovered { session }
+            | ServerEvent::SessionUpdated { session }
+            | ServerEvent::StateChanged { session, .. }
+            | ServerEvent::ContextWarning { session, .. } => self.apply_prefs(session).await,
+            _ => {}
+        }
+    }
+
+    /// Fill in the preference-derived fields of a summary coming from the provider.
+    pub async fn apply_prefs(&self, summary: &mut AgentSessionSummary) {
+        let prefs = self.prefs.get(&summary.session_id).await;
+        apply(summary, &prefs);
+    }
+
+    pub async fn is_muted(&self, session_id: &str) -> bool {
+        self.prefs.get(session_id).await.muted
+    }
+
+    /// Archived sessions are kept off the broadcast channels, as they are kept out of
+    /// `/api/sessions`.
+    pub async fn is_archived(&self, session_id: &str) -> bool {
+        self.prefs.get(session_id).await.archived
+    }
+
+    /// The notification rules of a local session; None for relayed and unknown ones,
+    /// which go by the global rules.
+    pub async fn notify_rules(&self, session_id: &str) -> Option<NotifyRules> {
+        self.provider.notify_rules(session_id).await
+    }
+
+    /// Update a session's preferences. Returns None if the session is unknown.
+    pub async fn update_prefs(
+        &self,
+        session_id: &str,
+        patch: &SessionPrefsPatch,
+    ) -> Option<SessionPrefs> {
+        if !self.has_session(session_id).await {
+            return None;
+        }
+        Some(self.prefs.update(session_id, patch).await)
+    }
+
+    /// Replace a session's note. Returns None if the session is unknown.
+    pub async fn set_note(&self, session_id: &str, note: String) -> Option<SessionPrefs> {
+        if !self.has_session(session_id).await {
+            return None;
+        }
+        Some(self.prefs.set_note(session_id, note).await)
+    }
+
+    /// Replace a session's labels. Returns None if the session is unknown.
+    pub async fn set_labels(&self, session_id: &str, labels: Vec<String>) -> Option<SessionPrefs> {
+        if !self.has_session(session_id).await {
+            return None;
+        }
+        Some(self.prefs.set_labels(session_id, labels).await)
+    }
+
+    /// A session's preferences with `change` applied, without saving them. Returns None
+    /// if the session is unknown.
+    pub async fn plan_prefs(
+        &self,
+        session_id: &str,
+        change: impl FnOnce(&mut SessionPrefs),
+    ) -> Option<SessionPrefs> {
+        if !self.has_session(session_id).await {
+            return None;
+        }
+        Some(self.prefs.preview(session_id, change).await)
+    }
+
+    pub async fn plan_dismiss(&self, session_id: &str) -> Result<DismissPlan, DismissError> {
+        self.provider.plan_dismiss(session_id).await
+    }
+
+    pub async fn dismiss_session(&self, session_id: &str) -> Result<(), DismissError> {
+        self.provider.dismiss_session(session_id).await
+    }
+
+    /// Which Claude process `interrupt_session` would signal. `pid` picks one when
+    /// several processes share the session's working directory.
+    pub async fn plan_interrupt(
+        &self,
+        session_id: &str,
+        pid: Option<u32>,
+        signal: InterruptSignal,
+    ) -> Result<InterruptPlan, InterruptError> {
+        let working_directory = self
+            .provider
+            .working_directory(session_id)
+            .await
+            .ok_or(InterruptError::SessionNotFound)?;
+        let processes = tokio::task::spawn_blocking(claude_processes)
+            .await
+            .ok()
+            .flatten()
+            .ok_or(InterruptError::Unavailable)?;
+        let mut candidates: Vec<u32> = processes
+            .into_iter()
+            .filter(|p| p.cwd == working_directory)
+            .map(|p| p.pid)
+            .collect();
+        candidates.sort_unstable();
+        let pid = match (pid, candidates.as_slice()) {
+            (Some(pid), _) if candidates.contains(&pid) => pid,
+            (Some(_), _) | (None, []) => return Err(InterruptError::NoProcess),
+            (None, [pid]) => *pid,
+            (None, _) => return Err(InterruptError::Ambiguous(candidates)),
+        };
+        Ok(InterruptPlan {
+            session_id: session_id.to_string(),
+            pid,
+            signal: signal.to_string(),
+            working_directory,
+        })
+    }
+
+    /// Signal the Claude process running in a session's working directory, as Ctrl+C
+    /// in its terminal would, and note it in the session's messages. `pid` picks one
+    /// when several processes share the directory. Returns the pid signalled.
+    pub async fn interrupt_session(
+        &self,
+        session_id: &str,
+        pid: Option<u32>,
+        signal: InterruptSignal,
+    ) -> Result<u32, InterruptError> {
+        let pid = self.plan_interrupt(session_id, pid, signal).await?.pid;
+        tokio::task::spawn_blocking(move || signal_claude_process(pid, signal))
+            .await
+            .map_err(|e| InterruptError::Signal(e.to_string()))?
+            .map_err(InterruptError::Signal)?;
+        info!(
+            "[SessionManager] Sent {} to process {} of {}",
+            signal, pid, session_id
+        );
+        let metadata = HashMap::from([
+            ("interrupt".to_string(), serde_json::json!(true)),
+            ("pid".to_string(), serde_json::json!(pid)),
+            ("signal".to_string(), serde_json::json!(signal.to_string())),
+        ]);
+        self.provider
+            .record_message(
+                session_id,
+                MessageType::StateChange,
+                format!(
+                    "Interrupted from the dashboard ({} to process {})",
+                    signal, pid
+                ),
+                metadata,
+            )
+            .await;
+        Ok(pid)
+    }
+
+    /// Which sessions `load_history` would read. Returns None if the project has no logs.
+    pub async fn plan_history(
+        &self,
+        project_path: &str,
+        session_ids: &[String],
+    ) -> Option<HistoryPlan> {
+        let session_ids = self
+            .provider
+            .plan_history(project_path, session_ids)
+            .await?;
+        Some(HistoryPlan {
+            project_path: project_path.to_string(),
+            session_ids,
+        })
+    }
+
+    /// Load old sessions of `project_path`; see `ClaudeCodeProvider::load_history`.
+    pub async fn load_history(
+        &self,
+        project_path: &str,
+        session_ids: &[String],
+    ) -> Option<Vec<AgentSessionSummary>> {
+        let mut sessions = self
+            .provider
+            .load_history(project_path, session_ids)
+            .await?;
+        info!(
+            "[SessionManager] Loaded {} historical sessions of {}",
+            sessions.len(),
+            project_path
+        );
+        for session in &mut sessions {
+            self.apply_prefs(session).await;
+        }
+        Some(sessions)
+    }
+
+    /// What replaying `log` would do; None if it has no entries.
+    pub fn plan_start_replay(&self, log: &str, speed: f64) -> Option<ReplayPlan> {
+        let steps = plan_replay(log, speed);
+        if steps.is_empty() {
+            return None;
+        }
+        let duration: Duration = steps.iter().map(|step| step.delay).sum();
+        Some(ReplayPlan {
+            entry_count: steps.len() as u64,
+            speed,
+            duration_ms: duration.as_millis() as u64,
+        })
+    }
+
+    /// Where local sessions' logs are discovered; replays are read from under it.
+    pub fn projects_dir(&self) -> &std::path::Path {
+        self.provider.projects_dir()
+    }
+
+    /// Replay a recorded JSONL log as a new session at `speed` times the original pace.
+    /// Returns None if the log has no entries.
+    pub async fn start_replay(&self, log: &str, speed: f64) -> Option<String> {
+        let steps = plan_replay(log, speed);
+        if steps.is_empty() {
+            return None;
+        }
+        let count = steps.len();
+        let session_id = self.provider.start_replay(steps).await;
+        info!("[SessionManager] Replaying {} entries as {}", count, session_id);
+        Some(session_id)
+    }
+
+    pub async fn plan_stop_replay(&self, session_id: &str) -> Option<StopReplayPlan> {
+        self.provider.plan_stop_replay(session_id).await
+    }
+
+    pub async fn stop_replay(&self, session_id: &str) -> bool {
+        self.provider.stop_replay(session_id).await
+    }
+
+    pub async fn search_sessions(
+        &self,
+        query: &str,
+        case_sensitive: bool,
+        scopes: &[SearchScope],
+        host: Option<&str>,
+        group_chains: bool,
+    ) -> SearchResponse {
+        let parsed = SearchQuery::parse(query, case_sensitive);
+        let mut results: Vec<SessionSearchResult> = self.provider.search_sessions(&parsed, scopes).await;
+        for result in &mut results {
+            self.apply_prefs(&mut result.session).await;
+        }
+        if scopes.contains(&SearchScope::Note) || scopes.contains(&SearchScope::Labels) {
+            self.search_prefs(&parsed, scopes, &mut results).await;
+            results.sort_by_key(|r| std::cmp::Reverse(r.match_count));
+        }
+        if let Some(host) = host {
+            results.retain(|r| r.session.host == host);
+        }
+        if group_chains {
+            results = merge_chains(results, &self.provider.chain_roots().await);
+        }
         let total_sessions = results.len() as u32;
         SearchResponse {
             query: query.to_string(),
@@ -58,4 +1105,151 @@ impl SessionManager {
             results,
         }
     }
+
+    /// Match notes and labels, merging hits into existing results.
+    async fn search_prefs(
+        &self,
+        query: &SearchQuery,
+        scopes: &[SearchScope],
+        results: &mut Vec<SessionSearchResult>,
+    ) {
+        let limits = self.provider.session_settings().load().text_limits;
+        for (session_id, prefs) in self.prefs.snapshot().await {
+            let mut matches: Vec<SearchMatch> = Vec::new();
+            let make_match = |content: String, scope: SearchScope| SearchMatch {
+                content,
+                scope,
+                message_role: MessageRole::System,
+                message_type: MessageType::Text,
+                timestamp: String::new(),
+                truncated_source: false,
+                message_id: None,
+                message_index: None,
+                message_trimmed: false,
+                task: None,
+            };
+            if scopes.contains(&SearchScope::Note) {
+                if let Some(span) = query.find(&prefs.note) {
+                    matches.push(make_match(
+                        make_snippet(&prefs.note, span, limits.snippet_context),
+                        SearchScope::Note,
+                    ));
+                }
+            }
+            if scopes.contains(&SearchScope::Labels) {
+                for label in prefs.labels.iter().filter(|l| query.find(l).is_some()) {
+                    matches.push(make_match(label.clone(), SearchScope::Labels));
+                }
+            }
+            if matches.is_empty() {
+                continue;
+            }
+
+            if let Some(existing) = results.iter_mut().find(|r| r.session.session_id == session_id) {
+                existing.match_count += matches.len() as u32;
+                let room = 3usize.saturating_sub(existing.matches.len());
+                for mut m in matches.into_iter().take(room) {
+                    m.timestamp = existing.session.started_at.clone();
+                    existing.matches.push(m);
+                }
+            } else if let Some(session) = self.get_session_summary(&session_id).await {
+                let match_count = matches.len() as u32;
+                matches.truncate(3);
+                for m in &mut matches {
+                    m.timestamp = session.started_at.clone();
+                }
+                results.push(SessionSearchResult {
+                    session,
+                    match_count,
+                    matches,
+                    earlier_in_chain: Vec::new(),
+                });
+            }
+        }
+    }
+}
+
+/// The sessions with each resume chain (by `roots`, as `chain_roots` gives them) cut
+/// down to its latest session, in the order they came.
+fn latest_of_chains(
+    sessions: Vec<AgentSessionSummary>,
+    roots: &HashMap<String, String>,
+) -> Vec<AgentSessionSummary> {
+    let mut latest: HashMap<&str, &AgentSessionSummary> = HashMap::new();
+    for session in &sessions {
+        if let Some(root) = roots.get(&session.session_id) {
+            let entry = latest.entry(root.as_str()).or_insert(session);
+            if session.started_at > entry.started_at {
+                *entry = session;
+            }
+        }
+    }
+    let keep: std::collections::HashSet<String> =
+        latest.values().map(|s| s.session_id.clone()).collect();
+    sessions
+        .into_iter()
+        .filter(|s| !roots.contains_key(&s.session_id) || keep.contains(&s.session_id))
+        .collect()
+}
+
+/// One result per resume chain: the latest session that matched, with the results
+/// of the chain's earlier sessions nested under it and their matches counted in. A
+/// chain ranks by its total matches.
+fn merge_chains(
+    results: Vec<SessionSearchResult>,
+    roots: &HashMap<String, String>,
+) -> Vec<SessionSearchResult> {
+    let mut groups: Vec<Vec<SessionSearchResult>> = Vec::new();
+    let mut group_of: HashMap<&str, usize> = HashMap::new();
+    for result in results {
+        match roots.get(&result.session.session_id) {
+            Some(root) => match group_of.get(root.as_str()) {
+                Some(&i) => groups[i].push(result),
+                None => {
+                    group_of.insert(root, groups.len());
+                    groups.push(vec![result]);
+                }
+            },
+            None => groups.push(vec![result]),
+        }
+    }
+    let mut merged: Vec<SessionSearchResult> = groups
+        .into_iter()
+        .map(|mut group| {
+            group.sort_by(|a, b| a.session.started_at.cmp(&b.session.started_at));
+            let mut latest = group.pop().expect("groups are never empty");
+            latest.match_count += group.iter().map(|r| r.match_count).sum::<u32>();
+            latest.earlier_in_chain = group;
+            latest
+        })
+        .collect();
+    merged.sort_by_key(|r| std::cmp::Reverse(r.match_count));
+    merged
+}
+
+fn apply(summary: &mut AgentSessionSummary, prefs: &SessionPrefs) {
+    summary.pinned = prefs.pinned;
+    summary.note = (!prefs.note.is_empty()).then(|| prefs.note.clone());
+    summary.labels = prefs.labels.clone();
+    summary.summary = prefs.summary.clone();
+}
+
+/// The session an event tells clients about. Removals are left out: a client that
+/// still lists an archived session should drop it.
+fn broadcast_session_id(event: &ServerEvent) -> Option<&str> {
+    match event {
+        ServerEvent::SessionDiscovered { session } | ServerEvent::SessionUpdated { session } => {
+            Some(&session.session_id)
+        }
+        ServerEvent::StateChanged { session_id, .. }
+        | ServerEvent::NewMessage { session_id, .. }
+        | ServerEvent::MessagesAppended { session_id, .. }
+        | ServerEvent::MessagesInit { session_id, .. }
+        | ServerEvent::UsageUpdated { session_id, .. }
+        | ServerEvent::GitStatusUpdated { session_id, .. }
+        | ServerEvent::ContextWarning { session_id, .. }
+        | ServerEvent::ModelChanged { session_id, .. }
+        | ServerEvent::LogDeleted { session_id, .. } => Some(session_id),
+        _ => None,
+    }
 }
@@ -1 +1,12 @@
+pub mod archive;
+pub mod day_digest;
+pub mod diff_snapshots;
+pub mod journal;
 pub mod manager;
+pub mod prefs;
+pub mod project_usage;
+pub mod read_marks;
+pub mod remote;
+pub mod summarizer;
+pub mod usage_report;
+pub mod watchdog;
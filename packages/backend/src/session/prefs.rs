@@ -0,0 +1,317 @@
+use crate::types::{GeneratedSummary, SessionPrefs, SessionPrefsPatch};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+const PREFS_FILE: &str = "session-prefs.json";
+pub const MAX_NOTE_LEN: usize = 2000;
+pub const MAX_LABELS: usize = 20;
+pub const MAX_LABEL_LEN: usize = 32;
+
+/// Trim a note and check its length (in characters).
+pub fn normalize_note(note: &str) -> Result<String, String> {
+    let note = note.trim();
+    if note.chars().count() > MAX_NOTE_LEN {
+        return Err(format!("Note must be at most {} characters", MAX_NOTE_LEN));
+    }
+    Ok(note.to_string())
+}
+
+/// Trim labels, drop empties and duplicates (keeping first occurrence), and check limits.
+pub fn normalize_labels(labels: &[String]) -> Result<Vec<String>, String> {
+    let mut out: Vec<String> = Vec::new();
+    for label in labels {
+        let label = label.trim();
+        if label.is_empty() || out.iter().any(|l| l == label) {
+            continue;
+        }
+        if label.chars().count() > MAX_LABEL_LEN {
+            return Err(format!("Labels must be at most {} characters", MAX_LABEL_LEN));
+        }
+        out.push(label.to_string());
+    }
+    if out.len() > MAX_LABELS {
+        return Err(format!("At most {} labels are allowed", MAX_LABELS));
+    }
+    Ok(out)
+}
+
+/// Platform default directory for dashboard-owned state.
+pub fn default_data_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| dirs::home_dir().unwrap_or_else(|| PathBuf::from(".")))
+        .join("agents-dashboard")
+}
+
+/// Per-session preferences (muted / pinned / archived, note, labels) persisted as a JSON map
+/// keyed by session id.
+pub struct PrefsStore {
+    path: Option<PathBuf>,
+    prefs: RwLock<HashMap<String, SessionPrefs>>,
+}
+
+impl PrefsStore {
+    /// Load preferences from `<data_dir>/session-prefs.json`.
+    pub fn load_in(data_dir: &Path) -> Self {
+        Self::load(data_dir.join(PREFS_FILE))
+    }
+
+    pub fn load(path: PathBuf) -> Self {
+        let prefs = match std::fs::read_to_string(&path) {
+            Ok(text) => match serde_json::from_str::<HashMap<String, SessionPrefs>>(&text) {
+                Ok(map) => {
+                    info!("[Prefs] Loaded {} session prefs from {}", map.len(), path.display());
+                    map
+                }
+                Err(e) => {
+                    warn!("[Prefs] Ignoring unreadable {}: {}", path.display(), e);
+                    HashMap::new()
+                }
+            },
+            Err(_) => HashMap::new(),
+        };
+        Self {
+            path: Some(path),
+            prefs: RwLock::new(prefs),
+        }
+    }
+
+    /// A store that is never written to disk.
+    #[cfg(test)]
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            prefs: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub async fn get(&self, session_id: &str) -> SessionPrefs {
+        self.prefs
+            .read()
+            .await
+            .get(session_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub async fn snapshot(&self) -> HashMap<String, SessionPrefs> {
+        self.prefs.read().await.clone()
+    }
+
+    /// Apply a partial update and persist the result.
+    pub async fn update(&self, session_id: &str, patch: &SessionPrefsPatch) -> SessionPrefs {
+        self.modify(session_id, |entry| patch.apply(entry)).await
+    }
+
+    /// Replace the note. Expects a value already passed through `normalize_note`.
+    pub async fn set_note(&self, session_id: &str, note: String) -> SessionPrefs {
+        self.modify(session_id, |entry| entry.note = note).await
+    }
+
+    /// Replace the labels. Expects a value already passed through `normalize_labels`.
+    pub async fn set_labels(&self, session_id: &str, labels: Vec<String>) -> SessionPrefs {
+        self.modify(session_id, |entry| entry.labels = labels).await
+    }
+
+    pub async fn set_summary(&self, session_id: &str, summary: GeneratedSummary) -> SessionPrefs {
+        self.modify(session_id, |entry| entry.summary = Some(summary))
+            .await
+    }
+
+    /// A session's prefs with `f` applied, without keeping the change.
+    pub async fn preview(
+        &self,
+        session_id: &str,
+        f: impl FnOnce(&mut SessionPrefs),
+    ) -> SessionPrefs {
+        let mut entry = self.get(session_id).await;
+        f(&mut entry);
+        entry
+    }
+
+    async fn modify(&self, session_id: &str, f: impl FnOnce(&mut SessionPrefs)) -> SessionPrefs {
+        let (updated, snapshot) = {
+            let mut prefs = self.prefs.write().await;
+            let mut entry = prefs.get(session_id).cloned().unwrap_or_default();
+            f(&mut entry);
+            // Keep the file small: empty entries are the default anyway.
+            if entry == SessionPrefs::default() {
+                prefs.remove(session_id);
+            } else {
+                prefs.insert(session_id.to_string(), entry.clone());
+            }
+            (entry, prefs.clone())
+        };
+
+        if let Some(path) = &self.path {
+            if let Err(e) = write_json(path, &snapshot).await {
+                warn!("[Prefs] Failed to save {}: {}", path.display(), e);
+            }
+        }
+        updated
+    }
+}
+
+async fn write_json(path: &Path, prefs: &HashMap<String, SessionPrefs>) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let json = serde_json::to_string_pretty(prefs).map_err(std::io::Error::other)?;
+    // Write to a temp file and rename so a crash never leaves a truncated file.
+    let tmp = path.with_extension("json.tmp");
+    tokio::fs::write(&tmp, json).await?;
+    tokio::fs::rename(&tmp, path).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path() -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("agents-dashboard-prefs-{}", uuid::Uuid::new_v4()))
+            .join(PREFS_FILE)
+    }
+
+    #[tokio::test]
+    async fn test_default_prefs_for_unknown_session() {
+        let store = PrefsStore::in_memory();
+        assert_eq!(store.get("nope").await, SessionPrefs::default());
+    }
+
+    #[tokio::test]
+    async fn test_partial_update_keeps_other_flags() {
+        let store = PrefsStore::in_memory();
+        store
+            .update(
+                "s1",
+                &SessionPrefsPatch {
+                    pinned: Some(true),
+                    ..Default::default()
+                },
+            )
+            .await;
+        let prefs = store
+            .update(
+                "s1",
+                &SessionPrefsPatch {
+                    muted: Some(true),
+                    ..Default::default()
+                },
+            )
+            .await;
+        assert!(prefs.pinned);
+        assert!(prefs.muted);
+        assert!(!prefs.archived);
+    }
+
+    #[tokio::test]
+    async fn test_all_false_entry_is_dropped() {
+        let store = PrefsStore::in_memory();
+        store
+            .update(
+                "s1",
+                &SessionPrefsPatch {
+                    archived: Some(true),
+                    ..Default::default()
+                },
+            )
+            .await;
+        store
+            .update(
+                "s1",
+                &SessionPrefsPatch {
+                    archived: Some(false),
+                    ..Default::default()
+                },
+            )
+            .await;
+        assert!(store.snapshot().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_note_and_labels_keep_flags() {
+        let store = PrefsStore::in_memory();
+        store
+            .update(
+                "s1",
+                &SessionPrefsPatch {
+                    pinned: Some(true),
+                    ..Default::default()
+                },
+            )
+            .await;
+        store.set_note("s1", "flaky-test investigation".into()).await;
+        let prefs = store.set_labels("s1", vec!["ci".into()]).await;
+        assert!(prefs.pinned);
+        assert_eq!(prefs.note, "flaky-test investigation");
+        assert_eq!(prefs.labels, vec!["ci".to_string()]);
+
+        store.set_note("s1", String::new()).await;
+        store.set_labels("s1", Vec::new()).await;
+        store
+            .update(
+                "s1",
+                &SessionPrefsPatch {
+                    pinned: Some(false),
+                    ..Default::default()
+                },
+            )
+            .await;
+        assert!(store.snapshot().await.is_empty());
+    }
+
+    #[test]
+    fn test_normalize_labels() {
+        let labels = vec![" ci ".to_string(), "".into(), "ci".into(), "bug".into()];
+        assert_eq!(
+            normalize_labels(&labels).unwrap(),
+            vec!["ci".to_string(), "bug".to_string()]
+        );
+        assert!(normalize_labels(&["x".repeat(MAX_LABEL_LEN + 1)]).is_err());
+        let many: Vec<String> = (0..=MAX_LABELS).map(|i| i.to_string()).collect();
+        assert!(normalize_labels(&many).is_err());
+    }
+
+    #[test]
+    fn test_normalize_note() {
+        assert_eq!(normalize_note("  hi \n").unwrap(), "hi");
+        assert!(normalize_note(&"あ".repeat(MAX_NOTE_LEN)).is_ok());
+        assert!(normalize_note(&"a".repeat(MAX_NOTE_LEN + 1)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_prefs_survive_reload() {
+        let path = temp_path();
+        {
+            let store = PrefsStore::load(path.clone());
+            store
+                .update(
+                    "s1",
+                    &SessionPrefsPatch {
+                        pinned: Some(true),
+                        archived: Some(true),
+                        ..Default::default()
+                    },
+                )
+                .await;
+        }
+        let reloaded = PrefsStore::load(path.clone());
+        let prefs = reloaded.get("s1").await;
+        assert!(prefs.pinned);
+        assert!(prefs.archived);
+        assert!(!prefs.muted);
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_file_is_ignored() {
+        let path = temp_path();
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, "not json").unwrap();
+        let store = PrefsStore::load(path.clone());
+        assert!(store.snapshot().await.is_empty());
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+}
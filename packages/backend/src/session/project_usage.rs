@@ -0,0 +1,587 @@
+use crate::config::ProjectBudget;
+use crate::days;
+use crate::session::usage_report::{self, ReportInput};
+use crate::types::{
+    BudgetPeriod, BudgetStatus, CumulativeUsage, ProjectUsage, ReportPeriod, ToolCount,
+    UsageReport,
+};
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+const PROJECT_USAGE_FILE: &str = "project-usage.json";
+/// Daily costs older than this are dropped; no budget or report period is longer
+/// than a month.
+const KEEP_DAYS: i64 = 62;
+
+/// What one session has cost, as last reported by the provider.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionTally {
+    pub usage: CumulativeUsage,
+    /// Cost incurred on each day (days since the epoch), dated in `timezone`.
+    pub daily_cost: BTreeMap<i64, f64>,
+    // The rest feeds usage reports. Tallies saved before reports existed lack it.
+    #[serde(default)]
+    pub daily_tokens: BTreeMap<i64, u64>,
+    #[serde(default)]
+    pub model: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub started_at: String,
+    #[serde(default)]
+    pub last_activity_at: String,
+    #[serde(default)]
+    pub top_tools: Vec<ToolCount>,
+    /// Zone the latest days were counted in; None in tallies saved before zones
+    /// could be set, which counted in UTC.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    /// Days before this were counted in an earlier zone and are kept as saved.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kept_before: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProjectTally {
+    name: String,
+    sessions: HashMap<String, SessionTally>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Tallies {
+    projects: BTreeMap<String, ProjectTally>,
+    /// Budgets already reported as exceeded: first day of the period and the limit,
+    /// so each is reported once per period (and again if the limit changes).
+    alerted: HashMap<String, (i64, f64)>,
+    /// Day of the first tally recorded with this version, which keeps tokens and
+    /// sessions for reports; None in tallies saved before.
+    #[serde(default)]
+    tracking_since: Option<i64>,
+}
+
+/// Usage per project (keyed by the discovery project path), kept on disk so that
+/// totals include sessions no longer tracked. Each session's tally is replaced
+/// rather than added to, so re-reading logs after a restart counts nothing twice.
+pub struct ProjectUsageStore {
+    path: Option<PathBuf>,
+    tallies: Mutex<Tallies>,
+    budgets: Mutex<Vec<ProjectBudget>>,
+    // Not a lock of its own: set while `tallies` is held, and read before taking it.
+    dirty: AtomicBool,
+}
+
+/// A project went over its budget.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BudgetExceeded {
+    pub project_path: String,
+    pub project_name: String,
+    pub status: BudgetStatus,
+}
+
+impl ProjectUsageStore {
+    /// Load tallies from `<data_dir>/project-usage.json`.
+    pub fn load_in(data_dir: &Path) -> Self {
+        let path = data_dir.join(PROJECT_USAGE_FILE);
+        let tallies = match std::fs::read_to_string(&path) {
+            Ok(text) => match serde_json::from_str::<Tallies>(&text) {
+                Ok(tallies) => {
+                    info!(
+                        "[Projects] Loaded usage for {} projects from {}",
+                        tallies.projects.len(),
+                        path.display()
+                    );
+                    tallies
+                }
+                Err(e) => {
+                    warn!("[Projects] Ignoring unreadable {}: {}", path.display(), e);
+                    Tallies::default()
+                }
+            },
+            Err(_) => Tallies::default(),
+        };
+        Self::with_tallies(Some(path), tallies)
+    }
+
+    /// A store that is never written to disk.
+    #[cfg(test)]
+    pub fn in_memory() -> Self {
+        Self::with_tallies(None, Tallies::default())
+    }
+
+    fn with_tallies(path: Option<PathBuf>, tallies: Tallies) -> Self {
+        Self {
+            path,
+            tallies: Mutex::new(tallies),
+            budgets: Mutex::new(Vec::new()),
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    pub fn set_budgets(&self, budgets: Vec<ProjectBudget>) {
+        *self.budgets.lock().unwrap() = budgets;
+    }
+
+    /// Replace a session's tally, whose days were counted in `tz`. Returns the
+    /// project's budget status if this update took it over budget for the first
+    /// time this period.
+    pub fn record(
+        &self,
+        project_path: &str,
+        project_name: &str,
+        session_id: &str,
+        mut tally: SessionTally,
+        now: DateTime<Utc>,
+        tz: Tz,
+    ) -> Option<BudgetExceeded> {
+        let today = days::day_index(days::today(now, tz));
+
+        let mut tallies = self.tallies.lock().unwrap();
+        if tallies.tracking_since.is_none() {
+            tallies.tracking_since = Some(today);
+            self.dirty.store(true, Ordering::Release);
+        }
+        let project = tallies
+            .projects
+            .entry(project_path.to_string())
+            .or_default();
+        project.name = project_name.to_string();
+        tally.timezone = Some(tz.name().to_string());
+        if let Some(saved) = project.sessions.get(session_id) {
+            keep_counted_days(&mut tally, saved, today);
+        }
+        tally.daily_cost = tally.daily_cost.split_off(&(today - KEEP_DAYS));
+        tally.daily_tokens = tally.daily_tokens.split_off(&(today - KEEP_DAYS));
+        if project.sessions.get(session_id) == Some(&tally) {
+            return None;
+        }
+        project.sessions.insert(session_id.to_string(), tally);
+        self.dirty.store(true, Ordering::Release);
+
+        let status = self.budget_status(project_path, project, now, tz)?;
+        if !status.exceeded {
+            return None;
+        }
+        let key = (
+            period_start(status.period, days::today(now, tz)),
+            status.limit_usd,
+        );
+        if tallies.alerted.get(project_path) == Some(&key) {
+            return None;
+        }
+        tallies.alerted.insert(project_path.to_string(), key);
+        Some(BudgetExceeded {
+            project_path: project_path.to_string(),
+            project_name: project_name.to_string(),
+            status,
+        })
+    }
+
+    /// Add usage to a session's tally rather than replace it, for spending no log
+    /// keeps a running total of, such as the dashboard's own API calls.
+    pub fn add(
+        &self,
+        project_path: &str,
+        session_id: &str,
+        usage: &CumulativeUsage,
+        model: &str,
+        now: DateTime<Utc>,
+        tz: Tz,
+    ) {
+        let mut tally = self
+            .tallies
+            .lock()
+            .unwrap()
+            .projects
+            .get(project_path)
+            .and_then(|project| project.sessions.get(session_id))
+            .cloned()
+            .unwrap_or_default();
+        let today = days::day_index(days::today(now, tz));
+        let tokens = usage.input_tokens
+            + usage.output_tokens
+            + usage.cache_read_tokens
+            + usage.cache_creation_tokens;
+        tally.usage.input_tokens += usage.input_tokens;
+        tally.usage.output_tokens += usage.output_tokens;
+        tally.usage.cache_read_tokens += usage.cache_read_tokens;
+        tally.usage.cache_creation_tokens += usage.cache_creation_tokens;
+        tally.usage.estimated_cost += usage.estimated_cost;
+        *tally.daily_cost.entry(today).or_default() += usage.estimated_cost;
+        *tally.daily_tokens.entry(today).or_default() += tokens;
+        tally.model = model.to_string();
+        if tally.started_at.is_empty() {
+            tally.started_at = now.to_rfc3339();
+        }
+        tally.last_activity_at = now.to_rfc3339();
+        self.record(project_path, project_path, session_id, tally, now, tz);
+    }
+
+    /// Every project seen, most expensive first, with periods in `tz`.
+    pub fn snapshot(&self, now: DateTime<Utc>, tz: Tz) -> Vec<ProjectUsage> {
+        let tallies = self.tallies.lock().unwrap();
+        let today = days::today(now, tz);
+        let mut projects: Vec<ProjectUsage> = tallies
+            .projects
+            .iter()
+            .map(|(path, project)| ProjectUsage {
+                project_path: path.clone(),
+                project_name: project.name.clone(),
+                sessions: project.sessions.len(),
+                usage: total_usage(project),
+                cost_today: cost_since(project, period_start(BudgetPeriod::Day, today)),
+                cost_this_week: cost_since(project, period_start(BudgetPeriod::Week, today)),
+                cost_this_month: cost_since(project, period_start(BudgetPeriod::Month, today)),
+                budget: self.budget_status(path, project, now, tz),
+            })
+            .collect();
+        projects.sort_by(|a, b| b.usage.estimated_cost.total_cmp(&a.usage.estimated_cost));
+        projects
+    }
+
+    /// Usage over the `period` ending today in `tz`.
+    pub fn report(&self, period: ReportPeriod, now: DateTime<Utc>, tz: Tz) -> UsageReport {
+        let tallies = self.tallies.lock().unwrap();
+        let inputs: Vec<ReportInput> = tallies
+            .projects
+            .iter()
+            .flat_map(|(path, project)| {
+                project
+                    .sessions
+                    .iter()
+                    .map(move |(session_id, tally)| ReportInput {
+                        project_path: path,
+                        project_name: &project.name,
+                        session_id,
+                        tally,
+                    })
+            })
+            .collect();
+        usage_report::build(&inputs, tallies.tracking_since, period, now, tz)
+    }
+
+    /// A project's name and the tallies of its sessions, or None if nothing was
+    /// recorded for it.
+    pub fn project(&self, project_path: &str) -> Option<(String, Vec<(String, SessionTally)>)> {
+        let tallies = self.tallies.lock().unwrap();
+        let project = tallies.projects.get(project_path)?;
+        let sessions = project
+            .sessions
+            .iter()
+            .map(|(id, tally)| (id.clone(), tally.clone()))
+            .collect();
+        Some((project.name.clone(), sessions))
+    }
+
+    /// Write the tallies if anything changed since the last save.
+    pub async fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        if !self.dirty.swap(false, Ordering::AcqRel) {
+            return;
+        }
+        let json = serde_json::to_string(&*self.tallies.lock().unwrap());
+        let result = match json {
+            Ok(json) => write_file(path, json).await,
+            Err(e) => Err(std::io::Error::other(e)),
+        };
+        if let Err(e) = result {
+            warn!("[Projects] Failed to save {}: {}", path.display(), e);
+            self.dirty.store(true, Ordering::Release);
+        }
+    }
+
+    fn budget_status(
+        &self,
+        project_path: &str,
+        project: &ProjectTally,
+        now: DateTime<Utc>,
+        tz: Tz,
+    ) -> Option<BudgetStatus> {
+        let budgets = self.budgets.lock().unwrap();
+        let budget = budgets.iter().find(|b| b.project == project_path)?;
+        let spent_usd = match budget.period {
+            BudgetPeriod::Total => total_usage(project).estimated_cost,
+            period => cost_since(project, period_start(period, days::today(now, tz))),
+        };
+        Some(BudgetStatus {
+            period: budget.period,
+            limit_usd: budget.limit_usd,
+            spent_usd,
+            exceeded: spent_usd > budget.limit_usd,
+        })
+    }
+}
+
+/// First day of the period containing `today`, in days since the epoch.
+fn period_start(period: BudgetPeriod, today: NaiveDate) -> i64 {
+    let start = match period {
+        BudgetPeriod::Day => today,
+        BudgetPeriod::Week => today.week(chrono::Weekday::Mon).first_day(),
+        BudgetPeriod::Month => today.with_day(1).unwrap_or(today),
+        BudgetPeriod::Total => return i64::MIN,
+    };
+    days::day_index(start)
+}
+
+/// Days a saved tally counted in another zone keep their dates: from a zone change
+/// on, days before it come from `saved` instead of the provider, which dates
+/// whatever it reads again (after a restart) in the current zone.
+fn keep_counted_days(tally: &mut SessionTally, saved: &SessionTally, today: i64) {
+    let same_zone =
+        saved.timezone.as_deref().unwrap_or("UTC") == tally.timezone.as_deref().unwrap_or("UTC");
+    tally.kept_before = if same_zone {
+        saved.kept_before
+    } else {
+        Some(today)
+    };
+    if let Some(before) = tally.kept_before {
+        splice_days(&mut tally.daily_cost, &saved.daily_cost, before);
+        splice_days(&mut tally.daily_tokens, &saved.daily_tokens, before);
+    }
+}
+
+/// `days` from `before` on, after `saved` up to it.
+fn splice_days<T: Copy>(days: &mut BTreeMap<i64, T>, saved: &BTreeMap<i64, T>, before: i64) {
+    let later = days.split_off(&before);
+    *days = saved
+        .range(..before)
+        .map(|(day, value)| (*day, *value))
+        .collect();
+    days.extend(later);
+}
+
+fn cost_since(project: &ProjectTally, from_day: i64) -> f64 {
+    project
+        .sessions
+        .values()
+        .flat_map(|session| session.daily_cost.range(from_day..).map(|(_, cost)| cost))
+        .sum()
+}
+
+fn total_usage(project: &ProjectTally) -> CumulativeUsage {
+    let mut total = CumulativeUsage::default();
+    for session in project.sessions.values() {
+        total.input_tokens += session.usage.input_tokens;
+        total.output_tokens += session.usage.output_tokens;
+        total.cache_read_tokens += session.usage.cache_read_tokens;
+        total.cache_creation_tokens += session.usage.cache_creation_tokens;
+        total.estimated_cost += session.usage.estimated_cost;
+    }
+    total
+}
+
+async fn write_file(path: &Path, json: String) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    // Write to a temp file and rename so a crash never leaves a truncated file.
+    let tmp = path.with_extension("json.tmp");
+    tokio::fs::write(&tmp, json).await?;
+    tokio::fs::rename(&tmp, path).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::Tz::UTC;
+
+    fn now() -> DateTime<Utc> {
+        // A Wednesday.
+        "2025-01-15T12:00:00Z".parse().unwrap()
+    }
+
+    fn day(date: &str) -> i64 {
+        days::day_index(date.parse().unwrap())
+    }
+
+    fn tally(days: &[(&str, f64)]) -> SessionTally {
+        SessionTally {
+            usage: CumulativeUsage {
+                output_tokens: 100,
+                estimated_cost: days.iter().map(|(_, cost)| cost).sum(),
+                ..Default::default()
+            },
+            daily_cost: days.iter().map(|(date, cost)| (day(date), *cost)).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn budget(period: BudgetPeriod, limit_usd: f64) -> ProjectBudget {
+        ProjectBudget {
+            project: "/work/billing".to_string(),
+            limit_usd,
+            period,
+        }
+    }
+
+    #[test]
+    fn test_totals_span_sessions_and_periods() {
+        let store = ProjectUsageStore::in_memory();
+        let record = |session_id, days: &[(&str, f64)]| {
+            store.record(
+                "/work/billing",
+                "billing",
+                session_id,
+                tally(days),
+                now(),
+                UTC,
+            )
+        };
+        record("s1", &[("2024-12-20", 5.0), ("2025-01-13", 2.0)]);
+        record("s2", &[("2025-01-15", 1.0)]);
+        // Re-reported after a restart: replaced, not added.
+        record("s2", &[("2025-01-15", 1.5)]);
+        store.record(
+            "/work/site",
+            "site",
+            "s3",
+            tally(&[("2025-01-15", 0.5)]),
+            now(),
+            UTC,
+        );
+
+        let projects = store.snapshot(now(), UTC);
+        assert_eq!(projects.len(), 2);
+        let billing = &projects[0];
+        assert_eq!(billing.project_path, "/work/billing");
+        assert_eq!(billing.sessions, 2);
+        assert_eq!(billing.usage.output_tokens, 200);
+        assert_eq!(billing.usage.estimated_cost, 8.5);
+        assert_eq!(billing.cost_today, 1.5);
+        assert_eq!(billing.cost_this_week, 3.5);
+        assert_eq!(billing.cost_this_month, 3.5);
+        assert!(billing.budget.is_none());
+    }
+
+    #[test]
+    fn test_added_usage_accumulates() {
+        let store = ProjectUsageStore::in_memory();
+        let usage = CumulativeUsage {
+            input_tokens: 1_000,
+            output_tokens: 100,
+            estimated_cost: 0.25,
+            ..Default::default()
+        };
+        store.add("dashboard", "s1", &usage, "claude-haiku", now(), UTC);
+        store.add("dashboard", "s1", &usage, "claude-haiku", now(), UTC);
+
+        let projects = store.snapshot(now(), UTC);
+        assert_eq!(projects[0].project_name, "dashboard");
+        assert_eq!(projects[0].sessions, 1);
+        assert_eq!(projects[0].usage.input_tokens, 2_000);
+        assert_eq!(projects[0].cost_today, 0.5);
+    }
+
+    #[test]
+    fn test_budget_exceeded_once_per_period() {
+        let store = ProjectUsageStore::in_memory();
+        store.set_budgets(vec![budget(BudgetPeriod::Week, 3.0)]);
+        let record = |session_id, cost| {
+            store.record(
+                "/work/billing",
+                "billing",
+                session_id,
+                tally(&[("2025-01-14", cost)]),
+                now(),
+                UTC,
+            )
+        };
+        assert!(record("s1", 2.0).is_none());
+        let exceeded = record("s2", 1.5).unwrap();
+        assert_eq!(exceeded.project_path, "/work/billing");
+        assert_eq!(exceeded.status.spent_usd, 3.5);
+        assert!(record("s2", 2.5).is_none());
+
+        // A new limit is a new budget.
+        store.set_budgets(vec![budget(BudgetPeriod::Week, 4.0)]);
+        assert!(record("s2", 3.0).is_some());
+
+        let status = store.snapshot(now(), UTC)[0].budget.clone().unwrap();
+        assert!(status.exceeded);
+        assert_eq!(status.limit_usd, 4.0);
+    }
+
+    #[test]
+    fn test_zone_change_keeps_counted_days() {
+        let store = ProjectUsageStore::in_memory();
+        let tokyo = chrono_tz::Tz::Asia__Tokyo;
+        // 01:00 on the 16th in Tokyo, still the 15th in UTC.
+        let late: DateTime<Utc> = "2025-01-15T16:00:00Z".parse().unwrap();
+        let record = |days: &[(&str, f64)], tz| {
+            store.record("/work/billing", "billing", "s1", tally(days), late, tz);
+        };
+        let days = || {
+            let (_, sessions) = store.project("/work/billing").unwrap();
+            let tally = &sessions[0].1;
+            (tally.daily_cost.clone(), tally.kept_before)
+        };
+        record(
+            &[
+                ("2025-01-13", 1.0),
+                ("2025-01-14", 2.0),
+                ("2025-01-16", 0.5),
+            ],
+            tokyo,
+        );
+        assert_eq!(store.snapshot(late, tokyo)[0].cost_today, 0.5);
+        assert_eq!(days().1, None);
+
+        // Read again after switching to UTC: the days counted in Tokyo stay put.
+        let again = [
+            ("2025-01-12", 1.0),
+            ("2025-01-14", 2.0),
+            ("2025-01-15", 0.5),
+        ];
+        record(&again, UTC);
+        let expected = |today: f64| {
+            BTreeMap::from([
+                (day("2025-01-13"), 1.0),
+                (day("2025-01-14"), 2.0),
+                (day("2025-01-15"), today),
+            ])
+        };
+        assert_eq!(days(), (expected(0.5), Some(day("2025-01-15"))));
+        let again = [
+            ("2025-01-12", 1.0),
+            ("2025-01-14", 2.0),
+            ("2025-01-15", 0.75),
+        ];
+        record(&again, UTC);
+        assert_eq!(days(), (expected(0.75), Some(day("2025-01-15"))));
+        assert_eq!(store.snapshot(late, UTC)[0].cost_today, 0.75);
+    }
+
+    #[tokio::test]
+    async fn test_persisted_across_restarts() {
+        let dir = std::env::temp_dir().join(format!(
+            "agents-dashboard-projects-{}",
+            uuid::Uuid::new_v4()
+        ));
+        let store = ProjectUsageStore::load_in(&dir);
+        store.record(
+            "/work/billing",
+            "billing",
+            "s1",
+            tally(&[("2025-01-15", 2.0)]),
+            now(),
+            UTC,
+        );
+        store.save().await;
+
+        let reloaded = ProjectUsageStore::load_in(&dir);
+        let projects = reloaded.snapshot(now(), UTC);
+        assert_eq!(projects[0].usage.estimated_cost, 2.0);
+        assert_eq!(projects[0].cost_today, 2.0);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
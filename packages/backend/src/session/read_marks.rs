@@ -0,0 +1,213 @@
+//! How far each dashboard client has read into each session. Clients name
+//! themselves in `client:hello`, so what they acked survives a reconnect or a
+//! server restart.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+const READ_MARKS_FILE: &str = "read-marks.json";
+pub const MAX_CLIENT_ID_LEN: usize = 64;
+/// Clients that haven't acked anything for longest are forgotten beyond this.
+const MAX_CLIENTS: usize = 32;
+/// Sessions acked longest ago are forgotten beyond this, per client.
+const MAX_MARKS_PER_CLIENT: usize = 1000;
+
+/// The last message a client has seen in a session.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadMark {
+    pub message_id: String,
+    /// The message's own timestamp, for when it's no longer in memory.
+    pub timestamp: String,
+    /// When the client acked it (ms since the epoch).
+    #[serde(default)]
+    pub acked_at: i64,
+}
+
+/// Client ids become keys of the saved file; keep them short and plain.
+pub fn valid_client_id(client_id: &str) -> bool {
+    !client_id.is_empty()
+        && client_id.len() <= MAX_CLIENT_ID_LEN
+        && client_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Read marks persisted as a JSON map of client id to session id to mark.
+pub struct ReadMarkStore {
+    path: Option<PathBuf>,
+    clients: RwLock<HashMap<String, HashMap<String, ReadMark>>>,
+}
+
+impl ReadMarkStore {
+    /// Load read marks from `<data_dir>/read-marks.json`.
+    pub fn load_in(data_dir: &Path) -> Self {
+        Self::load(data_dir.join(READ_MARKS_FILE))
+    }
+
+    pub fn load(path: PathBuf) -> Self {
+        let clients = match std::fs::read_to_string(&path) {
+            Ok(text) => {
+                match serde_json::from_str::<HashMap<String, HashMap<String, ReadMark>>>(&text) {
+                    Ok(map) => {
+                        info!(
+                            "[ReadMarks] Loaded marks of {} clients from {}",
+                            map.len(),
+                            path.display()
+                        );
+                        map
+                    }
+                    Err(e) => {
+                        warn!("[ReadMarks] Ignoring unreadable {}: {}", path.display(), e);
+                        HashMap::new()
+                    }
+                }
+            }
+            Err(_) => HashMap::new(),
+        };
+        Self {
+            path: Some(path),
+            clients: RwLock::new(clients),
+        }
+    }
+
+    /// A store that is never written to disk.
+    #[cfg(test)]
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            clients: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub async fn get(&self, client_id: &str) -> HashMap<String, ReadMark> {
+        self.clients
+            .read()
+            .await
+            .get(client_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Record a mark and persist the result.
+    pub async fn set(&self, client_id: &str, session_id: &str, mark: ReadMark) {
+        let snapshot = {
+            let mut clients = self.clients.write().await;
+            let marks = clients.entry(client_id.to_string()).or_default();
+            marks.insert(session_id.to_string(), mark);
+            if marks.len() > MAX_MARKS_PER_CLIENT {
+                let oldest = marks
+                    .iter()
+                    .min_by_key(|(_, mark)| mark.acked_at)
+                    .map(|(id, _)| id.clone());
+                if let Some(oldest) = oldest {
+                    marks.remove(&oldest);
+                }
+            }
+            if clients.len() > MAX_CLIENTS {
+                let idlest = clients
+                    .iter()
+                    .min_by_key(|(_, marks)| marks.values().map(|m| m.acked_at).max())
+                    .map(|(id, _)| id.clone());
+                if let Some(idlest) = idlest {
+                    clients.remove(&idlest);
+                }
+            }
+            clients.clone()
+        };
+
+        if let Some(path) = &self.path {
+            if let Err(e) = write_json(path, &snapshot).await {
+                warn!("[ReadMarks] Failed to save {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+async fn write_json(
+    path: &Path,
+    clients: &HashMap<String, HashMap<String, ReadMark>>,
+) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let json = serde_json::to_string(clients).map_err(std::io::Error::other)?;
+    // Write to a temp file and rename so a crash never leaves a truncated file.
+    let tmp = path.with_extension("json.tmp");
+    tokio::fs::write(&tmp, json).await?;
+    tokio::fs::rename(&tmp, path).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mark(message_id: &str, timestamp: &str, acked_at: i64) -> ReadMark {
+        ReadMark {
+            message_id: message_id.to_string(),
+            timestamp: timestamp.to_string(),
+            acked_at,
+        }
+    }
+
+    #[test]
+    fn test_valid_client_id() {
+        assert!(valid_client_id("tab-3f2a_01"));
+        assert!(!valid_client_id(""));
+        assert!(!valid_client_id("../etc"));
+        assert!(!valid_client_id(&"a".repeat(MAX_CLIENT_ID_LEN + 1)));
+    }
+
+    #[tokio::test]
+    async fn test_marks_survive_reload() {
+        let path = std::env::temp_dir()
+            .join(format!(
+                "agents-dashboard-read-marks-{}",
+                uuid::Uuid::new_v4()
+            ))
+            .join(READ_MARKS_FILE);
+        let store = ReadMarkStore::load(path.clone());
+        store
+            .set("laptop", "s1", mark("m1", "2025-01-01T10:00:00Z", 1))
+            .await;
+        store
+            .set("laptop", "s1", mark("m2", "2025-01-01T10:01:00Z", 2))
+            .await;
+        store
+            .set("phone", "s2", mark("m7", "2025-01-01T11:00:00Z", 3))
+            .await;
+
+        let reloaded = ReadMarkStore::load(path.clone());
+        let laptop = reloaded.get("laptop").await;
+        assert_eq!(laptop.len(), 1);
+        assert_eq!(laptop["s1"].message_id, "m2");
+        assert_eq!(reloaded.get("phone").await["s2"].message_id, "m7");
+        assert!(reloaded.get("tablet").await.is_empty());
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_oldest_marks_are_dropped() {
+        let store = ReadMarkStore::in_memory();
+        for i in 0..=MAX_MARKS_PER_CLIENT as i64 {
+            store
+                .set("laptop", &format!("s{}", i), mark("m", "", i))
+                .await;
+        }
+        let marks = store.get("laptop").await;
+        assert_eq!(marks.len(), MAX_MARKS_PER_CLIENT);
+        assert!(!marks.contains_key("s0"));
+
+        for i in 0..MAX_CLIENTS as i64 {
+            store
+                .set(&format!("tab{}", i), "s1", mark("m", "", 10_000 + i))
+                .await;
+        }
+        // "laptop" acked longest ago.
+        assert!(store.get("laptop").await.is_empty());
+        assert_eq!(store.get("tab0").await.len(), 1);
+    }
+}
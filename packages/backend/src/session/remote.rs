@@ -0,0 +1,555 @@
+//! Sessions relayed to `/ingest` by other instances. Their ids are prefixed with
+//! the sending host (`laptop:<id>`) so sessions from different machines never
+//! collide with each other or with this instance's own.
+
+use crate::providers::claude_code::timestamps::normalize_timestamp;
+use crate::providers::claude_code::LOG_DELETED;
+use crate::types::{AgentMessage, AgentSessionSummary, AgentStateType, ServerEvent};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How long a host may stay disconnected before its sessions are shown as stopped.
+pub const OFFLINE_TIMEOUT: Duration = Duration::from_secs(60);
+const MAX_HOST_LEN: usize = 64;
+/// Messages kept per relayed session, like the provider keeps for its own.
+const MAX_MESSAGES: usize = 500;
+
+/// Host names are used in session ids, so they are kept to URL-safe characters.
+pub fn is_valid_host(host: &str) -> bool {
+    !host.is_empty()
+        && host.len() <= MAX_HOST_LEN
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+}
+
+/// `host` with anything [`is_valid_host`] rejects replaced by `-`; None if
+/// nothing is left.
+pub fn sanitize_host(host: &str) -> Option<String> {
+    let host: String = host
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') {
+                c
+            } else {
+                '-'
+            }
+        })
+        .take(MAX_HOST_LEN)
+        .collect();
+    (!host.is_empty()).then_some(host)
+}
+
+pub fn namespaced(host: &str, session_id: &str) -> String {
+    format!("{}:{}", host, session_id)
+}
+
+struct RemoteSession {
+    summary: AgentSessionSummary,
+    messages: VecDeque<Arc<AgentMessage>>,
+}
+
+#[derive(Default)]
+struct RemoteHost {
+    sessions: HashMap<String, RemoteSession>,
+    /// Bumped on every connection, so a stale disconnect doesn't mark a host
+    /// offline after it has reconnected.
+    connection: u64,
+    online: bool,
+}
+
+#[derive(Default)]
+pub struct RemoteSessions {
+    hosts: Mutex<HashMap<String, RemoteHost>>,
+}
+
+impl RemoteSessions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn summaries(&self) -> Vec<AgentSessionSummary> {
+        let hosts = self.hosts.lock().unwrap();
+        hosts
+            .values()
+            .flat_map(|host| host.sessions.values().map(|s| s.summary.clone()))
+            .collect()
+    }
+
+    pub fn summary(&self, session_id: &str) -> Option<AgentSessionSummary> {
+        self.with_session(session_id, |s| s.summary.clone())
+    }
+
+    pub fn messages(&self, session_id: &str) -> Option<Vec<Arc<AgentMessage>>> {
+        self.with_session(session_id, |s| s.messages.iter().cloned().collect())
+    }
+
+    pub fn contains(&self, session_id: &str) -> bool {
+        self.with_session(session_id, |_| ()).is_some()
+    }
+
+    fn with_session<T>(&self, session_id: &str, f: impl FnOnce(&RemoteSession) -> T) -> Option<T> {
+        let (host, _) = session_id.split_once(':')?;
+        let hosts = self.hosts.lock().unwrap();
+        hosts.get(host)?.sessions.get(session_id).map(f)
+    }
+
+    /// A relay for `host` connected. Returns the connection's number, to pass
+    /// back to [`disconnected`](Self::disconnected).
+    pub fn connected(&self, host: &str) -> u64 {
+        let mut hosts = self.hosts.lock().unwrap();
+        let remote = hosts.entry(host.to_string()).or_default();
+        remote.connection += 1;
+        remote.online = true;
+        remote.connection
+    }
+
+    pub fn disconnected(&self, host: &str, connection: u64) {
+        let mut hosts = self.hosts.lock().unwrap();
+        if let Some(remote) = hosts.get_mut(host).filter(|r| r.connection == connection) {
+            remote.online = false;
+        }
+    }
+
+    /// Once a host has been gone for [`OFFLINE_TIMEOUT`]: mark its sessions
+    /// stopped, unless it reconnected since `connection` closed. Returns the
+    /// state changes to send.
+    pub fn mark_offline(&self, host: &str, connection: u64) -> Vec<ServerEvent> {
+        let mut hosts = self.hosts.lock().unwrap();
+        let Some(remote) = hosts
+            .get_mut(host)
+            .filter(|r| r.connection == connection && !r.online)
+        else {
+            return Vec::new();
+        };
+        remote
+            .sessions
+            .values_mut()
+            .filter(|s| s.summary.state != AgentStateType::Stopped)
+            .map(|s| {
+                let previous = s.summary.state;
+                s.summary.state = AgentStateType::Stopped;
+                s.summary.pending_tool = None;
+                ServerEvent::StateChanged {
+                    session_id: s.summary.session_id.clone(),
+                    previous,
+                    current: AgentStateType::Stopped,
+                    session: s.summary.clone(),
+                    unread_count: None,
+                }
+            })
+            .collect()
+    }
+
+    /// Apply an event relayed by `host` and return what to send this instance's
+    /// clients, with session ids namespaced. Events for unknown sessions are dropped.
+    pub fn apply(&self, host: &str, event: ServerEvent) -> Vec<ServerEvent> {
+        let mut hosts = self.hosts.lock().unwrap();
+        let remote = hosts.entry(host.to_string()).or_default();
+        let sessions = &mut remote.sessions;
+        match event {
+            ServerEvent::SessionsInit {
+                sessions: incoming, ..
+            } => {
+                let incoming: HashMap<String, AgentSessionSummary> = incoming
+                    .into_iter()
+                    .map(|s| {
+                        let s = adopt(host, s);
+                        (s.session_id.clone(), s)
+                    })
+                    .collect();
+                let mut events: Vec<ServerEvent> = Vec::new();
+                sessions.retain(|id, _| {
+                    let keep = incoming.contains_key(id);
+                    if !keep {
+                        events.push(ServerEvent::SessionRemoved {
+                            session_id: id.clone(),
+                        });
+                    }
+                    keep
+                });
+                for (id, session) in incoming {
+                    match sessions.get_mut(&id) {
+                        Some(existing) => {
+                            existing.summary = session.clone();
+                            events.push(ServerEvent::SessionUpdated { session });
+                        }
+                        None => {
+                            sessions.insert(id, new_session(session.clone()));
+                            events.push(ServerEvent::SessionDiscovered { session });
+                        }
+                    }
+                }
+                events
+            }
+            ServerEvent::SessionDiscovered { session } => {
+                let session = adopt(host, session);
+                let id = session.session_id.clone();
+                match sessions.get_mut(&id) {
+                    Some(existing) => existing.summary = session.clone(),
+                    None => {
+                        sessions.insert(id, new_session(session.clone()));
+                    }
+                }
+                vec![ServerEvent::SessionDiscovered { session }]
+            }
+            ServerEvent::SessionRemoved { session_id } => {
+                let session_id = namespaced(host, &session_id);
+                if sessions.remove(&session_id).is_none() {
+                    return Vec::new();
+                }
+                vec![ServerEvent::SessionRemoved { session_id }]
+            }
+            ServerEvent::SessionUpdated { session } => {
+                let session = adopt(host, session);
+                let Some(existing) = sessions.get_mut(&session.session_id) else {
+                    return Vec::new();
+                };
+                existing.summary = session.clone();
+                vec![ServerEvent::SessionUpdated { session }]
+            }
+            ServerEvent::StateChanged {
+                session_id,
+                previous,
+                current,
+                session,
+                ..
+            } => {
+                let session = adopt(host, session);
+                let session_id = namespaced(host, &session_id);
+                let Some(existing) = sessions.get_mut(&session_id) else {
+                    return Vec::new();
+                };
+                existing.summary = session.clone();
+                vec![ServerEvent::StateChanged {
+                    session_id,
+                    previous,
+                    current,
+                    session,
+                    unread_count: None,
+                }]
+            }
+            ServerEvent::NewMessage {
+                session_id,
+                mut message,
+            } => {
+                let session_id = namespaced(host, &session_id);
+                let Some(existing) = sessions.get_mut(&session_id) else {
+                    return Vec::new();
+                };
+                // Messages can arrive twice around a reconnect: once live and once
+                // in the snapshot that follows it.
+                if existing.messages.iter().rev().any(|m| m.id == message.id) {
+                    return Vec::new();
+                }
+                message.session_id = session_id.clone();
+                // The sending instance may be older and not normalize them yet.
+                message.timestamp = normalize_timestamp(&message.timestamp);
+                existing.messages.push_back(Arc::new(message.clone()));
+                if existing.messages.len() > MAX_MESSAGES {
+                    existing.messages.pop_front();
+                }
+                vec![ServerEvent::NewMessage {
+                    session_id,
+                    message,
+                }]
+            }
+            ServerEvent::MessagesInit {
+                session_id,
+                messages,
+            } => {
+                // The relay sends a session's messages whenever it can't send them one
+                // by one; tell subscribed clients to fetch them again.
+                let session_id = namespaced(host, &session_id);
+                let Some(existing) = sessions.get_mut(&session_id) else {
+                    return Vec::new();
+                };
+                let skip = messages.len().saturating_sub(MAX_MESSAGES);
+                existing.messages = messages
+                    .into_iter()
+                    .skip(skip)
+                    .map(|m| {
+                        let mut m = Arc::unwrap_or_clone(m);
+                        m.session_id = session_id.clone();
+                        m.timestamp = normalize_timestamp(&m.timestamp);
+                        Arc::new(m)
+                    })
+                    .collect();
+                let Some(latest) = existing.messages.back() else {
+                    return Vec::new();
+                };
+                vec![ServerEvent::MessagesAppended {
+                    session_id,
+                    count: existing.messages.len() as u32,
+                    latest_timestamp: latest.timestamp.clone(),
+                }]
+            }
+            ServerEvent::UsageUpdated {
+                session_id,
+                usage,
+                spend_rate_per_hour,
+                tokens_per_minute,
+                context_tokens,
+                context_limit,
+            } => {
+                let session_id = namespaced(host, &session_id);
+                let Some(existing) = sessions.get_mut(&session_id) else {
+                    return Vec::new();
+                };
+                let summary = &mut existing.summary;
+                summary.cumulative_usage = usage.clone();
+                summary.spend_rate_per_hour = spend_rate_per_hour;
+                summary.tokens_per_minute = tokens_per_minute;
+                summary.context_tokens = context_tokens;
+                summary.context_limit = context_limit;
+                vec![ServerEvent::UsageUpdated {
+                    session_id,
+                    usage,
+                    spend_rate_per_hour,
+                    tokens_per_minute,
+                    context_tokens,
+                    context_limit,
+                }]
+            }
+            ServerEvent::GitStatusUpdated {
+                session_id,
+                git_status,
+            } => {
+                let session_id = namespaced(host, &session_id);
+                let Some(existing) = sessions.get_mut(&session_id) else {
+                    return Vec::new();
+                };
+                existing.summary.git_status = git_status.clone();
+                vec![ServerEvent::GitStatusUpdated {
+                    session_id,
+                    git_status,
+                }]
+            }
+            ServerEvent::ContextWarning {
+                session_id,
+                percent_used,
+                threshold,
+                session,
+            } => {
+                let session = adopt(host, session);
+                let session_id = namespaced(host, &session_id);
+                let Some(existing) = sessions.get_mut(&session_id) else {
+                    return Vec::new();
+                };
+                existing.summary = session.clone();
+                vec![ServerEvent::ContextWarning {
+                    session_id,
+                    percent_used,
+                    threshold,
+                    session,
+                }]
+            }
+            ServerEvent::ModelChanged {
+                session_id,
+                previous,
+                current,
+            } => {
+                let session_id = namespaced(host, &session_id);
+                let Some(existing) = sessions.get_mut(&session_id) else {
+                    return Vec::new();
+                };
+                existing.summary.model = current.clone();
+                vec![ServerEvent::ModelChanged {
+                    session_id,
+                    previous,
+                    current,
+                }]
+            }
+            ServerEvent::LogDeleted {
+                session_id,
+                log_file,
+            } => {
+                let session_id = namespaced(host, &session_id);
+                let Some(existing) = sessions.get_mut(&session_id) else {
+                    return Vec::new();
+                };
+                existing.summary.stop_reason = Some(LOG_DELETED.to_string());
+                vec![ServerEvent::LogDeleted {
+                    session_id,
+                    log_file,
+                }]
+            }
+            // Summaries are resent in full after a batch; nothing to relay on its own.
+            ServerEvent::MessagesAppended { .. } => Vec::new(),
+            // Budgets are per host too: the central instance's projects are its own.
+            ServerEvent::BudgetExceeded {
+                project_path,
+                project_name,
+                period,
+                limit_usd,
+                spent_usd,
+            } => vec![ServerEvent::BudgetExceeded {
+                project_path: namespaced(host, &project_path),
+                project_name,
+                period,
+                limit_usd,
+                spent_usd,
+            }],
+            // Errors are about frames the relay connection sent, not sessions.
+            ServerEvent::Hello { .. } | ServerEvent::Error { .. } => Vec::new(),
+            // The other instance's startup says nothing about this one's.
+            ServerEvent::StartupComplete { .. } => Vec::new(),
+        }
+    }
+}
+
+/// A summary as this instance shows it: ids prefixed with the host it came from.
+fn adopt(host: &str, mut summary: AgentSessionSummary) -> AgentSessionSummary {
+    summary.session_id = namespaced(host, &summary.session_id);
+    summary.resumed_from = summary.resumed_from.map(|id| namespaced(host, &id));
+    summary.host = host.to_string();
+    summary.started_at = normalize_timestamp(&summary.started_at);
+    summary.last_activity_at = normalize_timestamp(&summary.last_activity_at);
+    summary
+}
+
+fn new_session(summary: AgentSessionSummary) -> RemoteSession {
+    RemoteSession {
+        summary,
+        messages: VecDeque::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{BudgetPeriod, MessageRole, MessageType};
+
+    fn summary(id: &str, state: AgentStateType) -> AgentSessionSummary {
+        AgentSessionSummary {
+            session_id: id.to_string(),
+            state,
+            ..Default::default()
+        }
+    }
+
+    fn message(id: &str) -> AgentMessage {
+        AgentMessage {
+            id: id.to_string(),
+            session_id: "s1".to_string(),
+            timestamp: "2025-01-01T00:00:00Z".to_string(),
+            role: MessageRole::Assistant,
+            msg_type: MessageType::Text,
+            content: "hi".into(),
+            metadata: None,
+            parent_id: None,
+            tool_use_message_id: None,
+        }
+    }
+
+    fn ids(events: &[ServerEvent]) -> Vec<String> {
+        events
+            .iter()
+            .map(|e| {
+                let json = serde_json::to_value(e).unwrap();
+                let id = json["sessionId"]
+                    .as_str()
+                    .or(json["session"]["sessionId"].as_str())
+                    .unwrap_or_default()
+                    .to_string();
+                format!("{} {}", e.event_type(), id)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_host_names() {
+        assert!(is_valid_host("laptop-2.local"));
+        assert!(!is_valid_host("my laptop"));
+        assert!(!is_valid_host(""));
+        assert_eq!(sanitize_host(" My Laptop ").as_deref(), Some("My-Laptop"));
+        assert_eq!(sanitize_host("  "), None);
+    }
+
+    #[test]
+    fn test_sessions_namespaced_by_host() {
+        let remote = RemoteSessions::new();
+        let init = |sessions| ServerEvent::SessionsInit {
+            sessions,
+            unread_counts: None,
+        };
+        let events = remote.apply("laptop", init(vec![summary("s1", AgentStateType::Running)]));
+        assert_eq!(ids(&events), ["session:discovered laptop:s1"]);
+        remote.apply("desktop", init(vec![summary("s1", AgentStateType::Idle)]));
+
+        let laptop = remote.summary("laptop:s1").unwrap();
+        assert_eq!(laptop.host, "laptop");
+        assert_eq!(laptop.state, AgentStateType::Running);
+        assert_eq!(
+            remote.summary("desktop:s1").unwrap().state,
+            AgentStateType::Idle
+        );
+        assert!(remote.summary("s1").is_none());
+
+        let new_message = || ServerEvent::NewMessage {
+            session_id: "s1".to_string(),
+            message: message("m1"),
+        };
+        let events = remote.apply("laptop", new_message());
+        assert_eq!(ids(&events), ["session:new_message laptop:s1"]);
+        assert!(remote.apply("laptop", new_message()).is_empty());
+        let messages = remote.messages("laptop:s1").unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].session_id, "laptop:s1");
+        assert_eq!(messages[0].timestamp, "2025-01-01T00:00:00.000Z");
+
+        // A reconnect's snapshot replaces the host's sessions.
+        let events = remote.apply("laptop", init(vec![summary("s2", AgentStateType::Idle)]));
+        assert_eq!(
+            ids(&events),
+            ["session:removed laptop:s1", "session:discovered laptop:s2"]
+        );
+        assert_eq!(remote.summaries().len(), 2);
+
+        let events = remote.apply(
+            "laptop",
+            ServerEvent::BudgetExceeded {
+                project_path: "/work/app".to_string(),
+                project_name: "app".to_string(),
+                period: BudgetPeriod::Day,
+                limit_usd: 5.0,
+                spent_usd: 6.0,
+            },
+        );
+        let ServerEvent::BudgetExceeded { project_path, .. } = &events[0] else {
+            panic!("{:?}", events);
+        };
+        assert_eq!(project_path, "laptop:/work/app");
+    }
+
+    #[test]
+    fn test_offline_host_sessions_stop() {
+        let remote = RemoteSessions::new();
+        let first = remote.connected("laptop");
+        remote.apply(
+            "laptop",
+            ServerEvent::SessionsInit {
+                sessions: vec![
+                    summary("s1", AgentStateType::Running),
+                    summary("s2", AgentStateType::Stopped),
+                ],
+                unread_counts: None,
+            },
+        );
+        remote.disconnected("laptop", first);
+
+        // Reconnected in time: nothing changes.
+        let second = remote.connected("laptop");
+        assert!(remote.mark_offline("laptop", first).is_empty());
+
+        remote.disconnected("laptop", second);
+        let events = remote.mark_offline("laptop", second);
+        assert_eq!(ids(&events), ["session:state_changed laptop:s1"]);
+        assert_eq!(
+            remote.summary("laptop:s1").unwrap().state,
+            AgentStateType::Stopped
+        );
+    }
+}
@@ -0,0 +1,375 @@
+//! Short summaries of sessions, written by a model through the Anthropic API. Only a
+//! session's outline is sent, never its transcript, which keeps each request small.
+
+use crate::types::{AgentSessionSummary, SessionOutline, TurnOutline};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::sync::Mutex;
+use std::time::Duration;
+
+pub const DEFAULT_MODEL: &str = "claude-haiku-4-5";
+pub const DEFAULT_API_URL: &str = "https://api.anthropic.com";
+/// Costs of summaries are counted under this project path (and name).
+pub const DASHBOARD_PROJECT: &str = "dashboard";
+const API_VERSION: &str = "2023-06-01";
+const MAX_TOKENS: u32 = 300;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+/// A session is summarized (or tried) at most once in this long.
+const MIN_INTERVAL_MS: i64 = 60 * 60_000;
+/// Turns described to the model; older ones are left out.
+const MAX_TURNS: usize = 40;
+/// Files listed per turn.
+const MAX_FILES: usize = 5;
+
+/// Where summaries are written and when; see `Config::summary_config`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SummaryConfig {
+    pub api_key: String,
+    pub model: String,
+    /// Without a trailing slash.
+    pub api_url: String,
+    pub on_stop: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SummaryError {
+    /// No API key is configured.
+    Disabled,
+    SessionNotFound,
+    /// The session has no turns yet.
+    Empty,
+    /// Summarized or tried too recently; try again after this long.
+    RateLimited(Duration),
+    /// The request failed or the API turned it down.
+    Api(String),
+}
+
+impl std::fmt::Display for SummaryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SummaryError::Disabled => {
+                write!(f, "Summaries are off; set SUMMARY_API_KEY to turn them on")
+            }
+            SummaryError::SessionNotFound => write!(f, "Session not found"),
+            SummaryError::Empty => write!(f, "Nothing to summarize yet"),
+            SummaryError::RateLimited(wait) => write!(
+                f,
+                "Summarized less than an hour ago; try again in {} minutes",
+                wait.as_secs().div_ceil(60)
+            ),
+            SummaryError::Api(message) => write!(f, "Summary request failed: {}", message),
+        }
+    }
+}
+
+/// What the model wrote and the tokens it took.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    pub text: String,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+pub struct Summarizer {
+    config: SummaryConfig,
+    client: reqwest::Client,
+    /// When each session was last summarized or tried, in ms.
+    attempts: Mutex<HashMap<String, i64>>,
+    started_at_ms: i64,
+}
+
+impl Summarizer {
+    pub fn new(config: SummaryConfig) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            attempts: Mutex::new(HashMap::new()),
+            started_at_ms: chrono::Utc::now().timestamp_millis(),
+        }
+    }
+
+    pub fn model(&self) -> &str {
+        &self.config.model
+    }
+
+    /// Whether a session that just stopped should be summarized. Sessions last active
+    /// before the server started are left alone, so a restart doesn't summarize every
+    /// recent log as its session is found to have stopped; so are sessions whose
+    /// summary is newer than their last activity.
+    pub fn wants_on_stop(&self, session: &AgentSessionSummary) -> bool {
+        let Some(last_activity) = parse_ms(&session.last_activity_at) else {
+            return false;
+        };
+        let summarized = session
+            .summary
+            .as_ref()
+            .and_then(|summary| parse_ms(&summary.generated_at));
+        self.config.on_stop
+            && last_activity >= self.started_at_ms
+            && summarized.is_none_or(|at| at < last_activity)
+    }
+
+    /// Take the session's one attempt for the hour. `summarized_at` is when its stored
+    /// summary was written, which counts as well.
+    pub fn claim(
+        &self,
+        session_id: &str,
+        now_ms: i64,
+        summarized_at: Option<&str>,
+    ) -> Result<(), SummaryError> {
+        let mut attempts = self.attempts.lock().unwrap();
+        attempts.retain(|_, at| now_ms - *at < MIN_INTERVAL_MS);
+        let last = attempts
+            .get(session_id)
+            .copied()
+            .into_iter()
+            .chain(summarized_at.and_then(parse_ms))
+            .max();
+        if let Some(last) = last.filter(|last| now_ms - last < MIN_INTERVAL_MS) {
+            let wait = (last + MIN_INTERVAL_MS - now_ms) as u64;
+            return Err(SummaryError::RateLimited(Duration::from_millis(wait)));
+        }
+        attempts.insert(session_id.to_string(), now_ms);
+        Ok(())
+    }
+
+    /// Ask the model for a summary of `outline`.
+    pub async fn complete(
+        &self,
+        session: &AgentSessionSummary,
+        outline: &SessionOutline,
+    ) -> Result<Completion, SummaryError> {
+        let body = serde_json::json!({
+            "model": self.config.model,
+            "max_tokens": MAX_TOKENS,
+            "messages": [{ "role": "user", "content": build_prompt(session, outline) }],
+        });
+        let response = self
+            .client
+            .post(format!("{}/v1/messages", self.config.api_url))
+            .header("x-api-key", &self.config.api_key)
+            .header("anthropic-version", API_VERSION)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| SummaryError::Api(e.to_string()))?;
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .map_err(|e| SummaryError::Api(e.to_string()))?;
+        if !status.is_success() {
+            let message = serde_json::from_str::<ApiError>(&text)
+                .map(|e| e.error.message)
+                .unwrap_or(text);
+            return Err(SummaryError::Api(format!("{}: {}", status, message)));
+        }
+        let reply: ApiReply =
+            serde_json::from_str(&text).map_err(|e| SummaryError::Api(e.to_string()))?;
+        let text = reply
+            .content
+            .iter()
+            .filter_map(|block| block.text.as_deref())
+            .collect::<Vec<_>>()
+            .join("")
+            .trim()
+            .to_string();
+        if text.is_empty() {
+            return Err(SummaryError::Api("empty reply".to_string()));
+        }
+        Ok(Completion {
+            text,
+            input_tokens: reply.usage.input_tokens,
+            output_tokens: reply.usage.output_tokens,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct ApiReply {
+    content: Vec<ApiBlock>,
+    usage: ApiUsage,
+}
+
+#[derive(Deserialize)]
+struct ApiBlock {
+    text: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ApiUsage {
+    input_tokens: u64,
+    output_tokens: u64,
+}
+
+#[derive(Deserialize)]
+struct ApiError {
+    error: ApiErrorDetail,
+}
+
+#[derive(Deserialize)]
+struct ApiErrorDetail {
+    message: String,
+}
+
+/// The request sent to the model: instructions, then one line per turn.
+fn build_prompt(session: &AgentSessionSummary, outline: &SessionOutline) -> String {
+    let mut prompt = String::from(
+        "Summarize this coding agent session in three sentences: what was asked, \
+         what the agent did, and where it ended up. Reply with the summary only.\n\n",
+    );
+    let title = session.title.as_deref().unwrap_or(&session.initial_task);
+    let _ = writeln!(prompt, "Project: {}", session.project_name);
+    if !title.is_empty() {
+        let _ = writeln!(prompt, "Title: {}", title);
+    }
+    let skipped = outline.turns.len().saturating_sub(MAX_TURNS);
+    let _ = writeln!(prompt, "\nTurns, oldest first:");
+    if skipped > 0 {
+        let _ = writeln!(prompt, "({} earlier turns left out)", skipped);
+    }
+    for (i, turn) in outline.turns.iter().enumerate().skip(skipped) {
+        let _ = writeln!(prompt, "{}. {}", i + 1, describe_turn(turn));
+    }
+    prompt
+}
+
+fn describe_turn(turn: &TurnOutline) -> String {
+    let mut parts = vec![match &turn.prompt {
+        Some(prompt) => format!("Prompt: {:?}", prompt),
+        None => "Continued from before".to_string(),
+    }];
+    if !turn.tools.is_empty() {
+        let tools: Vec<String> = turn
+            .tools
+            .iter()
+            .map(|t| format!("{} x{}", t.name, t.count))
+            .collect();
+        parts.push(format!("Tools: {}", tools.join(", ")));
+    }
+    if !turn.files.is_empty() {
+        let mut files = turn.files[..turn.files.len().min(MAX_FILES)].join(", ");
+        if turn.files.len() > MAX_FILES {
+            let _ = write!(files, " and {} more", turn.files.len() - MAX_FILES);
+        }
+        parts.push(format!("Files: {}", files));
+    }
+    match turn.duration_ms {
+        Some(ms) => parts.push(format!("{}s", ms / 1000)),
+        None if turn.ended_at.is_none() => parts.push("still running".to_string()),
+        None => {}
+    }
+    parts.join(" | ")
+}
+
+fn parse_ms(timestamp: &str) -> Option<i64> {
+    chrono::DateTime::parse_from_rfc3339(timestamp)
+        .ok()
+        .map(|dt| dt.timestamp_millis())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{GeneratedSummary, ToolCount};
+
+    fn summarizer() -> Summarizer {
+        Summarizer::new(SummaryConfig {
+            api_key: "key".into(),
+            model: DEFAULT_MODEL.into(),
+            api_url: DEFAULT_API_URL.into(),
+            on_stop: true,
+        })
+    }
+
+    fn turn(prompt: Option<&str>) -> TurnOutline {
+        TurnOutline {
+            prompt: prompt.map(str::to_string),
+            started_at: "2026-01-01T00:00:00Z".into(),
+            ended_at: Some("2026-01-01T00:01:00Z".into()),
+            duration_ms: Some(60_000),
+            tools: vec![ToolCount {
+                name: "Edit".into(),
+                count: 3,
+            }],
+            files: (0..7).map(|i| format!("src/{}.rs", i)).collect(),
+            message_count: 10,
+            usage: None,
+        }
+    }
+
+    #[test]
+    fn test_prompt_describes_turns() {
+        let session = AgentSessionSummary {
+            project_name: "api".into(),
+            initial_task: "fix the parser".into(),
+            ..Default::default()
+        };
+        let outline = SessionOutline {
+            session_id: "s1".into(),
+            turns: vec![turn(None), turn(Some("now add tests"))],
+        };
+        let prompt = build_prompt(&session, &outline);
+        assert!(prompt.contains("Title: fix the parser"));
+        assert!(prompt.contains("1. Continued from before | Tools: Edit x3"));
+        assert!(prompt.contains("2. Prompt: \"now add tests\""));
+        assert!(prompt.contains("src/4.rs and 2 more | 60s"));
+    }
+
+    #[test]
+    fn test_long_sessions_send_latest_turns() {
+        let outline = SessionOutline {
+            session_id: "s1".into(),
+            turns: (0..MAX_TURNS + 5).map(|_| turn(Some("go"))).collect(),
+        };
+        let prompt = build_prompt(&AgentSessionSummary::default(), &outline);
+        assert!(prompt.contains("(5 earlier turns left out)"));
+        assert!(!prompt.contains("\n5. "));
+        assert!(prompt.contains(&format!("\n{}. ", MAX_TURNS + 5)));
+    }
+
+    #[test]
+    fn test_one_attempt_per_hour() {
+        let summarizer = summarizer();
+        assert!(summarizer.claim("s1", 0, None).is_ok());
+        assert_eq!(
+            summarizer.claim("s1", 15 * 60_000, None),
+            Err(SummaryError::RateLimited(Duration::from_secs(45 * 60)))
+        );
+        assert!(summarizer.claim("s2", 15 * 60_000, None).is_ok());
+        assert!(summarizer.claim("s1", MIN_INTERVAL_MS, None).is_ok());
+
+        // A stored summary counts too, e.g. one written before a restart.
+        let written = chrono::DateTime::from_timestamp_millis(10 * MIN_INTERVAL_MS)
+            .unwrap()
+            .to_rfc3339();
+        assert!(summarizer
+            .claim("s3", 10 * MIN_INTERVAL_MS + 1_000, Some(&written))
+            .is_err());
+    }
+
+    #[test]
+    fn test_on_stop_skips_old_and_unchanged_sessions() {
+        let summarizer = summarizer();
+        let now = chrono::Utc::now();
+        let mut session = AgentSessionSummary {
+            last_activity_at: now.to_rfc3339(),
+            ..Default::default()
+        };
+        assert!(summarizer.wants_on_stop(&session));
+
+        session.summary = Some(GeneratedSummary {
+            generated_at: (now + chrono::Duration::seconds(1)).to_rfc3339(),
+            ..Default::default()
+        });
+        assert!(!summarizer.wants_on_stop(&session));
+
+        session.summary = None;
+        session.last_activity_at = (now - chrono::Duration::hours(1)).to_rfc3339();
+        assert!(!summarizer.wants_on_stop(&session));
+    }
+}
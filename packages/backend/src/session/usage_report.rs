@@ -0,0 +1,433 @@
+//! Weekly and monthly usage reports, built from the per-project tallies. History
+//! only goes back as far as the tallies do, so each report says which days it
+//! actually covers.
+
+use crate::days;
+use crate::providers::claude_code::timestamps::format_timestamp;
+use crate::session::project_usage::SessionTally;
+use crate::types::{
+    ReportCoverage, ReportDay, ReportModel, ReportPeriod, ReportProject, ReportSession, ToolCount,
+    UsageReport,
+};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use std::collections::{BTreeMap, HashMap};
+
+const BUSIEST_DAYS: usize = 5;
+const LONGEST_SESSIONS: usize = 5;
+const TOP_TOOLS: usize = 10;
+
+/// One session's tally and where it belongs.
+pub struct ReportInput<'a> {
+    pub project_path: &'a str,
+    pub project_name: &'a str,
+    pub session_id: &'a str,
+    pub tally: &'a SessionTally,
+}
+
+/// Sessions count towards the report if they used anything during the period, or
+/// were last active in it. The period ends today in `tz`.
+pub fn build(
+    inputs: &[ReportInput],
+    tracking_since: Option<i64>,
+    period: ReportPeriod,
+    now: DateTime<Utc>,
+    tz: Tz,
+) -> UsageReport {
+    let to_day = days::day_index(days::today(now, tz));
+    let from_day = to_day - (period.days() - 1);
+    let in_period = |day: &i64| (from_day..=to_day).contains(day);
+
+    let history_from = inputs
+        .iter()
+        .filter_map(|input| input.tally.daily_cost.keys().next().copied())
+        .chain(tracking_since)
+        .min();
+
+    let mut report = UsageReport {
+        period,
+        from: date(from_day),
+        to: date(to_day),
+        generated_at: format_timestamp(now),
+        coverage: ReportCoverage {
+            history_from: history_from.map(date),
+            complete: history_from.is_some_and(|day| day <= from_day),
+            sessions_without_details: 0,
+        },
+        total_cost: 0.0,
+        total_tokens: 0,
+        sessions: 0,
+        projects: Vec::new(),
+        models: Vec::new(),
+        busiest_days: Vec::new(),
+        longest_sessions: Vec::new(),
+        top_tools: Vec::new(),
+    };
+    let mut projects: BTreeMap<&str, ReportProject> = BTreeMap::new();
+    let mut models: BTreeMap<&str, ReportModel> = BTreeMap::new();
+    let mut days: BTreeMap<i64, ReportDay> = BTreeMap::new();
+    let mut tools: HashMap<&str, u64> = HashMap::new();
+
+    for input in inputs {
+        let tally = input.tally;
+        let cost: f64 = tally
+            .daily_cost
+            .iter()
+            .filter(|(day, _)| in_period(day))
+            .map(|(_, cost)| cost)
+            .sum();
+        let tokens: u64 = tally
+            .daily_tokens
+            .iter()
+            .filter(|(day, _)| in_period(day))
+            .map(|(_, tokens)| tokens)
+            .sum();
+        let used = tally.daily_cost.keys().any(&in_period)
+            || tally.daily_tokens.keys().any(&in_period);
+        let last_active = parse_time(&tally.last_activity_at)
+            .is_some_and(|at| in_period(&days::day_of(at.timestamp_millis(), tz)));
+        if !used && !last_active {
+            continue;
+        }
+
+        report.sessions += 1;
+        report.total_cost += cost;
+        report.total_tokens += tokens;
+        let project = projects
+            .entry(input.project_path)
+            .or_insert_with(|| ReportProject {
+                project_path: input.project_path.to_string(),
+                project_name: input.project_name.to_string(),
+                cost: 0.0,
+                tokens: 0,
+                sessions: 0,
+            });
+        project.cost += cost;
+        project.tokens += tokens;
+        project.sessions += 1;
+        for (day, cost) in tally.daily_cost.iter().filter(|(day, _)| in_period(day)) {
+            days.entry(*day).or_insert_with(|| empty_day(*day)).cost += cost;
+        }
+        for (day, tokens) in tally.daily_tokens.iter().filter(|(day, _)| in_period(day)) {
+            days.entry(*day).or_insert_with(|| empty_day(*day)).tokens += tokens;
+        }
+
+        // Only the cost of sessions recorded before reports existed is known.
+        if tally.started_at.is_empty() {
+            report.coverage.sessions_without_details += 1;
+            continue;
+        }
+        if !tally.model.is_empty() {
+            let model = models.entry(&tally.model).or_insert_with(|| ReportModel {
+                model: tally.model.clone(),
+                cost: 0.0,
+                tokens: 0,
+                sessions: 0,
+            });
+            model.cost += cost;
+            model.tokens += tokens;
+            model.sessions += 1;
+        }
+        for tool in &tally.top_tools {
+            *tools.entry(&tool.name).or_default() += tool.count;
+        }
+        report.longest_sessions.push(ReportSession {
+            session_id: input.session_id.to_string(),
+            project_name: input.project_name.to_string(),
+            title: tally.title.clone(),
+            model: tally.model.clone(),
+            started_at: tally.started_at.clone(),
+            last_activity_at: tally.last_activity_at.clone(),
+            duration_secs: duration_secs(&tally.started_at, &tally.last_activity_at),
+            cost,
+        });
+    }
+
+    report.projects = projects.into_values().collect();
+    report.projects.sort_by(|a, b| {
+        b.cost
+            .total_cmp(&a.cost)
+            .then_with(|| a.project_name.cmp(&b.project_name))
+    });
+    report.models = models.into_values().collect();
+    report.models.sort_by(|a, b| {
+        b.cost
+            .total_cmp(&a.cost)
+            .then_with(|| a.model.cmp(&b.model))
+    });
+    report.busiest_days = days.into_values().collect();
+    report
+        .busiest_days
+        .sort_by(|a, b| b.cost.total_cmp(&a.cost).then_with(|| a.date.cmp(&b.date)));
+    report.busiest_days.truncate(BUSIEST_DAYS);
+    report.longest_sessions.sort_by(|a, b| {
+        b.duration_secs
+            .cmp(&a.duration_secs)
+            .then_with(|| a.session_id.cmp(&b.session_id))
+    });
+    report.longest_sessions.truncate(LONGEST_SESSIONS);
+    report.top_tools = tools
+        .into_iter()
+        .map(|(name, count)| ToolCount {
+            name: name.to_string(),
+            count,
+        })
+        .collect();
+    report
+        .top_tools
+        .sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+    report.top_tools.truncate(TOP_TOOLS);
+    report
+}
+
+fn empty_day(day: i64) -> ReportDay {
+    ReportDay {
+        date: date(day),
+        cost: 0.0,
+        tokens: 0,
+    }
+}
+
+/// `YYYY-MM-DD` for a day since the epoch.
+pub(crate) fn date(day: i64) -> String {
+    days::date(day).to_string()
+}
+
+pub(crate) fn parse_time(timestamp: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .ok()
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+fn duration_secs(started_at: &str, last_activity_at: &str) -> u64 {
+    match (parse_time(started_at), parse_time(last_activity_at)) {
+        (Some(start), Some(end)) => (end - start).num_seconds().max(0) as u64,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::CumulativeUsage;
+    use chrono_tz::Tz::UTC;
+
+    fn now() -> DateTime<Utc> {
+        "2025-01-15T12:00:00Z".parse().unwrap()
+    }
+
+    fn day(date: &str) -> i64 {
+        days::day_index(date.parse().unwrap())
+    }
+
+    fn tally(model: &str, started_at: &str, days: &[(&str, f64, u64)]) -> SessionTally {
+        SessionTally {
+            usage: CumulativeUsage::default(),
+            daily_cost: days.iter().map(|(d, cost, _)| (day(d), *cost)).collect(),
+            daily_tokens: days
+                .iter()
+                .map(|(d, _, tokens)| (day(d), *tokens))
+                .collect(),
+            model: model.to_string(),
+            title: format!("{} work", model),
+            started_at: started_at.to_string(),
+            last_activity_at: days
+                .last()
+                .map(|(d, _, _)| format!("{}T18:00:00Z", d))
+                .unwrap_or_default(),
+            top_tools: vec![
+                ToolCount {
+                    name: "Read".to_string(),
+                    count: 4,
+                },
+                ToolCount {
+                    name: "Edit".to_string(),
+                    count: 2,
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    fn input<'a>(
+        project: &'a str,
+        session_id: &'a str,
+        tally: &'a SessionTally,
+    ) -> ReportInput<'a> {
+        ReportInput {
+            project_path: project,
+            project_name: project.trim_start_matches('/'),
+            session_id,
+            tally,
+        }
+    }
+
+    #[test]
+    fn test_week_report() {
+        let opus = tally(
+            "claude-opus-4-1",
+            "2025-01-13T09:00:00Z",
+            &[("2025-01-13", 3.0, 3000), ("2025-01-14", 1.0, 1000)],
+        );
+        let sonnet = tally(
+            "claude-sonnet-4-5",
+            "2025-01-15T10:00:00Z",
+            &[("2025-01-15", 0.5, 800)],
+        );
+        // Mostly before the period: only the last day counts.
+        let old = tally(
+            "claude-sonnet-4-5",
+            "2025-01-01T10:00:00Z",
+            &[("2025-01-02", 9.0, 9000), ("2025-01-09", 0.25, 200)],
+        );
+        let outside = tally(
+            "claude-sonnet-4-5",
+            "2025-01-01T10:00:00Z",
+            &[("2025-01-08", 5.0, 5000)],
+        );
+        let inputs = [
+            input("/api", "a1", &opus),
+            input("/web", "w1", &sonnet),
+            input("/web", "w2", &old),
+            input("/web", "w3", &outside),
+        ];
+        let report = build(
+            &inputs,
+            Some(day("2025-01-10")),
+            ReportPeriod::Week,
+            now(),
+            UTC,
+        );
+
+        assert_eq!(
+            (report.from.as_str(), report.to.as_str()),
+            ("2025-01-09", "2025-01-15")
+        );
+        assert_eq!(report.sessions, 3);
+        assert_eq!(report.total_cost, 4.75);
+        assert_eq!(report.total_tokens, 5000);
+        assert_eq!(
+            report.coverage,
+            ReportCoverage {
+                history_from: Some("2025-01-02".to_string()),
+                complete: true,
+                sessions_without_details: 0,
+            }
+        );
+
+        let projects: Vec<(&str, f64, u64, usize)> = report
+            .projects
+            .iter()
+            .map(|p| (p.project_name.as_str(), p.cost, p.tokens, p.sessions))
+            .collect();
+        assert_eq!(projects, [("api", 4.0, 4000, 1), ("web", 0.75, 1000, 2)]);
+        let models: Vec<(&str, f64, usize)> = report
+            .models
+            .iter()
+            .map(|m| (m.model.as_str(), m.cost, m.sessions))
+            .collect();
+        assert_eq!(
+            models,
+            [("claude-opus-4-1", 4.0, 1), ("claude-sonnet-4-5", 0.75, 2)]
+        );
+        let days: Vec<&str> = report
+            .busiest_days
+            .iter()
+            .map(|d| d.date.as_str())
+            .collect();
+        assert_eq!(
+            days,
+            ["2025-01-13", "2025-01-14", "2025-01-15", "2025-01-09"]
+        );
+
+        // The old session ran for days; its whole span counts.
+        let longest: Vec<(&str, u64)> = report
+            .longest_sessions
+            .iter()
+            .map(|s| (s.session_id.as_str(), s.duration_secs))
+            .collect();
+        assert_eq!(
+            longest,
+            [
+                ("w2", 8 * 86400 + 8 * 3600),
+                ("a1", 33 * 3600),
+                ("w1", 8 * 3600)
+            ]
+        );
+        assert_eq!(report.longest_sessions[1].cost, 4.0);
+        assert_eq!(
+            report.top_tools,
+            [
+                ToolCount {
+                    name: "Read".to_string(),
+                    count: 12
+                },
+                ToolCount {
+                    name: "Edit".to_string(),
+                    count: 6
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_coverage_is_honest() {
+        // Recorded before reports existed: only cost is known.
+        let legacy = SessionTally {
+            daily_cost: [(day("2025-01-12"), 2.0)].into(),
+            ..Default::default()
+        };
+        let inputs = [input("/api", "a1", &legacy)];
+        let report = build(
+            &inputs,
+            Some(day("2025-01-14")),
+            ReportPeriod::Week,
+            now(),
+            UTC,
+        );
+        assert_eq!(
+            report.coverage,
+            ReportCoverage {
+                history_from: Some("2025-01-12".to_string()),
+                complete: false,
+                sessions_without_details: 1,
+            }
+        );
+        assert_eq!(report.total_cost, 2.0);
+        assert_eq!(report.projects.len(), 1);
+        assert!(report.models.is_empty());
+        assert!(report.longest_sessions.is_empty());
+
+        let month = build(&inputs, None, ReportPeriod::Month, now(), UTC);
+        assert_eq!(month.from, "2024-12-17");
+        assert!(!month.coverage.complete);
+
+        let empty = build(&[], None, ReportPeriod::Week, now(), UTC);
+        assert_eq!(empty.coverage, ReportCoverage::default());
+        assert_eq!(empty.sessions, 0);
+    }
+
+    #[test]
+    fn test_period_ends_today_in_zone() {
+        let tokyo = chrono_tz::Tz::Asia__Tokyo;
+        // 01:00 on the 16th in Tokyo.
+        let late: DateTime<Utc> = "2025-01-15T16:00:00Z".parse().unwrap();
+        let mut session = tally("opus", "2025-01-08T09:00:00Z", &[("2025-01-08", 1.0, 10)]);
+        // 23:00 on the 9th in Tokyo.
+        session.last_activity_at = "2025-01-09T14:00:00Z".to_string();
+        let inputs = [input("/api", "a1", &session)];
+
+        let report = build(&inputs, None, ReportPeriod::Week, late, tokyo);
+        assert_eq!(
+            (report.from.as_str(), report.to.as_str()),
+            ("2025-01-10", "2025-01-16")
+        );
+        assert_eq!(report.sessions, 0);
+        let report = build(&inputs, None, ReportPeriod::Week, late, UTC);
+        assert_eq!(
+            (report.from.as_str(), report.to.as_str()),
+            ("2025-01-09", "2025-01-15")
+        );
+        assert_eq!(report.sessions, 1);
+    }
+}
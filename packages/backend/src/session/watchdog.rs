@@ -0,0 +1,147 @@
+//! Checks every minute that what notices new sessions and new log lines is still
+//! running: discovery's scans, the watchers of followed sessions, and the loop that
+//! routes provider events. Discovery and watchers are restarted in place; the
+//! manager runs the restarts, this keeps the heartbeat and the status.
+
+use crate::providers::claude_code::timestamps::format_timestamp;
+use crate::types::WatchdogStatus;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// How often the routing loop beats while it has nothing to route, well within
+/// `CHECK_INTERVAL`.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+/// Discovery counts as stopped once this many scan intervals pass without a scan.
+const MISSED_SCANS: u32 = 3;
+
+#[derive(Default)]
+pub struct Watchdog {
+    beats: AtomicU64,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Beats as of the last check.
+    beats_seen: Option<u64>,
+    status: WatchdogStatus,
+}
+
+/// What one round of checks found and restarted.
+#[derive(Debug, Default)]
+pub struct Findings {
+    pub last_scan_ms: Option<i64>,
+    pub discovery_restarted: bool,
+    pub watchers_restarted: Vec<String>,
+    pub routing_stalled: bool,
+}
+
+/// Whether discovery, last scanning at `last_scan_ms`, has missed too many scans.
+/// Discovery that hasn't started isn't late.
+pub fn scan_overdue(last_scan_ms: Option<i64>, scan_interval: Duration, now_ms: i64) -> bool {
+    let allowed = (scan_interval * MISSED_SCANS).as_millis() as i64;
+    last_scan_ms.is_some_and(|at| now_ms - at > allowed)
+}
+
+impl Watchdog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Called by the routing loop for every event and every `HEARTBEAT_INTERVAL`.
+    pub fn beat(&self) {
+        self.beats.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Whether the routing loop beat since the last call. The first call has
+    /// nothing to compare with.
+    pub fn routing_moved(&self) -> bool {
+        let beats = self.beats.load(Ordering::Relaxed);
+        let mut state = self.state.lock().unwrap();
+        let moved = state.beats_seen.is_none_or(|seen| seen != beats);
+        state.beats_seen = Some(beats);
+        moved
+    }
+
+    pub fn record(&self, now_ms: i64, findings: Findings) {
+        let mut state = self.state.lock().unwrap();
+        let status = &mut state.status;
+        status.last_check_at = Some(timestamp(now_ms));
+        status.last_discovery_scan_at = findings.last_scan_ms.map(timestamp);
+        status.failing.clear();
+        if findings.discovery_restarted {
+            status.discovery_restarts += 1;
+            status.failing.push("discovery".to_string());
+        }
+        if findings.routing_stalled {
+            status.routing_stalls += 1;
+            status.failing.push("routing".to_string());
+        }
+        status.watcher_restarts += findings.watchers_restarted.len() as u64;
+        for session_id in findings.watchers_restarted {
+            status.failing.push(format!("watcher:{}", session_id));
+        }
+    }
+
+    pub fn status(&self) -> WatchdogStatus {
+        self.state.lock().unwrap().status.clone()
+    }
+}
+
+fn timestamp(ms: i64) -> String {
+    chrono::DateTime::from_timestamp_millis(ms)
+        .map(format_timestamp)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checks_and_status() {
+        let interval = Duration::from_secs(5);
+        assert!(!scan_overdue(None, interval, 100_000));
+        assert!(!scan_overdue(Some(90_000), interval, 100_000));
+        assert!(scan_overdue(Some(80_000), interval, 100_000));
+
+        let watchdog = Watchdog::new();
+        assert!(watchdog.routing_moved());
+        assert!(!watchdog.routing_moved());
+        watchdog.beat();
+        assert!(watchdog.routing_moved());
+        assert!(watchdog.status().last_check_at.is_none());
+
+        watchdog.record(
+            1_741_564_800_000,
+            Findings {
+                last_scan_ms: Some(1_741_564_700_000),
+                discovery_restarted: true,
+                watchers_restarted: vec!["s1".into()],
+                routing_stalled: true,
+            },
+        );
+        assert_eq!(
+            watchdog.status().failing,
+            ["discovery", "routing", "watcher:s1"]
+        );
+        watchdog.record(1_741_564_860_000, Findings::default());
+        let status = watchdog.status();
+        assert_eq!(
+            status.last_check_at.as_deref(),
+            Some("2025-03-10T00:01:00.000Z")
+        );
+        assert_eq!(status.last_discovery_scan_at, None);
+        assert!(status.failing.is_empty());
+        assert_eq!(
+            (
+                status.discovery_restarts,
+                status.watcher_restarts,
+                status.routing_stalls
+            ),
+            (1, 1, 1)
+        );
+    }
+}
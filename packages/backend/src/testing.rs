@@ -0,0 +1,2207 @@
+//! End-to-end test support: a fake `~/.claude/projects` tree on disk, watched by the
+//! real provider with short intervals, with the HTTP API on top.
+
+use crate::config::{Config, ConfigSource};
+use crate::notifications::Notifier;
+use crate::providers::claude_code::session_discovery::{encode_project_path, DiscoveryConfig};
+use crate::providers::claude_code::{ProviderConfig, SharedSettings};
+use crate::providers::ProviderEvent;
+use crate::server::confirm::CONFIRM_HEADER;
+use crate::server::connections::ConnectionRegistry;
+use crate::server::http::{create_router, AppState};
+use crate::server::limits::ApiLimits;
+use crate::session::manager::SessionManager;
+use crate::session::prefs::PrefsStore;
+use crate::session::project_usage::ProjectUsageStore;
+use crate::session::read_marks::ReadMarkStore;
+use crate::types::ServerEvent;
+use arc_swap::ArcSwap;
+use axum::body::Body;
+use axum::http::{Method, Request, StatusCode};
+use axum::Router;
+use serde_json::Value;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tower::ServiceExt;
+
+/// How long to wait for an expected event before failing the test.
+const EVENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A temporary projects directory, removed on drop.
+pub struct FixtureTree {
+    root: PathBuf,
+}
+
+impl FixtureTree {
+    pub fn new() -> Self {
+        let root =
+            std::env::temp_dir().join(format!("agents-dashboard-fixture-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        Self { root }
+    }
+
+    /// Watch this tree, checking timers and polling logs far more often than in production.
+    pub fn provider_config(&self) -> ProviderConfig {
+        ProviderConfig {
+            discovery: DiscoveryConfig {
+                projects_dir: self.root.clone(),
+                scan_interval: Duration::from_millis(50),
+                max_log_age: Duration::from_secs(60),
+            },
+            timer_interval: Duration::from_millis(50),
+            poll_interval: Duration::from_millis(50),
+            // Small enough for tests to write an over-long line cheaply.
+            max_line_bytes: 64 * 1024,
+            process_lookup: None,
+            session: SharedSettings::default(),
+            host: "workstation".to_string(),
+        }
+    }
+
+    pub fn log_path(&self, project_path: &str, session_id: &str) -> PathBuf {
+        self.root
+            .join(encode_project_path(project_path))
+            .join(format!("{}.jsonl", session_id))
+    }
+
+    /// Append entries to a session's log, creating it (and its project directory) if needed.
+    pub fn append(&self, project_path: &str, session_id: &str, entries: &[Value]) {
+        let path = self.log_path(project_path, session_id);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        for entry in entries {
+            writeln!(file, "{}", entry).unwrap();
+        }
+    }
+}
+
+impl Drop for FixtureTree {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.root);
+    }
+}
+
+/// A started session manager watching `fixtures`, and a router serving it.
+pub async fn start_app(fixtures: &FixtureTree) -> (Arc<SessionManager>, Router) {
+    start_app_with_config(fixtures, Config::default()).await
+}
+
+pub async fn start_app_with_config(
+    fixtures: &FixtureTree,
+    config: Config,
+) -> (Arc<SessionManager>, Router) {
+    let manager = new_manager(fixtures);
+    manager.start().await;
+    let (broadcast_tx, _) = broadcast::channel(16);
+    let (message_tx, _) = broadcast::channel(16);
+    let state = app_state(&manager, broadcast_tx, message_tx, config);
+    (manager, create_router(state, None))
+}
+
+/// A session manager watching `fixtures`, not yet started.
+pub fn new_manager(fixtures: &FixtureTree) -> Arc<SessionManager> {
+    Arc::new(SessionManager::with_config(
+        PrefsStore::in_memory(),
+        ProjectUsageStore::in_memory(),
+        ReadMarkStore::in_memory(),
+        fixtures.provider_config(),
+    ))
+}
+
+/// Start `manager` the way the server does, with its events routed to the broadcast
+/// channels, and serve the router on a local port.
+pub async fn serve_app(manager: Arc<SessionManager>) -> SocketAddr {
+    let (broadcast_tx, _) = broadcast::channel(256);
+    let (message_tx, _) = broadcast::channel(256);
+    serve_app_with_channels(manager, broadcast_tx, message_tx).await
+}
+
+/// `serve_app` with the given configuration.
+pub async fn serve_app_with_config(manager: Arc<SessionManager>, config: Config) -> SocketAddr {
+    let (broadcast_tx, _) = broadcast::channel(256);
+    let (message_tx, _) = broadcast::channel(256);
+    serve(manager, broadcast_tx, message_tx, config).await
+}
+
+/// `serve_app` with the given broadcast channels, e.g. tiny ones to make clients lag.
+pub async fn serve_app_with_channels(
+    manager: Arc<SessionManager>,
+    broadcast_tx: broadcast::Sender<ServerEvent>,
+    message_tx: broadcast::Sender<ServerEvent>,
+) -> SocketAddr {
+    serve(manager, broadcast_tx, message_tx, Config::default()).await
+}
+
+async fn serve(
+    manager: Arc<SessionManager>,
+    broadcast_tx: broadcast::Sender<ServerEvent>,
+    message_tx: broadcast::Sender<ServerEvent>,
+    config: Config,
+) -> SocketAddr {
+    crate::server::routing::spawn(
+        manager.clone(),
+        broadcast_tx.clone(),
+        message_tx.clone(),
+        None,
+        Arc::new(Notifier::new(None)),
+        Duration::ZERO,
+    );
+    manager.start().await;
+    let state = app_state(&manager, broadcast_tx, message_tx, config);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let router = create_router(state, None);
+    tokio::spawn(async move { axum::serve(listener, router).await });
+    addr
+}
+
+fn app_state(
+    manager: &Arc<SessionManager>,
+    broadcast_tx: broadcast::Sender<ServerEvent>,
+    message_tx: broadcast::Sender<ServerEvent>,
+    config: Config,
+) -> Arc<AppState> {
+    Arc::new(AppState {
+        session_manager: manager.clone(),
+        broadcast_tx,
+        message_tx,
+        limits: Arc::new(ApiLimits::new(0, 1_000, 4)),
+        journal: None,
+        connections: Arc::new(ConnectionRegistry::new()),
+        config: ArcSwap::from_pointee(config),
+        config_source: ConfigSource::default(),
+        notifier: Arc::new(Notifier::new(None)),
+        schema: Arc::new(crate::schema::combined()),
+    })
+}
+
+/// Receive provider events until one matches `done`, returning every event seen
+/// as `type` (plus the new state for state changes), e.g. `state_changed:idle`.
+pub async fn events_until(
+    manager: &SessionManager,
+    done: impl Fn(&ProviderEvent) -> bool,
+) -> Vec<String> {
+    let mut rx = manager.event_rx.lock().await;
+    let mut seen = Vec::new();
+    loop {
+        let event = tokio::time::timeout(EVENT_TIMEOUT, rx.recv())
+            .await
+            .unwrap_or_else(|_| panic!("timed out waiting for events; saw {:?}", seen))
+            .expect("event channel closed");
+        seen.push(describe(&event));
+        if done(&event) {
+            return seen;
+        }
+    }
+}
+
+fn describe(event: &ProviderEvent) -> String {
+    let kind = serde_json::to_value(event).unwrap()["type"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    match event {
+        ProviderEvent::StateChanged { current, .. } => format!("{}:{}", kind, current),
+        _ => kind,
+    }
+}
+
+/// GET `uri` and parse the response as JSON, failing the test on any other status.
+pub async fn get_json(router: &Router, uri: &str) -> Value {
+    let request = Request::get(uri).body(Body::empty()).unwrap();
+    let response = router.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK, "GET {}", uri);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+/// Send `body` as JSON to `uri`, with `X-Confirm: yes` if `confirm`, returning the
+/// status and the response as JSON (null if it isn't).
+pub async fn send_json(
+    router: &Router,
+    method: Method,
+    uri: &str,
+    body: Option<Value>,
+    confirm: bool,
+) -> (StatusCode, Value) {
+    let mut request = Request::builder().method(method).uri(uri);
+    if confirm {
+        request = request.header(CONFIRM_HEADER, "yes");
+    }
+    let request = match body {
+        Some(body) => request
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string())),
+        None => request.body(Body::empty()),
+    };
+    let response = router.clone().oneshot(request.unwrap()).await.unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use tokio::sync::oneshot;
+    use tokio_tungstenite::tungstenite::Message;
+
+    fn now() -> String {
+        chrono::Utc::now().to_rfc3339()
+    }
+
+    fn user(uuid: &str, text: &str) -> Value {
+        json!({
+            "type": "user",
+            "uuid": uuid,
+            "timestamp": now(),
+            "cwd": "/work/demo",
+            "gitBranch": "main",
+            "message": {"role": "user", "content": text}
+        })
+    }
+
+    fn assistant(uuid: &str, text: &str) -> Value {
+        json!({
+            "type": "assistant",
+            "uuid": uuid,
+            "timestamp": now(),
+            "message": {
+                "model": "claude-sonnet-4-20250514",
+                "content": [{"type": "text", "text": text}],
+                "usage": {"input_tokens": 100, "output_tokens": 20}
+            }
+        })
+    }
+
+    fn turn_done() -> Value {
+        json!({
+            "type": "system",
+            "subtype": "turn_duration",
+            "timestamp": now(),
+            "durationMs": 1500
+        })
+    }
+
+    #[tokio::test]
+    async fn test_discovery_to_http() {
+        let fixtures = FixtureTree::new();
+        fixtures.append(
+            "/work/demo",
+            "s1",
+            &[user("u1", "fix the build"), assistant("a1", "Looking.")],
+        );
+        let (manager, router) = start_app(&fixtures).await;
+
+        // The initial read: shown once the model is known, then its backlog.
+        let events = events_until(&manager, |e| {
+            matches!(e, ProviderEvent::UsageUpdated { .. })
+        })
+        .await;
+        assert_eq!(
+            events,
+            [
+                "session_discovered",
+                "new_message",
+                "new_message",
+                "usage_updated"
+            ]
+        );
+
+        // Entries appended later arrive through the watcher.
+        fixtures.append("/work/demo", "s1", &[turn_done()]);
+        let events =
+            events_until(&manager, |e| matches!(e, ProviderEvent::NewMessage { .. })).await;
+        assert_eq!(events, ["state_changed:idle", "new_message"]);
+
+        let sessions = get_json(&router, "/api/sessions").await;
+        assert_eq!(sessions.as_array().unwrap().len(), 1);
+        let session = &sessions[0];
+        assert_eq!(session["sessionId"], "s1");
+        assert_eq!(session["state"], "idle");
+        assert_eq!(session["projectName"], "demo");
+        assert_eq!(session["initialTask"], "fix the build");
+        assert_eq!(session["gitStatus"]["branch"], "main");
+        assert_eq!(session["cumulativeUsage"]["inputTokens"], 100);
+        assert_eq!(session["host"], "workstation");
+        assert_eq!(session["messageCount"], 3);
+        assert_eq!(session["toolCallCount"], 0);
+        let log_bytes = std::fs::metadata(fixtures.log_path("/work/demo", "s1"))
+            .unwrap()
+            .len();
+        assert_eq!(session["transcriptBytes"], log_bytes);
+
+        let search = get_json(&router, "/api/search?q=build&host=workstation").await;
+        assert_eq!(search["totalSessions"], 1);
+        let search = get_json(&router, "/api/search?q=build&host=laptop").await;
+        assert_eq!(search["totalSessions"], 0);
+
+        let detail = get_json(&router, "/api/sessions/s1").await;
+        let contents: Vec<&str> = detail["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["content"].as_str().unwrap())
+            .collect();
+        assert_eq!(contents[..2], ["fix the build", "Looking."]);
+        assert_eq!(contents.len(), 3);
+
+        let stats = get_json(&router, "/api/stats").await;
+        assert_eq!(stats["totalSessions"], 1);
+        assert_eq!(stats["totalTurns"], 1);
+
+        // Usage is totalled by the directory the session was discovered in.
+        assert!(manager.record_project_usage("s1").await.is_none());
+        let projects = get_json(&router, "/api/projects").await;
+        assert_eq!(projects[0]["projectPath"], "/work/demo");
+        assert_eq!(projects[0]["sessions"], 1);
+        assert_eq!(projects[0]["usage"]["inputTokens"], 100);
+
+        let report = get_json(&router, "/api/reports/usage?period=month").await;
+        assert_eq!(report["period"], "month");
+        assert_eq!(report["sessions"], 1);
+        assert_eq!(report["totalTokens"], 120);
+        assert_eq!(report["projects"][0]["projectPath"], "/work/demo");
+        assert_eq!(report["models"][0]["model"], "claude-sonnet-4-20250514");
+        assert_eq!(report["topTools"], json!([]));
+        // Recording only just started.
+        assert_eq!(report["coverage"]["complete"], false);
+        let request = Request::get("/api/reports/usage?format=markdown")
+            .body(Body::empty())
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(
+            response.headers()["content-type"],
+            "text/markdown; charset=utf-8"
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let markdown = String::from_utf8(body.to_vec()).unwrap();
+        assert!(markdown.starts_with("# Usage report: "), "{}", markdown);
+        assert!(markdown.contains("`claude-sonnet-4-20250514`"));
+        for bad in ["period=year", "format=pdf"] {
+            let request = Request::get(format!("/api/reports/usage?{}", bad))
+                .body(Body::empty())
+                .unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", bad);
+        }
+
+        // A second session in another project is discovered by the periodic scan.
+        fixtures.append("/work/other", "s2", &[assistant("b1", "Hi.")]);
+        let events = events_until(&manager, |e| {
+            matches!(e, ProviderEvent::SessionDiscovered { .. })
+        })
+        .await;
+        assert_eq!(events.last().unwrap(), "session_discovered");
+        assert_eq!(
+            get_json(&router, "/api/sessions")
+                .await
+                .as_array()
+                .unwrap()
+                .len(),
+            2
+        );
+
+        manager.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_raw_log_pages_and_redacts() {
+        use axum::extract::ConnectInfo;
+
+        let fixtures = FixtureTree::new();
+        fixtures.append(
+            "/work/demo",
+            "s1",
+            &[
+                user("u1", "use sk-abcdefghijklmnopqrstuvwxyz"),
+                assistant("a1", "Done."),
+            ],
+        );
+        let (manager, router) = start_app(&fixtures).await;
+        events_until(&manager, |e| {
+            matches!(e, ProviderEvent::UsageUpdated { .. })
+        })
+        .await;
+        let size = std::fs::metadata(fixtures.log_path("/work/demo", "s1"))
+            .unwrap()
+            .len();
+
+        let log = std::fs::read(fixtures.log_path("/work/demo", "s1")).unwrap();
+        let first_line = log.iter().position(|&b| b == b'\n').unwrap() as u64 + 1;
+        let secret = log.windows(3).position(|w| w == b"sk-").unwrap();
+
+        let request_from = |uri: String, peer: &str| {
+            let router = router.clone();
+            let peer: SocketAddr = peer.parse().unwrap();
+            async move {
+                let mut request = Request::get(uri).body(Body::empty()).unwrap();
+                request.extensions_mut().insert(ConnectInfo(peer));
+                router.oneshot(request).await.unwrap()
+            }
+        };
+        let get = |uri: &str| request_from(uri.to_string(), "127.0.0.1:40000");
+        // A range starting inside the secret is widened to the whole line, so the
+        // secret is still masked rather than returned in two unmasked halves.
+        let response = get(&format!("/api/sessions/s1/raw?offset={}&limit=20", secret + 5)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-file-size"], size.to_string().as_str());
+        assert_eq!(response.headers()["x-offset"], "0");
+        assert_eq!(
+            response.headers()["x-next-offset"],
+            first_line.to_string().as_str()
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.starts_with("{\"cwd\":\"/work/demo\","));
+        assert!(body.contains("sk-a…wxyz") && !body.contains("ghijklmnop"));
+
+        let response = get("/api/sessions/s1/raw").await;
+        assert_eq!(
+            response.headers()["x-next-offset"],
+            size.to_string().as_str()
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("sk-a…wxyz") && !body.contains("sk-abcdefgh"));
+
+        assert_eq!(
+            get("/api/sessions/nope/raw").await.status(),
+            StatusCode::NOT_FOUND
+        );
+        std::fs::remove_file(fixtures.log_path("/work/demo", "s1")).unwrap();
+        assert_eq!(
+            request_from("/api/sessions/s1/raw".to_string(), "192.168.1.20:40000")
+                .await
+                .status(),
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(get("/api/sessions/s1/raw").await.status(), StatusCode::GONE);
+
+        manager.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_resume_chains_grouped_in_search_and_projects() {
+        let fixtures = FixtureTree::new();
+        let at = |minutes_ago: i64, mut entry: Value| {
+            let when = chrono::Utc::now() - chrono::Duration::minutes(minutes_ago);
+            entry["timestamp"] = json!(when.to_rfc3339());
+            entry
+        };
+        fixtures.append(
+            "/work/demo",
+            "old",
+            &[
+                at(10, user("o1", "fix the build")),
+                at(9, assistant("o2", "The build needs a newer toolchain.")),
+            ],
+        );
+        let mut resumed = user("n1", "the build still fails");
+        resumed["parentUuid"] = json!("o2");
+        fixtures.append(
+            "/work/demo",
+            "new",
+            &[at(1, resumed), at(1, assistant("n2", "Looking."))],
+        );
+        fixtures.append(
+            "/work/demo",
+            "other",
+            &[user("x1", "build the docs"), assistant("x2", "Done.")],
+        );
+        let (manager, router) = start_app(&fixtures).await;
+        for _ in 0..100 {
+            let (_, chain) =
+                send_json(&router, Method::GET, "/api/sessions/new/chain", None, false).await;
+            if chain["sessions"].as_array().is_some_and(|s| s.len() == 2) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        let ungrouped = get_json(&router, "/api/search?q=build").await;
+        assert_eq!(ungrouped["totalSessions"], 3);
+        let search = get_json(&router, "/api/search?q=build&group_chains=true").await;
+        assert_eq!(search["totalSessions"], 2);
+        let chain = search["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|r| r["session"]["sessionId"] == "new")
+            .unwrap();
+        let earlier = chain["earlierInChain"].as_array().unwrap();
+        assert_eq!(earlier.len(), 1);
+        assert_eq!(earlier[0]["session"]["sessionId"], "old");
+        // Counted across the chain.
+        let count = |id: &str| {
+            ungrouped["results"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|r| r["session"]["sessionId"] == id)
+                .unwrap()["matchCount"]
+                .as_u64()
+                .unwrap()
+        };
+        assert_eq!(chain["matchCount"], count("new") + count("old"));
+
+        let ids = |grouped: Value| -> Vec<String> {
+            let mut ids: Vec<String> = grouped[0]["sessions"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|s| s["sessionId"].as_str().unwrap().to_string())
+                .collect();
+            ids.sort();
+            ids
+        };
+        let grouped = get_json(&router, "/api/sessions?group_by=project").await;
+        assert_eq!(ids(grouped), ["new", "old", "other"]);
+        let uri = "/api/sessions?group_by=project&group_chains=true";
+        assert_eq!(ids(get_json(&router, uri).await), ["new", "other"]);
+
+        manager.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_sessions_batch() {
+        let fixtures = FixtureTree::new();
+        fixtures.append("/work/demo", "s1", &[assistant("a1", "Hello.")]);
+        fixtures.append("/work/other", "s2", &[assistant("b1", "Hi.")]);
+        let (manager, router) = start_app(&fixtures).await;
+        let discovered = std::cell::Cell::new(0);
+        events_until(&manager, |e| {
+            if matches!(e, ProviderEvent::SessionDiscovered { .. }) {
+                discovered.set(discovered.get() + 1);
+            }
+            discovered.get() == 2
+        })
+        .await;
+
+        let post = |ids: Vec<String>| {
+            let router = router.clone();
+            async move {
+                let request = Request::post("/api/sessions/batch")
+                    .header("content-type", "application/json")
+                    .header(CONFIRM_HEADER, "yes")
+                    .body(Body::from(json!({ "sessionIds": ids }).to_string()))
+                    .unwrap();
+                router.oneshot(request).await.unwrap()
+            }
+        };
+        let response = post(vec!["s2".into(), "gone".into(), "s1".into()]).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let batch: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(batch["sessions"]["s1"]["projectName"], "demo");
+        assert_eq!(batch["sessions"]["s2"]["projectName"], "other");
+        assert_eq!(batch["sessions"].as_object().unwrap().len(), 2);
+        assert_eq!(batch["notFound"], json!(["gone"]));
+
+        let ids = json!({ "sessionIds": ["s2", "gone", "s2"] });
+        let (status, _) =
+            send_json(&router, Method::POST, "/api/sessions/batch", Some(ids.clone()), false).await;
+        assert_eq!(status, StatusCode::PRECONDITION_REQUIRED);
+        let uri = "/api/sessions/batch?dry_run=true";
+        let (status, plan) = send_json(&router, Method::POST, uri, Some(ids), false).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(plan, json!({ "found": ["s2"], "notFound": ["gone"] }));
+
+        let too_many = (0..201).map(|i| format!("s{}", i)).collect();
+        assert_eq!(post(too_many).await.status(), StatusCode::BAD_REQUEST);
+
+        manager.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_sessions_grouped_by_project() {
+        let fixtures = FixtureTree::new();
+        let at = |minutes_ago: i64| {
+            json!((chrono::Utc::now() - chrono::Duration::minutes(minutes_ago)).to_rfc3339())
+        };
+        let mut older = assistant("a1", "Hello.");
+        older["timestamp"] = at(5);
+        fixtures.append("/work/demo", "s1", &[older]);
+        let mut newer = assistant("b1", "Hi.");
+        newer["timestamp"] = at(3);
+        fixtures.append("/work/demo", "s2", &[newer]);
+        // Found under /work/other, but working in /work/demo from its first prompt on.
+        let mut prompt = user("c1", "update the demo docs");
+        let mut reply = assistant("c2", "On it.");
+        prompt["timestamp"] = at(2);
+        reply["timestamp"] = at(1);
+        fixtures.append("/work/other", "s3", &[prompt, reply]);
+        let (manager, router) = start_app(&fixtures).await;
+        let discovered = std::cell::Cell::new(0);
+        events_until(&manager, |e| {
+            if matches!(e, ProviderEvent::SessionDiscovered { .. }) {
+                discovered.set(discovered.get() + 1);
+            }
+            discovered.get() == 3
+        })
+        .await;
+
+        let sessions = get_json(&router, "/api/sessions").await;
+        let wandered = sessions
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|s| s["sessionId"] == "s3")
+            .unwrap();
+        assert_eq!(wandered["projectPath"], "/work/demo");
+
+        // Grouped by where each session was found, most recent activity first.
+        let groups = get_json(&router, "/api/sessions?group_by=project").await;
+        let grouped: Vec<(&str, Vec<&str>)> = groups
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|group| {
+                let ids = group["sessions"].as_array().unwrap().iter();
+                (
+                    group["project"]["projectPath"].as_str().unwrap(),
+                    ids.map(|s| s["sessionId"].as_str().unwrap()).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            grouped,
+            [
+                ("/work/other", vec!["s3"]),
+                ("/work/demo", vec!["s2", "s1"])
+            ]
+        );
+        assert_eq!(groups[1]["project"]["projectName"], "demo");
+        assert_eq!(groups[1]["project"]["sessions"], 2);
+
+        let request = Request::get("/api/sessions?group_by=model")
+            .body(Body::empty())
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        manager.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_diff_errors() {
+        let fixtures = FixtureTree::new();
+        // The session's working directory, /work/demo, doesn't exist here.
+        fixtures.append(
+            "/work/demo",
+            "s1",
+            &[user("u1", "hello"), assistant("a1", "Hi.")],
+        );
+        let (manager, router) = start_app(&fixtures).await;
+        events_until(&manager, |e| {
+            matches!(e, ProviderEvent::UsageUpdated { .. })
+        })
+        .await;
+
+        let status = |uri: &'static str| {
+            let router = router.clone();
+            async move {
+                let request = Request::get(uri).body(Body::empty()).unwrap();
+                router.oneshot(request).await.unwrap().status()
+            }
+        };
+        assert_eq!(status("/api/sessions/s1/diff").await, StatusCode::CONFLICT);
+        assert_eq!(
+            status("/api/sessions/s1/diff?path=../secrets").await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status("/api/sessions/nope/diff").await,
+            StatusCode::NOT_FOUND
+        );
+
+        manager.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_open_session() {
+        use axum::extract::ConnectInfo;
+        use std::net::SocketAddr;
+
+        let fixtures = FixtureTree::new();
+        // A directory name a shell would trip over.
+        let dir = std::env::temp_dir()
+            .join(format!("agents-dashboard-open-{}", uuid::Uuid::new_v4()))
+            .join("it's a \"repo\"; $(exit 1)");
+        std::fs::create_dir_all(&dir).unwrap();
+        let cwd = dir.to_str().unwrap();
+        let mut first = user("u1", "hello");
+        first["cwd"] = json!(cwd);
+        fixtures.append(cwd, "s1", &[first, assistant("a1", "Hi.")]);
+
+        let open = |router: Router,
+                    session: &'static str,
+                    peer: &'static str,
+                    origin: Option<&'static str>| async move {
+            let mut request = Request::post(format!("/api/sessions/{}/open", session))
+                .header(CONFIRM_HEADER, "yes");
+            if let Some(origin) = origin {
+                request = request.header("origin", origin);
+            }
+            let mut request = request.body(Body::empty()).unwrap();
+            let peer: SocketAddr = peer.parse().unwrap();
+            request.extensions_mut().insert(ConnectInfo(peer));
+            let response = router.oneshot(request).await.unwrap();
+            let status = response.status();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (
+                status,
+                serde_json::from_slice::<Value>(&body).unwrap_or(Value::Null),
+            )
+        };
+        let local = "127.0.0.1:40000";
+
+        // Off by default.
+        let (manager, router) = start_app(&fixtures).await;
+        events_until(&manager, |e| {
+            matches!(e, ProviderEvent::UsageUpdated { .. })
+        })
+        .await;
+        assert_eq!(
+            open(router, "s1", local, None).await.0,
+            StatusCode::FORBIDDEN
+        );
+        manager.stop().await;
+
+        let mut config = Config::default();
+        config.server.allow_open_commands = true;
+        config.server.open_command = Some(r#"touch "{path}/opened""#.to_string());
+        let (manager, router) = start_app_with_config(&fixtures, config).await;
+        events_until(&manager, |e| {
+            matches!(e, ProviderEvent::UsageUpdated { .. })
+        })
+        .await;
+
+        let forbidden = [
+            ("192.168.1.5:40000", None),
+            (local, Some("https://example.com")),
+        ];
+        for (peer, origin) in forbidden {
+            let (status, _) = open(router.clone(), "s1", peer, origin).await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{} {:?}", peer, origin);
+        }
+        assert!(!dir.join("opened").exists());
+        assert_eq!(
+            open(router.clone(), "nope", local, None).await.0,
+            StatusCode::NOT_FOUND
+        );
+
+        // Unconfirmed, nothing runs; a dry run shows the command.
+        let open_unconfirmed = |uri: &str| {
+            let mut request = Request::post(uri).body(Body::empty()).unwrap();
+            request
+                .extensions_mut()
+                .insert(ConnectInfo(local.parse::<SocketAddr>().unwrap()));
+            router.clone().oneshot(request)
+        };
+        let response = open_unconfirmed("/api/sessions/s1/open").await.unwrap();
+        assert_eq!(response.status(), StatusCode::PRECONDITION_REQUIRED);
+        let response = open_unconfirmed("/api/sessions/s1/open?dry_run=true")
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let plan: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            plan,
+            json!({
+                "sessionId": "s1",
+                "command": ["touch", format!("{}/opened", cwd)],
+                "workingDirectory": cwd,
+            })
+        );
+        assert!(!dir.join("opened").exists());
+
+        let (status, body) = open(router, "s1", local, Some("http://localhost:5173")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["success"], true, "{}", body);
+        assert_eq!(body["command"], json!(["touch", format!("{}/opened", cwd)]));
+        assert!(dir.join("opened").exists());
+
+        manager.stop().await;
+        let _ = std::fs::remove_dir_all(dir.parent().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_interrupt_session() {
+        use axum::extract::ConnectInfo;
+        use std::os::unix::process::ExitStatusExt;
+
+        let Some(sleep) = ["/bin/sleep", "/usr/bin/sleep"]
+            .into_iter()
+            .find(|p| std::path::Path::new(p).exists())
+        else {
+            return;
+        };
+        let root = std::env::temp_dir().join(format!(
+            "agents-dashboard-interrupt-{}",
+            uuid::Uuid::new_v4()
+        ));
+        let dir = root.join("project");
+        std::fs::create_dir_all(&dir).unwrap();
+        let dir = dir.canonicalize().unwrap();
+        // Process detection goes by name.
+        let fake_claude = root.join("claude");
+        std::fs::copy(sleep, &fake_claude).unwrap();
+        let spawn = || {
+            std::process::Command::new(&fake_claude)
+                .arg("30")
+                .current_dir(&dir)
+                .spawn()
+                .unwrap()
+        };
+
+        let fixtures = FixtureTree::new();
+        let cwd = dir.to_str().unwrap();
+        let mut first = user("u1", "hello");
+        first["cwd"] = json!(cwd);
+        fixtures.append(cwd, "s1", &[first, assistant("a1", "Hi.")]);
+
+        let local: SocketAddr = "127.0.0.1:40000".parse().unwrap();
+        let interrupt = move |router: Router, session: &'static str, body: Option<Value>| async move {
+            let request = Request::post(format!("/api/sessions/{}/interrupt", session))
+                .header("X-Confirm", "yes");
+            let request = match body {
+                Some(body) => request
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string())),
+                None => request.body(Body::empty()),
+            };
+            let mut request = request.unwrap();
+            request.extensions_mut().insert(ConnectInfo(local));
+            let response = router.oneshot(request).await.unwrap();
+            let status = response.status();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (
+                status,
+                serde_json::from_slice::<Value>(&body).unwrap_or(Value::Null),
+            )
+        };
+
+        // Off by default.
+        let (manager, router) = start_app(&fixtures).await;
+        events_until(&manager, |e| {
+            matches!(e, ProviderEvent::UsageUpdated { .. })
+        })
+        .await;
+        assert_eq!(interrupt(router, "s1", None).await.0, StatusCode::FORBIDDEN);
+        manager.stop().await;
+
+        let mut config = Config::default();
+        config.server.allow_session_control = true;
+        let (manager, router) = start_app_with_config(&fixtures, config).await;
+        events_until(&manager, |e| {
+            matches!(e, ProviderEvent::UsageUpdated { .. })
+        })
+        .await;
+        assert_eq!(
+            interrupt(router.clone(), "nope", None).await.0,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            interrupt(router.clone(), "s1", None).await.0,
+            StatusCode::NOT_FOUND
+        );
+        // Only from this machine, and not from another site open in its browser.
+        let forbidden = [
+            (Some("192.168.1.5:40000"), None),
+            (Some("127.0.0.1:40000"), Some("https://example.com")),
+            (None, None),
+        ];
+        for (peer, origin) in forbidden {
+            let mut request = Request::post("/api/sessions/s1/interrupt?dry_run=true");
+            if let Some(origin) = origin {
+                request = request.header("origin", origin);
+            }
+            let mut request = request.body(Body::empty()).unwrap();
+            if let Some(peer) = peer {
+                let peer: SocketAddr = peer.parse().unwrap();
+                request.extensions_mut().insert(ConnectInfo(peer));
+            }
+            let status = router.clone().oneshot(request).await.unwrap().status();
+            assert_eq!(status, StatusCode::FORBIDDEN, "{:?} {:?}", peer, origin);
+        }
+
+        let mut children = [spawn(), spawn()];
+        let pids: Vec<u32> = children.iter().map(|c| c.id()).collect();
+        let (status, body) = interrupt(router.clone(), "s1", None).await;
+        if status == StatusCode::SERVICE_UNAVAILABLE {
+            // Processes can't be listed in this environment.
+            for child in &mut children {
+                let _ = child.kill();
+            }
+            manager.stop().await;
+            return;
+        }
+        assert_eq!(status, StatusCode::CONFLICT);
+        let mut listed: Vec<u32> = serde_json::from_value(body["pids"].clone()).unwrap();
+        listed.sort_unstable();
+        let mut expected = pids.clone();
+        expected.sort_unstable();
+        assert_eq!(listed, expected);
+        // A pid that isn't one of the session's processes is refused.
+        assert_eq!(
+            interrupt(router.clone(), "s1", Some(json!({ "pid": 1 })))
+                .await
+                .0,
+            StatusCode::NOT_FOUND
+        );
+
+        // Unconfirmed, nothing is sent; a dry run names the process it would signal.
+        let post = |uri: &str| {
+            let mut request = Request::post(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(json!({ "pid": pids[0] }).to_string()))
+                .unwrap();
+            request.extensions_mut().insert(ConnectInfo(local));
+            router.clone().oneshot(request)
+        };
+        let response = post("/api/sessions/s1/interrupt").await.unwrap();
+        assert_eq!(response.status(), StatusCode::PRECONDITION_REQUIRED);
+        let response = post("/api/sessions/s1/interrupt?dry_run=true")
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let plan: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            plan,
+            json!({
+                "sessionId": "s1",
+                "pid": pids[0],
+                "signal": "SIGINT",
+                "workingDirectory": cwd,
+            })
+        );
+        assert!(children[0].try_wait().unwrap().is_none());
+
+        let (status, body) = interrupt(router.clone(), "s1", Some(json!({ "pid": pids[0] }))).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body, json!({ "pid": pids[0], "signal": "SIGINT" }));
+        assert_eq!(children[0].wait().unwrap().signal(), Some(2));
+
+        // With one process left, no pid is needed.
+        let (status, body) = interrupt(router.clone(), "s1", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["pid"], pids[1]);
+        assert_eq!(children[1].wait().unwrap().signal(), Some(2));
+
+        let detail = get_json(&router, "/api/sessions/s1").await;
+        let notes: Vec<&Value> = detail["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|m| m["metadata"]["interrupt"] == true)
+            .collect();
+        assert_eq!(notes.len(), 2);
+        assert_eq!(notes[0]["type"], "state_change");
+        assert_eq!(notes[0]["role"], "system");
+
+        manager.stop().await;
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn test_dismiss_needs_confirmation() {
+        let fixtures = FixtureTree::new();
+        fixtures.append(
+            "/work/demo",
+            "s1",
+            &[user("u1", "hello"), assistant("a1", "Hi."), turn_done()],
+        );
+        let (manager, router) = start_app(&fixtures).await;
+        events_until(&manager, |e| {
+            matches!(e, ProviderEvent::UsageUpdated { .. })
+        })
+        .await;
+        let delete = |uri: &str, confirm: bool| {
+            let mut request = Request::delete(uri);
+            if confirm {
+                request = request.header("X-Confirm", "yes");
+            }
+            router.clone().oneshot(request.body(Body::empty()).unwrap())
+        };
+
+        let response = delete("/api/sessions/s1?dry_run=true", false)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let plan: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(plan["sessionId"], "s1");
+        assert_eq!(plan["state"], "idle");
+        assert_eq!(
+            plan["logFile"],
+            fixtures.log_path("/work/demo", "s1").display().to_string()
+        );
+        assert_eq!(
+            delete("/api/sessions/nope?dry_run=true", false)
+                .await
+                .unwrap()
+                .status(),
+            StatusCode::NOT_FOUND
+        );
+
+        assert_eq!(
+            delete("/api/sessions/s1", false).await.unwrap().status(),
+            StatusCode::PRECONDITION_REQUIRED
+        );
+        get_json(&router, "/api/sessions/s1").await;
+        assert_eq!(
+            delete("/api/sessions/s1", true).await.unwrap().status(),
+            StatusCode::NO_CONTENT
+        );
+        assert!(get_json(&router, "/api/sessions")
+            .await
+            .as_array()
+            .unwrap()
+            .is_empty());
+
+        manager.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_prefs_need_confirmation() {
+        let fixtures = FixtureTree::new();
+        fixtures.append("/work/demo", "s1", &[user("u1", "hello"), assistant("a1", "Hi.")]);
+        let (manager, router) = start_app(&fixtures).await;
+        events_until(&manager, |e| {
+            matches!(e, ProviderEvent::SessionDiscovered { .. })
+        })
+        .await;
+        let changes = [
+            (Method::PATCH, "prefs", json!({ "pinned": true })),
+            (Method::PUT, "labels", json!(["ci"])),
+        ];
+        for (method, path, body) in changes {
+            let uri = format!("/api/sessions/s1/{}", path);
+            let (status, _) =
+                send_json(&router, method.clone(), &uri, Some(body.clone()), false).await;
+            assert_eq!(status, StatusCode::PRECONDITION_REQUIRED, "{}", uri);
+            let dry_run = format!("{}?dry_run=true", uri);
+            let (status, plan) =
+                send_json(&router, method.clone(), &dry_run, Some(body.clone()), false).await;
+            assert_eq!(status, StatusCode::OK, "{}", uri);
+            assert_eq!(plan["pinned"], path == "prefs", "{}", plan);
+            let missing = format!("/api/sessions/nope/{}?dry_run=true", path);
+            let (status, _) = send_json(&router, method, &missing, Some(body), false).await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+        }
+        // The note is sent as plain text.
+        let note = |uri: &str, confirm: bool| {
+            let mut request = Request::put(uri);
+            if confirm {
+                request = request.header(CONFIRM_HEADER, "yes");
+            }
+            router
+                .clone()
+                .oneshot(request.body(Body::from("flaky test")).unwrap())
+        };
+        let response = note("/api/sessions/s1/note", false).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PRECONDITION_REQUIRED);
+        let response = note("/api/sessions/s1/note?dry_run=true", false)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let plan: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(plan["note"], "flaky test");
+
+        // Nothing was kept.
+        let session = get_json(&router, "/api/sessions/s1").await;
+        assert_eq!(session["pinned"], false);
+        assert!(session.get("note").is_none_or(|note| note.is_null()));
+        assert!(session.get("labels").is_none_or(|labels| labels == &json!([])));
+
+        let (status, prefs) = send_json(
+            &router,
+            Method::PATCH,
+            "/api/sessions/s1/prefs",
+            Some(json!({ "pinned": true })),
+            true,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(prefs["pinned"], true);
+        assert_eq!(
+            note("/api/sessions/s1/note", true).await.unwrap().status(),
+            StatusCode::OK
+        );
+        let session = get_json(&router, "/api/sessions/s1").await;
+        assert_eq!(session["pinned"], true);
+        assert_eq!(session["note"], "flaky test");
+
+        manager.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_history_loads_old_sessions() {
+        let fixtures = FixtureTree::new();
+        let days_ago = chrono::Duration::days(3);
+        let old = (chrono::Utc::now() - days_ago).to_rfc3339();
+        for (id, text) in [("old1", "first try"), ("old2", "second try")] {
+            let mut entries = vec![user("u1", text), assistant("a1", "Done."), turn_done()];
+            for entry in &mut entries {
+                entry["timestamp"] = json!(old);
+            }
+            fixtures.append("/work/demo", id, &entries);
+            let modified = std::time::SystemTime::now() - days_ago.to_std().unwrap();
+            std::fs::File::options()
+                .write(true)
+                .open(fixtures.log_path("/work/demo", id))
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+        }
+        fixtures.append(
+            "/work/demo",
+            "s1",
+            &[user("u1", "fix the build"), assistant("a1", "On it.")],
+        );
+        let (manager, router) = start_app(&fixtures).await;
+        events_until(&manager, |e| {
+            matches!(e, ProviderEvent::SessionDiscovered { .. })
+        })
+        .await;
+        assert_eq!(
+            get_json(&router, "/api/sessions")
+                .await
+                .as_array()
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let history = |body: Value| {
+            let router = router.clone();
+            async move {
+                let request = Request::post("/api/projects/history")
+                    .header("content-type", "application/json")
+                    .header(CONFIRM_HEADER, "yes")
+                    .body(Body::from(body.to_string()))
+                    .unwrap();
+                let response = router.oneshot(request).await.unwrap();
+                let status = response.status();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                (status, serde_json::from_slice::<Value>(&body).unwrap())
+            }
+        };
+        // Unconfirmed, nothing is read; a dry run names the logs it would read.
+        let request = json!({ "projectPath": "/work/demo", "sessionIds": ["old1"] });
+        let (status, _) = send_json(
+            &router,
+            Method::POST,
+            "/api/projects/history",
+            Some(request.clone()),
+            false,
+        )
+        .await;
+        assert_eq!(status, StatusCode::PRECONDITION_REQUIRED);
+        let uri = "/api/projects/history?dry_run=true";
+        let (status, plan) = send_json(&router, Method::POST, uri, Some(request), false).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            plan,
+            json!({ "projectPath": "/work/demo", "sessionIds": ["old1"] })
+        );
+        let (status, plan) = send_json(
+            &router,
+            Method::POST,
+            uri,
+            Some(json!({ "projectPath": "/work/demo" })),
+            false,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(plan["sessionIds"].as_array().unwrap().len(), 3);
+        assert_eq!(
+            get_json(&router, "/api/sessions")
+                .await
+                .as_array()
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let (status, body) =
+            history(json!({ "projectPath": "/work/demo", "sessionIds": ["old1"] })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.as_array().unwrap().len(), 1);
+        assert_eq!(body[0]["sessionId"], "old1");
+        assert_eq!(body[0]["historical"], true);
+        assert_eq!(body[0]["state"], "stopped");
+        assert_eq!(body[0]["initialTask"], "first try");
+        assert_eq!(body[0]["lastActivityAt"].as_str().unwrap()[..10], old[..10]);
+
+        // Without ids, the project's sessions, the live one as it was.
+        let (status, body) = history(json!({ "projectPath": "/work/demo" })).await;
+        assert_eq!(status, StatusCode::OK);
+        let mut sessions: Vec<(&str, bool)> = body
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| (s["sessionId"].as_str().unwrap(), s["historical"] == true))
+            .collect();
+        sessions.sort();
+        assert_eq!(sessions, [("old1", true), ("old2", true), ("s1", false)]);
+        let live = get_json(&router, "/api/sessions/s1").await;
+        assert_ne!(live["state"], "stopped");
+
+        let detail = get_json(&router, "/api/sessions/old2").await;
+        assert_eq!(detail["messages"][0]["content"], "second try");
+        assert!(detail.get("watcher").is_none());
+        let stats = get_json(&router, "/api/stats").await;
+        assert_eq!(stats["totalSessions"], 1);
+
+        let (status, _) = history(json!({ "projectPath": "/work/missing" })).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        manager.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_oversized_line_is_skipped() {
+        let fixtures = FixtureTree::new();
+        fixtures.append(
+            "/work/demo",
+            "s1",
+            &[user("u1", "read the dump"), assistant("a0", "Reading it.")],
+        );
+        let (manager, router) = start_app(&fixtures).await;
+        events_until(&manager, |e| {
+            matches!(e, ProviderEvent::SessionDiscovered { .. })
+        })
+        .await;
+
+        let huge = assistant("a1", &"QUJD".repeat(50_000));
+        fixtures.append(
+            "/work/demo",
+            "s1",
+            &[huge, assistant("a2", "Too big to read.")],
+        );
+        events_until(&manager, |e| {
+            matches!(e, ProviderEvent::NewMessage { message, .. } if &*message.content == "Too big to read.")
+        })
+        .await;
+
+        let detail = get_json(&router, "/api/sessions/s1").await;
+        let contents: Vec<&str> = detail["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["content"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            contents,
+            [
+                "read the dump",
+                "Reading it.",
+                "Skipped a 0.2 MB log line (the limit is 0.1 MB)",
+                "Too big to read."
+            ]
+        );
+        assert_eq!(detail["messages"][2]["type"], "error");
+        manager.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_startup_summary() {
+        let fixtures = FixtureTree::new();
+        fixtures.append(
+            "/work/demo",
+            "s1",
+            &[
+                user("u1", "fix the build"),
+                json!("not an entry"),
+                turn_done(),
+            ],
+        );
+        fixtures.append("/work/api", "s2", &[user("u2", "add a route")]);
+        let manager = new_manager(&fixtures);
+        let mut startup = manager.subscribe_startup();
+        let addr = serve_app(manager.clone()).await;
+
+        tokio::time::timeout(EVENT_TIMEOUT, startup.changed())
+            .await
+            .expect("startup never completed")
+            .unwrap();
+        let summary = reqwest::get(format!("http://{}/api/startup", addr))
+            .await
+            .unwrap()
+            .json::<Value>()
+            .await
+            .unwrap();
+        assert_eq!(summary["sessionsDiscovered"], 2);
+        assert_eq!(summary["entriesProcessed"], 3);
+        assert_eq!(summary["parseErrors"], 1);
+        assert_eq!(summary["timedOut"], false);
+        manager.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_project_digest() {
+        let fixtures = FixtureTree::new();
+        let edit = json!({
+            "type": "assistant",
+            "uuid": "a1",
+            "timestamp": now(),
+            "message": {
+                "model": "claude-sonnet-4-20250514",
+                "content": [{
+                    "type": "tool_use",
+                    "id": "t1",
+                    "name": "Edit",
+                    "input": {"file_path": "/work/demo/src/lib.rs"}
+                }],
+                "usage": {"input_tokens": 100, "output_tokens": 20}
+            }
+        });
+        fixtures.append("/work/demo", "s1", &[user("u1", "fix the build"), edit]);
+        let (manager, router) = start_app(&fixtures).await;
+        events_until(&manager, |e| {
+            matches!(e, ProviderEvent::SessionDiscovered { .. })
+        })
+        .await;
+
+        let digest = get_json(&router, "/api/projects/%2Fwork%2Fdemo/digest").await;
+        assert_eq!(digest["projectPath"], "/work/demo");
+        assert_eq!(digest["date"], manager.today().to_string());
+        assert_eq!(digest["sessionsStarted"], 1);
+        assert_eq!(digest["totalTokens"], 120);
+        assert_eq!(digest["files"], json!(["/work/demo/src/lib.rs"]));
+        assert_eq!(digest["sessions"][0]["sessionId"], "s1");
+        assert_eq!(digest["sessions"][0]["state"], "running");
+
+        let quiet = get_json(
+            &router,
+            "/api/projects/%2Fwork%2Fdemo/digest?date=2000-01-01",
+        )
+        .await;
+        assert_eq!(quiet["sessions"], json!([]));
+        assert_eq!(quiet["totalCost"], 0.0);
+
+        let request = Request::get("/api/projects/%2Fwork%2Fdemo/digest?format=markdown")
+            .body(Body::empty())
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()["content-type"],
+            "text/markdown; charset=utf-8"
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("- `/work/demo/src/lib.rs`"));
+
+        for (uri, status) in [
+            (
+                "/api/projects/%2Fwork%2Fmissing/digest",
+                StatusCode::NOT_FOUND,
+            ),
+            ("/api/projects/demo/digest", StatusCode::BAD_REQUEST),
+            (
+                "/api/projects/%2Fwork%2Fdemo/digest?date=yesterday",
+                StatusCode::BAD_REQUEST,
+            ),
+        ] {
+            let request = Request::get(uri).body(Body::empty()).unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), status, "GET {}", uri);
+        }
+
+        manager.stop().await;
+    }
+
+    /// Serve a stand-in for the Messages API that records each request and replies
+    /// with a fixed summary, or an error for prompts asking it to fail.
+    async fn fake_messages_api() -> (SocketAddr, Arc<std::sync::Mutex<Vec<Value>>>) {
+        let requests = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        let router = Router::new().route(
+            "/v1/messages",
+            axum::routing::post(
+                move |headers: axum::http::HeaderMap, axum::Json(body): axum::Json<Value>| {
+                    let recorded = recorded.clone();
+                    async move {
+                        let prompt = body["messages"][0]["content"].as_str().unwrap().to_string();
+                        recorded.lock().unwrap().push(json!({
+                            "apiKey": headers["x-api-key"].to_str().unwrap(),
+                            "model": body["model"],
+                            "prompt": prompt,
+                        }));
+                        if prompt.contains("please fail") {
+                            let error = json!({
+                                "type": "error",
+                                "error": {"type": "invalid_request_error", "message": "bad model"}
+                            });
+                            return (StatusCode::BAD_REQUEST, axum::Json(error));
+                        }
+                        let reply = json!({
+                            "content": [{"type": "text", "text": "Fixed the build. "}],
+                            "usage": {"input_tokens": 500, "output_tokens": 40}
+                        });
+                        (StatusCode::OK, axum::Json(reply))
+                    }
+                },
+            ),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        (addr, requests)
+    }
+
+    #[tokio::test]
+    async fn test_summarize_session() {
+        use crate::session::summarizer::{Summarizer, SummaryConfig, DEFAULT_MODEL};
+
+        let fixtures = FixtureTree::new();
+        fixtures.append(
+            "/work/demo",
+            "s1",
+            &[
+                user("u1", "fix the build"),
+                assistant("a1", "On it."),
+                turn_done(),
+            ],
+        );
+        fixtures.append(
+            "/work/other",
+            "s2",
+            &[
+                user("u1", "please fail"),
+                assistant("a1", "Sure."),
+                turn_done(),
+            ],
+        );
+        let (manager, router) = start_app(&fixtures).await;
+        let found = std::cell::Cell::new(0);
+        events_until(&manager, |e| {
+            found.set(found.get() + matches!(e, ProviderEvent::SessionDiscovered { .. }) as usize);
+            found.get() == 2
+        })
+        .await;
+        let summarize = |session_id: &str| {
+            let request = Request::post(format!("/api/sessions/{}/summarize", session_id))
+                .header(CONFIRM_HEADER, "yes")
+                .body(Body::empty())
+                .unwrap();
+            let router = router.clone();
+            async move {
+                let response = router.oneshot(request).await.unwrap();
+                let status = response.status();
+                let retry_after = response.headers().get("retry-after").cloned();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                let body: Value = serde_json::from_slice(&body).unwrap();
+                (status, body, retry_after)
+            }
+        };
+
+        // Nothing happens without a key.
+        let (status, body, _) = summarize("s1").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(body["error"].as_str().unwrap().contains("SUMMARY_API_KEY"));
+
+        let (addr, requests) = fake_messages_api().await;
+        manager.set_summarizer(Some(Summarizer::new(SummaryConfig {
+            api_key: "test-key".to_string(),
+            model: DEFAULT_MODEL.to_string(),
+            api_url: format!("http://{}", addr),
+            on_stop: false,
+        })));
+        // Unconfirmed, nothing is sent; a dry run names the model.
+        let (status, _) =
+            send_json(&router, Method::POST, "/api/sessions/s1/summarize", None, false).await;
+        assert_eq!(status, StatusCode::PRECONDITION_REQUIRED);
+        let uri = "/api/sessions/s1/summarize?dry_run=true";
+        let (status, plan) = send_json(&router, Method::POST, uri, None, false).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            plan,
+            json!({ "sessionId": "s1", "model": DEFAULT_MODEL, "turnCount": 1 })
+        );
+        assert!(requests.lock().unwrap().is_empty());
+
+        let (status, body, _) = summarize("s1").await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["text"], "Fixed the build.");
+        assert!(body["estimatedCost"].as_f64().unwrap() > 0.0);
+
+        // The outline is sent, not the transcript.
+        let sent = requests.lock().unwrap()[0].clone();
+        assert_eq!(sent["apiKey"], "test-key");
+        assert_eq!(sent["model"], DEFAULT_MODEL);
+        let prompt = sent["prompt"].as_str().unwrap();
+        assert!(
+            prompt.contains("1. Prompt: \"fix the build\""),
+            "{}",
+            prompt
+        );
+        assert!(!prompt.contains("On it."));
+
+        let session = get_json(&router, "/api/sessions/s1").await;
+        assert_eq!(session["summary"]["text"], "Fixed the build.");
+        let projects = get_json(&router, "/api/projects").await;
+        let dashboard = projects
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|p| p["projectPath"] == "dashboard")
+            .expect("summary cost counted under the dashboard project");
+        assert_eq!(dashboard["usage"]["inputTokens"], 500);
+
+        let (status, _, retry_after) = summarize("s1").await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert!(retry_after.is_some());
+
+        let (status, body, _) = summarize("s2").await;
+        assert_eq!(status, StatusCode::BAD_GATEWAY);
+        assert!(body["error"].as_str().unwrap().contains("bad model"));
+        let (status, _, _) = summarize("missing").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        manager.stop().await;
+    }
+
+    /// Connect to the dashboard's WebSocket and count how often each session is
+    /// announced, in the initial snapshot or as a discovery, until `expected` have
+    /// been seen and nothing more arrives for a while. `on_init` is told once the
+    /// snapshot is in.
+    async fn count_announced(
+        addr: SocketAddr,
+        expected: usize,
+        mut on_init: Option<oneshot::Sender<()>>,
+    ) -> HashMap<String, usize> {
+        let url = format!("ws://{}/ws", addr);
+        let (mut socket, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        loop {
+            let wait = if counts.len() < expected {
+                EVENT_TIMEOUT
+            } else {
+                Duration::from_millis(300)
+            };
+            let message = match tokio::time::timeout(wait, socket.next()).await {
+                Ok(message) => message.unwrap().unwrap(),
+                Err(_) if counts.len() == expected => return counts,
+                Err(_) => panic!("timed out; saw {:?}", counts),
+            };
+            let Message::Text(text) = message else {
+                continue;
+            };
+            let event: Value = serde_json::from_str(&text).unwrap();
+            let sessions = match event["type"].as_str().unwrap() {
+                "sessions:init" => event["sessions"].as_array().unwrap().clone(),
+                "session:discovered" => vec![event["session"].clone()],
+                _ => continue,
+            };
+            for session in sessions {
+                let id = session["sessionId"].as_str().unwrap().to_string();
+                *counts.entry(id).or_default() += 1;
+            }
+            if let Some(on_init) = on_init.take() {
+                let _ = on_init.send(());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ws_unread_counts_follow_acks() {
+        use futures::SinkExt;
+        type Socket = tokio_tungstenite::WebSocketStream<
+            tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+        >;
+
+        async fn connect(addr: SocketAddr) -> Socket {
+            let url = format!("ws://{}/ws", addr);
+            let (mut socket, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+            let hello = json!({"type": "client:hello", "protocolVersion": 2, "clientId": "laptop"});
+            socket
+                .send(Message::Text(hello.to_string().into()))
+                .await
+                .unwrap();
+            socket
+        }
+
+        async fn next_event(socket: &mut Socket, matches: impl Fn(&Value) -> bool) -> Value {
+            loop {
+                let message = tokio::time::timeout(EVENT_TIMEOUT, socket.next())
+                    .await
+                    .expect("timed out waiting for an event")
+                    .unwrap()
+                    .unwrap();
+                if let Message::Text(text) = message {
+                    let event: Value = serde_json::from_str(&text).unwrap();
+                    if matches(&event) {
+                        return event;
+                    }
+                }
+            }
+        }
+
+        let fixtures = FixtureTree::new();
+        fixtures.append(
+            "/work/demo",
+            "s1",
+            &[user("u1", "fix the build"), assistant("a1", "Looking.")],
+        );
+        let manager = new_manager(&fixtures);
+        let addr = serve_app(manager.clone()).await;
+        let read = tokio::time::timeout(EVENT_TIMEOUT, async {
+            loop {
+                match manager.get_session_messages("s1").await {
+                    Some(messages) if messages.len() == 2 => return messages[1].id.clone(),
+                    _ => tokio::time::sleep(Duration::from_millis(20)).await,
+                }
+            }
+        })
+        .await
+        .expect("session not read");
+
+        let mut socket = connect(addr).await;
+        let ack = json!({"type": "ack:read", "sessionId": "s1", "messageId": read});
+        socket
+            .send(Message::Text(ack.to_string().into()))
+            .await
+            .unwrap();
+        tokio::time::timeout(EVENT_TIMEOUT, async {
+            while manager.read_marks("laptop").await.is_empty() {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("ack not recorded");
+
+        fixtures.append(
+            "/work/demo",
+            "s1",
+            &[assistant("a2", "Fixed."), turn_done()],
+        );
+        let changed = next_event(&mut socket, |e| e["type"] == "session:state_changed").await;
+        assert!(changed["unreadCount"].as_u64().unwrap() >= 1, "{}", changed);
+        drop(socket);
+
+        // A new connection from the same client gets its counts with the snapshot.
+        let messages = manager.get_session_messages("s1").await.unwrap();
+        let since = messages.len() - 1 - messages.iter().position(|m| m.id == read).unwrap();
+        assert!(since >= 2);
+        let mut socket = connect(addr).await;
+        let init = next_event(&mut socket, |e| {
+            e["type"] == "sessions:init" && e.get("unreadCounts").is_some()
+        })
+        .await;
+        assert_eq!(init["unreadCounts"]["s1"], since);
+
+        // Other clients have read nothing.
+        assert!(manager.read_marks("phone").await.is_empty());
+        manager.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_ws_rejects_unknown_events() {
+        use futures::SinkExt;
+
+        let fixtures = FixtureTree::new();
+        let manager = new_manager(&fixtures);
+        let addr = serve_app(manager.clone()).await;
+        let url = format!("ws://{}/ws", addr);
+        let (mut socket, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+
+        let frames = [
+            (
+                json!({"type": "client:shout"}).to_string(),
+                json!("client:shout"),
+            ),
+            (
+                json!({"type": "subscribe:session"}).to_string(),
+                json!("subscribe:session"),
+            ),
+            ("not json".to_string(), Value::Null),
+        ];
+        for (frame, event_type) in frames {
+            socket.send(Message::Text(frame.into())).await.unwrap();
+            let error = loop {
+                let message = tokio::time::timeout(EVENT_TIMEOUT, socket.next())
+                    .await
+                    .expect("timed out waiting for an error")
+                    .unwrap()
+                    .unwrap();
+                if let Message::Text(text) = message {
+                    let event: Value = serde_json::from_str(&text).unwrap();
+                    if event["type"] == "server:error" {
+                        break event;
+                    }
+                }
+            };
+            assert_eq!(error["eventType"], event_type, "{}", error);
+            assert!(!error["message"].as_str().unwrap().is_empty());
+        }
+        manager.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_ws_client_lagging_on_messages_gets_them_again() {
+        use futures::SinkExt;
+
+        let fixtures = FixtureTree::new();
+        fixtures.append(
+            "/work/demo",
+            "s1",
+            &[user("u1", "fix the build"), assistant("a1", "Looking.")],
+        );
+        let manager = new_manager(&fixtures);
+        let (broadcast_tx, _) = broadcast::channel(256);
+        let (message_tx, _) = broadcast::channel(2);
+        let addr = serve_app_with_channels(manager.clone(), broadcast_tx, message_tx.clone()).await;
+        tokio::time::timeout(EVENT_TIMEOUT, async {
+            while manager
+                .get_session_messages("s1")
+                .await
+                .map_or(0, |m| m.len())
+                < 2
+            {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("session not read");
+
+        let url = format!("ws://{}/ws", addr);
+        let (mut socket, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        let subscribe = json!({"type": "subscribe:session", "sessionId": "s1"});
+        socket
+            .send(Message::Text(subscribe.to_string().into()))
+            .await
+            .unwrap();
+        async fn messages_init(
+            socket: &mut tokio_tungstenite::WebSocketStream<
+                tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+            >,
+        ) -> Value {
+            loop {
+                let message = tokio::time::timeout(EVENT_TIMEOUT, socket.next())
+                    .await
+                    .expect("timed out waiting for messages")
+                    .unwrap()
+                    .unwrap();
+                if let Message::Text(text) = message {
+                    let event: Value = serde_json::from_str(&text).unwrap();
+                    if event["type"] == "session:messages_init" {
+                        return event;
+                    }
+                }
+            }
+        }
+        let init = messages_init(&mut socket).await;
+        assert_eq!(init["messages"].as_array().unwrap().len(), 2);
+
+        // Far more than the channel holds, sent before the connection can read any.
+        let messages = manager.get_session_messages("s1").await.unwrap();
+        for _ in 0..10 {
+            let _ = message_tx.send(ServerEvent::NewMessage {
+                session_id: "s1".to_string(),
+                message: (*messages[1]).clone(),
+            });
+        }
+        let refresh = messages_init(&mut socket).await;
+        assert_eq!(refresh["sessionId"], "s1");
+        assert_eq!(refresh["messages"].as_array().unwrap().len(), 2);
+
+        let get = |uri: &str| {
+            let url = format!("http://{}{}", addr, uri);
+            async move {
+                reqwest::get(url)
+                    .await
+                    .unwrap()
+                    .json::<Value>()
+                    .await
+                    .unwrap()
+            }
+        };
+        let connections = get("/api/connections").await;
+        assert_eq!(connections[0]["lags"], 1);
+        assert_eq!(connections[0]["eventsMissed"], 8);
+        let health = get("/api/health").await;
+        assert_eq!(health["connectionLags"], 1);
+        manager.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_ws_project_subscription_follows_its_sessions() {
+        use futures::SinkExt;
+        type Socket = tokio_tungstenite::WebSocketStream<
+            tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+        >;
+
+        /// Sessions of the message events received within `window`.
+        async fn message_sessions(socket: &mut Socket, window: Duration) -> Vec<String> {
+            let mut sessions = Vec::new();
+            let deadline = tokio::time::Instant::now() + window;
+            while let Ok(Some(Ok(message))) = tokio::time::timeout_at(deadline, socket.next()).await
+            {
+                if let Message::Text(text) = message {
+                    let event: Value = serde_json::from_str(&text).unwrap();
+                    let kind = event["type"].as_str().unwrap_or_default();
+                    if kind == "session:messages_init" || kind == "session:new_message" {
+                        sessions.push(event["sessionId"].as_str().unwrap().to_string());
+                    }
+                }
+            }
+            sessions
+        }
+
+        async fn send(socket: &mut Socket, event: Value) {
+            socket
+                .send(Message::Text(event.to_string().into()))
+                .await
+                .unwrap();
+        }
+
+        async fn wait_for_messages(manager: &SessionManager, session_id: &str, count: usize) {
+            tokio::time::timeout(EVENT_TIMEOUT, async {
+                while manager
+                    .get_session_messages(session_id)
+                    .await
+                    .map_or(0, |m| m.len())
+                    < count
+                {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+            })
+            .await
+            .expect("session not read");
+        }
+
+        let fixtures = FixtureTree::new();
+        fixtures.append("/work/app", "s1", &[assistant("a1", "One.")]);
+        fixtures.append("/work/app", "s2", &[assistant("a2", "Two.")]);
+        fixtures.append("/work/other", "s3", &[assistant("a3", "Three.")]);
+        let manager = new_manager(&fixtures);
+        let addr = serve_app(manager.clone()).await;
+        for session_id in ["s1", "s2", "s3"] {
+            wait_for_messages(&manager, session_id, 1).await;
+        }
+
+        let url = format!("ws://{}/ws", addr);
+        let (mut socket, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        send(
+            &mut socket,
+            json!({"type": "subscribe:project", "projectPath": "/work/app"}),
+        )
+        .await;
+        let mut initial = message_sessions(&mut socket, Duration::from_millis(500)).await;
+        initial.sort();
+        assert_eq!(initial, ["s1", "s2"]);
+
+        // A session started in the project later is followed too; others are not.
+        fixtures.append("/work/app", "s4", &[assistant("a4", "Four.")]);
+        fixtures.append("/work/other", "s3", &[assistant("a5", "Five.")]);
+        wait_for_messages(&manager, "s4", 1).await;
+        wait_for_messages(&manager, "s3", 2).await;
+        let later = message_sessions(&mut socket, Duration::from_millis(500)).await;
+        assert!(later.iter().any(|s| s == "s4"), "{:?}", later);
+        assert!(!later.iter().any(|s| s == "s3"), "{:?}", later);
+
+        let connections: Value = reqwest::get(format!("http://{}/api/connections", addr))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(connections[0]["projectSubscriptions"], json!(["/work/app"]));
+
+        send(
+            &mut socket,
+            json!({"type": "unsubscribe:project", "projectPath": "/work/app"}),
+        )
+        .await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        fixtures.append("/work/app", "s1", &[assistant("a6", "Six.")]);
+        wait_for_messages(&manager, "s1", 2).await;
+        let after = message_sessions(&mut socket, Duration::from_millis(300)).await;
+        assert!(after.is_empty(), "{:?}", after);
+        manager.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_public_status_shows_no_content() {
+        let fixtures = FixtureTree::new();
+        fixtures.append(
+            "/work/demo",
+            "s1",
+            &[user("u1", "fix the build"), assistant("a1", "Looking.")],
+        );
+
+        // Off by default.
+        let manager = new_manager(&fixtures);
+        let addr = serve_app(manager.clone()).await;
+        let url = format!("http://{}/status/api/sessions", addr);
+        assert_eq!(reqwest::get(url).await.unwrap().status(), 404);
+        manager.stop().await;
+
+        let mut config = Config::default();
+        config.server.public_status = true;
+        let manager = new_manager(&fixtures);
+        let addr = serve_app_with_config(manager.clone(), config).await;
+        tokio::time::timeout(EVENT_TIMEOUT, async {
+            while manager.get_sessions(false).await.is_empty() {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("session not discovered");
+
+        let url = format!("http://{}/status/api/sessions", addr);
+        let text = reqwest::get(url).await.unwrap().text().await.unwrap();
+        let sessions: Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(sessions[0]["sessionId"], "s1");
+        assert_eq!(sessions[0]["projectName"], "demo");
+        assert!(
+            !text.contains("fix the build") && !text.contains("/work"),
+            "{}",
+            text
+        );
+
+        let url = format!("http://{}/status/api/events", addr);
+        let mut events = reqwest::get(url).await.unwrap();
+        assert_eq!(
+            events.headers()[reqwest::header::CONTENT_TYPE],
+            "text/event-stream"
+        );
+        // Reads until the stream has sent `wanted`, returning everything so far.
+        async fn read_until(events: &mut reqwest::Response, seen: &mut String, wanted: &str) {
+            while !seen.contains(wanted) {
+                let chunk = tokio::time::timeout(EVENT_TIMEOUT, events.chunk())
+                    .await
+                    .unwrap_or_else(|_| panic!("no {:?} in {:?}", wanted, seen))
+                    .unwrap()
+                    .expect("stream ended");
+                seen.push_str(&String::from_utf8_lossy(&chunk));
+            }
+        }
+        let mut seen = String::new();
+        read_until(&mut events, &mut seen, "event: sessions:init").await;
+
+        fixtures.append(
+            "/work/demo",
+            "s1",
+            &[assistant("a2", "Fixed the flaky build."), turn_done()],
+        );
+        read_until(&mut events, &mut seen, "event: session:updated").await;
+        read_until(&mut events, &mut seen, r#""state":"idle""#).await;
+        assert!(
+            !seen.contains("flaky") && !seen.contains("/work"),
+            "{}",
+            seen
+        );
+        manager.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_ws_clients_see_each_session_once() {
+        let fixtures = Arc::new(FixtureTree::new());
+        let write_session = |fixtures: &FixtureTree, i: usize| {
+            let project = format!("/work/p{}", i);
+            fixtures.append(&project, &format!("s{}", i), &[assistant("a1", "Hi.")]);
+        };
+        for i in 0..20 {
+            write_session(&fixtures, i);
+        }
+        let manager = new_manager(&fixtures);
+
+        // Hold the routing back until the first client has its snapshot, so the
+        // discoveries it is then sent are of sessions it already has.
+        let held = manager.event_rx.lock().await;
+        let addr = serve_app(manager.clone()).await;
+        tokio::time::timeout(EVENT_TIMEOUT, async {
+            while manager.get_sessions(false).await.len() < 20 {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("sessions not discovered");
+        let (init_tx, init_rx) = oneshot::channel();
+        let mut clients = vec![tokio::spawn(count_announced(addr, 30, Some(init_tx)))];
+        init_rx.await.unwrap();
+        drop(held);
+
+        // More clients connect while the queued discoveries go out and more
+        // sessions turn up.
+        let writer = {
+            let fixtures = fixtures.clone();
+            tokio::spawn(async move {
+                for i in 20..30 {
+                    write_session(&fixtures, i);
+                    tokio::time::sleep(Duration::from_millis(15)).await;
+                }
+            })
+        };
+        for delay in [0, 10, 40, 90, 160] {
+            clients.push(tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                count_announced(addr, 30, None).await
+            }));
+        }
+        writer.await.unwrap();
+
+        for client in clients {
+            let counts = client.await.unwrap();
+            assert_eq!(counts.len(), 30);
+            let repeated: Vec<_> = counts.iter().filter(|(_, n)| **n != 1).collect();
+            assert!(
+                repeated.is_empty(),
+                "announced more than once: {:?}",
+                repeated
+            );
+        }
+        manager.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_stopped_sessions_are_archived_and_served() {
+        use crate::session::archive::{ArchiveConfig, ArchiveStore};
+
+        let fixtures = FixtureTree::new();
+        fixtures.append(
+            "/work/demo",
+            "s1",
+            &[
+                user("u1", "fix the build"),
+                assistant("a1", "Fixed."),
+                turn_done(),
+                user("u2", "<command-name>/exit</command-name>"),
+            ],
+        );
+        let archive_dir =
+            std::env::temp_dir().join(format!("agents-dashboard-archive-{}", uuid::Uuid::new_v4()));
+        let (manager, router) = start_app_with_config(&fixtures, Config::default()).await;
+        let request = Request::get("/api/archive").body(Body::empty()).unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        manager.set_archive(Some(ArchiveStore::load(ArchiveConfig {
+            dir: archive_dir.clone(),
+            after: Duration::ZERO,
+        })));
+        events_until(&manager, |e| {
+            matches!(
+                e,
+                ProviderEvent::StateChanged {
+                    current: crate::types::AgentStateType::Stopped,
+                    ..
+                }
+            )
+        })
+        .await;
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert_eq!(manager.archive_stopped().await, 1);
+        assert!(manager.get_session_detail("s1").await.is_none());
+
+        let listed = get_json(&router, "/api/archive?project=/work/demo").await;
+        assert_eq!(listed[0]["sessionId"], "s1");
+        assert_eq!(listed[0]["initialTask"], "fix the build");
+        let today = chrono::Utc::now().date_naive();
+        let tomorrow = today.succ_opt().unwrap();
+        let listed = get_json(&router, &format!("/api/archive?from={}", tomorrow)).await;
+        assert_eq!(listed, json!([]));
+        let listed = get_json(&router, "/api/archive?project=/work/other").await;
+        assert_eq!(listed, json!([]));
+
+        let archived = get_json(&router, "/api/archive/s1").await;
+        assert_eq!(archived["detail"]["sessionId"], "s1");
+        let messages = archived["detail"]["messages"].as_array().unwrap();
+        assert!(messages.iter().any(|m| m["content"] == "fix the build"));
+        assert_eq!(archived["usage"]["sessionId"], "s1");
+
+        for uri in [
+            "/api/archive/nope",
+            "/api/archive?from=2025-02-01&to=2025-01-01",
+        ] {
+            let request = Request::get(uri).body(Body::empty()).unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert!(response.status().is_client_error(), "{}", uri);
+        }
+
+        manager.stop().await;
+        let _ = std::fs::remove_dir_all(&archive_dir);
+    }
+
+    #[tokio::test]
+    async fn test_replay_paths_stay_in_the_projects_dir() {
+        let fixtures = FixtureTree::new();
+        fixtures.append("/work/demo", "s1", &[user("u1", "hello"), assistant("a1", "Hi.")]);
+        let (manager, router) = start_app(&fixtures).await;
+        let replay = |path: &std::path::Path| {
+            let body = json!({ "path": path.to_str().unwrap(), "speed": 1000 });
+            send_json(&router, Method::POST, "/api/replay", Some(body), true)
+        };
+
+        let log = fixtures.log_path("/work/demo", "s1");
+        let (status, created) = replay(&log).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", created);
+
+        // A log outside it, reached directly, through `..` or through a link.
+        let outside = FixtureTree::new();
+        outside.append("/work/other", "s2", &[user("u2", "secret")]);
+        let outside_log = outside.log_path("/work/other", "s2");
+        let (status, _) = replay(&outside_log).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let dotted = log
+            .parent()
+            .unwrap()
+            .join("../..")
+            .join(outside_log.strip_prefix(std::env::temp_dir()).unwrap());
+        let (status, _) = replay(&dotted).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let link = log.with_file_name("link.jsonl");
+        std::os::unix::fs::symlink(&outside_log, &link).unwrap();
+        let (status, _) = replay(&link).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        manager.stop().await;
+    }
+}
@@ -38,9 +38,11 @@ export const mockSessions: AgentSessionSummary[] = [
     workingDirectory: "/Users/dev/projects/web-app",
     model: "claude-sonnet-4-20250514",
     state: "running",
-    currentTask: "Add user authentication with JWT tokens and role-based access control",
+    initialTask: "Add user authentication with JWT tokens and role-based access control",
+    latestTask: "Add user authentication with JWT tokens and role-based access control",
     startedAt: ts(32 * min),
     lastActivityAt: ts(10_000),
+    pinned: false,
     cumulativeUsage: {
       inputTokens: 184_320,
       outputTokens: 12_800,
@@ -48,7 +50,19 @@ export const mockSessions: AgentSessionSummary[] = [
       cacheCreationTokens: 45_000,
       estimatedCost: 0.42,
     },
-    gitStatus: { branch: "feat/auth", additions: 247, deletions: 38 },
+    gitStatus: {
+      branch: "feat/auth",
+      additions: 247,
+      deletions: 38,
+      stagedAdditions: 0,
+      stagedDeletions: 0,
+      untracked: 0,
+      dirtySubmodules: 0,
+      ahead: 0,
+      behind: 0,
+      files: [],
+      isWorktree: false,
+    },
   },
   {
     sessionId: "a1b2c3d4-2222-4000-8000-000000000002",
@@ -58,9 +72,11 @@ export const mockSessions: AgentSessionSummary[] = [
     workingDirectory: "/Users/dev/projects/api-server",
     model: "claude-sonnet-4-20250514",
     state: "idle",
-    currentTask: "Refactor database connection pooling for better performance",
+    initialTask: "Refactor database connection pooling for better performance",
+    latestTask: "Refactor database connection pooling for better performance",
     startedAt: ts(85 * min),
     lastActivityAt: ts(4 * min),
+    pinned: false,
     cumulativeUsage: {
       inputTokens: 312_000,
       outputTokens: 28_400,
@@ -68,7 +84,19 @@ export const mockSessions: AgentSessionSummary[] = [
       cacheCreationTokens: 78_000,
       estimatedCost: 0.87,
     },
-    gitStatus: { branch: "refactor/db-pool", additions: 189, deletions: 142 },
+    gitStatus: {
+      branch: "refactor/db-pool",
+      additions: 189,
+      deletions: 142,
+      stagedAdditions: 0,
+      stagedDeletions: 0,
+      untracked: 0,
+      dirtySubmodules: 0,
+      ahead: 0,
+      behind: 0,
+      files: [],
+      isWorktree: false,
+    },
   },
   {
     sessionId: "a1b2c3d4-3333-4000-8000-000000000003",
@@ -78,9 +106,11 @@ export const mockSessions: AgentSessionSummary[] = [
     workingDirectory: "/Users/dev/projects/mobile-app",
     model: "claude-opus-4-20250514",
     state: "permission_waiting",
-    currentTask: "Fix image upload handling and add compression",
+    initialTask: "Fix image upload handling and add compression",
+    latestTask: "Fix image upload handling and add compression",
     startedAt: ts(18 * min),
     lastActivityAt: ts(45_000),
+    pinned: false,
     cumulativeUsage: {
       inputTokens: 95_000,
       outputTokens: 8_200,
@@ -88,7 +118,19 @@ export const mockSessions: AgentSessionSummary[] = [
       cacheCreationTokens: 24_000,
       estimatedCost: 0.68,
     },
-    gitStatus: { branch: "fix/image-upload", additions: 63, deletions: 12 },
+    gitStatus: {
+      branch: "fix/image-upload",
+      additions: 63,
+      deletions: 12,
+      stagedAdditions: 0,
+      stagedDeletions: 0,
+      untracked: 0,
+      dirtySubmodules: 0,
+      ahead: 0,
+      behind: 0,
+      files: [],
+      isWorktree: false,
+    },
   },
   {
     sessionId: "a1b2c3d4-4444-4000-8000-000000000004",
@@ -98,9 +140,11 @@ export const mockSessions: AgentSessionSummary[] = [
     workingDirectory: "/Users/dev/projects/data-pipeline",
     model: "claude-sonnet-4-20250514",
     state: "running",
-    currentTask: "Implement real-time data streaming with WebSocket support",
+    initialTask: "Implement real-time data streaming with WebSocket support",
+    latestTask: "Implement real-time data streaming with WebSocket support",
     startedAt: ts(12 * min),
     lastActivityAt: ts(5_000),
+    pinned: false,
     cumulativeUsage: {
       inputTokens: 67_000,
       outputTokens: 5_100,
@@ -108,7 +152,19 @@ export const mockSessions: AgentSessionSummary[] = [
       cacheCreationTokens: 17_000,
       estimatedCost: 0.19,
     },
-    gitStatus: { branch: "feat/streaming", additions: 312, deletions: 45 },
+    gitStatus: {
+      branch: "feat/streaming",
+      additions: 312,
+      deletions: 45,
+      stagedAdditions: 0,
+      stagedDeletions: 0,
+      untracked: 0,
+      dirtySubmodules: 0,
+      ahead: 0,
+      behind: 0,
+      files: [],
+      isWorktree: false,
+    },
   },
   {
     sessionId: "a1b2c3d4-5555-4000-8000-000000000005",
@@ -118,9 +174,11 @@ export const mockSessions: AgentSessionSummary[] = [
     workingDirectory: "/Users/dev/projects/cli-tool",
     model: "claude-haiku-4-5-20251001",
     state: "stopped",
-    currentTask: "Add config file parsing and validation",
+    initialTask: "Add config file parsing and validation",
+    latestTask: "Add config file parsing and validation",
     startedAt: ts(120 * min),
     lastActivityAt: ts(65 * min),
+    pinned: false,
     cumulativeUsage: {
       inputTokens: 420_000,
       outputTokens: 38_000,
@@ -128,7 +186,19 @@ export const mockSessions: AgentSessionSummary[] = [
       cacheCreationTokens: 105_000,
       estimatedCost: 0.15,
     },
-    gitStatus: { branch: "feat/config", additions: 534, deletions: 89 },
+    gitStatus: {
+      branch: "feat/config",
+      additions: 534,
+      deletions: 89,
+      stagedAdditions: 0,
+      stagedDeletions: 0,
+      untracked: 0,
+      dirtySubmodules: 0,
+      ahead: 0,
+      behind: 0,
+      files: [],
+      isWorktree: false,
+    },
   },
   {
     sessionId: "a1b2c3d4-6666-4000-8000-000000000006",
@@ -138,9 +208,11 @@ export const mockSessions: AgentSessionSummary[] = [
     workingDirectory: "/Users/dev/projects/e-commerce",
     model: "claude-sonnet-4-20250514",
     state: "error",
-    currentTask: "Debug checkout payment processing flow",
+    initialTask: "Debug checkout payment processing flow",
+    latestTask: "Debug checkout payment processing flow",
     startedAt: ts(45 * min),
     lastActivityAt: ts(8 * min),
+    pinned: false,
     cumulativeUsage: {
       inputTokens: 245_000,
       outputTokens: 19_600,
@@ -148,7 +220,19 @@ export const mockSessions: AgentSessionSummary[] = [
       cacheCreationTokens: 61_000,
       estimatedCost: 0.58,
     },
-    gitStatus: { branch: "fix/checkout", additions: 78, deletions: 23 },
+    gitStatus: {
+      branch: "fix/checkout",
+      additions: 78,
+      deletions: 23,
+      stagedAdditions: 0,
+      stagedDeletions: 0,
+      untracked: 0,
+      dirtySubmodules: 0,
+      ahead: 0,
+      behind: 0,
+      files: [],
+      isWorktree: false,
+    },
   },
 ];
 
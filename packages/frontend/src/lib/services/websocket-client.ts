@@ -2,6 +2,31 @@ import type { ClientEvent, ServerEvent } from "@agents-dashboard/shared";
 
 export type ServerEventHandler = (event: ServerEvent) => void;
 
+/** WebSocket protocol version this frontend was written against. */
+const PROTOCOL_VERSION = 2;
+
+/** Event types and optional features this frontend understands. */
+const CAPABILITIES = [
+  "server:hello",
+  "sessions:init",
+  "session:discovered",
+  "session:removed",
+  "session:updated",
+  "session:state_changed",
+  "session:new_message",
+  "session:messages_appended",
+  "session:messages_init",
+  "session:usage_updated",
+  "session:git_status_updated",
+  "session:context_warning",
+  "session:model_changed",
+  "session:log_deleted",
+  "project:budget_exceeded",
+  "server:startup_complete",
+  "server:error",
+  "thinking",
+];
+
 export class WebSocketClient {
   private ws: WebSocket | null = null;
   private handlers = new Set<ServerEventHandler>();
@@ -22,6 +47,11 @@ export class WebSocketClient {
     this.ws.onopen = () => {
       this._connected = true;
       console.log("[WS] Connected");
+      this.send({
+        type: "client:hello",
+        protocolVersion: PROTOCOL_VERSION,
+        capabilities: CAPABILITIES,
+      });
     };
 
     this.ws.onclose = () => {
@@ -38,6 +68,9 @@ export class WebSocketClient {
     this.ws.onmessage = (event) => {
       try {
         const data = JSON.parse(event.data) as ServerEvent;
+        if (data.type === "server:error") {
+          console.warn("[WS] Server rejected", data.eventType ?? "frame", data.message);
+        }
         for (const handler of this.handlers) {
           handler(data);
         }
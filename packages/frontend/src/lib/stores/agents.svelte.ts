@@ -1,7 +1,7 @@
 import type { AgentMessage, AgentSessionSummary, AgentStateType, CumulativeUsage, SearchResponse, SearchScope, SessionSearchResult, ServerEvent } from "@agents-dashboard/shared";
 import { API_BASE } from "../config.js";
 
-const ALL_SCOPES: SearchScope[] = ["project_name", "current_task", "working_directory", "content"];
+const ALL_SCOPES: SearchScope[] = ["project_name", "title", "current_task", "working_directory", "content", "note", "labels"];
 
 export type RecencyFilter = "1h" | "6h" | "24h" | "7d" | "30d" | "all";
 
@@ -28,6 +28,7 @@ class AgentsStore {
   // Search state
   searchQuery = $state("");
   searchScopes = $state<SearchScope[]>([...ALL_SCOPES]);
+  caseSensitive = $state(false);
   searchResults = $state<SearchResponse | null>(null);
   isSearching = $state(false);
 
@@ -48,17 +49,36 @@ class AgentsStore {
         this.sessions = [...this.sessions, event.session];
         break;
 
+      case "session:updated":
+        this.sessions = this.sessions.map((s) =>
+          s.sessionId === event.session.sessionId ? { ...event.session } : s,
+        );
+        break;
+
       case "session:removed":
         this.sessions = this.sessions.filter((s) => s.sessionId !== event.sessionId);
         delete this.sessionMessages[event.sessionId];
         break;
 
       case "session:state_changed":
+      case "session:context_warning":
         this.sessions = this.sessions.map((s) =>
           s.sessionId === event.sessionId ? { ...event.session } : s,
         );
         break;
 
+      case "session:model_changed":
+        this.sessions = this.sessions.map((s) =>
+          s.sessionId === event.sessionId ? { ...s, model: event.current } : s,
+        );
+        break;
+
+      case "session:log_deleted":
+        this.sessions = this.sessions.map((s) =>
+          s.sessionId === event.sessionId ? { ...s, stopReason: "log_deleted" } : s,
+        );
+        break;
+
       case "session:messages_init": {
         // Replace existing messages with the full backlog from the server
         this.sessionMessages[event.sessionId] = event.messages.slice(-200);
@@ -67,6 +87,8 @@ class AgentsStore {
 
       case "session:new_message": {
         const msgs = this.sessionMessages[event.sessionId] ?? [];
+        // May already be in a messages_init sent after a messages_appended
+        if (msgs.some((m) => m.id === event.message.id)) break;
         const updated = [...msgs, event.message];
         this.sessionMessages[event.sessionId] = updated.length > 200 ? updated.slice(-150) : updated;
         break;
@@ -74,7 +96,16 @@ class AgentsStore {
 
       case "session:usage_updated":
         this.sessions = this.sessions.map((s) =>
-          s.sessionId === event.sessionId ? { ...s, cumulativeUsage: event.usage } : s,
+          s.sessionId === event.sessionId
+            ? {
+                ...s,
+                cumulativeUsage: event.usage,
+                spendRatePerHour: event.spendRatePerHour,
+                tokensPerMinute: event.tokensPerMinute,
+                contextTokens: event.contextTokens,
+                contextLimit: event.contextLimit,
+              }
+            : s,
         );
         break;
 
@@ -121,7 +152,8 @@ class AgentsStore {
     try {
       const scopeParam = this.searchScopes.join(",");
       const res = await fetch(
-        `${API_BASE}/api/search?q=${encodeURIComponent(query)}&scope=${scopeParam}`,
+        `${API_BASE}/api/search?q=${encodeURIComponent(query)}&scope=${scopeParam}` +
+          (this.caseSensitive ? "&case_sensitive=true" : ""),
       );
       if (!res.ok) return;
       const data: SearchResponse = await res.json();
@@ -148,6 +180,13 @@ class AgentsStore {
     return this.searchResults?.results.find((r) => r.session.sessionId === sessionId);
   }
 
+  toggleCaseSensitive(): void {
+    this.caseSensitive = !this.caseSensitive;
+    if (this.searchQuery.trim()) {
+      this.search(this.searchQuery);
+    }
+  }
+
   toggleScope(scope: SearchScope): void {
     const idx = this.searchScopes.indexOf(scope);
     if (idx >= 0) {
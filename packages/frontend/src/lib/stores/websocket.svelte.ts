@@ -17,6 +17,11 @@ class WebSocketStore {
     this.unsubscribe = this.client.onEvent((event) => {
       agentsStore.handleEvent(event);
 
+      // Too many messages to stream one by one: re-subscribing sends them all at once
+      if (event.type === "session:messages_appended") {
+        this.subscribe(event.sessionId);
+      }
+
       // Trigger notifications on state change
       if (event.type === "session:state_changed") {
         const session = agentsStore.getSession(event.sessionId);
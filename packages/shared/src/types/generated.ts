@@ -20,11 +20,33 @@ export type MessageType = "text" | "tool_use" | "tool_result" | "thinking" | "st
  * via the `definition` "AgentStateType".
  */
 export type AgentStateType = "running" | "idle" | "permission_waiting" | "error" | "stopped";
+/**
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "WatcherMode".
+ */
+export type WatcherMode = "notify" | "poll_only" | "stopped";
+/**
+ * The span a project budget covers. Periods start at UTC midnight; weeks on Monday.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "BudgetPeriod".
+ */
+export type BudgetPeriod = "day" | "week" | "month" | "total";
 /**
  * This interface was referenced by `AgentsDashboard`'s JSON-Schema
  * via the `definition` "ClientEvent".
  */
 export type ClientEvent =
+  | {
+      capabilities?: string[];
+      /**
+       * Stable id the client picked for itself, so what it has read is kept across reconnects.
+       */
+      clientId?: string | null;
+      protocolVersion: number;
+      type: "client:hello";
+      [k: string]: unknown;
+    }
   | {
       sessionId: string;
       type: "subscribe:session";
@@ -34,20 +56,96 @@ export type ClientEvent =
       sessionId: string;
       type: "unsubscribe:session";
       [k: string]: unknown;
+    }
+  | {
+      projectPath: string;
+      type: "subscribe:project";
+      [k: string]: unknown;
+    }
+  | {
+      projectPath: string;
+      type: "unsubscribe:project";
+      [k: string]: unknown;
+    }
+  | {
+      messageId: string;
+      sessionId: string;
+      type: "ack:read";
+      [k: string]: unknown;
+    }
+  | {
+      type: "unknown";
+      [k: string]: unknown;
     };
+/**
+ * Events on `GET /status/api/events`, sent as server-sent events named after `type`.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "PublicStatusEvent".
+ */
+export type PublicStatusEvent =
+  | {
+      sessions: PublicSessionSummary[];
+      type: "sessions:init";
+      [k: string]: unknown;
+    }
+  | {
+      session: PublicSessionSummary;
+      type: "session:updated";
+      [k: string]: unknown;
+    }
+  | {
+      sessionId: string;
+      type: "session:removed";
+      [k: string]: unknown;
+    };
+/**
+ * The days a usage report covers: the last 7 or 30 UTC days, today included.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "ReportPeriod".
+ */
+export type ReportPeriod = "week" | "month";
 /**
  * This interface was referenced by `AgentsDashboard`'s JSON-Schema
  * via the `definition` "SearchScope".
  */
-export type SearchScope = "project_name" | "current_task" | "working_directory" | "content";
+export type SearchScope =
+  | "project_name"
+  | "title"
+  | "current_task"
+  | "working_directory"
+  | "content"
+  | "note"
+  | "labels";
+/**
+ * The prompts a session's task is shown from.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "TaskPrompt".
+ */
+export type TaskPrompt = "initial" | "latest";
 /**
  * This interface was referenced by `AgentsDashboard`'s JSON-Schema
  * via the `definition` "ServerEvent".
  */
 export type ServerEvent =
+  | {
+      capabilities: string[];
+      protocolVersion: number;
+      serverVersion: string;
+      type: "server:hello";
+      [k: string]: unknown;
+    }
   | {
       sessions: AgentSessionSummary[];
       type: "sessions:init";
+      /**
+       * Messages since this client's last `ack:read`, for sessions it has acked.
+       */
+      unreadCounts?: {
+        [k: string]: number;
+      } | null;
       [k: string]: unknown;
     }
   | {
@@ -60,12 +158,21 @@ export type ServerEvent =
       type: "session:removed";
       [k: string]: unknown;
     }
+  | {
+      session: AgentSessionSummary;
+      type: "session:updated";
+      [k: string]: unknown;
+    }
   | {
       current: AgentStateType;
       previous: AgentStateType;
       session: AgentSessionSummary;
       sessionId: string;
       type: "session:state_changed";
+      /**
+       * Messages since this client's last `ack:read` of the session, if it has one.
+       */
+      unreadCount?: number | null;
       [k: string]: unknown;
     }
   | {
@@ -74,6 +181,13 @@ export type ServerEvent =
       type: "session:new_message";
       [k: string]: unknown;
     }
+  | {
+      count: number;
+      latestTimestamp: string;
+      sessionId: string;
+      type: "session:messages_appended";
+      [k: string]: unknown;
+    }
   | {
       messages: AgentMessage[];
       sessionId: string;
@@ -81,7 +195,11 @@ export type ServerEvent =
       [k: string]: unknown;
     }
   | {
+      contextLimit?: number | null;
+      contextTokens: number;
       sessionId: string;
+      spendRatePerHour: number;
+      tokensPerMinute: number;
       type: "session:usage_updated";
       usage: CumulativeUsage;
       [k: string]: unknown;
@@ -91,11 +209,81 @@ export type ServerEvent =
       sessionId: string;
       type: "session:git_status_updated";
       [k: string]: unknown;
+    }
+  | {
+      percentUsed: number;
+      session: AgentSessionSummary;
+      sessionId: string;
+      threshold: number;
+      type: "session:context_warning";
+      [k: string]: unknown;
+    }
+  | {
+      current: string;
+      previous: string;
+      sessionId: string;
+      type: "session:model_changed";
+      [k: string]: unknown;
+    }
+  | {
+      logFile: string;
+      sessionId: string;
+      type: "session:log_deleted";
+      [k: string]: unknown;
+    }
+  | {
+      limitUsd: number;
+      period: BudgetPeriod;
+      projectName: string;
+      projectPath: string;
+      spentUsd: number;
+      type: "project:budget_exceeded";
+      [k: string]: unknown;
+    }
+  | {
+      durationMs: number;
+      entriesProcessed: number;
+      parseErrors: number;
+      sessionsDiscovered: number;
+      timedOut: boolean;
+      type: "server:startup_complete";
+      [k: string]: unknown;
+    }
+  | {
+      eventType?: string | null;
+      message: string;
+      type: "server:error";
+      [k: string]: unknown;
     };
+/**
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "SourceKind".
+ */
+export type SourceKind = "fetch" | "search";
 
 export interface AgentsDashboard {
   [k: string]: unknown;
 }
+/**
+ * A contiguous period during which the session was Running.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "ActivityInterval".
+ */
+export interface ActivityInterval {
+  cacheCreationTokens: number;
+  cacheReadTokens: number;
+  durationMs: number;
+  /**
+   * None while the session is still running.
+   */
+  end?: string | null;
+  estimatedCost: number;
+  inputTokens: number;
+  outputTokens: number;
+  start: string;
+  [k: string]: unknown;
+}
 /**
  * This interface was referenced by `AgentsDashboard`'s JSON-Schema
  * via the `definition` "AgentMessage".
@@ -106,9 +294,17 @@ export interface AgentMessage {
   metadata?: {
     [k: string]: unknown;
   } | null;
+  /**
+   * Uuid of the log entry this message's entry follows. The ids of that entry's messages start with it (`{uuid}:{block}`).
+   */
+  parentId?: string | null;
   role: MessageRole;
   sessionId: string;
   timestamp: string;
+  /**
+   * For a tool result, the id of the message with the tool call it answers.
+   */
+  toolUseMessageId?: string | null;
   type: MessageType;
   [k: string]: unknown;
 }
@@ -117,18 +313,155 @@ export interface AgentMessage {
  * via the `definition` "AgentSessionDetail".
  */
 export interface AgentSessionDetail {
+  /**
+   * Time spent Running, up to the latest log entry. Unlike `startedAt` to `lastActivityAt`, this leaves out idle time and waiting for permission.
+   */
+  activeDurationMs?: number;
+  /**
+   * The kind of work the session is doing, e.g. `coding` or `research`, from the activity rules; re-evaluated as the session goes on.
+   */
+  activityType?: string | null;
+  /**
+   * Claude Code version that wrote the most recent log entry.
+   */
+  cliVersion?: string | null;
+  /**
+   * The model's context window; null for models not in the context limit table.
+   */
+  contextLimit?: number | null;
+  /**
+   * Tokens in the latest prompt (input plus cache reads and writes), which is how much of the context window is in use.
+   */
+  contextTokens?: number;
   cumulativeUsage: CumulativeUsage;
-  currentTask: string;
+  /**
+   * Where the agent is working now, e.g. a package it cd'd into. The project fields stay on the directory the session was started in.
+   */
+  currentCwd?: string;
+  /**
+   * `remote-or-container` when the working directory isn't on the dashboard's machine (a devcontainer, Docker or SSH session), so there is no git information. Git checks resume once the directory appears.
+   */
+  environment?: string | null;
+  /**
+   * Turns that ended in an API error, or in a response the API cut short. Three in a row put the session in Error until a response goes through.
+   */
+  failedTurns?: number;
   gitStatus: GitStatus;
+  /**
+   * Loaded from an old log by `POST /api/projects/history`; not watched, and dropped again after a while.
+   */
+  historical?: boolean;
+  /**
+   * Hook runs that failed, or blocked a tool call or the agent.
+   */
+  hookErrors?: number;
+  /**
+   * The machine the session runs on: this instance's name, or for a relayed session the name of the instance that relayed it.
+   */
+  host?: string;
+  /**
+   * The first prompt of the session.
+   */
+  initialTask: string;
+  labels?: string[];
   lastActivityAt: string;
+  /**
+   * The start of the agent's most recent reply text, as plain text. Cleared when the user sends another prompt.
+   */
+  latestAssistantText?: string | null;
+  /**
+   * The most recent prompt the user typed, leaving out slash commands and tool results. The same as `initial_task` until they send another.
+   */
+  latestTask?: string;
+  /**
+   * Messages read from the log so far, including those no longer kept in memory.
+   */
+  messageCount?: number;
   messages: AgentMessage[];
   model: string;
+  /**
+   * The first model the session ran on, then each switch, oldest first.
+   */
+  modelHistory?: ModelSwitch[];
+  /**
+   * Free-form triage note set via the API.
+   */
+  note?: string | null;
+  /**
+   * The tool call awaiting approval while the session is permission_waiting.
+   */
+  pendingTool?: PendingTool | null;
+  /**
+   * The Claude Code permission mode, e.g. `default`, `acceptEdits`, `plan` or `bypassPermissions`, as of the latest entry that showed it. Sessions bypassing permissions run tool calls without asking and never wait for approval.
+   */
+  permissionMode?: string | null;
+  pinned: boolean;
   projectName: string;
   projectPath: string;
   provider: string;
+  /**
+   * Fed from a recorded log by `POST /api/replay` rather than a live CLI session.
+   */
+  replay?: boolean;
+  /**
+   * Session this one continues (`claude --resume`), when that could be detected.
+   */
+  resumedFrom?: string | null;
+  /**
+   * Requests sent again after failing.
+   */
+  retryCount?: number;
   sessionId: string;
+  /**
+   * The settings the session is judged by. None for relayed sessions.
+   */
+  settings?: SessionConfig | null;
+  /**
+   * Distinct pages and searches pulled in with WebFetch and WebSearch.
+   */
+  sourceCount?: number;
+  /**
+   * Estimated cost per hour over the last 10 minutes; decays to 0 when quiet.
+   */
+  spendRatePerHour?: number;
   startedAt: string;
   state: AgentStateType;
+  /**
+   * Why the session stopped, when that was something other than going quiet: `log_deleted` if its log file disappeared.
+   */
+  stopReason?: string | null;
+  /**
+   * Written on request or when the session stops, if summaries are configured.
+   */
+  summary?: GeneratedSummary | null;
+  /**
+   * Human-readable title from Claude Code (a conversation summary, else the session slug). Preferred over `initial_task` for display.
+   */
+  title?: string | null;
+  /**
+   * Input + output tokens per minute over the same window.
+   */
+  tokensPerMinute?: number;
+  /**
+   * Tool calls among them.
+   */
+  toolCallCount?: number;
+  /**
+   * The session's most used tools, most calls first (at most 5).
+   */
+  topTools?: ToolCount[];
+  /**
+   * Size of the session's log file as of the latest read.
+   */
+  transcriptBytes?: number;
+  /**
+   * The most recent usage points, for a sparkline.
+   */
+  usagePoints?: UsagePoint[];
+  /**
+   * How the session's log is being read. None for replayed, relayed and historical sessions.
+   */
+  watcher?: WatcherHealth | null;
   workingDirectory: string;
   [k: string]: unknown;
 }
@@ -137,6 +470,14 @@ export interface AgentSessionDetail {
  * via the `definition` "CumulativeUsage".
  */
 export interface CumulativeUsage {
+  /**
+   * Cache writes with the 1-hour TTL, which cost more, counted the same way.
+   */
+  cacheCreation1hTokens?: number | null;
+  /**
+   * Cache writes with the 5-minute TTL, counted from entries that split them by TTL; None if none did (older CLI versions).
+   */
+  cacheCreation5mTokens?: number | null;
   cacheCreationTokens: number;
   cacheReadTokens: number;
   estimatedCost: number;
@@ -149,9 +490,188 @@ export interface CumulativeUsage {
  * via the `definition` "GitStatus".
  */
 export interface GitStatus {
+  /**
+   * Unstaged line changes (`git diff --shortstat`).
+   */
   additions: number;
+  /**
+   * Commits ahead of / behind the upstream branch. 0 when there is no upstream.
+   */
+  ahead: number;
+  behind: number;
   branch: string;
   deletions: number;
+  /**
+   * Submodules with new commits, modified content or untracked files.
+   */
+  dirtySubmodules: number;
+  /**
+   * Unstaged changes per file, largest first, capped.
+   */
+  files: GitFileChange[];
+  /**
+   * The working directory is a linked `git worktree` checkout.
+   */
+  isWorktree: boolean;
+  /**
+   * None for repositories without any commits.
+   */
+  lastCommit?: GitCommitInfo | null;
+  /**
+   * Staged line changes (`git diff --cached --shortstat`).
+   */
+  stagedAdditions: number;
+  stagedDeletions: number;
+  untracked: number;
+  [k: string]: unknown;
+}
+/**
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "GitFileChange".
+ */
+export interface GitFileChange {
+  additions: number;
+  deletions: number;
+  path: string;
+  [k: string]: unknown;
+}
+/**
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "GitCommitInfo".
+ */
+export interface GitCommitInfo {
+  author: string;
+  /**
+   * Abbreviated commit hash.
+   */
+  hash: string;
+  subject: string;
+  /**
+   * Committer date, RFC 3339.
+   */
+  timestamp: string;
+  [k: string]: unknown;
+}
+/**
+ * The model a session ran on from `timestamp`.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "ModelSwitch".
+ */
+export interface ModelSwitch {
+  model: string;
+  timestamp: string;
+  [k: string]: unknown;
+}
+/**
+ * A tool call with no result yet.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "PendingTool".
+ */
+export interface PendingTool {
+  name: string;
+  /**
+   * One line about what the call does: the command for Bash, the file for Edit and Write, the pattern for Grep, and so on.
+   */
+  summary: string;
+  toolUseId: string;
+  [k: string]: unknown;
+}
+/**
+ * A session's settings: the global ones, or those of the `[projects."<path>"]` section whose path is the longest prefix of its discovery project path.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "SessionConfig".
+ */
+export interface SessionConfig {
+  /**
+   * How long the session stays stopped before its messages are dropped.
+   */
+  dehydrateAfterSecs: number;
+  gitCheckIntervalRunningSecs: number;
+  /**
+   * Between git checks of an idle or waiting session.
+   */
+  gitCheckIntervalSecs: number;
+  idleSecs: number;
+  idleStoppedSecs: number;
+  /**
+   * Messages kept in memory.
+   */
+  maxMessages: number;
+  notifyContextWarnings: boolean;
+  /**
+   * States that send a push notification.
+   */
+  notifyStates: AgentStateType[];
+  permissionWaitSecs: number;
+  processGoneSecs: number;
+  /**
+   * The `[projects]` section that applies; None when only global settings do.
+   */
+  projectOverride?: string | null;
+  runningStoppedSecs: number;
+  [k: string]: unknown;
+}
+/**
+ * A short summary of a session written by a model from its outline.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "GeneratedSummary".
+ */
+export interface GeneratedSummary {
+  /**
+   * What writing it cost, counted under the `dashboard` project.
+   */
+  estimatedCost: number;
+  generatedAt: string;
+  model: string;
+  text: string;
+  [k: string]: unknown;
+}
+/**
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "ToolCount".
+ */
+export interface ToolCount {
+  count: number;
+  name: string;
+  [k: string]: unknown;
+}
+/**
+ * Cumulative usage of a session as of `timestamp`.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "UsagePoint".
+ */
+export interface UsagePoint {
+  cacheCreationTokens: number;
+  cacheReadTokens: number;
+  estimatedCost: number;
+  inputTokens: number;
+  outputTokens: number;
+  timestamp: string;
+  [k: string]: unknown;
+}
+/**
+ * How a session's log watcher is doing, to tell a quiet session from a watcher that stopped reading.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "WatcherHealth".
+ */
+export interface WatcherHealth {
+  /**
+   * Bytes read from the log since the watcher started.
+   */
+  bytesRead: number;
+  lastError?: string | null;
+  lastErrorAt?: string | null;
+  /**
+   * When the log was last read without an error.
+   */
+  lastReadAt?: string | null;
+  mode: WatcherMode;
   [k: string]: unknown;
 }
 /**
@@ -159,30 +679,897 @@ export interface GitStatus {
  * via the `definition` "AgentSessionSummary".
  */
 export interface AgentSessionSummary {
+  /**
+   * Time spent Running, up to the latest log entry. Unlike `startedAt` to `lastActivityAt`, this leaves out idle time and waiting for permission.
+   */
+  activeDurationMs?: number;
+  /**
+   * The kind of work the session is doing, e.g. `coding` or `research`, from the activity rules; re-evaluated as the session goes on.
+   */
+  activityType?: string | null;
+  /**
+   * Claude Code version that wrote the most recent log entry.
+   */
+  cliVersion?: string | null;
+  /**
+   * The model's context window; null for models not in the context limit table.
+   */
+  contextLimit?: number | null;
+  /**
+   * Tokens in the latest prompt (input plus cache reads and writes), which is how much of the context window is in use.
+   */
+  contextTokens?: number;
   cumulativeUsage: CumulativeUsage;
-  currentTask: string;
+  /**
+   * Where the agent is working now, e.g. a package it cd'd into. The project fields stay on the directory the session was started in.
+   */
+  currentCwd?: string;
+  /**
+   * `remote-or-container` when the working directory isn't on the dashboard's machine (a devcontainer, Docker or SSH session), so there is no git information. Git checks resume once the directory appears.
+   */
+  environment?: string | null;
+  /**
+   * Turns that ended in an API error, or in a response the API cut short. Three in a row put the session in Error until a response goes through.
+   */
+  failedTurns?: number;
   gitStatus: GitStatus;
+  /**
+   * Loaded from an old log by `POST /api/projects/history`; not watched, and dropped again after a while.
+   */
+  historical?: boolean;
+  /**
+   * Hook runs that failed, or blocked a tool call or the agent.
+   */
+  hookErrors?: number;
+  /**
+   * The machine the session runs on: this instance's name, or for a relayed session the name of the instance that relayed it.
+   */
+  host?: string;
+  /**
+   * The first prompt of the session.
+   */
+  initialTask: string;
+  labels?: string[];
   lastActivityAt: string;
+  /**
+   * The start of the agent's most recent reply text, as plain text. Cleared when the user sends another prompt.
+   */
+  latestAssistantText?: string | null;
+  /**
+   * The most recent prompt the user typed, leaving out slash commands and tool results. The same as `initial_task` until they send another.
+   */
+  latestTask?: string;
+  /**
+   * Messages read from the log so far, including those no longer kept in memory.
+   */
+  messageCount?: number;
   model: string;
+  /**
+   * Free-form triage note set via the API.
+   */
+  note?: string | null;
+  /**
+   * The tool call awaiting approval while the session is permission_waiting.
+   */
+  pendingTool?: PendingTool | null;
+  /**
+   * The Claude Code permission mode, e.g. `default`, `acceptEdits`, `plan` or `bypassPermissions`, as of the latest entry that showed it. Sessions bypassing permissions run tool calls without asking and never wait for approval.
+   */
+  permissionMode?: string | null;
+  pinned: boolean;
   projectName: string;
   projectPath: string;
   provider: string;
+  /**
+   * Fed from a recorded log by `POST /api/replay` rather than a live CLI session.
+   */
+  replay?: boolean;
+  /**
+   * Session this one continues (`claude --resume`), when that could be detected.
+   */
+  resumedFrom?: string | null;
+  /**
+   * Requests sent again after failing.
+   */
+  retryCount?: number;
   sessionId: string;
+  /**
+   * Distinct pages and searches pulled in with WebFetch and WebSearch.
+   */
+  sourceCount?: number;
+  /**
+   * Estimated cost per hour over the last 10 minutes; decays to 0 when quiet.
+   */
+  spendRatePerHour?: number;
   startedAt: string;
   state: AgentStateType;
+  /**
+   * Why the session stopped, when that was something other than going quiet: `log_deleted` if its log file disappeared.
+   */
+  stopReason?: string | null;
+  /**
+   * Written on request or when the session stops, if summaries are configured.
+   */
+  summary?: GeneratedSummary | null;
+  /**
+   * Human-readable title from Claude Code (a conversation summary, else the session slug). Preferred over `initial_task` for display.
+   */
+  title?: string | null;
+  /**
+   * Input + output tokens per minute over the same window.
+   */
+  tokensPerMinute?: number;
+  /**
+   * Tool calls among them.
+   */
+  toolCallCount?: number;
+  /**
+   * The session's most used tools, most calls first (at most 5).
+   */
+  topTools?: ToolCount[];
+  /**
+   * Size of the session's log file as of the latest read.
+   */
+  transcriptBytes?: number;
   workingDirectory: string;
   [k: string]: unknown;
 }
+/**
+ * A stopped session as it was when it left memory, from `GET /api/archive/{id}`.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "ArchivedSession".
+ */
+export interface ArchivedSession {
+  archivedAt: string;
+  detail: AgentSessionDetail;
+  /**
+   * When the session was running.
+   */
+  timeline?: SessionTimeline | null;
+  usage?: UsageTimeline | null;
+  [k: string]: unknown;
+}
+/**
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "SessionTimeline".
+ */
+export interface SessionTimeline {
+  intervals: ActivityInterval[];
+  sessionId: string;
+  startedAt: string;
+  totalActiveMs: number;
+  [k: string]: unknown;
+}
+/**
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "UsageTimeline".
+ */
+export interface UsageTimeline {
+  points: UsagePoint[];
+  sessionId: string;
+  [k: string]: unknown;
+}
+/**
+ * An archived session as listed by `GET /api/archive`.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "ArchivedSessionInfo".
+ */
+export interface ArchivedSessionInfo {
+  archivedAt: string;
+  compressedBytes: number;
+  initialTask: string;
+  lastActivityAt: string;
+  model: string;
+  projectName: string;
+  projectPath: string;
+  sessionId: string;
+  startedAt: string;
+  title?: string | null;
+  [k: string]: unknown;
+}
+/**
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "BudgetStatus".
+ */
+export interface BudgetStatus {
+  exceeded: boolean;
+  limitUsd: number;
+  period: BudgetPeriod;
+  /**
+   * Spent in the current period.
+   */
+  spentUsd: number;
+  [k: string]: unknown;
+}
+/**
+ * Outcome of `POST /api/config/reload`: dotted keys that changed in the file, split by whether they took effect.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "ConfigReload".
+ */
+export interface ConfigReload {
+  applied: string[];
+  /**
+   * Changed, but only read at startup.
+   */
+  requiresRestart: string[];
+  [k: string]: unknown;
+}
+/**
+ * A connected WebSocket client, for `GET /api/connections`.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "ConnectionInfo".
+ */
+export interface ConnectionInfo {
+  connectedAt: string;
+  /**
+   * Events dropped or merged because the client was not reading fast enough.
+   */
+  eventsDropped: number;
+  /**
+   * Events it missed when it did.
+   */
+  eventsMissed?: number;
+  eventsSent: number;
+  id: number;
+  /**
+   * Times the connection fell behind the server's event channels.
+   */
+  lags?: number;
+  /**
+   * The last message sent to or received from the client.
+   */
+  lastActivityAt: string;
+  /**
+   * Projects whose sessions' messages the client is subscribed to, sorted.
+   */
+  projectSubscriptions?: string[];
+  /**
+   * Unknown when the server was not started with connection info.
+   */
+  remoteAddr?: string | null;
+  /**
+   * Sessions whose messages the client is subscribed to, sorted.
+   */
+  subscriptions: string[];
+  [k: string]: unknown;
+}
+/**
+ * Totals across every tracked session, for the dashboard header.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "DashboardStats".
+ */
+export interface DashboardStats {
+  /**
+   * Time sessions spent Running since UTC midnight, summed.
+   */
+  activeDurationMsToday?: number;
+  /**
+   * Sessions that are Running or waiting for permission.
+   */
+  activeSessions: number;
+  /**
+   * Mean time from a session's first to its latest activity.
+   */
+  averageSessionDurationMs: number;
+  /**
+   * Project name whose sessions used the most tokens.
+   */
+  busiestProject?: string | null;
+  /**
+   * Cost since Monday 00:00 UTC.
+   */
+  costThisWeek: number;
+  /**
+   * Cost since UTC midnight.
+   */
+  costToday: number;
+  /**
+   * Sessions stopped long enough that their messages were dropped until needed.
+   */
+  dehydratedSessions: number;
+  generatedAt: string;
+  /**
+   * Sessions holding their messages in memory and watching their logs.
+   */
+  hydratedSessions: number;
+  toolCalls: number;
+  /**
+   * Tool stats summed over sessions, most calls first.
+   */
+  tools: ToolStats[];
+  totalSessions: number;
+  /**
+   * Input, output, cache read and cache creation tokens.
+   */
+  totalTokens: number;
+  /**
+   * Completed turns: times a session went back to Idle after working.
+   */
+  totalTurns: number;
+  [k: string]: unknown;
+}
+/**
+ * Calls a session made to one tool.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "ToolStats".
+ */
+export interface ToolStats {
+  count: number;
+  /**
+   * Results flagged `is_error`.
+   */
+  errorCount: number;
+  name: string;
+  /**
+   * Size of the tool results as logged, before truncation for display.
+   */
+  outputBytes: number;
+  [k: string]: unknown;
+}
+/**
+ * `GET /api/projects/{path}/digest`: what agents did in one project on one UTC day. Sessions no longer tracked only left their usage behind, so their active time, files and commits are missing.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "DayDigest".
+ */
+export interface DayDigest {
+  /**
+   * Time spent running, across the sessions still tracked.
+   */
+  activeMs: number;
+  /**
+   * Commits made that day, newest first. Only the latest commit in each session's working tree is seen. None when no session has a git status.
+   */
+  commits?: GitCommitInfo[] | null;
+  /**
+   * `YYYY-MM-DD` (UTC).
+   */
+  date: string;
+  /**
+   * Files edited or written by the sessions whose messages are in memory, in the order first touched.
+   */
+  files: string[];
+  generatedAt: string;
+  projectName: string;
+  projectPath: string;
+  /**
+   * Sessions that used anything, started or were last active that day, oldest first.
+   */
+  sessions: DigestSession[];
+  sessionsEnded: number;
+  sessionsStarted: number;
+  totalCost: number;
+  totalTokens: number;
+  [k: string]: unknown;
+}
+/**
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "DigestSession".
+ */
+export interface DigestSession {
+  /**
+   * Time spent running on this day. None for sessions no longer tracked.
+   */
+  activeMs?: number | null;
+  /**
+   * Cost and tokens on this day only.
+   */
+  cost: number;
+  /**
+   * When the session stopped, if that was on this day. Sessions no longer tracked count as stopped at their last activity.
+   */
+  endedAt?: string | null;
+  /**
+   * Files edited or written on this day, in the order first touched. None when the session's messages aren't in memory.
+   */
+  files?: string[] | null;
+  model: string;
+  sessionId: string;
+  startedAt: string;
+  startedOnDay: boolean;
+  /**
+   * None for sessions no longer tracked.
+   */
+  state?: AgentStateType | null;
+  title?: string | null;
+  tokens: number;
+  /**
+   * Calls over the session's lifetime, most first.
+   */
+  topTools: ToolCount[];
+  [k: string]: unknown;
+}
+/**
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "DiffSnapshot".
+ */
+export interface DiffSnapshot {
+  diff: GitDiff;
+  /**
+   * The state the session had just entered.
+   */
+  state: AgentStateType;
+  takenAt: string;
+  timestamp: number;
+  [k: string]: unknown;
+}
+/**
+ * A session's uncommitted changes as a unified diff, for `GET /api/sessions/{id}/diff`.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "GitDiff".
+ */
+export interface GitDiff {
+  /**
+   * Every changed file, in the order of the patch, whether or not it was truncated.
+   */
+  files: GitFileChange[];
+  patch: string;
+  /**
+   * Changes staged in the index rather than in the working tree.
+   */
+  staged: boolean;
+  /**
+   * The patch was cut at the size limit, at the end of a line.
+   */
+  truncated: boolean;
+  [k: string]: unknown;
+}
+/**
+ * A diff saved when a session changed state, as listed by `GET /api/sessions/{id}/diff/snapshots`.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "DiffSnapshotInfo".
+ */
+export interface DiffSnapshotInfo {
+  compressedBytes: number;
+  takenAt: string;
+  /**
+   * Milliseconds since the epoch; names the snapshot in its URL.
+   */
+  timestamp: number;
+  [k: string]: unknown;
+}
+/**
+ * What `DELETE /api/sessions/{id}?dry_run=true` would do.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "DismissPlan".
+ */
+export interface DismissPlan {
+  /**
+   * The log that would no longer be watched. It stays on disk, and the session comes back if it is written to again.
+   */
+  logFile: string;
+  sessionId: string;
+  state: AgentStateType;
+  [k: string]: unknown;
+}
+/**
+ * `GET /api/health`.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "HealthResponse".
+ */
+export interface HealthResponse {
+  /**
+   * Tracked sessions per Claude Code version.
+   */
+  cliVersions: {
+    [k: string]: number;
+  };
+  /**
+   * Times WebSocket connections fell behind the server's event channels since it started, and the events they missed.
+   */
+  connectionLags?: number;
+  eventsMissed?: number;
+  /**
+   * Names the machine the server runs on.
+   */
+  instance: string;
+  status: string;
+  /**
+   * Git checks skipped since the server started because the session's working directory isn't on this machine.
+   */
+  suppressedGitChecks?: number;
+  transcriptMemory?: TranscriptMemory;
+  /**
+   * Log timestamps that could not be read and were replaced by the time they were seen, since the server started.
+   */
+  unparseableTimestamps?: number;
+  watchdog?: WatchdogStatus;
+  watchers?: WatcherCounts;
+  [k: string]: unknown;
+}
+/**
+ * Memory held by the messages of every tracked session, and what was dropped to stay within `MAX_TRANSCRIPT_MEMORY_MB`.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "TranscriptMemory".
+ */
+export interface TranscriptMemory {
+  /**
+   * 0 when there is no limit.
+   */
+  budgetBytes: number;
+  /**
+   * Roughly: message text and metadata plus a fixed overhead per message.
+   */
+  retainedBytes: number;
+  shedMessages: number;
+  /**
+   * Times since the server started that a session's messages were dropped to stay within the budget, and the messages dropped.
+   */
+  sheds: number;
+  [k: string]: unknown;
+}
+/**
+ * What the watchdog found when it last checked that discovery, the log watchers and event routing are still running, and what it restarted since the server started.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "WatchdogStatus".
+ */
+export interface WatchdogStatus {
+  discoveryRestarts: number;
+  /**
+   * The checks that failed last time: `discovery`, `routing`, or `watcher:<session id>`.
+   */
+  failing: string[];
+  /**
+   * None before the first check.
+   */
+  lastCheckAt?: string | null;
+  lastDiscoveryScanAt?: string | null;
+  /**
+   * Checks that found the routing loop not moving. It can't be restarted in place.
+   */
+  routingStalls: number;
+  watcherRestarts: number;
+  [k: string]: unknown;
+}
+/**
+ * The log watchers of the sessions being followed.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "WatcherCounts".
+ */
+export interface WatcherCounts {
+  /**
+   * Sessions whose watcher's last read failed, or that stopped reading while their log kept growing.
+   */
+  failing: string[];
+  notify: number;
+  pollOnly: number;
+  [k: string]: unknown;
+}
+/**
+ * What `POST /api/projects/history?dry_run=true` would do.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "HistoryPlan".
+ */
+export interface HistoryPlan {
+  projectPath: string;
+  /**
+   * Sessions whose logs would be read, most recent first.
+   */
+  sessionIds: string[];
+  [k: string]: unknown;
+}
+/**
+ * Body of `POST /api/projects/history`.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "HistoryRequest".
+ */
+export interface HistoryRequest {
+  /**
+   * The project's directory, as in a session's `projectPath`.
+   */
+  projectPath: string;
+  /**
+   * Sessions to load. Empty loads the project's most recent ones.
+   */
+  sessionIds?: string[];
+  [k: string]: unknown;
+}
+/**
+ * What `POST /api/sessions/{id}/interrupt?dry_run=true` would do.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "InterruptPlan".
+ */
+export interface InterruptPlan {
+  /**
+   * The Claude process that would be signalled.
+   */
+  pid: number;
+  sessionId: string;
+  /**
+   * E.g. `SIGINT`.
+   */
+  signal: string;
+  /**
+   * Where the process runs.
+   */
+  workingDirectory: string;
+  [k: string]: unknown;
+}
+/**
+ * Body of `POST /api/sessions/{id}/interrupt`, optional.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "InterruptRequest".
+ */
+export interface InterruptRequest {
+  /**
+   * Which process to signal when several run in the session's directory.
+   */
+  pid?: number | null;
+  [k: string]: unknown;
+}
+/**
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "InterruptResponse".
+ */
+export interface InterruptResponse {
+  pid: number;
+  /**
+   * E.g. `SIGINT`.
+   */
+  signal: string;
+  [k: string]: unknown;
+}
+/**
+ * A provider event as recorded by the event journal.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "JournalEntry".
+ */
+export interface JournalEntry {
+  /**
+   * The event, tagged by `type` (e.g. `state_changed`).
+   */
+  event: unknown;
+  /**
+   * When the event was routed.
+   */
+  ingestedAt: string;
+  sessionId: string;
+  [k: string]: unknown;
+}
+/**
+ * What `POST /api/sessions/{id}/open?dry_run=true` would do.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "OpenPlan".
+ */
+export interface OpenPlan {
+  /**
+   * The command that would run, with the working directory filled in.
+   */
+  command: string[];
+  sessionId: string;
+  workingDirectory: string;
+  [k: string]: unknown;
+}
+/**
+ * Outcome of `POST /api/sessions/{id}/open`.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "OpenSessionResponse".
+ */
+export interface OpenSessionResponse {
+  /**
+   * The command as run, with the working directory filled in.
+   */
+  command: string[];
+  message: string;
+  /**
+   * The command exited successfully or was still running after a few seconds.
+   */
+  success: boolean;
+  [k: string]: unknown;
+}
+/**
+ * A project and the sessions discovered in it, from `GET /api/sessions?group_by=project`.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "ProjectSessions".
+ */
+export interface ProjectSessions {
+  /**
+   * As `GET /api/projects` lists it; zero usage if none was recorded yet.
+   */
+  project: ProjectUsage;
+  /**
+   * Most recently active first. With `group_chains`, only the latest session of each resume chain.
+   */
+  sessions: AgentSessionSummary[];
+  [k: string]: unknown;
+}
+/**
+ * Usage summed over every session seen for a project, including sessions no longer tracked. Keyed by the project directory the session was discovered in.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "ProjectUsage".
+ */
+export interface ProjectUsage {
+  /**
+   * Only set when the config file has a budget for this project.
+   */
+  budget?: BudgetStatus | null;
+  /**
+   * Cost since the first of the month, 00:00 UTC.
+   */
+  costThisMonth: number;
+  /**
+   * Cost since Monday 00:00 UTC.
+   */
+  costThisWeek: number;
+  /**
+   * Cost since UTC midnight.
+   */
+  costToday: number;
+  projectName: string;
+  projectPath: string;
+  sessions: number;
+  usage: CumulativeUsage;
+  [k: string]: unknown;
+}
+/**
+ * A session as the public status page shows it: no prompts, paths, messages or costs.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "PublicSessionSummary".
+ */
+export interface PublicSessionSummary {
+  /**
+   * From `startedAt` to `lastActivityAt`.
+   */
+  durationMs: number;
+  inputTokens: number;
+  lastActivityAt: string;
+  model: string;
+  outputTokens: number;
+  projectName: string;
+  sessionId: string;
+  startedAt: string;
+  state: AgentStateType;
+  [k: string]: unknown;
+}
+/**
+ * What `POST /api/replay?dry_run=true` would do.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "ReplayPlan".
+ */
+export interface ReplayPlan {
+  /**
+   * How long the replay would take at that speed.
+   */
+  durationMs: number;
+  /**
+   * Log entries that would be replayed.
+   */
+  entryCount: number;
+  speed: number;
+  [k: string]: unknown;
+}
+/**
+ * How much of the period the recorded history spans.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "ReportCoverage".
+ */
+export interface ReportCoverage {
+  /**
+   * Whether the history reaches back to the start of the period.
+   */
+  complete: boolean;
+  /**
+   * First day with recorded usage, `YYYY-MM-DD`; null if nothing was recorded yet.
+   */
+  historyFrom?: string | null;
+  /**
+   * Sessions recorded before tokens, models, durations and tools were kept; only their cost is counted.
+   */
+  sessionsWithoutDetails: number;
+  [k: string]: unknown;
+}
+/**
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "ReportDay".
+ */
+export interface ReportDay {
+  cost: number;
+  /**
+   * `YYYY-MM-DD` (UTC).
+   */
+  date: string;
+  tokens: number;
+  [k: string]: unknown;
+}
+/**
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "ReportModel".
+ */
+export interface ReportModel {
+  cost: number;
+  model: string;
+  sessions: number;
+  tokens: number;
+  [k: string]: unknown;
+}
+/**
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "ReportProject".
+ */
+export interface ReportProject {
+  cost: number;
+  projectName: string;
+  projectPath: string;
+  sessions: number;
+  tokens: number;
+  [k: string]: unknown;
+}
+/**
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "ReportSession".
+ */
+export interface ReportSession {
+  /**
+   * Cost within the period.
+   */
+  cost: number;
+  /**
+   * From the first to the latest activity.
+   */
+  durationSecs: number;
+  lastActivityAt: string;
+  model: string;
+  projectName: string;
+  sessionId: string;
+  startedAt: string;
+  title: string;
+  [k: string]: unknown;
+}
 /**
  * This interface was referenced by `AgentsDashboard`'s JSON-Schema
  * via the `definition` "SearchMatch".
  */
 export interface SearchMatch {
   content: string;
+  /**
+   * Content scope: id of the matched message, for jumping to it. Null for other scopes.
+   */
+  messageId?: string | null;
+  /**
+   * Content scope: position of the message in the session's current message list. Null for other scopes and for trimmed messages.
+   */
+  messageIndex?: number | null;
   messageRole: MessageRole;
+  /**
+   * The message has been trimmed from the session's message list; only the snippet is available.
+   */
+  messageTrimmed?: boolean;
   messageType: MessageType;
   scope: SearchScope;
+  /**
+   * Current task scope: which of the session's prompts matched.
+   */
+  task?: TaskPrompt | null;
   timestamp: string;
+  /**
+   * The hit is in part of the message that was truncated away; `content` shows what is still stored.
+   */
+  truncatedSource?: boolean;
   [k: string]: unknown;
 }
 /**
@@ -200,8 +1587,299 @@ export interface SearchResponse {
  * via the `definition` "SessionSearchResult".
  */
 export interface SessionSearchResult {
+  /**
+   * With `group_chains`, the results for earlier sessions of the same resume chain, oldest first; `session` is then the latest session of the chain that matched.
+   */
+  earlierInChain?: SessionSearchResult[];
+  /**
+   * With `group_chains`, counts the matches of the whole resume chain.
+   */
   matchCount: number;
   matches: SearchMatch[];
   session: AgentSessionSummary;
   [k: string]: unknown;
 }
+/**
+ * What `POST /api/sessions/batch?dry_run=true` would return.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "SessionBatchPlan".
+ */
+export interface SessionBatchPlan {
+  /**
+   * Requested ids that would be returned, in request order.
+   */
+  found: string[];
+  /**
+   * Requested ids that aren't tracked, in request order.
+   */
+  notFound: string[];
+  [k: string]: unknown;
+}
+/**
+ * Body of `POST /api/sessions/batch`.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "SessionBatchRequest".
+ */
+export interface SessionBatchRequest {
+  sessionIds: string[];
+  [k: string]: unknown;
+}
+/**
+ * Summaries of the requested sessions, by id. Archived sessions are included.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "SessionBatchResponse".
+ */
+export interface SessionBatchResponse {
+  /**
+   * Requested ids that aren't tracked, in request order.
+   */
+  notFound: string[];
+  sessions: {
+    [k: string]: AgentSessionSummary;
+  };
+  [k: string]: unknown;
+}
+/**
+ * A conversation spread over several sessions by resuming, oldest session first.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "SessionChain".
+ */
+export interface SessionChain {
+  sessionId: string;
+  sessions: AgentSessionSummary[];
+  /**
+   * Usage summed over every session in the chain.
+   */
+  totalUsage: CumulativeUsage;
+  [k: string]: unknown;
+}
+/**
+ * A session's transcript split into turns: a prompt and everything the agent did for it, up to the `turn_duration` marker.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "SessionOutline".
+ */
+export interface SessionOutline {
+  sessionId: string;
+  /**
+   * Oldest first. Only turns within the messages the server keeps are listed.
+   */
+  turns: TurnOutline[];
+  [k: string]: unknown;
+}
+/**
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "TurnOutline".
+ */
+export interface TurnOutline {
+  /**
+   * As reported by the agent when the turn completed.
+   */
+  durationMs?: number | null;
+  /**
+   * None while the turn is still running.
+   */
+  endedAt?: string | null;
+  /**
+   * Files edited, written or read, in the order first touched.
+   */
+  files: string[];
+  messageCount: number;
+  /**
+   * Start of the prompt. None for a turn already under way where the kept messages begin.
+   */
+  prompt?: string | null;
+  startedAt: string;
+  /**
+   * Most calls first.
+   */
+  tools: ToolCount[];
+  /**
+   * Tokens and cost of the turn. None when the usage at its start isn't known.
+   */
+  usage?: CumulativeUsage | null;
+  [k: string]: unknown;
+}
+/**
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "SessionPrefs".
+ */
+export interface SessionPrefs {
+  archived?: boolean;
+  labels?: string[];
+  muted?: boolean;
+  note?: string;
+  pinned?: boolean;
+  summary?: GeneratedSummary | null;
+  [k: string]: unknown;
+}
+/**
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "SessionPrefsPatch".
+ */
+export interface SessionPrefsPatch {
+  archived?: boolean | null;
+  muted?: boolean | null;
+  pinned?: boolean | null;
+  [k: string]: unknown;
+}
+/**
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "SessionSources".
+ */
+export interface SessionSources {
+  sessionId: string;
+  /**
+   * Oldest first; a source requested again moves to the end.
+   */
+  sources: SourceRef[];
+  [k: string]: unknown;
+}
+/**
+ * A page or search an agent pulled in while researching.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "SourceRef".
+ */
+export interface SourceRef {
+  /**
+   * Whether the latest result was flagged `is_error`.
+   */
+  isError: boolean;
+  kind: SourceKind;
+  /**
+   * The message with the latest call.
+   */
+  messageId: string;
+  /**
+   * The search query, for `search`.
+   */
+  query?: string | null;
+  /**
+   * Size of the latest result as logged, or None while it hasn't come back.
+   */
+  resultBytes?: number | null;
+  /**
+   * When it was last requested.
+   */
+  timestamp: string;
+  /**
+   * The fetched URL, for `fetch`.
+   */
+  url?: string | null;
+  [k: string]: unknown;
+}
+/**
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "SessionToolStats".
+ */
+export interface SessionToolStats {
+  sessionId: string;
+  /**
+   * Most calls first.
+   */
+  tools: ToolStats[];
+  [k: string]: unknown;
+}
+/**
+ * What the server found when it first read the logs after starting: the sessions discovery's first scan found, and what the first read of their logs held.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "StartupSummary".
+ */
+export interface StartupSummary {
+  /**
+   * From the server starting until the last of those logs was read.
+   */
+  durationMs: number;
+  entriesProcessed: number;
+  /**
+   * Log lines that held no entry this server could read.
+   */
+  parseErrors: number;
+  sessionsDiscovered: number;
+  /**
+   * Some logs were still being read when startup stopped waiting for them; their entries aren't counted.
+   */
+  timedOut?: boolean;
+  [k: string]: unknown;
+}
+/**
+ * What `DELETE /api/replay/{id}?dry_run=true` would do.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "StopReplayPlan".
+ */
+export interface StopReplayPlan {
+  /**
+   * Messages replayed so far, which would be dropped with the session.
+   */
+  messageCount: number;
+  sessionId: string;
+  [k: string]: unknown;
+}
+/**
+ * What `POST /api/sessions/{id}/summarize?dry_run=true` would do.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "SummarizePlan".
+ */
+export interface SummarizePlan {
+  /**
+   * The model that would be asked.
+   */
+  model: string;
+  sessionId: string;
+  /**
+   * Turns of the outline it would be sent.
+   */
+  turnCount: number;
+  [k: string]: unknown;
+}
+/**
+ * `GET /api/reports/usage`: usage over a period, from the per-project tallies, so sessions no longer tracked are included. Tokens count input, output, cache read and cache creation tokens.
+ *
+ * This interface was referenced by `AgentsDashboard`'s JSON-Schema
+ * via the `definition` "UsageReport".
+ */
+export interface UsageReport {
+  /**
+   * The most expensive days, at most 5.
+   */
+  busiestDays: ReportDay[];
+  coverage: ReportCoverage;
+  /**
+   * First and last day of the period, `YYYY-MM-DD` (UTC).
+   */
+  from: string;
+  generatedAt: string;
+  /**
+   * At most 5, longest first.
+   */
+  longestSessions: ReportSession[];
+  /**
+   * Most expensive first.
+   */
+  models: ReportModel[];
+  period: ReportPeriod;
+  /**
+   * Most expensive first.
+   */
+  projects: ReportProject[];
+  /**
+   * Sessions with usage in the period.
+   */
+  sessions: number;
+  to: string;
+  /**
+   * Calls over the lifetime of the period's sessions, at most 10, most first.
+   */
+  topTools: ToolCount[];
+  totalCost: number;
+  totalTokens: number;
+  [k: string]: unknown;
+}
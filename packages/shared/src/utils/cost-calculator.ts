@@ -2,17 +2,81 @@ import type { CumulativeUsage } from "../types/index.js";
 
 // Pricing per million tokens (USD) — Claude model pricing
 // Uses prefix matching to handle versioned model names (e.g. claude-opus-4-1-20250805)
-const MODEL_PRICING: Array<{ prefix: string; pricing: { input: number; output: number; cacheRead: number; cacheCreation: number } }> = [
-  { prefix: "claude-opus", pricing: { input: 15, output: 75, cacheRead: 1.5, cacheCreation: 18.75 } },
-  { prefix: "claude-sonnet", pricing: { input: 3, output: 15, cacheRead: 0.3, cacheCreation: 3.75 } },
-  { prefix: "claude-haiku", pricing: { input: 0.8, output: 4, cacheRead: 0.08, cacheCreation: 1 } },
+// cacheCreation is for 5-minute cache writes, cacheCreation1h for 1-hour ones
+interface Prices {
+  input: number;
+  output: number;
+  cacheRead: number;
+  cacheCreation: number;
+  cacheCreation1h: number;
+}
+
+const BUILTIN_PRICING: Array<{ prefix: string; pricing: Prices }> = [
+  { prefix: "claude-opus", pricing: { input: 15, output: 75, cacheRead: 1.5, cacheCreation: 18.75, cacheCreation1h: 30 } },
+  { prefix: "claude-sonnet", pricing: { input: 3, output: 15, cacheRead: 0.3, cacheCreation: 3.75, cacheCreation1h: 6 } },
+  { prefix: "claude-haiku", pricing: { input: 0.8, output: 4, cacheRead: 0.08, cacheCreation: 1, cacheCreation1h: 1.6 } },
 ];
 
-const DEFAULT_PRICING = MODEL_PRICING[1].pricing; // sonnet
+/** Unknown models are priced like this one unless the config names another. */
+const DEFAULT_FALLBACK_MODEL = "claude-sonnet";
+
+// Batch requests are billed at half price; other tiers at the listed prices
+const BATCH_DISCOUNT = 0.5;
+
+/** A `[[cost.models]]` entry, as `GET /api/config` returns it under `cost.models`. */
+export interface ModelPricing {
+  prefix: string;
+  input: number;
+  output: number;
+  cache_read: number;
+  cache_creation: number;
+  /** Twice the input price if not given. */
+  cache_creation_1h?: number;
+}
+
+/** The pricing part of the `cost` section of `GET /api/config`. */
+export interface PricingConfig {
+  fallback_model?: string;
+  models?: ModelPricing[];
+}
+
+let modelPricing = BUILTIN_PRICING;
+let fallbackPricing = BUILTIN_PRICING[1].pricing; // sonnet
 
-function getPricing(model: string) {
-  const match = MODEL_PRICING.find((m) => model.startsWith(m.prefix));
-  return match?.pricing ?? DEFAULT_PRICING;
+/**
+ * Price as the server does: the configured models matched before the built-in ones, and
+ * unknown models like the first entry whose prefix is `fallback_model`. Returns false,
+ * keeping the prices as they were, if there is no such entry.
+ */
+export function configurePricing(config: PricingConfig): boolean {
+  const overrides = (config.models ?? []).map((m) => ({
+    prefix: m.prefix,
+    pricing: {
+      input: m.input,
+      output: m.output,
+      cacheRead: m.cache_read,
+      cacheCreation: m.cache_creation,
+      cacheCreation1h: m.cache_creation_1h ?? m.input * 2,
+    },
+  }));
+  const models = [...overrides, ...BUILTIN_PRICING];
+  const fallbackModel = config.fallback_model ?? DEFAULT_FALLBACK_MODEL;
+  const fallback = models.find((m) => m.prefix === fallbackModel);
+  if (!fallback) return false;
+  modelPricing = models;
+  fallbackPricing = fallback.pricing;
+  return true;
+}
+
+/** How an entry's cache writes split by TTL, when its log line says. */
+export interface CacheCreationTtl {
+  fiveMinuteTokens: number;
+  oneHourTokens: number;
+}
+
+function getPricing(model: string): Prices {
+  const match = modelPricing.find((m) => model.startsWith(m.prefix));
+  return match?.pricing ?? fallbackPricing;
 }
 
 export function calculateCost(
@@ -21,15 +85,19 @@ export function calculateCost(
   outputTokens: number,
   cacheReadTokens: number,
   cacheCreationTokens: number,
+  cacheCreationTtl?: CacheCreationTtl,
+  serviceTier?: string,
 ): number {
   const pricing = getPricing(model);
-  return (
+  const oneHourTokens = Math.min(cacheCreationTtl?.oneHourTokens ?? 0, cacheCreationTokens);
+  const cost =
     (inputTokens * pricing.input +
       outputTokens * pricing.output +
       cacheReadTokens * pricing.cacheRead +
-      cacheCreationTokens * pricing.cacheCreation) /
-    1_000_000
-  );
+      (cacheCreationTokens - oneHourTokens) * pricing.cacheCreation +
+      oneHourTokens * pricing.cacheCreation1h) /
+    1_000_000;
+  return serviceTier === "batch" ? cost * BATCH_DISCOUNT : cost;
 }
 
 export function createEmptyUsage(): CumulativeUsage {
@@ -49,13 +117,32 @@ export function addUsage(
   outputTokens: number,
   cacheReadTokens: number,
   cacheCreationTokens: number,
+  cacheCreationTtl?: CacheCreationTtl,
+  serviceTier?: string,
 ): CumulativeUsage {
-  const entryCost = calculateCost(model, inputTokens, outputTokens, cacheReadTokens, cacheCreationTokens);
-  return {
+  const entryCost = calculateCost(
+    model,
+    inputTokens,
+    outputTokens,
+    cacheReadTokens,
+    cacheCreationTokens,
+    cacheCreationTtl,
+    serviceTier,
+  );
+  const usage: CumulativeUsage = {
     inputTokens: current.inputTokens + inputTokens,
     outputTokens: current.outputTokens + outputTokens,
     cacheReadTokens: current.cacheReadTokens + cacheReadTokens,
     cacheCreationTokens: current.cacheCreationTokens + cacheCreationTokens,
     estimatedCost: current.estimatedCost + entryCost,
   };
+  // The TTL split is only totalled once an entry has reported it
+  if (cacheCreationTtl) {
+    usage.cacheCreation5mTokens = (current.cacheCreation5mTokens ?? 0) + cacheCreationTtl.fiveMinuteTokens;
+    usage.cacheCreation1hTokens = (current.cacheCreation1hTokens ?? 0) + cacheCreationTtl.oneHourTokens;
+  } else {
+    if (current.cacheCreation5mTokens != null) usage.cacheCreation5mTokens = current.cacheCreation5mTokens;
+    if (current.cacheCreation1hTokens != null) usage.cacheCreation1hTokens = current.cacheCreation1hTokens;
+  }
+  return usage;
 }